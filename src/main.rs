@@ -8,10 +8,15 @@ mod ui;
 mod utils;
 
 use network::discovery::DeviceDiscovery;
+use network::backend::BleBackend;
 use network::ble::BleManager;
 use protocols::airdrop::AirDrop;
 use protocols::airplay::AirPlay;
+use protocols::cert_pins::PinStore;
 use protocols::awdl::{AwdlManager, AwdlManagerConfig};
+use utils::diagnostics::Diagnostics;
+use utils::event_log;
+use utils::startup_summary::StartupSummary;
 
 /// Struttura principale dell'applicazione AirWin
 pub struct AirWinServices {
@@ -20,45 +25,160 @@ pub struct AirWinServices {
     pub airplay: Arc<Mutex<AirPlay>>,
     pub ble: Arc<Mutex<BleManager>>,
     pub awdl: Arc<Mutex<AwdlManager>>,
+    /// Privilegi e porte rilevati all'avvio
+    pub diagnostics: Diagnostics,
+    /// Servizi abilitati nelle impostazioni
+    pub airdrop_enabled: bool,
+    pub airplay_enabled: bool,
+    /// API di controllo locale, disattivata se `None`
+    pub control_api: Option<utils::config::ControlApiSettings>,
+    /// Esito dell'avvio, `None` finché l'inizializzazione non è terminata
+    startup_summary: tokio::sync::watch::Sender<Option<StartupSummary>>,
 }
 
 impl AirWinServices {
     /// Crea una nuova istanza dei servizi AirWin
     pub async fn new() -> anyhow::Result<Self> {
         // Construct services with correct constructors
-        let discovery = DeviceDiscovery::new()?;
-        let airdrop = AirDrop::new();
-        let airplay = AirPlay::new();
-        let ble = BleManager::new().await?;
+        let mut settings = utils::config::Settings::load();
+        let ble = BleManager::new()
+            .await?
+            .with_poll_interval(std::time::Duration::from_millis(settings.ble_poll_interval_ms));
+        let ble = Arc::new(Mutex::new(ble));
+        let discovery = DeviceDiscovery::new()?
+            .with_backend(Box::new(BleBackend::new(ble.clone())))
+            .with_ip_mode(settings.ip_mode);
+        if settings.control_api.enabled && settings.control_api.ensure_token() {
+            // Il token va salvato subito per poterlo leggere dagli script
+            if let Err(e) = settings.save() {
+                tracing::warn!("Failed to save control API token: {}", e);
+            }
+        }
+        let diagnostics = Diagnostics::collect(settings.ports);
+        let cache = utils::cache::Cache::from_settings(&settings);
+        if let Err(e) = cache.ensure() {
+            tracing::warn!("Failed to create cache directory {:?}: {}", cache.root(), e);
+        }
         let awdl = AwdlManager::new(AwdlManagerConfig::default());
+        // Nome mostrato agli altri dispositivi, il nome del computer se non impostato,
+        // seguito dal suffisso scelto
+        let friendly_name = protocols::apple_records::with_suffix(
+            &protocols::apple_records::friendly_name(settings.friendly_name.as_deref())?,
+            settings.name_suffix.as_deref(),
+        );
+        let airdrop = AirDrop::new()
+            .with_friendly_name(friendly_name.clone())
+            .with_name_collision_check(settings.avoid_name_collisions)
+            .with_awdl_availability(awdl.availability())
+            .with_multicast_settings(settings.multicast)
+            .with_ip_mode(settings.ip_mode)
+            .with_ports(diagnostics.ports)
+            .with_receive_dir(cache.received_dir())
+            .with_archive_dir(cache.archives_dir())
+            .with_conflict_policy(settings.file_conflict_policy)
+            .with_max_file_size(settings.max_incoming_file_size)
+            .with_chunk_size(settings.transfer_chunk_kb as usize * 1024)
+            .with_compression(settings.transfer_compression)
+            .with_https_connection_limits(settings.https_connection_limits())
+            .with_tls_backend(settings.tls_backend)
+            .with_after_transfer(settings.after_transfer)
+            .with_peer_filter(settings.peer_filter.clone())
+            .with_visibility(settings.visibility)
+            .with_trusted_devices(settings.trusted_devices.clone());
+        let airdrop = if settings.pin_peer_certificates {
+            // I certificati autofirmati sono verificati tramite il pin invece che da una CA
+            airdrop
+                .with_self_signed_peers(true)
+                .with_cert_pinning(PinStore::load(PinStore::default_path()))
+        } else {
+            airdrop
+        };
+        let airplay = AirPlay::new()
+            .with_friendly_name(friendly_name)
+            .with_name_collision_check(settings.avoid_name_collisions)
+            .with_quality_tier(settings.airplay_quality)
+            .with_capture_region(settings.capture_region)
+            .with_cursor(settings.airplay_show_cursor)
+            .with_idle_timeout(settings.airplay_idle_timeout())
+            .with_ip_mode(settings.ip_mode)
+            .with_socket_tuning(settings.airplay_socket)
+            .with_port(settings.airplay_port.unwrap_or(diagnostics.ports.airplay));
 
         Ok(Self {
             device_discovery: Arc::new(Mutex::new(discovery)),
             airdrop: Arc::new(Mutex::new(airdrop)),
             airplay: Arc::new(Mutex::new(airplay)),
-            ble: Arc::new(Mutex::new(ble)),
+            ble,
             awdl: Arc::new(Mutex::new(awdl)),
+            diagnostics,
+            airdrop_enabled: settings.airdrop_enabled,
+            airplay_enabled: settings.airplay_enabled,
+            control_api: settings.control_api.enabled.then_some(settings.control_api),
+            startup_summary: tokio::sync::watch::channel(None).0,
         })
     }
+
+    /// Esito dell'avvio dei servizi, pubblicato al termine dell'inizializzazione
+    pub fn startup_summary(&self) -> tokio::sync::watch::Receiver<Option<StartupSummary>> {
+        self.startup_summary.subscribe()
+    }
     
     /// Inizializza tutti i servizi
     pub async fn initialize(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.diagnostics.elevated {
+            event_log::info("AirWin", "Avviato con privilegi di amministratore");
+        } else {
+            tracing::warn!("Not running as administrator, some features are limited");
+            for limitation in &self.diagnostics.limitations {
+                event_log::warning("AirWin", limitation.clone());
+            }
+        }
+
         // Inizializza device discovery (mDNS)
         {
             let discovery = self.device_discovery.lock().await;
             discovery.start_discovery().await?;
         }
 
-        // Avvia AirDrop HTTPS server e servizi mDNS
-        {
-            let airdrop = self.airdrop.lock().await;
-            airdrop.start_server().await?;
-        }
+        // Avvia AirDrop HTTPS server e servizi mDNS, se abilitato. Un servizio
+        // che non parte non ferma gli altri: l'errore finisce nel riepilogo.
+        let airdrop_started = if self.airdrop_enabled {
+            Some(self.airdrop.lock().await.start_server().await)
+        } else {
+            event_log::info("AirDrop", "Disabilitato nelle impostazioni");
+            None
+        };
 
-        // Avvia server AirPlay per ricezione
-        {
-            let airplay = self.airplay.lock().await;
-            airplay.start_server().await?;
+        // Registra di nuovo i servizi quando cambiano le interfacce di rete o lo stato di AWDL
+        network::interface_watcher::spawn(self.airdrop.lock().await.clone());
+        self.airdrop.lock().await.watch_awdl_availability();
+
+        // Avvia server AirPlay per ricezione, se abilitato
+        let airplay_started = if self.airplay_enabled {
+            Some(self.airplay.lock().await.start_server().await)
+        } else {
+            event_log::info("AirPlay", "Disabilitato nelle impostazioni");
+            None
+        };
+
+        self.publish_startup_summary(airdrop_started, airplay_started).await;
+
+        // Avvia l'API di controllo locale, se abilitata
+        if let Some(control_api) = &self.control_api {
+            let target = network::control_api::ServicesTarget::new(
+                self.device_discovery.clone(),
+                self.airdrop.lock().await.clone(),
+                self.airplay.lock().await.clone(),
+            );
+            let api = network::control_api::ControlApi::new(
+                control_api.port,
+                control_api.token.clone().unwrap_or_default(),
+                Arc::new(target),
+            );
+            match api.start().await {
+                Ok((addr, _)) => event_log::info("ControlAPI", format!("In ascolto su {}", addr)),
+                Err(e) => event_log::error("ControlAPI", format!("Avvio fallito: {}", e)),
+            }
         }
 
         // Inizializza BLE
@@ -67,14 +187,87 @@ impl AirWinServices {
             ble.initialize().await?;
         }
 
-        // Inizializza e avvia AWDL
-        {
+        // Inizializza e avvia AWDL, che richiede l'accesso diretto alla scheda di rete
+        if self.diagnostics.elevated {
             let mut awdl = self.awdl.lock().await;
             awdl.initialize().await?;
         }
         
         Ok(())
     }
+
+    /// Riepiloga con quale nome, su quali indirizzi e con quali servizi gli
+    /// altri dispositivi trovano questo PC, leggendo lo stato effettivo dei
+    /// servizi appena avviati
+    async fn publish_startup_summary(
+        &self,
+        airdrop_started: Option<anyhow::Result<()>>,
+        airplay_started: Option<anyhow::Result<()>>,
+    ) {
+        let airdrop = self.airdrop.lock().await.clone();
+        let name = airdrop.advertised_name().borrow().clone();
+        let mut summary = StartupSummary::new(name, airdrop.discoverable_addresses());
+
+        match airdrop_started {
+            Some(Ok(())) => match airdrop.send_only().borrow().clone() {
+                None => summary.mark_advertised("AirDrop"),
+                Some(reason) => summary.mark_failed("AirDrop", format!("servizi mDNS non registrati, solo invio ({})", reason)),
+            },
+            Some(Err(e)) => summary.mark_failed("AirDrop", e.to_string()),
+            None => {}
+        }
+        match airplay_started {
+            Some(Ok(())) if self.airplay.lock().await.is_advertised().await => summary.mark_advertised("AirPlay"),
+            Some(Ok(())) => summary.mark_failed("AirPlay", "ricevitore non annunciato via mDNS"),
+            Some(Err(e)) => summary.mark_failed("AirPlay", e.to_string()),
+            None => {}
+        }
+
+        if summary.is_discoverable() && summary.failed.is_empty() {
+            event_log::info("AirWin", summary.message());
+        } else {
+            event_log::warning("AirWin", summary.message());
+        }
+        self.startup_summary.send_replace(Some(summary));
+    }
+
+    /// Ferma i servizi alla chiusura dell'app, anche quelli che l'interfaccia
+    /// non conosce come il Bluetooth. Le scansioni vengono annullate per prime,
+    /// così non leggono daemon mDNS e adattatori mentre vengono chiusi.
+    pub async fn shutdown(&self) {
+        self.device_discovery.lock().await.stop_discovery().await;
+        if let Err(e) = self.ble.lock().await.stop_scanning().await {
+            tracing::debug!("BLE scan not stopped: {}", e);
+        }
+
+        if let Err(e) = self.airdrop.lock().await.stop_server().await {
+            event_log::warning("AirDrop", format!("Arresto non riuscito: {}", e));
+        }
+        if let Err(e) = self.airplay.lock().await.stop_server().await {
+            event_log::warning("AirPlay", format!("Arresto non riuscito: {}", e));
+        }
+    }
+}
+
+/// Dimensione in MB richiesta con `--benchmark <MB>`, se presente
+fn benchmark_arg(mut args: impl Iterator<Item = String>) -> Option<Result<u64, String>> {
+    args.find(|arg| arg == "--benchmark")?;
+    Some(match args.next() {
+        Some(mb) => mb.parse().map_err(|_| format!("Dimensione non valida: {}", mb)),
+        None => Err("Uso: airwin --benchmark <MB>".to_string()),
+    })
+}
+
+/// Esegue il benchmark di trasferimento in loopback e stampa il risultato in JSON,
+/// senza avviare i servizi né l'interfaccia
+fn run_benchmark(runtime: &tokio::runtime::Runtime, megabytes: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let settings = utils::config::Settings::load();
+    let airdrop = AirDrop::new()
+        .with_chunk_size(settings.transfer_chunk_kb as usize * 1024)
+        .with_tls_backend(settings.tls_backend);
+    let result = runtime.block_on(airdrop.loopback_benchmark(megabytes))?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -83,6 +276,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Crea un runtime separato per i servizi di background
     let runtime = tokio::runtime::Runtime::new()?;
+
+    // Benchmark nascosto per i manutentori: non compare nell'interfaccia
+    if let Some(megabytes) = benchmark_arg(std::env::args().skip(1)) {
+        return run_benchmark(&runtime, megabytes?);
+    }
     
     // Crea i servizi AirWin nel runtime
     let services = runtime.block_on(async {
@@ -95,6 +293,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
     
+    // Canale dei file ricevuti e servizi AirDrop/AirPlay, inoltrati all'interfaccia
+    let (incoming_files, airdrop, airplay, awdl, awdl_mac) = runtime.block_on(async {
+        let airdrop = services.airdrop.lock().await.clone();
+        let awdl = services.awdl.lock().await;
+        (
+            airdrop.incoming_events(),
+            airdrop,
+            services.airplay.lock().await.clone(),
+            awdl.availability(),
+            awdl.local_mac_updates(),
+        )
+    });
+    
     // Inizializza i servizi in background
     let services_clone = services.clone();
     runtime.spawn(async move {
@@ -105,6 +316,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     
     // Mantieni il runtime attivo in un thread separato
+    let runtime_handle = runtime.handle().clone();
     std::thread::spawn(move || {
         runtime.block_on(async {
             // Mantieni il runtime attivo
@@ -116,7 +328,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Avvia l'interfaccia utente Iced nel thread principale
     // Iced gestisce il proprio event loop, quindi non serve async qui
-    ui::run()?;
+    ui::run(ui::UiFlags {
+        incoming_files: Some(incoming_files),
+        discovery: Some(services.device_discovery.clone()),
+        airdrop: Some(airdrop),
+        airplay: Some(airplay),
+        awdl: Some(awdl),
+        awdl_mac: Some(awdl_mac),
+        startup_summary: Some(services.startup_summary()),
+        diagnostics: services.diagnostics.clone(),
+    })?;
+
+    // Finestra chiusa: ferma i servizi prima di uscire
+    runtime_handle.block_on(services.shutdown());
     
     Ok(())
 }
\ No newline at end of file
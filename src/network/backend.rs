@@ -0,0 +1,422 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use tracing::{debug, warn};
+
+use super::ble::BleManager;
+use super::discovery::{device_from_info, DiscoveredDevice, ServiceType, SERVICE_TYPES};
+use crate::utils::event_log;
+
+/// Longest wait for a device to answer again just before a send
+pub const RESOLVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A device from an earlier scan didn't answer under its name any more
+#[derive(Debug, thiserror::Error)]
+#[error("{0} moved or left the network, please rescan")]
+pub struct DeviceMoved(pub String);
+
+/// Stops a scan before its timeout. Clones share the same state.
+#[derive(Clone, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Resolves once the scan is cancelled
+    pub async fn cancelled(&self) {
+        let mut cancelled = self.cancelled.subscribe();
+        let _ = cancelled.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// Wait until `deadline` or until cancelled. Returns `false` if cancelled.
+    pub async fn sleep_until(&self, deadline: tokio::time::Instant) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => true,
+            _ = self.cancelled() => false,
+        }
+    }
+}
+
+/// A source of nearby devices.
+///
+/// `DeviceDiscovery` queries every registered backend and merges the results,
+/// so new protocols can be added without touching the UI.
+#[async_trait]
+pub trait DiscoveryBackend: Send + Sync {
+    /// Short name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Collect the devices offering one of the mDNS `services` seen within
+    /// `timeout`. When `cancel` fires the backend stops querying and returns
+    /// what it has found so far.
+    async fn browse(&self, timeout: Duration, services: &[&str], cancel: &CancelToken) -> Vec<DiscoveredDevice>;
+}
+
+/// Query all backends concurrently and drop duplicates reported by more than one
+pub async fn browse_all(
+    backends: &[Arc<dyn DiscoveryBackend>],
+    timeout: Duration,
+    services: &[&str],
+    cancel: &CancelToken,
+) -> Vec<DiscoveredDevice> {
+    let results = join_all(backends.iter().map(|backend| async move {
+        let devices = backend.browse(timeout, services, cancel).await;
+        debug!("{} backend found {} devices", backend.name(), devices.len());
+        devices
+    }))
+    .await;
+
+    let mut seen = HashSet::new();
+    results
+        .into_iter()
+        .flatten()
+        .filter(|device| seen.insert(device.id()))
+        .collect()
+}
+
+/// Look `device` up again by name and service, so that a send goes to the
+/// address it has now instead of the one seen in an earlier scan, which may
+/// have been handed to another host in the meantime.
+pub async fn resolve(
+    backends: &[Arc<dyn DiscoveryBackend>],
+    device: &DiscoveredDevice,
+    timeout: Duration,
+) -> Result<DiscoveredDevice, DeviceMoved> {
+    let services: Vec<&str> = match &device.service_type {
+        ServiceType::Other(service) => vec![service.as_str()],
+        known => SERVICE_TYPES
+            .iter()
+            .copied()
+            .filter(|service| ServiceType::from_mdns(service) == *known)
+            .collect(),
+    };
+    // BLE entries have no address to send to
+    let answers: Vec<_> = browse_all(backends, timeout, &services, &CancelToken::new())
+        .await
+        .into_iter()
+        .filter(|found| found.name == device.name && found.service_type == device.service_type)
+        .filter(|found| !found.address.is_unspecified())
+        .collect();
+
+    // Several devices can share a name: one still at the old address is the
+    // same device. Otherwise only a single answer can be trusted to be it.
+    let fresh = match answers.iter().find(|found| found.address == device.address) {
+        Some(found) => found.clone(),
+        None if answers.len() == 1 => answers[0].clone(),
+        None => return Err(DeviceMoved(device.name.clone())),
+    };
+    if fresh.address != device.address || fresh.port != device.port {
+        event_log::info(
+            "Discovery",
+            format!(
+                "{} moved from {}:{} to {}:{}",
+                device.name, device.address, device.port, fresh.address, fresh.port
+            ),
+        );
+    }
+    Ok(fresh)
+}
+
+/// Devices advertising Apple services over mDNS
+pub struct MdnsBackend {
+    mdns: ServiceDaemon,
+}
+
+impl MdnsBackend {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            mdns: ServiceDaemon::new()?,
+        })
+    }
+}
+
+#[async_trait]
+impl DiscoveryBackend for MdnsBackend {
+    fn name(&self) -> &'static str {
+        "mDNS"
+    }
+
+    async fn browse(&self, timeout: Duration, services: &[&str], cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut receivers = Vec::new();
+        for &service_type in services {
+            match self.mdns.browse(service_type) {
+                Ok(receiver) => receivers.push((service_type, receiver)),
+                Err(e) => warn!("Failed to browse for service {}: {}", service_type, e),
+            }
+        }
+
+        let devices = join_all(receivers.iter().map(|(service_type, receiver)| async move {
+            let mut found = Vec::new();
+            loop {
+                let event = tokio::select! {
+                    event = tokio::time::timeout_at(deadline, receiver.recv_async()) => event,
+                    _ = cancel.cancelled() => break,
+                };
+                match event {
+                    Ok(Ok(ServiceEvent::ServiceResolved(info))) => {
+                        found.extend(device_from_info(service_type, &info));
+                    }
+                    Ok(Ok(_)) => {}
+                    _ => break,
+                }
+            }
+            found
+        }))
+        .await;
+
+        for (service_type, _) in &receivers {
+            if let Err(e) = self.mdns.stop_browse(service_type) {
+                debug!("Failed to stop browsing {}: {}", service_type, e);
+            }
+        }
+
+        devices.into_iter().flatten().collect()
+    }
+}
+
+/// AirDrop devices seen over Bluetooth LE.
+///
+/// BLE only tells that a device is nearby: it has no IP endpoint until it is
+/// also resolved over mDNS, so address and port are left unspecified.
+pub struct BleBackend {
+    ble: Arc<Mutex<BleManager>>,
+}
+
+impl BleBackend {
+    pub fn new(ble: Arc<Mutex<BleManager>>) -> Self {
+        Self { ble }
+    }
+}
+
+#[async_trait]
+impl DiscoveryBackend for BleBackend {
+    fn name(&self) -> &'static str {
+        "BLE"
+    }
+
+    async fn browse(&self, timeout: Duration, services: &[&str], cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+        // BLE advertisements only ever announce AirDrop
+        if !services.iter().any(|service| ServiceType::from_mdns(service) == ServiceType::AirDrop) {
+            return Vec::new();
+        }
+        if let Err(e) = self.ble.lock().await.start_scanning().await {
+            debug!("BLE scan unavailable: {}", e);
+            return Vec::new();
+        }
+        if !cancel.sleep_until(tokio::time::Instant::now() + timeout).await {
+            if let Err(e) = self.ble.lock().await.stop_scanning().await {
+                debug!("Failed to stop the BLE scan: {}", e);
+            }
+        }
+
+        self.ble
+            .lock()
+            .await
+            .get_discovered_devices()
+            .await
+            .into_iter()
+            .map(|device| DiscoveredDevice {
+                name: device.name,
+                address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                port: 0,
+                service_type: ServiceType::AirDrop,
+                txt_records: HashMap::from([
+                    ("ble_id".to_string(), device.id),
+                    ("rssi".to_string(), device.rssi.to_string()),
+                ]),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::discovery::{ScanScope, SERVICE_TYPES};
+
+    /// Backend returning a fixed list, standing in for real network scans
+    struct MockBackend {
+        devices: Vec<DiscoveredDevice>,
+    }
+
+    #[async_trait]
+    impl DiscoveryBackend for MockBackend {
+        fn name(&self) -> &'static str {
+            "Mock"
+        }
+
+        async fn browse(&self, _timeout: Duration, services: &[&str], _cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+            // Like a real network, only responders for the queried services answer
+            self.devices
+                .iter()
+                .filter(|device| services.iter().any(|service| ServiceType::from_mdns(service) == device.service_type))
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Backend that finds one device right away and another only at the
+    /// end of the timeout
+    struct SlowBackend {
+        early: DiscoveredDevice,
+        late: DiscoveredDevice,
+    }
+
+    #[async_trait]
+    impl DiscoveryBackend for SlowBackend {
+        fn name(&self) -> &'static str {
+            "Slow"
+        }
+
+        async fn browse(&self, timeout: Duration, _services: &[&str], cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+            let mut found = vec![self.early.clone()];
+            if cancel.sleep_until(tokio::time::Instant::now() + timeout).await {
+                found.push(self.late.clone());
+            }
+            found
+        }
+    }
+
+    fn device(name: &str, last_octet: u8, service_type: ServiceType) -> DiscoveredDevice {
+        DiscoveredDevice {
+            name: name.to_string(),
+            address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, last_octet)),
+            port: 8771,
+            service_type,
+            txt_records: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_browse_all_merges_backends() {
+        let iphone = device("iPhone", 100, ServiceType::AirDrop);
+        let apple_tv = device("Apple TV", 101, ServiceType::AirPlay);
+        let macbook = device("MacBook", 102, ServiceType::AirDrop);
+
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![
+            Arc::new(MockBackend { devices: vec![iphone.clone(), apple_tv.clone()] }),
+            // Seen by both backends: reported once
+            Arc::new(MockBackend { devices: vec![iphone.clone(), macbook.clone()] }),
+            Arc::new(MockBackend { devices: Vec::new() }),
+        ];
+
+        let devices = browse_all(&backends, Duration::from_millis(10), SERVICE_TYPES, &CancelToken::new()).await;
+        let ids: Vec<_> = devices.iter().map(DiscoveredDevice::id).collect();
+        assert_eq!(ids, vec![iphone.id(), apple_tv.id(), macbook.id()]);
+    }
+
+    #[tokio::test]
+    async fn test_airplay_scope_only_returns_airplay_responders() {
+        let iphone = device("iPhone", 100, ServiceType::AirDrop);
+        let apple_tv = device("Apple TV", 101, ServiceType::AirPlay);
+        let homepod = device("HomePod", 102, ServiceType::Raop);
+        let watch = device("Watch", 103, ServiceType::Companion);
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![
+            Arc::new(MockBackend { devices: vec![iphone.clone(), apple_tv.clone(), homepod.clone(), watch.clone()] }),
+        ];
+
+        let backends = &backends;
+        let scoped = move |scope: ScanScope| async move {
+            browse_all(backends, Duration::from_millis(10), scope.service_types(), &CancelToken::new())
+                .await
+                .iter()
+                .map(DiscoveredDevice::id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(scoped(ScanScope::AirPlay).await, vec![apple_tv.id(), homepod.id()]);
+        assert_eq!(scoped(ScanScope::AirDrop).await, vec![iphone.id(), watch.id()]);
+        assert_eq!(scoped(ScanScope::All).await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_uses_the_fresh_address() {
+        let cached = device("iPhone", 100, ServiceType::AirDrop);
+        let moved = device("iPhone", 150, ServiceType::AirDrop);
+        // Another host now answers on the old address
+        let other = device("MacBook", 100, ServiceType::AirDrop);
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![
+            Arc::new(MockBackend { devices: vec![other, moved.clone(), device("iPhone", 151, ServiceType::AirPlay)] }),
+        ];
+
+        let fresh = resolve(&backends, &cached, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(fresh.id(), moved.id());
+
+        let gone = device("iPad", 120, ServiceType::AirDrop);
+        let error = resolve(&backends, &gone, Duration::from_millis(10)).await.unwrap_err();
+        assert!(error.to_string().contains("please rescan"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_never_guesses_between_devices() {
+        let cached = device("iPhone", 100, ServiceType::AirDrop);
+        let ble = DiscoveredDevice {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 0,
+            ..device("iPhone", 0, ServiceType::AirDrop)
+        };
+
+        // Only seen over BLE: there is no address to send to
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![Arc::new(MockBackend { devices: vec![ble.clone()] })];
+        assert!(resolve(&backends, &cached, Duration::from_millis(10)).await.is_err());
+
+        // Two other devices with the same name: either could be a stranger
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![Arc::new(MockBackend {
+            devices: vec![ble.clone(), device("iPhone", 150, ServiceType::AirDrop), device("iPhone", 151, ServiceType::AirDrop)],
+        })];
+        assert!(resolve(&backends, &cached, Duration::from_millis(10)).await.is_err());
+
+        // A device seen only over BLE resolves to its single mDNS answer
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![Arc::new(MockBackend { devices: vec![ble.clone(), cached.clone()] })];
+        let fresh = resolve(&backends, &ble, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(fresh.id(), cached.id());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_browse_returns_partial_results() {
+        let iphone = device("iPhone", 100, ServiceType::AirDrop);
+        let macbook = device("MacBook", 102, ServiceType::AirDrop);
+        let backends: Vec<Arc<dyn DiscoveryBackend>> = vec![
+            Arc::new(SlowBackend { early: iphone.clone(), late: macbook }),
+        ];
+
+        let cancel = CancelToken::new();
+        let stopper = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stopper.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let devices = browse_all(&backends, Duration::from_secs(60), SERVICE_TYPES, &cancel).await;
+        assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+        assert!(cancel.is_cancelled());
+        assert_eq!(devices.iter().map(DiscoveredDevice::id).collect::<Vec<_>>(), vec![iphone.id()]);
+    }
+}
@@ -1,7 +1,9 @@
-use btleplug::api::{Central, Manager as _, Peripheral, ScanFilter};
-use btleplug::platform::{Adapter, Manager};
+use btleplug::api::{Central, CentralEvent, Manager as _, Peripheral, PeripheralProperties, ScanFilter};
+use btleplug::platform::{Adapter, Manager, PeripheralId};
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::runtime::Handle; 
@@ -10,12 +12,22 @@ use uuid::Uuid;
 use std::time::Duration;
 use rand::Rng;
 use sha2::{Sha256, Digest};
+use super::backend::CancelToken;
 
 // Apple AirDrop BLE Service UUIDs
-const AIRDROP_SERVICE_UUID: &str = "7ba94d80-ca9b-4d8d-b1db-21e8a4e6b256";
+const AIRDROP_SERVICE_UUID: Uuid = Uuid::from_u128(0x7ba94d80_ca9b_4d8d_b1db_21e8a4e6b256);
 
 // Apple Continuity Service UUID (used for device identification)
-const CONTINUITY_SERVICE_UUID: &str = "d0611e78-bbb4-4591-a5f8-487910ae4366";
+const CONTINUITY_SERVICE_UUID: Uuid = Uuid::from_u128(0xd0611e78_bbb4_4591_a5f8_487910ae4366);
+
+/// Apple's Bluetooth company identifier
+const APPLE_COMPANY_ID: u16 = 0x004C;
+
+/// Type byte of an AirDrop advertisement in Apple's manufacturer data
+const AIRDROP_ADVERTISEMENT_TYPE: u8 = 0x05;
+
+/// Default time between two reads of the discovered peripherals
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Clone, Debug)]
 #[allow(dead_code)]
@@ -34,6 +46,9 @@ pub struct BleManager {
     discovered_devices: Arc<Mutex<HashMap<String, BleDevice>>>,
     is_scanning: Arc<Mutex<bool>>,
     is_advertising: Arc<Mutex<bool>>,
+    poll_interval: Duration,
+    /// Stops the poll loop of the current scan
+    scan_cancel: Arc<Mutex<CancelToken>>,
 }
 
 impl BleManager {
@@ -48,9 +63,17 @@ impl BleManager {
             discovered_devices: Arc::new(Mutex::new(HashMap::new())),
             is_scanning: Arc::new(Mutex::new(false)),
             is_advertising: Arc::new(Mutex::new(false)),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            scan_cancel: Arc::new(Mutex::new(CancelToken::new())),
         })
     }
 
+    /// Read the discovered peripherals every `interval` while scanning
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
     pub async fn initialize(&mut self) -> Result<()> {
         info!("Getting BLE adapters...");
         let adapters = self.manager.adapters().await?;
@@ -120,69 +143,49 @@ impl BleManager {
 
         // Create scan filter for AirDrop services
         let scan_filter = ScanFilter {
-            services: vec![
-                Uuid::parse_str(AIRDROP_SERVICE_UUID)?,
-                Uuid::parse_str(CONTINUITY_SERVICE_UUID)?,
-            ],
+            services: vec![AIRDROP_SERVICE_UUID, CONTINUITY_SERVICE_UUID],
         };
 
         adapter.start_scan(scan_filter).await?;
         *is_scanning = true;
 
+        // Advertisement events tell which peripherals changed since the last poll.
+        // Without them every known peripheral is read on each poll.
+        let changed = match adapter.events().await {
+            Ok(mut events) => {
+                let changed = Arc::new(std::sync::Mutex::new(HashSet::new()));
+                let sink = changed.clone();
+                let listener = tokio::spawn(async move {
+                    while let Some(event) = events.next().await {
+                        if let Some(id) = advertised_peripheral(event) {
+                            sink.lock().unwrap().insert(id);
+                        }
+                    }
+                });
+                Some((changed, listener))
+            }
+            Err(e) => {
+                debug!("No BLE advertisement events, polling all peripherals: {}", e);
+                None
+            }
+        };
+
         // Start device discovery loop
-        let adapter_clone = adapter.clone();
+        let cancel = CancelToken::new();
+        *self.scan_cancel.lock().await = cancel.clone();
+        let adapter = adapter.clone();
         let devices = self.discovered_devices.clone();
-        let scanning_flag = self.is_scanning.clone();
+        let poll_interval = self.poll_interval;
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(2));
-            
-            while *scanning_flag.lock().await {
-                interval.tick().await;
-                
-                match adapter_clone.peripherals().await {
-                    Ok(peripherals) => {
-                        for peripheral in peripherals {
-                            if let Ok(properties) = peripheral.properties().await {
-                                if let Some(props) = properties {
-                                    let device_id = peripheral.id().to_string();
-                                    
-                                    // Check if this looks like an AirDrop device
-                                    let is_airdrop_device = props.manufacturer_data
-                                        .get(&0x004C) // Apple Company ID
-                                        .map(|data| data.len() >= 3 && data[2] == 0x05) // AirDrop type
-                                        .unwrap_or(false);
-
-                                    if is_airdrop_device || 
-                                       props.services.contains(&Uuid::parse_str(AIRDROP_SERVICE_UUID).unwrap()) {
-                                        
-                                        let local_name = props
-                                        .local_name
-                                        .clone()
-                                        .unwrap_or_else(|| "Unknown AirDrop Device".to_string());
-
-                                    let device = BleDevice {
-                                        id: device_id.clone(),
-                                        name: local_name.clone(),
-                                        rssi: props.rssi.unwrap_or(0),
-                                        manufacturer_data: props.manufacturer_data,
-                                        service_data: props.service_data,
-                                        last_seen: std::time::Instant::now(),
-                                    };
-
-                                        let mut devices_lock = devices.lock().await;
-                                        devices_lock.insert(device_id, device);
-                                            
-                                    debug!("Discovered AirDrop BLE device: {}", local_name);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Error getting BLE peripherals: {}", e);
-                    }
-                }
+            let changed_ids = changed.as_ref().map(|(changed, _)| changed.clone());
+            poll_until_cancelled(poll_interval, cancel, || {
+                poll_peripherals(adapter.clone(), changed_ids.clone(), devices.clone())
+            })
+            .await;
+
+            if let Some((_, listener)) = changed {
+                listener.abort();
             }
         });
 
@@ -199,6 +202,8 @@ impl BleManager {
         }
 
         info!("Stopping BLE scan...");
+        // The poll loop stops first, so it doesn't read an adapter being stopped
+        self.scan_cancel.lock().await.cancel();
         adapter.stop_scan().await?;
         *is_scanning = false;
 
@@ -256,12 +261,121 @@ impl BleManager {
     }
 }
 
+/// Call `poll` every `interval` until `cancel` fires. A poll still running
+/// when it fires is abandoned.
+async fn poll_until_cancelled<F, Fut>(interval: Duration, cancel: CancelToken, mut poll: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = poll() => {}
+        }
+    }
+}
+
+/// Read the peripherals whose advertisement changed, or all of them without
+/// advertisement events, and record the AirDrop devices among them
+async fn poll_peripherals(
+    adapter: Adapter,
+    changed: Option<Arc<std::sync::Mutex<HashSet<PeripheralId>>>>,
+    devices: Arc<Mutex<HashMap<String, BleDevice>>>,
+) {
+    crate::utils::poll_rate::record("BLE scan");
+
+    let peripherals = match &changed {
+        Some(changed) => {
+            let ids: Vec<PeripheralId> = changed.lock().unwrap().drain().collect();
+            let mut peripherals = Vec::with_capacity(ids.len());
+            for id in ids {
+                if let Ok(peripheral) = adapter.peripheral(&id).await {
+                    peripherals.push(peripheral);
+                }
+            }
+            peripherals
+        }
+        None => match adapter.peripherals().await {
+            Ok(peripherals) => peripherals,
+            Err(e) => {
+                warn!("Error getting BLE peripherals: {}", e);
+                return;
+            }
+        },
+    };
+
+    // Parsed without holding the lock, then inserted in one go
+    let now = std::time::Instant::now();
+    let mut found = Vec::new();
+    for peripheral in peripherals {
+        if let Ok(Some(props)) = peripheral.properties().await {
+            if let Some(device) = airdrop_device(peripheral.id().to_string(), props, now) {
+                debug!("Discovered AirDrop BLE device: {}", device.name);
+                found.push(device);
+            }
+        }
+    }
+
+    if !found.is_empty() {
+        devices
+            .lock()
+            .await
+            .extend(found.into_iter().map(|device| (device.id.clone(), device)));
+    }
+}
+
+/// Peripheral whose advertisement changed, for events that carry one
+fn advertised_peripheral(event: CentralEvent) -> Option<PeripheralId> {
+    match event {
+        CentralEvent::DeviceDiscovered(id)
+        | CentralEvent::DeviceUpdated(id)
+        | CentralEvent::ManufacturerDataAdvertisement { id, .. }
+        | CentralEvent::ServiceDataAdvertisement { id, .. }
+        | CentralEvent::ServicesAdvertisement { id, .. } => Some(id),
+        _ => None,
+    }
+}
+
+/// AirDrop device advertised in `props`, `None` for any other peripheral
+fn airdrop_device(id: String, props: PeripheralProperties, seen: std::time::Instant) -> Option<BleDevice> {
+    let is_airdrop_advertisement = props
+        .manufacturer_data
+        .get(&APPLE_COMPANY_ID)
+        .is_some_and(|data| data.len() >= 3 && data[2] == AIRDROP_ADVERTISEMENT_TYPE);
+    if !is_airdrop_advertisement && !props.services.contains(&AIRDROP_SERVICE_UUID) {
+        return None;
+    }
+
+    Some(BleDevice {
+        id,
+        name: props.local_name.unwrap_or_else(|| "Unknown AirDrop Device".to_string()),
+        rssi: props.rssi.unwrap_or(0),
+        manufacturer_data: props.manufacturer_data,
+        service_data: props.service_data,
+        last_seen: seen,
+    })
+}
+
+/// Number of BLE adapters on the system, without claiming one
+pub async fn adapter_count() -> Result<usize> {
+    Ok(Manager::new().await?.adapters().await?.len())
+}
+
 impl Drop for BleManager {
     fn drop(&mut self) {
         // Do NOT create or block a new runtime here. This object is often
         // dropped from within an existing Tokio runtime worker thread, and
         // calling Runtime::new().block_on(...) would panic with:
         // "Cannot start a runtime from within a runtime".
+        if let Ok(cancel) = self.scan_cancel.try_lock() {
+            cancel.cancel();
+        }
         if let Some(adapter) = self.adapter.clone() {
             let scanning = self.is_scanning.clone();
             let advertising = self.is_advertising.clone();
@@ -288,3 +402,63 @@ impl Drop for BleManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_airdrop_device_from_advertisement() {
+        let now = std::time::Instant::now();
+        let props = PeripheralProperties {
+            local_name: Some("iPhone".to_string()),
+            rssi: Some(-60),
+            manufacturer_data: HashMap::from([(APPLE_COMPANY_ID, vec![0x4C, 0x00, AIRDROP_ADVERTISEMENT_TYPE, 0x01])]),
+            ..Default::default()
+        };
+
+        let device = airdrop_device("aa:bb".to_string(), props, now).unwrap();
+        assert_eq!(device.name, "iPhone");
+        assert_eq!(device.rssi, -60);
+        assert_eq!(device.last_seen, now);
+    }
+
+    #[test]
+    fn test_other_peripherals_are_ignored() {
+        let now = std::time::Instant::now();
+        let props = PeripheralProperties {
+            local_name: Some("Cuffie".to_string()),
+            manufacturer_data: HashMap::from([(APPLE_COMPANY_ID, vec![0x4C, 0x00, 0x10])]),
+            ..Default::default()
+        };
+        assert!(airdrop_device("cc:dd".to_string(), props, now).is_none());
+
+        let advertised = PeripheralProperties {
+            services: vec![AIRDROP_SERVICE_UUID],
+            ..Default::default()
+        };
+        let device = airdrop_device("ee:ff".to_string(), advertised, now).unwrap();
+        assert_eq!(device.name, "Unknown AirDrop Device");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_polling_promptly() {
+        let polls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let cancel = CancelToken::new();
+        let counter = polls.clone();
+        let task = tokio::spawn(poll_until_cancelled(Duration::from_millis(10), cancel.clone(), move || {
+            let polls = counter.clone();
+            async move {
+                // The second read never completes, like an adapter being torn down
+                if polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) > 0 {
+                    std::future::pending::<()>().await;
+                }
+            }
+        }));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_millis(100), task).await.unwrap().unwrap();
+        assert_eq!(polls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+}
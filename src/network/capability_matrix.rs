@@ -0,0 +1,183 @@
+//! Capabilities of every discovered device side by side.
+//!
+//! Each row shows what a device advertises in its TXT records, parsed with
+//! the same helpers that decide which actions are offered, so the table
+//! explains why an action is enabled or not. It is shown in the
+//! diagnostics and can be copied as Markdown for an issue.
+
+use std::net::IpAddr;
+
+use super::discovery::{DiscoveredDevice, ServiceType};
+use crate::protocols::airplay_sender::{self, Features};
+
+/// Meaning of each column, in table order
+pub const LEGEND: [(&str, &str); 9] = [
+    ("Files", "accepts AirDrop files (`supports_airdrop`)"),
+    ("Links", "accepts links (`supports_url`, or the URL bit of `flags`)"),
+    ("DV", "`supports_dv` TXT record"),
+    ("Mirroring", "AirPlay receiver with the screen bit in `features`"),
+    ("AirPlay features", "raw `features` TXT record"),
+    ("AirPlay blocker", "why AirWin can't mirror to it, if known before connecting"),
+    ("AWDL", "resolved at an IPv6 link-local address, as AirDrop over AWDL is"),
+    ("BLE", "found over Bluetooth LE, with no IP endpoint"),
+    ("Service", "mDNS service the device was found with"),
+];
+
+/// Capabilities of one device. `None` where its records don't say.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapabilityRow {
+    pub name: String,
+    pub service: String,
+    pub files: Option<bool>,
+    pub links: Option<bool>,
+    pub supports_dv: Option<bool>,
+    pub mirroring: Option<bool>,
+    pub airplay_features: Option<String>,
+    pub airplay_blocker: Option<String>,
+    pub awdl: bool,
+    pub ble: bool,
+}
+
+impl CapabilityRow {
+    pub fn from_device(device: &DiscoveredDevice) -> Self {
+        let capabilities = device.capabilities();
+        let is_airplay = device.service_type == ServiceType::AirPlay;
+        let features = Features::from_txt(&device.txt_records).filter(|_| is_airplay);
+        Self {
+            name: device.name.clone(),
+            service: device.service_type.to_string(),
+            files: capabilities.files,
+            links: capabilities.links,
+            supports_dv: device
+                .txt_records
+                .get("supports_dv")
+                .map(|value| value == "1" || value == "true"),
+            mirroring: capabilities.mirroring,
+            airplay_features: features.and(device.txt_records.get("features").cloned()),
+            airplay_blocker: is_airplay
+                .then(|| airplay_sender::unsupported_reason(&device.txt_records))
+                .flatten(),
+            awdl: matches!(device.address, IpAddr::V6(ip) if ip.segments()[0] & 0xffc0 == 0xfe80),
+            ble: device.txt_records.contains_key("ble_id"),
+        }
+    }
+
+    /// Cells in table order, after the name
+    pub fn cells(&self) -> [String; 9] {
+        let known = |value: Option<bool>| match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "?",
+        };
+        let flag = |value: bool| if value { "yes" } else { "no" };
+        [
+            known(self.files).to_string(),
+            known(self.links).to_string(),
+            known(self.supports_dv).to_string(),
+            known(self.mirroring).to_string(),
+            self.airplay_features.clone().unwrap_or_else(|| "-".to_string()),
+            self.airplay_blocker.clone().unwrap_or_else(|| "-".to_string()),
+            flag(self.awdl).to_string(),
+            flag(self.ble).to_string(),
+            self.service.clone(),
+        ]
+    }
+}
+
+/// One row per device, sorted by name and service
+pub fn rows(devices: &[DiscoveredDevice]) -> Vec<CapabilityRow> {
+    let mut rows: Vec<_> = devices.iter().map(CapabilityRow::from_device).collect();
+    rows.sort_by(|a, b| (&a.name, &a.service).cmp(&(&b.name, &b.service)));
+    rows
+}
+
+/// The table and its legend as Markdown
+pub fn to_markdown(devices: &[DiscoveredDevice]) -> String {
+    // A pipe in a name or a TXT value would split the cell
+    let escape = |cell: &str| cell.replace('|', "\\|");
+    let mut markdown = String::from("| Device |");
+    for (column, _) in LEGEND {
+        markdown.push_str(&format!(" {} |", column));
+    }
+    markdown.push_str("\n|---|");
+    markdown.push_str(&"---|".repeat(LEGEND.len()));
+    for row in rows(devices) {
+        markdown.push_str(&format!("\n| {} |", escape(&row.name)));
+        for cell in row.cells() {
+            markdown.push_str(&format!(" {} |", escape(&cell)));
+        }
+    }
+    markdown.push_str("\n\n? = not advertised.");
+    for (column, meaning) in LEGEND {
+        markdown.push_str(&format!("\n- **{}**: {}", column, meaning));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn device(name: &str, service_type: ServiceType, address: IpAddr, txt: &[(&str, &str)]) -> DiscoveredDevice {
+        DiscoveredDevice {
+            name: name.to_string(),
+            address,
+            port: 8771,
+            service_type,
+            txt_records: txt.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_matrix_from_devices_with_differing_capabilities() {
+        let lan = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        let devices = [
+            device(
+                "MacBook",
+                ServiceType::AirDrop,
+                IpAddr::V6("fe80::1c2d:3e4f:5a6b:7c8d".parse::<Ipv6Addr>().unwrap()),
+                &[("supports_airdrop", "1"), ("supports_url", "1"), ("supports_dv", "true")],
+            ),
+            device("Apple TV", ServiceType::AirPlay, lan, &[("features", "0x5A7FFFF7,0x1E")]),
+            device("Smart TV", ServiceType::AirPlay, lan, &[("features", "0x80")]),
+            device(
+                "iPhone",
+                ServiceType::AirDrop,
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                &[("ble_id", "AA:BB"), ("rssi", "-60")],
+            ),
+        ];
+
+        let rows = rows(&devices);
+        let names: Vec<_> = rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, ["Apple TV", "MacBook", "Smart TV", "iPhone"]);
+
+        let apple_tv = &rows[0];
+        assert_eq!(apple_tv.mirroring, Some(true));
+        assert_eq!(apple_tv.files, Some(false));
+        assert!(apple_tv.airplay_blocker.as_deref().unwrap().contains("FairPlay"));
+
+        let mac = &rows[1];
+        assert_eq!((mac.files, mac.links, mac.supports_dv), (Some(true), Some(true), Some(true)));
+        assert!(mac.awdl && !mac.ble);
+        assert_eq!(mac.airplay_features, None);
+
+        let smart_tv = &rows[2];
+        assert!(smart_tv.airplay_blocker.as_deref().unwrap().contains("not an AirWin PC"));
+        assert_eq!(smart_tv.airplay_features.as_deref(), Some("0x80"));
+
+        let iphone = &rows[3];
+        assert!(iphone.ble && !iphone.awdl);
+        assert_eq!((iphone.files, iphone.links, iphone.supports_dv), (None, None, None));
+
+        let markdown = to_markdown(&devices);
+        let lines: Vec<_> = markdown.lines().collect();
+        assert_eq!(lines[0], "| Device | Files | Links | DV | Mirroring | AirPlay features | AirPlay blocker | AWDL | BLE | Service |");
+        assert_eq!(lines[2], "| Apple TV | no | no | ? | yes | 0x5A7FFFF7,0x1E | the receiver requires FairPlay | no | no | AirPlay |");
+        assert_eq!(lines[3], "| MacBook | yes | yes | yes | no | - | - | yes | no | AirDrop |");
+        assert_eq!(lines[5], "| iPhone | ? | ? | ? | no | - | - | no | yes | AirDrop |");
+        assert!(markdown.contains("- **AWDL**: "));
+    }
+}
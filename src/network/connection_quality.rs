@@ -0,0 +1,173 @@
+//! Quality of the connection to each peer.
+//!
+//! Nothing is measured just for the rating: the round trip comes from the
+//! TCP probe of a device before a send ([`super::device_cache::probe`]) and
+//! the throughput from the files AirDrop sent to it, as timed in their
+//! [`crate::protocols::airdrop::TransferOutcome`]. A peer that hasn't
+//! received such a file yet is rated from its round trip alone. Each peer
+//! keeps its latest few samples of both, and its rating is the worse of what
+//! the median round trip and the median throughput say. A peer without
+//! samples has no rating.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Samples of each kind kept per peer
+const SAMPLES_KEPT: usize = 5;
+
+/// Round trips up to this are good
+const GOOD_RTT: Duration = Duration::from_millis(30);
+/// Round trips above this are poor
+const POOR_RTT: Duration = Duration::from_millis(150);
+
+/// Throughput from this up is good, in bytes per second
+const GOOD_THROUGHPUT: f64 = 5.0 * 1024.0 * 1024.0;
+/// Throughput below this is poor, in bytes per second
+const POOR_THROUGHPUT: f64 = 1024.0 * 1024.0;
+
+/// Transfers smaller than this are mostly handshake and say little about
+/// the link
+const MIN_TRANSFER_BYTES: u64 = 256 * 1024;
+
+/// How well a peer can be reached, worst first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConnectionQuality {
+    Poor,
+    Fair,
+    Good,
+}
+
+/// Rating for a round trip and a throughput in bytes per second, the worse
+/// of the two when both are known
+pub fn rate(rtt: Option<Duration>, throughput: Option<f64>) -> Option<ConnectionQuality> {
+    let by_rtt = rtt.map(|rtt| {
+        if rtt <= GOOD_RTT {
+            ConnectionQuality::Good
+        } else if rtt <= POOR_RTT {
+            ConnectionQuality::Fair
+        } else {
+            ConnectionQuality::Poor
+        }
+    });
+    let by_throughput = throughput.map(|throughput| {
+        if throughput >= GOOD_THROUGHPUT {
+            ConnectionQuality::Good
+        } else if throughput >= POOR_THROUGHPUT {
+            ConnectionQuality::Fair
+        } else {
+            ConnectionQuality::Poor
+        }
+    });
+    match (by_rtt, by_throughput) {
+        (Some(rtt), Some(throughput)) => Some(rtt.min(throughput)),
+        (rtt, throughput) => rtt.or(throughput),
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Samples {
+    rtts: VecDeque<Duration>,
+    throughputs: VecDeque<f64>,
+}
+
+/// Recent round trips and throughputs of each peer, by address
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionQualityTracker {
+    peers: HashMap<IpAddr, Samples>,
+}
+
+impl ConnectionQualityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the round trip of a probe to `peer`
+    pub fn record_rtt(&mut self, peer: IpAddr, rtt: Duration) {
+        keep_latest(&mut self.peers.entry(peer).or_default().rtts, rtt);
+    }
+
+    /// Record a transfer of `bytes` to `peer` that took `elapsed`. Small
+    /// transfers are ignored.
+    pub fn record_transfer(&mut self, peer: IpAddr, bytes: u64, elapsed: Duration) {
+        if bytes < MIN_TRANSFER_BYTES || elapsed.is_zero() {
+            return;
+        }
+        let throughput = bytes as f64 / elapsed.as_secs_f64();
+        keep_latest(&mut self.peers.entry(peer).or_default().throughputs, throughput);
+    }
+
+    /// Rating of `peer` from its recent samples
+    pub fn quality(&self, peer: IpAddr) -> Option<ConnectionQuality> {
+        let samples = self.peers.get(&peer)?;
+        rate(median(&samples.rtts), median(&samples.throughputs))
+    }
+}
+
+fn keep_latest<T>(samples: &mut VecDeque<T>, sample: T) {
+    if samples.len() == SAMPLES_KEPT {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// Middle sample, the lower of the two middle ones for an even count
+fn median<T: Copy + PartialOrd>(samples: &VecDeque<T>) -> Option<T> {
+    let mut sorted: Vec<T> = samples.iter().copied().collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sorted.get(sorted.len().saturating_sub(1) / 2).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const MB: f64 = 1024.0 * 1024.0;
+
+    #[test]
+    fn test_samples_map_to_buckets() {
+        let ms = Duration::from_millis;
+        let cases = [
+            (Some(ms(5)), Some(20.0 * MB), Some(ConnectionQuality::Good)),
+            (Some(ms(30)), Some(5.0 * MB), Some(ConnectionQuality::Good)),
+            (Some(ms(31)), Some(20.0 * MB), Some(ConnectionQuality::Fair)),
+            (Some(ms(5)), Some(2.0 * MB), Some(ConnectionQuality::Fair)),
+            (Some(ms(150)), Some(MB), Some(ConnectionQuality::Fair)),
+            (Some(ms(151)), Some(20.0 * MB), Some(ConnectionQuality::Poor)),
+            (Some(ms(5)), Some(0.5 * MB), Some(ConnectionQuality::Poor)),
+            (Some(ms(80)), None, Some(ConnectionQuality::Fair)),
+            (None, Some(8.0 * MB), Some(ConnectionQuality::Good)),
+            (None, None, None),
+        ];
+        for (rtt, throughput, expected) in cases {
+            assert_eq!(rate(rtt, throughput), expected, "{:?}, {:?}", rtt, throughput);
+        }
+    }
+
+    #[test]
+    fn test_rating_follows_recent_samples() {
+        let peer = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        let mut tracker = ConnectionQualityTracker::new();
+        assert_eq!(tracker.quality(peer), None);
+
+        // A single slow probe among fast ones doesn't change the rating
+        for rtt in [10, 12, 400, 9] {
+            tracker.record_rtt(peer, Duration::from_millis(rtt));
+        }
+        assert_eq!(tracker.quality(peer), Some(ConnectionQuality::Good));
+
+        // Small files are ignored, large slow ones pull the rating down
+        tracker.record_transfer(peer, 1024, Duration::from_secs(10));
+        assert_eq!(tracker.quality(peer), Some(ConnectionQuality::Good));
+        tracker.record_transfer(peer, 10 * 1024 * 1024, Duration::from_secs(5));
+        assert_eq!(tracker.quality(peer), Some(ConnectionQuality::Fair));
+
+        // Only the latest probes count
+        for _ in 0..SAMPLES_KEPT {
+            tracker.record_rtt(peer, Duration::from_millis(300));
+        }
+        assert_eq!(tracker.quality(peer), Some(ConnectionQuality::Poor));
+        assert_eq!(tracker.quality(IpAddr::V4(Ipv4Addr::LOCALHOST)), None);
+    }
+}
@@ -0,0 +1,540 @@
+//! Local HTTP control API for scripts and automation.
+//!
+//! The API is disabled by default. When `control_api.enabled` is set in
+//! `settings.json`, AirWin generates a random token on the next start, saves
+//! it next to the other settings and listens on `127.0.0.1` only. Every
+//! request must carry the token as `Authorization: Bearer <token>`; requests
+//! without it get `401`. Delete the token from the settings file to rotate it.
+//!
+//! Endpoints, all returning JSON:
+//! - `GET /devices`: devices currently discovered
+//! - `GET /status`: AirDrop and AirPlay state
+//! - `POST /send` with `{"device": "<name>", "path": "<file>"}`: send a file over AirDrop.
+//!   The device is looked up again first, so the file goes to its current
+//!   address; a device that no longer answers fails with "please rescan".
+//! - `POST /benchmark` with `{"megabytes": <n>}`: time a loopback transfer of a
+//!   generated file and report `bytes`, `seconds` and `mb_per_s`. Meant for
+//!   comparing performance changes, it has no counterpart in the UI.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::discovery::{DeviceDiscovery, ServiceType};
+use crate::protocols::airdrop::{
+    read_with_timeout, AirDrop, BenchmarkResult, TransferOutcome, ACCEPT_RETRY_DELAY, READ_TIMEOUT,
+};
+use crate::protocols::airplay::AirPlay;
+use crate::utils::{event_log, supervisor};
+
+/// Port used when the settings don't name one
+pub const DEFAULT_PORT: u16 = 8780;
+
+/// Largest request accepted, headers and body included
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+/// Device as reported by `GET /devices`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSummary {
+    pub name: String,
+    pub address: SocketAddr,
+    pub service: String,
+}
+
+/// Body of `GET /status`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusSummary {
+    pub airdrop: String,
+    pub airplay: String,
+}
+
+/// Body of `POST /send`
+#[derive(Debug, Deserialize)]
+struct SendRequest {
+    device: String,
+    path: PathBuf,
+}
+
+/// Body of `POST /benchmark`
+#[derive(Debug, Deserialize)]
+struct BenchmarkRequest {
+    megabytes: u64,
+}
+
+/// Services driven by the control API
+#[async_trait]
+pub trait ControlTarget: Send + Sync {
+    async fn devices(&self) -> Vec<DeviceSummary>;
+
+    /// Send the file at `path` to the device called `device`
+    async fn send(&self, device: &str, path: PathBuf) -> Result<TransferOutcome>;
+
+    async fn status(&self) -> StatusSummary;
+
+    /// Time a loopback transfer of a generated `megabytes` MB file
+    async fn benchmark(&self, megabytes: u64) -> Result<BenchmarkResult>;
+}
+
+/// The running AirWin services
+pub struct ServicesTarget {
+    discovery: Arc<Mutex<DeviceDiscovery>>,
+    airdrop: AirDrop,
+    airplay: AirPlay,
+}
+
+impl ServicesTarget {
+    pub fn new(discovery: Arc<Mutex<DeviceDiscovery>>, airdrop: AirDrop, airplay: AirPlay) -> Self {
+        Self {
+            discovery,
+            airdrop,
+            airplay,
+        }
+    }
+}
+
+#[async_trait]
+impl ControlTarget for ServicesTarget {
+    async fn devices(&self) -> Vec<DeviceSummary> {
+        let devices = self.discovery.lock().await.get_devices().await.unwrap_or_default();
+        devices
+            .into_iter()
+            .map(|device| DeviceSummary {
+                address: SocketAddr::new(device.address, device.port),
+                service: device.service_type.to_string(),
+                name: device.name,
+            })
+            .collect()
+    }
+
+    async fn send(&self, device: &str, path: PathBuf) -> Result<TransferOutcome> {
+        // The resolve takes up to two seconds: don't block scans meanwhile
+        let (devices, resolver) = {
+            let discovery = self.discovery.lock().await;
+            (discovery.get_devices().await?, discovery.resolver())
+        };
+        let target = devices
+            .iter()
+            .filter(|d| d.name == device)
+            .max_by_key(|d| (d.service_type == ServiceType::AirDrop, d.service_type.accepts_airdrop()))
+            .ok_or_else(|| anyhow!("Unknown device: {}", device))?;
+        let target = resolver.resolve(target).await?;
+        let addr = target
+            .airdrop_addr()
+            .unwrap_or_else(|| SocketAddr::new(target.address, target.port));
+
+        self.airdrop.send_file_to(addr, path).await
+    }
+
+    async fn status(&self) -> StatusSummary {
+        StatusSummary {
+            airdrop: format!("{:?}", self.airdrop.get_status().await),
+            airplay: format!("{:?}", self.airplay.get_status().await),
+        }
+    }
+
+    async fn benchmark(&self, megabytes: u64) -> Result<BenchmarkResult> {
+        self.airdrop.loopback_benchmark(megabytes).await
+    }
+}
+
+/// HTTP server exposing a `ControlTarget` on the loopback interface
+pub struct ControlApi {
+    port: u16,
+    token: String,
+    target: Arc<dyn ControlTarget>,
+}
+
+impl ControlApi {
+    pub fn new(port: u16, token: String, target: Arc<dyn ControlTarget>) -> Self {
+        Self { port, token, target }
+    }
+
+    /// Bind to `127.0.0.1` and serve requests in the background
+    pub async fn start(self) -> Result<(SocketAddr, JoinHandle<()>)> {
+        if self.token.is_empty() {
+            return Err(anyhow!("Control API token is empty"));
+        }
+
+        let listener = Arc::new(TcpListener::bind((Ipv4Addr::LOCALHOST, self.port)).await?);
+        let addr = listener.local_addr()?;
+        info!("Control API listening on {}", addr);
+
+        let token = Arc::new(self.token);
+        let target = self.target;
+        let task = supervisor::global().spawn(format!("Control API {}", addr), move || {
+            let listener = listener.clone();
+            let token = token.clone();
+            let target = target.clone();
+            async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            let token = token.clone();
+                            let target = target.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = Self::handle_connection(stream, &token, target.as_ref()).await {
+                                    debug!("Control API request from {} failed: {}", peer, e);
+                                }
+                            });
+                        }
+                        // Usually transient, so the listener is kept
+                        Err(e) => {
+                            warn!("Control API accept error: {}", e);
+                            event_log::warning("Control API", format!("Accept error on {}: {}", addr, e));
+                            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((addr, task))
+    }
+
+    async fn handle_connection(mut stream: TcpStream, token: &str, target: &dyn ControlTarget) -> Result<()> {
+        let (status, body) = match read_request(&mut stream, READ_TIMEOUT).await {
+            Ok(request) => route(&request, token, target).await,
+            Err(e) => (400, json!({ "error": e.to_string() })),
+        };
+
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason(status),
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+/// Parsed HTTP request
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Read a whole request, failing if the client sends nothing for `timeout`
+async fn read_request(stream: &mut TcpStream, timeout: Duration) -> Result<Request> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        if let Some(request) = parse_request(&buffer)? {
+            return Ok(request);
+        }
+        if buffer.len() > MAX_REQUEST_SIZE {
+            return Err(anyhow!("Request too large"));
+        }
+
+        let n = read_with_timeout(stream, &mut chunk, timeout).await?;
+        if n == 0 {
+            return Err(anyhow!("Connection closed before the request was complete"));
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Parse a complete request, or return `None` if more bytes are needed
+fn parse_request(buffer: &[u8]) -> Result<Option<Request>> {
+    let Some(header_end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+
+    let head = std::str::from_utf8(&buffer[..header_end])?;
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().ok_or_else(|| anyhow!("Missing method"))?.to_string();
+    let path = request_line.next().ok_or_else(|| anyhow!("Missing path"))?.to_string();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = match headers.get("content-length") {
+        Some(value) => value.parse::<usize>()?,
+        None => 0,
+    };
+    if content_length > MAX_REQUEST_SIZE {
+        return Err(anyhow!("Request too large"));
+    }
+
+    let body_start = header_end + 4;
+    if buffer.len() < body_start + content_length {
+        return Ok(None);
+    }
+
+    Ok(Some(Request {
+        method,
+        path,
+        headers,
+        body: buffer[body_start..body_start + content_length].to_vec(),
+    }))
+}
+
+/// Check the bearer token without leaking its length or prefix through timing
+fn authorized(request: &Request, token: &str) -> bool {
+    let Some(provided) = request
+        .headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    let provided = provided.trim().as_bytes();
+    let expected = token.as_bytes();
+    let mut diff = provided.len() ^ expected.len();
+    for (i, byte) in expected.iter().enumerate() {
+        diff |= (byte ^ provided.get(i).copied().unwrap_or(0)) as usize;
+    }
+    diff == 0
+}
+
+async fn route(request: &Request, token: &str, target: &dyn ControlTarget) -> (u16, serde_json::Value) {
+    if !authorized(request, token) {
+        return (401, json!({ "error": "Missing or invalid token" }));
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/devices") => (200, json!(target.devices().await)),
+        ("GET", "/status") => (200, json!(target.status().await)),
+        ("POST", "/send") => {
+            let send: SendRequest = match serde_json::from_slice(&request.body) {
+                Ok(send) => send,
+                Err(e) => return (400, json!({ "error": format!("Invalid body: {}", e) })),
+            };
+
+            info!("Control API send of {:?} to {}", send.path, send.device);
+            match target.send(&send.device, send.path).await {
+                Ok(outcome) => (
+                    200,
+                    json!({
+                        "file_name": outcome.file_name,
+                        "total_bytes": outcome.total_bytes,
+                        "elapsed_ms": outcome.elapsed.as_millis() as u64,
+                        "peer": outcome.peer,
+                    }),
+                ),
+                Err(e) => (500, json!({ "error": e.to_string() })),
+            }
+        }
+        ("POST", "/benchmark") => {
+            let benchmark: BenchmarkRequest = match serde_json::from_slice(&request.body) {
+                Ok(benchmark) => benchmark,
+                Err(e) => return (400, json!({ "error": format!("Invalid body: {}", e) })),
+            };
+
+            info!("Control API loopback benchmark of {} MB", benchmark.megabytes);
+            match target.benchmark(benchmark.megabytes).await {
+                Ok(result) => (200, json!(result)),
+                Err(e) => (500, json!({ "error": e.to_string() })),
+            }
+        }
+        _ => (404, json!({ "error": "Not found" })),
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    const TOKEN: &str = "secret-token";
+
+    struct MockTarget;
+
+    #[async_trait]
+    impl ControlTarget for MockTarget {
+        async fn devices(&self) -> Vec<DeviceSummary> {
+            vec![DeviceSummary {
+                name: "iPhone".to_string(),
+                address: "192.168.1.20:8771".parse().unwrap(),
+                service: "AirDrop".to_string(),
+            }]
+        }
+
+        async fn send(&self, device: &str, path: PathBuf) -> Result<TransferOutcome> {
+            if device != "iPhone" {
+                return Err(anyhow!("Unknown device: {}", device));
+            }
+            Ok(TransferOutcome {
+                file_name: path.file_name().unwrap().to_string_lossy().to_string(),
+                total_bytes: 42,
+                elapsed: Duration::from_millis(10),
+                peer: device.to_string(),
+            })
+        }
+
+        async fn status(&self) -> StatusSummary {
+            StatusSummary {
+                airdrop: "Idle".to_string(),
+                airplay: "Idle".to_string(),
+            }
+        }
+
+        async fn benchmark(&self, megabytes: u64) -> Result<BenchmarkResult> {
+            Ok(BenchmarkResult {
+                bytes: megabytes * 1_000_000,
+                seconds: 0.5,
+                mb_per_s: megabytes as f64 * 2.0,
+                chunk_size: 64 * 1024,
+                tls_backend: Default::default(),
+            })
+        }
+    }
+
+    async fn request(addr: SocketAddr, raw: String) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(raw.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    async fn start() -> SocketAddr {
+        let api = ControlApi::new(0, TOKEN.to_string(), Arc::new(MockTarget));
+        let (addr, _) = api.start().await.unwrap();
+        assert!(addr.ip().is_loopback());
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_or_wrong_token() {
+        let addr = start().await;
+
+        let (status, _) = request(addr, "GET /status HTTP/1.1\r\n\r\n".to_string()).await;
+        assert_eq!(status, 401);
+
+        let (status, _) = request(
+            addr,
+            "GET /status HTTP/1.1\r\nAuthorization: Bearer secret-tokem\r\n\r\n".to_string(),
+        )
+        .await;
+        assert_eq!(status, 401);
+    }
+
+    #[tokio::test]
+    async fn test_lists_devices_and_status() {
+        let addr = start().await;
+
+        let (status, body) = request(
+            addr,
+            format!("GET /devices HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n", TOKEN),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let devices: Vec<DeviceSummary> = serde_json::from_value(body).unwrap();
+        assert_eq!(devices[0].name, "iPhone");
+
+        let (status, body) = request(
+            addr,
+            format!("GET /status HTTP/1.1\r\nAuthorization: Bearer {}\r\n\r\n", TOKEN),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(body["airdrop"], "Idle");
+    }
+
+    #[tokio::test]
+    async fn test_send_returns_outcome_or_error() {
+        let addr = start().await;
+        let send = |device: &str| {
+            let body = json!({ "device": device, "path": "C:\\photo.jpg" }).to_string();
+            format!(
+                "POST /send HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+                TOKEN,
+                body.len(),
+                body
+            )
+        };
+
+        let (status, body) = request(addr, send("iPhone")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["total_bytes"], 42);
+
+        let (status, body) = request(addr, send("iPad")).await;
+        assert_eq!(status, 500);
+        assert!(body["error"].as_str().unwrap().contains("iPad"));
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_returns_structured_result() {
+        let addr = start().await;
+        let body = json!({ "megabytes": 8 }).to_string();
+
+        let (status, body) = request(
+            addr,
+            format!(
+                "POST /benchmark HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+                TOKEN,
+                body.len(),
+                body
+            ),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let result: BenchmarkResult = serde_json::from_value(body).unwrap();
+        assert_eq!(result.bytes, 8_000_000);
+        assert_eq!(result.mb_per_s, 16.0);
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_times_out() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // Half a request, then nothing
+        client.write_all(b"GET /status HTTP/1.1\r\n").await.unwrap();
+        let started = std::time::Instant::now();
+        assert!(read_request(&mut stream, Duration::from_millis(100)).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malformed_body() {
+        let addr = start().await;
+
+        let (status, _) = request(
+            addr,
+            format!(
+                "POST /send HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: 3\r\n\r\n{{}}x",
+                TOKEN
+            ),
+        )
+        .await;
+        assert_eq!(status, 400);
+    }
+}
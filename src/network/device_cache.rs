@@ -0,0 +1,303 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+use super::discovery::{DeviceId, DiscoveredDevice};
+
+/// How long a device that missed a scan stays in the "recently seen" list
+pub const DEFAULT_RECENT_EXPIRY: Duration = Duration::from_secs(5 * 60);
+
+/// Time allowed for a recently seen device to answer a probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a device that appeared in a scan is marked as new
+pub const NEW_DEVICE_HIGHLIGHT: Duration = Duration::from_secs(5);
+
+/// How long a device that dropped out of a scan takes to fade
+pub const DROPPED_DEVICE_FADE: Duration = Duration::from_secs(2);
+
+/// Devices that appeared, dropped out or stayed between two scans
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanDiff {
+    pub added: HashSet<DeviceId>,
+    pub removed: HashSet<DeviceId>,
+    pub unchanged: HashSet<DeviceId>,
+}
+
+impl ScanDiff {
+    fn between(previous: &HashSet<DeviceId>, current: &HashSet<DeviceId>) -> Self {
+        Self {
+            added: current.difference(previous).cloned().collect(),
+            removed: previous.difference(current).cloned().collect(),
+            unchanged: current.intersection(previous).cloned().collect(),
+        }
+    }
+
+    /// Whether the scan found the same devices as the previous one
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    device: DiscoveredDevice,
+    last_seen: Instant,
+}
+
+/// Remembers when each device was last found, so devices that briefly drop
+/// off (e.g. Wi-Fi power saving) can still be shown and selected
+#[derive(Clone, Debug)]
+pub struct DeviceCache {
+    entries: HashMap<DeviceId, Entry>,
+    /// Devices found by the latest scan
+    current: HashSet<DeviceId>,
+    expiry: Duration,
+}
+
+impl Default for DeviceCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_EXPIRY)
+    }
+}
+
+impl DeviceCache {
+    pub fn new(expiry: Duration) -> Self {
+        Self {
+            entries: HashMap::new(),
+            current: HashSet::new(),
+            expiry,
+        }
+    }
+
+    pub fn set_expiry(&mut self, expiry: Duration) {
+        self.expiry = expiry;
+    }
+
+    /// Record the devices found by a scan and forget those that expired.
+    /// Returns what changed since the previous scan.
+    pub fn record_scan(&mut self, devices: &[DiscoveredDevice]) -> ScanDiff {
+        self.record_scan_at(devices, Instant::now())
+    }
+
+    fn record_scan_at(&mut self, devices: &[DiscoveredDevice], now: Instant) -> ScanDiff {
+        let current: HashSet<DeviceId> = devices.iter().map(DiscoveredDevice::id).collect();
+        let diff = ScanDiff::between(&self.current, &current);
+        self.current = current;
+        for device in devices {
+            self.entries.insert(device.id(), Entry { device: device.clone(), last_seen: now });
+        }
+        let expiry = self.expiry;
+        self.entries.retain(|_, entry| now.duration_since(entry.last_seen) <= expiry);
+        diff
+    }
+
+    /// Mark a device as found again outside of a scan, e.g. after a probe
+    pub fn mark_seen(&mut self, device: &DiscoveredDevice) {
+        let id = device.id();
+        self.entries.insert(id.clone(), Entry { device: device.clone(), last_seen: Instant::now() });
+        self.current.insert(id);
+    }
+
+    /// Devices that missed the latest scan but have not expired, most recent first
+    pub fn recently_seen(&self) -> Vec<DiscoveredDevice> {
+        self.recently_seen_at(Instant::now())
+    }
+
+    fn recently_seen_at(&self, now: Instant) -> Vec<DiscoveredDevice> {
+        let mut recent: Vec<_> = self.entries
+            .iter()
+            .filter(|(id, entry)| {
+                !self.current.contains(*id) && now.duration_since(entry.last_seen) <= self.expiry
+            })
+            .map(|(_, entry)| entry)
+            .collect();
+        recent.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        recent.into_iter().map(|entry| entry.device.clone()).collect()
+    }
+
+    /// Time since the device was last found
+    pub fn seen_ago(&self, device: &DiscoveredDevice) -> Option<Duration> {
+        self.entries.get(&device.id()).map(|entry| entry.last_seen.elapsed())
+    }
+}
+
+/// Devices to highlight after a scan: new ones for a few seconds, dropped
+/// ones while they fade
+#[derive(Clone, Debug, Default)]
+pub struct ScanHighlights {
+    /// When each new device appeared
+    added: HashMap<DeviceId, Instant>,
+    /// When each device dropped out
+    removed: HashMap<DeviceId, Instant>,
+}
+
+impl ScanHighlights {
+    /// Start highlighting the changes of `diff`
+    pub fn record(&mut self, diff: &ScanDiff, now: Instant) {
+        for id in &diff.added {
+            self.removed.remove(id);
+            self.added.insert(id.clone(), now);
+        }
+        for id in &diff.removed {
+            self.added.remove(id);
+            self.removed.insert(id.clone(), now);
+        }
+    }
+
+    /// Forget the highlights that are over
+    pub fn expire(&mut self, now: Instant) {
+        self.added.retain(|_, since| now.duration_since(*since) < NEW_DEVICE_HIGHLIGHT);
+        self.removed.retain(|_, since| now.duration_since(*since) < DROPPED_DEVICE_FADE);
+    }
+
+    /// Whether some highlight is still running, so the view must be refreshed
+    pub fn is_active(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty()
+    }
+
+    /// Whether `device` appeared in a recent scan
+    pub fn is_new(&self, device: &DiscoveredDevice, now: Instant) -> bool {
+        self.added
+            .get(&device.id())
+            .is_some_and(|since| now.duration_since(*since) < NEW_DEVICE_HIGHLIGHT)
+    }
+
+    /// How far `device` has faded since it dropped out, from 0 to 1. A
+    /// device that didn't drop out recently is fully faded.
+    pub fn fade(&self, device: &DiscoveredDevice, now: Instant) -> f32 {
+        match self.removed.get(&device.id()) {
+            Some(since) => (now.duration_since(*since).as_secs_f32() / DROPPED_DEVICE_FADE.as_secs_f32()).min(1.0),
+            None => 1.0,
+        }
+    }
+}
+
+/// Check whether a device that missed the last scan still accepts
+/// connections. Returns how long the connection took, `None` if it failed.
+pub async fn probe(device: &DiscoveredDevice) -> Option<Duration> {
+    let addr = SocketAddr::new(device.address, device.port);
+    let started = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Some(started.elapsed()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ServiceType;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn device(name: &str, last_octet: u8) -> DiscoveredDevice {
+        DiscoveredDevice {
+            name: name.to_string(),
+            address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, last_octet)),
+            port: 8771,
+            service_type: ServiceType::AirDrop,
+            txt_records: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_missed_scan_moves_device_to_recent() {
+        let mut cache = DeviceCache::new(Duration::from_secs(300));
+        let start = Instant::now();
+        let phone = device("iPhone", 10);
+        let mac = device("MacBook", 11);
+
+        cache.record_scan_at(&[phone.clone(), mac.clone()], start);
+        assert!(cache.recently_seen_at(start).is_empty());
+
+        cache.record_scan_at(&[mac.clone()], start + Duration::from_secs(30));
+        let recent = cache.recently_seen_at(start + Duration::from_secs(30));
+        assert_eq!(recent.len(), 1);
+        assert!(recent[0].same_device(&phone));
+
+        // Back in the next scan: no longer "recent"
+        cache.record_scan_at(&[phone, mac], start + Duration::from_secs(60));
+        assert!(cache.recently_seen_at(start + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_scan_diff_sets() {
+        let mut cache = DeviceCache::new(Duration::from_secs(300));
+        let start = Instant::now();
+        let phone = device("iPhone", 10);
+        let mac = device("MacBook", 11);
+        let ipad = device("iPad", 12);
+
+        let first = cache.record_scan_at(&[phone.clone(), mac.clone()], start);
+        assert_eq!(first.added, HashSet::from([phone.id(), mac.id()]));
+        assert!(first.removed.is_empty() && first.unchanged.is_empty());
+
+        let second = cache.record_scan_at(&[mac.clone(), ipad.clone()], start + Duration::from_secs(10));
+        assert_eq!(second.added, HashSet::from([ipad.id()]));
+        assert_eq!(second.removed, HashSet::from([phone.id()]));
+        assert_eq!(second.unchanged, HashSet::from([mac.id()]));
+
+        let same = cache.record_scan_at(&[mac.clone(), ipad.clone()], start + Duration::from_secs(20));
+        assert!(same.is_empty());
+        assert_eq!(same.unchanged, HashSet::from([mac.id(), ipad.id()]));
+    }
+
+    #[test]
+    fn test_highlights_end_after_their_duration() {
+        let start = Instant::now();
+        let phone = device("iPhone", 10);
+        let mac = device("MacBook", 11);
+        let mut highlights = ScanHighlights::default();
+        highlights.record(
+            &ScanDiff {
+                added: HashSet::from([phone.id()]),
+                removed: HashSet::from([mac.id()]),
+                unchanged: HashSet::new(),
+            },
+            start,
+        );
+
+        assert!(highlights.is_new(&phone, start + Duration::from_secs(1)));
+        assert!(!highlights.is_new(&mac, start));
+        assert_eq!(highlights.fade(&mac, start), 0.0);
+        assert_eq!(highlights.fade(&mac, start + DROPPED_DEVICE_FADE / 2), 0.5);
+        assert_eq!(highlights.fade(&phone, start), 1.0);
+
+        highlights.expire(start + DROPPED_DEVICE_FADE);
+        assert_eq!(highlights.fade(&mac, start), 1.0);
+        assert!(highlights.is_active());
+        highlights.expire(start + NEW_DEVICE_HIGHLIGHT);
+        assert!(!highlights.is_new(&phone, start + NEW_DEVICE_HIGHLIGHT));
+        assert!(!highlights.is_active());
+    }
+
+    #[test]
+    fn test_recent_devices_expire() {
+        let mut cache = DeviceCache::new(Duration::from_secs(120));
+        let start = Instant::now();
+        cache.record_scan_at(&[device("iPhone", 10)], start);
+        cache.record_scan_at(&[], start + Duration::from_secs(60));
+        assert_eq!(cache.recently_seen_at(start + Duration::from_secs(60)).len(), 1);
+
+        cache.record_scan_at(&[], start + Duration::from_secs(121));
+        assert!(cache.recently_seen_at(start + Duration::from_secs(121)).is_empty());
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_recent_devices_sorted_by_last_seen() {
+        let mut cache = DeviceCache::new(Duration::from_secs(300));
+        let start = Instant::now();
+        cache.record_scan_at(&[device("Older", 10)], start);
+        cache.record_scan_at(&[device("Newer", 11)], start + Duration::from_secs(10));
+        cache.record_scan_at(&[], start + Duration::from_secs(20));
+
+        let names: Vec<_> = cache
+            .recently_seen_at(start + Duration::from_secs(20))
+            .into_iter()
+            .map(|device| device.name)
+            .collect();
+        assert_eq!(names, ["Newer", "Older"]);
+    }
+}
@@ -1,4 +1,4 @@
-use mdns_sd::{ServiceDaemon, ServiceEvent};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use anyhow::Result;
@@ -6,8 +6,14 @@ use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr, Ipv4Addr};
 use tracing::{info, error, warn};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use socket2::{Socket, Domain, Type, Protocol};
+use super::backend::{self, CancelToken, DiscoveryBackend, MdnsBackend};
 use super::interface::NetworkManager;
+use super::ip_mode::IpMode;
+use crate::protocols::airdrop::AIRDROP_HTTPS_PORT;
+use crate::protocols::airplay_sender::AirPlayFeatures;
+use crate::utils::event_log;
 
 #[derive(Clone, Debug)]
 #[allow(dead_code)]
@@ -20,7 +26,156 @@ pub struct DiscoveredDevice {
 	pub txt_records: HashMap<String, String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// Stable identity of a discovered device: two devices can share a name
+/// (e.g. "iPhone") but not the same name, endpoint and service
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId {
+	pub name: String,
+	pub address: IpAddr,
+	pub port: u16,
+	pub service_type: ServiceType,
+}
+
+impl DiscoveredDevice {
+	pub fn id(&self) -> DeviceId {
+		DeviceId {
+			name: self.name.clone(),
+			address: self.address,
+			port: self.port,
+			service_type: self.service_type.clone(),
+		}
+	}
+
+	/// Whether both values describe the same device
+	pub fn same_device(&self, other: &DiscoveredDevice) -> bool {
+		self.id() == other.id()
+	}
+
+	/// Where AirDrop transfers to this device go, if it accepts them.
+	///
+	/// Companion Link advertises its own port, so transfers to a companion
+	/// device use the standard AirDrop port instead. A device seen only over
+	/// BLE has no address until mDNS resolves it, so it has none yet.
+	pub fn airdrop_addr(&self) -> Option<SocketAddr> {
+		if self.address.is_unspecified() {
+			return None;
+		}
+		match self.service_type {
+			ServiceType::AirDrop => Some(SocketAddr::new(self.address, self.port)),
+			ServiceType::Companion => Some(SocketAddr::new(self.address, AIRDROP_HTTPS_PORT)),
+			_ => None,
+		}
+	}
+
+	/// What the device accepts, according to its TXT records
+	pub fn capabilities(&self) -> Capabilities {
+		Capabilities::from_txt(&self.service_type, &self.txt_records)
+	}
+
+	/// Plain-text summary of the device and all its TXT records, sorted by
+	/// key, for pasting into a bug report
+	pub fn details_report(&self) -> String {
+		let known = |value: Option<bool>| match value {
+			Some(true) => "yes",
+			Some(false) => "no",
+			None => "unknown",
+		};
+		let capabilities = self.capabilities();
+		let mut report = format!(
+			"Name: {}\nService: {}\nAddress: {}:{}\nFiles: {}\nLinks: {}\nMirroring: {}\nTXT records:",
+			self.name,
+			self.service_type,
+			self.address,
+			self.port,
+			known(capabilities.files),
+			known(capabilities.links),
+			known(capabilities.mirroring),
+		);
+		let mut records: Vec<_> = self.txt_records.iter().collect();
+		records.sort();
+		if records.is_empty() {
+			report.push_str(" none");
+		}
+		for (key, value) in records {
+			report.push_str(&format!("\n  {}={}", key, value));
+		}
+		report
+	}
+}
+
+/// AirDrop `flags` bit set by devices that accept links
+const AIRDROP_SUPPORTS_URL: u32 = 0x01;
+
+/// Actions offered for a selected device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceAction {
+	SendFile,
+	SendLink,
+	ScreenMirroring,
+}
+
+/// Capabilities advertised by a device, `None` where its records don't say.
+///
+/// Devices found over BLE or without TXT records have unknown capabilities:
+/// their actions stay enabled and a transfer they can't handle fails with an
+/// error instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+	pub files: Option<bool>,
+	pub links: Option<bool>,
+	pub mirroring: Option<bool>,
+	/// AirPlay audio streaming
+	pub audio: Option<bool>,
+}
+
+impl Capabilities {
+	pub fn from_txt(service_type: &ServiceType, txt: &HashMap<String, String>) -> Self {
+		let flag = |key: &str| txt.get(key).map(|value| value == "1" || value == "true");
+
+		let (files, links) = if service_type.accepts_airdrop() {
+			let links = flag("supports_url").or_else(|| {
+				let flags = txt.get("flags")?;
+				let flags = match flags.strip_prefix("0x") {
+					Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+					None => flags.parse::<u32>().ok()?,
+				};
+				Some(flags & AIRDROP_SUPPORTS_URL != 0)
+			});
+			(flag("supports_airdrop"), links)
+		} else {
+			(Some(false), Some(false))
+		};
+
+		let (mirroring, audio) = if *service_type == ServiceType::AirPlay {
+			let features = AirPlayFeatures::from_txt(txt);
+			(
+				features.map(|features| features.screen_mirroring),
+				features.map(|features| features.audio || features.buffered_audio),
+			)
+		} else {
+			(Some(false), Some(false))
+		};
+
+		Self { files, links, mirroring, audio }
+	}
+
+	/// An AirPlay speaker: it plays audio but doesn't take the screen
+	pub fn is_audio_only(&self) -> bool {
+		self.audio == Some(true) && self.mirroring == Some(false)
+	}
+
+	/// Whether the action should be offered; unknown capabilities are allowed
+	pub fn allows(&self, action: DeviceAction) -> bool {
+		let supported = match action {
+			DeviceAction::SendFile => self.files,
+			DeviceAction::SendLink => self.links,
+			DeviceAction::ScreenMirroring => self.mirroring,
+		};
+		supported.unwrap_or(true)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ServiceType {
 	AirPlay,
 	AirDrop,
@@ -53,6 +208,103 @@ pub enum ServiceType {
 	AppleAuth,
 	#[allow(dead_code)]
 	Presence,
+	/// Service type AirWin doesn't recognize, as received over mDNS
+	Other(String),
+}
+
+impl std::fmt::Display for ServiceType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ServiceType::Other(service) => f.write_str(service),
+			known => write!(f, "{:?}", known),
+		}
+	}
+}
+
+impl ServiceType {
+	/// Type of an mDNS service such as `_airplay._tcp.local.`, keeping the
+	/// name of services that aren't recognized
+	pub fn from_mdns(service_type: &str) -> Self {
+		match service_type {
+			"_airplay._tcp.local." => ServiceType::AirPlay,
+			"_raop._tcp.local." => ServiceType::Raop,
+			"_airdrop._tcp.local." => ServiceType::AirDrop,
+			"_companion-link._tcp.local." => ServiceType::Companion,
+			"_device-info._tcp.local." => ServiceType::DeviceInfo,
+			other => ServiceType::Other(other.to_string()),
+		}
+	}
+
+	/// Whether files and links can be sent to devices of this type
+	pub fn accepts_airdrop(&self) -> bool {
+		matches!(self, ServiceType::AirDrop | ServiceType::Companion)
+	}
+}
+
+/// Service types browsed over mDNS
+pub(crate) const SERVICE_TYPES: &[&str] = &[
+	"_airplay._tcp.local.",
+	"_raop._tcp.local.",
+	"_airdrop._tcp.local.",
+	"_companion-link._tcp.local.",
+	"_device-info._tcp.local.",
+];
+
+/// Services a scan asks for, to skip responders the user doesn't care about
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScanScope {
+	#[default]
+	All,
+	/// Peers that accept files and links, Companion devices included
+	AirDrop,
+	/// AirPlay and AirTunes receivers
+	AirPlay,
+}
+
+impl ScanScope {
+	pub const ALL: [ScanScope; 3] = [ScanScope::All, ScanScope::AirDrop, ScanScope::AirPlay];
+
+	/// mDNS service types browsed in this scope
+	pub fn service_types(self) -> &'static [&'static str] {
+		match self {
+			ScanScope::All => SERVICE_TYPES,
+			ScanScope::AirDrop => &["_airdrop._tcp.local.", "_companion-link._tcp.local."],
+			ScanScope::AirPlay => &["_airplay._tcp.local.", "_raop._tcp.local."],
+		}
+	}
+}
+
+/// Build a device from a resolved mDNS service
+pub(crate) fn device_from_info(service_type: &str, info: &ServiceInfo) -> Option<DiscoveredDevice> {
+	let addr = info.get_addresses().iter().next()?;
+	Some(DiscoveredDevice {
+		name: info.get_fullname().to_string(),
+		address: IpAddr::V4(*addr),
+		port: info.get_port(),
+		service_type: ServiceType::from_mdns(service_type),
+		txt_records: info.get_properties().iter().map(|prop| {
+			(prop.key().to_string(), prop.val_str().to_string())
+		}).collect(),
+	})
+}
+
+/// Finds a device again just before a send, see [`DeviceDiscovery::resolver`]
+#[derive(Clone)]
+pub struct DeviceResolver {
+	backends: Vec<Arc<dyn DiscoveryBackend>>,
+	ip_mode: IpMode,
+}
+
+impl DeviceResolver {
+	/// Current endpoint of `device`, see [`backend::resolve`]
+	pub async fn resolve(&self, device: &DiscoveredDevice) -> std::result::Result<DiscoveredDevice, backend::DeviceMoved> {
+		let fresh = backend::resolve(&self.backends, device, backend::RESOLVE_TIMEOUT).await?;
+		if self.ip_mode.allows(fresh.address) {
+			Ok(fresh)
+		} else {
+			Err(backend::DeviceMoved(device.name.clone()))
+		}
+	}
 }
 
 #[allow(dead_code)]
@@ -60,7 +312,20 @@ pub struct DeviceDiscovery {
 	mdns: Arc<ServiceDaemon>,
 	devices: Arc<Mutex<HashMap<String, DiscoveredDevice>>>,
 	running: Arc<AtomicBool>,
+	/// Stops the browse loops of the current discovery
+	cancel: std::sync::Mutex<CancelToken>,
 	network_manager: NetworkManager,
+	backends: Vec<Arc<dyn DiscoveryBackend>>,
+	/// Devices at addresses of a disabled IP version are left out
+	ip_mode: IpMode,
+}
+
+impl std::fmt::Debug for DeviceDiscovery {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DeviceDiscovery")
+			.field("backends", &self.backends.iter().map(|b| b.name()).collect::<Vec<_>>())
+			.finish_non_exhaustive()
+	}
 }
 
 impl DeviceDiscovery {
@@ -123,58 +388,75 @@ impl DeviceDiscovery {
 			mdns: Arc::new(mdns),
 			devices: Arc::new(Mutex::new(HashMap::new())),
 			running: Arc::new(AtomicBool::new(false)),
+			cancel: std::sync::Mutex::new(CancelToken::new()),
 			network_manager,
+			backends: vec![Arc::new(MdnsBackend::new()?)],
+			ip_mode: IpMode::Auto,
 		})
 	}
 
+	/// Only report devices reachable over the IP versions allowed by `mode`
+	pub fn with_ip_mode(mut self, mode: IpMode) -> Self {
+		self.ip_mode = mode;
+		self
+	}
+
+	/// Add another source of devices to the scans
+	pub fn with_backend(mut self, backend: Box<dyn DiscoveryBackend>) -> Self {
+		self.backends.push(Arc::from(backend));
+		self
+	}
+
+	/// Scan every backend for the `services` types for `timeout`, or until
+	/// `cancel` fires, and merge the results
+	pub async fn browse(&self, timeout: Duration, services: &[&str], cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+		let mut devices = backend::browse_all(&self.backends, timeout, services, cancel).await;
+		devices.retain(|device| self.ip_mode.allows(device.address));
+		devices
+	}
+
+	/// Finds devices again just before a send. It shares the backends, so
+	/// the discovery lock can be released before the resolve starts.
+	pub fn resolver(&self) -> DeviceResolver {
+		DeviceResolver {
+			backends: self.backends.clone(),
+			ip_mode: self.ip_mode,
+		}
+	}
+
 	pub async fn start_discovery(&self) -> Result<()> {
 		if self.running.load(Ordering::SeqCst) {
 			return Ok(());
 		}
 		self.running.store(true, Ordering::SeqCst);
+		let cancel = CancelToken::new();
+		*self.cancel.lock().unwrap() = cancel.clone();
 
 		info!("Starting device discovery service...");
 		
-		let service_types = [
-			"_airplay._tcp.local.",
-			"_raop._tcp.local.",
-			"_airdrop._tcp.local.",
-			"_companion-link._tcp.local.",
-			"_device-info._tcp.local.",
-		];
-
-		for &service_type in &service_types {
+		for &service_type in SERVICE_TYPES {
 			match self.mdns.browse(service_type) {
 				Ok(receiver) => {
 					let devices = self.devices.clone();
 					let service_type = service_type.to_string();
-					let running = self.running.clone();
+					let cancel = cancel.clone();
 					
 					tokio::spawn(async move {
-						while running.load(Ordering::SeqCst) {
-							match receiver.recv_async().await {
+						loop {
+							// Cancelled before the daemon goes away, so shutting
+							// down doesn't log a receive error for every service
+							let event = tokio::select! {
+								_ = cancel.cancelled() => break,
+								event = receiver.recv_async() => event,
+							};
+							match event {
 								Ok(event) => {
 									if let ServiceEvent::ServiceResolved(info) = event {
-										let addresses = info.get_addresses();
-										if !addresses.is_empty() {
-											if let Some(addr) = addresses.iter().next() {
-												let mut devices = devices.lock().await;
-												let device = DiscoveredDevice {
-													name: info.get_fullname().to_string(),
-													address: IpAddr::V4(*addr),
-													port: info.get_port(),
-													service_type: match service_type.as_str() {
-														"_airplay._tcp.local." => ServiceType::AirPlay,
-														"_raop._tcp.local." => ServiceType::Raop,
-														"_airdrop._tcp.local." => ServiceType::AirDrop,
-														"_companion-link._tcp.local." => ServiceType::Companion,
-														_ => ServiceType::DeviceInfo,
-													},
-													txt_records: info.get_properties().iter().map(|prop| {
-														(prop.key().to_string(), prop.val_str().to_string())
-													}).collect(),
-												};
-												devices.insert(device.name.clone(), device);
+										if let Some(device) = device_from_info(&service_type, &info) {
+											let mut devices = devices.lock().await;
+											let summary = format!("{} at {}:{}", device.name, device.address, device.port);
+											if devices.insert(device.name.clone(), device).is_none() {
+												event_log::info("Discovery", format!("Discovered {}", summary));
 											}
 										}
 									}
@@ -197,8 +479,9 @@ impl DeviceDiscovery {
 
 	}
 
+	/// Stop the browse loops and forget the devices found
 	pub async fn stop_discovery(&self) {
-
+		self.cancel.lock().unwrap().cancel();
 		self.running.store(false, Ordering::SeqCst);
 		self.devices.lock().await.clear();
 	}
@@ -206,6 +489,136 @@ impl DeviceDiscovery {
 
 	pub async fn get_devices(&self) -> Result<Vec<DiscoveredDevice>> {
 		let devices = self.devices.lock().await;
-		Ok(devices.values().cloned().collect())
+		Ok(devices
+			.values()
+			.filter(|device| self.ip_mode.allows(device.address))
+			.cloned()
+			.collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn device(name: &str, last_octet: u8) -> DiscoveredDevice {
+		DiscoveredDevice {
+			name: name.to_string(),
+			address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, last_octet)),
+			port: 8771,
+			service_type: ServiceType::AirDrop,
+			txt_records: HashMap::new(),
+		}
+	}
+
+	#[test]
+	fn test_same_name_devices_select_independently() {
+		let devices = vec![device("iPhone", 100), device("iPhone", 101)];
+		assert_ne!(devices[0].id(), devices[1].id());
+
+		for selected in &devices {
+			let highlighted: Vec<_> = devices
+				.iter()
+				.filter(|device| device.same_device(selected))
+				.collect();
+			assert_eq!(highlighted.len(), 1);
+			assert_eq!(highlighted[0].address, selected.address);
+		}
+	}
+
+	#[test]
+	fn test_companion_devices_use_airdrop_port() {
+		let mut companion = device("iPhone", 100);
+		companion.service_type = ServiceType::Companion;
+		companion.port = 49152;
+		assert!(companion.service_type.accepts_airdrop());
+		assert_eq!(companion.airdrop_addr().unwrap().port(), AIRDROP_HTTPS_PORT);
+
+		let mut airplay = device("Apple TV", 101);
+		airplay.service_type = ServiceType::AirPlay;
+		assert!(!airplay.service_type.accepts_airdrop());
+		assert_eq!(airplay.airdrop_addr(), None);
+
+		let mut ble = device("iPhone", 102);
+		ble.address = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+		ble.port = 0;
+		assert_eq!(ble.airdrop_addr(), None);
+	}
+
+	#[test]
+	fn test_txt_flags_map_to_actions() {
+		let mut phone = device("iPhone", 100);
+		phone.txt_records = HashMap::from([
+			("supports_airdrop".to_string(), "1".to_string()),
+			("supports_url".to_string(), "0".to_string()),
+		]);
+		let capabilities = phone.capabilities();
+		assert!(capabilities.allows(DeviceAction::SendFile));
+		assert!(!capabilities.allows(DeviceAction::SendLink));
+		assert!(!capabilities.allows(DeviceAction::ScreenMirroring));
+
+		// Apple devices only advertise the decimal flags, 0x3fb includes links
+		phone.txt_records = HashMap::from([("flags".to_string(), "1019".to_string())]);
+		assert!(phone.capabilities().allows(DeviceAction::SendLink));
+		phone.txt_records = HashMap::from([("flags".to_string(), "1018".to_string())]);
+		assert!(!phone.capabilities().allows(DeviceAction::SendLink));
+
+		let mut apple_tv = device("Apple TV", 101);
+		apple_tv.service_type = ServiceType::AirPlay;
+		apple_tv.txt_records = HashMap::from([("features".to_string(), "0x5A7FFFF7,0x1E".to_string())]);
+		assert!(apple_tv.capabilities().allows(DeviceAction::ScreenMirroring));
+		assert!(!apple_tv.capabilities().allows(DeviceAction::SendFile));
+		assert!(!apple_tv.capabilities().is_audio_only());
+		// Audio-only receiver
+		apple_tv.txt_records = HashMap::from([("features".to_string(), "0x4A00".to_string())]);
+		assert!(!apple_tv.capabilities().allows(DeviceAction::ScreenMirroring));
+		assert!(apple_tv.capabilities().is_audio_only());
+	}
+
+	#[test]
+	fn test_unknown_service_type_keeps_its_name() {
+		assert_eq!(ServiceType::from_mdns("_airplay._tcp.local."), ServiceType::AirPlay);
+		assert_eq!(ServiceType::from_mdns("_device-info._tcp.local."), ServiceType::DeviceInfo);
+
+		let info = ServiceInfo::new(
+			"_sleep-proxy._udp.local.",
+			"Base Station",
+			"base-station.local.",
+			"192.168.1.1",
+			5353,
+			None,
+		)
+		.unwrap();
+		let device = device_from_info("_sleep-proxy._udp.local.", &info).unwrap();
+		assert_eq!(device.service_type, ServiceType::Other("_sleep-proxy._udp.local.".to_string()));
+		assert_eq!(device.service_type.to_string(), "_sleep-proxy._udp.local.");
+		assert!(!device.service_type.accepts_airdrop());
+		assert_eq!(ServiceType::Companion.to_string(), "Companion");
+	}
+
+	#[test]
+	fn test_details_report_lists_sorted_txt_records() {
+		let mut airdrop = device("iPhone", 100);
+		airdrop.txt_records.insert("supports_url".to_string(), "1".to_string());
+		airdrop.txt_records.insert("flags".to_string(), "0x1".to_string());
+
+		let report = airdrop.details_report();
+		assert!(report.starts_with("Name: iPhone\nService: AirDrop\nAddress: 192.168.1.100:8771\n"), "{}", report);
+		assert!(report.contains("Links: yes\nMirroring: no\n"), "{}", report);
+		assert!(report.ends_with("TXT records:\n  flags=0x1\n  supports_url=1"), "{}", report);
+
+		assert!(device("Mac", 101).details_report().ends_with("TXT records: none"));
+	}
+
+	#[test]
+	fn test_unknown_capabilities_are_allowed() {
+		let ble = device("iPhone", 100);
+		assert_eq!(ble.capabilities(), Capabilities::default());
+		assert!(ble.capabilities().allows(DeviceAction::SendFile));
+		assert!(ble.capabilities().allows(DeviceAction::SendLink));
+
+		let mut apple_tv = device("Apple TV", 101);
+		apple_tv.service_type = ServiceType::AirPlay;
+		assert!(apple_tv.capabilities().allows(DeviceAction::ScreenMirroring));
 	}
 }
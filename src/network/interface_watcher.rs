@@ -0,0 +1,215 @@
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::protocols::airdrop::AirDrop;
+use crate::utils::{event_log, poll_rate, supervisor};
+
+/// How often the interface list is polled
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Poll interval while a change is waiting for the debounce, so it settles promptly
+pub const SETTLING_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a new interface set must stay unchanged before services are re-registered
+pub const DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// Usable IPv4 interfaces as (name, address) pairs
+pub type InterfaceSet = BTreeSet<(String, IpAddr)>;
+
+/// Keep the interfaces mDNS and multicast can use, the same ones `setup_multicast` joins
+pub fn usable_interfaces(interfaces: impl IntoIterator<Item = (String, IpAddr)>) -> InterfaceSet {
+    interfaces
+        .into_iter()
+        .filter(|(_, ip)| match ip {
+            IpAddr::V4(addr) => !addr.is_loopback() && !addr.is_multicast() && !addr.is_link_local(),
+            IpAddr::V6(_) => false,
+        })
+        .collect()
+}
+
+/// Human readable form of an interface set for logs
+pub fn describe(interfaces: &InterfaceSet) -> String {
+    if interfaces.is_empty() {
+        return "none".to_string();
+    }
+    interfaces
+        .iter()
+        .map(|(name, ip)| format!("{} ({})", name, ip))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A settled change of the interface set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceChange {
+    pub old: InterfaceSet,
+    pub new: InterfaceSet,
+}
+
+/// Detects interface changes, ignoring sets that don't stay stable for the debounce period
+#[derive(Debug)]
+pub struct InterfaceWatcher {
+    current: InterfaceSet,
+    pending: Option<(InterfaceSet, Instant)>,
+    debounce: Duration,
+}
+
+impl InterfaceWatcher {
+    pub fn new(initial: InterfaceSet, debounce: Duration) -> Self {
+        Self {
+            current: initial,
+            pending: None,
+            debounce,
+        }
+    }
+
+    /// How long to wait before the next poll
+    pub fn poll_interval(&self) -> Duration {
+        if self.pending.is_some() {
+            SETTLING_POLL_INTERVAL
+        } else {
+            POLL_INTERVAL
+        }
+    }
+
+    /// Feed a fresh interface list; returns the change once it has settled
+    pub fn observe(&mut self, interfaces: InterfaceSet, now: Instant) -> Option<InterfaceChange> {
+        if interfaces == self.current {
+            self.pending = None;
+            return None;
+        }
+
+        match &self.pending {
+            Some((pending, since)) if *pending == interfaces => {
+                if now.duration_since(*since) < self.debounce {
+                    return None;
+                }
+                self.pending = None;
+                let old = std::mem::replace(&mut self.current, interfaces.clone());
+                Some(InterfaceChange { old, new: interfaces })
+            }
+            _ => {
+                // New or still-changing set: restart the debounce timer
+                self.pending = Some((interfaces, now));
+                None
+            }
+        }
+    }
+}
+
+fn list_interfaces() -> InterfaceSet {
+    match local_ip_address::list_afinet_netifas() {
+        Ok(interfaces) => usable_interfaces(interfaces),
+        Err(e) => {
+            warn!("Failed to list network interfaces: {}", e);
+            InterfaceSet::new()
+        }
+    }
+}
+
+/// Poll the interfaces in the background and re-register AirDrop's mDNS
+/// services and multicast memberships whenever they change
+pub fn spawn(airdrop: AirDrop) -> JoinHandle<()> {
+    supervisor::global().spawn("Interface watcher", move || {
+        let airdrop = airdrop.clone();
+        async move {
+            let mut watcher = InterfaceWatcher::new(list_interfaces(), DEBOUNCE);
+
+            loop {
+                tokio::time::sleep(watcher.poll_interval()).await;
+                poll_rate::record("Network interfaces");
+                let Some(change) = watcher.observe(list_interfaces(), Instant::now()) else {
+                    continue;
+                };
+
+                info!(
+                    "Network interfaces changed: [{}] -> [{}]",
+                    describe(&change.old),
+                    describe(&change.new)
+                );
+                event_log::info(
+                    "Network",
+                    format!("Interfaces changed: [{}] -> [{}]", describe(&change.old), describe(&change.new)),
+                );
+
+                match airdrop.refresh_network_services().await {
+                    Ok(true) => info!("Re-registered mDNS services after interface change"),
+                    Ok(false) => debug!("AirDrop not running, nothing to re-register"),
+                    Err(e) => {
+                        warn!("Failed to re-register services after interface change: {}", e);
+                        event_log::error("Network", format!("Failed to re-register services: {}", e));
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn set(interfaces: &[(&str, [u8; 4])]) -> InterfaceSet {
+        usable_interfaces(
+            interfaces
+                .iter()
+                .map(|(name, ip)| (name.to_string(), IpAddr::V4(Ipv4Addr::from(*ip)))),
+        )
+    }
+
+    #[test]
+    fn test_ignores_unusable_interfaces() {
+        let interfaces = set(&[
+            ("lo", [127, 0, 0, 1]),
+            ("apipa", [169, 254, 3, 4]),
+            ("wlan0", [192, 168, 1, 20]),
+        ]);
+        assert_eq!(interfaces, set(&[("wlan0", [192, 168, 1, 20])]));
+    }
+
+    #[test]
+    fn test_change_reported_after_debounce() {
+        let start = Instant::now();
+        let wifi = set(&[("wlan0", [192, 168, 1, 20])]);
+        let ethernet = set(&[("wlan0", [192, 168, 1, 20]), ("eth0", [10, 0, 0, 5])]);
+        let mut watcher = InterfaceWatcher::new(wifi.clone(), Duration::from_secs(3));
+
+        assert_eq!(watcher.observe(wifi.clone(), start), None);
+        assert_eq!(watcher.poll_interval(), POLL_INTERVAL);
+        assert_eq!(watcher.observe(ethernet.clone(), start), None);
+        // Polled faster until the new set settles
+        assert_eq!(watcher.poll_interval(), SETTLING_POLL_INTERVAL);
+        assert_eq!(watcher.observe(ethernet.clone(), start + Duration::from_secs(1)), None);
+
+        let change = watcher
+            .observe(ethernet.clone(), start + Duration::from_secs(3))
+            .unwrap();
+        assert_eq!(change, InterfaceChange { old: wifi, new: ethernet.clone() });
+        assert_eq!(watcher.poll_interval(), POLL_INTERVAL);
+
+        // Settled: the same set is no longer a change
+        assert_eq!(watcher.observe(ethernet, start + Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn test_flapping_interfaces_are_debounced() {
+        let start = Instant::now();
+        let wifi = set(&[("wlan0", [192, 168, 1, 20])]);
+        let roaming = set(&[("wlan0", [192, 168, 7, 9])]);
+        let mut watcher = InterfaceWatcher::new(wifi.clone(), Duration::from_secs(3));
+
+        // Switching away and back before the debounce expires reports nothing
+        assert_eq!(watcher.observe(roaming.clone(), start), None);
+        assert_eq!(watcher.observe(wifi.clone(), start + Duration::from_secs(2)), None);
+        assert_eq!(watcher.observe(roaming.clone(), start + Duration::from_secs(4)), None);
+
+        // The timer restarted when the set changed again
+        assert_eq!(watcher.observe(roaming.clone(), start + Duration::from_secs(6)), None);
+        assert!(watcher.observe(roaming, start + Duration::from_secs(7)).is_some());
+    }
+}
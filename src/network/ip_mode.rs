@@ -0,0 +1,169 @@
+//! IP versions used by the local services.
+//!
+//! Some networks handle dual-stack badly: a peer is found over one family
+//! and the transfer goes out over the other, or an IPv6 listener steals
+//! connections that never complete. The mode restricts which listeners are
+//! bound, which multicast groups are joined and which peers are contacted.
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// IP versions AirWin listens on and connects over
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpMode {
+    /// IPv4, plus IPv6 where the host supports it
+    #[default]
+    Auto,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl IpMode {
+    pub fn uses_ipv4(self) -> bool {
+        self != IpMode::Ipv6Only
+    }
+
+    pub fn uses_ipv6(self) -> bool {
+        self != IpMode::Ipv4Only
+    }
+
+    /// Whether a peer at `ip` may be contacted
+    pub fn allows(self, ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(_) => self.uses_ipv4(),
+            IpAddr::V6(_) => self.uses_ipv6(),
+        }
+    }
+
+    /// Address a single-listener server binds on `port`
+    pub fn primary_addr(self, port: u16) -> SocketAddr {
+        match self {
+            IpMode::Ipv6Only => SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+            IpMode::Auto | IpMode::Ipv4Only => SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+        }
+    }
+
+    /// Addresses to listen on for `port`, primary first. Only the primary
+    /// one has to bind: in auto mode IPv6 is skipped if the host lacks it.
+    pub fn listen_addrs(self, port: u16) -> Vec<SocketAddr> {
+        match self {
+            IpMode::Auto => vec![
+                SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+                SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+            ],
+            IpMode::Ipv4Only | IpMode::Ipv6Only => vec![self.primary_addr(port)],
+        }
+    }
+
+    /// Bind every listener for `port` with `bind`. Fails if the primary
+    /// address can't be bound; other failures are returned alongside.
+    pub fn bind_listeners<T>(
+        self,
+        port: u16,
+        mut bind: impl FnMut(SocketAddr) -> io::Result<T>,
+    ) -> io::Result<BoundListeners<T>> {
+        let mut addrs = self.listen_addrs(port).into_iter();
+        let primary = addrs.next().expect("every mode listens on one address");
+        let primary = bind(primary)?;
+        let (mut secondary, mut skipped) = (Vec::new(), Vec::new());
+        for addr in addrs {
+            match bind(addr) {
+                Ok(listener) => secondary.push(listener),
+                Err(e) => skipped.push((addr, e)),
+            }
+        }
+        Ok(BoundListeners { primary, secondary, skipped })
+    }
+}
+
+/// Listeners bound by [`IpMode::bind_listeners`]
+#[derive(Debug)]
+pub struct BoundListeners<T> {
+    pub primary: T,
+    pub secondary: Vec<T>,
+    /// Optional addresses that couldn't be bound, with the error
+    pub skipped: Vec<(SocketAddr, io::Error)>,
+}
+
+/// Pending connections a listener queues by default
+pub const DEFAULT_BACKLOG: u32 = 128;
+
+/// Bind a TCP listener on `addr`. IPv6 listeners are IPv6-only, so they
+/// can share the port with the IPv4 one.
+pub fn bind_tcp(addr: SocketAddr) -> io::Result<tokio::net::TcpListener> {
+    bind_tcp_with_backlog(addr, DEFAULT_BACKLOG)
+}
+
+/// [`bind_tcp`] queueing up to `backlog` pending connections
+pub fn bind_tcp_with_backlog(addr: SocketAddr, backlog: u32) -> io::Result<tokio::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+        socket.set_reuse_address(true)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(backlog.min(i32::MAX as u32) as i32)?;
+    socket.set_nonblocking(true)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the attempted addresses, failing the IPv6 ones when asked
+    fn attempts(mode: IpMode, ipv6_fails: bool) -> (io::Result<BoundListeners<SocketAddr>>, Vec<SocketAddr>) {
+        let mut attempted = Vec::new();
+        let result = mode.bind_listeners(7000, |addr| {
+            attempted.push(addr);
+            if ipv6_fails && addr.is_ipv6() {
+                Err(io::Error::new(io::ErrorKind::AddrNotAvailable, "no IPv6"))
+            } else {
+                Ok(addr)
+            }
+        });
+        (result, attempted)
+    }
+
+    #[test]
+    fn test_each_mode_binds_its_sockets() {
+        let v4 = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 7000));
+        let v6 = SocketAddr::from((Ipv6Addr::UNSPECIFIED, 7000));
+
+        let (bound, attempted) = attempts(IpMode::Auto, false);
+        assert_eq!(attempted, vec![v4, v6]);
+        let bound = bound.unwrap();
+        assert_eq!((bound.primary, bound.secondary), (v4, vec![v6]));
+
+        let (bound, attempted) = attempts(IpMode::Ipv4Only, false);
+        assert_eq!(attempted, vec![v4]);
+        assert!(bound.unwrap().secondary.is_empty());
+
+        let (bound, attempted) = attempts(IpMode::Ipv6Only, false);
+        assert_eq!(attempted, vec![v6]);
+        assert_eq!(bound.unwrap().primary, v6);
+    }
+
+    #[test]
+    fn test_missing_ipv6_only_fails_ipv6_mode() {
+        let (bound, _) = attempts(IpMode::Auto, true);
+        let bound = bound.unwrap();
+        assert!(bound.secondary.is_empty());
+        assert_eq!(bound.skipped.len(), 1);
+
+        let (bound, _) = attempts(IpMode::Ipv6Only, true);
+        assert_eq!(bound.unwrap_err().kind(), io::ErrorKind::AddrNotAvailable);
+    }
+
+    #[test]
+    fn test_peers_are_filtered_by_family() {
+        let v4: IpAddr = Ipv4Addr::new(192, 168, 1, 20).into();
+        let v6: IpAddr = "fe80::1".parse().unwrap();
+        assert!(IpMode::Auto.allows(v4) && IpMode::Auto.allows(v6));
+        assert!(IpMode::Ipv4Only.allows(v4) && !IpMode::Ipv4Only.allows(v6));
+        assert!(!IpMode::Ipv6Only.allows(v4) && IpMode::Ipv6Only.allows(v6));
+    }
+}
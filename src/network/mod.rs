@@ -1,6 +1,14 @@
-mod interface;
+pub(crate) mod interface;
 
+pub mod backend;
+pub mod capability_matrix;
+pub mod connection_quality;
+pub mod control_api;
+pub mod device_cache;
 pub mod discovery;
-pub use discovery::{DiscoveredDevice, ServiceType};
+pub use discovery::{DeviceAction, DiscoveredDevice, ScanScope, ServiceType};
 
 pub mod ble;
+
+pub mod interface_watcher;
+pub mod ip_mode;
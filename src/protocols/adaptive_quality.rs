@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Number of frame write samples averaged before taking a decision
+const SAMPLE_WINDOW: usize = 10;
+
+/// Writes slower than this share of the frame budget mean the link is backing up
+const BACKLOG_RATIO: f64 = 0.8;
+
+/// Writes faster than this share of the frame budget leave room to raise quality
+const HEADROOM_RATIO: f64 = 0.4;
+
+const QUALITY_STEP_DOWN: u8 = 10;
+const QUALITY_STEP_UP: u8 = 5;
+const FPS_STEP: u32 = 5;
+
+/// Quality tier selected by the user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityTier {
+    Low,
+    Medium,
+    High,
+    #[default]
+    Auto,
+}
+
+/// Range the adaptive controller may move within for a tier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QualityBounds {
+    pub min_quality: u8,
+    pub max_quality: u8,
+    pub min_fps: u32,
+    pub max_fps: u32,
+}
+
+impl QualityTier {
+    pub fn bounds(self) -> QualityBounds {
+        match self {
+            QualityTier::Low => QualityBounds { min_quality: 30, max_quality: 50, min_fps: 15, max_fps: 30 },
+            QualityTier::Medium => QualityBounds { min_quality: 50, max_quality: 75, min_fps: 24, max_fps: 45 },
+            QualityTier::High => QualityBounds { min_quality: 75, max_quality: 95, min_fps: 30, max_fps: 60 },
+            QualityTier::Auto => QualityBounds { min_quality: 30, max_quality: 95, min_fps: 15, max_fps: 60 },
+        }
+    }
+
+    /// Longest side of streamed frames, `None` for the native resolution
+    pub fn max_dimension(self) -> Option<u32> {
+        match self {
+            QualityTier::Low => Some(1280),
+            QualityTier::Medium | QualityTier::Auto => Some(1920),
+            QualityTier::High => None,
+        }
+    }
+}
+
+/// Adjusts JPEG quality and frame rate from measured frame write times.
+///
+/// When writes back up, quality is lowered first and the frame rate only once
+/// quality hits the tier minimum. With headroom the frame rate is restored
+/// first, then quality.
+#[derive(Clone, Debug)]
+pub struct AdaptiveQuality {
+    tier: QualityTier,
+    bounds: QualityBounds,
+    quality: u8,
+    fps: u32,
+    samples: VecDeque<Duration>,
+}
+
+impl AdaptiveQuality {
+    pub fn new(tier: QualityTier) -> Self {
+        let bounds = tier.bounds();
+        Self {
+            tier,
+            bounds,
+            quality: bounds.max_quality,
+            fps: bounds.max_fps,
+            samples: VecDeque::with_capacity(SAMPLE_WINDOW),
+        }
+    }
+
+    /// Switch tier, restarting from its best settings
+    pub fn set_tier(&mut self, tier: QualityTier) {
+        *self = Self::new(tier);
+    }
+
+    pub fn tier(&self) -> QualityTier {
+        self.tier
+    }
+
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// Record how long a frame write took. Returns true if quality or fps changed.
+    pub fn record(&mut self, write_time: Duration) -> bool {
+        if self.samples.len() == SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(write_time);
+
+        if self.samples.len() < SAMPLE_WINDOW {
+            return false;
+        }
+
+        let average = self.samples.iter().sum::<Duration>().as_secs_f64() / self.samples.len() as f64;
+        let budget = 1.0 / self.fps as f64;
+
+        let changed = if average > budget * BACKLOG_RATIO {
+            self.step_down()
+        } else if average < budget * HEADROOM_RATIO {
+            self.step_up()
+        } else {
+            false
+        };
+
+        // Judge the new settings on fresh samples only
+        if changed {
+            self.samples.clear();
+        }
+        changed
+    }
+
+    fn step_down(&mut self) -> bool {
+        if self.quality > self.bounds.min_quality {
+            self.quality = self.quality.saturating_sub(QUALITY_STEP_DOWN).max(self.bounds.min_quality);
+            true
+        } else if self.fps > self.bounds.min_fps {
+            self.fps = self.fps.saturating_sub(FPS_STEP).max(self.bounds.min_fps);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn step_up(&mut self) -> bool {
+        if self.fps < self.bounds.max_fps {
+            self.fps = (self.fps + FPS_STEP).min(self.bounds.max_fps);
+            true
+        } else if self.quality < self.bounds.max_quality {
+            self.quality = (self.quality + QUALITY_STEP_UP).min(self.bounds.max_quality);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(controller: &mut AdaptiveQuality, millis: u64) {
+        for _ in 0..SAMPLE_WINDOW {
+            controller.record(Duration::from_millis(millis));
+        }
+    }
+
+    #[test]
+    fn test_backlog_lowers_quality_then_fps() {
+        let mut controller = AdaptiveQuality::new(QualityTier::High);
+        assert_eq!((controller.quality(), controller.fps()), (95, 60));
+
+        // 60 fps leaves ~16ms per frame; 30ms writes are backing up
+        feed(&mut controller, 30);
+        assert_eq!((controller.quality(), controller.fps()), (85, 60));
+
+        feed(&mut controller, 30);
+        assert_eq!(controller.quality(), 75);
+
+        // Quality is at the tier minimum, so the frame rate drops next
+        feed(&mut controller, 30);
+        assert_eq!((controller.quality(), controller.fps()), (75, 55));
+    }
+
+    #[test]
+    fn test_headroom_restores_fps_then_quality() {
+        let mut controller = AdaptiveQuality::new(QualityTier::Medium);
+        for _ in 0..5 {
+            feed(&mut controller, 100);
+        }
+        assert_eq!(controller.quality(), 50);
+        assert!(controller.fps() < 45);
+
+        while controller.fps() < 45 {
+            feed(&mut controller, 1);
+        }
+        assert_eq!(controller.quality(), 50);
+
+        feed(&mut controller, 1);
+        assert_eq!((controller.quality(), controller.fps()), (55, 45));
+    }
+
+    #[test]
+    fn test_stays_within_tier_bounds() {
+        let mut controller = AdaptiveQuality::new(QualityTier::Low);
+        for _ in 0..50 {
+            feed(&mut controller, 500);
+        }
+        assert_eq!((controller.quality(), controller.fps()), (30, 15));
+
+        for _ in 0..50 {
+            feed(&mut controller, 0);
+        }
+        assert_eq!((controller.quality(), controller.fps()), (50, 30));
+    }
+
+    #[test]
+    fn test_steady_latency_keeps_settings() {
+        let mut controller = AdaptiveQuality::new(QualityTier::Auto);
+        // 10ms is between 40% and 80% of the ~16ms budget at 60 fps
+        feed(&mut controller, 10);
+        assert_eq!((controller.quality(), controller.fps()), (95, 60));
+    }
+}
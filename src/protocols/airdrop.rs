@@ -1,488 +1,3268 @@
 use anyhow::{Result, Context, anyhow};
 use std::path::PathBuf;
+use crate::network::backend::CancelToken;
+use crate::network::discovery::DeviceDiscovery;
+use crate::network::{DeviceAction, DiscoveredDevice, ScanScope};
+use crate::network::ip_mode::{self, IpMode};
+use crate::utils::{archive, event_log, mime_type, supervisor, AirWinError};
+use crate::utils::clock::{self, SharedClock};
+use crate::utils::supervisor::Backoff;
+use chrono::{DateTime, Utc};
+use crate::utils::config::{MulticastSettings, Ports};
+use crate::utils::privileges::AIRDROP_FALLBACK_PORT;
+use std::collections::HashSet;
 use tokio::fs::File;
-use tokio::net::{TcpStream, TcpListener, UdpSocket};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::Mutex;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::JoinHandle;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 use serde_json;
 use uuid::Uuid;
-use tracing::{info, warn, error};
-use mdns_sd::{ServiceDaemon, ServiceInfo};
+use tracing::{debug, info, warn, error};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
 
-use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType};
-use tokio_native_tls::{TlsAcceptor, native_tls, TlsConnector};
-use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use std::net::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr};
 use socket2::{Socket, Domain, Type, Protocol};
-use super::apple_records::AppleRecords;
-use super::http_server::AirDropHttpServer;
-use mime_guess;
+use super::apple_records::{self, AppleRecords};
+use super::awdl::AwdlAvailability;
+use super::file_conflict::{ConflictPrompt, ConflictResolver, FileConflictPolicy};
+use super::file_name;
+use super::peer_filter::PeerFilter;
+use super::send_progress::{ProgressTracker, SendProgress};
+use super::visibility::{ReceiveGate, TransferPrompt, TrustedDevice, VisibilitySettings};
+use super::capabilities::{self, Capabilities};
+use super::compression::{self, Algorithm, CompressionMode};
+use super::cert_pins::{self, PinCheck, PinStore};
+use super::connection_pool::{self, AfterTransfer, Connection, ConnectionPool};
+use super::http_server::{AirDropHttpServer, ConnectionLimits};
+use super::tls::{self, Connector, TlsBackend};
+use super::transfer_protocol::{Payload, TransferProtocol};
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum AirDropStatus {
-    Idle,
-    Connecting,
-    Connected,
-    Failed(String),
-    Transferring(f32),  // Progress percentage
+/// Retries of the mDNS registration before falling back to send-only mode
+const MDNS_RETRY: Backoff = Backoff {
+    initial: Duration::from_millis(500),
+    max: Duration::from_secs(4),
+    reset_after: Duration::ZERO,
+    max_restarts: Some(3),
+};
+
+/// Retries of a connection the peer refused, for a peer that is busy or
+/// just restarting its receiver
+const CONNECT_RETRY: Backoff = Backoff {
+    initial: Duration::from_millis(500),
+    max: Duration::from_secs(2),
+    reset_after: Duration::ZERO,
+    max_restarts: Some(2),
+};
+
+/// Creates the daemon the services are registered with, replaced in tests
+type DaemonFactory = fn() -> mdns_sd::Result<ServiceDaemon>;
+
+/// The part of the mDNS daemon that advertises services, mocked in tests
+trait MdnsResponder: Send + Sync {
+    fn register(&self, service: ServiceInfo) -> mdns_sd::Result<()>;
+    /// Send goodbye packets for the service with this full name
+    fn unregister(&self, fullname: &str) -> mdns_sd::Result<()>;
+    fn shutdown(&self) -> mdns_sd::Result<()>;
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FileTransfer {
-    id: String,
-    name: String,
-    size: u64,
-    mime_type: String,
+impl MdnsResponder for ServiceDaemon {
+    fn register(&self, service: ServiceInfo) -> mdns_sd::Result<()> {
+        ServiceDaemon::register(self, service)
+    }
+
+    fn unregister(&self, fullname: &str) -> mdns_sd::Result<()> {
+        // The daemon handles commands in order, so the goodbyes go out
+        // before a shutdown sent right after
+        ServiceDaemon::unregister(self, fullname).map(|_| ())
+    }
+
+    fn shutdown(&self) -> mdns_sd::Result<()> {
+        ServiceDaemon::shutdown(self)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AirDropHandshake {
-    sender: String,
-    receiver: String,
-    files: Vec<FileTransfer>,
+/// Services registered on a daemon
+struct Advertisement {
+    daemon: Box<dyn MdnsResponder>,
+    /// Full names of the services, to unregister them
+    services: Vec<String>,
 }
 
-#[derive(Clone)]
-pub struct AirDrop {
-    current_file: Arc<Mutex<Option<PathBuf>>>,
-    transfer_progress: Arc<Mutex<f32>>,
-    connection: Arc<Mutex<Option<TcpStream>>>,
-    mdns: Arc<Mutex<Option<ServiceDaemon>>>,
-    udp_socket: Arc<Mutex<Option<UdpSocket>>>,
-    http_server: Arc<Mutex<Option<AirDropHttpServer>>>,
-    pub status: Arc<Mutex<AirDropStatus>>,
+/// State of the connection to a peer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Idle,
+    Connecting,
+    Connected,
 }
 
+/// A transfer in progress
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransferState {
+    /// Progress percentage (0.0-100.0)
+    pub progress: f32,
+    pub started: DateTime<Utc>,
+}
 
-impl AirDrop {
-    pub fn new() -> Self {
-        Self {
-            current_file: Arc::new(Mutex::new(None)),
-            transfer_progress: Arc::new(Mutex::new(0.0)),
-            connection: Arc::new(Mutex::new(None)),
-            mdns: Arc::new(Mutex::new(None)),
-            udp_socket: Arc::new(Mutex::new(None)),
-            http_server: Arc::new(Mutex::new(None)),
-            status: Arc::new(Mutex::new(AirDropStatus::Idle)),
+impl TransferState {
+    /// Time left at the average speed so far, unknown until some progress is made
+    pub fn remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        if self.progress <= 0.0 {
+            return None;
         }
+        let elapsed = (now - self.started).to_std().ok()?;
+        let left = (100.0 - self.progress).max(0.0) / self.progress;
+        Some(elapsed.mul_f32(left))
     }
+}
 
-    pub async fn send_file_to(&self, addr: SocketAddr, file_path: PathBuf) -> Result<()> {
-        *self.status.lock().await = AirDropStatus::Connecting;
-
-        let file = File::open(&file_path)
-            .await
-            .context("Failed to open file")?;
-
-        let metadata = file.metadata().await?;
-        let transfer = FileTransfer {
-            id: Uuid::new_v4().to_string(),
-            name: file_path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
-            size: metadata.len(),
-            mime_type: mime_guess::from_path(&file_path)
-                .first_or_octet_stream()
-                .to_string(),
-        };
-
-        // Generate certificate for TLS
-        let (identity, _) = Self::generate_certificate().await?;
-        let connector = native_tls::TlsConnector::builder()
-            .identity(identity)
-            .build()?;
-        let connector = TlsConnector::from(connector);
+/// Connection and transfer state, tracked separately so that finishing a
+/// transfer doesn't pretend a connection is still open and vice versa
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AirDropStatus {
+    pub connection: ConnectionState,
+    pub transfer: Option<TransferState>,
+    /// Last error, cleared when a new connection or transfer starts
+    pub error: Option<String>,
+}
 
-        // Establish TCP connection to target peer
-        let stream = TcpStream::connect(addr).await?;
-        *self.status.lock().await = AirDropStatus::Connected;
+impl AirDropStatus {
+    pub fn connecting(&mut self) {
+        self.connection = ConnectionState::Connecting;
+        self.error = None;
+    }
 
-        // Perform TLS handshake; server name must match CN used by server cert
-        let mut tls_stream = connector.connect("AirWin", stream).await?;
+    pub fn connected(&mut self) {
+        self.connection = ConnectionState::Connected;
+    }
 
-        // Send a simple JSON handshake
-        let handshake = AirDropHandshake {
-            sender: "AirWin".to_string(),
-            receiver: "AirWin".to_string(),
-            files: vec![transfer],
-        };
+    pub fn start_transfer(&mut self, now: DateTime<Utc>) {
+        self.transfer = Some(TransferState { progress: 0.0, started: now });
+        self.error = None;
+    }
 
-        let handshake_json = serde_json::to_string(&handshake)?;
-        tls_stream.write_all(handshake_json.as_bytes()).await?;
-        tls_stream.write_all(b"\n\n").await?;
+    /// Update the progress of the current transfer, if any
+    pub fn set_progress(&mut self, progress: f32) {
+        if let Some(transfer) = self.transfer.as_mut() {
+            transfer.progress = progress;
+        }
+    }
 
-        // Stream file contents
-        let mut file = File::open(&file_path).await?;
-        let mut buffer = vec![0; 8192];
-        let mut sent = 0u64;
+    pub fn finish_transfer(&mut self) {
+        self.transfer = None;
+    }
 
-        *self.status.lock().await = AirDropStatus::Transferring(0.0);
+    pub fn disconnected(&mut self) {
+        self.connection = ConnectionState::Idle;
+        self.transfer = None;
+    }
 
-        loop {
-            let n = file.read(&mut buffer).await?;
-            if n == 0 { break; }
-            tls_stream.write_all(&buffer[..n]).await?;
-            sent += n as u64;
-            let progress = (sent as f32 / metadata.len() as f32) * 100.0;
-            *self.transfer_progress.lock().await = progress;
-            *self.status.lock().await = AirDropStatus::Transferring(progress);
+    /// The connection kept after a send was closed. A send or a receive
+    /// started since then keeps its state.
+    pub fn kept_connection_closed(&mut self) {
+        if self.connection == ConnectionState::Connected && self.transfer.is_none() {
+            self.connection = ConnectionState::Idle;
         }
+    }
 
-        *self.status.lock().await = AirDropStatus::Connected;
-        *self.current_file.lock().await = Some(file_path);
-        Ok(())
+    pub fn fail(&mut self, error: impl Into<String>) {
+        self.disconnected();
+        self.error = Some(error.into());
     }
-    
 
-    pub async fn get_status(&self) -> AirDropStatus {
-        self.status.lock().await.clone()
+    pub fn progress(&self) -> Option<f32> {
+        self.transfer.map(|transfer| transfer.progress)
     }
 
-    async fn setup_multicast() -> Result<UdpSocket> {
-        // Create socket with socket2 for more control
-        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
-        
-        // Set socket options
-        socket.set_reuse_address(true)?;
-        socket.set_multicast_loop_v4(true)?;
-        socket.set_multicast_ttl_v4(255)?;
-        socket.set_broadcast(true)?;
+    /// Estimated time left for the current transfer
+    pub fn remaining(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.transfer?.remaining(now)
+    }
 
-        
-        // Bind to mDNS port
-        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 7000);  // Changed to 7000
-        socket.bind(&addr.into())?;
-        
-        // Join multicast group on all interfaces
-        let multicast_addr: Ipv4Addr = "224.0.0.251".parse()?;
-        let interfaces = local_ip_address::list_afinet_netifas()?;
-        
-        for (name, ip) in interfaces {
-            if let IpAddr::V4(interface_addr) = ip {
-                // Skip loopback, multicast, and link-local addresses (169.254.x.x)
-                if !ip.is_loopback() && !ip.is_multicast() && !interface_addr.is_link_local() {  
-                    info!("Joining multicast group on interface {} ({})", name, interface_addr);
-                    if let Err(e) = socket.join_multicast_v4(&multicast_addr, &interface_addr) {
-                        warn!("Failed to join multicast on {}: {}", name, e);
-                    }
-                }
-            }
-        }
-        
-        // Convert to tokio UdpSocket
-        let std_socket: std::net::UdpSocket = socket.into();
-        std_socket.set_nonblocking(true)?;
-        Ok(UdpSocket::from_std(std_socket)?)
+    /// Whether a new send can start now
+    pub fn is_ready(&self) -> bool {
+        self.connection != ConnectionState::Connecting && self.transfer.is_none()
     }
+}
 
+/// Capacity of the inbound event channel. Events published while the
+/// channel is full are dropped with a warning instead of overwriting older ones.
+pub const INCOMING_EVENTS_CAPACITY: usize = 32;
 
+/// Standard AirDrop HTTPS port
+pub const AIRDROP_HTTPS_PORT: u16 = 8771;
 
-    async fn register_mdns_services(&self) -> Result<()> {
-        let mdns = ServiceDaemon::new().map_err(|e| anyhow!("Failed to initialize mDNS: {}", e))?;
-        
-        // Use Apple-compatible TXT records
-        let airdrop_properties = AppleRecords::create_airdrop_txt_records()?;
-        let companion_properties = AppleRecords::create_companion_txt_records()?;
-        let device_info_properties = AppleRecords::create_device_info_txt_records()?;
-        
-        let hostname = hostname::get()?.to_string_lossy().to_string();
-        
-        // Register AirDrop TCP service on standard port
-        let airdrop_tcp_service = ServiceInfo::new(
-            "_airdrop._tcp.local.",
-            &hostname,
-            "local.",
-            "",
-            8771, // Standard AirDrop port
-            Some(airdrop_properties.clone())
-        )?;
+/// Port advertised for the Companion Link service
+pub const COMPANION_LINK_PORT: u16 = 7001;
 
-        // Register AirDrop UDP service
-        let airdrop_udp_service = ServiceInfo::new(
-            "_airdrop._udp.local.",
-            &hostname,
-            "local.",
-            "",
-            8771, // Standard AirDrop port
-            Some(airdrop_properties)
-        )?;
+/// Port advertised for the Device Info service
+pub const DEVICE_INFO_PORT: u16 = 7002;
 
-        // Register Companion Link service (for device pairing)
-        let companion_service = ServiceInfo::new(
-            "_companion-link._tcp.local.",
-            &hostname,
-            "local.",
-            "",
-            7001,
-            Some(companion_properties)
-        )?;
+/// Largest handshake accepted from a peer before the connection is dropped
+pub const MAX_HANDSHAKE_SIZE: usize = 64 * 1024;
 
-        // Register Device Info service
-        let device_info_service = ServiceInfo::new(
-            "_device-info._tcp.local.",
-            &hostname,
-            "local.",
-            "",
-            7002,
-            Some(device_info_properties)
-        )?;
+/// How long a peer has to send the whole handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
-        // Register all services
-        mdns.register(airdrop_tcp_service)
-            .map_err(|e| anyhow!("Failed to register AirDrop TCP service: {}", e))?;
-        mdns.register(airdrop_udp_service)
-            .map_err(|e| anyhow!("Failed to register AirDrop UDP service: {}", e))?;
-        mdns.register(companion_service)
-            .map_err(|e| anyhow!("Failed to register Companion Link service: {}", e))?;
-        mdns.register(device_info_service)
-            .map_err(|e| anyhow!("Failed to register Device Info service: {}", e))?;
+/// Default limit for a single incoming file
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
 
-        info!("Successfully registered Apple-compatible mDNS services");
-        *self.mdns.lock().await = Some(mdns);
+/// Longest a connected peer may stay silent before the connection is closed
+pub const READ_TIMEOUT: Duration = Duration::from_secs(30);
 
-        // Setup UDP multicast with explicit binding to all interfaces
-        let socket = Self::setup_multicast().await?;
-        *self.udp_socket.lock().await = Some(socket);
+/// Idle time before the OS sends the first keepalive probe, and the time between probes
+const KEEPALIVE_TIME: Duration = Duration::from_secs(30);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
 
-        Ok(())
-    }
+/// Pause after a failed accept, such as when the process runs out of file
+/// descriptors, before an accept loop tries again
+pub(crate) const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(500);
 
-    async fn generate_certificate() -> Result<(native_tls::Identity, String)> {
-        info!("Generating new TLS certificate...");
-        let mut params = CertificateParams::new(vec!["AirWin".to_string()]);
-        params.distinguished_name = DistinguishedName::new();
-        params.distinguished_name.push(DnType::CommonName, "AirWin");
-        params.distinguished_name.push(DnType::OrganizationName, "AirWin");
-        params.distinguished_name.push(DnType::CountryName, "US");
+/// How long stopping waits for an accept loop to end before aborting it
+const ACCEPT_LOOP_STOP_TIMEOUT: Duration = Duration::from_secs(2);
 
-        let cert = Certificate::from_params(params)?;
-        // Keep a PEM string for optional display/diagnostics
-        let cert_pem = cert.serialize_pem()?;
-        // Use DER for native-tls on Windows when constructing an Identity from PKCS#8
-        let cert_der = cert.serialize_der()?;
-        let key_der = cert.serialize_private_key_der();
+/// Bytes of a malformed handshake quoted in the error
+const HANDSHAKE_PREVIEW_LEN: usize = 64;
 
-        let identity = native_tls::Identity::from_pkcs8(&cert_der, &key_der)?;
+/// Default size of the chunks files are read from and written to the connection in
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 
-        Ok((identity, cert_pem))
-    }
+/// Bounds of a configured chunk size
+pub const MIN_CHUNK_SIZE: usize = 4 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
 
-    async fn handle_connection(stream: TcpStream, addr: SocketAddr) -> Result<()> {
-        info!("Handling new connection from {}", addr);
-        
-        // Generate or load certificate
-        let (identity, _) = Self::generate_certificate().await?;
-        let acceptor = TlsAcceptor::from(native_tls::TlsAcceptor::new(identity)?);
+/// Largest payload a loopback benchmark may generate, in MB
+pub const MAX_BENCHMARK_MB: u64 = 4096;
 
-        let mut stream = acceptor.accept(stream).await?;
+/// How long the benchmark waits for the receiver to report the file once
+/// the sender is done
+const BENCHMARK_RECEIVE_TIMEOUT: Duration = Duration::from_secs(30);
 
-        // Read handshake
+/// Buffer used to transfer a `file_size`-byte file: the configured size,
+/// kept within bounds, but no larger than the file itself
+fn chunk_size_for(configured: usize, file_size: u64) -> usize {
+    let chunk = configured.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+    usize::try_from(file_size).map_or(chunk, |size| chunk.min(size.max(1)))
+}
+
+/// Why a peer's handshake was rejected
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error("Handshake larger than {limit} bytes")]
+    TooLarge { limit: usize },
+    #[error("No complete handshake within {0:?}")]
+    Timeout(Duration),
+    #[error("Connection closed before the handshake was complete")]
+    Closed,
+    #[error("{name} is {size} bytes, more than the {limit} bytes allowed")]
+    FileTooLarge { name: String, size: u64, limit: u64 },
+    #[error(
+        "Handshake is not AirWin JSON ({source}), starts with {preview}. \
+         The peer may be an Apple device expecting the plist protocol, which AirWin doesn't speak yet"
+    )]
+    NotJson { preview: String, source: serde_json::Error },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Read a handshake terminated by a blank line, bounded in size and time
+pub(crate) async fn read_handshake<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    max_size: usize,
+    timeout: Duration,
+) -> std::result::Result<Vec<u8>, HandshakeError> {
+    let read = async {
         let mut buffer = Vec::new();
         let mut temp_buf = [0u8; 1024];
-        
+
         loop {
             let n = stream.read(&mut temp_buf).await?;
-            if n == 0 { break; }
+            if n == 0 {
+                return Err(HandshakeError::Closed);
+            }
+
+            // Only the new bytes and the one before them can complete the delimiter
+            let scan_from = buffer.len().saturating_sub(1);
             buffer.extend_from_slice(&temp_buf[..n]);
-            if buffer.windows(2).any(|w| w == b"\n\n") {
-                break;
+            if buffer[scan_from..].windows(2).any(|w| w == b"\n\n") {
+                return Ok(buffer);
+            }
+            if buffer.len() > max_size {
+                return Err(HandshakeError::TooLarge { limit: max_size });
             }
         }
+    };
 
-        let handshake: AirDropHandshake = serde_json::from_slice(&buffer)?;
-        info!("Received handshake from {}: {:?}", addr, handshake);
+    tokio::time::timeout(timeout, read)
+        .await
+        .unwrap_or(Err(HandshakeError::Timeout(timeout)))
+}
 
-        // Accept the transfer
-        let response = serde_json::json!({
-            "status": "accept",
-            "receiver": handshake.receiver,
-        });
-        
-        stream.write_all(serde_json::to_string(&response)?.as_bytes()).await?;
-        stream.write_all(b"\n\n").await?;
+/// Parse a JSON handshake, quoting the start of anything else in the error
+fn parse_handshake(buffer: &[u8]) -> std::result::Result<AirDropHandshake, HandshakeError> {
+    serde_json::from_slice(buffer).map_err(|source| HandshakeError::NotJson {
+        preview: preview_bytes(buffer, HANDSHAKE_PREVIEW_LEN),
+        source,
+    })
+}
 
-        // Receive files
-        for file in handshake.files {
-            let mut file_data = Vec::with_capacity(file.size as usize);
-            let mut received = 0u64;
-            
-            while received < file.size {
-                let n = stream.read(&mut temp_buf).await?;
-                if n == 0 { break; }
-                file_data.extend_from_slice(&temp_buf[..n]);
-                received += n as u64;
-            }
+/// The first `limit` bytes, escaped so binary data stays readable in the log
+fn preview_bytes(bytes: &[u8], limit: usize) -> String {
+    let preview = format!("{:?}", String::from_utf8_lossy(&bytes[..bytes.len().min(limit)]));
+    if bytes.len() > limit {
+        format!("{}…", preview)
+    } else {
+        preview
+    }
+}
 
-            // Save file
-            let path = std::env::temp_dir().join(&file.name);
-            tokio::fs::write(&path, file_data).await?;
-            info!("Saved file {} to {:?}", file.name, path);
-        }
+/// Read into `buf`, failing with `TimedOut` if the peer sends nothing for `timeout`
+pub(crate) async fn read_with_timeout<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut [u8],
+    timeout: Duration,
+) -> std::io::Result<usize> {
+    tokio::time::timeout(timeout, stream.read(buf))
+        .await
+        .unwrap_or_else(|_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("no data received for {:?}", timeout),
+            ))
+        })
+}
 
-        Ok(())
+/// Have the OS probe an idle peer, so a connection to a device that vanished
+/// without closing it is reset instead of kept open forever
+pub(crate) fn enable_keepalive(stream: &TcpStream) {
+    let keepalive = socket2::TcpKeepalive::new()
+        .with_time(KEEPALIVE_TIME)
+        .with_interval(KEEPALIVE_INTERVAL);
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        debug!("Failed to enable TCP keepalive: {}", e);
     }
+}
 
-    pub async fn start_server(&self) -> Result<()> {
-        *self.status.lock().await = AirDropStatus::Connecting;
-        
-        // Register mDNS services first
-        self.register_mdns_services().await?;
+/// An address other devices on the network can connect to: not loopback,
+/// multicast or link-local
+fn is_lan_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(addr) => !addr.is_loopback() && !addr.is_multicast() && !addr.is_link_local(),
+        IpAddr::V6(addr) => !addr.is_loopback() && !addr.is_multicast() && addr.segments()[0] & 0xffc0 != 0xfe80,
+    }
+}
 
-        // Initialize and start HTTPS server for AirDrop protocol
-        let mut http_server = AirDropHttpServer::new(8771); // Use standard AirDrop port
-        http_server.initialize().await?;
-        http_server.start().await?;
-        
-        *self.http_server.lock().await = Some(http_server);
-        info!("Started AirDrop HTTPS server on port 8771");
+/// Limits and options applied to each incoming connection
+#[derive(Clone, Copy, Debug)]
+struct ReceiveLimits {
+    max_file_size: u64,
+    read_timeout: Duration,
+    chunk_size: usize,
+    /// Compression announced to AirWin senders
+    compression: CompressionMode,
+}
 
-        // Keep the old TCP listener for backward compatibility
-        let v4_listener = match TcpListener::bind(("0.0.0.0", 7000)).await {
-            Ok(listener) => {
-                info!("Started AirDrop IPv4 fallback server on 0.0.0.0:7000");
-                listener
-            }
-            Err(e) => {
-                warn!("Failed to start AirDrop IPv4 fallback server: {}", e);
-                // Don't fail completely if fallback server can't start
-                *self.status.lock().await = AirDropStatus::Connected;
-                return Ok(());
-            }
-        };
+/// Reject a handshake declaring a file above `limit`, before anything is allocated
+fn check_declared_sizes(files: &[FileTransfer], limit: u64) -> std::result::Result<(), HandshakeError> {
+    match files.iter().find(|file| file.size > limit) {
+        Some(file) => Err(HandshakeError::FileTooLarge {
+            name: file.name.clone(),
+            size: file.size,
+            limit,
+        }),
+        None => Ok(()),
+    }
+}
 
-        let status = self.status.clone();
-        let transfer_progress = self.transfer_progress.clone();
-        
-        tokio::spawn(async move {
-            loop {
-                match v4_listener.accept().await {
-                    Ok((stream, addr)) => {
-                        info!("Accepted IPv4 connection from {}", addr);
-                        *status.lock().await = AirDropStatus::Connected;
-                        
-                        let status = status.clone();
-                        let progress = transfer_progress.clone();
-                        
-                        tokio::spawn(async move {
-                            if let Err(e) = Self::handle_connection(stream, addr).await {
-                                error!("Error handling connection: {}", e);
-                                *status.lock().await = AirDropStatus::Failed(format!("Connection error: {}", e));
-                            }
-                            let current_progress = *progress.lock().await;
-                            *status.lock().await = AirDropStatus::Transferring(current_progress);
-                        });
-                    }
-                    Err(e) => {
-                        warn!("IPv4 accept error: {}", e);
-                        *status.lock().await = AirDropStatus::Failed(format!("Accept error: {}", e));
-                        break;
-                    }
-                }
-            }
-        });
+/// Why a file can't be sent, found before the peer is contacted
+#[derive(Debug, thiserror::Error)]
+pub enum PreflightError {
+    #[error("{0:?} does not exist")]
+    NotFound(PathBuf),
+    #[error("{0:?} can't be read, it may be locked by another program")]
+    PermissionDenied(PathBuf),
+    #[error("{0:?} is not a file")]
+    NotAFile(PathBuf),
+    #[error("Failed to read {path:?}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+}
 
-        // Try binding to IPv6 as optional
-        if let Ok(v6_listener) = TcpListener::bind(("[::1]", 7000)).await {
-            info!("Started AirDrop IPv6 server on [::1]:7000");
-            let status = self.status.clone();
-            let transfer_progress = self.transfer_progress.clone();
-            
-            tokio::spawn(async move {
-                loop {
-                    if let Ok((stream, addr)) = v6_listener.accept().await {
-                        info!("Accepted IPv6 connection from {}", addr);
-                        let status = status.clone();
-                        let progress = transfer_progress.clone();
-                        
-                        tokio::spawn(async move {
-                            if let Err(e) = Self::handle_connection(stream, addr).await {
-                                error!("Error handling IPv6 connection: {}", e);
-                                *status.lock().await = AirDropStatus::Failed(format!("IPv6 connection error: {}", e));
-                            }
-                            let current_progress = *progress.lock().await;
-                            *status.lock().await = AirDropStatus::Transferring(current_progress);
-                        });
-                    }
-                }
-            });
+impl PreflightError {
+    fn from_io(path: &std::path::Path, error: std::io::Error) -> Self {
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION: another program has the file open
+        let locked = cfg!(windows) && matches!(error.raw_os_error(), Some(32) | Some(33));
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(path.to_path_buf()),
+            _ if locked => Self::PermissionDenied(path.to_path_buf()),
+            _ => Self::Io { path: path.to_path_buf(), source: error },
         }
+    }
+}
 
-        Ok(())
+/// Check that `path` is a file that can be opened for reading, so a missing
+/// or locked file fails at once instead of in the middle of a handshake.
+/// Returns the size of the file.
+pub async fn preflight(path: &std::path::Path) -> std::result::Result<u64, PreflightError> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| PreflightError::from_io(path, e))?;
+    if !metadata.is_file() {
+        return Err(PreflightError::NotAFile(path.to_path_buf()));
     }
+    File::open(path).await.map_err(|e| PreflightError::from_io(path, e))?;
+    Ok(metadata.len())
+}
 
-    pub async fn send_file(&self, file_path: PathBuf) -> Result<()> {
-        *self.status.lock().await = AirDropStatus::Connecting;
-        
-        let file = File::open(&file_path)
-            .await
-            .context("Failed to open file")?;
-        
-        let metadata = file.metadata().await?;
-        let transfer = FileTransfer {
-            id: Uuid::new_v4().to_string(),
-            name: file_path.file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
-            size: metadata.len(),
-            mime_type: mime_guess::from_path(&file_path)
-                .first_or_octet_stream()
-                .to_string(),
-        };
+/// A certificate or TLS configuration failure, kept apart from network errors
+/// so the interface can explain it
+pub(crate) fn tls_error(error: impl std::fmt::Display) -> AirWinError {
+    AirWinError::TlsError(error.to_string())
+}
 
-        // Generate certificate for TLS
-        let (identity, _) = Self::generate_certificate().await?;
-        let connector = native_tls::TlsConnector::builder()
-            .identity(identity)
-            .build()?;
-        let connector = tokio_native_tls::TlsConnector::from(connector);
+/// Whether `error` comes from setting up TLS rather than from the peer or the network
+pub fn is_tls_error(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<AirWinError>(), Some(AirWinError::TlsError(_)))
+}
 
-        // Try IPv4 connection first
-        let mut connection = self.connection.lock().await;
-        if let Some(stream) = connection.take() {
-            info!("Sending file over IPv4 connection");
-            *self.status.lock().await = AirDropStatus::Connected;
-            
-            let mut tls_stream = connector.connect("AirDrop", stream).await?;
-            
-            let handshake = AirDropHandshake {
-                sender: "AirWin".to_string(),
-                receiver: "AirDrop".to_string(),
-                files: vec![transfer],
-            };
+/// Where a file is written while it's being received, next to its final path
+pub(crate) fn part_path(path: &std::path::Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
 
-            let handshake_json = serde_json::to_string(&handshake)?;
-            tls_stream.write_all(handshake_json.as_bytes()).await?;
-            tls_stream.write_all(b"\n\n").await?;
+/// Move a completely written `.part` file to `path`, or remove it if writing failed
+async fn finish_part<T>(part: &std::path::Path, path: &std::path::Path, written: Result<T>) -> Result<T> {
+    let result = match written {
+        Ok(value) => tokio::fs::rename(part, path).await.map(|_| value).map_err(Into::into),
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        if let Err(e) = tokio::fs::remove_file(part).await {
+            warn!("Failed to remove partial file {:?}: {}", part, e);
+        }
+    }
+    result
+}
 
-            let mut file = File::open(&file_path).await?;
-            let mut buffer = vec![0; 8192];
-            let mut sent = 0u64;
-            
-            *self.status.lock().await = AirDropStatus::Transferring(0.0);
-            
-            while let Ok(n) = file.read(&mut buffer).await {
-                if n == 0 { break; }
-                tls_stream.write_all(&buffer[..n]).await?;
-                sent += n as u64;
-                let progress = (sent as f32 / metadata.len() as f32) * 100.0;
-                *self.transfer_progress.lock().await = progress;
-                *self.status.lock().await = AirDropStatus::Transferring(progress);
+/// Copy exactly `size` bytes from the stream to `path`, one chunk at a time.
+/// The bytes go to a `.part` file that is renamed to `path` once complete.
+/// Fails, leaving nothing behind, if the peer disconnects early or sends
+/// nothing for `read_timeout`.
+pub(crate) async fn receive_to_file<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    path: &std::path::Path,
+    size: u64,
+    read_timeout: Duration,
+    chunk_size: usize,
+    compression: Option<Algorithm>,
+) -> Result<u64> {
+    let part = part_path(path);
+    let written = receive_to_part(stream, &part, size, read_timeout, chunk_size, compression).await;
+    finish_part(&part, path, written).await
+}
+
+async fn receive_to_part<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    part: &std::path::Path,
+    size: u64,
+    read_timeout: Duration,
+    chunk_size: usize,
+    compression: Option<Algorithm>,
+) -> Result<u64> {
+    let mut file = File::create(part).await?;
+    let mut received = 0u64;
+
+    // Frames of a negotiated compression, each at most the bytes still missing
+    if let Some(algorithm) = compression {
+        while received < size {
+            let remaining = usize::try_from(size - received).unwrap_or(usize::MAX);
+            let data = tokio::time::timeout(read_timeout, compression::read_frame(stream, algorithm, remaining))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        format!("no data received for {:?}", read_timeout),
+                    ))
+                })?;
+            if data.is_empty() {
+                return Err(anyhow!("empty frame after {} of {} bytes", received, size));
             }
+            file.write_all(&data).await?;
+            received += data.len() as u64;
+        }
+        file.flush().await?;
+        return Ok(received);
+    }
 
-            *self.status.lock().await = AirDropStatus::Connected;
-            
-            // After transfer, establish a new connection for future use
-            let new_stream = TcpStream::connect(tls_stream.get_ref().get_ref().get_ref().peer_addr()?).await?;
-            *connection = Some(new_stream);
+    let mut chunk = vec![0u8; chunk_size_for(chunk_size, size)];
+    while received < size {
+        let wanted = (size - received).min(chunk.len() as u64) as usize;
+        let n = read_with_timeout(stream, &mut chunk[..wanted], read_timeout).await?;
+        if n == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("peer disconnected after {} of {} bytes", received, size),
+            )
+            .into());
+        }
+        file.write_all(&chunk[..n]).await?;
+        received += n as u64;
+    }
+
+    file.flush().await?;
+    Ok(received)
+}
+
+/// Send a handshake and read the receiver's answer
+async fn offer<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, handshake_json: &str) -> Result<serde_json::Value> {
+    stream.write_all(handshake_json.as_bytes()).await?;
+    stream.write_all(b"\n\n").await?;
+    let response = read_handshake(stream, MAX_HANDSHAKE_SIZE, HANDSHAKE_TIMEOUT).await?;
+    Ok(serde_json::from_slice(&response)?)
+}
+
+/// Percentage of a `total`-byte file sent after `sent` bytes. An empty file
+/// has nothing to stream and is complete as soon as it is accepted.
+fn send_progress(sent: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 100.0;
+    }
+    (sent as f32 / total as f32 * 100.0).min(100.0)
+}
+
+/// Result of a completed outgoing transfer
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferOutcome {
+    pub file_name: String,
+    pub total_bytes: u64,
+    pub elapsed: Duration,
+    /// Address or name of the receiving device
+    pub peer: String,
+}
+
+/// Result of a loopback benchmark
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub bytes: u64,
+    pub seconds: f64,
+    pub mb_per_s: f64,
+    /// Chunk size the transfer ran with
+    pub chunk_size: usize,
+    pub tls_backend: TlsBackend,
+}
+
+impl TransferOutcome {
+    /// Average throughput in bytes per second
+    pub fn average_speed(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.total_bytes as f64 / secs
         } else {
-            *self.status.lock().await = AirDropStatus::Failed("No active connection available".to_string());
-            return Err(anyhow!("No active connection available"));
+            self.total_bytes as f64
+        }
+    }
+}
+
+/// Event published when a file has been received and saved to disk
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncomingFile {
+    pub name: String,
+    pub size: u64,
+    pub from: SocketAddr,
+    /// Where the file was saved, after resolving name conflicts
+    pub path: PathBuf,
+}
+
+/// Publish an inbound transfer event without blocking the receive path
+pub(crate) fn publish_incoming(events: &broadcast::Sender<IncomingFile>, file: IncomingFile) {
+    event_log::info(
+        "AirDrop",
+        format!("Received {} ({} bytes) from {}", file.name, file.size, file.from),
+    );
+
+    if events.len() >= INCOMING_EVENTS_CAPACITY {
+        warn!("Incoming file event queue full, dropping event for {}", file.name);
+        return;
+    }
+
+    if events.send(file).is_err() {
+        debug!("No subscribers for incoming file events");
+    }
+}
+
+/// How a link is packaged for the receiver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkEncoding {
+    /// `text/uri-list` payload (UTI `public.url`), which Apple devices
+    /// open as a tappable link
+    UriList,
+    /// Windows Internet shortcut (`.url`), for other AirWin instances.
+    /// iOS can't open it.
+    InternetShortcut,
+}
+
+impl LinkEncoding {
+    /// Encoding for a receiver advertising `txt`. AirWin advertises a
+    /// `Windows` model; anything else, records missing included, is
+    /// treated as an Apple device.
+    pub fn for_recipient(txt: &std::collections::HashMap<String, String>) -> Self {
+        let windows = ["model", "rpMd"]
+            .iter()
+            .filter_map(|key| txt.get(*key))
+            .any(|model| model.starts_with("Windows"));
+        if windows {
+            LinkEncoding::InternetShortcut
+        } else {
+            LinkEncoding::UriList
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            LinkEncoding::UriList => "text/uri-list",
+            LinkEncoding::InternetShortcut => "application/x-mswinurl",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            LinkEncoding::UriList => "link.uri",
+            LinkEncoding::InternetShortcut => "link.url",
+        }
+    }
+
+    /// The payload carrying `url`
+    pub fn contents(self, url: &str) -> String {
+        match self {
+            LinkEncoding::UriList => format!("{}\r\n", url),
+            LinkEncoding::InternetShortcut => format!("[InternetShortcut]\r\nURL={}\r\n", url),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileTransfer {
+    id: String,
+    name: String,
+    size: u64,
+    mime_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AirDropHandshake {
+    sender: String,
+    /// Id the receiver can trust the sender by, sent by AirWin only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sender_id: Option<String>,
+    receiver: String,
+    files: Vec<FileTransfer>,
+    /// Missing in handshakes from Apple devices and older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capabilities: Option<Capabilities>,
+}
+
+/// Id this PC is trusted by on other AirWin receivers: the fingerprint of
+/// the receiver certificate, which is kept across restarts. Receivers can't
+/// verify it, so it only spares a contact the prompt.
+async fn device_id() -> Option<String> {
+    tls::receiver_certificate().await.and_then(|certificate| certificate.fingerprint()).ok()
+}
+
+#[derive(Clone)]
+pub struct AirDrop {
+    current_file: Arc<Mutex<Option<PathBuf>>>,
+    transfer_progress: Arc<Mutex<f32>>,
+    /// Progress of the current file and of the whole send
+    send_progress: Arc<Mutex<Option<SendProgress>>>,
+    transfer_size: Arc<Mutex<Option<u64>>>,
+    connection: Arc<Mutex<Option<TcpStream>>>,
+    mdns: Arc<Mutex<Option<Advertisement>>>,
+    mdns_daemon: DaemonFactory,
+    mdns_retry: Backoff,
+    /// Retries of a refused connection to a peer
+    connect_retry: Backoff,
+    /// Why the services aren't advertised, `None` while they are
+    send_only: Arc<watch::Sender<Option<String>>>,
+    /// Last failure to set up TLS for a transfer, `None` until one happens
+    tls_failures: Arc<watch::Sender<Option<String>>>,
+    /// Last peer whose certificate no longer matched its pin
+    certificate_changes: Arc<watch::Sender<Option<String>>>,
+    /// Name configured for this PC
+    friendly_name: String,
+    /// Name shown to peers in the mDNS records and handshakes: the friendly
+    /// name, numbered if another device already advertises it
+    advertised_name: Arc<watch::Sender<String>>,
+    /// Look for devices advertising the same name before registering
+    avoid_name_collisions: bool,
+    udp_socket: Arc<Mutex<Option<UdpSocket>>>,
+    http_server: Arc<Mutex<Option<AirDropHttpServer>>>,
+    /// Timeouts of the connections to the HTTPS server
+    https_limits: ConnectionLimits,
+    /// Connections open on the HTTPS server, kept across restarts
+    https_connections: Arc<AtomicUsize>,
+    incoming_events: broadcast::Sender<IncomingFile>,
+    conflicts: ConflictResolver,
+    receive_dir: PathBuf,
+    archive_dir: PathBuf,
+    multicast: MulticastSettings,
+    /// IP versions listened on, joined for multicast and connected over
+    ip_mode: IpMode,
+    failed_multicast_joins: Arc<Mutex<HashSet<String>>>,
+    https_port: u16,
+    fallback_port: u16,
+    companion_port: u16,
+    device_info_port: u16,
+    accept_self_signed: bool,
+    /// TLS stack used for outgoing transfers
+    tls_backend: TlsBackend,
+    /// Outgoing connections kept for the next file to the same peer
+    pool: Option<Arc<ConnectionPool>>,
+    cert_pins: Option<Arc<Mutex<PinStore>>>,
+    max_file_size: u64,
+    read_timeout: Duration,
+    /// Configured transfer buffer size, tuned per file by [`chunk_size_for`]
+    chunk_size: usize,
+    /// Compression offered to other AirWin instances, both ways
+    compression: CompressionMode,
+    peer_filter: Arc<PeerFilter>,
+    receive_gate: ReceiveGate,
+    fallback_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    /// Ends the accept loops of the fallback servers, replaced on each stop
+    fallback_shutdown: Arc<std::sync::Mutex<CancelToken>>,
+    awdl: watch::Receiver<AwdlAvailability>,
+    clock: SharedClock,
+    pub status: Arc<Mutex<AirDropStatus>>,
+}
+
+
+impl std::fmt::Debug for AirDrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AirDrop")
+            .field("https_port", &self.https_port)
+            .field("fallback_port", &self.fallback_port)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AirDrop {
+    pub fn new() -> Self {
+        let (incoming_events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let default_name = apple_records::friendly_name(None).unwrap_or_else(|_| "AirWin".to_string());
+        Self {
+            current_file: Arc::new(Mutex::new(None)),
+            transfer_progress: Arc::new(Mutex::new(0.0)),
+            send_progress: Arc::new(Mutex::new(None)),
+            transfer_size: Arc::new(Mutex::new(None)),
+            connection: Arc::new(Mutex::new(None)),
+            mdns: Arc::new(Mutex::new(None)),
+            mdns_daemon: ServiceDaemon::new,
+            mdns_retry: MDNS_RETRY,
+            connect_retry: CONNECT_RETRY,
+            send_only: Arc::new(watch::channel(None).0),
+            tls_failures: Arc::new(watch::channel(None).0),
+            certificate_changes: Arc::new(watch::channel(None).0),
+            friendly_name: default_name.clone(),
+            advertised_name: Arc::new(watch::channel(default_name).0),
+            avoid_name_collisions: false,
+            udp_socket: Arc::new(Mutex::new(None)),
+            http_server: Arc::new(Mutex::new(None)),
+            https_limits: ConnectionLimits::default(),
+            https_connections: Arc::new(AtomicUsize::new(0)),
+            incoming_events,
+            conflicts: ConflictResolver::new(FileConflictPolicy::default()),
+            receive_dir: std::env::temp_dir(),
+            archive_dir: std::env::temp_dir(),
+            multicast: MulticastSettings::default(),
+            ip_mode: IpMode::Auto,
+            failed_multicast_joins: Arc::new(Mutex::new(HashSet::new())),
+            https_port: AIRDROP_HTTPS_PORT,
+            fallback_port: AIRDROP_FALLBACK_PORT,
+            companion_port: COMPANION_LINK_PORT,
+            device_info_port: DEVICE_INFO_PORT,
+            accept_self_signed: false,
+            tls_backend: TlsBackend::default(),
+            pool: None,
+            cert_pins: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            read_timeout: READ_TIMEOUT,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compression: CompressionMode::Off,
+            peer_filter: Arc::new(PeerFilter::default()),
+            receive_gate: ReceiveGate::new(VisibilitySettings::default()),
+            fallback_tasks: Arc::new(Mutex::new(Vec::new())),
+            fallback_shutdown: Arc::new(std::sync::Mutex::new(CancelToken::new())),
+            awdl: watch::channel(AwdlAvailability::Off).1,
+            clock: clock::system(),
+            status: Arc::new(Mutex::new(AirDropStatus::default())),
+        }
+    }
+
+    /// Time transfers with `clock` instead of the system time
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Show `name` to peers instead of the hostname. The `_device-info`
+    /// record keeps the hostname.
+    pub fn with_friendly_name(mut self, name: String) -> Self {
+        self.advertised_name.send_replace(name.clone());
+        self.friendly_name = name;
+        self
+    }
+
+    /// Before registering, look for devices already advertising the name
+    /// and number it if one does, like "PC (2)"
+    pub fn with_name_collision_check(mut self, enabled: bool) -> Self {
+        self.avoid_name_collisions = enabled;
+        self
+    }
+
+    /// Name peers see, once the collision check has run
+    pub fn advertised_name(&self) -> watch::Receiver<String> {
+        self.advertised_name.subscribe()
+    }
+
+    fn name(&self) -> String {
+        self.advertised_name.borrow().clone()
+    }
+
+    /// Create the mDNS daemon with `factory`, retrying failures with `retry`
+    pub fn with_mdns_daemon(mut self, factory: DaemonFactory, retry: Backoff) -> Self {
+        self.mdns_daemon = factory;
+        self.mdns_retry = retry;
+        self
+    }
+
+    /// Addresses peers find this PC at: those of the interfaces the mDNS
+    /// services are advertised on, in the versions the IP mode allows
+    pub fn discoverable_addresses(&self) -> Vec<IpAddr> {
+        let interfaces = match local_ip_address::list_afinet_netifas() {
+            Ok(interfaces) => interfaces,
+            Err(e) => {
+                warn!("Failed to list network interfaces: {}", e);
+                return Vec::new();
+            }
+        };
+        let mut addrs: Vec<IpAddr> = interfaces
+            .into_iter()
+            .filter(|(name, ip)| self.multicast.allows_interface(name) && self.ip_mode.allows(*ip) && is_lan_address(*ip))
+            .map(|(_, ip)| ip)
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+    }
+
+    /// Reason the services couldn't be advertised, `None` while they are.
+    /// Files can still be sent when they aren't.
+    pub fn send_only(&self) -> watch::Receiver<Option<String>> {
+        self.send_only.subscribe()
+    }
+
+    /// Certificate or TLS failures, while sending or receiving
+    pub fn tls_failures(&self) -> watch::Receiver<Option<String>> {
+        self.tls_failures.subscribe()
+    }
+
+    /// Peers whose certificate no longer matches their pin, so the UI can
+    /// offer to forget it
+    pub fn certificate_changes(&self) -> watch::Receiver<Option<String>> {
+        self.certificate_changes.subscribe()
+    }
+
+    /// Forget the pinned certificate of `peer`, an address as reported by
+    /// [`AirWinError::CertificateChanged`]. The next send pins the
+    /// certificate it presents then.
+    pub async fn forget_certificate(&self, peer: &str) -> Result<()> {
+        let Some(pins) = &self.cert_pins else {
+            return Ok(());
+        };
+        let mut pins = pins.lock().await;
+        pins.forget(peer);
+        pins.save()?;
+        info!("Forgot the pinned certificate of {}", peer);
+        event_log::info("AirDrop", format!("Forgot the certificate of {}", peer));
+        Ok(())
+    }
+
+    /// Advertise `supports_awdl` from the AWDL manager's actual state
+    pub fn with_awdl_availability(mut self, awdl: watch::Receiver<AwdlAvailability>) -> Self {
+        self.awdl = awdl;
+        self
+    }
+
+    /// Use the given TTL and interface allow-list for the multicast socket
+    pub fn with_multicast_settings(mut self, multicast: MulticastSettings) -> Self {
+        self.multicast = multicast;
+        self
+    }
+
+    /// Listen, join multicast groups and connect only over the IP versions
+    /// allowed by `mode`
+    pub fn with_ip_mode(mut self, mode: IpMode) -> Self {
+        self.ip_mode = mode;
+        self
+    }
+
+    /// Listen and advertise on the configured ports
+    pub fn with_ports(mut self, ports: Ports) -> Self {
+        self.https_port = ports.airdrop_https;
+        self.fallback_port = ports.airdrop_fallback;
+        self.companion_port = ports.companion_link;
+        self.device_info_port = ports.device_info;
+        self
+    }
+
+    /// Retry connections refused by a peer with `retry`
+    pub fn with_connect_retry(mut self, retry: Backoff) -> Self {
+        self.connect_retry = retry;
+        self
+    }
+
+    /// Trust peers presenting self-signed certificates when sending,
+    /// like the ones this crate's own receiver generates
+    pub fn with_self_signed_peers(mut self, accept: bool) -> Self {
+        self.accept_self_signed = accept;
+        self
+    }
+
+    /// Send over `backend` instead of the system TLS stack
+    pub fn with_tls_backend(mut self, backend: TlsBackend) -> Self {
+        self.tls_backend = backend;
+        self
+    }
+
+    /// Keep outgoing connections open for `idle_timeout` after a transfer and
+    /// reuse them for the next file to the same peer. The status stays
+    /// connected until the connection is closed.
+    pub fn with_connection_pool(mut self, idle_timeout: Duration) -> Self {
+        self.pool = Some(Arc::new(ConnectionPool::new(idle_timeout)));
+        self
+    }
+
+    /// Close the connection after each send, or keep it for the next one
+    /// with the default idle window
+    pub fn with_after_transfer(mut self, after: AfterTransfer) -> Self {
+        self.pool = match after {
+            AfterTransfer::Disconnect => None,
+            AfterTransfer::StayConnected => Some(Arc::new(ConnectionPool::new(connection_pool::DEFAULT_IDLE_TIMEOUT))),
+        };
+        self
+    }
+
+    /// Remember the certificate of each peer after the first successful
+    /// transfer and refuse to send when it changes. Off by default for interop with
+    /// Apple devices, which rotate their certificates.
+    pub fn with_cert_pinning(mut self, store: PinStore) -> Self {
+        self.cert_pins = Some(Arc::new(Mutex::new(store)));
+        self
+    }
+
+    /// Refuse incoming files larger than `bytes`
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Close incoming connections whose peer sends nothing for `timeout`
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Close connections to the HTTPS server that stay idle or open longer
+    /// than `limits`
+    pub fn with_https_connection_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.https_limits = limits;
+        self
+    }
+
+    /// Connections currently open on the HTTPS server
+    pub fn active_https_connections(&self) -> usize {
+        self.https_connections.load(Ordering::Relaxed)
+    }
+
+    /// Read and write files in chunks of `bytes`, within
+    /// [`MIN_CHUNK_SIZE`] and [`MAX_CHUNK_SIZE`]. Larger chunks mean fewer
+    /// system calls on fast networks.
+    pub fn with_chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = bytes.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE);
+        self
+    }
+
+    /// Compress files sent to, and accept compressed files from, other
+    /// AirWin instances that support it
+    pub fn with_compression(mut self, mode: CompressionMode) -> Self {
+        self.compression = mode;
+        self
+    }
+
+    /// Only accept connections permitted by the allow/deny lists
+    pub fn with_peer_filter(mut self, filter: PeerFilter) -> Self {
+        self.peer_filter = Arc::new(filter);
+        self
+    }
+
+    /// Who may send files, and whether contacts are accepted without asking
+    pub fn with_visibility(self, settings: VisibilitySettings) -> Self {
+        self.receive_gate.set_settings(settings);
+        self
+    }
+
+    /// Change the visibility of the running servers, advertising or
+    /// withdrawing the AirDrop services to match
+    pub async fn set_visibility(&self, settings: VisibilitySettings) -> Result<()> {
+        let was_advertised = self.receive_gate.settings().advertised();
+        self.receive_gate.set_settings(settings);
+        if settings.advertised() != was_advertised {
+            self.refresh_network_services().await?;
         }
-        
-        *self.current_file.lock().await = Some(file_path);
         Ok(())
     }
+
+    /// Devices accepted without asking when auto-accept is on
+    pub fn with_trusted_devices(self, devices: Vec<TrustedDevice>) -> Self {
+        self.receive_gate.set_trusted_devices(devices);
+        self
+    }
+
+    /// Change the trusted devices of the running servers
+    pub fn set_trusted_devices(&self, devices: Vec<TrustedDevice>) {
+        self.receive_gate.set_trusted_devices(devices);
+    }
+
+    /// Sender side of the prompts raised for transfers that need confirmation
+    pub fn transfer_prompts(&self) -> broadcast::Sender<TransferPrompt> {
+        self.receive_gate.prompts()
+    }
+
+    /// Save received files in `dir` instead of the system temp directory
+    pub fn with_receive_dir(mut self, dir: PathBuf) -> Self {
+        self.receive_dir = dir;
+        self
+    }
+
+    /// Create the archives of folders being sent in `dir`
+    pub fn with_archive_dir(mut self, dir: PathBuf) -> Self {
+        self.archive_dir = dir;
+        self
+    }
+
+    /// Decide what happens when an incoming file name is already taken
+    pub fn with_conflict_policy(self, policy: FileConflictPolicy) -> Self {
+        self.conflicts.set_policy(policy);
+        self
+    }
+
+    /// Change the conflict policy of the running servers
+    pub fn set_conflict_policy(&self, policy: FileConflictPolicy) {
+        self.conflicts.set_policy(policy);
+    }
+
+    /// Sender side of the prompts raised by the "Ask" conflict policy
+    pub fn conflict_prompts(&self) -> broadcast::Sender<ConflictPrompt> {
+        self.conflicts.prompts()
+    }
+
+    pub async fn send_file_to(&self, addr: SocketAddr, file_path: PathBuf) -> Result<TransferOutcome> {
+        self.send_file_with_mime(addr, file_path, None).await
+    }
+
+    /// Send a file, optionally overriding the detected MIME type.
+    ///
+    /// Without an override the type is guessed from the extension and, for
+    /// unknown extensions, from the file's magic bytes.
+    pub async fn send_file_with_mime(
+        &self,
+        addr: SocketAddr,
+        file_path: PathBuf,
+        mime_override: Option<String>,
+    ) -> Result<TransferOutcome> {
+        let tracker = ProgressTracker::new(self.prepare_send(addr, std::slice::from_ref(&file_path)).await?);
+        self.status.lock().await.connecting();
+
+        let result = self.transfer_file(addr, file_path, mime_override, &tracker, 0).await;
+        match &result {
+            Ok(_) => self.finish_send().await,
+            Err(e) => self.status.lock().await.fail(e.to_string()),
+        }
+        result
+    }
+
+    /// Status once a send is complete: connected while the connection is
+    /// kept for the next send, idle once it is closed
+    async fn finish_send(&self) {
+        if self.pool.is_none() {
+            self.status.lock().await.disconnected();
+        }
+    }
+
+    /// Send several files one after the other, reporting the progress of the
+    /// current file and of the whole send. Stops at the first failure.
+    pub async fn send_files_to(&self, addr: SocketAddr, paths: Vec<PathBuf>) -> Result<Vec<TransferOutcome>> {
+        let tracker = ProgressTracker::new(self.prepare_send(addr, &paths).await?);
+        self.status.lock().await.connecting();
+
+        let mut outcomes = Vec::with_capacity(paths.len());
+        for (index, path) in paths.into_iter().enumerate() {
+            match self.transfer_file(addr, path, None, &tracker, index).await {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(e) => {
+                    self.status.lock().await.fail(e.to_string());
+                    return Err(e);
+                }
+            }
+        }
+        self.finish_send().await;
+        Ok(outcomes)
+    }
+
+    /// Check that every file can be sent to `addr` before anything is
+    /// transferred. Returns the name and size of each file.
+    async fn prepare_send(&self, addr: SocketAddr, paths: &[PathBuf]) -> Result<Vec<(String, u64)>> {
+        // The progress of an earlier send isn't this one's
+        *self.send_progress.lock().await = None;
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            match preflight(path).await {
+                Ok(size) => files.push((file_name::wire_name(path).name, size)),
+                Err(e) => {
+                    self.status.lock().await.fail(e.to_string());
+                    return Err(e.into());
+                }
+            }
+        }
+        if !self.ip_mode.allows(addr.ip()) {
+            let error = format!("{} is not reachable in {:?} mode", addr, self.ip_mode);
+            self.status.lock().await.fail(error.clone());
+            return Err(anyhow!(error));
+        }
+        Ok(files)
+    }
+
+    /// Send the file at `index` of the send tracked by `tracker`
+    async fn transfer_file(
+        &self,
+        addr: SocketAddr,
+        file_path: PathBuf,
+        mime_override: Option<String>,
+        tracker: &ProgressTracker,
+        index: usize,
+    ) -> Result<TransferOutcome> {
+        let file = File::open(&file_path)
+            .await
+            .context("Failed to open file")?;
+
+        let metadata = file.metadata().await?;
+        *self.transfer_size.lock().await = Some(metadata.len());
+        let mime_type = match mime_override {
+            Some(mime) => mime,
+            None => mime_type::detect_mime_type(&file_path).await,
+        };
+        let wire_name = file_name::wire_name(&file_path);
+        if wire_name.transliterated {
+            event_log::warning(
+                "AirDrop",
+                format!("{:?} has a non-Unicode name, sending it as {}", file_path, wire_name.name),
+            );
+        }
+        let transfer = FileTransfer {
+            id: Uuid::new_v4().to_string(),
+            name: wire_name.name,
+            size: metadata.len(),
+            mime_type,
+        };
+        event_log::info(
+            "AirDrop",
+            format!("Sending {} ({} bytes) to {}", transfer.name, transfer.size, addr),
+        );
+        let file_name = transfer.name.clone();
+        let started = Instant::now();
+
+        // Send a simple JSON handshake
+        let handshake = AirDropHandshake {
+            sender: self.name(),
+            sender_id: device_id().await,
+            receiver: "AirWin".to_string(),
+            files: vec![transfer],
+            capabilities: Some(Capabilities::local(self.compression)),
+        };
+        let handshake_json = serde_json::to_string(&handshake)?;
+
+        // A pooled connection the peer closed in the meantime fails before
+        // the file is accepted and is replaced by a new one
+        let mut offered = None;
+        if let Some(mut connection) = self.take_pooled(addr).await {
+            match offer(&mut connection.stream, &handshake_json).await {
+                Ok(response) => offered = Some((connection, response)),
+                Err(e) => debug!("Reused connection to {} failed, reconnecting: {}", addr, e),
+            }
+        }
+        let (mut connection, response) = match offered {
+            Some(offered) => offered,
+            None => {
+                let mut connection = self.open_connection(addr).await?;
+                let response = offer(&mut connection.stream, &handshake_json).await?;
+                (connection, response)
+            }
+        };
+        self.status.lock().await.connected();
+
+        // Wait for the receiver to accept before streaming the file
+        if response["status"] != "accept" {
+            let reason = response["reason"].as_str().unwrap_or("no reason given");
+            return Err(anyhow!("{} refused the transfer: {}", addr, reason));
+        }
+        let receiver_capabilities: Option<Capabilities> = response
+            .get("capabilities")
+            .and_then(|capabilities| serde_json::from_value(capabilities.clone()).ok());
+        let negotiated = capabilities::negotiate(handshake.capabilities.as_ref(), receiver_capabilities.as_ref());
+        debug!("Negotiated with {}: {:?}", addr, negotiated);
+        // Once compression is negotiated every file is framed; an already
+        // compressed one is sent in stored frames
+        let framed = negotiated.compression.is_some();
+        let algorithm = negotiated
+            .compression
+            .as_deref()
+            .and_then(Algorithm::from_name)
+            .filter(|_| !mime_type::is_compressed(&handshake.files[0].mime_type));
+
+        // Stream file contents
+        let mut file = File::open(&file_path).await?;
+        let mut buffer = vec![0; chunk_size_for(self.chunk_size, metadata.len())];
+        let mut sent = 0u64;
+
+        self.status.lock().await.start_transfer(self.clock.now());
+        *self.send_progress.lock().await = Some(tracker.update(index, 0));
+
+        // An empty file is complete once the handshake is accepted
+        if metadata.len() == 0 {
+            *self.transfer_progress.lock().await = 100.0;
+            self.status.lock().await.set_progress(100.0);
+        }
+        while sent < metadata.len() {
+            let n = file.read(&mut buffer).await?;
+            if n == 0 { break; }
+            if framed {
+                compression::write_frame(&mut connection.stream, algorithm, &buffer[..n]).await?;
+            } else {
+                connection.stream.write_all(&buffer[..n]).await?;
+            }
+            sent += n as u64;
+            let progress = send_progress(sent, metadata.len());
+            *self.transfer_progress.lock().await = progress;
+            *self.send_progress.lock().await = Some(tracker.update(index, sent));
+            self.status.lock().await.set_progress(progress);
+        }
+        connection.stream.flush().await?;
+
+        let peer_fingerprint = connection.peer_certificate.as_deref().map(cert_pins::fingerprint);
+        let peer_key = addr.ip().to_string();
+        match &self.pool {
+            // Keep the connection for the next file to this peer
+            Some(pool) => {
+                let expiry = pool.put(addr, connection).await;
+                let (pool, status) = (pool.clone(), self.status.clone());
+                tokio::spawn(async move {
+                    if matches!(expiry.await, Ok(true)) && pool.is_empty().await {
+                        status.lock().await.kept_connection_closed();
+                    }
+                });
+            }
+            None => connection.stream.shutdown().await?,
+        }
+
+        self.status.lock().await.finish_transfer();
+
+        // Pin the certificate once a transfer to this peer has succeeded
+        if let (Some(pins), Some(fingerprint)) = (&self.cert_pins, &peer_fingerprint) {
+            let mut pins = pins.lock().await;
+            if pins.pin_if_unknown(&peer_key, fingerprint) {
+                info!("Pinned certificate of {}", peer_key);
+                if let Err(e) = pins.save() {
+                    warn!("Failed to save certificate pins: {}", e);
+                }
+            }
+        }
+
+        let outcome = TransferOutcome {
+            file_name,
+            total_bytes: sent,
+            elapsed: started.elapsed(),
+            peer: addr.to_string(),
+        };
+        event_log::info(
+            "AirDrop",
+            format!("Sent {} ({} bytes) to {} in {:.1}s", outcome.file_name, sent, addr, outcome.elapsed.as_secs_f64()),
+        );
+        *self.current_file.lock().await = Some(file_path);
+        Ok(outcome)
+    }
+    
+
+    async fn take_pooled(&self, addr: SocketAddr) -> Option<Connection> {
+        match &self.pool {
+            Some(pool) => pool.take(addr).await,
+            None => None,
+        }
+    }
+
+    /// TCP connection to `addr`. A refused connection is retried with
+    /// `connect_retry`, then reported as [`AirWinError::PeerUnreachable`].
+    async fn connect(&self, addr: SocketAddr) -> Result<TcpStream> {
+        let mut attempt = 0;
+        loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    attempt += 1;
+                    if self.connect_retry.max_restarts.is_some_and(|max| attempt > max) {
+                        event_log::warning("AirDrop", format!("{} refused the connection", addr));
+                        return Err(AirWinError::PeerUnreachable(addr).into());
+                    }
+                    let delay = self.connect_retry.delay(attempt);
+                    debug!("{} refused the connection (attempt {}), retrying in {:?}", addr, attempt, delay);
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// New TLS connection to `addr`. Fails with
+    /// [`AirWinError::CertificateChanged`] if the peer presents a certificate
+    /// other than the pinned one.
+    async fn open_connection(&self, addr: SocketAddr) -> Result<Connection> {
+        let connector = tls::sender_certificate()
+            .and_then(|certificate| Connector::new(self.tls_backend, certificate, self.accept_self_signed));
+        let connector = match connector {
+            Ok(connector) => connector,
+            Err(e) => {
+                error!("{}", e);
+                event_log::error("AirDrop", e.to_string());
+                self.status.lock().await.fail(e.to_string());
+                self.tls_failures.send_replace(Some(e.to_string()));
+                return Err(e.into());
+            }
+        };
+
+        let stream = self.connect(addr).await?;
+
+        // Perform TLS handshake; server name must match CN used by server cert
+        let (stream, peer_certificate) = connector.connect("AirWin", stream).await?;
+        if let Some(pool) = &self.pool {
+            pool.record_opened();
+        }
+
+        let peer_key = addr.ip().to_string();
+        if let (Some(pins), Some(der)) = (&self.cert_pins, &peer_certificate) {
+            let fingerprint = cert_pins::fingerprint(der);
+            if let PinCheck::Mismatch { pinned } = pins.lock().await.check(&peer_key, &fingerprint) {
+                warn!("Certificate of {} changed: pinned {}, got {}", peer_key, pinned, fingerprint);
+                let e = AirWinError::CertificateChanged(peer_key.clone());
+                event_log::error("AirDrop", e.to_string());
+                self.status.lock().await.fail(e.to_string());
+                self.certificate_changes.send_replace(Some(peer_key));
+                return Err(e.into());
+            }
+        }
+
+        Ok(Connection { stream, peer_certificate })
+    }
+
+    /// Send a whole folder by zipping it into a temporary archive first.
+    ///
+    /// The archive is named after the folder and removed once the transfer
+    /// completes or fails.
+    pub async fn send_folder_to(&self, addr: SocketAddr, folder: PathBuf) -> Result<TransferOutcome> {
+        self.status.lock().await.connecting();
+
+        let archive_dir = self.archive_dir.clone();
+        let archive_path = match tokio::task::spawn_blocking(move || archive::zip_directory(&folder, &archive_dir)).await? {
+            Ok(path) => path,
+            Err(e) => {
+                self.status.lock().await.fail(format!("Compression failed: {}", e));
+                return Err(e);
+            }
+        };
+
+        let size = tokio::fs::metadata(&archive_path).await.map(|m| m.len()).unwrap_or(0);
+        info!("Compressed folder into {:?} ({} bytes)", archive_path, size);
+
+        let result = self.send_file_to(addr, archive_path.clone()).await;
+        archive::remove_archive(&archive_path).await;
+        result
+    }
+
+    /// Send a link, packaged as `encoding` asks.
+    ///
+    /// The payload is written to its own temporary folder under the archive
+    /// directory and removed once the transfer completes or fails.
+    pub async fn send_link_to(&self, addr: SocketAddr, url: &str, encoding: LinkEncoding) -> Result<TransferOutcome> {
+        let dir = self.archive_dir.join(format!("link_{}", Uuid::new_v4().simple()));
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Failed to create {:?}", dir))?;
+        let payload = dir.join(encoding.file_name());
+        let result = match tokio::fs::write(&payload, encoding.contents(url)).await {
+            Ok(()) => self.send_file_with_mime(addr, payload.clone(), Some(encoding.mime_type().to_string())).await,
+            Err(e) => Err(e.into()),
+        };
+        archive::remove_archive(&payload).await;
+        result
+    }
+
+    pub async fn get_status(&self) -> AirDropStatus {
+        self.status.lock().await.clone()
+    }
+
+    /// Sender side of the inbound transfer events, used to create new subscriptions
+    pub fn incoming_events(&self) -> broadcast::Sender<IncomingFile> {
+        self.incoming_events.clone()
+    }
+
+    /// Subscribe to files received over either the HTTPS or the fallback TCP server
+    pub fn subscribe_incoming(&self) -> broadcast::Receiver<IncomingFile> {
+        self.incoming_events.subscribe()
+    }
+
+    /// Total size in bytes of the file currently (or last) being sent
+    pub async fn get_transfer_size(&self) -> Option<u64> {
+        *self.transfer_size.lock().await
+    }
+
+    /// Progress of the last send, kept after it ends
+    pub async fn get_send_progress(&self) -> Option<SendProgress> {
+        self.send_progress.lock().await.clone()
+    }
+
+    async fn setup_multicast(&self) -> Result<UdpSocket> {
+        self.multicast.validate()?;
+        if !self.ip_mode.uses_ipv4() {
+            return self.setup_multicast_v6();
+        }
+
+        // Create socket with socket2 for more control
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        
+        // Set socket options
+        socket.set_reuse_address(true)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_multicast_ttl_v4(self.multicast.ttl)?;
+        socket.set_broadcast(true)?;
+
+        
+        // Bind to the fallback port, UDP so it does not clash with the TCP listener
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), self.fallback_port);
+        socket.bind(&addr.into())?;
+        
+        // Join multicast group on every allowed interface
+        let multicast_addr: Ipv4Addr = "224.0.0.251".parse()?;
+        let interfaces = local_ip_address::list_afinet_netifas()?;
+        let mut failed_joins = self.failed_multicast_joins.lock().await;
+        let mut joined = 0;
+        
+        for (name, ip) in interfaces {
+            if let IpAddr::V4(interface_addr) = ip {
+                // Skip loopback, multicast, and link-local addresses (169.254.x.x)
+                if ip.is_loopback() || ip.is_multicast() || interface_addr.is_link_local() {
+                    continue;
+                }
+
+                if !self.multicast.allows_interface(&name) {
+                    debug!("Skipping interface {} (not in multicast allow-list)", name);
+                    continue;
+                }
+
+                match socket.join_multicast_v4(&multicast_addr, &interface_addr) {
+                    Ok(()) => {
+                        info!("Joined multicast group on interface {} ({})", name, interface_addr);
+                        failed_joins.remove(&name);
+                        joined += 1;
+                    }
+                    // Only warn the first time an interface fails, not on every rescan
+                    Err(e) if failed_joins.insert(name.clone()) => {
+                        warn!("Failed to join multicast on {}, skipping: {}", name, e);
+                    }
+                    Err(e) => {
+                        debug!("Still unable to join multicast on {}: {}", name, e);
+                    }
+                }
+            }
+        }
+
+        if joined == 0 {
+            warn!("Multicast group not joined on any interface");
+        }
+        
+        // Convert to tokio UdpSocket
+        let std_socket: std::net::UdpSocket = socket.into();
+        std_socket.set_nonblocking(true)?;
+        Ok(UdpSocket::from_std(std_socket)?)
+    }
+
+    /// IPv6-only counterpart of [`setup_multicast`](Self::setup_multicast),
+    /// joining the mDNS group `ff02::fb` on the default interface
+    fn setup_multicast_v6(&self) -> Result<UdpSocket> {
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(true)?;
+        socket.set_reuse_address(true)?;
+        socket.set_multicast_loop_v6(true)?;
+        socket.set_multicast_hops_v6(self.multicast.ttl)?;
+        socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, self.fallback_port)).into())?;
+
+        let multicast_addr: Ipv6Addr = "ff02::fb".parse()?;
+        match socket.join_multicast_v6(&multicast_addr, 0) {
+            Ok(()) => info!("Joined IPv6 multicast group {}", multicast_addr),
+            Err(e) => warn!("Multicast group {} not joined: {}", multicast_addr, e),
+        }
+
+        let std_socket: std::net::UdpSocket = socket.into();
+        std_socket.set_nonblocking(true)?;
+        Ok(UdpSocket::from_std(std_socket)?)
+    }
+
+
+
+    /// Advertise the services, retrying with backoff. On failure the
+    /// receiver is not discoverable and the error is published on
+    /// [`send_only`](Self::send_only).
+    async fn register_mdns_services(&self) -> Result<()> {
+        if self.avoid_name_collisions {
+            self.pick_advertised_name().await;
+        }
+        let mut attempt = 0;
+        let advertisement = loop {
+            attempt += 1;
+            match self.advertise_services() {
+                Ok(advertisement) => break advertisement,
+                Err(e) if self.mdns_retry.max_restarts.is_some_and(|max| attempt > max) => {
+                    error!("Giving up on mDNS registration after {} attempts: {}", attempt, e);
+                    event_log::warning(
+                        "mDNS",
+                        format!("Services not registered, other devices can't find this PC: {}", e),
+                    );
+                    self.send_only.send_replace(Some(e.to_string()));
+                    return Err(e);
+                }
+                Err(e) => {
+                    let delay = self.mdns_retry.delay(attempt);
+                    warn!("mDNS registration failed (attempt {}), retrying in {:?}: {}", attempt, delay, e);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+        *self.mdns.lock().await = Some(advertisement);
+        self.send_only.send_replace(None);
+
+        // Setup UDP multicast with explicit binding to all interfaces. The
+        // services are already advertised, so a failure here is not send-only
+        match self.setup_multicast().await {
+            Ok(socket) => *self.udp_socket.lock().await = Some(socket),
+            Err(e) => {
+                warn!("UDP multicast setup failed: {}", e);
+                event_log::warning("mDNS", format!("UDP multicast not available: {}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Advertise the friendly name, numbered if another device on the
+    /// network already advertises it
+    async fn pick_advertised_name(&self) {
+        let taken = self.names_in_use().await;
+        let name = apple_records::unique_name(&self.friendly_name, &taken);
+        if name != self.friendly_name {
+            event_log::info(
+                "mDNS",
+                format!("\"{}\" is already in use on the network, advertising as \"{}\"", self.friendly_name, name),
+            );
+        }
+        self.advertised_name.send_replace(name);
+    }
+
+    /// Instance names other devices advertise AirDrop and Companion Link
+    /// under, collected for [`apple_records::NAME_CHECK_TIMEOUT`]
+    async fn names_in_use(&self) -> Vec<String> {
+        let mdns = match (self.mdns_daemon)() {
+            Ok(mdns) => mdns,
+            Err(e) => {
+                warn!("Can't check whether the name is in use: {}", e);
+                return Vec::new();
+            }
+        };
+        let deadline = tokio::time::Instant::now() + apple_records::NAME_CHECK_TIMEOUT;
+        let receivers: Vec<_> = ScanScope::AirDrop
+            .service_types()
+            .iter()
+            .filter_map(|service_type| mdns.browse(service_type).ok())
+            .collect();
+        let names = futures::future::join_all(receivers.iter().map(|receiver| async move {
+            let mut names = Vec::new();
+            while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, receiver.recv_async()).await {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    names.push(info.get_fullname().to_string());
+                }
+            }
+            names
+        }))
+        .await;
+        if let Err(e) = mdns.shutdown() {
+            debug!("Failed to shut down the mDNS daemon of the name check: {}", e);
+        }
+        names.into_iter().flatten().collect()
+    }
+
+    /// Create a daemon and register every service on it
+    fn advertise_services(&self) -> Result<Advertisement> {
+        let mdns = (self.mdns_daemon)().map_err(|e| anyhow!("Failed to initialize mDNS: {}", e))?;
+        match self.register_services(&mdns) {
+            Ok(services) => Ok(Advertisement { daemon: Box::new(mdns), services }),
+            Err(e) => {
+                let _ = mdns.shutdown();
+                Err(e)
+            }
+        }
+    }
+
+    /// Register the services on `mdns`, returning their full names
+    fn register_services(&self, mdns: &dyn MdnsResponder) -> Result<Vec<String>> {
+        // Use Apple-compatible TXT records
+        let name = self.name();
+        let supports_awdl = *self.awdl.borrow() == AwdlAvailability::On;
+        let airdrop_properties = AppleRecords::create_airdrop_txt_records(supports_awdl, &name)?;
+        let companion_properties = AppleRecords::create_companion_txt_records(&name)?;
+        let device_info_properties = AppleRecords::create_device_info_txt_records()?;
+        
+        let hostname = apple_records::hostname()?;
+        
+        // Register AirDrop TCP service on standard port
+        let airdrop_tcp_service = ServiceInfo::new(
+            "_airdrop._tcp.local.",
+            &name,
+            "local.",
+            "",
+            self.https_port,
+            Some(airdrop_properties.clone())
+        )?;
+
+        // Register AirDrop UDP service
+        let airdrop_udp_service = ServiceInfo::new(
+            "_airdrop._udp.local.",
+            &name,
+            "local.",
+            "",
+            self.https_port,
+            Some(airdrop_properties)
+        )?;
+
+        // Register Companion Link service (for device pairing)
+        let companion_service = ServiceInfo::new(
+            "_companion-link._tcp.local.",
+            &name,
+            "local.",
+            "",
+            self.companion_port,
+            Some(companion_properties)
+        )?;
+
+        // Register Device Info service
+        let device_info_service = ServiceInfo::new(
+            "_device-info._tcp.local.",
+            &hostname,
+            "local.",
+            "",
+            self.device_info_port,
+            Some(device_info_properties)
+        )?;
+
+        // Register all services, AirDrop only while receiving is on
+        let advertised = self.receive_gate.settings().advertised();
+        let mut services = Vec::new();
+        if advertised {
+            services.push(airdrop_tcp_service.get_fullname().to_string());
+            mdns.register(airdrop_tcp_service)
+                .map_err(|e| anyhow!("Failed to register AirDrop TCP service: {}", e))?;
+            services.push(airdrop_udp_service.get_fullname().to_string());
+            mdns.register(airdrop_udp_service)
+                .map_err(|e| anyhow!("Failed to register AirDrop UDP service: {}", e))?;
+        }
+        services.push(companion_service.get_fullname().to_string());
+        mdns.register(companion_service)
+            .map_err(|e| anyhow!("Failed to register Companion Link service: {}", e))?;
+        services.push(device_info_service.get_fullname().to_string());
+        mdns.register(device_info_service)
+            .map_err(|e| anyhow!("Failed to register Device Info service: {}", e))?;
+
+        info!("Successfully registered Apple-compatible mDNS services");
+        if advertised {
+            event_log::info("mDNS", "Registered AirDrop, Companion Link and Device Info services");
+        } else {
+            event_log::info("mDNS", "Receiving is off, registered only Companion Link and Device Info");
+        }
+        Ok(services)
+    }
+
+    /// Withdraw every registered service by name, so peers drop it right
+    /// away instead of when its records expire, then shut the daemon down
+    pub async fn unregister_mdns_services(&self) {
+        let Some(advertisement) = self.mdns.lock().await.take() else {
+            return;
+        };
+        for service in &advertisement.services {
+            match advertisement.daemon.unregister(service) {
+                Ok(()) => debug!("Unregistered mDNS service {}", service),
+                Err(e) => warn!("Failed to unregister mDNS service {}: {}", service, e),
+            }
+        }
+        if let Err(e) = advertisement.daemon.shutdown() {
+            warn!("Failed to shut down mDNS daemon: {}", e);
+        }
+    }
+
+    async fn handle_connection(
+        stream: TcpStream,
+        addr: SocketAddr,
+        events: broadcast::Sender<IncomingFile>,
+        conflicts: ConflictResolver,
+        receive_dir: PathBuf,
+        limits: ReceiveLimits,
+        peer_filter: Arc<PeerFilter>,
+        gate: ReceiveGate,
+    ) -> Result<()> {
+        info!("Handling new connection from {}", addr);
+        enable_keepalive(&stream);
+        
+        // Load or generate certificate
+        let acceptor = tls::native_acceptor().await?;
+
+        // A peer that connects and never starts TLS would otherwise hold the task forever
+        let mut stream = match tokio::time::timeout(limits.read_timeout, acceptor.accept(stream)).await {
+            Ok(stream) => stream?,
+            Err(_) => {
+                warn!("Closing idle connection from {}: no TLS handshake within {:?}", addr, limits.read_timeout);
+                return Err(HandshakeError::Timeout(limits.read_timeout).into());
+            }
+        };
+
+        // Senders may reuse the connection for more transfers after the first
+        let mut first = true;
+        loop {
+            let buffer = if first {
+                match read_handshake(&mut stream, MAX_HANDSHAKE_SIZE, HANDSHAKE_TIMEOUT).await {
+                    Ok(buffer) => buffer,
+                    Err(e) => {
+                        warn!("Rejecting handshake from {}: {}", addr, e);
+                        return Err(e.into());
+                    }
+                }
+            } else {
+                match read_handshake(&mut stream, MAX_HANDSHAKE_SIZE, limits.read_timeout).await {
+                    Ok(buffer) => buffer,
+                    // The sender closed the connection, possibly without a TLS
+                    // close_notify, or let it go idle
+                    Err(HandshakeError::Closed | HandshakeError::Timeout(_) | HandshakeError::Io(_)) => return Ok(()),
+                    Err(e) => {
+                        warn!("Rejecting handshake from {}: {}", addr, e);
+                        return Err(e.into());
+                    }
+                }
+            };
+            first = false;
+
+            let handshake = match parse_handshake(&buffer) {
+                Ok(handshake) => handshake,
+                Err(e) => {
+                    warn!("Rejecting handshake from {}: {}", addr, e);
+                    return Err(e.into());
+                }
+            };
+            info!("Received handshake from {}: {:?}", addr, handshake);
+
+            if !peer_filter.permits(addr.ip(), Some(&handshake.sender)) {
+                event_log::warning("AirDrop", format!("Rejected transfer from {} ({})", handshake.sender, addr.ip()));
+                let response = serde_json::json!({
+                    "status": "reject",
+                    "reason": "Sender not allowed",
+                });
+                stream.write_all(serde_json::to_string(&response)?.as_bytes()).await?;
+                stream.write_all(b"\n\n").await?;
+                return Err(anyhow!("Sender {} is not allowed", handshake.sender));
+            }
+
+            let files = handshake.files.iter().map(|file| file.name.clone()).collect();
+            let is_contact = peer_filter.is_contact(addr.ip(), &handshake.sender);
+            let device_id = handshake.sender_id.as_deref();
+            if let Err(reason) = gate.admit(&handshake.sender, device_id, files, is_contact).await {
+                event_log::warning("AirDrop", format!("Rejected transfer from {} ({}): {}", handshake.sender, addr.ip(), reason));
+                let response = serde_json::json!({
+                    "status": "reject",
+                    "reason": reason.to_string(),
+                });
+                stream.write_all(serde_json::to_string(&response)?.as_bytes()).await?;
+                stream.write_all(b"\n\n").await?;
+                return Err(anyhow!("Transfer from {} rejected: {}", handshake.sender, reason));
+            }
+
+            if let Err(e) = check_declared_sizes(&handshake.files, limits.max_file_size) {
+                warn!("Rejecting transfer from {}: {}", addr, e);
+                let response = serde_json::json!({
+                    "status": "reject",
+                    "reason": e.to_string(),
+                });
+                stream.write_all(serde_json::to_string(&response)?.as_bytes()).await?;
+                stream.write_all(b"\n\n").await?;
+                return Err(e.into());
+            }
+
+            // Accept the transfer, announcing what this side supports
+            let local = Capabilities::local(limits.compression);
+            let negotiated = capabilities::negotiate(handshake.capabilities.as_ref(), Some(&local));
+            debug!("Negotiated with {}: {:?}", addr, negotiated);
+            let algorithm = negotiated.compression.as_deref().and_then(Algorithm::from_name);
+            let response = serde_json::json!({
+                "status": "accept",
+                "receiver": handshake.receiver,
+                "capabilities": local,
+            });
+        
+            stream.write_all(serde_json::to_string(&response)?.as_bytes()).await?;
+            stream.write_all(b"\n\n").await?;
+
+            // Receive files
+            tokio::fs::create_dir_all(&receive_dir).await?;
+            for file in handshake.files {
+                // Names come from the peer: keep only a name Windows can create
+                let destination = conflicts.resolve(&receive_dir, &file_name::safe_file_name(&file.name)).await?;
+                let path = destination.path.clone();
+                let received = match receive_to_file(&mut stream, &path, file.size, limits.read_timeout, limits.chunk_size, algorithm).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("Closing connection from {} while receiving {}: {}", addr, file.name, e);
+                        destination.release().await;
+                        return Err(e);
+                    }
+                };
+                info!("Saved file {} to {:?}", file.name, path);
+
+                publish_incoming(&events, IncomingFile {
+                    name: file.name,
+                    size: received,
+                    from: addr,
+                    path,
+                });
+            }
+        }
+    }
+
+    pub async fn start_server(&self) -> Result<()> {
+        if self.http_server.lock().await.is_some() {
+            info!("AirDrop server already running");
+            return Ok(());
+        }
+
+        // Register mDNS services first. Without them peers can't find this
+        // PC, but outgoing transfers still work.
+        if let Err(e) = self.register_mdns_services().await {
+            warn!("Continuing in send-only mode: {}", e);
+        }
+
+        // Initialize and start HTTPS server for AirDrop protocol
+        let mut http_server = AirDropHttpServer::new(
+            self.https_port,
+            self.incoming_events.clone(),
+            self.conflicts.clone(),
+            self.peer_filter.clone(),
+        )
+        .with_receive_dir(self.receive_dir.clone())
+        .with_receive_gate(self.receive_gate.clone())
+        .with_advertised_name(self.advertised_name())
+        .with_ip_mode(self.ip_mode)
+        .with_connection_limits(self.https_limits)
+        .with_max_file_size(self.max_file_size)
+        .with_connection_counter(self.https_connections.clone());
+        if let Err(e) = http_server.initialize().await {
+            if is_tls_error(&e) {
+                self.tls_failures.send_replace(Some(e.to_string()));
+            }
+            return Err(e);
+        }
+        http_server.start().await?;
+        
+        *self.http_server.lock().await = Some(http_server);
+        info!("Started AirDrop HTTPS server on port {}", self.https_port);
+
+        // Keep the old TCP listener for backward compatibility, on every
+        // address the IP mode allows. Only a failure on the first one is
+        // warned about, and it doesn't stop the others.
+        let mut addrs = self.ip_mode.listen_addrs(self.fallback_port).into_iter();
+        if let Some(primary) = addrs.next() {
+            if let Err(e) = self.start_fallback_server(primary).await {
+                // Don't fail completely if fallback server can't start
+                warn!("Failed to start AirDrop fallback server on {}: {}", primary, e);
+            }
+        }
+        for addr in addrs {
+            if let Err(e) = self.start_fallback_server(addr).await {
+                debug!("No AirDrop fallback server on {}: {}", addr, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accept legacy TCP transfers on `addr` and return the address actually bound,
+    /// so port 0 can be used to pick a free one
+    pub async fn start_fallback_server(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        let listener = Arc::new(ip_mode::bind_tcp(addr)?);
+        let local_addr = listener.local_addr()?;
+        info!("Started AirDrop fallback server on {}", local_addr);
+
+        let status = self.status.clone();
+        let events = self.incoming_events.clone();
+        let conflicts = self.conflicts.clone();
+        let receive_dir = self.receive_dir.clone();
+        let limits = ReceiveLimits {
+            max_file_size: self.max_file_size,
+            read_timeout: self.read_timeout,
+            chunk_size: self.chunk_size,
+            compression: self.compression,
+        };
+        let peer_filter = self.peer_filter.clone();
+        let gate = self.receive_gate.clone();
+        let tls_failures = self.tls_failures.clone();
+        let shutdown = self.fallback_shutdown.lock().unwrap().clone();
+
+        // A restart after a panic reuses the bound listener instead of binding again
+        let task = supervisor::global().spawn(format!("AirDrop fallback {}", local_addr), move || {
+            let listener = listener.clone();
+            let status = status.clone();
+            let events = events.clone();
+            let conflicts = conflicts.clone();
+            let receive_dir = receive_dir.clone();
+            let peer_filter = peer_filter.clone();
+            let gate = gate.clone();
+            let tls_failures = tls_failures.clone();
+            let shutdown = shutdown.clone();
+            async move {
+                loop {
+                    let accepted = tokio::select! {
+                        _ = shutdown.cancelled() => break,
+                        accepted = listener.accept() => accepted,
+                    };
+                    match accepted {
+                        Ok((stream, addr)) => {
+                            if !peer_filter.permits(addr.ip(), None) {
+                                event_log::warning("AirDrop", format!("Rejected connection from {}", addr.ip()));
+                                continue;
+                            }
+                            info!("Accepted connection from {}", addr);
+                            status.lock().await.connected();
+
+                            let status = status.clone();
+                            let events = events.clone();
+                            let conflicts = conflicts.clone();
+                            let receive_dir = receive_dir.clone();
+                            let peer_filter = peer_filter.clone();
+                            let gate = gate.clone();
+                            let tls_failures = tls_failures.clone();
+
+                            tokio::spawn(async move {
+                                if let Err(e) = Self::handle_connection(stream, addr, events, conflicts, receive_dir, limits, peer_filter, gate).await {
+                                    error!("Error handling connection: {}", e);
+                                    if is_tls_error(&e) {
+                                        tls_failures.send_replace(Some(e.to_string()));
+                                    }
+                                    event_log::error("AirDrop", format!("Connection from {} failed: {}", addr, e));
+                                    status.lock().await.fail(format!("Connection error: {}", e));
+                                } else {
+                                    status.lock().await.disconnected();
+                                }
+                            });
+                        }
+                        // Usually transient, so the listener is kept
+                        Err(e) => {
+                            warn!("Accept error on {}: {}", local_addr, e);
+                            event_log::warning("AirDrop", format!("Accept error on {}: {}", local_addr, e));
+                            status.lock().await.fail(format!("Accept error: {}", e));
+                            let retry = tokio::time::Instant::now() + ACCEPT_RETRY_DELAY;
+                            if !shutdown.sleep_until(retry).await {
+                                break;
+                            }
+                        }
+                    }
+                }
+                debug!("AirDrop fallback server on {} stopped", local_addr);
+            }
+        });
+        self.fallback_tasks.lock().await.push(task);
+
+        Ok(local_addr)
+    }
+
+    /// Unregister the mDNS services and release every listening socket
+    pub async fn stop_server(&self) -> Result<()> {
+        let http_server = self.http_server.lock().await.take();
+        let running = http_server.is_some();
+        if let Some(server) = http_server {
+            server.stop().await;
+        }
+
+        // Cancel the running loops; servers started later get a new token
+        std::mem::take(&mut *self.fallback_shutdown.lock().unwrap()).cancel();
+        let tasks: Vec<_> = self.fallback_tasks.lock().await.drain(..).collect();
+        for mut task in tasks {
+            if tokio::time::timeout(ACCEPT_LOOP_STOP_TIMEOUT, &mut task).await.is_err() {
+                warn!("AirDrop accept loop didn't stop in time, aborting it");
+                task.abort();
+            }
+        }
+
+        self.unregister_mdns_services().await;
+        *self.udp_socket.lock().await = None;
+        self.send_only.send_replace(None);
+        *self.status.lock().await = AirDropStatus::default();
+
+        if running {
+            info!("AirDrop server stopped");
+            event_log::info("AirDrop", "Server stopped and mDNS services unregistered");
+        }
+        Ok(())
+    }
+
+    /// Send a generated `megabytes` MB file to a fallback server on the
+    /// loopback interface and measure the throughput.
+    ///
+    /// Sender and receiver are fresh instances using this instance's chunk
+    /// size and TLS backend, so the running services and their status are not touched and
+    /// nothing reaches the incoming files list. Only the transfer is timed,
+    /// not writing the payload.
+    pub async fn loopback_benchmark(&self, megabytes: u64) -> Result<BenchmarkResult> {
+        if megabytes == 0 || megabytes > MAX_BENCHMARK_MB {
+            return Err(anyhow!("Benchmark size must be between 1 and {} MB", MAX_BENCHMARK_MB));
+        }
+
+        let dir = std::env::temp_dir().join(format!("airwin_benchmark_{}", Uuid::new_v4().simple()));
+        let result = Self::run_benchmark(&dir, megabytes, self.chunk_size, self.tls_backend).await;
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            warn!("Failed to remove benchmark files in {:?}: {}", dir, e);
+        }
+
+        let result = result?;
+        info!(
+            "Loopback benchmark: {} bytes in {:.3}s, {:.1} MB/s",
+            result.bytes, result.seconds, result.mb_per_s
+        );
+        Ok(result)
+    }
+
+    async fn run_benchmark(
+        dir: &std::path::Path,
+        megabytes: u64,
+        chunk_size: usize,
+        tls_backend: TlsBackend,
+    ) -> Result<BenchmarkResult> {
+        const MB: usize = 1_000_000;
+        tokio::fs::create_dir_all(dir).await?;
+        let source = dir.join("payload.bin");
+        let block: Vec<u8> = (0..MB).map(|i| (i % 251) as u8).collect();
+        let mut file = File::create(&source).await?;
+        for _ in 0..megabytes {
+            file.write_all(&block).await?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        let receiver = AirDrop::new()
+            .with_receive_dir(dir.join("received"))
+            .with_chunk_size(chunk_size);
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await?;
+        let sender = AirDrop::new()
+            .with_self_signed_peers(true)
+            .with_chunk_size(chunk_size)
+            .with_tls_backend(tls_backend);
+
+        let started = Instant::now();
+        let transfer: Result<IncomingFile> = async {
+            sender.send_file_to(addr, source).await?;
+            tokio::time::timeout(BENCHMARK_RECEIVE_TIMEOUT, incoming.recv())
+                .await
+                .map_err(|_| anyhow!("Benchmark receiver didn't report the file within {:?}", BENCHMARK_RECEIVE_TIMEOUT))?
+                .context("Benchmark receiver stopped")
+        }
+        .await;
+        let seconds = started.elapsed().as_secs_f64();
+        receiver.stop_server().await?;
+
+        let bytes = transfer?.size;
+        Ok(BenchmarkResult {
+            bytes,
+            seconds,
+            mb_per_s: bytes as f64 / seconds.max(f64::EPSILON) / MB as f64,
+            chunk_size: chunk_size_for(chunk_size, bytes),
+            tls_backend,
+        })
+    }
+
+    /// Re-register the mDNS services and rejoin multicast groups after the
+    /// network interfaces changed. Returns false if the server isn't running.
+    /// In send-only mode this is another attempt at registering.
+    pub async fn refresh_network_services(&self) -> Result<bool> {
+        if self.http_server.lock().await.is_none() {
+            return Ok(false);
+        }
+        // Withdraw the old records first, the AirDrop ones may not come back
+        self.unregister_mdns_services().await;
+
+        // Drop the old socket so the new one can bind, and retry every interface
+        *self.udp_socket.lock().await = None;
+        self.failed_multicast_joins.lock().await.clear();
+
+        self.register_mdns_services().await?;
+        Ok(true)
+    }
+
+    /// Re-register the mDNS services whenever AWDL availability changes,
+    /// so `supports_awdl` never advertises a transport that is not running
+    pub fn watch_awdl_availability(&self) -> JoinHandle<()> {
+        let airdrop = self.clone();
+        let mut awdl = self.awdl.clone();
+        tokio::spawn(async move {
+            while awdl.changed().await.is_ok() {
+                let availability = *awdl.borrow_and_update();
+                match airdrop.refresh_network_services().await {
+                    Ok(true) => event_log::info(
+                        "mDNS",
+                        format!("AWDL is now {:?}, services re-registered", availability),
+                    ),
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to re-register services after AWDL change: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Start or stop the server to match the user's setting
+    pub async fn set_enabled(&self, enabled: bool) -> Result<()> {
+        if enabled {
+            self.start_server().await
+        } else {
+            self.stop_server().await
+        }
+    }
+
+    pub async fn send_file(&self, file_path: PathBuf) -> Result<()> {
+        preflight(&file_path).await?;
+        self.status.lock().await.connecting();
+        
+        let file = File::open(&file_path)
+            .await
+            .context("Failed to open file")?;
+        
+        let metadata = file.metadata().await?;
+        let transfer = FileTransfer {
+            id: Uuid::new_v4().to_string(),
+            name: file_name::wire_name(&file_path).name,
+            size: metadata.len(),
+            mime_type: mime_type::detect_mime_type(&file_path).await,
+        };
+
+        let connector = Connector::new(self.tls_backend, tls::sender_certificate()?, self.accept_self_signed)?;
+
+        // Try IPv4 connection first
+        let mut connection = self.connection.lock().await;
+        if let Some(stream) = connection.take() {
+            info!("Sending file over IPv4 connection");
+            self.status.lock().await.connected();
+            
+            let peer_addr = stream.peer_addr()?;
+            let (mut tls_stream, _) = connector.connect("AirDrop", stream).await?;
+            
+            let handshake = AirDropHandshake {
+                sender: self.name(),
+                sender_id: device_id().await,
+                receiver: "AirDrop".to_string(),
+                files: vec![transfer],
+                // The answer isn't read here, so no compression can be agreed on
+                capabilities: Some(Capabilities::local(CompressionMode::Off)),
+            };
+
+            let handshake_json = serde_json::to_string(&handshake)?;
+            tls_stream.write_all(handshake_json.as_bytes()).await?;
+            tls_stream.write_all(b"\n\n").await?;
+
+            let mut file = File::open(&file_path).await?;
+            let mut buffer = vec![0; chunk_size_for(self.chunk_size, metadata.len())];
+            let mut sent = 0u64;
+            
+            self.status.lock().await.start_transfer(self.clock.now());
+            
+            if metadata.len() == 0 {
+                *self.transfer_progress.lock().await = 100.0;
+                self.status.lock().await.set_progress(100.0);
+            }
+            while let Ok(n) = file.read(&mut buffer).await {
+                if n == 0 { break; }
+                tls_stream.write_all(&buffer[..n]).await?;
+                sent += n as u64;
+                let progress = send_progress(sent, metadata.len());
+                *self.transfer_progress.lock().await = progress;
+                self.status.lock().await.set_progress(progress);
+            }
+
+            // The connection stays open for the next file
+            self.status.lock().await.finish_transfer();
+            
+            // After transfer, establish a new connection for future use
+            let new_stream = TcpStream::connect(peer_addr).await?;
+            *connection = Some(new_stream);
+        } else {
+            self.status.lock().await.fail("No active connection available");
+            return Err(anyhow!("No active connection available"));
+        }
+        
+        *self.current_file.lock().await = Some(file_path);
+        Ok(())
+    }
+}
+
+/// AirDrop to devices advertising an AirDrop or Companion Link service
+#[async_trait::async_trait]
+impl TransferProtocol for AirDrop {
+    fn name(&self) -> &'static str {
+        "AirDrop"
+    }
+
+    fn can_handle(&self, device: &DiscoveredDevice) -> bool {
+        device.airdrop_addr().is_some()
+    }
+
+    fn actions(&self, device: &DiscoveredDevice) -> Vec<DeviceAction> {
+        let capabilities = device.capabilities();
+        [DeviceAction::SendFile, DeviceAction::SendLink]
+            .into_iter()
+            .filter(|action| capabilities.allows(*action))
+            .collect()
+    }
+
+    async fn advertise(&self) -> Result<()> {
+        self.start_server().await
+    }
+
+    async fn discover(&self, discovery: &DeviceDiscovery, timeout: Duration, cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+        let mut devices = discovery.browse(timeout, ScanScope::AirDrop.service_types(), cancel).await;
+        devices.retain(|device| self.can_handle(device));
+        devices
+    }
+
+    async fn send(&self, device: &DiscoveredDevice, payload: Payload) -> Result<Vec<TransferOutcome>> {
+        let addr = device
+            .airdrop_addr()
+            .ok_or_else(|| anyhow!("{} doesn't accept AirDrop transfers", device.name))?;
+        match payload {
+            Payload::Files(paths) => self.send_files_to(addr, paths).await,
+            Payload::File { path, mime_type } => Ok(vec![self.send_file_with_mime(addr, path, Some(mime_type)).await?]),
+            Payload::Folder(folder) => Ok(vec![self.send_folder_to(addr, folder).await?]),
+            Payload::Link(url) => {
+                let encoding = LinkEncoding::for_recipient(&device.txt_records);
+                Ok(vec![self.send_link_to(addr, &url, encoding).await?])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::{Clock, FakeClock};
+    use tokio::net::TcpListener;
+
+    fn incoming(name: &str) -> IncomingFile {
+        IncomingFile {
+            name: name.to_string(),
+            size: 1,
+            from: SocketAddr::from((Ipv4Addr::LOCALHOST, 7000)),
+            path: PathBuf::from(name),
+        }
+    }
+
+    #[test]
+    fn test_publish_incoming_drops_when_full() {
+        let (events, mut receiver) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+
+        for i in 0..INCOMING_EVENTS_CAPACITY + 5 {
+            publish_incoming(&events, incoming(&format!("file_{}", i)));
+        }
+
+        // The oldest events are kept and the subscriber never lags
+        assert_eq!(receiver.try_recv().unwrap().name, "file_0");
+        let mut received = 1;
+        while receiver.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, INCOMING_EVENTS_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_transfer_is_byte_exact() {
+        let receiver = AirDrop::new();
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        // Several chunks, with a pattern that catches reordered or dropped bytes
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let name = format!("airwin_loopback_{}.bin", Uuid::new_v4().simple());
+        // Outside the receive directory, so the received copy keeps its name
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let source = source_dir.join(&name);
+        std::fs::write(&source, &data).unwrap();
+
+        let sender = AirDrop::new().with_self_signed_peers(true);
+        let outcome = sender.send_file_to(addr, source).await.unwrap();
+        assert_eq!(outcome.total_bytes, data.len() as u64);
+
+        let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name, name);
+        assert_eq!(event.size, data.len() as u64);
+
+        let received = std::env::temp_dir().join(&name);
+        assert_eq!(std::fs::read(&received).unwrap(), data);
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(received).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    /// Send `data` over loopback with the given chunk sizes and return the received bytes
+    async fn loopback_with_chunks(data: &[u8], send_chunk: usize, receive_chunk: usize) -> Vec<u8> {
+        let sender = AirDrop::new().with_self_signed_peers(true).with_chunk_size(send_chunk);
+        loopback(data, sender, AirDrop::new().with_chunk_size(receive_chunk)).await
+    }
+
+    /// Send `data` from `sender` to `receiver` over loopback and return the received bytes
+    async fn loopback(data: &[u8], sender: AirDrop, receiver: AirDrop) -> Vec<u8> {
+        loopback_as(data, "bin", sender, receiver).await
+    }
+
+    /// [`loopback`] with a file of the given extension, which sets its MIME type
+    async fn loopback_as(data: &[u8], extension: &str, sender: AirDrop, receiver: AirDrop) -> Vec<u8> {
+        let receive_dir = std::env::temp_dir().join(format!("airwin_chunks_{}", Uuid::new_v4().simple()));
+        let receiver = receiver.with_receive_dir(receive_dir.clone());
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let source = std::env::temp_dir().join(format!("airwin_chunks_{}.{}", Uuid::new_v4().simple(), extension));
+        std::fs::write(&source, data).unwrap();
+        sender.send_file_to(addr, source.clone()).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(30), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let received = std::fs::read(&event.path).unwrap();
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(source).unwrap();
+        std::fs::remove_dir_all(receive_dir).unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn test_compressed_and_plain_transfers_produce_identical_files() {
+        // Compressible text followed by bytes that aren't, across several chunks
+        let mut data = b"AirWin compression round trip\n".repeat(40_000);
+        data.extend((0..500_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8));
+        let sender = |mode| AirDrop::new().with_self_signed_peers(true).with_compression(mode);
+        let receiver = |mode| AirDrop::new().with_compression(mode);
+
+        let plain = loopback(&data, sender(CompressionMode::Off), receiver(CompressionMode::Off)).await;
+        let zstd = loopback(&data, sender(CompressionMode::Zstd), receiver(CompressionMode::Auto)).await;
+        // Already compressed by its type: sent in stored frames
+        let jpeg = loopback_as(&data, "jpg", sender(CompressionMode::Auto), receiver(CompressionMode::Auto)).await;
+        // The receiver doesn't offer compression, so none is used
+        let refused = loopback(&data, sender(CompressionMode::Auto), receiver(CompressionMode::Off)).await;
+
+        assert_eq!(plain, data);
+        assert_eq!(zstd, data);
+        assert_eq!(jpeg, data);
+        assert_eq!(refused, data);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_size_does_not_change_received_bytes() {
+        // Not a multiple of either chunk size, so the last chunk is partial
+        let data: Vec<u8> = (0..1_000_003u32).map(|i| (i % 251) as u8).collect();
+
+        let small = loopback_with_chunks(&data, MIN_CHUNK_SIZE, MAX_CHUNK_SIZE).await;
+        let large = loopback_with_chunks(&data, MAX_CHUNK_SIZE, MIN_CHUNK_SIZE).await;
+        assert_eq!(small, data);
+        assert_eq!(large, data);
+    }
+
+    #[test]
+    fn test_chunk_size_is_bounded_and_fits_the_file() {
+        assert_eq!(chunk_size_for(DEFAULT_CHUNK_SIZE, 10_000_000), DEFAULT_CHUNK_SIZE);
+        assert_eq!(chunk_size_for(1, 10_000_000), MIN_CHUNK_SIZE);
+        assert_eq!(chunk_size_for(usize::MAX, 10_000_000), MAX_CHUNK_SIZE);
+        assert_eq!(chunk_size_for(DEFAULT_CHUNK_SIZE, 100), 100);
+        assert_eq!(chunk_size_for(DEFAULT_CHUNK_SIZE, 0), 1);
+    }
+
+    /// Send two files from `sender` to a fallback receiver, `pause` apart,
+    /// and return both incoming events
+    async fn send_twice(sender: &AirDrop, receiver: AirDrop, pause: Duration) -> (IncomingFile, IncomingFile) {
+        let dir = std::env::temp_dir().join(format!("airwin_pool_{}", Uuid::new_v4().simple()));
+        let receiver = receiver.with_receive_dir(dir.join("received"));
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        for (i, pause) in [Duration::ZERO, pause].into_iter().enumerate() {
+            tokio::time::sleep(pause).await;
+            let source = dir.join(format!("file_{}.txt", i));
+            std::fs::write(&source, format!("file {}", i)).unwrap();
+            sender.send_file_to(addr, source).await.unwrap();
+            let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            events.push(event);
+        }
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        let second = events.pop().unwrap();
+        (events.pop().unwrap(), second)
+    }
+
+    #[tokio::test]
+    async fn test_sequential_sends_reuse_one_connection() {
+        let sender = AirDrop::new()
+            .with_self_signed_peers(true)
+            .with_connection_pool(Duration::from_secs(10));
+        let (first, second) = send_twice(&sender, AirDrop::new(), Duration::ZERO).await;
+
+        assert_eq!(sender.pool.as_ref().unwrap().opened(), 1);
+        // Same source port, so the same TCP connection
+        assert_eq!(first.from, second.from);
+        assert_eq!((first.name.as_str(), second.name.as_str()), ("file_0.txt", "file_1.txt"));
+        assert_eq!((first.size, second.size), (6, 6));
+    }
+
+    #[tokio::test]
+    async fn test_pooled_connection_closed_by_peer_is_replaced() {
+        let sender = AirDrop::new()
+            .with_self_signed_peers(true)
+            .with_connection_pool(Duration::from_secs(10));
+        // The receiver drops the idle connection long before the pool would
+        let receiver = AirDrop::new().with_read_timeout(Duration::from_millis(200));
+        let (first, second) = send_twice(&sender, receiver, Duration::from_millis(600)).await;
+
+        assert_eq!(sender.pool.as_ref().unwrap().opened(), 2);
+        assert_ne!(first.from, second.from);
+    }
+
+    #[tokio::test]
+    async fn test_status_after_a_send_follows_the_after_transfer_setting() {
+        let dir = std::env::temp_dir().join(format!("airwin_after_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let receiver = AirDrop::new().with_receive_dir(dir.join("received"));
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let source = dir.join("file.txt");
+        std::fs::write(&source, "file").unwrap();
+
+        let disconnecting = AirDrop::new()
+            .with_self_signed_peers(true)
+            .with_after_transfer(AfterTransfer::Disconnect);
+        disconnecting.send_file_to(addr, source.clone()).await.unwrap();
+        assert_eq!(disconnecting.status.lock().await.connection, ConnectionState::Idle);
+
+        // Connected while the connection is kept, idle once the window closes it
+        let staying = AirDrop::new()
+            .with_self_signed_peers(true)
+            .with_connection_pool(Duration::from_millis(300));
+        staying.send_file_to(addr, source).await.unwrap();
+        let status = staying.status.lock().await.clone();
+        assert_eq!(status.connection, ConnectionState::Connected);
+        assert_eq!(status.transfer, None);
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert_eq!(staying.status.lock().await.connection, ConnectionState::Idle);
+
+        receiver.stop_server().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_receiver_announces_capabilities_to_legacy_and_aware_senders() {
+        let legacy = r#"{"sender":"iPhone","receiver":"AirWin","files":[]}"#;
+        let aware = serde_json::to_string(&AirDropHandshake {
+            sender: "PC".to_string(),
+            sender_id: None,
+            receiver: "AirWin".to_string(),
+            files: Vec::new(),
+            capabilities: Some(Capabilities::local(CompressionMode::Auto)),
+        })
+        .unwrap();
+        assert_eq!(parse_handshake(legacy.as_bytes()).unwrap().capabilities, None);
+        assert!(aware.contains("\"capabilities\""));
+
+        let receiver = AirDrop::new();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let connector = Connector::new(TlsBackend::NativeTls, tls::sender_certificate().unwrap(), true).unwrap();
+        for handshake in [legacy, aware.as_str()] {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (mut stream, _) = connector.connect("AirWin", stream).await.unwrap();
+            let response = offer(&mut stream, handshake).await.unwrap();
+            assert_eq!(response["status"], "accept");
+            let announced: Capabilities = serde_json::from_value(response["capabilities"].clone()).unwrap();
+            assert_eq!(announced, Capabilities::local(CompressionMode::Off));
+        }
+        receiver.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_both_tls_backends_complete_a_loopback_transfer() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        for backend in [TlsBackend::NativeTls, TlsBackend::Rustls] {
+            let sender = AirDrop::new().with_self_signed_peers(true).with_tls_backend(backend);
+            assert_eq!(loopback(&data, sender, AirDrop::new()).await, data, "{:?}", backend);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_loopback_benchmark_reports_throughput() {
+        let airdrop = AirDrop::new().with_chunk_size(MIN_CHUNK_SIZE);
+        let result = airdrop.loopback_benchmark(2).await.unwrap();
+        assert_eq!(result.bytes, 2_000_000);
+        assert_eq!(result.chunk_size, MIN_CHUNK_SIZE);
+        assert!(result.seconds > 0.0);
+        assert!(result.mb_per_s > 0.0);
+
+        assert!(airdrop.loopback_benchmark(0).await.is_err());
+        assert!(airdrop.loopback_benchmark(MAX_BENCHMARK_MB + 1).await.is_err());
+    }
+
+    /// Loopback throughput per chunk size, for choosing the default
+    #[tokio::test]
+    #[ignore]
+    async fn bench_chunk_sizes() {
+        let data: Vec<u8> = (0..64 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+        for chunk in [8 * 1024, DEFAULT_CHUNK_SIZE, 256 * 1024, MAX_CHUNK_SIZE] {
+            let started = Instant::now();
+            let received = loopback_with_chunks(&data, chunk, chunk).await;
+            let elapsed = started.elapsed();
+            assert_eq!(received.len(), data.len());
+            println!(
+                "{} KiB chunks: {:.1} MB/s",
+                chunk / 1024,
+                data.len() as f64 / elapsed.as_secs_f64() / 1_000_000.0
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preflight_rejects_missing_file_and_folder() {
+        let dir = std::env::temp_dir().join(format!("airwin_preflight_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("note.txt");
+        std::fs::write(&file, b"ciao").unwrap();
+        assert_eq!(preflight(&file).await.unwrap(), 4);
+
+        let missing = dir.join("missing.txt");
+        assert!(matches!(preflight(&missing).await, Err(PreflightError::NotFound(path)) if path == missing));
+        assert!(matches!(preflight(&dir).await, Err(PreflightError::NotAFile(path)) if path == dir));
+
+        // The send fails before any connection is attempted
+        let airdrop = AirDrop::new();
+        let unreachable = SocketAddr::from((Ipv4Addr::LOCALHOST, 9));
+        let error = airdrop.send_file_to(unreachable, missing.clone()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<PreflightError>(), Some(PreflightError::NotFound(_))));
+        let status = airdrop.get_status().await;
+        assert_eq!(status.connection, ConnectionState::Idle);
+        assert!(status.error.unwrap().contains("missing.txt"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_link_encoding_per_recipient() {
+        let records = |pairs: &[(&str, &str)]| -> std::collections::HashMap<String, String> {
+            pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+        };
+
+        // Another AirWin, through its AirDrop or Companion Link records
+        let airwin = AppleRecords::create_airdrop_txt_records(false, "PC").unwrap();
+        assert_eq!(LinkEncoding::for_recipient(&airwin), LinkEncoding::InternetShortcut);
+        let companion = AppleRecords::create_companion_txt_records("PC").unwrap();
+        assert_eq!(LinkEncoding::for_recipient(&companion), LinkEncoding::InternetShortcut);
+
+        let mac = records(&[("model", "MacBookPro18,1"), ("supports_url", "1")]);
+        assert_eq!(LinkEncoding::for_recipient(&mac), LinkEncoding::UriList);
+        let iphone = records(&[("rpMd", "iPhone15,2")]);
+        assert_eq!(LinkEncoding::for_recipient(&iphone), LinkEncoding::UriList);
+        // Found over BLE, no records
+        assert_eq!(LinkEncoding::for_recipient(&records(&[])), LinkEncoding::UriList);
+
+        assert_eq!(LinkEncoding::UriList.mime_type(), "text/uri-list");
+        assert_eq!(LinkEncoding::InternetShortcut.mime_type(), "application/x-mswinurl");
+    }
+
+    #[tokio::test]
+    async fn test_link_send_leaves_no_temp_file() {
+        let root = std::env::temp_dir().join(format!("airwin_link_{}", Uuid::new_v4().simple()));
+        let receiver = AirDrop::new().with_receive_dir(root.join("received"));
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let archives = root.join("archives");
+        std::fs::create_dir_all(&archives).unwrap();
+        let sender = AirDrop::new().with_self_signed_peers(true).with_archive_dir(archives.clone());
+        sender
+            .send_link_to(addr, "https://example.com/a?b=c", LinkEncoding::InternetShortcut)
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name, "link.url");
+        assert!(std::fs::read_to_string(&event.path).unwrap().contains("URL=https://example.com/a?b=c"));
+        assert_eq!(std::fs::read_dir(&archives).unwrap().count(), 0);
+
+        sender.send_link_to(addr, "https://example.com/d", LinkEncoding::UriList).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name, "link.uri");
+        assert_eq!(std::fs::read_to_string(&event.path).unwrap(), "https://example.com/d\r\n");
+        assert_eq!(std::fs::read_dir(&archives).unwrap().count(), 0);
+
+        // A failed send cleans up as well
+        receiver.stop_server().await.unwrap();
+        let closed = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap();
+        assert!(sender.send_link_to(closed, "https://example.com", LinkEncoding::UriList).await.is_err());
+        assert_eq!(std::fs::read_dir(&archives).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_loopback_keeps_unicode_name() {
+        let receiver = AirDrop::new();
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let name = format!("vacanze 🏖️ à Noël {}.txt", Uuid::new_v4().simple());
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let source = source_dir.join(&name);
+        std::fs::write(&source, b"ciao").unwrap();
+
+        let sender = AirDrop::new().with_self_signed_peers(true);
+        sender.send_file_to(addr, source).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name, name);
+        assert_eq!(event.path.file_name().and_then(|n| n.to_str()), Some(name.as_str()));
+        assert_eq!(std::fs::read(&event.path).unwrap(), b"ciao");
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(event.path).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_is_peer_unreachable() {
+        // Bound and released, so nothing listens on it
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap();
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let path = source_dir.join("note.txt");
+        std::fs::write(&path, b"ciao").unwrap();
+
+        let retry = Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(10),
+            reset_after: Duration::ZERO,
+            max_restarts: Some(1),
+        };
+        let sender = AirDrop::new().with_connect_retry(retry);
+        let error = sender.send_file_to(addr, path).await.unwrap_err();
+        match error.downcast_ref::<AirWinError>() {
+            Some(AirWinError::PeerUnreachable(peer)) => assert_eq!(*peer, addr),
+            other => panic!("expected PeerUnreachable, got {:?}: {}", other, error),
+        }
+        assert!(error.to_string().contains("AirDrop may be disabled"));
+
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_changed_certificate_stops_the_send() {
+        let receive_dir = std::env::temp_dir().join(format!("airwin_pins_{}", Uuid::new_v4().simple()));
+        let receiver = AirDrop::new().with_receive_dir(receive_dir.clone());
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let pins_path = std::env::temp_dir().join(format!("airwin_pins_{}.json", Uuid::new_v4().simple()));
+        let mut pins = PinStore::load(&pins_path);
+        pins.pin_if_unknown(&addr.ip().to_string(), &cert_pins::fingerprint(b"another certificate"));
+
+        let source = std::env::temp_dir().join(format!("airwin_pins_{}.txt", Uuid::new_v4().simple()));
+        std::fs::write(&source, b"ciao").unwrap();
+        let sender = AirDrop::new().with_self_signed_peers(true).with_cert_pinning(pins);
+        let changes = sender.certificate_changes();
+        let error = sender.send_file_to(addr, source.clone()).await.unwrap_err();
+        assert!(
+            matches!(error.downcast_ref::<AirWinError>(), Some(AirWinError::CertificateChanged(_))),
+            "expected CertificateChanged, got {}",
+            error
+        );
+        assert_eq!(changes.borrow().as_deref(), Some(addr.ip().to_string().as_str()));
+        // Nothing reached the receiver
+        assert!(std::fs::read_dir(&receive_dir).map_or(true, |mut entries| entries.next().is_none()));
+
+        // Once forgotten, the new certificate is accepted and pinned
+        sender.forget_certificate(&addr.ip().to_string()).await.unwrap();
+        sender.send_file_to(addr, source.clone()).await.unwrap();
+        let saved = PinStore::load(&pins_path);
+        assert!(matches!(
+            saved.check(&addr.ip().to_string(), &cert_pins::fingerprint(b"another certificate")),
+            PinCheck::Mismatch { .. }
+        ));
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(source).unwrap();
+        let _ = std::fs::remove_file(pins_path);
+        let _ = std::fs::remove_dir_all(receive_dir);
+    }
+
+    #[tokio::test]
+    async fn test_multi_file_send_reports_overall_progress() {
+        let receiver = AirDrop::new();
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let big = source_dir.join("big.bin");
+        let small = source_dir.join("small.txt");
+        std::fs::write(&big, vec![7u8; 300 * 1024]).unwrap();
+        std::fs::write(&small, b"ciao").unwrap();
+
+        let sender = AirDrop::new().with_self_signed_peers(true);
+        let outcomes = sender.send_files_to(addr, vec![big, small]).await.unwrap();
+        assert_eq!(outcomes.len(), 2);
+
+        let progress = sender.get_send_progress().await.unwrap();
+        assert_eq!(progress.current_file_index, 1);
+        assert_eq!(progress.file_count, 2);
+        assert_eq!(progress.current_file_name, "small.txt");
+        assert_eq!(progress.overall_progress, 100.0);
+
+        for expected in ["big.bin", "small.txt"] {
+            let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event.name, expected);
+            std::fs::remove_file(event.path).unwrap();
+        }
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_loopback_empty_file() {
+        let receiver = AirDrop::new();
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+
+        let name = format!("airwin_empty_{}.txt", Uuid::new_v4().simple());
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let source = source_dir.join(&name);
+        std::fs::write(&source, b"").unwrap();
+
+        let sender = AirDrop::new().with_self_signed_peers(true);
+        let outcome = tokio::time::timeout(Duration::from_secs(10), sender.send_file_to(addr, source))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.total_bytes, 0);
+        assert_eq!(*sender.transfer_progress.lock().await, 100.0);
+
+        let event = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.name, name);
+        assert_eq!(event.size, 0);
+
+        let received = std::env::temp_dir().join(&name);
+        assert_eq!(std::fs::metadata(&received).unwrap().len(), 0);
+        assert!(!part_path(&received).exists());
+
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(received).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[test]
+    fn test_send_progress_of_empty_file_is_complete() {
+        assert_eq!(send_progress(0, 0), 100.0);
+        assert_eq!(send_progress(50, 200), 25.0);
+        // A file that grew while being sent never goes past 100%
+        assert_eq!(send_progress(300, 200), 100.0);
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind(("0.0.0.0", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Records the services registered and unregistered on it
+    #[derive(Clone, Default)]
+    struct RecordingResponder {
+        registered: Arc<std::sync::Mutex<Vec<String>>>,
+        unregistered: Arc<std::sync::Mutex<Vec<String>>>,
+        shut_down: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl MdnsResponder for RecordingResponder {
+        fn register(&self, service: ServiceInfo) -> mdns_sd::Result<()> {
+            self.registered.lock().unwrap().push(service.get_fullname().to_string());
+            Ok(())
+        }
+
+        fn unregister(&self, fullname: &str) -> mdns_sd::Result<()> {
+            assert!(!self.shut_down.load(Ordering::SeqCst), "unregistered {} after shutdown", fullname);
+            self.unregistered.lock().unwrap().push(fullname.to_string());
+            Ok(())
+        }
+
+        fn shutdown(&self) -> mdns_sd::Result<()> {
+            self.shut_down.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_unregisters_every_mdns_service() {
+        let airdrop = AirDrop::new().with_friendly_name("AirWin Test".to_string());
+        let responder = RecordingResponder::default();
+        let services = airdrop.register_services(&responder).unwrap();
+        *airdrop.mdns.lock().await = Some(Advertisement { daemon: Box::new(responder.clone()), services });
+
+        airdrop.stop_server().await.unwrap();
+
+        let registered = responder.registered.lock().unwrap().clone();
+        assert_eq!(registered.len(), 4);
+        for service_type in ["_airdrop._tcp", "_airdrop._udp", "_companion-link._tcp", "_device-info._tcp"] {
+            assert!(
+                registered.iter().any(|name| name.contains(service_type)),
+                "{} not registered: {:?}", service_type, registered
+            );
+        }
+        assert_eq!(*responder.unregistered.lock().unwrap(), registered);
+        assert!(responder.shut_down.load(Ordering::SeqCst));
+        assert!(airdrop.mdns.lock().await.is_none());
+
+        // Nothing left to unregister
+        airdrop.unregister_mdns_services().await;
+        assert_eq!(responder.unregistered.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_failed_mdns_registration_still_sends() {
+        let no_retry = Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            reset_after: Duration::ZERO,
+            max_restarts: Some(1),
+        };
+        let ports = Ports {
+            airdrop_https: free_port(),
+            airdrop_fallback: free_port(),
+            ..Ports::default()
+        };
+        let sender = AirDrop::new()
+            .with_ports(ports)
+            .with_self_signed_peers(true)
+            .with_mdns_daemon(|| Err(mdns_sd::Error::Msg("simulated failure".to_string())), no_retry);
+        let send_only = sender.send_only();
+
+        // The server starts anyway, in send-only mode
+        sender.start_server().await.unwrap();
+        assert!(send_only.borrow().as_deref().is_some_and(|reason| reason.contains("simulated failure")));
+        assert!(sender.mdns.lock().await.is_none());
+
+        let receiver = AirDrop::new();
+        let mut incoming = receiver.subscribe_incoming();
+        let addr = receiver
+            .start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let source_dir = std::env::temp_dir().join(format!("airwin_src_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&source_dir).unwrap();
+        let name = format!("airwin_send_only_{}.txt", Uuid::new_v4().simple());
+        std::fs::write(source_dir.join(&name), b"still sending").unwrap();
+
+        let outcome = sender.send_file_to(addr, source_dir.join(&name)).await.unwrap();
+        assert_eq!(outcome.total_bytes, 13);
+        let received = tokio::time::timeout(Duration::from_secs(10), incoming.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        sender.stop_server().await.unwrap();
+        assert!(send_only.borrow().is_none());
+        receiver.stop_server().await.unwrap();
+        std::fs::remove_file(received.path).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stop_ends_every_accept_loop() {
+        let server = AirDrop::new();
+        let mut addrs = vec![server.start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).await.unwrap()];
+        // Not every machine running the tests has IPv6
+        if let Ok(addr) = server.start_fallback_server(SocketAddr::from((Ipv6Addr::LOCALHOST, 0))).await {
+            addrs.push(addr);
+        }
+
+        server.stop_server().await.unwrap();
+
+        let health = supervisor::global().health();
+        for addr in &addrs {
+            let name = format!("AirDrop fallback {}", addr);
+            assert!(health.iter().all(|task| task.name != name), "{}", name);
+            // The listener was released with its loop
+            assert!(std::net::TcpListener::bind(addr).is_ok(), "{}", addr);
+        }
+
+        // Servers started after a stop accept again
+        let addr = server.start_fallback_server(SocketAddr::from((Ipv4Addr::LOCALHOST, 0))).await.unwrap();
+        assert!(TcpStream::connect(addr).await.is_ok());
+        server.stop_server().await.unwrap();
+    }
+
+    #[test]
+    fn test_status_accept_transfer_idle() {
+        let mut status = AirDropStatus::default();
+        assert!(status.is_ready());
+
+        // Accepting a connection doesn't make up a transfer
+        status.connected();
+        assert_eq!(status.connection, ConnectionState::Connected);
+        assert_eq!(status.progress(), None);
+
+        status.start_transfer(Utc::now());
+        status.set_progress(40.0);
+        assert_eq!(status.progress(), Some(40.0));
+        assert!(!status.is_ready());
+
+        // Finishing keeps the connection, closing it ends everything
+        status.finish_transfer();
+        assert_eq!(status.connection, ConnectionState::Connected);
+        assert_eq!(status.progress(), None);
+        status.disconnected();
+        assert_eq!(status, AirDropStatus::default());
+    }
+
+    #[test]
+    fn test_remaining_time_follows_the_clock() {
+        let clock = FakeClock::new("2024-01-01T00:00:00Z".parse().unwrap());
+        let mut status = AirDropStatus::default();
+        status.start_transfer(clock.now());
+        assert_eq!(status.remaining(clock.now()), None);
+
+        clock.advance(Duration::from_secs(10));
+        status.set_progress(25.0);
+        assert_eq!(status.remaining(clock.now()), Some(Duration::from_secs(30)));
+
+        status.set_progress(100.0);
+        assert_eq!(status.remaining(clock.now()), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_status_failure_and_recovery() {
+        let mut status = AirDropStatus::default();
+        status.connecting();
+        assert!(!status.is_ready());
+
+        status.start_transfer(Utc::now());
+        status.fail("Connection reset");
+        assert_eq!(status.connection, ConnectionState::Idle);
+        assert_eq!(status.transfer, None);
+        assert_eq!(status.error.as_deref(), Some("Connection reset"));
+
+        // Progress without a transfer is ignored
+        status.set_progress(50.0);
+        assert_eq!(status.progress(), None);
+
+        status.connecting();
+        assert_eq!(status.error, None);
+    }
+
+    #[test]
+    fn test_transfer_outcome_average_speed() {
+        let outcome = TransferOutcome {
+            file_name: "photo.jpg".to_string(),
+            total_bytes: 4_000_000,
+            elapsed: Duration::from_secs(2),
+            peer: "192.168.1.100:8771".to_string(),
+        };
+        assert_eq!(outcome.average_speed(), 2_000_000.0);
+
+        // An instant transfer must not divide by zero
+        let instant = TransferOutcome { elapsed: Duration::ZERO, ..outcome };
+        assert!(instant.average_speed().is_finite());
+    }
+
+    fn declared(name: &str, size: u64) -> FileTransfer {
+        FileTransfer {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            size,
+            mime_type: "application/octet-stream".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_huge_declared_size_is_rejected() {
+        // Checked against the handshake alone: nothing is allocated for the file
+        let files = vec![declared("small.txt", 10), declared("bogus.bin", 10 * 1024 * 1024 * 1024)];
+
+        let result = check_declared_sizes(&files, DEFAULT_MAX_FILE_SIZE);
+        assert!(matches!(
+            result,
+            Err(HandshakeError::FileTooLarge { ref name, limit: DEFAULT_MAX_FILE_SIZE, .. }) if name == "bogus.bin"
+        ));
+        assert!(check_declared_sizes(&files[..1], DEFAULT_MAX_FILE_SIZE).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_receive_streams_exactly_declared_bytes() {
+        let (mut peer, mut stream) = tokio::io::duplex(1024);
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let sent = data.clone();
+        tokio::spawn(async move {
+            peer.write_all(&sent).await.unwrap();
+            // Bytes of the next file must not end up in this one
+            peer.write_all(b"next").await.unwrap();
+        });
+
+        let path = std::env::temp_dir().join(format!("airwin_receive_{}.bin", Uuid::new_v4().simple()));
+        let received = receive_to_file(&mut stream, &path, data.len() as u64, READ_TIMEOUT, DEFAULT_CHUNK_SIZE, None).await.unwrap();
+
+        assert_eq!(received, data.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_empty_file() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        tokio::spawn(async move {
+            peer.write_all(b"next").await.unwrap();
+        });
+
+        let path = std::env::temp_dir().join(format!("airwin_receive_{}.bin", Uuid::new_v4().simple()));
+        let received = receive_to_file(&mut stream, &path, 0, READ_TIMEOUT, DEFAULT_CHUNK_SIZE, None).await.unwrap();
+
+        assert_eq!(received, 0);
+        assert_eq!(std::fs::read(&path).unwrap(), Vec::<u8>::new());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_silent_peer_is_disconnected() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        // Connects and then sends nothing, not even the TLS handshake
+        let _peer = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, addr) = listener.accept().await.unwrap();
+
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let limits = ReceiveLimits {
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            read_timeout: Duration::from_millis(200),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            compression: CompressionMode::Off,
+        };
+        let started = Instant::now();
+        let result = tokio::time::timeout(
+            Duration::from_secs(10),
+            AirDrop::handle_connection(
+                stream,
+                addr,
+                events,
+                ConflictResolver::new(FileConflictPolicy::KeepBoth),
+                std::env::temp_dir(),
+                limits,
+                Arc::new(PeerFilter::default()),
+                ReceiveGate::new(VisibilitySettings::default()),
+            ),
+        )
+        .await
+        .expect("handler still waiting on a silent peer");
+
+        assert!(result.is_err());
+        assert!(started.elapsed() >= limits.read_timeout);
+    }
+
+    #[tokio::test]
+    async fn test_receive_stalled_peer_times_out() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        // Half of the declared bytes, then the peer stays connected but silent
+        peer.write_all(&[7u8; 10]).await.unwrap();
+
+        let path = std::env::temp_dir().join(format!("airwin_receive_{}.bin", Uuid::new_v4().simple()));
+        let result = receive_to_file(&mut stream, &path, 20, Duration::from_millis(50), DEFAULT_CHUNK_SIZE, None).await;
+
+        let error = result.unwrap_err();
+        let io_error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::TimedOut);
+        assert!(!path.exists());
+        assert!(!part_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_transfer_leaves_no_file() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        // The peer disconnects halfway through the declared size
+        peer.write_all(&[7u8; 10]).await.unwrap();
+        drop(peer);
+
+        let path = std::env::temp_dir().join(format!("airwin_receive_{}.bin", Uuid::new_v4().simple()));
+        let error = receive_to_file(&mut stream, &path, 20, READ_TIMEOUT, DEFAULT_CHUNK_SIZE, None).await.unwrap_err();
+
+        let io_error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(!path.exists());
+        assert!(!part_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_received_file_replaces_the_file() {
+        let path = std::env::temp_dir().join(format!("airwin_upload_{}.bin", Uuid::new_v4().simple()));
+        std::fs::write(&path, b"old").unwrap();
+
+        receive_to_file(&mut &b"new"[..], &path, 3, READ_TIMEOUT, DEFAULT_CHUNK_SIZE, None).await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!part_path(&path).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_read_until_blank_line() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        peer.write_all(b"{\"receiver\":\"pc\"}\n\n").await.unwrap();
+
+        let buffer = read_handshake(&mut stream, MAX_HANDSHAKE_SIZE, HANDSHAKE_TIMEOUT).await.unwrap();
+        assert!(buffer.ends_with(b"\n\n"));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_handshake_is_rejected() {
+        let (mut peer, mut stream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let chunk = [b'a'; 1024];
+            while peer.write_all(&chunk).await.is_ok() {}
+        });
+
+        let result = read_handshake(&mut stream, 8 * 1024, HANDSHAKE_TIMEOUT).await;
+        assert!(matches!(result, Err(HandshakeError::TooLarge { limit: 8192 })));
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_handshake_times_out() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        peer.write_all(b"{\"receiver\":").await.unwrap();
+
+        // The peer stays connected but never sends the delimiter
+        let result = read_handshake(&mut stream, MAX_HANDSHAKE_SIZE, Duration::from_millis(50)).await;
+        assert!(matches!(result, Err(HandshakeError::Timeout(_))));
+        drop(peer);
+    }
+
+    #[test]
+    fn test_plist_handshake_explains_the_mismatch() {
+        let mut plist = b"bplist00\xd2\x01\x02\x03\x04_\x10\x13SenderComputerName".to_vec();
+        plist.extend_from_slice(&[0u8; 100]);
+        plist.extend_from_slice(b"\n\n");
+
+        let error = parse_handshake(&plist).unwrap_err();
+        assert!(matches!(error, HandshakeError::NotJson { .. }));
+        let message = error.to_string();
+        assert!(message.contains("\"bplist00"), "{}", message);
+        assert!(message.contains("…"), "preview is not truncated: {}", message);
+        assert!(message.contains("Apple device expecting the plist protocol"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_closed_before_handshake_is_rejected() {
+        let (mut peer, mut stream) = tokio::io::duplex(64);
+        peer.write_all(b"{\"receiver\":").await.unwrap();
+        drop(peer);
+
+        let result = read_handshake(&mut stream, MAX_HANDSHAKE_SIZE, HANDSHAKE_TIMEOUT).await;
+        assert!(matches!(result, Err(HandshakeError::Closed)));
+    }
 }
@@ -1,15 +1,34 @@
-use anyhow::{Result, Context, anyhow};
+use anyhow::{Result, anyhow};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use tokio::net::{TcpStream, TcpListener};
 use tokio::io::AsyncWriteExt;
-use windows::Win32::Graphics::Gdi::{GetDC, BitBlt, SRCCOPY};
+use windows::Win32::Graphics::Gdi::{
+    BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits, ReleaseDC,
+    SelectObject, DIB_RGB_COLORS, HDC, SRCCOPY,
+};
 use windows::Win32::UI::WindowsAndMessaging::GetSystemMetrics;
 use windows::Win32::UI::WindowsAndMessaging::{SM_CXSCREEN, SM_CYSCREEN};
-use image::{ImageBuffer, Rgba};
 use tokio::time::Duration;
-use tracing::{debug, info, error};
+use tracing::{debug, info, warn, error};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use super::adaptive_quality::{AdaptiveQuality, QualityTier};
+use super::airplay_sender;
+use super::airplay_stats::{AirPlayStats, StatsRecorder};
+use super::apple_records::{self, AppleRecords};
+use super::cursor_overlay::{self, CursorSource, SystemCursor};
+use super::frame_header::FrameHeader;
+use super::frame_processor::{FrameProcessor, ProcessedFrame};
+use super::frame_queue::FrameQueue;
+use super::screen_capture::{self, rgba_image, BlackFrames, CaptureRegion, SessionKind};
+use crate::network::ip_mode::{self, IpMode};
+use crate::utils::{event_log, supervisor, AirWinError};
+use crate::utils::clock::{self, SharedClock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, PartialEq)]
 
 pub enum AirPlayStatus {
@@ -27,78 +46,418 @@ pub struct ScreenFrame {
     pub timestamp: u64,
 }
 
+/// Default port of the AirPlay receiver
+pub const DEFAULT_AIRPLAY_PORT: u16 = 7100;
+
+/// mDNS service the receiver is advertised as
+const AIRPLAY_SERVICE_TYPE: &str = "_airplay._tcp.local.";
+
+/// Encoded frames waiting for the sender task. Kept small so a slow peer
+/// sees recent frames instead of a growing backlog.
+const FRAME_QUEUE_CAPACITY: usize = 2;
+
+/// Time without activity after which receiving stops by default
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How often a running stream is checked for inactivity
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Inactivity stops kept for a subscriber that falls behind
+const IDLE_STOP_EVENTS_CAPACITY: usize = 4;
+
+/// Status changes kept for a subscriber that falls behind
+const STATUS_EVENTS_CAPACITY: usize = 16;
+
+/// Send buffer of an accepted stream by default, room for the two frames
+/// the queue holds at the highest quality
+pub const DEFAULT_SEND_BUFFER_SIZE: u32 = 1024 * 1024;
+
+/// TCP options of the receiver's listeners and accepted streams
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SocketTuning {
+    /// Send each frame at once instead of waiting to fill a segment
+    pub nodelay: bool,
+    /// Send buffer in bytes, `None` to keep the system default
+    pub send_buffer_size: Option<u32>,
+    /// Pending connections each listener queues
+    pub backlog: u32,
+}
+
+impl Default for SocketTuning {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            send_buffer_size: Some(DEFAULT_SEND_BUFFER_SIZE),
+            backlog: ip_mode::DEFAULT_BACKLOG,
+        }
+    }
+}
+
+impl SocketTuning {
+    pub fn validate(&self) -> Result<()> {
+        if self.backlog == 0 {
+            return Err(anyhow!("AirPlay listener backlog must be at least 1"));
+        }
+        if self.send_buffer_size == Some(0) {
+            return Err(anyhow!("AirPlay send buffer must not be empty"));
+        }
+        Ok(())
+    }
+
+    /// Apply the per-stream options to an accepted `stream`
+    fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        if let Some(size) = self.send_buffer_size {
+            socket2::SockRef::from(stream).set_send_buffer_size(size as usize)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned to a start request made while another one is still binding or
+/// waiting for the peer
+#[derive(Debug, thiserror::Error)]
+#[error("AirPlay is already starting a stream")]
+pub struct AlreadyStarting;
+
+/// Whether `error` means a stream was already running, so the request had
+/// nothing to do
+pub fn is_already_active(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<AirWinError>(), Some(AirWinError::AlreadyActive))
+}
+
+/// Frame encoded by the capture task, ready to be written to the socket
+#[derive(Clone)]
+struct EncodedFrame {
+    header: Vec<u8>,
+    processed: Arc<ProcessedFrame>,
+}
+
+/// Instance names other receivers advertise AirPlay under, collected for
+/// [`apple_records::NAME_CHECK_TIMEOUT`]
+async fn airplay_names_in_use(mdns: &ServiceDaemon) -> Vec<String> {
+    let receiver = match mdns.browse(AIRPLAY_SERVICE_TYPE) {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            warn!("Can't check whether the AirPlay name is in use: {}", e);
+            return Vec::new();
+        }
+    };
+    let deadline = tokio::time::Instant::now() + apple_records::NAME_CHECK_TIMEOUT;
+    let mut names = Vec::new();
+    while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, receiver.recv_async()).await {
+        if let ServiceEvent::ServiceResolved(info) = event {
+            names.push(info.get_fullname().to_string());
+        }
+    }
+    if let Err(e) = mdns.stop_browse(AIRPLAY_SERVICE_TYPE) {
+        debug!("Failed to stop the AirPlay name check: {}", e);
+    }
+    names
+}
+
+/// Size of the primary screen, `None` if Windows reports no valid size
+pub fn primary_screen_size() -> Option<(u32, u32)> {
+    let (width, height) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+    CaptureRegion::screen(width, height)
+        .ok()
+        .map(|screen| (screen.width as u32, screen.height as u32))
+}
+
 #[derive(Clone)]
 pub struct AirPlay {
     is_receiving: Arc<AtomicBool>,
     fps: Arc<Mutex<u32>>,
-    stream: Arc<Mutex<Option<TcpStream>>>,
+    quality: Arc<Mutex<AdaptiveQuality>>,
+    /// Connection to the peer. Its own lock is held by the frame writer, so
+    /// replacing or clearing the connection never waits for a slow write.
+    stream: Arc<Mutex<Option<Arc<Mutex<TcpStream>>>>>,
+    frame_queue: Arc<Mutex<Option<Arc<FrameQueue<EncodedFrame>>>>>,
+    stats: Arc<Mutex<StatsRecorder>>,
     current_frame: Arc<Mutex<Option<ScreenFrame>>>,
+    /// Last frame sent, shared with the in-app preview
+    preview: Arc<Mutex<Option<EncodedFrame>>>,
+    /// Black captures in a row, to tell a session without a screen
+    black_frames: Arc<Mutex<BlackFrames>>,
+    /// Listener on IPv4, or on IPv6 in IPv6-only mode
     listener: Arc<Mutex<Option<TcpListener>>>,
+    /// Additional IPv6 listener in auto mode
+    listener_v6: Arc<Mutex<Option<TcpListener>>>,
+    /// IP versions listened on and connected over
+    ip_mode: IpMode,
+    /// TCP options of the listeners and the accepted stream
+    socket_tuning: SocketTuning,
+    /// Held while the listeners are being bound, so concurrent starts bind once
+    binding: Arc<Mutex<()>>,
+    /// Held by the start request in progress until its stream is set up
+    starting: Arc<Mutex<()>>,
+    port: Arc<Mutex<u16>>,
     status: Arc<Mutex<AirPlayStatus>>,
+    /// Daemon advertising `_airplay._tcp` while the server is running
+    mdns: Arc<Mutex<Option<ServiceDaemon>>>,
+    /// Name the receiver is listed under
+    friendly_name: String,
+    /// Number the name if another receiver already advertises it
+    avoid_name_collisions: bool,
+    /// Part of the screen to mirror, `None` for the whole screen
+    capture_region: Arc<Mutex<Option<CaptureRegion>>>,
+    /// Draw the mouse cursor onto captured frames
+    draw_cursor: Arc<AtomicBool>,
+    /// Where the drawn cursor comes from
+    cursor_source: Arc<dyn CursorSource>,
+    /// Time without a connection or a sent frame after which receiving
+    /// stops, `None` to never stop
+    idle_timeout: Arc<Mutex<Option<Duration>>>,
+    /// When the stream connected or last sent a frame
+    last_activity: Arc<Mutex<DateTime<Utc>>>,
+    /// Source of the timestamps used for the idle timeout
+    clock: SharedClock,
+    /// Published with the idle time when receiving is stopped for inactivity
+    idle_stops: broadcast::Sender<Duration>,
+    /// Published with every status change, so the UI follows the real stream
+    status_changes: broadcast::Sender<AirPlayStatus>,
 }
 
-
+impl std::fmt::Debug for AirPlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AirPlay")
+            .field("is_receiving", &self.is_receiving)
+            .finish_non_exhaustive()
+    }
+}
 
 impl AirPlay {
     pub fn new() -> Self {
         Self {
             is_receiving: Arc::new(AtomicBool::new(false)),
-            fps: Arc::new(Mutex::new(60)),
+            fps: Arc::new(Mutex::new(QualityTier::Auto.bounds().max_fps)),
+            quality: Arc::new(Mutex::new(AdaptiveQuality::new(QualityTier::Auto))),
             stream: Arc::new(Mutex::new(None)),
+            frame_queue: Arc::new(Mutex::new(None)),
+            stats: Arc::new(Mutex::new(StatsRecorder::default())),
             current_frame: Arc::new(Mutex::new(None)),
+            preview: Arc::new(Mutex::new(None)),
+            black_frames: Arc::new(Mutex::new(BlackFrames::default())),
             listener: Arc::new(Mutex::new(None)),
+            listener_v6: Arc::new(Mutex::new(None)),
+            ip_mode: IpMode::Auto,
+            socket_tuning: SocketTuning::default(),
+            binding: Arc::new(Mutex::new(())),
+            starting: Arc::new(Mutex::new(())),
+            port: Arc::new(Mutex::new(DEFAULT_AIRPLAY_PORT)),
             status: Arc::new(Mutex::new(AirPlayStatus::Idle)),
+            mdns: Arc::new(Mutex::new(None)),
+            friendly_name: apple_records::friendly_name(None).unwrap_or_else(|_| "AirWin".to_string()),
+            avoid_name_collisions: false,
+            capture_region: Arc::new(Mutex::new(None)),
+            draw_cursor: Arc::new(AtomicBool::new(true)),
+            cursor_source: Arc::new(SystemCursor),
+            idle_timeout: Arc::new(Mutex::new(Some(DEFAULT_IDLE_TIMEOUT))),
+            last_activity: Arc::new(Mutex::new(Utc::now())),
+            clock: clock::system(),
+            idle_stops: broadcast::channel(IDLE_STOP_EVENTS_CAPACITY).0,
+            status_changes: broadcast::channel(STATUS_EVENTS_CAPACITY).0,
         }
     }
 
+    /// Start from the given quality tier instead of `Auto`
+    pub fn with_quality_tier(mut self, tier: QualityTier) -> Self {
+        self.quality = Arc::new(Mutex::new(AdaptiveQuality::new(tier)));
+        self.fps = Arc::new(Mutex::new(tier.bounds().max_fps));
+        self
+    }
+
+    /// List the receiver under `name` instead of the hostname
+    pub fn with_friendly_name(mut self, name: String) -> Self {
+        self.friendly_name = name;
+        self
+    }
+
+    /// Before registering, look for receivers already advertising the name
+    /// and number it if one does, like "PC (2)"
+    pub fn with_name_collision_check(mut self, enabled: bool) -> Self {
+        self.avoid_name_collisions = enabled;
+        self
+    }
+
+    /// Mirror only `region` of the screen
+    pub fn with_capture_region(mut self, region: Option<CaptureRegion>) -> Self {
+        self.capture_region = Arc::new(Mutex::new(region));
+        self
+    }
+
+    /// Draw the mouse cursor onto captured frames, or not
+    pub fn with_cursor(self, draw: bool) -> Self {
+        self.draw_cursor.store(draw, Ordering::Relaxed);
+        self
+    }
+
+    /// Take the drawn cursor from `source` instead of Windows
+    pub fn with_cursor_source(mut self, source: Arc<dyn CursorSource>) -> Self {
+        self.cursor_source = source;
+        self
+    }
+
+    /// Listen on `port` instead of the default AirPlay port
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Arc::new(Mutex::new(port));
+        self
+    }
+
+    /// Listen and connect only over the IP versions allowed by `mode`
+    pub fn with_ip_mode(mut self, mode: IpMode) -> Self {
+        self.ip_mode = mode;
+        self
+    }
+
+    /// Use `tuning` for the listeners and the accepted stream
+    pub fn with_socket_tuning(mut self, tuning: SocketTuning) -> Self {
+        self.socket_tuning = tuning;
+        self
+    }
+
+    /// Stop receiving after `timeout` without activity, `None` to never stop
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = Arc::new(Mutex::new(timeout));
+        self
+    }
+
+    /// Read the idle timestamps from `clock` instead of the system time
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.last_activity = Arc::new(Mutex::new(clock.now()));
+        self.clock = clock;
+        self
+    }
+
+    /// Sender of the inactivity stops, to subscribe to
+    pub fn idle_stops(&self) -> broadcast::Sender<Duration> {
+        self.idle_stops.clone()
+    }
+
     pub async fn get_status(&self) -> AirPlayStatus {
         self.status.lock().await.clone()
     }
 
+    /// Whether the receiver is registered over mDNS, so Apple devices list it
+    pub async fn is_advertised(&self) -> bool {
+        self.mdns.lock().await.is_some()
+    }
+
+    /// Sender of the status changes, to subscribe to
+    pub fn status_changes(&self) -> broadcast::Sender<AirPlayStatus> {
+        self.status_changes.clone()
+    }
+
+    /// Change the status, publishing it if it differs from the current one
+    async fn set_status(&self, status: AirPlayStatus) {
+        let mut current = self.status.lock().await;
+        if *current != status {
+            *current = status.clone();
+            let _ = self.status_changes.send(status);
+        }
+    }
+
 
     pub async fn start_server(&self) -> Result<()> {
+        let _binding = self.binding.lock().await;
         // Check if server is already running
         if self.listener.lock().await.is_some() {
             info!("AirPlay server already running");
             return Ok(());
         }
 
-        // Try binding to all interfaces directly
-        match TcpListener::bind(("0.0.0.0", 7100)).await {
-            Ok(listener) => {
-                info!("Started AirPlay server on 0.0.0.0:7100");
-                *self.listener.lock().await = Some(listener);
+        let port = *self.port.lock().await;
 
-                // Also bind to IPv6 if available
-                if let Ok(_v6_listener) = TcpListener::bind(("[::]", 7100)).await {
-                    info!("Started AirPlay server on [::]:7100");
-                    // Store the IPv6 listener or handle it as needed
+        // Bind on all interfaces, over the IP versions the mode allows
+        let backlog = self.socket_tuning.backlog;
+        match self.ip_mode.bind_listeners(port, |addr| ip_mode::bind_tcp_with_backlog(addr, backlog)) {
+            Ok(bound) => {
+                info!("Started AirPlay server on {}", self.ip_mode.primary_addr(port));
+                event_log::info("AirPlay", format!("Server listening on port {}", port));
+                *self.listener.lock().await = Some(bound.primary);
+
+                // In auto mode IPv6 is also used when the host supports it
+                if let Some(v6_listener) = bound.secondary.into_iter().next() {
+                    info!("Started AirPlay server on [::]:{}", port);
+                    *self.listener_v6.lock().await = Some(v6_listener);
+                }
+                for (addr, e) in bound.skipped {
+                    warn!("{} unavailable, AirPlay listening on IPv4 only: {}", addr, e);
+                }
+
+                // The server still works for known peers without the advertisement
+                if let Err(e) = self.register_mdns_service(port).await {
+                    warn!("Failed to advertise AirPlay receiver: {}", e);
+                    event_log::warning("AirPlay", format!("Receiver not advertised over mDNS: {}", e));
                 }
 
                 Ok(())
             }
             Err(e) => {
                 error!("Failed to start AirPlay server: {}", e);
+                event_log::error("AirPlay", format!("Failed to bind port {}: {}", port, e));
                 Err(anyhow!("Failed to start AirPlay server. Try running as administrator or check firewall settings."))
             }
         }
     }
 
+    /// Advertise the receiver as `_airplay._tcp` so Apple devices list it
+    async fn register_mdns_service(&self, port: u16) -> Result<()> {
+        let mdns = ServiceDaemon::new().map_err(|e| anyhow!("Failed to initialize mDNS: {}", e))?;
+        let properties = AppleRecords::create_airplay_txt_records()?;
+        let name = if self.avoid_name_collisions {
+            let taken = airplay_names_in_use(&mdns).await;
+            let name = apple_records::unique_name(&self.friendly_name, &taken);
+            if name != self.friendly_name {
+                event_log::info(
+                    "mDNS",
+                    format!("\"{}\" is already in use by an AirPlay receiver, advertising as \"{}\"", self.friendly_name, name),
+                );
+            }
+            name
+        } else {
+            self.friendly_name.clone()
+        };
+
+        let airplay_service = ServiceInfo::new(
+            AIRPLAY_SERVICE_TYPE,
+            &name,
+            "local.",
+            "",
+            port,
+            Some(properties)
+        )?;
+        mdns.register(airplay_service)
+            .map_err(|e| anyhow!("Failed to register AirPlay service: {}", e))?;
+
+        info!("Registered AirPlay mDNS service \"{}\" on port {}", name, port);
+        event_log::info("mDNS", "Registered AirPlay receiver service");
+        *self.mdns.lock().await = Some(mdns);
+        Ok(())
+    }
+
 
     pub async fn start_receiving(&self) -> Result<()> {
+        // A second request leaves the status of the first one alone
+        let Ok(_starting) = self.starting.try_lock() else {
+            info!("AirPlay is already starting, ignoring the new request");
+            return Err(AlreadyStarting.into());
+        };
+        // The running stream is fine, so its status stays as it is
         if self.is_receiving.load(Ordering::Relaxed) {
-            *self.status.lock().await = AirPlayStatus::Failed("Already receiving a stream".to_string());
-            return Err(anyhow!("A stream is already in progress"));
+            info!("AirPlay is already receiving, ignoring the new request");
+            return Err(AirWinError::AlreadyActive.into());
         }
 
-        *self.status.lock().await = AirPlayStatus::Connecting;
+        self.set_status(AirPlayStatus::Connecting).await;
 
         if self.listener.lock().await.is_none() {
             match self.start_server().await {
                 Ok(_) => info!("AirPlay server started successfully"),
                 Err(e) => {
                     let error_msg = format!("Failed to start server. Please check your network settings: {}", e);
-                    *self.status.lock().await = AirPlayStatus::Failed(error_msg.clone());
+                    self.set_status(AirPlayStatus::Failed(error_msg.clone())).await;
                     return Err(anyhow!(error_msg));
                 }
             }
@@ -109,153 +468,288 @@ impl AirPlay {
         
         match tokio::time::timeout(Duration::from_secs(15), self.setup_stream()).await {
             Ok(Ok(_)) => {
-                self.is_receiving.store(true, Ordering::Relaxed);
-                *self.status.lock().await = AirPlayStatus::Connected;
-
-                
-                let status = self.status.clone();
-                let is_receiving = self.is_receiving.clone();
-                let fps = self.fps.clone();
-                let this = self.clone();
-                
-                tokio::spawn(async move {
-                    while is_receiving.load(Ordering::Relaxed) {
-                        if let Err(e) = this.capture_screen().await {
-                            error!("Screen capture error: {}", e);
-                            *status.lock().await = AirPlayStatus::Failed(format!("Capture error: {}", e));
-                            is_receiving.store(false, Ordering::Relaxed);
-                            break;
-                        }
-                        let current_fps = *fps.lock().await;
-                        tokio::time::sleep(Duration::from_millis(1000u64 / current_fps as u64)).await;
-                    }
-                    info!("Screen receiving stopped");
-                    *status.lock().await = AirPlayStatus::Idle;
-                });
-                
+                self.spawn_streaming().await;
                 Ok(())
             }
             Ok(Err(e)) => {
                 self.is_receiving.store(false, Ordering::Relaxed);
                 let error_msg = format!("Failed to setup stream: {}", e);
-                *self.status.lock().await = AirPlayStatus::Failed(error_msg.clone());
+                self.set_status(AirPlayStatus::Failed(error_msg.clone())).await;
                 Err(anyhow!(error_msg))
             }
             Err(_) => {
                 self.is_receiving.store(false, Ordering::Relaxed);
                 let error_msg = "Connection timeout after 15 seconds".to_string();
-                *self.status.lock().await = AirPlayStatus::Failed(error_msg.clone());
+                self.set_status(AirPlayStatus::Failed(error_msg.clone())).await;
                 Err(anyhow!(error_msg))
             }
         }
     }
 
-    async fn setup_stream(&self) -> Result<()> {
-        info!("Waiting for AirPlay connection... Please connect from your iOS/macOS device");
-        let listener = self.listener.lock().await;
-        
-        if let Some(listener) = &*listener {
-            let _ = listener;
-            
-            match tokio::time::timeout(Duration::from_secs(15), async {
-                if let Some(listener) = &*self.listener.lock().await {
-                    match listener.accept().await {
-                        Ok((stream, addr)) => {
-                            info!("Accepted AirPlay connection from {}", addr);
-                            stream.set_nodelay(true)?;
-                            Ok((stream, addr))
-                        }
-                        Err(e) => Err(e),
+    /// Start the capture and sender tasks on the connected stream
+    async fn spawn_streaming(&self) {
+        self.is_receiving.store(true, Ordering::Relaxed);
+        self.set_status(AirPlayStatus::Connected).await;
+
+        // Capture and network writes run in separate tasks so a slow
+        // peer never stalls the capture cadence
+        let queue = Arc::new(FrameQueue::new(FRAME_QUEUE_CAPACITY));
+        *self.frame_queue.lock().await = Some(queue.clone());
+        *self.stats.lock().await = StatsRecorder::default();
+        *self.black_frames.lock().await = BlackFrames::default();
+        self.touch().await;
+
+        // A session nobody watches anymore is stopped instead of capturing forever
+        let this = self.clone();
+        supervisor::global().spawn("AirPlay idle watchdog", move || {
+            let this = this.clone();
+            async move {
+                while this.is_receiving.load(Ordering::Relaxed) {
+                    tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+                    if this.stop_if_idle().await {
+                        break;
                     }
-                } else {
-                    Err(std::io::Error::new(std::io::ErrorKind::Other, "Server not ready"))
                 }
-            }).await {
-                Ok(Ok((stream, addr))) => {
-                    info!("Successfully established connection with {}", addr);
-                    *self.stream.lock().await = Some(stream);
-                    Ok(())
-                }
-                Ok(Err(e)) => {
-                    error!("Failed to accept connection: {}", e);
-                    Err(anyhow!("Connection failed. Please check your network settings and try again."))
+            }
+        });
+
+        // Both loops are restarted on panic so the UI never shows a dead stream as connected
+        let this = self.clone();
+        let capture_queue = queue.clone();
+        supervisor::global().spawn("AirPlay capture", move || {
+            let this = this.clone();
+            let capture_queue = capture_queue.clone();
+            async move {
+                let mut failure = None;
+                while this.is_receiving.load(Ordering::Relaxed) {
+                    if let Err(e) = this.capture_screen(&capture_queue).await {
+                        error!("Screen capture error: {}", e);
+                        let reason = match e.downcast_ref::<AirWinError>() {
+                            Some(unavailable @ AirWinError::CaptureUnavailable(_)) => {
+                                event_log::error("AirPlay", unavailable.to_string());
+                                unavailable.to_string()
+                            }
+                            _ => format!("Capture error: {}", e),
+                        };
+                        failure = Some(AirPlayStatus::Failed(reason));
+                        this.is_receiving.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                    let current_fps = *this.fps.lock().await;
+                    tokio::time::sleep(Duration::from_millis(1000u64 / current_fps as u64)).await;
                 }
-                Err(_) => {
-                    error!("Connection attempt timed out");
-                    Err(anyhow!("Connection timeout. Please ensure your device is on the same network and try connecting again."))
+                capture_queue.close();
+                info!("Screen receiving stopped");
+                // A failure stays shown instead of turning into Idle
+                this.set_status(failure.unwrap_or(AirPlayStatus::Idle)).await;
+            }
+        });
+
+        let this = self.clone();
+        supervisor::global().spawn("AirPlay sender", move || {
+            let this = this.clone();
+            let queue = queue.clone();
+            async move {
+                while let Some(frame) = queue.pop().await {
+                    if let Err(e) = this.send_frame(frame).await {
+                        error!("Failed to send frame: {}", e);
+                        this.set_status(AirPlayStatus::Failed(format!("Send error: {}", e))).await;
+                        this.is_receiving.store(false, Ordering::Relaxed);
+                        queue.close();
+                        break;
+                    }
                 }
+                let stats = this.stats.lock().await.snapshot(queue.dropped(), std::time::Instant::now());
+                debug!(
+                    "Frame sender stopped ({} sent, {} bytes, {} dropped)",
+                    stats.frames_sent,
+                    stats.bytes_sent,
+                    stats.frames_dropped
+                );
             }
-        } else {
+        });
+    }
+
+    /// Mirror this screen to the AirPlay receiver at `addr`.
+    ///
+    /// `txt` holds the receiver's mDNS TXT records, used to refuse FairPlay
+    /// receivers before connecting.
+    pub async fn start_sending(&self, addr: SocketAddr, txt: &HashMap<String, String>) -> Result<()> {
+        let Ok(_starting) = self.starting.try_lock() else {
+            return Err(AlreadyStarting.into());
+        };
+        if self.is_receiving.load(Ordering::Relaxed) {
+            return Err(AirWinError::AlreadyActive.into());
+        }
+        if !self.ip_mode.allows(addr.ip()) {
+            let error = format!("{} is not reachable in {:?} mode", addr, self.ip_mode);
+            self.set_status(AirPlayStatus::Failed(error.clone())).await;
+            return Err(anyhow!(error));
+        }
+
+        self.set_status(AirPlayStatus::Connecting).await;
+        match airplay_sender::connect(addr, txt).await {
+            Ok(stream) => {
+                info!("Mirroring screen to {}", addr);
+                event_log::info("AirPlay", format!("Mirroring to {}", addr));
+                *self.stream.lock().await = Some(Arc::new(Mutex::new(stream)));
+                self.spawn_streaming().await;
+                Ok(())
+            }
+            Err(e) => {
+                warn!("AirPlay sender could not connect to {}: {}", addr, e);
+                self.set_status(AirPlayStatus::Failed(e.to_string())).await;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Accept the first connection arriving on either listener
+    async fn accept_any(
+        v4: &TcpListener,
+        v6: Option<&TcpListener>,
+    ) -> std::io::Result<(TcpStream, SocketAddr)> {
+        match v6 {
+            Some(v6) => tokio::select! {
+                accepted = v4.accept() => accepted,
+                accepted = v6.accept() => accepted,
+            },
+            None => v4.accept().await,
+        }
+    }
+
+    async fn setup_stream(&self) -> Result<()> {
+        info!("Waiting for AirPlay connection... Please connect from your iOS/macOS device");
+        let listener = self.listener.lock().await;
+        let listener_v6 = self.listener_v6.lock().await;
+
+        let Some(listener) = &*listener else {
             error!("AirPlay server not started");
-            Err(anyhow!("Server not ready. Please restart the application and try again."))
+            return Err(anyhow!("Server not ready. Please restart the application and try again."));
+        };
+
+        match tokio::time::timeout(
+            Duration::from_secs(15),
+            Self::accept_any(listener, listener_v6.as_ref()),
+        )
+        .await
+        {
+            Ok(Ok((stream, addr))) => {
+                info!("Accepted AirPlay connection from {}", addr);
+                self.socket_tuning.apply(&stream)?;
+                *self.stream.lock().await = Some(Arc::new(Mutex::new(stream)));
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                error!("Failed to accept connection: {}", e);
+                Err(anyhow!("Connection failed. Please check your network settings and try again."))
+            }
+            Err(_) => {
+                error!("Connection attempt timed out");
+                Err(anyhow!("Connection timeout. Please ensure your device is on the same network and try connecting again."))
+            }
         }
     }
 
 
-    async fn capture_screen(&self) -> Result<()> {
+    async fn capture_screen(&self, queue: &FrameQueue<EncodedFrame>) -> Result<()> {
         if self.stream.lock().await.is_none() {
             return Err(anyhow!("No active connection"));
         }
+        let selected = *self.capture_region.lock().await;
+        let session = SessionKind::current();
 
-        unsafe {
-            let screen_dc = GetDC(None);
-            if !screen_dc.is_invalid() {
-                let width = GetSystemMetrics(SM_CXSCREEN);
-                let height = GetSystemMetrics(SM_CYSCREEN);
-                
-                let buffer = vec![0u8; (width * height * 4) as usize];
-                
-                if BitBlt(
-                    screen_dc,
-                    0,
-                    0,
-                    width,
-                    height,
-                    screen_dc,
-                    0,
-                    0,
-                    SRCCOPY,
-                ).as_bool() {
-                    let img = ImageBuffer::<Rgba<u8>, _>::from_raw(
-                        width as u32,
-                        height as u32,
-                        buffer,
-                    ).context("Failed to create image buffer")?;
-                    
-                    let frame = ScreenFrame {
-                        data: img.into_raw(),
-                        width: width as u32,
-                        height: height as u32,
-                        timestamp: std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs(),
-                    };
-
-                    // Use frame dimensions for stream header
-                    if let Some(stream) = &mut *self.stream.lock().await {
-                        // Write frame header with dimensions and timestamp
-                        let header = format!(
-                            "{}x{}@{}\n",
-                            frame.width,
-                            frame.height,
-                            frame.timestamp
-                        );
-                        stream.write_all(header.as_bytes()).await?;
-                        stream.write_all(&frame.data).await?;
-
-                        debug!(
-                            "Sent frame: {}x{} at timestamp {}",
-                            frame.width,
-                            frame.height,
-                            frame.timestamp
-                        );
+        // SAFETY: grab_screen releases every GDI handle it creates
+        let (region, mut buffer) = unsafe { grab_screen(selected, session)? };
+        self.black_frames.lock().await.record(&buffer, session)?;
+        self.overlay_cursor(&mut buffer, region);
+        screen_capture::bgra_to_rgba(&mut buffer);
+        let img = rgba_image(region.width as u32, region.height as u32, buffer)?;
 
-                    }
-                    
-                    *self.current_frame.lock().await = Some(frame);
-                }
-            }
+        let frame = ScreenFrame {
+            width: img.width(),
+            height: img.height(),
+            data: img.into_raw(),
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+
+        let processor = {
+            let controller = self.quality.lock().await;
+            FrameProcessor::new(controller.tier().max_dimension(), controller.quality())
+        };
+        let encode_started = std::time::Instant::now();
+        let processed = Arc::new(processor.process(&frame.data, frame.width, frame.height)?);
+        self.stats.lock().await.record_encode(encode_started.elapsed());
+
+        // Frame header with dimensions, timestamp and JPEG length
+        let header = FrameHeader {
+            width: processed.width,
+            height: processed.height,
+            timestamp: frame.timestamp,
+            payload_len: Some(processed.jpeg.len()),
+        };
+        let encoded = EncodedFrame { header: header.encode(), processed };
+
+        // Never waits: if the sender is behind, the oldest frame is dropped
+        queue.push(encoded.clone());
+
+        *self.preview.lock().await = Some(encoded);
+        *self.current_frame.lock().await = Some(frame);
+
+        Ok(())
+    }
+
+    /// Draw the cursor onto the BGRA pixels of `region`, if enabled and visible
+    fn overlay_cursor(&self, buffer: &mut [u8], region: CaptureRegion) {
+        if !self.draw_cursor.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(cursor) = self.cursor_source.cursor() {
+            cursor_overlay::composite(buffer, region, &cursor);
+        }
+    }
+
+    /// Current connection to the peer, if any
+    async fn connection(&self) -> Option<Arc<Mutex<TcpStream>>> {
+        self.stream.lock().await.clone()
+    }
+
+    /// Write one encoded frame and feed the write time to the quality controller
+    async fn send_frame(&self, frame: EncodedFrame) -> Result<()> {
+        let connection = self.connection().await.ok_or_else(|| anyhow!("No active connection"))?;
+        let mut stream = connection.lock().await;
+
+        let started = std::time::Instant::now();
+        stream.write_all(&frame.header).await?;
+        stream.write_all(&frame.processed.jpeg).await?;
+        let write_time = started.elapsed();
+        self.touch().await;
+        self.stats
+            .lock()
+            .await
+            .record_sent(frame.header.len() + frame.processed.jpeg.len(), std::time::Instant::now());
+
+        debug!(
+            "Sent frame {}x{} ({} bytes, quality {}, {:?})",
+            frame.processed.width,
+            frame.processed.height,
+            frame.processed.jpeg.len(),
+            frame.processed.quality,
+            write_time
+        );
+
+        // Adapt quality and frame rate to how fast the peer is draining
+        let mut controller = self.quality.lock().await;
+        if controller.record(write_time) {
+            info!(
+                "AirPlay quality adjusted to {} at {} fps",
+                controller.quality(),
+                controller.fps()
+            );
+            *self.fps.lock().await = controller.fps();
         }
         Ok(())
     }
@@ -263,18 +757,149 @@ impl AirPlay {
     pub async fn stop_receiving(&self) -> Result<()> {
         info!("Stopping screen receiving...");
         self.is_receiving.store(false, Ordering::Relaxed);
+        if let Some(queue) = self.frame_queue.lock().await.take() {
+            queue.close();
+        }
         // Clear current frame and stream
         *self.current_frame.lock().await = None;
+        *self.preview.lock().await = None;
         *self.stream.lock().await = None;
-        *self.status.lock().await = AirPlayStatus::Idle;
+        self.set_status(AirPlayStatus::Idle).await;
         Ok(())
     }
 
+    /// Record activity on the stream, postponing the idle stop
+    async fn touch(&self) {
+        *self.last_activity.lock().await = self.clock.now();
+    }
+
+    /// Stop receiving if nothing happened for longer than the idle timeout.
+    /// Returns `true` when the stream was stopped.
+    pub async fn stop_if_idle(&self) -> bool {
+        let Some(timeout) = *self.idle_timeout.lock().await else {
+            return false;
+        };
+        if !self.is_receiving.load(Ordering::Relaxed) {
+            return false;
+        }
+        let idle = (self.clock.now() - *self.last_activity.lock().await)
+            .to_std()
+            .unwrap_or_default();
+        if idle <= timeout {
+            return false;
+        }
+
+        info!("No AirPlay activity for {:?}, stopping the stream", idle);
+        event_log::warning("AirPlay", format!("Stream stopped after {} minutes without activity", idle.as_secs() / 60));
+        if let Err(e) = self.stop_receiving().await {
+            warn!("Failed to stop idle AirPlay stream: {}", e);
+        }
+        let _ = self.idle_stops.send(idle);
+        true
+    }
+
+    /// Stop receiving and release the listening sockets
+    pub async fn stop_server(&self) -> Result<()> {
+        self.stop_receiving().await?;
+
+        let released = self.listener.lock().await.take().is_some();
+        *self.listener_v6.lock().await = None;
+
+        if let Some(mdns) = self.mdns.lock().await.take() {
+            if let Err(e) = mdns.shutdown() {
+                warn!("Failed to shut down AirPlay mDNS daemon: {}", e);
+            }
+        }
+
+        if released {
+            let port = *self.port.lock().await;
+            info!("AirPlay server stopped, port {} released", port);
+            event_log::info("AirPlay", format!("Server stopped, port {} released", port));
+        }
+        Ok(())
+    }
+
+    /// Start or stop the server to match the user's setting
+    pub async fn set_enabled(&self, enabled: bool) -> Result<()> {
+        if enabled {
+            self.start_server().await
+        } else {
+            self.stop_server().await
+        }
+    }
+
+    /// Rebind the server on `port`, freeing the previous one first
+    pub async fn restart_server(&self, port: u16) -> Result<()> {
+        self.stop_server().await?;
+        *self.port.lock().await = port;
+        self.start_server().await
+    }
+
+    /// Select the quality tier the adaptive controller works within
+    pub async fn set_quality_tier(&self, tier: QualityTier) {
+        let mut controller = self.quality.lock().await;
+        controller.set_tier(tier);
+        *self.fps.lock().await = controller.fps();
+    }
+
+    /// Change the idle timeout, `None` to never stop. Applies to the running
+    /// stream at its next check.
+    pub async fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        *self.idle_timeout.lock().await = timeout;
+    }
+
+    /// Mirror only `region` of the screen, or the whole screen with `None`.
+    /// Applies from the next captured frame.
+    pub async fn set_capture_region(&self, region: Option<CaptureRegion>) {
+        *self.capture_region.lock().await = region;
+    }
+
+    /// Draw the mouse cursor onto frames from the next capture, or stop
+    pub fn set_draw_cursor(&self, draw: bool) {
+        self.draw_cursor.store(draw, Ordering::Relaxed);
+    }
+
+    /// Current JPEG quality and frame rate chosen by the adaptive controller
+    pub async fn get_effective_quality(&self) -> (u8, u32) {
+        let controller = self.quality.lock().await;
+        (controller.quality(), controller.fps())
+    }
+
+    /// Throughput and encode time of the current streaming session
+    pub async fn get_stats(&self) -> AirPlayStats {
+        let frames_dropped = self.frame_queue
+            .lock()
+            .await
+            .as_ref()
+            .map(|queue| queue.dropped())
+            .unwrap_or(0);
+        self.stats.lock().await.snapshot(frames_dropped, std::time::Instant::now())
+    }
+
+    pub async fn get_port(&self) -> u16 {
+        *self.port.lock().await
+    }
+
     #[allow(dead_code)]
     pub async fn get_current_frame(&self) -> Option<ScreenFrame> {
         self.current_frame.lock().await.clone()
     }
 
+    /// Last encoded frame, for the preview.
+    ///
+    /// The header is decoded as a receiver would, so a frame the peer can't
+    /// read doesn't show up in the preview either.
+    pub async fn get_preview_frame(&self) -> Option<Arc<ProcessedFrame>> {
+        let frame = self.preview.lock().await.clone()?;
+        match FrameHeader::decode(&frame.header) {
+            Some((header, _)) if header.payload_len == Some(frame.processed.jpeg.len()) => Some(frame.processed),
+            _ => {
+                warn!("Discarding preview frame with an invalid header");
+                None
+            }
+        }
+    }
+
     pub async fn get_frame_info(&self) -> Option<(u32, u32, u64)> {
         if let Some(frame) = self.current_frame.lock().await.as_ref() {
             Some((frame.width, frame.height, frame.timestamp))
@@ -282,4 +907,420 @@ impl AirPlay {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+/// Copy `selected`, or the whole screen, into a top-down BGRA buffer.
+///
+/// # Safety
+///
+/// Calls GDI. Every handle created here is released before returning.
+unsafe fn grab_screen(selected: Option<CaptureRegion>, session: SessionKind) -> Result<(CaptureRegion, Vec<u8>)> {
+    let screen_dc = screen_capture::screen_dc(GetDC(None), session)?;
+    let grabbed = copy_screen(screen_dc, selected, session);
+    ReleaseDC(None, screen_dc);
+    grabbed
+}
+
+/// # Safety
+///
+/// `screen_dc` must be a valid screen DC.
+unsafe fn copy_screen(
+    screen_dc: HDC,
+    selected: Option<CaptureRegion>,
+    session: SessionKind,
+) -> Result<(CaptureRegion, Vec<u8>)> {
+    let screen_width = GetSystemMetrics(SM_CXSCREEN);
+    let screen_height = GetSystemMetrics(SM_CYSCREEN);
+
+    // Sizes are checked before allocating, so a display mode change can't
+    // produce a buffer of the wrong length. The selected region is clamped
+    // to the current screen.
+    let region = screen_capture::capture_region(selected, screen_width, screen_height)?;
+    let mut buffer = vec![0u8; region.buffer_len()?];
+
+    let memory_dc = CreateCompatibleDC(screen_dc);
+    let bitmap = CreateCompatibleBitmap(screen_dc, region.width, region.height);
+    let previous = SelectObject(memory_dc, bitmap);
+    let copied = BitBlt(memory_dc, 0, 0, region.width, region.height, screen_dc, region.x, region.y, SRCCOPY).as_bool();
+    // GetDIBits needs the bitmap out of the DC
+    SelectObject(memory_dc, previous);
+
+    let mut info = screen_capture::bitmap_info(region.width, region.height);
+    let rows = copied.then(|| {
+        GetDIBits(
+            memory_dc,
+            bitmap,
+            0,
+            region.height as u32,
+            Some(buffer.as_mut_ptr().cast()),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    });
+    DeleteObject(bitmap);
+    DeleteDC(memory_dc);
+
+    // BitBlt fails on the secure desktop and in sessions without a screen
+    if rows != Some(region.height) {
+        return Err(screen_capture::unavailable("copying the screen failed", session).into());
+    }
+    Ok((region, buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    /// A 2x2 white cursor, or none when hidden
+    struct StubCursor(Option<(i32, i32)>);
+
+    impl CursorSource for StubCursor {
+        fn cursor(&self) -> Option<cursor_overlay::CursorImage> {
+            self.0.map(|(x, y)| cursor_overlay::CursorImage {
+                x,
+                y,
+                width: 2,
+                height: 2,
+                pixels: [255, 255, 255, 255].repeat(4),
+            })
+        }
+    }
+
+    #[test]
+    fn test_cursor_is_drawn_at_its_position() {
+        let region = CaptureRegion { x: 10, y: 20, width: 4, height: 4 };
+        let frame = vec![0u8; region.buffer_len().unwrap()];
+        let pixel = |buffer: &[u8], x: usize, y: usize| buffer[(y * 4 + x) * 4..][..3].to_vec();
+
+        let airplay = AirPlay::new().with_cursor_source(Arc::new(StubCursor(Some((12, 21)))));
+        let mut drawn = frame.clone();
+        airplay.overlay_cursor(&mut drawn, region);
+        assert_ne!(drawn, frame);
+        for (x, y) in [(2, 1), (3, 1), (2, 2), (3, 2)] {
+            assert_eq!(pixel(&drawn, x, y), [255, 255, 255], "({}, {})", x, y);
+        }
+        assert_eq!(pixel(&drawn, 1, 1), [0, 0, 0]);
+        assert_eq!(pixel(&drawn, 2, 3), [0, 0, 0]);
+
+        // Turned off, or with the cursor hidden, the frame is untouched
+        let mut untouched = frame.clone();
+        airplay.set_draw_cursor(false);
+        airplay.overlay_cursor(&mut untouched, region);
+        let hidden = AirPlay::new().with_cursor_source(Arc::new(StubCursor(None)));
+        hidden.overlay_cursor(&mut untouched, region);
+        assert_eq!(untouched, frame);
+    }
+
+    #[tokio::test]
+    async fn test_idle_stream_is_stopped() {
+        use crate::utils::clock::FakeClock;
+
+        let clock = FakeClock::new("2024-01-01T00:00:00Z".parse().unwrap());
+        let airplay = AirPlay::new()
+            .with_clock(clock.clone())
+            .with_idle_timeout(Some(Duration::from_secs(5 * 60)));
+        let mut stops = airplay.idle_stops().subscribe();
+        airplay.is_receiving.store(true, Ordering::Relaxed);
+        airplay.touch().await;
+
+        // Sent frames postpone the stop
+        clock.advance(Duration::from_secs(4 * 60));
+        assert!(!airplay.stop_if_idle().await);
+        airplay.touch().await;
+        clock.advance(Duration::from_secs(4 * 60));
+        assert!(!airplay.stop_if_idle().await);
+
+        // "Never" keeps a forgotten stream running
+        clock.advance(Duration::from_secs(2 * 60));
+        airplay.set_idle_timeout(None).await;
+        assert!(!airplay.stop_if_idle().await);
+
+        airplay.set_idle_timeout(Some(Duration::from_secs(5 * 60))).await;
+        assert!(airplay.stop_if_idle().await);
+        assert!(!airplay.is_receiving.load(Ordering::Relaxed));
+        assert_eq!(airplay.get_status().await, AirPlayStatus::Idle);
+        assert_eq!(stops.try_recv().unwrap(), Duration::from_secs(6 * 60));
+        assert!(!airplay.stop_if_idle().await);
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind(("0.0.0.0", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[tokio::test]
+    async fn test_stop_releases_port_and_rebinds() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port);
+
+        airplay.start_server().await.unwrap();
+        assert!(TcpListener::bind(("0.0.0.0", port)).await.is_err());
+
+        airplay.stop_server().await.unwrap();
+        drop(TcpListener::bind(("0.0.0.0", port)).await.unwrap());
+
+        airplay.restart_server(port).await.unwrap();
+        assert_eq!(airplay.get_port().await, port);
+        assert!(airplay.listener.lock().await.is_some());
+
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restart_moves_to_new_port() {
+        let old_port = free_port();
+        let new_port = free_port();
+        let airplay = AirPlay::new().with_port(old_port);
+
+        airplay.start_server().await.unwrap();
+        airplay.restart_server(new_port).await.unwrap();
+
+        // The old port is free again and the new one is taken
+        drop(TcpListener::bind(("0.0.0.0", old_port)).await.unwrap());
+        assert!(TcpListener::bind(("0.0.0.0", new_port)).await.is_err());
+
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disabled_server_binds_no_listener() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port);
+
+        airplay.set_enabled(false).await.unwrap();
+        assert!(airplay.listener.lock().await.is_none());
+        drop(TcpListener::bind(("0.0.0.0", port)).await.unwrap());
+
+        airplay.set_enabled(true).await.unwrap();
+        assert!(TcpListener::bind(("0.0.0.0", port)).await.is_err());
+
+        airplay.set_enabled(false).await.unwrap();
+        drop(TcpListener::bind(("0.0.0.0", port)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_starts_bind_once() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port);
+        let mut first = tokio::spawn({
+            let airplay = airplay.clone();
+            async move { airplay.start_receiving().await }
+        });
+        let mut second = tokio::spawn({
+            let airplay = airplay.clone();
+            async move { airplay.start_receiving().await }
+        });
+
+        // Whichever request lost returns at once, the other waits for a peer
+        let (loser, winner) = tokio::select! {
+            result = &mut first => (result, second),
+            result = &mut second => (result, first),
+        };
+        assert!(loser.unwrap().unwrap_err().downcast_ref::<AlreadyStarting>().is_some());
+
+        while airplay.listener.lock().await.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(airplay.get_status().await, AirPlayStatus::Connecting);
+        let _peer = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        winner.await.unwrap().unwrap();
+
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_publishes_status_changes() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port).with_ip_mode(IpMode::Ipv4Only);
+        let mut changes = airplay.status_changes().subscribe();
+        let starting = tokio::spawn({
+            let airplay = airplay.clone();
+            async move { airplay.start_receiving().await }
+        });
+        assert_eq!(changes.recv().await.unwrap(), AirPlayStatus::Connecting);
+
+        while airplay.listener.lock().await.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _peer = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        starting.await.unwrap().unwrap();
+        assert_eq!(changes.recv().await.unwrap(), AirPlayStatus::Connected);
+
+        airplay.stop_receiving().await.unwrap();
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bind_failure_publishes_failed_with_reason() {
+        let taken = TcpListener::bind(("0.0.0.0", 0)).await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let airplay = AirPlay::new().with_port(port).with_ip_mode(IpMode::Ipv4Only);
+        let mut changes = airplay.status_changes().subscribe();
+
+        let error = airplay.start_receiving().await.unwrap_err();
+        assert_eq!(changes.recv().await.unwrap(), AirPlayStatus::Connecting);
+        match changes.recv().await.unwrap() {
+            AirPlayStatus::Failed(reason) => {
+                assert!(reason.contains("Failed to start server"), "{}", reason);
+                assert_eq!(reason, error.to_string());
+            }
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_second_start_while_receiving_keeps_status() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port);
+        let first = tokio::spawn({
+            let airplay = airplay.clone();
+            async move { airplay.start_receiving().await }
+        });
+        while airplay.listener.lock().await.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let _peer = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        first.await.unwrap().unwrap();
+        assert_eq!(airplay.get_status().await, AirPlayStatus::Connected);
+
+        let error = airplay.start_receiving().await.unwrap_err();
+        assert!(is_already_active(&error), "{}", error);
+        assert_eq!(airplay.get_status().await, AirPlayStatus::Connected);
+
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_accepts_connections_on_v6_listener() {
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port);
+        airplay.start_server().await.unwrap();
+
+        if airplay.listener_v6.lock().await.is_none() {
+            // No IPv6 on this host: nothing to check
+            airplay.stop_server().await.unwrap();
+            return;
+        }
+
+        let accepting = {
+            let airplay = airplay.clone();
+            tokio::spawn(async move { airplay.setup_stream().await })
+        };
+        let client = TcpStream::connect((Ipv6Addr::LOCALHOST, port)).await.unwrap();
+
+        accepting.await.unwrap().unwrap();
+        let accepted = airplay.connection().await.unwrap().lock().await.peer_addr().unwrap();
+        assert_eq!(accepted, client.local_addr().unwrap());
+
+        airplay.stop_server().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_socket_tuning_is_applied_to_accepted_stream() {
+        let port = free_port();
+        let tuning = SocketTuning {
+            nodelay: false,
+            send_buffer_size: Some(256 * 1024),
+            backlog: 4,
+        };
+        let airplay = AirPlay::new().with_port(port).with_ip_mode(IpMode::Ipv4Only).with_socket_tuning(tuning);
+        airplay.start_server().await.unwrap();
+
+        let accepting = {
+            let airplay = airplay.clone();
+            tokio::spawn(async move { airplay.setup_stream().await })
+        };
+        let _client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        accepting.await.unwrap().unwrap();
+
+        {
+            let connection = airplay.connection().await.unwrap();
+            let stream = connection.lock().await;
+            assert!(!stream.nodelay().unwrap());
+            // Some systems round the size up or double it for bookkeeping
+            assert!(socket2::SockRef::from(&*stream).send_buffer_size().unwrap() >= 256 * 1024);
+        }
+        airplay.stop_server().await.unwrap();
+
+        // The defaults send frames at once
+        let port = free_port();
+        let airplay = AirPlay::new().with_port(port).with_ip_mode(IpMode::Ipv4Only);
+        airplay.start_server().await.unwrap();
+        let accepting = {
+            let airplay = airplay.clone();
+            tokio::spawn(async move { airplay.setup_stream().await })
+        };
+        let _client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        accepting.await.unwrap().unwrap();
+        assert!(airplay.connection().await.unwrap().lock().await.nodelay().unwrap());
+        airplay.stop_server().await.unwrap();
+
+        assert!(SocketTuning { backlog: 0, ..SocketTuning::default() }.validate().is_err());
+        assert!(SocketTuning { send_buffer_size: Some(0), ..SocketTuning::default() }.validate().is_err());
+        assert!(SocketTuning { send_buffer_size: None, ..SocketTuning::default() }.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_sent_frames_are_counted() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (mut peer, _) = listener.accept().await.unwrap();
+        let draining = tokio::spawn(async move {
+            let mut sink = Vec::new();
+            tokio::io::AsyncReadExt::read_to_end(&mut peer, &mut sink).await.unwrap();
+            sink.len()
+        });
+
+        let airplay = AirPlay::new();
+        *airplay.stream.lock().await = Some(Arc::new(Mutex::new(client)));
+
+        const FRAMES: usize = 5;
+        let processed = Arc::new(ProcessedFrame { width: 4, height: 4, quality: 80, jpeg: vec![0xFF; 100] });
+        let header = FrameHeader { width: 4, height: 4, timestamp: 0, payload_len: Some(100) }.encode();
+        for _ in 0..FRAMES {
+            let frame = EncodedFrame { header: header.clone(), processed: processed.clone() };
+            airplay.send_frame(frame).await.unwrap();
+        }
+
+        let stats = airplay.get_stats().await;
+        let frame_len = (header.len() + 100) as u64;
+        assert_eq!(stats.frames_sent, FRAMES as u64);
+        assert_eq!(stats.bytes_sent, FRAMES as u64 * frame_len);
+        assert_eq!(stats.frames_dropped, 0);
+        assert!(stats.current_fps > 0.0);
+
+        *airplay.stream.lock().await = None;
+        assert_eq!(draining.await.unwrap() as u64, stats.bytes_sent);
+    }
+
+    #[tokio::test]
+    async fn test_stopping_does_not_wait_for_a_stalled_write() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        // The peer never reads, so the write fills the socket buffers and stalls
+        let (_peer, _) = listener.accept().await.unwrap();
+
+        let airplay = AirPlay::new();
+        *airplay.stream.lock().await = Some(Arc::new(Mutex::new(client)));
+        let processed = Arc::new(ProcessedFrame { width: 4, height: 4, quality: 80, jpeg: vec![0xFF; 64 * 1024 * 1024] });
+        let header = FrameHeader { width: 4, height: 4, timestamp: 0, payload_len: Some(processed.jpeg.len()) }.encode();
+        let writing = {
+            let airplay = airplay.clone();
+            tokio::spawn(async move { airplay.send_frame(EncodedFrame { header, processed }).await })
+        };
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!writing.is_finished());
+
+        tokio::time::timeout(Duration::from_secs(2), airplay.stop_receiving())
+            .await
+            .expect("stopping waited for the write")
+            .unwrap();
+        assert!(airplay.connection().await.is_none());
+        writing.abort();
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info};
+
+use super::frame_header::{FRAMING_TXT_KEY, FRAME_HEADER_VERSION};
+
+/// Time allowed for the connection and each handshake response
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest handshake response accepted from a receiver
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+const USER_AGENT: &str = "AirWin/0.1";
+
+/// AirPlay feature bits advertised in the `features` TXT record
+mod feature {
+    /// Video playback from a URL
+    pub const VIDEO: u64 = 1 << 0;
+    pub const PHOTO: u64 = 1 << 1;
+    /// Screen mirroring
+    pub const SCREEN: u64 = 1 << 7;
+    /// Audio streaming (RAOP)
+    pub const AUDIO: u64 = 1 << 9;
+    /// FairPlay SAP v2.5 stream encryption
+    pub const FAIRPLAY_SAP: u64 = 1 << 12;
+    /// FairPlay authentication
+    pub const FAIRPLAY_AUTH: u64 = 1 << 14;
+    /// MFi hardware authentication
+    pub const MFI_AUTH: u64 = 1 << 26;
+    /// Buffered audio, as AirPlay 2 speakers play it
+    pub const BUFFERED_AUDIO: u64 = 1 << 40;
+    /// HomeKit pairing and access control
+    pub const HOMEKIT_PAIRING: u64 = 1 << 46;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SenderError {
+    /// The receiver needs something AirWin cannot do, typically FairPlay
+    #[error("Receiver not supported: {0}")]
+    NotSupported(String),
+    #[error("Handshake failed: {0}")]
+    Handshake(String),
+    #[error("No response from the receiver within {0:?}")]
+    Timeout(Duration),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Feature flags of an AirPlay receiver.
+///
+/// `features` is a 64-bit mask written either as one hex number or as two
+/// comma-separated 32-bit halves, low half first ("0x5A7FFFF7,0x1E").
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Features(u64);
+
+impl Features {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut halves = value.split(',').map(|half| {
+            let half = half.trim();
+            let digits = half.strip_prefix("0x").or_else(|| half.strip_prefix("0X")).unwrap_or(half);
+            u64::from_str_radix(digits, 16).ok()
+        });
+        let low = halves.next()??;
+        let high = halves.next().unwrap_or(Some(0))?;
+        Some(Self(low | (high << 32)))
+    }
+
+    pub fn from_txt(txt: &HashMap<String, String>) -> Option<Self> {
+        txt.get("features").and_then(|value| Self::parse(value))
+    }
+
+    pub fn has(&self, bits: u64) -> bool {
+        self.0 & bits != 0
+    }
+}
+
+/// What an AirPlay receiver accepts and requires, decoded from its
+/// [`Features`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AirPlayFeatures {
+    pub video: bool,
+    pub photo: bool,
+    pub screen_mirroring: bool,
+    pub audio: bool,
+    /// AirPlay 2 buffered audio
+    pub buffered_audio: bool,
+    /// FairPlay authentication or stream encryption
+    pub fairplay: bool,
+    pub mfi_auth: bool,
+    pub homekit_pairing: bool,
+}
+
+impl From<Features> for AirPlayFeatures {
+    fn from(features: Features) -> Self {
+        Self {
+            video: features.has(feature::VIDEO),
+            photo: features.has(feature::PHOTO),
+            screen_mirroring: features.has(feature::SCREEN),
+            audio: features.has(feature::AUDIO),
+            buffered_audio: features.has(feature::BUFFERED_AUDIO),
+            fairplay: features.has(feature::FAIRPLAY_AUTH | feature::FAIRPLAY_SAP),
+            mfi_auth: features.has(feature::MFI_AUTH),
+            homekit_pairing: features.has(feature::HOMEKIT_PAIRING),
+        }
+    }
+}
+
+impl AirPlayFeatures {
+    pub fn parse(value: &str) -> Option<Self> {
+        Features::parse(value).map(Self::from)
+    }
+
+    pub fn from_txt(txt: &HashMap<String, String>) -> Option<Self> {
+        Features::from_txt(txt).map(Self::from)
+    }
+
+    /// A speaker: plays audio but doesn't take the screen, so the only
+    /// AirPlay action it could get is audio streaming
+    pub fn is_audio_only(&self) -> bool {
+        (self.audio || self.buffered_audio) && !self.screen_mirroring
+    }
+
+    /// Why a receiver with these features cannot take a mirroring stream
+    fn unsupported_reason(&self) -> Option<String> {
+        if self.is_audio_only() {
+            Some("the receiver only plays audio".to_string())
+        } else if !self.screen_mirroring {
+            Some("the receiver does not accept screen mirroring".to_string())
+        } else if self.fairplay {
+            Some("the receiver requires FairPlay".to_string())
+        } else if self.mfi_auth {
+            Some("the receiver requires MFi authentication".to_string())
+        } else if self.homekit_pairing {
+            Some("the receiver requires HomeKit pairing".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// Why the receiver described by `txt` cannot accept a stream from AirWin,
+/// if that is known before connecting.
+///
+/// AirWin streams JPEG frames with its own headers, which only another
+/// AirWin reads, so a receiver that doesn't advertise them is refused even
+/// when it would pass the handshake.
+pub fn unsupported_reason(txt: &HashMap<String, String>) -> Option<String> {
+    if txt.get("pw").is_some_and(|pw| pw == "true" || pw == "1") {
+        return Some("the receiver asks for a password".to_string());
+    }
+    if let Some(reason) = AirPlayFeatures::from_txt(txt).and_then(|features| features.unsupported_reason()) {
+        return Some(reason);
+    }
+    let framing = txt.get(FRAMING_TXT_KEY).and_then(|version| version.parse::<u8>().ok());
+    if !matches!(framing, Some(version) if version >= FRAME_HEADER_VERSION) {
+        return Some("the receiver is not an AirWin PC; mirroring to AirPlay devices is not implemented".to_string());
+    }
+    None
+}
+
+/// Connect to an AirPlay receiver and run the unencrypted part of the handshake.
+///
+/// Receivers that answer with an authentication or FairPlay challenge are
+/// reported as `NotSupported`. On success the stream is ready for frames.
+pub async fn connect(addr: SocketAddr, txt: &HashMap<String, String>) -> Result<TcpStream, SenderError> {
+    if let Some(reason) = unsupported_reason(txt) {
+        return Err(SenderError::NotSupported(reason));
+    }
+
+    let mut stream = timeout(HANDSHAKE_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| SenderError::Timeout(HANDSHAKE_TIMEOUT))??;
+    stream.set_nodelay(true)?;
+    info!("Connected to AirPlay receiver at {}", addr);
+
+    let info = request(&mut stream, "GET", "/info", 1).await?;
+    check_status(&info)?;
+    // The advertisement can be stale or incomplete: trust what the receiver reports now
+    if let Some(features) = info.header("x-apple-features").or_else(|| info.header("features")) {
+        if let Some(reason) = AirPlayFeatures::parse(features).and_then(|features| features.unsupported_reason()) {
+            return Err(SenderError::NotSupported(reason));
+        }
+    }
+
+    let setup = request(&mut stream, "SETUP", "/stream", 2).await?;
+    check_status(&setup)?;
+    debug!("AirPlay handshake with {} complete", addr);
+    Ok(stream)
+}
+
+/// Status line and headers of an RTSP response
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    reason: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Response {
+    fn parse(raw: &str) -> Result<Self, SenderError> {
+        let mut lines = raw.lines();
+        let status_line = lines.next().unwrap_or_default();
+        let mut parts = status_line.splitn(3, ' ');
+        let protocol = parts.next().unwrap_or_default();
+        if !protocol.starts_with("RTSP/") && !protocol.starts_with("HTTP/") {
+            return Err(SenderError::Handshake(format!("Unexpected response: {:?}", status_line)));
+        }
+        let status = parts
+            .next()
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| SenderError::Handshake(format!("Invalid status line: {:?}", status_line)))?;
+        let reason = parts.next().unwrap_or_default().to_string();
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        Ok(Self { status, reason, headers })
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn check_status(response: &Response) -> Result<(), SenderError> {
+    match response.status {
+        200..=299 => Ok(()),
+        // 470 "Connection Authorization Required" is the FairPlay / pairing challenge
+        401 | 403 | 470 => Err(SenderError::NotSupported(format!(
+            "the receiver requires authentication ({} {})",
+            response.status, response.reason
+        ))),
+        status => Err(SenderError::Handshake(format!(
+            "receiver answered {} {}",
+            status, response.reason
+        ))),
+    }
+}
+
+async fn request(stream: &mut TcpStream, method: &str, path: &str, cseq: u32) -> Result<Response, SenderError> {
+    let request = format!(
+        "{} {} RTSP/1.0\r\nCSeq: {}\r\nUser-Agent: {}\r\nContent-Length: 0\r\n\r\n",
+        method, path, cseq, USER_AGENT
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let head = timeout(HANDSHAKE_TIMEOUT, read_head(stream))
+        .await
+        .map_err(|_| SenderError::Timeout(HANDSHAKE_TIMEOUT))??;
+    let response = Response::parse(&head)?;
+
+    // Skip the body so the next response starts at a clean boundary
+    let length: usize = response
+        .header("content-length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_RESPONSE_SIZE {
+        return Err(SenderError::Handshake(format!("{} body of {} bytes is too large", path, length)));
+    }
+    let mut body = vec![0u8; length];
+    timeout(HANDSHAKE_TIMEOUT, stream.read_exact(&mut body))
+        .await
+        .map_err(|_| SenderError::Timeout(HANDSHAKE_TIMEOUT))??;
+    Ok(response)
+}
+
+/// Read up to the blank line ending the response headers, one byte at a
+/// time so nothing past the headers is consumed
+async fn read_head(stream: &mut TcpStream) -> Result<String, SenderError> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_RESPONSE_SIZE {
+            return Err(SenderError::Handshake("Response headers too large".to_string()));
+        }
+        if stream.read(&mut byte).await? == 0 {
+            return Err(SenderError::Handshake("Connection closed during the handshake".to_string()));
+        }
+        head.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::apple_records::AppleRecords;
+    use tokio::net::TcpListener;
+
+    /// Receiver answering every request with `status`
+    async fn mock_receiver(status: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Ok(head) = read_head(&mut stream).await {
+                let cseq = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("CSeq: "))
+                    .unwrap_or("0")
+                    .to_string();
+                let response = format!("RTSP/1.0 {}\r\nCSeq: {}\r\nContent-Length: 2\r\n\r\nok", status, cseq);
+                if stream.write_all(response.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    fn txt(features: &str) -> HashMap<String, String> {
+        HashMap::from([("features".to_string(), features.to_string())])
+    }
+
+    /// TXT records of an AirWin receiver with `features`
+    fn airwin_txt(features: &str) -> HashMap<String, String> {
+        let mut txt = txt(features);
+        txt.insert(FRAMING_TXT_KEY.to_string(), FRAME_HEADER_VERSION.to_string());
+        txt
+    }
+
+    #[test]
+    fn test_parse_features() {
+        assert_eq!(Features::parse("0x80"), Some(Features(0x80)));
+        assert_eq!(Features::parse("0x5A7FFFF7,0x1E"), Some(Features(0x1E_5A7F_FFF7)));
+        assert_eq!(Features::parse("nonsense"), None);
+    }
+
+    #[test]
+    fn test_decode_real_world_features() {
+        // Apple TV 4K
+        let apple_tv = AirPlayFeatures::parse("0x5A7FFFF7,0x1E").unwrap();
+        assert_eq!(
+            apple_tv,
+            AirPlayFeatures {
+                video: true,
+                photo: true,
+                screen_mirroring: true,
+                audio: true,
+                buffered_audio: false,
+                fairplay: true,
+                mfi_auth: false,
+                homekit_pairing: false,
+            }
+        );
+        assert!(!apple_tv.is_audio_only());
+
+        // AirPlay 2 speaker (AirPort Express): audio only, with MFi and HomeKit
+        let speaker = AirPlayFeatures::parse("0x445F8A00,0x1C340").unwrap();
+        assert_eq!(
+            speaker,
+            AirPlayFeatures {
+                video: false,
+                photo: false,
+                screen_mirroring: false,
+                audio: true,
+                buffered_audio: true,
+                fairplay: false,
+                mfi_auth: true,
+                homekit_pairing: true,
+            }
+        );
+        assert!(speaker.is_audio_only());
+        assert!(unsupported_reason(&txt("0x445F8A00,0x1C340")).unwrap().contains("only plays audio"));
+
+        // Mirroring-only receiver, as AirWin advertises itself to others
+        let receiver = AirPlayFeatures::parse("0x80").unwrap();
+        assert!(receiver.screen_mirroring && !receiver.audio && !receiver.is_audio_only());
+        assert_eq!(AirPlayFeatures::parse("0x0,nonsense"), None);
+    }
+
+    #[test]
+    fn test_fairplay_receivers_are_not_supported() {
+        // Apple TV 4K advertisement: mirroring with FairPlay and HomeKit pairing
+        assert!(unsupported_reason(&txt("0x5A7FFFF7,0x1E")).unwrap().contains("FairPlay"));
+        assert!(unsupported_reason(&txt("0x0")).unwrap().contains("mirroring"));
+        assert_eq!(unsupported_reason(&airwin_txt("0x80")), None);
+    }
+
+    #[test]
+    fn test_only_airwin_receivers_are_supported() {
+        // An open receiver would take the stream but can't decode AirWin's frames
+        assert!(unsupported_reason(&txt("0x80")).unwrap().contains("not an AirWin PC"));
+        assert!(unsupported_reason(&HashMap::new()).unwrap().contains("not an AirWin PC"));
+        let mut old = airwin_txt("0x80");
+        old.insert(FRAMING_TXT_KEY.to_string(), "0".to_string());
+        assert!(unsupported_reason(&old).is_some());
+
+        // AirWin advertises itself so that other AirWin PCs accept it
+        let own = AppleRecords::create_airplay_txt_records().unwrap();
+        assert!(!own.contains_key("pw"));
+        assert_eq!(own.get(FRAMING_TXT_KEY).map(String::as_str), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_open_receiver() {
+        let addr = mock_receiver("200 OK").await;
+        let stream = connect(addr, &airwin_txt("0x80")).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+    }
+
+    #[tokio::test]
+    async fn test_authorization_challenge_is_not_supported() {
+        let addr = mock_receiver("470 Connection Authorization Required").await;
+        let err = connect(addr, &airwin_txt("0x80")).await.unwrap_err();
+        assert!(matches!(err, SenderError::NotSupported(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_advertised_fairplay_fails_before_connecting() {
+        // Nothing listens here: the error must come from the TXT record alone
+        let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let err = connect(addr, &txt("0x5A7FFFF7,0x1E")).await.unwrap_err();
+        assert!(matches!(err, SenderError::NotSupported(_)), "{:?}", err);
+    }
+}
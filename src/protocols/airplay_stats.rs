@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Window over which the current frame rate is measured
+pub const FPS_WINDOW: Duration = Duration::from_secs(2);
+
+/// Performance of the current streaming session
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AirPlayStats {
+    pub frames_sent: u64,
+    /// Header and JPEG bytes written to the peer
+    pub bytes_sent: u64,
+    /// Frames discarded because the network could not keep up with capture
+    pub frames_dropped: u64,
+    /// Frames sent per second over the last [`FPS_WINDOW`]
+    pub current_fps: f64,
+    /// Mean time spent scaling and encoding a captured frame
+    pub avg_encode_ms: f64,
+}
+
+/// Counters fed by the capture and sender tasks
+#[derive(Debug, Default)]
+pub struct StatsRecorder {
+    frames_sent: u64,
+    bytes_sent: u64,
+    frames_encoded: u64,
+    encode_time: Duration,
+    recent_sends: VecDeque<Instant>,
+}
+
+impl StatsRecorder {
+    /// Record the time taken to encode one captured frame
+    pub fn record_encode(&mut self, elapsed: Duration) {
+        self.frames_encoded += 1;
+        self.encode_time += elapsed;
+    }
+
+    /// Record one frame of `bytes` written to the peer at `now`
+    pub fn record_sent(&mut self, bytes: usize, now: Instant) {
+        self.frames_sent += 1;
+        self.bytes_sent += bytes as u64;
+        self.recent_sends.push_back(now);
+        self.prune(now);
+    }
+
+    /// Counters as of `now`. Dropped frames are counted by the frame queue.
+    pub fn snapshot(&mut self, frames_dropped: u64, now: Instant) -> AirPlayStats {
+        self.prune(now);
+        AirPlayStats {
+            frames_sent: self.frames_sent,
+            bytes_sent: self.bytes_sent,
+            frames_dropped,
+            current_fps: self.recent_sends.len() as f64 / FPS_WINDOW.as_secs_f64(),
+            avg_encode_ms: if self.frames_encoded == 0 {
+                0.0
+            } else {
+                self.encode_time.as_secs_f64() * 1000.0 / self.frames_encoded as f64
+            },
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self.recent_sends.front().is_some_and(|sent| now.duration_since(*sent) > FPS_WINDOW) {
+            self.recent_sends.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fps_counts_only_the_window() {
+        let mut recorder = StatsRecorder::default();
+        let start = Instant::now();
+        // 30 frames per second for 4 seconds
+        for i in 0..120 {
+            recorder.record_sent(1_000, start + Duration::from_millis(i * 1000 / 30));
+        }
+        recorder.record_encode(Duration::from_millis(4));
+        recorder.record_encode(Duration::from_millis(8));
+
+        let stats = recorder.snapshot(3, start + Duration::from_secs(4));
+        assert_eq!(stats.frames_sent, 120);
+        assert_eq!(stats.bytes_sent, 120_000);
+        assert_eq!(stats.frames_dropped, 3);
+        assert!((stats.current_fps - 30.0).abs() < 1.0, "{}", stats.current_fps);
+        assert!((stats.avg_encode_ms - 6.0).abs() < 0.01, "{}", stats.avg_encode_ms);
+
+        // Nothing sent for a while
+        let idle = recorder.snapshot(3, start + Duration::from_secs(10));
+        assert_eq!(idle.current_fps, 0.0);
+        assert_eq!(idle.frames_sent, 120);
+    }
+}
@@ -1,9 +1,100 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use uuid::Uuid;
 use sha2::{Sha256, Digest};
 use rand::Rng;
 use anyhow::Result;
 
+use super::frame_header::{FRAMING_TXT_KEY, FRAME_HEADER_VERSION};
+
+/// AirPlay feature bits advertised by the receiver, low and high 32 bits:
+/// screen mirroring (bit 7) only, the one thing the receiver does
+const AIRPLAY_FEATURES: &str = "0x80,0x0";
+
+/// File in the config folder keeping the AirPlay `deviceid`
+const AIRPLAY_DEVICE_ID_FILE: &str = "airplay_device_id";
+
+/// Longest name shown to peers, in bytes: the limit of a DNS label, which
+/// the name becomes in mDNS instance names
+pub const MAX_NAME_LEN: usize = 63;
+
+/// Longest suffix appended to the name, in bytes
+pub const MAX_SUFFIX_LEN: usize = 16;
+
+/// How long other devices get to answer before the name is registered
+pub const NAME_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Name of this computer, used for the `_device-info` record
+pub fn hostname() -> Result<String> {
+    Ok(::hostname::get()?.to_string_lossy().to_string())
+}
+
+/// `name` without control characters or surrounding whitespace, cut to
+/// [`MAX_NAME_LEN`] bytes. `None` if nothing is left.
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let cleaned: String = name.chars().filter(|c| !c.is_control()).collect();
+    let mut cleaned = cleaned.trim();
+    while cleaned.len() > MAX_NAME_LEN {
+        let mut end = MAX_NAME_LEN;
+        while !cleaned.is_char_boundary(end) {
+            end -= 1;
+        }
+        cleaned = cleaned[..end].trim_end();
+    }
+    (!cleaned.is_empty()).then(|| cleaned.to_string())
+}
+
+/// Name shown to peers: the configured friendly name, or the hostname
+pub fn friendly_name(configured: Option<&str>) -> Result<String> {
+    match configured.and_then(sanitize_name) {
+        Some(name) => Ok(name),
+        None => hostname(),
+    }
+}
+
+/// `name` followed by `suffix`, for telling apart PCs with the same name.
+/// The name is cut so that both fit in [`MAX_NAME_LEN`].
+pub fn with_suffix(name: &str, suffix: Option<&str>) -> String {
+    match suffix.and_then(sanitize_name) {
+        Some(suffix) => append(name, &format!(" {}", suffix)),
+        None => name.to_string(),
+    }
+}
+
+/// `name`, or the first of "name (2)", "name (3)", ... that isn't in
+/// `taken`. `taken` holds the mDNS instance names already on the network,
+/// compared ignoring case like mDNS does.
+pub fn unique_name(name: &str, taken: &[String]) -> String {
+    let taken: HashSet<String> = taken.iter().map(|taken| instance_name(taken).to_lowercase()).collect();
+    if !taken.contains(&name.to_lowercase()) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|number| append(name, &format!(" ({})", number)))
+        .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+        .expect("a free number exists")
+}
+
+/// `name` followed by `tail`, cut on a character boundary to fit
+fn append(name: &str, tail: &str) -> String {
+    let mut end = MAX_NAME_LEN.saturating_sub(tail.len()).min(name.len());
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", name[..end].trim_end(), tail)
+}
+
+/// Six colon-separated hex octets, like "AA:BB:CC:DD:EE:FF"
+fn is_device_id(id: &str) -> bool {
+    let octets: Vec<&str> = id.split(':').collect();
+    octets.len() == 6 && octets.iter().all(|octet| octet.len() == 2 && u8::from_str_radix(octet, 16).is_ok())
+}
+
+/// "Marco's PC._airdrop._tcp.local." advertises as "Marco's PC"
+fn instance_name(fullname: &str) -> &str {
+    fullname.split("._").next().unwrap_or(fullname).trim()
+}
+
 /// Apple-specific TXT record generator for AirDrop mDNS services
 pub struct AppleRecords;
 
@@ -50,8 +141,10 @@ impl AppleRecords {
         hex::encode(service_data).to_lowercase()
     }
 
-    /// Create complete AirDrop TXT records compatible with Apple devices
-    pub fn create_airdrop_txt_records() -> Result<HashMap<String, String>> {
+    /// Create complete AirDrop TXT records compatible with Apple devices.
+    /// `supports_awdl` must reflect whether AWDL is actually running, and
+    /// `name` is the friendly name shown to peers.
+    pub fn create_airdrop_txt_records(supports_awdl: bool, name: &str) -> Result<HashMap<String, String>> {
         let mut properties = HashMap::new();
         
         // Core AirDrop properties - using Apple compatible flags
@@ -70,8 +163,7 @@ impl AppleRecords {
         
         // Device info
         properties.insert("model".to_string(), "Windows,1".to_string());
-        properties.insert("name".to_string(), 
-            hostname::get()?.to_string_lossy().to_string());
+        properties.insert("name".to_string(), name.to_string());
         properties.insert("system_version".to_string(), "10.0".to_string());
         
         // Capabilities
@@ -86,7 +178,7 @@ impl AppleRecords {
         properties.insert("supports_sharing".to_string(), "1".to_string());
         
         // Transport capabilities
-        properties.insert("supports_awdl".to_string(), "1".to_string());
+        properties.insert("supports_awdl".to_string(), if supports_awdl { "1" } else { "0" }.to_string());
         properties.insert("supports_ble".to_string(), "1".to_string());
         properties.insert("supports_wifi_direct".to_string(), "1".to_string());
         
@@ -108,7 +200,7 @@ impl AppleRecords {
     }
 
     /// Create Companion Link TXT records (for device pairing)
-    pub fn create_companion_txt_records() -> Result<HashMap<String, String>> {
+    pub fn create_companion_txt_records(name: &str) -> Result<HashMap<String, String>> {
         let mut properties = HashMap::new();
         
         properties.insert("rpMRtID".to_string(), Self::generate_computer_id());
@@ -118,8 +210,7 @@ impl AppleRecords {
         properties.insert("rpHA".to_string(), Self::generate_device_hash());
         properties.insert("rpHI".to_string(), Self::generate_computer_id());
         properties.insert("rpMd".to_string(), "Windows,1".to_string());
-        properties.insert("rpNm".to_string(), 
-            hostname::get()?.to_string_lossy().to_string());
+        properties.insert("rpNm".to_string(), name.to_string());
         
         Ok(properties)
     }
@@ -139,6 +230,59 @@ impl AppleRecords {
         Ok(properties)
     }
 
+    /// Create AirPlay receiver TXT records
+    pub fn create_airplay_txt_records() -> Result<HashMap<String, String>> {
+        let mut properties = HashMap::new();
+
+        properties.insert("features".to_string(), AIRPLAY_FEATURES.to_string());
+        // iOS only lists receivers whose model it recognizes
+        properties.insert("model".to_string(), "AppleTV3,2".to_string());
+        // Senders remember receivers by this id, so it stays the same across restarts.
+        // No `pk` or `pi`: AirWin does no pairing.
+        properties.insert(
+            "deviceid".to_string(),
+            Self::airplay_device_id(&crate::utils::config::config_dir().join(AIRPLAY_DEVICE_ID_FILE)),
+        );
+        properties.insert("srcvers".to_string(), "220.68".to_string());
+        properties.insert("flags".to_string(), "0x4".to_string());
+        properties.insert("vv".to_string(), "2".to_string());
+        // Tells AirWin senders that this receiver reads their JPEG frames
+        properties.insert(FRAMING_TXT_KEY.to_string(), FRAME_HEADER_VERSION.to_string());
+
+        Ok(properties)
+    }
+
+    /// Device ID stored at `path`, generated and stored there the first time
+    /// or when the stored one is invalid. It is still returned when it can't
+    /// be stored, and only changes on the next start then.
+    pub fn airplay_device_id(path: &Path) -> String {
+        if let Ok(stored) = std::fs::read_to_string(path) {
+            let stored = stored.trim();
+            if is_device_id(stored) {
+                return stored.to_string();
+            }
+            tracing::warn!("Invalid AirPlay device id in {:?}, generating a new one", path);
+        }
+        let id = Self::generate_device_id();
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, &id));
+        if let Err(e) = saved {
+            tracing::warn!("Failed to store the AirPlay device id in {:?}: {}", path, e);
+        }
+        id
+    }
+
+    /// Generate a random MAC-style device ID, as used by AirPlay
+    pub fn generate_device_id() -> String {
+        let mut rng = rand::thread_rng();
+        let mut bytes: [u8; 6] = rng.gen();
+        // Locally administered, unicast
+        bytes[0] = (bytes[0] | 0x02) & 0xFE;
+        bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(":")
+    }
+
     /// Update TXT records with current session information
     pub fn update_session_records(properties: &mut HashMap<String, String>) {
         properties.insert("session_id".to_string(), Self::generate_session_hash());
@@ -161,3 +305,102 @@ impl AppleRecords {
         required_keys.iter().all(|key| properties.contains_key(*key))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_awdl_reflects_availability() {
+        let records = AppleRecords::create_airdrop_txt_records(false, "PC").unwrap();
+        assert_eq!(records["supports_awdl"], "0");
+        assert!(AppleRecords::validate_apple_compatibility(&records));
+
+        let records = AppleRecords::create_airdrop_txt_records(true, "PC").unwrap();
+        assert_eq!(records["supports_awdl"], "1");
+    }
+
+    #[test]
+    fn test_friendly_name_is_advertised_and_sanitized() {
+        let records = AppleRecords::create_airdrop_txt_records(false, "Marco's PC").unwrap();
+        assert_eq!(records["name"], "Marco's PC");
+        let records = AppleRecords::create_companion_txt_records("Marco's PC").unwrap();
+        assert_eq!(records["rpNm"], "Marco's PC");
+
+        assert_eq!(sanitize_name("  Marco's\u{7}\n PC \t").as_deref(), Some("Marco's PC"));
+        assert_eq!(sanitize_name("\u{0}\r\n "), None);
+
+        // Cut on a character boundary
+        let long = "è".repeat(MAX_NAME_LEN);
+        let cut = sanitize_name(&long).unwrap();
+        assert!(cut.len() <= MAX_NAME_LEN);
+        assert!(cut.chars().all(|c| c == 'è'));
+
+        assert_eq!(friendly_name(Some("Marco's PC")).unwrap(), "Marco's PC");
+        assert_eq!(friendly_name(Some(" ")).unwrap(), hostname().unwrap());
+        assert_eq!(friendly_name(None).unwrap(), hostname().unwrap());
+    }
+
+    #[test]
+    fn test_colliding_names_are_numbered() {
+        let taken = |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+
+        assert_eq!(unique_name("PC", &taken(&["Mac._airdrop._tcp.local."])), "PC");
+        assert_eq!(unique_name("PC", &taken(&["pc._airdrop._tcp.local."])), "PC (2)");
+        assert_eq!(
+            unique_name(
+                "PC",
+                &taken(&["PC._airdrop._tcp.local.", "PC (2)._companion-link._tcp.local.", "PC (4)._airdrop._tcp.local."])
+            ),
+            "PC (3)"
+        );
+
+        // The number always fits, the name is cut for it
+        let long = "a".repeat(MAX_NAME_LEN);
+        let numbered = unique_name(&long, &taken(&[&long]));
+        assert_eq!(numbered.len(), MAX_NAME_LEN);
+        assert!(numbered.ends_with("a (2)"));
+
+        assert_eq!(with_suffix("PC", Some(" ufficio ")), "PC ufficio");
+        assert_eq!(with_suffix("PC", Some("\n")), "PC");
+        assert_eq!(with_suffix("PC", None), "PC");
+        assert_eq!(with_suffix(&long, Some("x")).len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn test_airplay_records_advertise_mirroring() {
+        let records = AppleRecords::create_airplay_txt_records().unwrap();
+        for key in ["features", "model", "deviceid", "srcvers", "airwin"] {
+            assert!(records.contains_key(key), "{} missing", key);
+        }
+
+        let features: Vec<u32> = records["features"]
+            .split(',')
+            .map(|part| u32::from_str_radix(part.trim_start_matches("0x"), 16).unwrap())
+            .collect();
+        // Only screen mirroring is implemented, and nothing else is claimed
+        assert_eq!(features, [0x80, 0]);
+        assert!(!records.contains_key("pk") && !records.contains_key("pi"));
+        assert!(crate::protocols::airplay_sender::unsupported_reason(&records).is_none());
+
+        assert!(is_device_id(&records["deviceid"]));
+    }
+
+    #[test]
+    fn test_airplay_device_id_is_kept_across_starts() {
+        let dir = std::env::temp_dir().join(format!("airwin_device_id_{}", Uuid::new_v4().simple()));
+        let path = dir.join(AIRPLAY_DEVICE_ID_FILE);
+
+        let first = AppleRecords::airplay_device_id(&path);
+        assert!(is_device_id(&first));
+        assert_eq!(AppleRecords::airplay_device_id(&path), first);
+
+        // A damaged file gets a new id
+        std::fs::write(&path, "not an id").unwrap();
+        let replaced = AppleRecords::airplay_device_id(&path);
+        assert!(is_device_id(&replaced));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), replaced);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -10,9 +10,13 @@ use owdl::{
 };
 use owdl::daemon::{IoConfig, ServiceConfig, DaemonStats};
 
+use crate::utils::clock::{self, SharedClock};
 use crate::utils::{AirWinError, AirWinResult};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{info, warn, error, debug};
 use serde::{Deserialize, Serialize};
 
@@ -27,6 +31,16 @@ pub struct AwdlManager {
     state: Arc<RwLock<AwdlManagerState>>,
     /// Discovered peers
     peers: Arc<RwLock<Vec<AwdlPeer>>>,
+    /// When each peer was last heard from
+    sightings: Arc<RwLock<PeerSightings>>,
+    /// Source of the timestamps used for peer timeouts
+    clock: SharedClock,
+    /// Whether AWDL can actually be used, published to the UI and mDNS records
+    availability: watch::Sender<AwdlAvailability>,
+    /// Peer discovery loop, aborted on stop
+    discovery_task: Option<JoinHandle<()>>,
+    /// MAC address of the interface AWDL runs on, resolved on initialize
+    local_mac: watch::Sender<Option<[u8; 6]>>,
 }
 
 /// AWDL Manager configuration
@@ -46,6 +60,8 @@ pub struct AwdlManagerConfig {
     pub discovery_interval: u64,
     /// Maximum number of peers to maintain
     pub max_peers: usize,
+    /// Seconds without hearing from a peer before it is dropped
+    pub peer_timeout: u64,
 }
 
 /// AWDL Manager state
@@ -65,6 +81,17 @@ pub enum AwdlManagerState {
     Error,
 }
 
+/// Whether AWDL can be used, as advertised to peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AwdlAvailability {
+    /// The daemon is running
+    On,
+    /// Disabled in the configuration, not started yet, or stopped
+    Off,
+    /// Enabled but could not start, e.g. because its port is taken
+    Unavailable,
+}
+
 /// AWDL peer information for AirWin
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AwdlPeerInfo {
@@ -92,10 +119,35 @@ impl Default for AwdlManagerConfig {
             auto_start: true,
             discovery_interval: 30,
             max_peers: 50,
+            peer_timeout: 90,
         }
     }
 }
 
+/// Last time each peer was heard from, by MAC address
+#[derive(Debug, Default)]
+struct PeerSightings {
+    last_seen: HashMap<[u8; 6], DateTime<Utc>>,
+}
+
+impl PeerSightings {
+    /// Nothing reports AWDL peers until discovery is implemented, so only the
+    /// tests record sightings for now
+    #[cfg(test)]
+    fn saw(&mut self, mac: [u8; 6], now: DateTime<Utc>) {
+        self.last_seen.insert(mac, now);
+    }
+
+    fn last_seen(&self, mac: &[u8; 6]) -> Option<DateTime<Utc>> {
+        self.last_seen.get(mac).copied()
+    }
+
+    /// Forget the peers not heard from for more than `timeout`
+    fn expire(&mut self, now: DateTime<Utc>, timeout: chrono::Duration) {
+        self.last_seen.retain(|_, seen| now - *seen <= timeout);
+    }
+}
+
 impl AwdlManager {
     /// Create new AWDL manager
     pub fn new(config: AwdlManagerConfig) -> Self {
@@ -104,18 +156,67 @@ impl AwdlManager {
             config,
             state: Arc::new(RwLock::new(AwdlManagerState::Stopped)),
             peers: Arc::new(RwLock::new(Vec::new())),
+            sightings: Arc::new(RwLock::new(PeerSightings::default())),
+            clock: clock::system(),
+            availability: watch::channel(AwdlAvailability::Off).0,
+            discovery_task: None,
+            local_mac: watch::channel(None).0,
+        }
+    }
+
+    /// Read peer timestamps from `clock` instead of the system time
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Follow AWDL availability changes
+    pub fn availability(&self) -> watch::Receiver<AwdlAvailability> {
+        self.availability.subscribe()
+    }
+
+    /// MAC address of the local AWDL interface, `None` until it is resolved
+    pub fn local_mac(&self) -> Option<[u8; 6]> {
+        *self.local_mac.borrow()
+    }
+
+    /// Follow the local MAC address, which is only known after initialize
+    pub fn local_mac_updates(&self) -> watch::Receiver<Option<[u8; 6]>> {
+        self.local_mac.subscribe()
+    }
+
+    /// Look up the MAC address of the configured interface, or of the first
+    /// interface that has a usable one
+    fn resolve_local_mac(&self) {
+        let interfaces = pnet::datalink::interfaces()
+            .into_iter()
+            .map(|interface| (interface.name, interface.mac.map(|mac| mac.octets())));
+        let mac = pick_mac(interfaces, self.config.interface.as_deref());
+        match mac {
+            Some(mac) => info!("AWDL interface MAC address is {}", AwdlUtils::format_mac_address(&mac)),
+            None => warn!("Could not resolve the MAC address of the AWDL interface"),
         }
+        self.local_mac.send_replace(mac);
+    }
+
+    /// Disable AWDL after a failure that retrying will not fix
+    async fn mark_unavailable(&mut self) {
+        self.config.enabled = false;
+        self.set_state(AwdlManagerState::Stopped).await;
+        self.availability.send_replace(AwdlAvailability::Unavailable);
     }
 
     /// Initialize the AWDL manager
     pub async fn initialize(&mut self) -> AirWinResult<()> {
         if !self.config.enabled {
             info!("AWDL protocol is disabled in configuration");
+            self.availability.send_replace(AwdlAvailability::Off);
             return Ok(());
         }
 
         self.set_state(AwdlManagerState::Initializing).await;
         info!("Initializing AWDL manager");
+        self.resolve_local_mac();
 
         // Create daemon configuration
         let daemon_config = DaemonConfig::default();
@@ -140,8 +241,7 @@ impl AwdlManager {
                     // Check for port binding errors
                     if error_msg.contains("10048") || error_msg.contains("already in use") || error_msg.contains("bind") {
                         warn!("AWDL port already in use, disabling AWDL support: {}", e);
-                        self.config.enabled = false;
-                        self.set_state(AwdlManagerState::Stopped).await;
+                        self.mark_unavailable().await;
                         return Ok(());
                     }
                     
@@ -164,8 +264,7 @@ impl AwdlManager {
                 // Check for port binding errors
                 if error_msg.contains("10048") || error_msg.contains("already in use") || error_msg.contains("bind") {
                     warn!("Cannot build AWDL daemon due to port conflict, disabling AWDL: {}", e);
-                    self.config.enabled = false;
-                    self.set_state(AwdlManagerState::Stopped).await;
+                    self.mark_unavailable().await;
                     return Ok(());
                 }
                 
@@ -189,6 +288,7 @@ impl AwdlManager {
             match daemon.start().await {
                 Ok(()) => {
                     self.set_state(AwdlManagerState::Running).await;
+                    self.availability.send_replace(AwdlAvailability::On);
                     info!("AWDL manager started successfully");
 
                     // Start peer discovery task
@@ -201,9 +301,8 @@ impl AwdlManager {
                     let error_msg = e.to_string();
                     if error_msg.contains("10048") || error_msg.contains("already in use") || error_msg.contains("bind") {
                         warn!("AWDL port already in use, continuing without AWDL support");
-                        self.set_state(AwdlManagerState::Stopped).await;
                         // Don't fail completely, just disable AWDL
-                        self.config.enabled = false;
+                        self.mark_unavailable().await;
                         return Ok(());
                     }
                     
@@ -223,11 +322,13 @@ impl AwdlManager {
     pub async fn stop(&mut self) -> AirWinResult<()> {
         self.set_state(AwdlManagerState::Stopping).await;
         info!("Stopping AWDL manager");
+        self.stop_peer_discovery();
 
         if let Some(ref mut daemon) = self.daemon {
             match daemon.stop().await {
                 Ok(()) => {
                     self.set_state(AwdlManagerState::Stopped).await;
+                    self.availability.send_replace(AwdlAvailability::Off);
                     info!("AWDL manager stopped successfully");
                     Ok(())
                 }
@@ -251,17 +352,35 @@ impl AwdlManager {
     /// Get discovered peers
     pub async fn get_peers(&self) -> Vec<AwdlPeerInfo> {
         let peers = self.peers.read().await;
-        peers.iter().map(|peer| self.convert_peer_info(peer)).collect()
+        let sightings = self.sightings.read().await;
+        peers
+            .iter()
+            .map(|peer| self.convert_peer_info(peer, sightings.last_seen(&peer.address)))
+            .collect()
+    }
+
+    /// Drop the peers not heard from within the configured timeout
+    async fn expire_peers(
+        peers: &RwLock<Vec<AwdlPeer>>,
+        sightings: &RwLock<PeerSightings>,
+        now: DateTime<Utc>,
+        timeout: chrono::Duration,
+    ) {
+        let mut sightings = sightings.write().await;
+        sightings.expire(now, timeout);
+        peers.write().await.retain(|peer| sightings.last_seen(&peer.address).is_some());
     }
 
     /// Send data to a specific peer
     pub async fn send_data(&self, peer_mac: [u8; 6], data: &[u8]) -> AirWinResult<()> {
         if let Some(ref _daemon) = self.daemon {
+            let src_mac = self.local_mac().ok_or_else(|| {
+                AirWinError::NetworkError("Local AWDL MAC address is unknown".to_string())
+            })?;
             // Create AWDL data frame
-            let _src_mac = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00]; // TODO: Get actual MAC
             let _frame = AwdlData::new(
                 peer_mac,
-                _src_mac,
+                src_mac,
                 0x0800, // IP protocol
                 bytes::Bytes::copy_from_slice(data),
             );
@@ -300,6 +419,8 @@ impl AwdlManager {
 
         if was_running {
             self.stop().await?;
+        } else {
+            self.stop_peer_discovery();
         }
 
         self.config = config;
@@ -314,15 +435,22 @@ impl AwdlManager {
     /// Set manager state
     async fn set_state(&self, state: AwdlManagerState) {
         *self.state.write().await = state;
+        if state == AwdlManagerState::Error {
+            self.availability.send_replace(AwdlAvailability::Unavailable);
+        }
     }
 
-    /// Start peer discovery task
-    async fn start_peer_discovery(&self) {
+    /// Start the peer discovery task, replacing the one already running
+    async fn start_peer_discovery(&mut self) {
+        self.stop_peer_discovery();
         let peers: Arc<RwLock<Vec<AwdlPeer>>> = Arc::clone(&self.peers);
+        let sightings = Arc::clone(&self.sightings);
+        let clock = Arc::clone(&self.clock);
         let interval = self.config.discovery_interval;
         let max_peers = self.config.max_peers;
+        let peer_timeout = chrono::Duration::seconds(self.config.peer_timeout as i64);
 
-        tokio::spawn(async move {
+        self.discovery_task = Some(tokio::spawn(async move {
             let mut interval_timer = tokio::time::interval(
                 tokio::time::Duration::from_secs(interval)
             );
@@ -334,33 +462,58 @@ impl AwdlManager {
                 debug!("Running peer discovery...");
 
                 // Clean up old peers and maintain max peer limit
+                Self::expire_peers(&peers, &sightings, clock.now(), peer_timeout).await;
                 let mut peers_guard = peers.write().await;
-                let _now = chrono::Utc::now();
-                peers_guard.retain(|_peer| {
-                    // TODO: Implement peer timeout logic
-                    true
-                });
-
                 if peers_guard.len() > max_peers {
                     peers_guard.truncate(max_peers);
                 }
             }
-        });
+        }));
+    }
+
+    /// Abort the peer discovery task, if running
+    fn stop_peer_discovery(&mut self) {
+        if let Some(task) = self.discovery_task.take() {
+            task.abort();
+            debug!("Stopped AWDL peer discovery");
+        }
     }
 
     /// Convert OWDL peer to AirWin peer info
-    fn convert_peer_info(&self, peer: &AwdlPeer) -> AwdlPeerInfo {
+    fn convert_peer_info(&self, peer: &AwdlPeer, last_seen: Option<DateTime<Utc>>) -> AwdlPeerInfo {
         AwdlPeerInfo {
             mac_address: peer.address,
             device_name: peer.name.clone().unwrap_or_else(|| "Unknown".to_string()),
             service_name: self.config.service_name.clone(),
-            last_seen: chrono::Utc::now(), // TODO: Use actual timestamp
+            last_seen: last_seen.unwrap_or_else(|| self.clock.now()),
             signal_strength: None, // TODO: Get from peer if available
             capabilities: vec![], // TODO: Extract from peer
         }
     }
 }
 
+impl Drop for AwdlManager {
+    fn drop(&mut self) {
+        self.stop_peer_discovery();
+    }
+}
+
+/// MAC address of the interface named `wanted`, or of the first interface
+/// with a valid one when no name is configured or it isn't found
+fn pick_mac(
+    interfaces: impl IntoIterator<Item = (String, Option<[u8; 6]>)>,
+    wanted: Option<&str>,
+) -> Option<[u8; 6]> {
+    let usable: Vec<(String, [u8; 6])> = interfaces
+        .into_iter()
+        .filter_map(|(name, mac)| mac.filter(AwdlUtils::validate_mac_address).map(|mac| (name, mac)))
+        .collect();
+    wanted
+        .and_then(|wanted| usable.iter().find(|(name, _)| name == wanted))
+        .or_else(|| usable.first())
+        .map(|(_, mac)| *mac)
+}
+
 /// AWDL protocol utilities
 pub struct AwdlUtils;
 
@@ -395,6 +548,8 @@ impl AwdlUtils {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::clock::{Clock, FakeClock};
+    use std::time::Duration;
 
     #[test]
     fn test_awdl_manager_config_default() {
@@ -405,6 +560,18 @@ mod tests {
         assert_eq!(config.service_name, "_airwin._tcp");
     }
 
+    #[tokio::test]
+    async fn test_port_conflict_marks_unavailable() {
+        let mut manager = AwdlManager::new(AwdlManagerConfig::default());
+        let availability = manager.availability();
+        assert_eq!(*availability.borrow(), AwdlAvailability::Off);
+
+        manager.mark_unavailable().await;
+        assert_eq!(*availability.borrow(), AwdlAvailability::Unavailable);
+        assert!(!manager.config.enabled);
+        assert_eq!(manager.get_state().await, AwdlManagerState::Stopped);
+    }
+
     #[test]
     fn test_awdl_utils_mac_validation() {
         assert!(!AwdlUtils::validate_mac_address(&[0; 6]));
@@ -419,6 +586,78 @@ mod tests {
         assert_eq!(formatted, "00:11:22:33:44:55");
     }
 
+    #[test]
+    fn test_local_mac_is_picked_and_formatted() {
+        let interfaces = || {
+            vec![
+                ("lo".to_string(), Some([0; 6])),
+                ("Ethernet".to_string(), Some([0x02, 0x11, 0x22, 0x33, 0x44, 0x55])),
+                ("Wi-Fi".to_string(), Some([0xa4, 0x5e, 0x60, 0xd1, 0x0b, 0xee])),
+                ("Tunnel".to_string(), None),
+            ]
+        };
+        assert_eq!(pick_mac(interfaces(), Some("Wi-Fi")), Some([0xa4, 0x5e, 0x60, 0xd1, 0x0b, 0xee]));
+        // Loopback is skipped, a missing interface falls back to the first usable one
+        assert_eq!(pick_mac(interfaces(), None), Some([0x02, 0x11, 0x22, 0x33, 0x44, 0x55]));
+        assert_eq!(pick_mac(interfaces(), Some("Tunnel")), Some([0x02, 0x11, 0x22, 0x33, 0x44, 0x55]));
+        assert_eq!(pick_mac(vec![("lo".to_string(), Some([0; 6]))], None), None);
+
+        let manager = AwdlManager::new(AwdlManagerConfig::default());
+        let mut updates = manager.local_mac_updates();
+        assert_eq!(manager.local_mac(), None);
+        manager.local_mac.send_replace(Some([0xa4, 0x5e, 0x60, 0xd1, 0x0b, 0xee]));
+        assert!(updates.has_changed().unwrap());
+        let mac = updates.borrow_and_update().unwrap();
+        assert_eq!(AwdlUtils::format_mac_address(&mac), "a4:5e:60:d1:0b:ee");
+        assert_eq!(manager.local_mac(), Some(mac));
+    }
+
+    #[test]
+    fn test_silent_peer_expires() {
+        let clock = FakeClock::new("2024-01-01T00:00:00Z".parse().unwrap());
+        let timeout = chrono::Duration::seconds(AwdlManagerConfig::default().peer_timeout as i64);
+        let (quiet, chatty) = ([0x02, 0, 0, 0, 0, 1], [0x02, 0, 0, 0, 0, 2]);
+
+        let mut sightings = PeerSightings::default();
+        sightings.saw(quiet, clock.now());
+        sightings.saw(chatty, clock.now());
+
+        clock.advance(Duration::from_secs(60));
+        sightings.saw(chatty, clock.now());
+        sightings.expire(clock.now(), timeout);
+        assert!(sightings.last_seen(&quiet).is_some());
+
+        clock.advance(Duration::from_secs(31));
+        sightings.expire(clock.now(), timeout);
+        assert_eq!(sightings.last_seen(&quiet), None);
+        assert_eq!(sightings.last_seen(&chatty), Some(clock.now() - chrono::Duration::seconds(31)));
+    }
+
+    #[tokio::test]
+    async fn test_stop_halts_peer_discovery() {
+        let clock = FakeClock::new("2024-01-01T00:00:00Z".parse().unwrap());
+        let config = AwdlManagerConfig { discovery_interval: 1, ..AwdlManagerConfig::default() };
+        let mut manager = AwdlManager::new(config).with_clock(clock.clone());
+        let (first, second) = ([0x02, 0, 0, 0, 0, 1], [0x02, 0, 0, 0, 0, 2]);
+        let tick = Duration::from_millis(1200);
+
+        // Restarting replaces the task instead of adding a second one
+        manager.start_peer_discovery().await;
+        manager.start_peer_discovery().await;
+        manager.sightings.write().await.saw(first, clock.now());
+        clock.advance(Duration::from_secs(manager.config.peer_timeout + 1));
+        tokio::time::sleep(tick).await;
+        assert_eq!(manager.sightings.read().await.last_seen(&first), None);
+
+        manager.stop().await.unwrap();
+        assert!(manager.discovery_task.is_none());
+        let seen = clock.now();
+        manager.sightings.write().await.saw(second, seen);
+        clock.advance(Duration::from_secs(manager.config.peer_timeout + 1));
+        tokio::time::sleep(tick).await;
+        assert_eq!(manager.sightings.read().await.last_seen(&second), Some(seen));
+    }
+
     #[tokio::test]
     async fn test_awdl_manager_creation() {
         let config = AwdlManagerConfig::default();
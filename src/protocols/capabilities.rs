@@ -0,0 +1,113 @@
+//! Capabilities exchanged in the fallback transfer handshake.
+//!
+//! The sender lists what it supports in its handshake and the receiver
+//! answers with its own list when it accepts the transfer. Both sides then
+//! pick, for each feature, the first option in the sender's order that the
+//! receiver supports too, so they agree without a further round trip.
+//!
+//! A peer that sends no list, like an Apple device or an older AirWin, gets
+//! the baseline: no compression, no checksum and no resume. Names are plain
+//! strings so that algorithms added by newer versions are ignored instead of
+//! failing the handshake.
+
+use serde::{Deserialize, Serialize};
+
+use super::compression::CompressionMode;
+
+/// What one side of a transfer supports
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Compression algorithms, preferred first
+    pub compression: Vec<String>,
+    /// Checksum algorithms, preferred first
+    pub checksums: Vec<String>,
+    /// Whether an interrupted transfer can continue where it stopped
+    pub resume: bool,
+}
+
+impl Capabilities {
+    /// What this build supports with `compression`. Checksums and resume
+    /// aren't implemented yet: the list is announced so that they are
+    /// negotiated with peers running this version once they are.
+    pub fn local(compression: CompressionMode) -> Self {
+        Self {
+            compression: compression.algorithms(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Behavior both sides agreed on for a transfer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Negotiated {
+    pub compression: Option<String>,
+    pub checksum: Option<String>,
+    pub resume: bool,
+}
+
+/// Agree on the behavior of a transfer. Without the capabilities of both
+/// sides the result is the baseline.
+pub fn negotiate(sender: Option<&Capabilities>, receiver: Option<&Capabilities>) -> Negotiated {
+    let (Some(sender), Some(receiver)) = (sender, receiver) else {
+        return Negotiated::default();
+    };
+    let first_shared = |preferred: &[String], supported: &[String]| {
+        preferred.iter().find(|name| supported.contains(name)).cloned()
+    };
+    Negotiated {
+        compression: first_shared(&sender.compression, &receiver.compression),
+        checksum: first_shared(&sender.checksums, &receiver.checksums),
+        resume: sender.resume && receiver.resume,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_negotiation_with_a_capability_aware_peer() {
+        let sender = Capabilities {
+            compression: names(&["zstd", "deflate"]),
+            checksums: names(&["sha256", "crc32"]),
+            resume: true,
+        };
+        // Unknown fields from a newer peer are ignored
+        let receiver: Capabilities = serde_json::from_str(
+            r#"{"compression":["deflate","zstd"],"checksums":["blake3"],"resume":true,"encryption":["aes"]}"#,
+        )
+        .unwrap();
+
+        let negotiated = negotiate(Some(&sender), Some(&receiver));
+        // The sender's preference wins among the shared options
+        assert_eq!(negotiated.compression.as_deref(), Some("zstd"));
+        assert_eq!(negotiated.checksum, None);
+        assert!(negotiated.resume);
+
+        let no_resume = Capabilities { resume: false, ..receiver };
+        assert!(!negotiate(Some(&sender), Some(&no_resume)).resume);
+    }
+
+    #[test]
+    fn test_legacy_peer_gets_the_baseline() {
+        let aware = Capabilities {
+            compression: names(&["deflate"]),
+            checksums: names(&["sha256"]),
+            resume: true,
+        };
+        assert_eq!(negotiate(Some(&aware), None), Negotiated::default());
+        assert_eq!(negotiate(None, Some(&aware)), Negotiated::default());
+
+        // A handshake field that is empty or partial falls back field by field
+        let partial: Capabilities = serde_json::from_str(r#"{"checksums":["sha256"]}"#).unwrap();
+        let negotiated = negotiate(Some(&aware), Some(&partial));
+        assert_eq!(negotiated.compression, None);
+        assert_eq!(negotiated.checksum.as_deref(), Some("sha256"));
+        assert!(!negotiated.resume);
+    }
+}
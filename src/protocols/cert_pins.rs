@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+const PINS_FILE: &str = "peer_pins.json";
+
+/// Result of comparing a peer certificate with its pin
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PinCheck {
+    /// No pin yet for this peer
+    Unknown,
+    Match,
+    /// The peer presented a different certificate than the pinned one
+    Mismatch { pinned: String },
+}
+
+/// Certificate fingerprints of peers seen in earlier successful transfers,
+/// used to refuse a known peer that suddenly presents a different certificate
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PinStore {
+    #[serde(skip)]
+    path: PathBuf,
+    pins: HashMap<String, String>,
+}
+
+impl PinStore {
+    /// Default location of the pins, in the AirWin config directory
+    pub fn default_path() -> PathBuf {
+        crate::utils::config::config_dir().join(PINS_FILE)
+    }
+
+    /// Load the pins from `path`, starting empty if the file is missing or invalid
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let pins = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str::<PinStore>(&contents)
+                .map(|store| store.pins)
+                .unwrap_or_else(|e| {
+                    warn!("Invalid certificate pins file {:?}, starting empty: {}", path, e);
+                    HashMap::new()
+                }),
+            Err(_) => HashMap::new(),
+        };
+        Self { path, pins }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory {:?}", dir))?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write certificate pins to {:?}", self.path))
+    }
+
+    pub fn check(&self, peer: &str, fingerprint: &str) -> PinCheck {
+        match self.pins.get(peer) {
+            None => PinCheck::Unknown,
+            Some(pinned) if pinned == fingerprint => PinCheck::Match,
+            Some(pinned) => PinCheck::Mismatch { pinned: pinned.clone() },
+        }
+    }
+
+    /// Pin `fingerprint` for `peer` unless it already has a pin
+    pub fn pin_if_unknown(&mut self, peer: &str, fingerprint: &str) -> bool {
+        if self.pins.contains_key(peer) {
+            return false;
+        }
+        self.pins.insert(peer.to_string(), fingerprint.to_string());
+        true
+    }
+
+    /// Forget the pin of a peer whose certificate changed legitimately
+    pub fn forget(&mut self, peer: &str) {
+        self.pins.remove(peer);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// SHA-256 fingerprint of a DER certificate, as colon-separated hex
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_store() -> PinStore {
+        PinStore::load(std::env::temp_dir().join(format!("airwin_pins_{}.json", Uuid::new_v4().simple())))
+    }
+
+    #[test]
+    fn test_pin_match() {
+        let mut store = temp_store();
+        let print = fingerprint(b"certificate");
+
+        assert_eq!(store.check("192.168.1.20", &print), PinCheck::Unknown);
+        assert!(store.pin_if_unknown("192.168.1.20", &print));
+        assert_eq!(store.check("192.168.1.20", &print), PinCheck::Match);
+    }
+
+    #[test]
+    fn test_pin_mismatch() {
+        let mut store = temp_store();
+        let original = fingerprint(b"certificate");
+        let other = fingerprint(b"another certificate");
+        store.pin_if_unknown("192.168.1.20", &original);
+
+        assert_eq!(
+            store.check("192.168.1.20", &other),
+            PinCheck::Mismatch { pinned: original.clone() }
+        );
+        // A changed certificate never replaces the pin silently
+        assert!(!store.pin_if_unknown("192.168.1.20", &other));
+        assert_eq!(store.check("192.168.1.20", &original), PinCheck::Match);
+
+        store.forget("192.168.1.20");
+        assert_eq!(store.check("192.168.1.20", &other), PinCheck::Unknown);
+    }
+
+    #[test]
+    fn test_pins_survive_reload() {
+        let mut store = temp_store();
+        let print = fingerprint(b"certificate");
+        store.pin_if_unknown("mac-mini", &print);
+        store.save().unwrap();
+
+        let reloaded = PinStore::load(store.path());
+        assert_eq!(reloaded.check("mac-mini", &print), PinCheck::Match);
+        std::fs::remove_file(store.path()).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Compression of the file stream between AirWin instances.
+//!
+//! Compression is offered in the capabilities of the fallback handshake, so
+//! it is only used when both sides are AirWin and agree on an algorithm;
+//! Apple devices send no capabilities and get the file bytes as before.
+//!
+//! Once an algorithm is negotiated, every file of the transfer is sent as
+//! frames: a kind byte, the length of the data once decoded and the length
+//! of the data that follows, both big-endian `u32`. Each chunk read from the
+//! file is compressed on its own, so the receiver never buffers more than a
+//! chunk. A chunk that doesn't get smaller, or a file that is already
+//! compressed, is sent stored. The receiver stops at the size declared in
+//! the handshake, as without compression.
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Name of zstd in the capabilities
+pub const ZSTD: &str = "zstd";
+/// Name of gzip in the capabilities
+pub const GZIP: &str = "gzip";
+
+/// zstd level: fast enough for a gigabit link on one core
+const ZSTD_LEVEL: i32 = 3;
+
+/// Largest data a frame may announce, decoded or not. Chunks are at most
+/// [`super::airdrop::MAX_CHUNK_SIZE`]; the margin covers the rare chunk a
+/// compressor makes larger, which is sent stored anyway.
+const MAX_FRAME_LEN: usize = 2 * super::airdrop::MAX_CHUNK_SIZE;
+
+const STORED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// Whether files sent to other AirWin instances are compressed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionMode {
+    /// Never compress
+    #[default]
+    Off,
+    /// zstd, or gzip with a peer without zstd
+    Auto,
+    /// zstd only: peers without it get the file uncompressed
+    Zstd,
+}
+
+impl CompressionMode {
+    /// Algorithms announced in the handshake, preferred first
+    pub fn algorithms(self) -> Vec<String> {
+        let names: &[&str] = match self {
+            CompressionMode::Off => &[],
+            CompressionMode::Auto => &[ZSTD, GZIP],
+            CompressionMode::Zstd => &[ZSTD],
+        };
+        names.iter().map(|name| name.to_string()).collect()
+    }
+}
+
+/// A negotiated compression algorithm
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Zstd,
+    Gzip,
+}
+
+impl Algorithm {
+    /// The algorithm called `name` in the capabilities, if this build has it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            ZSTD => Some(Algorithm::Zstd),
+            GZIP => Some(Algorithm::Gzip),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Algorithm::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL),
+            Algorithm::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// `data` decoded, failing unless it is exactly `len` bytes
+    fn decompress(self, data: &[u8], len: usize) -> std::io::Result<Vec<u8>> {
+        let decoded = match self {
+            Algorithm::Zstd => zstd::bulk::decompress(data, len)?,
+            Algorithm::Gzip => {
+                let mut decoded = Vec::with_capacity(len);
+                // One byte more than announced is enough to tell a lying frame
+                flate2::read::GzDecoder::new(data)
+                    .take(len as u64 + 1)
+                    .read_to_end(&mut decoded)?;
+                decoded
+            }
+        };
+        if decoded.len() != len {
+            return Err(invalid(format!("frame decoded to {} bytes, expected {}", decoded.len(), len)));
+        }
+        Ok(decoded)
+    }
+}
+
+/// Write `chunk` as one frame, compressed with `algorithm` if that makes it
+/// smaller. Returns the bytes written.
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    algorithm: Option<Algorithm>,
+    chunk: &[u8],
+) -> std::io::Result<usize> {
+    let compressed = match algorithm {
+        Some(algorithm) => Some(algorithm.compress(chunk)?).filter(|compressed| compressed.len() < chunk.len()),
+        None => None,
+    };
+    let (kind, data) = match &compressed {
+        Some(compressed) => (COMPRESSED, compressed.as_slice()),
+        None => (STORED, chunk),
+    };
+    let mut header = [0u8; 9];
+    header[0] = kind;
+    header[1..5].copy_from_slice(&frame_len(chunk.len())?.to_be_bytes());
+    header[5..9].copy_from_slice(&frame_len(data.len())?.to_be_bytes());
+    writer.write_all(&header).await?;
+    writer.write_all(data).await?;
+    Ok(header.len() + data.len())
+}
+
+/// Read one frame and return its decoded data, at most `max_len` bytes
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    algorithm: Algorithm,
+    max_len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut header = [0u8; 9];
+    reader.read_exact(&mut header).await?;
+    let decoded_len = u32::from_be_bytes(header[1..5].try_into().unwrap()) as usize;
+    let data_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as usize;
+    if decoded_len > max_len.min(MAX_FRAME_LEN) || data_len > MAX_FRAME_LEN {
+        return Err(invalid(format!("frame of {} bytes ({} on the wire) is too large", decoded_len, data_len)));
+    }
+
+    let mut data = vec![0u8; data_len];
+    reader.read_exact(&mut data).await?;
+    match header[0] {
+        STORED if data_len == decoded_len => Ok(data),
+        STORED => Err(invalid(format!("stored frame of {} bytes announced as {}", data_len, decoded_len))),
+        COMPRESSED => algorithm.decompress(&data, decoded_len),
+        kind => Err(invalid(format!("unknown frame kind {}", kind))),
+    }
+}
+
+fn frame_len(len: usize) -> std::io::Result<u32> {
+    u32::try_from(len)
+        .ok()
+        .filter(|len| *len as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| invalid(format!("chunk of {} bytes is too large for a frame", len)))
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_round_trip_and_shrink_compressible_chunks() {
+        let text = b"AirWin ".repeat(10_000);
+        let noise: Vec<u8> = (0..10_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+
+        for algorithm in [Algorithm::Zstd, Algorithm::Gzip] {
+            let mut wire = Vec::new();
+            let compressed = write_frame(&mut wire, Some(algorithm), &text).await.unwrap();
+            assert!(compressed < text.len() / 10, "{:?}: {} bytes", algorithm, compressed);
+            write_frame(&mut wire, Some(algorithm), &noise).await.unwrap();
+            write_frame(&mut wire, None, b"raw").await.unwrap();
+
+            let mut reader = wire.as_slice();
+            assert_eq!(read_frame(&mut reader, algorithm, text.len()).await.unwrap(), text);
+            assert_eq!(read_frame(&mut reader, algorithm, noise.len()).await.unwrap(), noise);
+            assert_eq!(read_frame(&mut reader, algorithm, 3).await.unwrap(), b"raw");
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frames_larger_than_announced_are_rejected() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, Some(Algorithm::Zstd), &[0u8; 1000]).await.unwrap();
+        let error = read_frame(&mut wire.as_slice(), Algorithm::Zstd, 999).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // A compressed frame claiming fewer bytes than it decodes to
+        wire[1..5].copy_from_slice(&10u32.to_be_bytes());
+        assert!(read_frame(&mut wire.as_slice(), Algorithm::Zstd, 1000).await.is_err());
+    }
+
+    #[test]
+    fn test_modes_announce_their_algorithms() {
+        assert!(CompressionMode::Off.algorithms().is_empty());
+        assert_eq!(CompressionMode::Auto.algorithms(), [ZSTD, GZIP]);
+        assert_eq!(CompressionMode::Zstd.algorithms(), [ZSTD]);
+        assert_eq!(Algorithm::from_name("brotli"), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Reuse of outgoing AirDrop connections.
+//!
+//! After a transfer the TLS stream to the peer is kept for a short idle
+//! window, so the next file to the same address skips the TCP and TLS
+//! handshakes. The receiver waits for another handshake on the same
+//! connection for its read timeout, so the idle window must stay shorter.
+//!
+//! A stream is dropped when the window ends, when a transfer on it fails or
+//! when the peer closed it in the meantime. The sender still reconnects once
+//! if a reused stream fails before the file is accepted, which covers a peer
+//! closing it right after the check.
+//!
+//! The pool is only used with [`AfterTransfer::StayConnected`]. By default
+//! the connection is closed after every send.
+
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use super::tls::TlsStream;
+
+/// How long an idle connection is kept
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// What happens to the connection to a peer once a send is complete
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AfterTransfer {
+    /// Close it and go back to idle
+    #[default]
+    Disconnect,
+    /// Keep it for the idle window and reuse it for the next send to the
+    /// same peer, staying connected meanwhile
+    StayConnected,
+}
+
+/// An established connection and the certificate the peer presented
+pub struct Connection {
+    pub stream: Box<dyn TlsStream>,
+    pub peer_certificate: Option<Vec<u8>>,
+}
+
+struct IdleConnection {
+    connection: Connection,
+    /// Tells the expiry task whether the entry is still the one it was started for
+    id: u64,
+}
+
+/// Idle connections keyed by peer address
+pub struct ConnectionPool {
+    idle_timeout: Duration,
+    idle: Arc<Mutex<HashMap<SocketAddr, IdleConnection>>>,
+    next_id: AtomicU64,
+    opened: AtomicUsize,
+}
+
+impl ConnectionPool {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            idle: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(0),
+            opened: AtomicUsize::new(0),
+        }
+    }
+
+    /// Idle connection to `addr`, if one is still open
+    pub async fn take(&self, addr: SocketAddr) -> Option<Connection> {
+        let mut connection = self.idle.lock().await.remove(&addr)?.connection;
+        if is_closed(connection.stream.as_mut()) {
+            debug!("Pooled connection to {} was closed by the peer", addr);
+            return None;
+        }
+        debug!("Reusing connection to {}", addr);
+        Some(connection)
+    }
+
+    /// Keep `connection` to `addr` for the idle window, replacing any other.
+    /// The returned task ends with `true` if the window closed the connection,
+    /// `false` if it was taken or replaced before.
+    pub async fn put(&self, addr: SocketAddr, connection: Connection) -> JoinHandle<bool> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.idle.lock().await.insert(addr, IdleConnection { connection, id });
+
+        let idle = self.idle.clone();
+        let timeout = self.idle_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            let mut idle = idle.lock().await;
+            if !idle.get(&addr).is_some_and(|entry| entry.id == id) {
+                return false;
+            }
+            let mut expired = idle.remove(&addr).unwrap().connection;
+            drop(idle);
+            debug!("Closing idle connection to {}", addr);
+            let _ = expired.stream.shutdown().await;
+            true
+        })
+    }
+
+    /// Whether no connection is kept
+    pub async fn is_empty(&self) -> bool {
+        self.idle.lock().await.is_empty()
+    }
+
+    /// Count a newly opened connection
+    pub fn record_opened(&self) {
+        self.opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Connections opened since the pool was created
+    pub fn opened(&self) -> usize {
+        self.opened.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether the peer closed `stream` or sent something unexpected. A live
+/// idle connection has nothing to read, so the read doesn't complete.
+fn is_closed(stream: &mut dyn TlsStream) -> bool {
+    let mut byte = [0u8; 1];
+    match stream.read(&mut byte).now_or_never() {
+        None => false,
+        Some(Ok(0)) | Some(Err(_)) => true,
+        Some(Ok(_)) => {
+            debug!("Discarding pooled connection with unexpected data");
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(stream: tokio::io::DuplexStream) -> Connection {
+        Connection {
+            stream: Box::new(stream),
+            peer_certificate: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closed_and_expired_connections_are_not_reused() {
+        let addr: SocketAddr = "127.0.0.1:8771".parse().unwrap();
+        let pool = ConnectionPool::new(Duration::from_millis(50));
+
+        let (client, _server) = tokio::io::duplex(64);
+        pool.put(addr, connection(client)).await;
+        assert!(pool.take(addr).await.is_some());
+        assert!(pool.take(addr).await.is_none());
+
+        let (client, server) = tokio::io::duplex(64);
+        pool.put(addr, connection(client)).await;
+        drop(server);
+        assert!(pool.take(addr).await.is_none());
+
+        let (client, _server) = tokio::io::duplex(64);
+        let expiry = pool.put(addr, connection(client)).await;
+        assert!(expiry.await.unwrap());
+        assert!(pool.is_empty().await);
+        assert!(pool.take(addr).await.is_none());
+
+        // A connection taken before the window ends isn't reported as expired
+        let (client, _server) = tokio::io::duplex(64);
+        let expiry = pool.put(addr, connection(client)).await;
+        assert!(pool.take(addr).await.is_some());
+        assert!(!expiry.await.unwrap());
+    }
+}
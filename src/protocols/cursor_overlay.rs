@@ -0,0 +1,222 @@
+//! Mouse cursor drawn onto captured frames.
+//!
+//! `BitBlt` copies the screen without the pointer, so a mirrored screen
+//! shows none. When enabled, the cursor is blended onto every captured frame
+//! at its position. Its shape comes from a [`CursorSource`]: on Windows,
+//! [`SystemCursor`] draws the current cursor with `DrawIconEx` once on black
+//! and once on white, and the difference between the two gives its
+//! transparency. A hidden cursor, as during a full-screen video, draws
+//! nothing.
+
+use windows::Win32::Foundation::POINT;
+use windows::Win32::Graphics::Gdi::{
+    CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits, PatBlt, ReleaseDC,
+    SelectObject, BLACKNESS, DIB_RGB_COLORS, HBRUSH, HDC, ROP_CODE, WHITENESS,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    DrawIconEx, GetCursorInfo, GetIconInfo, GetSystemMetrics, CURSORINFO, CURSOR_SHOWING, DI_NORMAL, HICON,
+    ICONINFO, SM_CXCURSOR, SM_CYCURSOR,
+};
+
+use super::screen_capture::{self, CaptureRegion, BYTES_PER_PIXEL};
+
+/// The cursor as it appears on screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CursorImage {
+    /// Top-left corner on the screen, the position minus the hotspot
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// BGRA pixels, not premultiplied, rows from the top
+    pub pixels: Vec<u8>,
+}
+
+impl CursorImage {
+    /// Cursor drawn at (`x`, `y`) on a black and on a white background.
+    ///
+    /// A pixel that doesn't change between the two is opaque; one that
+    /// follows the background is transparent. Cursors that invert the
+    /// screen, like the text I-beam, come out white.
+    pub fn from_backgrounds(x: i32, y: i32, width: u32, height: u32, on_black: &[u8], on_white: &[u8]) -> Self {
+        let pixels = on_black
+            .chunks_exact(BYTES_PER_PIXEL)
+            .zip(on_white.chunks_exact(BYTES_PER_PIXEL))
+            .flat_map(|(black, white)| {
+                let difference: u32 = (0..3).map(|channel| u32::from(white[channel].saturating_sub(black[channel]))).sum();
+                let alpha = 255 - (difference + 1) / 3;
+                let color = |channel: usize| {
+                    if alpha == 0 {
+                        0
+                    } else {
+                        (u32::from(black[channel]) * 255 / alpha).min(255) as u8
+                    }
+                };
+                [color(0), color(1), color(2), alpha as u8]
+            })
+            .collect();
+        Self { x, y, width, height, pixels }
+    }
+}
+
+/// Where the cursor drawn on frames comes from
+pub trait CursorSource: Send + Sync {
+    /// The current cursor, `None` if it is hidden or can't be read
+    fn cursor(&self) -> Option<CursorImage>;
+}
+
+/// Blend `cursor` onto `buffer`, the BGRA pixels of `region`. The parts of
+/// the cursor outside the region are left out.
+pub fn composite(buffer: &mut [u8], region: CaptureRegion, cursor: &CursorImage) {
+    let Ok(frame_width) = usize::try_from(region.width) else {
+        return;
+    };
+    for row in 0..cursor.height as i64 {
+        let y = i64::from(cursor.y) + row - i64::from(region.y);
+        if y < 0 || y >= i64::from(region.height) {
+            continue;
+        }
+        for column in 0..cursor.width as i64 {
+            let x = i64::from(cursor.x) + column - i64::from(region.x);
+            if x < 0 || x >= i64::from(region.width) {
+                continue;
+            }
+            let source = (row as usize * cursor.width as usize + column as usize) * BYTES_PER_PIXEL;
+            let target = (y as usize * frame_width + x as usize) * BYTES_PER_PIXEL;
+            let (Some(source), Some(target)) = (
+                cursor.pixels.get(source..source + BYTES_PER_PIXEL),
+                buffer.get_mut(target..target + BYTES_PER_PIXEL),
+            ) else {
+                continue;
+            };
+            let alpha = u32::from(source[3]);
+            for channel in 0..3 {
+                let blended = u32::from(source[channel]) * alpha + u32::from(target[channel]) * (255 - alpha);
+                target[channel] = ((blended + 127) / 255) as u8;
+            }
+        }
+    }
+}
+
+/// The Windows cursor, read with `GetCursorInfo`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemCursor;
+
+impl CursorSource for SystemCursor {
+    fn cursor(&self) -> Option<CursorImage> {
+        // SAFETY: system_cursor releases every GDI handle it creates
+        unsafe { system_cursor() }
+    }
+}
+
+/// # Safety
+///
+/// Calls GDI. Every handle created here is released before returning.
+unsafe fn system_cursor() -> Option<CursorImage> {
+    let mut info = CURSORINFO {
+        cbSize: std::mem::size_of::<CURSORINFO>() as u32,
+        ..Default::default()
+    };
+    if !GetCursorInfo(&mut info).as_bool() || info.flags.0 & CURSOR_SHOWING.0 == 0 || info.hCursor.is_invalid() {
+        return None;
+    }
+    let icon = HICON(info.hCursor.0);
+
+    // GetIconInfo hands out copies of the cursor bitmaps, only the hotspot is needed
+    let mut icon_info = ICONINFO::default();
+    if !GetIconInfo(icon, &mut icon_info).as_bool() {
+        return None;
+    }
+    for bitmap in [icon_info.hbmColor, icon_info.hbmMask] {
+        if !bitmap.is_invalid() {
+            DeleteObject(bitmap);
+        }
+    }
+
+    let width = GetSystemMetrics(SM_CXCURSOR);
+    let height = GetSystemMetrics(SM_CYCURSOR);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let screen_dc = GetDC(None);
+    if screen_dc.is_invalid() {
+        return None;
+    }
+    let on_black = render_cursor(screen_dc, icon, width, height, BLACKNESS);
+    let on_white = render_cursor(screen_dc, icon, width, height, WHITENESS);
+    ReleaseDC(None, screen_dc);
+
+    let POINT { x, y } = info.ptScreenPos;
+    Some(CursorImage::from_backgrounds(
+        x - icon_info.xHotspot as i32,
+        y - icon_info.yHotspot as i32,
+        width as u32,
+        height as u32,
+        &on_black?,
+        &on_white?,
+    ))
+}
+
+/// `icon` drawn on a `background` filled bitmap, as BGRA pixels
+///
+/// # Safety
+///
+/// `screen_dc` must be a valid screen DC.
+unsafe fn render_cursor(screen_dc: HDC, icon: HICON, width: i32, height: i32, background: ROP_CODE) -> Option<Vec<u8>> {
+    let memory_dc = CreateCompatibleDC(screen_dc);
+    let bitmap = CreateCompatibleBitmap(screen_dc, width, height);
+    let previous = SelectObject(memory_dc, bitmap);
+    let drawn = PatBlt(memory_dc, 0, 0, width, height, background).as_bool()
+        && DrawIconEx(memory_dc, 0, 0, icon, width, height, 0, HBRUSH::default(), DI_NORMAL).as_bool();
+    // GetDIBits needs the bitmap out of the DC
+    SelectObject(memory_dc, previous);
+
+    let mut pixels = vec![0u8; width as usize * height as usize * BYTES_PER_PIXEL];
+    let mut info = screen_capture::bitmap_info(width, height);
+    let rows = drawn.then(|| {
+        GetDIBits(
+            memory_dc,
+            bitmap,
+            0,
+            height as u32,
+            Some(pixels.as_mut_ptr().cast()),
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    });
+    DeleteObject(bitmap);
+    DeleteDC(memory_dc);
+
+    (rows == Some(height)).then_some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_transparency_from_backgrounds() {
+        // An opaque red pixel, a transparent one and a half-transparent black one
+        let on_black = [0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let on_white = [0, 0, 255, 0, 255, 255, 255, 0, 128, 128, 128, 0];
+        let cursor = CursorImage::from_backgrounds(5, 6, 3, 1, &on_black, &on_white);
+        assert_eq!(cursor.pixels, [0, 0, 255, 255, 0, 0, 0, 0, 0, 0, 0, 127]);
+    }
+
+    #[test]
+    fn test_cursor_outside_the_region_is_clipped() {
+        let region = CaptureRegion { x: 100, y: 100, width: 2, height: 2 };
+        let mut buffer = vec![0u8; 2 * 2 * BYTES_PER_PIXEL];
+        let cursor = CursorImage {
+            x: 99,
+            y: 99,
+            width: 2,
+            height: 2,
+            pixels: [255, 255, 255, 255].repeat(4),
+        };
+        composite(&mut buffer, region, &cursor);
+        // Only the cursor's bottom-right pixel falls inside, on the region's first pixel
+        assert_eq!(&buffer[..4], [255, 255, 255, 0]);
+        assert!(buffer[4..].iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+use tracing::{info, warn};
+
+/// How long an "Ask" prompt waits for the user before keeping both files
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What to do when an incoming file has the same name as an existing one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileConflictPolicy {
+    /// Save the new file with a numeric suffix
+    #[default]
+    KeepBoth,
+    /// Replace the existing file
+    Overwrite,
+    /// Let the user choose for each conflict
+    Ask,
+}
+
+/// Answer to a conflict prompt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictChoice {
+    KeepBoth,
+    Overwrite,
+}
+
+/// Conflict waiting for the user's choice
+#[derive(Clone, Debug)]
+pub struct ConflictPrompt {
+    pub name: String,
+    pub existing: PathBuf,
+    responder: Arc<Mutex<Option<oneshot::Sender<ConflictChoice>>>>,
+}
+
+impl ConflictPrompt {
+    /// Send the user's choice back to the receiving connection
+    pub fn respond(&self, choice: ConflictChoice) {
+        if let Some(responder) = self.responder.lock().unwrap().take() {
+            let _ = responder.send(choice);
+        }
+    }
+
+    /// Whether the receiving connection still waits for the answer. It stops
+    /// waiting after [`PROMPT_TIMEOUT`] or when the transfer is dropped.
+    pub fn is_pending(&self) -> bool {
+        self.responder
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|responder| !responder.is_closed())
+    }
+}
+
+/// Where an incoming file is saved
+#[derive(Debug)]
+pub struct Destination {
+    pub path: PathBuf,
+    /// The name was free and is held by an empty file until the transfer
+    /// replaces it
+    reserved: bool,
+}
+
+impl Destination {
+    /// Give the name back after a failed transfer. A file that was already
+    /// there, and is being overwritten, is left alone.
+    pub async fn release(&self) {
+        if self.reserved {
+            if let Err(e) = tokio::fs::remove_file(&self.path).await {
+                warn!("Failed to release {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+/// Picks the path incoming files are saved to, following the conflict policy
+#[derive(Clone, Debug)]
+pub struct ConflictResolver {
+    policy: Arc<Mutex<FileConflictPolicy>>,
+    prompts: broadcast::Sender<ConflictPrompt>,
+}
+
+impl ConflictResolver {
+    pub fn new(policy: FileConflictPolicy) -> Self {
+        let (prompts, _) = broadcast::channel(8);
+        Self {
+            policy: Arc::new(Mutex::new(policy)),
+            prompts,
+        }
+    }
+
+    pub fn set_policy(&self, policy: FileConflictPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Sender side of the prompts, used by the UI to subscribe
+    pub fn prompts(&self) -> broadcast::Sender<ConflictPrompt> {
+        self.prompts.clone()
+    }
+
+    /// Path to save `name` to inside `dir`. A new name is claimed right away
+    /// by creating the file, so two transfers never get the same one.
+    pub async fn resolve(&self, dir: &Path, name: &str) -> std::io::Result<Destination> {
+        let path = dir.join(name);
+        if reserve(&path).await? {
+            return Ok(Destination { path, reserved: true });
+        }
+
+        let policy = *self.policy.lock().unwrap();
+        let choice = match policy {
+            FileConflictPolicy::KeepBoth => ConflictChoice::KeepBoth,
+            FileConflictPolicy::Overwrite => ConflictChoice::Overwrite,
+            FileConflictPolicy::Ask => self.ask(name, &path).await,
+        };
+
+        match choice {
+            ConflictChoice::Overwrite => {
+                info!("Overwriting existing file {:?}", path);
+                Ok(Destination { path, reserved: false })
+            }
+            ConflictChoice::KeepBoth => Ok(Destination {
+                path: unique_path(dir, name).await?,
+                reserved: true,
+            }),
+        }
+    }
+
+    async fn ask(&self, name: &str, existing: &Path) -> ConflictChoice {
+        let (responder, answer) = oneshot::channel();
+        let prompt = ConflictPrompt {
+            name: name.to_string(),
+            existing: existing.to_path_buf(),
+            responder: Arc::new(Mutex::new(Some(responder))),
+        };
+
+        if self.prompts.send(prompt).is_err() {
+            warn!("No UI to ask about {}, keeping both files", name);
+            return ConflictChoice::KeepBoth;
+        }
+
+        match tokio::time::timeout(PROMPT_TIMEOUT, answer).await {
+            Ok(Ok(choice)) => choice,
+            _ => {
+                warn!("No answer about {}, keeping both files", name);
+                ConflictChoice::KeepBoth
+            }
+        }
+    }
+}
+
+/// Claim `path` by creating it empty. `false` if it already exists.
+async fn reserve(path: &Path) -> std::io::Result<bool> {
+    match tokio::fs::OpenOptions::new().write(true).create_new(true).open(path).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// First free "name (n).ext" path in `dir`, claimed with an empty file
+pub async fn unique_path(dir: &Path, name: &str) -> std::io::Result<PathBuf> {
+    let original = Path::new(name);
+    let stem = original
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+    let extension = original.extension().map(|e| e.to_string_lossy().to_string());
+
+    for n in 1.. {
+        let candidate = match &extension {
+            Some(ext) => format!("{} ({}).{}", stem, n, ext),
+            None => format!("{} ({})", stem, n),
+        };
+        let path = dir.join(candidate);
+        if reserve(&path).await? {
+            return Ok(path);
+        }
+    }
+    unreachable!("unbounded range always yields a free name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn dir_with_existing(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("airwin_test_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(name), b"old").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_keep_both_adds_numeric_suffix() {
+        let dir = dir_with_existing("photo.jpg");
+        let resolver = ConflictResolver::new(FileConflictPolicy::KeepBoth);
+
+        let path = resolver.resolve(&dir, "photo.jpg").await.unwrap().path;
+        assert_eq!(path, dir.join("photo (1).jpg"));
+
+        std::fs::write(&path, b"new").unwrap();
+        assert_eq!(resolver.resolve(&dir, "photo.jpg").await.unwrap().path, dir.join("photo (2).jpg"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_reuses_existing_path() {
+        let dir = dir_with_existing("notes.txt");
+        let resolver = ConflictResolver::new(FileConflictPolicy::Overwrite);
+
+        assert_eq!(resolver.resolve(&dir, "notes.txt").await.unwrap().path, dir.join("notes.txt"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ask_uses_the_user_choice() {
+        let dir = dir_with_existing("report.pdf");
+        let resolver = ConflictResolver::new(FileConflictPolicy::Ask);
+        let mut prompts = resolver.prompts().subscribe();
+
+        let ui = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert_eq!(prompt.name, "report.pdf");
+            prompt.respond(ConflictChoice::Overwrite);
+        });
+
+        assert_eq!(resolver.resolve(&dir, "report.pdf").await.unwrap().path, dir.join("report.pdf"));
+        ui.await.unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ask_without_ui_keeps_both() {
+        let dir = dir_with_existing("song.mp3");
+        let resolver = ConflictResolver::new(FileConflictPolicy::Ask);
+
+        assert_eq!(resolver.resolve(&dir, "song.mp3").await.unwrap().path, dir.join("song (1).mp3"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_transfers_get_different_names() {
+        let dir = dir_with_existing("photo.jpg");
+        let resolver = ConflictResolver::new(FileConflictPolicy::KeepBoth);
+
+        // Nothing is written until the transfers end, yet no name is handed out twice
+        let (first, second) = tokio::join!(resolver.resolve(&dir, "photo.jpg"), resolver.resolve(&dir, "photo.jpg"));
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_ne!(first.path, second.path);
+
+        // A failed transfer gives its name back
+        second.release().await;
+        assert!(!second.path.exists());
+        assert_eq!(resolver.resolve(&dir, "photo.jpg").await.unwrap().path, second.path);
+
+        // Overwriting never deletes the existing file on failure
+        let overwrite = ConflictResolver::new(FileConflictPolicy::Overwrite);
+        overwrite.resolve(&dir, "photo.jpg").await.unwrap().release().await;
+        assert_eq!(std::fs::read(dir.join("photo.jpg")).unwrap(), b"old");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_prompt_stops_being_pending() {
+        let dir = dir_with_existing("clip.mov");
+        let resolver = ConflictResolver::new(FileConflictPolicy::Ask);
+        let mut prompts = resolver.prompts().subscribe();
+
+        let transfer = tokio::spawn({
+            let (resolver, dir) = (resolver.clone(), dir.clone());
+            async move { resolver.resolve(&dir, "clip.mov").await }
+        });
+        let prompt = prompts.recv().await.unwrap();
+        assert!(prompt.is_pending());
+
+        // The connection gave up, so the prompt can go
+        transfer.abort();
+        let _ = transfer.await;
+        assert!(!prompt.is_pending());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_conflict_keeps_name() {
+        let dir = dir_with_existing("a.txt");
+        let resolver = ConflictResolver::new(FileConflictPolicy::Ask);
+
+        assert_eq!(resolver.resolve(&dir, "b.txt").await.unwrap().path, dir.join("b.txt"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
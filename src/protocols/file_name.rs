@@ -0,0 +1,141 @@
+//! File names sent to and received from peers.
+//!
+//! The handshake carries names as JSON strings, so they must be valid UTF-8.
+//! Most names convert exactly. The rest, like Latin-1 bytes on Unix or
+//! unpaired surrogates on Windows, are transliterated the same way every
+//! time and logged, instead of all becoming "unknown". Received names are
+//! made safe to create on Windows.
+
+use std::path::Path;
+use tracing::warn;
+
+/// Used when a path has no file name at all
+const FALLBACK_NAME: &str = "unknown";
+
+/// Name of a file as declared to the peer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireName {
+    pub name: String,
+    /// The name wasn't valid Unicode and was converted
+    pub transliterated: bool,
+}
+
+/// Name to declare for the file at `path`
+pub fn wire_name(path: &Path) -> WireName {
+    let Some(name) = path.file_name() else {
+        return WireName { name: FALLBACK_NAME.to_string(), transliterated: true };
+    };
+    if let Some(name) = name.to_str() {
+        return WireName { name: name.to_string(), transliterated: false };
+    }
+
+    let name = transliterate(name);
+    warn!("File name {:?} is not valid Unicode, sending it as {:?}", path.file_name(), name);
+    WireName { name, transliterated: true }
+}
+
+/// Bytes that aren't UTF-8 are read as Latin-1, the usual encoding of old
+/// Unix file names, so "caf\xe9" becomes "café"
+#[cfg(unix)]
+fn transliterate(name: &std::ffi::OsStr) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().iter().map(|&byte| char::from(byte)).collect()
+}
+
+/// Unpaired surrogates become U+FFFD, the rest of the name is kept
+#[cfg(windows)]
+fn transliterate(name: &std::ffi::OsStr) -> String {
+    use std::os::windows::ffi::OsStrExt;
+    char::decode_utf16(name.encode_wide())
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn transliterate(name: &std::ffi::OsStr) -> String {
+    name.to_string_lossy().to_string()
+}
+
+/// Name a received file can be saved under on Windows.
+///
+/// Only the last path component is kept, characters Windows rejects become
+/// `_`, trailing dots and spaces are dropped and reserved device names such
+/// as `CON` get a `_` prefix. Any other Unicode, emoji included, is kept.
+pub fn safe_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"|?*"#.contains(c) { '_' } else { c })
+        .collect();
+    let name = name.trim_end_matches(['.', ' ']).trim_start();
+    if name.is_empty() {
+        return FALLBACK_NAME.to_string();
+    }
+
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if is_reserved(stem) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn is_reserved(stem: &str) -> bool {
+    let stem = stem.to_ascii_uppercase();
+    matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit()
+            && stem.as_bytes()[3] != b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_names_round_trip() {
+        for name in ["foto 🎉.jpg", "résumé.pdf", "文件.txt"] {
+            let wire = wire_name(&Path::new("dir").join(name));
+            assert_eq!(wire, WireName { name: name.to_string(), transliterated: false });
+            assert_eq!(safe_file_name(&wire.name), name);
+        }
+        assert!(wire_name(Path::new("/")).transliterated);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_latin1_name_is_transliterated() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/tmp/caf\xe9 \xabmenu\xbb.txt"));
+        assert_eq!(
+            wire_name(path),
+            WireName { name: "café «menu».txt".to_string(), transliterated: true }
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_unpaired_surrogate_is_replaced() {
+        use std::os::windows::ffi::OsStringExt;
+        let name: Vec<u16> = "caf".encode_utf16().chain([0xD800]).chain(".txt".encode_utf16()).collect();
+        let path = std::path::PathBuf::from(std::ffi::OsString::from_wide(&name));
+        assert_eq!(
+            wire_name(&path),
+            WireName { name: "caf\u{FFFD}.txt".to_string(), transliterated: true }
+        );
+    }
+
+    #[test]
+    fn test_received_names_are_safe_on_windows() {
+        assert_eq!(safe_file_name("../../Windows/win.ini"), "win.ini");
+        assert_eq!(safe_file_name(r"..\..\boot.ini"), "boot.ini");
+        assert_eq!(safe_file_name("what? <yes>.txt"), "what_ _yes_.txt");
+        assert_eq!(safe_file_name("notes. . "), "notes");
+        assert_eq!(safe_file_name("con.txt"), "_con.txt");
+        assert_eq!(safe_file_name("COM1"), "_COM1");
+        assert_eq!(safe_file_name("COM10.txt"), "COM10.txt");
+        assert_eq!(safe_file_name(".."), FALLBACK_NAME);
+        assert_eq!(safe_file_name(""), FALLBACK_NAME);
+    }
+}
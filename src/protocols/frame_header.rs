@@ -0,0 +1,145 @@
+//! Header written before each JPEG frame of the AirPlay stream.
+//!
+//! Version 0 headers are plain text, `"{w}x{h}@{ts}\n"`, optionally followed
+//! by `":{len}"` before the newline. Version 1 headers start with a version
+//! byte and always carry the payload length: `"\x01{w}x{h}@{ts}:{len}\n"`.
+//! A version 0 header starts with a digit, so the two can't be confused.
+
+/// Version byte of headers that carry the payload length
+pub const FRAME_HEADER_VERSION: u8 = 1;
+
+/// TXT record of an AirPlay receiver that reads these frames, with the
+/// highest header version it understands. Other receivers expect H.264.
+pub const FRAMING_TXT_KEY: &str = "airwin";
+
+/// Longest header accepted, so a peer that never sends a newline can't make
+/// the reader buffer forever
+pub const MAX_HEADER_LEN: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    /// Capture time in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Length of the JPEG that follows, unknown in old version 0 headers
+    pub payload_len: Option<usize>,
+}
+
+impl FrameHeader {
+    /// Header bytes, versioned when the payload length is known
+    pub fn encode(&self) -> Vec<u8> {
+        match self.payload_len {
+            Some(len) => {
+                let mut bytes = vec![FRAME_HEADER_VERSION];
+                bytes.extend(format!("{}x{}@{}:{}\n", self.width, self.height, self.timestamp, len).bytes());
+                bytes
+            }
+            None => format!("{}x{}@{}\n", self.width, self.height, self.timestamp).into_bytes(),
+        }
+    }
+
+    /// Parse the header at the start of `bytes`, returning it with the number
+    /// of bytes it takes. `None` if it's incomplete, malformed or of an
+    /// unknown version.
+    pub fn decode(bytes: &[u8]) -> Option<(Self, usize)> {
+        let (versioned, body) = match *bytes.first()? {
+            b'0'..=b'9' => (false, bytes),
+            FRAME_HEADER_VERSION => (true, &bytes[1..]),
+            _ => return None,
+        };
+        let end = body.iter().take(MAX_HEADER_LEN).position(|&byte| byte == b'\n')?;
+        let line = std::str::from_utf8(&body[..end]).ok()?;
+
+        let (size, rest) = line.split_once('@')?;
+        let (width, height) = size.split_once('x')?;
+        let (timestamp, payload_len) = match rest.split_once(':') {
+            Some((timestamp, len)) => (timestamp, Some(parse_number::<usize>(len)?)),
+            None if versioned => return None,
+            None => (rest, None),
+        };
+
+        let header = Self {
+            width: parse_number(width)?,
+            height: parse_number(height)?,
+            timestamp: parse_number(timestamp)?,
+            payload_len,
+        };
+        Some((header, bytes.len() - body.len() + end + 1))
+    }
+}
+
+/// Width, height and timestamp of the header at the start of `bytes`
+pub fn parse_frame_header(bytes: &[u8]) -> Option<(u32, u32, u64)> {
+    FrameHeader::decode(bytes).map(|(header, _)| (header.width, header.height, header.timestamp))
+}
+
+/// Decimal digits only, so signs and whitespace are rejected
+fn parse_number<T: std::str::FromStr>(digits: &str) -> Option<T> {
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(payload_len: Option<usize>) -> FrameHeader {
+        FrameHeader {
+            width: 1920,
+            height: 1080,
+            timestamp: 1_700_000_000,
+            payload_len,
+        }
+    }
+
+    #[test]
+    fn test_valid_headers_round_trip() {
+        for payload_len in [Some(48_213), None] {
+            let mut bytes = header(payload_len).encode();
+            let header_len = bytes.len();
+            bytes.extend_from_slice(b"\xFF\xD8jpeg");
+
+            assert_eq!(FrameHeader::decode(&bytes), Some((header(payload_len), header_len)));
+            assert_eq!(parse_frame_header(&bytes), Some((1920, 1080, 1_700_000_000)));
+        }
+        assert_eq!(header(Some(10)).encode()[0], FRAME_HEADER_VERSION);
+
+        // Unversioned headers with a length, as written by earlier builds
+        let (legacy, _) = FrameHeader::decode(b"800x600@42:1234\n").unwrap();
+        assert_eq!(legacy.payload_len, Some(1234));
+    }
+
+    #[test]
+    fn test_truncated_headers_are_incomplete() {
+        let bytes = header(Some(48_213)).encode();
+        for len in 0..bytes.len() {
+            assert_eq!(FrameHeader::decode(&bytes[..len]), None, "{} bytes", len);
+        }
+        assert_eq!(parse_frame_header(b"1920x1080@17"), None);
+    }
+
+    #[test]
+    fn test_malformed_headers_are_rejected() {
+        for bytes in [
+            &b"1920x@1700000000\n"[..],
+            b"1920x1080\n",
+            b"1920*1080@1700000000\n",
+            b"-1x1080@1700000000\n",
+            b"1920x1080@ 1700000000\n",
+            b"99999999999x1080@1\n",
+            b"1920x1080@1700000000:\n",
+            // Version 1 requires the payload length, unknown versions are refused
+            b"\x011920x1080@1700000000\n",
+            b"\x021920x1080@1700000000:10\n",
+            b"\xFF\xD8\xFF\xE0",
+        ] {
+            assert_eq!(FrameHeader::decode(bytes), None, "{:?}", String::from_utf8_lossy(bytes));
+        }
+
+        let endless = vec![b'1'; MAX_HEADER_LEN * 2];
+        assert_eq!(parse_frame_header(&endless), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Scaling and JPEG encoding of captured frames.
+//!
+//! Every capture goes through this pipeline exactly once: the encoded result
+//! is written to the AirPlay peer and the same bytes back the in-app preview.
+//! `bench_encode` in the tests prints the per-frame cost of each tier.
+
+use anyhow::{Context, Result};
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Rgba};
+use super::adaptive_quality::QualityTier;
+
+/// Frame ready to be sent and shown
+#[derive(Clone, Debug)]
+pub struct ProcessedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub quality: u8,
+    pub jpeg: Vec<u8>,
+}
+
+/// Downscales frames to a maximum dimension and encodes them as JPEG
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameProcessor {
+    /// Longest side of the output, `None` to keep the captured resolution
+    pub max_dimension: Option<u32>,
+    pub quality: u8,
+}
+
+impl FrameProcessor {
+    pub fn new(max_dimension: Option<u32>, quality: u8) -> Self {
+        Self { max_dimension, quality: quality.clamp(1, 100) }
+    }
+
+    /// Processor for a quality tier, starting from its best JPEG quality
+    pub fn for_tier(tier: QualityTier) -> Self {
+        Self::new(tier.max_dimension(), tier.bounds().max_quality)
+    }
+
+    /// Output size for a `width` x `height` capture, keeping the aspect ratio
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.max_dimension {
+            Some(max) if width.max(height) > max => {
+                let scale = max as f64 / width.max(height) as f64;
+                (
+                    ((width as f64 * scale).round() as u32).max(1),
+                    ((height as f64 * scale).round() as u32).max(1),
+                )
+            }
+            _ => (width, height),
+        }
+    }
+
+    /// Scale and encode an RGBA capture
+    pub fn process(&self, rgba: &[u8], width: u32, height: u32) -> Result<ProcessedFrame> {
+        let (out_width, out_height) = self.output_size(width, height);
+        let mut jpeg = Vec::new();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, self.quality);
+
+        if (out_width, out_height) == (width, height) {
+            encoder
+                .encode(rgba, width, height, image::ColorType::Rgba8)
+                .context("Failed to encode frame")?;
+        } else {
+            let image = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, rgba)
+                .context("Frame buffer does not match its dimensions")?;
+            let scaled = imageops::resize(&image, out_width, out_height, FilterType::Triangle);
+            encoder
+                .encode(scaled.as_raw(), out_width, out_height, image::ColorType::Rgba8)
+                .context("Failed to encode frame")?;
+        }
+
+        Ok(ProcessedFrame {
+            width: out_width,
+            height: out_height,
+            quality: self.quality,
+            jpeg,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let x = (i % width) as u8;
+                let y = (i / width) as u8;
+                [x, y, x ^ y, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_output_size_keeps_aspect_ratio() {
+        let processor = FrameProcessor::new(Some(1280), 80);
+        assert_eq!(processor.output_size(1920, 1080), (1280, 720));
+        assert_eq!(processor.output_size(1080, 1920), (720, 1280));
+        // Smaller captures are never upscaled
+        assert_eq!(processor.output_size(800, 600), (800, 600));
+        assert_eq!(FrameProcessor::new(None, 80).output_size(3840, 2160), (3840, 2160));
+    }
+
+    #[test]
+    fn test_process_scales_and_encodes() {
+        let frame = FrameProcessor::new(Some(64), 70).process(&gradient(128, 96), 128, 96).unwrap();
+
+        assert_eq!((frame.width, frame.height, frame.quality), (64, 48, 70));
+        let decoded = image::load_from_memory(&frame.jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (64, 48));
+    }
+
+    #[test]
+    fn test_process_rejects_short_buffer() {
+        let processor = FrameProcessor::new(Some(32), 70);
+        assert!(processor.process(&[0; 16], 128, 96).is_err());
+    }
+
+    /// Encode time per resolution, for documenting CPU cost
+    #[test]
+    #[ignore]
+    fn bench_encode() {
+        const RUNS: u32 = 10;
+        for (width, height) in [(1280, 720), (1920, 1080), (2560, 1440), (3840, 2160)] {
+            let rgba = gradient(width, height);
+            for tier in [QualityTier::Low, QualityTier::Medium, QualityTier::High] {
+                let processor = FrameProcessor::for_tier(tier);
+                let started = std::time::Instant::now();
+                for _ in 0..RUNS {
+                    processor.process(&rgba, width, height).unwrap();
+                }
+                let (out_width, out_height) = processor.output_size(width, height);
+                println!(
+                    "{}x{} {:?} -> {}x{} q{}: {:?} per frame",
+                    width, height, tier, out_width, out_height, processor.quality,
+                    started.elapsed() / RUNS
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// Bounded queue between a producer and a consumer running at different rates.
+///
+/// When the queue is full the oldest item is dropped so the consumer always
+/// gets the most recent data (latest-frame-wins). The producer never waits.
+pub struct FrameQueue<T> {
+    capacity: usize,
+    state: Mutex<QueueState<T>>,
+    notify: Notify,
+    dropped: AtomicU64,
+}
+
+struct QueueState<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+impl<T> FrameQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            notify: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue an item, evicting the oldest one if full. Returns false once closed.
+    pub fn push(&self, item: T) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return false;
+            }
+            if state.items.len() == self.capacity {
+                state.items.pop_front();
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            state.items.push_back(item);
+        }
+        self.notify.notify_one();
+        true
+    }
+
+    /// Wait for the next item. Returns `None` once the queue is closed.
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                if let Some(item) = state.items.pop_front() {
+                    return Some(item);
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Stop the queue and wake the consumer
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Number of items evicted because the consumer fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_full_queue_drops_oldest() {
+        let queue = FrameQueue::new(2);
+        for frame in 1..=5 {
+            assert!(queue.push(frame));
+        }
+
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(queue.pop().await, Some(4));
+        assert_eq!(queue.pop().await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue = Arc::new(FrameQueue::new(2));
+        let consumer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queue.push(7);
+        assert_eq!(consumer.await.unwrap(), Some(7));
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_wakes_consumer() {
+        let queue = Arc::new(FrameQueue::<u32>::new(2));
+        let consumer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.pop().await })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queue.close();
+        assert_eq!(consumer.await.unwrap(), None);
+        assert!(!queue.push(1));
+    }
+}
@@ -1,60 +1,241 @@
 use anyhow::{Result, anyhow};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::TlsAcceptor;
-use tracing::{info, error, debug};
+use tracing::{info, error, debug, warn};
 use serde_json;
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType};
-use tokio_rustls::rustls::{Certificate as RustlsCert, PrivateKey as RustlsKey, ServerConfig};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use tokio_rustls::server::TlsStream as RustlsTlsStream;
+use super::apple_records;
+use super::airdrop::{
+    enable_keepalive, part_path, publish_incoming, read_with_timeout, receive_to_file, IncomingFile, ACCEPT_RETRY_DELAY,
+    DEFAULT_CHUNK_SIZE, DEFAULT_MAX_FILE_SIZE, MAX_HANDSHAKE_SIZE, READ_TIMEOUT,
+};
+use super::file_conflict::ConflictResolver;
+use super::file_name::safe_file_name;
+use super::peer_filter::PeerFilter;
+use super::tls;
+use super::visibility::{ReceiveGate, Visibility, VisibilitySettings};
+use crate::network::ip_mode::IpMode;
+use crate::utils::{event_log, supervisor};
+
+/// Longest a connection stays open by default, long enough for a large
+/// upload over a slow network
+pub const DEFAULT_MAX_CONNECTION_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// Timeouts after which the server closes a connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Longest wait for the TLS handshake or the next bytes of a request
+    pub idle_timeout: Duration,
+    /// Longest a connection stays open, even while data keeps arriving
+    pub max_lifetime: Duration,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            idle_timeout: READ_TIMEOUT,
+            max_lifetime: DEFAULT_MAX_CONNECTION_LIFETIME,
+        }
+    }
+}
+
+/// Counts a connection as active until it is dropped
+struct ActiveConnection(Arc<AtomicUsize>);
+
+impl ActiveConnection {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::Relaxed);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Largest request line and headers accepted, as in the control API
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// How long an admitted `/Ask` lets its sender upload
+const ASK_APPROVAL_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Debug)]
+struct ApprovedAsk {
+    /// Names of the files the `/Ask` announced
+    files: Vec<String>,
+    expires_at: Instant,
+}
+
+/// Peers whose `/Ask` was admitted, by IP. An `/Upload` carries no sender
+/// name, so this is what ties it to an admitted request.
+#[derive(Clone, Debug, Default)]
+struct ApprovedAsks(Arc<std::sync::Mutex<HashMap<IpAddr, ApprovedAsk>>>);
+
+impl ApprovedAsks {
+    fn approve(&self, ip: IpAddr, files: Vec<String>) {
+        let now = Instant::now();
+        let mut asks = self.0.lock().unwrap();
+        asks.retain(|_, ask| ask.expires_at > now);
+        asks.insert(ip, ApprovedAsk { files, expires_at: now + ASK_APPROVAL_LIFETIME });
+    }
+
+    /// Files announced by the admitted `/Ask` of `ip`, `None` without one
+    fn files(&self, ip: IpAddr) -> Option<Vec<String>> {
+        let asks = self.0.lock().unwrap();
+        asks.get(&ip)
+            .filter(|ask| ask.expires_at > Instant::now())
+            .map(|ask| ask.files.clone())
+    }
+}
+
+/// What every connection of a server shares
+#[derive(Clone)]
+struct RequestContext {
+    events: broadcast::Sender<IncomingFile>,
+    conflicts: ConflictResolver,
+    receive_dir: PathBuf,
+    peer_filter: Arc<PeerFilter>,
+    gate: ReceiveGate,
+    approvals: ApprovedAsks,
+    /// Name returned as `ReceiverComputerName`, read at every request
+    name: watch::Receiver<String>,
+    idle_timeout: Duration,
+    /// Largest file an upload may announce
+    max_file_size: u64,
+}
+
+impl RequestContext {
+    /// Name an upload from `peer` is saved under: the file its admitted
+    /// `/Ask` announced, if it announced exactly one
+    fn upload_file_name(&self, peer: IpAddr) -> String {
+        match self.approvals.files(peer).as_deref() {
+            Some([name]) => safe_file_name(name),
+            _ => default_upload_name(),
+        }
+    }
+
+    /// Whether an `/Upload` is only accepted after an admitted `/Ask`: with
+    /// an allow list or "contacts only", the upload itself carries nothing
+    /// to check the sender by
+    fn upload_needs_ask(&self) -> bool {
+        !self.peer_filter.allow.is_empty() || self.gate.settings().visibility == Visibility::ContactsOnly
+    }
+}
 
 /// HTTP/HTTPS server for AirDrop protocol
 pub struct AirDropHttpServer {
     port: u16,
     tls_acceptor: Option<TlsAcceptor>,
     running: Arc<Mutex<bool>>,
+    incoming_events: broadcast::Sender<IncomingFile>,
+    conflicts: ConflictResolver,
+    receive_dir: PathBuf,
+    peer_filter: Arc<PeerFilter>,
+    receive_gate: ReceiveGate,
+    /// Name returned as `ReceiverComputerName`
+    name: watch::Receiver<String>,
+    /// Decides whether the server listens on IPv4 or IPv6
+    ip_mode: IpMode,
+    limits: ConnectionLimits,
+    max_file_size: u64,
+    /// Connections currently open, shared with whoever created the server
+    active: Arc<AtomicUsize>,
+    accept_task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl AirDropHttpServer {
-    pub fn new(port: u16) -> Self {
+    pub fn new(
+        port: u16,
+        incoming_events: broadcast::Sender<IncomingFile>,
+        conflicts: ConflictResolver,
+        peer_filter: Arc<PeerFilter>,
+    ) -> Self {
         Self {
             port,
             tls_acceptor: None,
             running: Arc::new(Mutex::new(false)),
+            incoming_events,
+            conflicts,
+            receive_dir: std::env::temp_dir(),
+            peer_filter,
+            receive_gate: ReceiveGate::new(VisibilitySettings::default()),
+            name: watch::channel(apple_records::friendly_name(None).unwrap_or_else(|_| "AirWin".to_string())).1,
+            ip_mode: IpMode::Auto,
+            limits: ConnectionLimits::default(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            active: Arc::new(AtomicUsize::new(0)),
+            accept_task: Mutex::new(None),
         }
     }
 
-    async fn build_rustls_config() -> Result<Arc<ServerConfig>> {
-        info!("Generating self-signed certificate for AirDrop HTTPS server (rustls)...");
+    /// Decide on `/Ask` requests with the given visibility settings
+    pub fn with_receive_gate(mut self, gate: ReceiveGate) -> Self {
+        self.receive_gate = gate;
+        self
+    }
 
-        let mut params = CertificateParams::new(vec!["AirWin".to_string()]);
-        let mut dn = DistinguishedName::new();
-        dn.push(DnType::CommonName, "AirWin");
-        dn.push(DnType::OrganizationName, "AirWin");
-        dn.push(DnType::CountryName, "US");
-        params.distinguished_name = dn;
+    /// Tell peers this name instead of the hostname
+    pub fn with_friendly_name(mut self, name: String) -> Self {
+        self.name = watch::channel(name).1;
+        self
+    }
 
-        let cert = Certificate::from_params(params)?;
-        let cert_der = cert.serialize_der()?;
-        let key_der = cert.serialize_private_key_der();
+    /// Tell peers the latest name published on `name`, so a name numbered
+    /// after a collision applies without restarting the server
+    pub fn with_advertised_name(mut self, name: watch::Receiver<String>) -> Self {
+        self.name = name;
+        self
+    }
 
-        let cert_chain = vec![RustlsCert(cert_der)];
-        let key = RustlsKey(key_der);
+    /// Listen on IPv6 instead of IPv4 in IPv6-only mode
+    pub fn with_ip_mode(mut self, mode: IpMode) -> Self {
+        self.ip_mode = mode;
+        self
+    }
 
-        let config = ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
-            .with_single_cert(cert_chain, key)?;
+    /// Close connections that stay idle or open longer than `limits`
+    pub fn with_connection_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        Ok(Arc::new(config))
+    /// Refuse uploads announcing more than `bytes`
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Count open connections in `active` instead of a counter of its own
+    pub fn with_connection_counter(mut self, active: Arc<AtomicUsize>) -> Self {
+        self.active = active;
+        self
+    }
+
+    /// Connections currently open
+    pub fn active_connections(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Save uploaded files in `dir` instead of the system temp directory
+    pub fn with_receive_dir(mut self, dir: PathBuf) -> Self {
+        self.receive_dir = dir;
+        self
     }
 
     pub async fn initialize(&mut self) -> Result<()> {
-        let config = Self::build_rustls_config().await?;
+        // Same stored certificate as the fallback receiver
+        let config = tls::server_config().await?;
         let acceptor = TlsAcceptor::from(config);
         self.tls_acceptor = Some(acceptor);
         Ok(())
@@ -64,31 +245,77 @@ impl AirDropHttpServer {
         let acceptor = self.tls_acceptor.as_ref()
             .ok_or_else(|| anyhow!("TLS acceptor not initialized"))?;
 
-        let listener = TcpListener::bind(("0.0.0.0", self.port)).await?;
-        info!("AirDrop HTTPS server listening on port {}", self.port);
+        let listener = Arc::new(TcpListener::bind(self.ip_mode.primary_addr(self.port)).await?);
+        info!("AirDrop HTTPS server listening on {}", self.ip_mode.primary_addr(self.port));
 
         *self.running.lock().await = true;
         let running = self.running.clone();
         let acceptor = acceptor.clone();
+        let context = RequestContext {
+            events: self.incoming_events.clone(),
+            conflicts: self.conflicts.clone(),
+            receive_dir: self.receive_dir.clone(),
+            peer_filter: self.peer_filter.clone(),
+            gate: self.receive_gate.clone(),
+            approvals: ApprovedAsks::default(),
+            name: self.name.clone(),
+            idle_timeout: self.limits.idle_timeout,
+            max_file_size: self.max_file_size,
+        };
+        let limits = self.limits;
+
+        // Uploads abandoned while AirWin was closed
+        let receive_dir = self.receive_dir.clone();
+        tokio::spawn(async move { remove_stale_uploads(&receive_dir, RESUMABLE_UPLOAD_EXPIRY).await });
+        let active = self.active.clone();
+        let port = self.port;
 
-        tokio::spawn(async move {
-            while *running.lock().await {
-                match listener.accept().await {
-                    Ok((stream, addr)) => {
-                        let acceptor = acceptor.clone();
-                        tokio::spawn(async move {
-                            if let Err(e) = Self::handle_connection(stream, addr, acceptor).await {
-                                error!("Error handling connection from {}: {}", addr, e);
+        // Restarted after a panic on the same listener, never bound twice
+        let task = supervisor::global().spawn(format!("AirDrop HTTPS :{}", self.port), move || {
+            let listener = listener.clone();
+            let running = running.clone();
+            let acceptor = acceptor.clone();
+            let context = context.clone();
+            let active = active.clone();
+            async move {
+                while *running.lock().await {
+                    match listener.accept().await {
+                        Ok((stream, addr)) => {
+                            // Dropped before the TLS handshake
+                            if !context.peer_filter.permits(addr.ip(), None) {
+                                event_log::warning("HTTPS", format!("Rejected connection from {}", addr.ip()));
+                                continue;
                             }
-                        });
-                    }
-                    Err(e) => {
-                        error!("Error accepting connection: {}", e);
-                        break;
+                            let acceptor = acceptor.clone();
+                            let context = context.clone();
+                            let active = ActiveConnection::new(active.clone());
+                            tokio::spawn(async move {
+                                let _active = active;
+                                let handled = Self::handle_connection(stream, addr, acceptor, context);
+                                match tokio::time::timeout(limits.max_lifetime, handled).await {
+                                    Ok(Ok(())) => {}
+                                    Ok(Err(e)) => {
+                                        error!("Error handling connection from {}: {}", addr, e);
+                                        event_log::error("HTTPS", format!("Request from {} failed: {}", addr, e));
+                                    }
+                                    Err(_) => {
+                                        warn!("Closing connection from {}: open for more than {:?}", addr, limits.max_lifetime);
+                                        event_log::warning("HTTPS", format!("Closed connection from {}: open for more than {:?}", addr, limits.max_lifetime));
+                                    }
+                                }
+                            });
+                        }
+                        // Usually transient, so the listener is kept
+                        Err(e) => {
+                            warn!("Accept error on HTTPS port {}: {}", port, e);
+                            event_log::warning("HTTPS", format!("Accept error on port {}: {}", port, e));
+                            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        }
                     }
                 }
             }
         });
+        *self.accept_task.lock().await = Some(task);
 
         Ok(())
     }
@@ -97,57 +324,144 @@ impl AirDropHttpServer {
         stream: TcpStream,
         addr: SocketAddr,
         acceptor: TlsAcceptor,
+        context: RequestContext,
     ) -> Result<()> {
+        let idle_timeout = context.idle_timeout;
         debug!("Handling HTTPS connection from {}", addr);
+        enable_keepalive(&stream);
 
-        let mut tls_stream = acceptor.accept(stream).await?;
+        let mut tls_stream = match tokio::time::timeout(idle_timeout, acceptor.accept(stream)).await {
+            Ok(stream) => stream?,
+            Err(_) => {
+                warn!("Closing idle connection from {}: no TLS handshake within {:?}", addr, idle_timeout);
+                return Err(anyhow!("No TLS handshake within {:?}", idle_timeout));
+            }
+        };
 
         // Read HTTP request
         let mut buffer = Vec::new();
         let mut temp_buf = [0u8; 1024];
         
         loop {
-            let n = tls_stream.read(&mut temp_buf).await?;
+            let n = match read_with_timeout(&mut tls_stream, &mut temp_buf, idle_timeout).await {
+                Ok(n) => n,
+                Err(e) => {
+                    warn!("Closing connection from {}: {}", addr, e);
+                    return Err(e.into());
+                }
+            };
             if n == 0 { break; }
+            // Only the new bytes, with the three before them, can end the headers
+            let scan_from = buffer.len().saturating_sub(3);
             buffer.extend_from_slice(&temp_buf[..n]);
             
             // Check for end of HTTP headers
-            if buffer.windows(4).any(|w| w == b"\r\n\r\n") {
+            if buffer[scan_from..].windows(4).any(|w| w == b"\r\n\r\n") {
                 break;
             }
+            if buffer.len() > MAX_HEADER_SIZE {
+                warn!("Closing connection from {}: headers larger than {} bytes", addr, MAX_HEADER_SIZE);
+                let response = "HTTP/1.1 431 Request Header Fields Too Large\r\nContent-Length: 0\r\n\r\n";
+                tls_stream.write_all(response.as_bytes()).await?;
+                return Err(anyhow!("Request headers larger than {} bytes", MAX_HEADER_SIZE));
+            }
         }
 
-        let request = String::from_utf8_lossy(&buffer);
-        let lines: Vec<&str> = request.lines().collect();
-        
-        if lines.is_empty() {
-            return Err(anyhow!("Empty HTTP request"));
-        }
+        let (method, path) = {
+            let request = String::from_utf8_lossy(&buffer);
+            let lines: Vec<&str> = request.lines().collect();
 
-        let request_line = lines[0];
-        let parts: Vec<&str> = request_line.split_whitespace().collect();
-        
-        if parts.len() < 3 {
-            return Err(anyhow!("Invalid HTTP request line"));
-        }
+            if lines.is_empty() {
+                return Err(anyhow!("Empty HTTP request"));
+            }
+
+            let request_line = lines[0];
+            let parts: Vec<&str> = request_line.split_whitespace().collect();
+
+            if parts.len() < 3 {
+                return Err(anyhow!("Invalid HTTP request line"));
+            }
+
+            (parts[0].to_string(), parts[1].to_string())
+        };
+        let (method, path) = (method.as_str(), path.as_str());
 
-        let method = parts[0];
-        let path = parts[1];
-        
         debug!("HTTP {} request to {}", method, path);
 
+        // The JSON body of the other requests is read whole before the
+        // sender is checked, however it was split across reads. Uploads are
+        // streamed to disk instead.
+        if path != "/Upload" {
+            let length = header_value(&buffer, "Content-Length")
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or(0);
+            if length > MAX_HANDSHAKE_SIZE {
+                warn!("Rejecting {} from {}: {} byte body, limit {}", path, addr, length, MAX_HANDSHAKE_SIZE);
+                let response = "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n";
+                tls_stream.write_all(response.as_bytes()).await?;
+                return Ok(());
+            }
+            let body_start = buffer.windows(4).position(|w| w == b"\r\n\r\n").map_or(buffer.len(), |end| end + 4);
+            while buffer.len() < body_start + length {
+                let n = read_with_timeout(&mut tls_stream, &mut temp_buf, idle_timeout).await?;
+                if n == 0 {
+                    break;
+                }
+                buffer.extend_from_slice(&temp_buf[..n]);
+            }
+        }
+
+        // The sender's name is only known once it asks to transfer. An
+        // upload has none: when it matters it must follow an admitted /Ask
+        // from the same address.
+        let sender = sender_name(&buffer);
+        let permitted = match (method, path) {
+            ("POST", "/Upload") if context.upload_needs_ask() => {
+                context.approvals.files(addr.ip()).is_some()
+            }
+            _ => context.peer_filter.admits(addr.ip(), sender.as_deref()),
+        };
+        if !permitted {
+            let peer = sender.as_deref().unwrap_or("unnamed peer");
+            event_log::warning("HTTPS", format!("Rejected {} from {} ({})", path, peer, addr.ip()));
+            Self::handle_forbidden(&mut tls_stream).await?;
+            return Ok(());
+        }
+
         match (method, path) {
             ("GET", "/") => {
                 Self::handle_root_request(&mut tls_stream).await?;
             }
             ("POST", "/Discover") => {
-                Self::handle_discover_request(&mut tls_stream, &buffer).await?;
+                let name = context.name.borrow().clone();
+                Self::handle_discover_request(&mut tls_stream, &buffer, &name).await?;
             }
             ("POST", "/Ask") => {
-                Self::handle_ask_request(&mut tls_stream, &buffer).await?;
+                let sender = sender.unwrap_or_else(|| addr.ip().to_string());
+                let is_contact = context.peer_filter.is_contact(addr.ip(), &sender);
+                let device_id = sender_id(&buffer);
+                let files = requested_files(&buffer);
+                match context.gate.admit(&sender, device_id.as_deref(), files.clone(), is_contact).await {
+                    Ok(()) => {
+                        context.approvals.approve(addr.ip(), files);
+                        let name = context.name.borrow().clone();
+                        Self::handle_ask_request(&mut tls_stream, &buffer, &name).await?
+                    }
+                    Err(reason) => {
+                        event_log::warning("HTTPS", format!("Rejected transfer from {} ({}): {}", sender, addr.ip(), reason));
+                        Self::handle_forbidden(&mut tls_stream).await?;
+                    }
+                }
+            }
+            ("POST", "/Upload") if !context.gate.settings().advertised() => {
+                event_log::warning("HTTPS", format!("Rejected upload from {}: receiving is off", addr.ip()));
+                Self::handle_forbidden(&mut tls_stream).await?;
+            }
+            ("POST", "/Upload") if header_value(&buffer, UPLOAD_ID_HEADER).is_some() => {
+                Self::handle_resumable_upload(&mut tls_stream, &buffer, addr, &context).await?;
             }
             ("POST", "/Upload") => {
-                Self::handle_upload_request(&mut tls_stream, &buffer).await?;
+                Self::handle_upload_request(&mut tls_stream, &buffer, addr, &context).await?;
             }
             _ => {
                 Self::handle_not_found(&mut tls_stream).await?;
@@ -166,6 +480,7 @@ impl AirDropHttpServer {
     async fn handle_discover_request(
         stream: &mut RustlsTlsStream<TcpStream>,
         _buffer: &[u8],
+        name: &str,
     ) -> Result<()> {
         info!("Handling /Discover request");
 
@@ -180,9 +495,7 @@ impl AirDropHttpServer {
                 }
             }
         }));
-        discover_response.insert("ReceiverComputerName", serde_json::Value::String(
-            hostname::get()?.to_string_lossy().to_string()
-        ));
+        discover_response.insert("ReceiverComputerName", serde_json::Value::String(name.to_string()));
         discover_response.insert("ReceiverModelName", serde_json::Value::String("Windows,1".to_string()));
 
         let response_json = serde_json::to_string(&discover_response)?;
@@ -200,13 +513,14 @@ impl AirDropHttpServer {
     async fn handle_ask_request(
         stream: &mut RustlsTlsStream<TcpStream>,
         _buffer: &[u8],
+        name: &str,
     ) -> Result<()> {
         info!("Handling /Ask request");
 
         // For now, always accept transfers
         let ask_response = serde_json::json!({
             "ReceiverModelName": "Windows,1",
-            "ReceiverComputerName": hostname::get()?.to_string_lossy().to_string()
+            "ReceiverComputerName": name
         });
 
         let response_json = serde_json::to_string(&ask_response)?;
@@ -224,6 +538,8 @@ impl AirDropHttpServer {
     async fn handle_upload_request(
         stream: &mut RustlsTlsStream<TcpStream>,
         buffer: &[u8],
+        from: SocketAddr,
+        context: &RequestContext,
     ) -> Result<()> {
         info!("Handling /Upload request");
 
@@ -232,32 +548,861 @@ impl AirDropHttpServer {
             .position(|w| w == b"\r\n\r\n")
             .ok_or_else(|| anyhow!("Could not find end of HTTP headers"))?;
 
-        let body_start = header_end + 4;
-        let body = &buffer[body_start..];
+        let Some(length) = header_value(buffer, "Content-Length").and_then(|value| value.parse::<u64>().ok()) else {
+            let response = "HTTP/1.1 411 Length Required\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        };
+        if length > context.max_file_size {
+            warn!("Rejecting upload from {}: {} bytes, limit {}", from, length, context.max_file_size);
+            let response = "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        }
 
-        // Save uploaded file to temp directory
-        let temp_dir = std::env::temp_dir();
-        let file_path = temp_dir.join(format!("airdrop_upload_{}.bin", 
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs()));
+        // Save uploaded file to the receive directory. The part of the body
+        // that came with the headers is read first; the file is only kept
+        // once `Content-Length` bytes have arrived.
+        tokio::fs::create_dir_all(&context.receive_dir).await?;
+        let destination = context.conflicts.resolve(&context.receive_dir, &context.upload_file_name(from.ip())).await?;
+        let file_path = destination.path.clone();
 
-        tokio::fs::write(&file_path, body).await?;
+        let early = &buffer[header_end + 4..];
+        let mut body = early.chain(&mut *stream);
+        let size = match receive_to_file(&mut body, &file_path, length, context.idle_timeout, DEFAULT_CHUNK_SIZE, None).await {
+            Ok(size) => size,
+            Err(e) => {
+                destination.release().await;
+                return Err(e);
+            }
+        };
         info!("Saved uploaded file to {:?}", file_path);
 
+        publish_incoming(&context.events, IncomingFile {
+            name: file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size,
+            from,
+            path: file_path,
+        });
+
         let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
         stream.write_all(response.as_bytes()).await?;
         Ok(())
     }
 
+    /// `/Upload` with an `X-Upload-Id`: bytes are appended to a `.part` file
+    /// kept for that id and peer, so an interrupted upload continues where it
+    /// stopped. A request without `Content-Range` only asks for the current
+    /// offset.
+    async fn handle_resumable_upload(
+        stream: &mut RustlsTlsStream<TcpStream>,
+        buffer: &[u8],
+        from: SocketAddr,
+        context: &RequestContext,
+    ) -> Result<()> {
+        let receive_dir = context.receive_dir.as_path();
+        let Some(id) = header_value(buffer, UPLOAD_ID_HEADER).filter(|id| valid_upload_id(id)) else {
+            let response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        };
+        info!("Handling resumable /Upload {}", id);
+
+        tokio::fs::create_dir_all(receive_dir).await?;
+        let part = resumable_part_path(receive_dir, from.ip(), &id);
+        let offset = match tokio::fs::metadata(&part).await {
+            Ok(meta) if is_stale(&meta, RESUMABLE_UPLOAD_EXPIRY) => {
+                info!("Upload {} expired, starting over", id);
+                tokio::fs::remove_file(&part).await?;
+                0
+            }
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                remove_stale_uploads(receive_dir, RESUMABLE_UPLOAD_EXPIRY).await;
+                0
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let Some(range) = header_value(buffer, "Content-Range") else {
+            return Self::respond_offset(stream, "308 Resume Incomplete", offset).await;
+        };
+        let Some((start, total)) = parse_content_range(&range) else {
+            let response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        };
+        if total > context.max_file_size {
+            warn!("Rejecting upload {} from {}: {} bytes, limit {}", id, from, total, context.max_file_size);
+            let _ = tokio::fs::remove_file(&part).await;
+            let response = "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response.as_bytes()).await?;
+            return Ok(());
+        }
+        // The client resends from whatever offset we report
+        if start != offset || offset > total {
+            return Self::respond_offset(stream, "416 Range Not Satisfiable", offset).await;
+        }
+
+        let length = header_value(buffer, "Content-Length")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0)
+            .min(total - offset);
+
+        let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&part).await?;
+        let header_end = buffer.windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or_else(|| anyhow!("Could not find end of HTTP headers"))?;
+        let early = &buffer[header_end + 4..];
+        let early = &early[..early.len().min(length as usize)];
+        file.write_all(early).await?;
+
+        let mut received = early.len() as u64;
+        let mut chunk = [0u8; 16 * 1024];
+        while received < length {
+            let wanted = chunk.len().min((length - received) as usize);
+            let n = match read_with_timeout(stream, &mut chunk[..wanted], context.idle_timeout).await {
+                Ok(n) => n,
+                Err(e) => {
+                    // What arrived stays in the .part file for the next attempt
+                    file.flush().await?;
+                    warn!("Upload {} interrupted at {} bytes: {}", id, offset + received, e);
+                    return Err(e.into());
+                }
+            };
+            if n == 0 {
+                break;
+            }
+            file.write_all(&chunk[..n]).await?;
+            received += n as u64;
+        }
+        file.flush().await?;
+        drop(file);
+
+        let offset = offset + received;
+        if offset < total {
+            debug!("Upload {} at {} of {} bytes", id, offset, total);
+            return Self::respond_offset(stream, "308 Resume Incomplete", offset).await;
+        }
+
+        let destination = context.conflicts.resolve(receive_dir, &context.upload_file_name(from.ip())).await?;
+        if let Err(e) = tokio::fs::rename(&part, &destination.path).await {
+            destination.release().await;
+            return Err(e.into());
+        }
+        let file_path = destination.path;
+        info!("Saved resumed upload {} to {:?}", id, file_path);
+
+        publish_incoming(&context.events, IncomingFile {
+            name: file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size: total,
+            from,
+            path: file_path,
+        });
+
+        Self::respond_offset(stream, "200 OK", offset).await
+    }
+
+    async fn respond_offset(stream: &mut RustlsTlsStream<TcpStream>, status: &str, offset: u64) -> Result<()> {
+        let response = format!(
+            "HTTP/1.1 {}\r\n{}: {}\r\nContent-Length: 0\r\n\r\n",
+            status, UPLOAD_OFFSET_HEADER, offset
+        );
+        stream.write_all(response.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn handle_forbidden(stream: &mut RustlsTlsStream<TcpStream>) -> Result<()> {
+        let response = "HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n";
+        stream.write_all(response.as_bytes()).await?;
+        Ok(())
+    }
+
     async fn handle_not_found(stream: &mut RustlsTlsStream<TcpStream>) -> Result<()> {
         let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
         stream.write_all(response.as_bytes()).await?;
         Ok(())
     }
 
+    /// Stop accepting connections and release the port
     pub async fn stop(&self) {
         *self.running.lock().await = false;
+        if let Some(task) = self.accept_task.lock().await.take() {
+            task.abort();
+        }
+    }
+}
+
+/// Client-chosen id that makes an `/Upload` resumable
+const UPLOAD_ID_HEADER: &str = "X-Upload-Id";
+
+/// Bytes of a resumable upload the server already has
+const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
+
+/// Value of the header `name`, compared case-insensitively
+fn header_value(buffer: &[u8], name: &str) -> Option<String> {
+    let header_end = buffer.windows(4).position(|w| w == b"\r\n\r\n")?;
+    String::from_utf8_lossy(&buffer[..header_end])
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// Name of an upload when its `/Ask` didn't announce a single file
+fn default_upload_name() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    format!("airdrop_upload_{}.bin", secs)
+}
+
+/// Start of the `.part` file names of resumable uploads
+const RESUMABLE_PREFIX: &str = "airdrop_upload_";
+
+/// How long the `.part` file of an interrupted resumable upload is kept
+/// after its last bytes arrived
+const RESUMABLE_UPLOAD_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Upload ids end up in a file name
+fn valid_upload_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `.part` file collecting the bytes `peer` uploads as `id`. The address is
+/// in the name, so a peer can neither continue nor probe another peer's
+/// upload by reusing its id.
+fn resumable_part_path(receive_dir: &Path, peer: IpAddr, id: &str) -> PathBuf {
+    let peer: String = peer
+        .to_canonical()
+        .to_string()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    part_path(&receive_dir.join(format!("{}{}_{}", RESUMABLE_PREFIX, peer, id)))
+}
+
+/// Whether a file was last written more than `max_age` ago
+fn is_stale(meta: &std::fs::Metadata, max_age: Duration) -> bool {
+    meta.modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > max_age)
+}
+
+/// Delete the `.part` files of resumable uploads left untouched for `max_age`
+async fn remove_stale_uploads(receive_dir: &Path, max_age: Duration) {
+    let Ok(mut entries) = tokio::fs::read_dir(receive_dir).await else {
+        return;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with(RESUMABLE_PREFIX) || !name.ends_with(".part") {
+            continue;
+        }
+        if entry.metadata().await.is_ok_and(|meta| is_stale(&meta, max_age)) {
+            match tokio::fs::remove_file(entry.path()).await {
+                Ok(()) => info!("Removed abandoned upload {:?}", entry.path()),
+                Err(e) => warn!("Failed to remove abandoned upload {:?}: {}", entry.path(), e),
+            }
+        }
+    }
+}
+
+/// First byte and total size from `Content-Range: bytes <start>-<end>/<total>`
+fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end, total) = (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?, total.parse::<u64>().ok()?);
+    (start <= end && end < total).then_some((start, total))
+}
+
+/// JSON request body
+fn json_body(buffer: &[u8]) -> Option<serde_json::Value> {
+    let header_end = buffer.windows(4).position(|w| w == b"\r\n\r\n")?;
+    serde_json::from_slice(&buffer[header_end + 4..]).ok()
+}
+
+/// `SenderComputerName` from a JSON request body
+fn sender_name(buffer: &[u8]) -> Option<String> {
+    json_body(buffer)?.get("SenderComputerName")?.as_str().map(str::to_string)
+}
+
+/// `SenderID` from a JSON request body, the id a device is trusted by
+fn sender_id(buffer: &[u8]) -> Option<String> {
+    json_body(buffer)?.get("SenderID")?.as_str().map(str::to_string)
+}
+
+/// Names of the files listed in an `/Ask` request body
+fn requested_files(buffer: &[u8]) -> Vec<String> {
+    let Some(body) = json_body(buffer) else {
+        return Vec::new();
+    };
+    body.get("Files")
+        .and_then(|files| files.as_array())
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|file| file.get("FileName")?.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::airdrop::INCOMING_EVENTS_CAPACITY;
+    use crate::protocols::file_conflict::FileConflictPolicy;
+    use tokio_native_tls::native_tls;
+
+    /// Context of a server saving into `receive_dir` and naming itself "PC"
+    fn context(
+        events: broadcast::Sender<IncomingFile>,
+        receive_dir: PathBuf,
+        peer_filter: PeerFilter,
+        gate: ReceiveGate,
+        idle_timeout: Duration,
+    ) -> RequestContext {
+        RequestContext {
+            events,
+            conflicts: ConflictResolver::new(FileConflictPolicy::KeepBoth),
+            receive_dir,
+            peer_filter: Arc::new(peer_filter),
+            gate,
+            approvals: ApprovedAsks::default(),
+            name: watch::channel("PC".to_string()).1,
+            idle_timeout,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        }
+    }
+
+    /// Client accepting the server's self-signed certificate
+    fn connector() -> tokio_native_tls::TlsConnector {
+        tokio_native_tls::TlsConnector::from(
+            native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// New TLS connection to the server
+    async fn connect(server_addr: SocketAddr) -> tokio_native_tls::TlsStream<TcpStream> {
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        connector().connect("AirWin", stream).await.unwrap()
+    }
+
+    /// Send `request` on a new connection and return the response
+    async fn exchange(server_addr: SocketAddr, request: &[u8]) -> String {
+        use tokio::io::AsyncReadExt;
+
+        let mut stream = connect(server_addr).await;
+        stream.write_all(request).await.unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        response
+    }
+
+    #[tokio::test]
+    async fn test_upload_publishes_incoming_event() {
+        let (events, mut incoming) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            let gate = ReceiveGate::new(VisibilitySettings::default());
+            let context = context(events, std::env::temp_dir(), PeerFilter::default(), gate, READ_TIMEOUT);
+            AirDropHttpServer::handle_connection(stream, addr, acceptor, context).await
+        });
+
+        // The server certificate is self-signed
+        let connector = tokio_native_tls::TlsConnector::from(
+            native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .unwrap(),
+        );
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let client_addr = stream.local_addr().unwrap();
+        let mut stream = connector.connect("AirWin", stream).await.unwrap();
+        stream
+            .write_all(b"POST /Upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nciao!")
+            .await
+            .unwrap();
+
+        server.await.unwrap().unwrap();
+
+        let event = incoming.recv().await.unwrap();
+        assert_eq!(event.size, 5);
+        assert_eq!(event.from, client_addr);
+        assert!(event.name.starts_with("airdrop_upload_"));
+
+        let _ = std::fs::remove_file(std::env::temp_dir().join(&event.name));
+    }
+
+    #[tokio::test]
+    async fn test_discover_answers_with_the_current_name() {
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+        let (name, names) = watch::channel("PC".to_string());
+        let context = RequestContext {
+            name: names,
+            ..context(events, std::env::temp_dir(), PeerFilter::default(), ReceiveGate::new(VisibilitySettings::default()), READ_TIMEOUT)
+        };
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (stream, addr) = listener.accept().await.unwrap();
+                AirDropHttpServer::handle_connection(stream, addr, acceptor.clone(), context.clone()).await.unwrap();
+            }
+        });
+
+        let request = b"POST /Discover HTTP/1.1\r\nContent-Length: 0\r\n\r\n";
+        assert!(exchange(server_addr, request).await.contains(r#""ReceiverComputerName":"PC""#));
+
+        // Numbered after a collision while the server runs
+        name.send_replace("PC (2)".to_string());
+        assert!(exchange(server_addr, request).await.contains(r#""ReceiverComputerName":"PC (2)""#));
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_truncated_upload_is_not_saved() {
+        let (events, mut incoming) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+        let receive_dir = std::env::temp_dir().join(format!("airwin_truncated_{}", uuid::Uuid::new_v4().simple()));
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let context = context(events, receive_dir.clone(), PeerFilter::default(), ReceiveGate::new(VisibilitySettings::default()), READ_TIMEOUT);
+        let server = tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            AirDropHttpServer::handle_connection(stream, addr, acceptor, context).await
+        });
+
+        // Ten bytes announced, five sent before the connection closes
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut stream = connector().connect("AirWin", stream).await.unwrap();
+        stream
+            .write_all(b"POST /Upload HTTP/1.1\r\nContent-Length: 10\r\n\r\nhello")
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+        drop(stream);
+
+        assert!(server.await.unwrap().is_err());
+        assert!(incoming.try_recv().is_err());
+        let saved = std::fs::read_dir(&receive_dir).map(|entries| entries.count()).unwrap_or(0);
+        assert_eq!(saved, 0);
+
+        let _ = std::fs::remove_dir_all(&receive_dir);
+    }
+
+    #[tokio::test]
+    async fn test_ask_rejected_when_receiving_off() {
+        use tokio::io::AsyncReadExt;
+
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            let gate = ReceiveGate::new(VisibilitySettings {
+                visibility: Visibility::ReceivingOff,
+                auto_accept_from_contacts: false,
+            });
+            let context = context(events, std::env::temp_dir(), PeerFilter::default(), gate, READ_TIMEOUT);
+            AirDropHttpServer::handle_connection(stream, addr, acceptor, context).await
+        });
+
+        let connector = tokio_native_tls::TlsConnector::from(
+            native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .unwrap(),
+        );
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut stream = connector.connect("AirWin", stream).await.unwrap();
+        let body = r#"{"SenderComputerName":"iPhone","Files":[{"FileName":"photo.jpg"}]}"#;
+        stream
+            .write_all(format!("POST /Ask HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+            .await
+            .unwrap();
+
+        server.await.unwrap().unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_needs_a_name_or_an_admitted_ask() {
+        let (events, mut incoming) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+        let receive_dir = std::env::temp_dir().join(format!("airwin_allow_{}", uuid::Uuid::new_v4().simple()));
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let peer_filter = PeerFilter {
+            allow: vec!["My iPad".to_string()],
+            deny: Vec::new(),
+        };
+        let context = context(events, receive_dir.clone(), peer_filter, ReceiveGate::new(VisibilitySettings::default()), READ_TIMEOUT);
+        let server = tokio::spawn(async move {
+            for _ in 0..4 {
+                let (stream, addr) = listener.accept().await.unwrap();
+                let _ = AirDropHttpServer::handle_connection(stream, addr, acceptor.clone(), context.clone()).await;
+            }
+        });
+
+        let upload = b"POST /Upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nciao!";
+        let ask = |name: &str| {
+            let body = format!(r#"{{"SenderComputerName":"{}","Files":[{{"FileName":"a.txt"}}]}}"#, name);
+            format!("POST /Ask HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+        };
+
+        // Neither an upload out of the blue nor an /Ask from another device gets in
+        assert!(exchange(server_addr, upload).await.starts_with("HTTP/1.1 403"));
+        assert!(exchange(server_addr, ask("Someone's iPhone").as_bytes()).await.starts_with("HTTP/1.1 403"));
+        assert!(incoming.try_recv().is_err());
+
+        // The listed device asks first, then its upload is accepted
+        assert!(exchange(server_addr, ask("My iPad").as_bytes()).await.starts_with("HTTP/1.1 200"));
+        assert!(exchange(server_addr, upload).await.starts_with("HTTP/1.1 200"));
+        server.await.unwrap();
+        assert_eq!(incoming.recv().await.unwrap().size, 5);
+
+        let _ = std::fs::remove_dir_all(&receive_dir);
+    }
+
+    #[tokio::test]
+    async fn test_sender_name_is_read_from_the_whole_body() {
+        use tokio::io::AsyncReadExt;
+
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let peer_filter = PeerFilter {
+            allow: vec!["My iPad".to_string()],
+            deny: Vec::new(),
+        };
+        let context = context(events, std::env::temp_dir(), peer_filter, ReceiveGate::new(VisibilitySettings::default()), READ_TIMEOUT);
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (stream, addr) = listener.accept().await.unwrap();
+                AirDropHttpServer::handle_connection(stream, addr, acceptor.clone(), context.clone()).await.unwrap();
+            }
+        });
+
+        // Body sent after the headers, in its own write
+        let body = r#"{"SenderComputerName":"My iPad","Files":[{"FileName":"a.txt"}]}"#;
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut stream = connector().connect("AirWin", stream).await.unwrap();
+        let headers = format!("POST /Ask HTTP/1.1\r\nContent-Length: {}\r\n\r\n", body.len());
+        stream.write_all(headers.as_bytes()).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(body.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        // Body longer than a single read, with the name at its end
+        let files: Vec<String> = (0..60).map(|i| format!(r#"{{"FileName":"photo_{:03}.jpg"}}"#, i)).collect();
+        let body = format!(r#"{{"Files":[{}],"SenderComputerName":"My iPad"}}"#, files.join(","));
+        assert!(body.len() > 1024);
+        let ask = format!("POST /Ask HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let response = exchange(server_addr, ask.as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_contacts_only_rejects_uploads_without_an_admitted_ask() {
+        let (events, mut incoming) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let gate = ReceiveGate::new(VisibilitySettings {
+            visibility: Visibility::ContactsOnly,
+            auto_accept_from_contacts: false,
+        });
+        let context = context(events, std::env::temp_dir(), PeerFilter::default(), gate, READ_TIMEOUT);
+        let server = tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            AirDropHttpServer::handle_connection(stream, addr, acceptor, context).await
+        });
+
+        let response = exchange(server_addr, b"POST /Upload HTTP/1.1\r\nContent-Length: 5\r\n\r\nciao!").await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+        server.await.unwrap().unwrap();
+        assert!(incoming.try_recv().is_err());
+    }
+
+    /// A started server with `limits` on a free port, its address and its
+    /// connection counter
+    async fn running_server(limits: ConnectionLimits) -> (AirDropHttpServer, SocketAddr, Arc<AtomicUsize>) {
+        let port = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap().port();
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let active = Arc::new(AtomicUsize::new(0));
+        let mut server = AirDropHttpServer::new(
+            port,
+            events,
+            ConflictResolver::new(FileConflictPolicy::KeepBoth),
+            Arc::new(PeerFilter::default()),
+        )
+        .with_connection_limits(limits)
+        .with_connection_counter(active.clone());
+        server.initialize().await.unwrap();
+        server.start().await.unwrap();
+        (server, SocketAddr::from(([127, 0, 0, 1], port)), active)
+    }
+
+    /// Wait up to five seconds for the server to count `expected` connections
+    async fn wait_for_connections(active: &AtomicUsize, expected: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while active.load(Ordering::Relaxed) != expected {
+            assert!(Instant::now() < deadline, "{} connections open, expected {}", active.load(Ordering::Relaxed), expected);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_is_reaped_after_the_idle_timeout() {
+        use tokio::io::AsyncReadExt;
+
+        let limits = ConnectionLimits {
+            idle_timeout: Duration::from_millis(200),
+            max_lifetime: DEFAULT_MAX_CONNECTION_LIFETIME,
+        };
+        let (server, server_addr, active) = running_server(limits).await;
+
+        // Connects and never starts the TLS handshake
+        let mut stream = TcpStream::connect(server_addr).await.unwrap();
+        wait_for_connections(&active, 1).await;
+        assert_eq!(server.active_connections(), 1);
+
+        let mut byte = [0u8; 1];
+        let closed = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut byte))
+            .await
+            .expect("the idle connection was not closed");
+        assert!(matches!(closed, Ok(0) | Err(_)));
+        wait_for_connections(&active, 0).await;
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_busy_connection_is_closed_after_the_max_lifetime() {
+        use tokio::io::AsyncReadExt;
+
+        let limits = ConnectionLimits {
+            idle_timeout: READ_TIMEOUT,
+            max_lifetime: Duration::from_millis(500),
+        };
+        let (server, server_addr, active) = running_server(limits).await;
+
+        // Keeps sending a header line byte by byte, so it is never idle
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let mut stream = connector().connect("AirWin", stream).await.unwrap();
+        stream.write_all(b"POST /Upload HTTP/1.1\r\nX-Slow: ").await.unwrap();
+        wait_for_connections(&active, 1).await;
+
+        let started = Instant::now();
+        let mut byte = [0u8; 1];
+        loop {
+            assert!(started.elapsed() < Duration::from_secs(5), "the connection outlived its maximum lifetime");
+            if stream.write_all(b"a").await.is_err() {
+                break;
+            }
+            if let Ok(read) = tokio::time::timeout(Duration::from_millis(50), stream.read(&mut byte)).await {
+                assert!(matches!(read, Ok(0) | Err(_)), "unexpected response");
+                break;
+            }
+        }
+        assert!(started.elapsed() < READ_TIMEOUT);
+        wait_for_connections(&active, 0).await;
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_oversized_headers_are_rejected() {
+        let (server, server_addr, active) = running_server(ConnectionLimits::default()).await;
+
+        // Headers that never end, one byte past the limit
+        let mut request = b"POST /Upload HTTP/1.1\r\n".to_vec();
+        while request.len() <= MAX_HEADER_SIZE {
+            request.extend_from_slice(b"X-Pad: aaaaaaaaaaaaaaaa\r\n");
+        }
+        request.truncate(MAX_HEADER_SIZE + 1);
+        let response = exchange(server_addr, &request).await;
+        assert!(response.starts_with("HTTP/1.1 431"), "{}", response);
+        wait_for_connections(&active, 0).await;
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_resumed_upload_reconstructs_the_file() {
+        let (events, mut incoming) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+        let receive_dir = std::env::temp_dir().join(format!("airwin_resume_{}", uuid::Uuid::new_v4().simple()));
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let dir = receive_dir.clone();
+        // One connection per request, handled in order
+        let server = tokio::spawn(async move {
+            let gate = ReceiveGate::new(VisibilitySettings::default());
+            let context = context(events, dir, PeerFilter::default(), gate, READ_TIMEOUT);
+            let mut results = Vec::new();
+            for _ in 0..4 {
+                let (stream, addr) = listener.accept().await.unwrap();
+                results.push(
+                    AirDropHttpServer::handle_connection(stream, addr, acceptor.clone(), context.clone())
+                        .await
+                        .is_ok(),
+                );
+            }
+            results
+        });
+
+        // The /Ask names the file the upload is saved as
+        let body = r#"{"SenderComputerName":"iPhone","Files":[{"FileName":"../notes.txt"}]}"#;
+        let response = exchange(server_addr, format!("POST /Ask HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        // First part: ten bytes announced, the connection drops after five
+        let mut stream = connect(server_addr).await;
+        stream
+            .write_all(b"POST /Upload HTTP/1.1\r\nX-Upload-Id: abc-1\r\nContent-Range: bytes 0-9/10\r\nContent-Length: 10\r\n\r\nhello")
+            .await
+            .unwrap();
+        stream.flush().await.unwrap();
+        drop(stream);
+
+        // Reconnection: the server reports what it kept
+        let response = exchange(server_addr, b"POST /Upload HTTP/1.1\r\nX-Upload-Id: abc-1\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 308"), "{}", response);
+        assert!(response.contains("Upload-Offset: 5\r\n"), "{}", response);
+
+        // Second part from the reported offset
+        let response = exchange(
+            server_addr,
+            b"POST /Upload HTTP/1.1\r\nX-Upload-Id: abc-1\r\nContent-Range: bytes 5-9/10\r\nContent-Length: 5\r\n\r\nworld",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        let results = server.await.unwrap();
+        assert_eq!(&results[2..], &[true, true]);
+
+        let event = incoming.recv().await.unwrap();
+        assert_eq!(event.size, 10);
+        assert_eq!(event.name, "notes.txt");
+        assert_eq!(std::fs::read(&event.path).unwrap(), b"helloworld");
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        assert!(!resumable_part_path(&receive_dir, localhost, "abc-1").exists());
+
+        let _ = std::fs::remove_dir_all(&receive_dir);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_abandoned_uploads_are_dropped() {
+        let (events, _) = broadcast::channel(INCOMING_EVENTS_CAPACITY);
+        let acceptor = TlsAcceptor::from(tls::server_config().await.unwrap());
+        let receive_dir = std::env::temp_dir().join(format!("airwin_stale_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&receive_dir).unwrap();
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let mut context = context(events, receive_dir.clone(), PeerFilter::default(), ReceiveGate::new(VisibilitySettings::default()), READ_TIMEOUT);
+        context.max_file_size = 8;
+        let server = tokio::spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            AirDropHttpServer::handle_connection(stream, addr, acceptor, context).await
+        });
+        let response = exchange(
+            server_addr,
+            b"POST /Upload HTTP/1.1\r\nX-Upload-Id: big\r\nContent-Range: bytes 0-9/10\r\nContent-Length: 10\r\n\r\n0123456789",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        server.await.unwrap().unwrap();
+
+        // Each peer has its own .part file for the same id
+        let phone = IpAddr::from([192, 168, 1, 20]);
+        let laptop = IpAddr::from([192, 168, 1, 30]);
+        let part = resumable_part_path(&receive_dir, phone, "abc");
+        assert_ne!(part, resumable_part_path(&receive_dir, laptop, "abc"));
+        assert_eq!(part, resumable_part_path(&receive_dir, "::ffff:192.168.1.20".parse().unwrap(), "abc"));
+
+        // Only the .part files of resumable uploads are cleaned up
+        std::fs::write(&part, b"hello").unwrap();
+        std::fs::write(receive_dir.join("photo.jpg.part"), b"hello").unwrap();
+        remove_stale_uploads(&receive_dir, Duration::from_secs(60)).await;
+        assert!(part.exists());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        remove_stale_uploads(&receive_dir, Duration::from_millis(10)).await;
+        assert!(!part.exists());
+        assert!(receive_dir.join("photo.jpg.part").exists());
+
+        let _ = std::fs::remove_dir_all(&receive_dir);
+    }
+
+    #[test]
+    fn test_content_range_and_upload_id_parsing() {
+        assert_eq!(parse_content_range("bytes 5-9/10"), Some((5, 10)));
+        assert_eq!(parse_content_range("bytes 9-5/10"), None);
+        assert_eq!(parse_content_range("bytes 0-10/10"), None);
+        assert_eq!(parse_content_range("bytes */10"), None);
+
+        assert!(valid_upload_id("abc-1_X"));
+        assert!(!valid_upload_id("../etc"));
+        assert!(!valid_upload_id(""));
+
+        let buffer = b"POST /Upload HTTP/1.1\r\nx-upload-id:  abc \r\n\r\nbody";
+        assert_eq!(header_value(buffer, UPLOAD_ID_HEADER).as_deref(), Some("abc"));
+        assert_eq!(header_value(buffer, "Content-Range"), None);
+    }
+
+    #[test]
+    fn test_requested_files_from_ask_body() {
+        let buffer = b"POST /Ask HTTP/1.1\r\n\r\n{\"Files\":[{\"FileName\":\"a.jpg\"},{\"FileName\":\"b.pdf\"}]}";
+        assert_eq!(requested_files(buffer), vec!["a.jpg".to_string(), "b.pdf".to_string()]);
+        assert!(requested_files(b"POST /Ask HTTP/1.1\r\n\r\nnot json").is_empty());
+    }
+
+    #[test]
+    fn test_sender_id_from_ask_body() {
+        let buffer = b"POST /Ask HTTP/1.1\r\n\r\n{\"SenderComputerName\":\"iPhone\",\"SenderID\":\"1a2b3c4d5e6f\"}";
+        assert_eq!(sender_name(buffer).as_deref(), Some("iPhone"));
+        assert_eq!(sender_id(buffer).as_deref(), Some("1a2b3c4d5e6f"));
+        assert_eq!(sender_id(b"POST /Ask HTTP/1.1\r\n\r\n{\"SenderComputerName\":\"iPhone\"}"), None);
     }
 }
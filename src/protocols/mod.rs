@@ -1,5 +1,26 @@
+pub mod adaptive_quality;
 pub mod airdrop;
 pub mod airplay;
+pub mod airplay_sender;
+pub mod airplay_stats;
 pub mod apple_records;
 pub mod http_server;
 pub mod awdl;
+pub mod capabilities;
+pub mod compression;
+pub mod cert_pins;
+pub mod connection_pool;
+pub mod cursor_overlay;
+pub mod file_conflict;
+pub mod file_name;
+pub mod frame_header;
+pub mod frame_processor;
+pub mod frame_queue;
+pub mod peer_filter;
+pub mod screen_capture;
+pub mod send_progress;
+pub mod tls;
+pub mod transfer_manager;
+pub mod transfer_method;
+pub mod transfer_protocol;
+pub mod visibility;
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Allow and deny lists for incoming AirDrop connections.
+///
+/// Entries are either IP addresses or device names (compared case-insensitively).
+/// The deny list always wins. When the allow list is not empty, only peers
+/// matching one of its entries may transfer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerFilter {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+impl PeerFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether the peer may connect.
+    ///
+    /// `name` is `None` before the handshake: then only IP entries can deny,
+    /// and a peer is let through if the allow list has names it could still match.
+    /// Call again with the advertised name once it is known.
+    pub fn permits(&self, ip: IpAddr, name: Option<&str>) -> bool {
+        if self.deny.iter().any(|entry| matches(entry, ip, name)) {
+            return false;
+        }
+        if self.allow.is_empty() {
+            return true;
+        }
+        self.allow.iter().any(|entry| matches(entry, ip, name))
+            || (name.is_none() && self.allow.iter().any(|entry| entry.parse::<IpAddr>().is_err()))
+    }
+
+    /// Whether a request may go on once its headers are read. Unlike
+    /// [`permits`](Self::permits), a request that carries no name only gets
+    /// past a non-empty allow list by IP.
+    pub fn admits(&self, ip: IpAddr, name: Option<&str>) -> bool {
+        !self.deny.iter().any(|entry| matches(entry, ip, name))
+            && (self.allow.is_empty() || self.allow.iter().any(|entry| matches(entry, ip, name)))
+    }
+
+    /// Whether the peer is on the allow list, which is what "contacts" means
+    /// for the visibility setting
+    pub fn is_contact(&self, ip: IpAddr, name: &str) -> bool {
+        self.allow.iter().any(|entry| matches(entry, ip, Some(name)))
+    }
+}
+
+fn matches(entry: &str, ip: IpAddr, name: Option<&str>) -> bool {
+    let entry = entry.trim();
+    match entry.parse::<IpAddr>() {
+        Ok(entry_ip) => entry_ip == normalize(ip),
+        Err(_) => name.is_some_and(|name| device_name(name).eq_ignore_ascii_case(entry)),
+    }
+}
+
+/// IPv4 peers reach dual-stack listeners as IPv4-mapped IPv6 addresses
+fn normalize(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// "Marco's iPhone._airdrop._tcp.local." advertises as "Marco's iPhone"
+fn device_name(name: &str) -> &str {
+    name.split("._").next().unwrap_or(name).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> PeerFilter {
+        PeerFilter {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            deny: deny.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_permits_everyone() {
+        let ip: IpAddr = "192.168.1.10".parse().unwrap();
+        assert!(PeerFilter::default().permits(ip, None));
+        assert!(PeerFilter::default().permits(ip, Some("iPhone")));
+    }
+
+    #[test]
+    fn test_deny_matches_ip_and_name() {
+        let filter = filter(&[], &["192.168.1.66", "work laptop"]);
+
+        assert!(!filter.permits("192.168.1.66".parse().unwrap(), None));
+        assert!(!filter.permits("::ffff:192.168.1.66".parse().unwrap(), None));
+        assert!(filter.permits("192.168.1.10".parse().unwrap(), None));
+        assert!(!filter.permits("192.168.1.10".parse().unwrap(), Some("Work Laptop._airdrop._tcp.local.")));
+    }
+
+    #[test]
+    fn test_allow_only_listed_peers() {
+        let filter = filter(&["192.168.1.20", "My iPad"], &[]);
+
+        assert!(filter.permits("192.168.1.20".parse().unwrap(), Some("Unknown")));
+        assert!(filter.permits("192.168.1.30".parse().unwrap(), Some("my ipad")));
+        assert!(!filter.permits("192.168.1.30".parse().unwrap(), Some("Someone's iPhone")));
+
+        // Before the handshake the name is unknown: decided once it is
+        assert!(filter.permits("192.168.1.30".parse().unwrap(), None));
+    }
+
+    #[test]
+    fn test_allow_by_ip_only_rejects_before_handshake() {
+        let filter = filter(&["192.168.1.20"], &[]);
+
+        assert!(filter.permits("192.168.1.20".parse().unwrap(), None));
+        assert!(!filter.permits("192.168.1.30".parse().unwrap(), None));
+    }
+
+    #[test]
+    fn test_requests_without_a_name_need_an_ip_entry() {
+        let filter = filter(&["192.168.1.20", "My iPad"], &[]);
+
+        assert!(!filter.admits("192.168.1.30".parse().unwrap(), None));
+        assert!(filter.admits("192.168.1.30".parse().unwrap(), Some("My iPad")));
+        assert!(filter.admits("192.168.1.20".parse().unwrap(), None));
+        assert!(PeerFilter::default().admits("192.168.1.30".parse().unwrap(), None));
+    }
+
+    #[test]
+    fn test_contacts_are_the_allow_list() {
+        let filter = filter(&["192.168.1.20", "My iPad"], &[]);
+
+        assert!(filter.is_contact("192.168.1.20".parse().unwrap(), "Unknown"));
+        assert!(filter.is_contact("192.168.1.30".parse().unwrap(), "My iPad._airdrop._tcp.local."));
+        assert!(!filter.is_contact("192.168.1.30".parse().unwrap(), "Someone's iPhone"));
+        assert!(!PeerFilter::default().is_contact("192.168.1.20".parse().unwrap(), "My iPad"));
+    }
+
+    #[test]
+    fn test_deny_wins_over_allow() {
+        let filter = filter(&["192.168.1.20"], &["192.168.1.20"]);
+        assert!(!filter.permits("192.168.1.20".parse().unwrap(), None));
+    }
+}
@@ -0,0 +1,303 @@
+//! Validation of captured screen regions and pixel buffers.
+//!
+//! The GDI calls report sizes as `i32` and can return zero or negative
+//! values, for example while the display mode changes. Everything is checked
+//! here before a buffer is turned into an image, so a bad capture is a
+//! descriptive error instead of a `None` from `ImageBuffer::from_raw`.
+//!
+//! Over Remote Desktop, or in a session without an interactive desktop, GDI
+//! either hands out no screen DC or copies only black pixels. Both end the
+//! stream with [`AirWinError::CaptureUnavailable`] and a hint for the
+//! session type, instead of sending black frames.
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Graphics::Gdi::{BITMAPINFO, BITMAPINFOHEADER, BI_RGB, HDC};
+
+use crate::utils::AirWinError;
+
+/// Bytes per captured pixel (BGRA/RGBA)
+pub const BYTES_PER_PIXEL: usize = 4;
+
+/// Consecutive black captures after which the session is assumed to have no
+/// screen to capture, about two seconds at 30 fps. A screen that really is
+/// black for longer, like a dark video in full screen, stops the stream too.
+pub const BLACK_FRAMES_BEFORE_UNAVAILABLE: u32 = 60;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Invalid capture size {width}x{height}")]
+    InvalidSize { width: i64, height: i64 },
+    #[error("Capture region {0:?} is outside the {1}x{2} screen")]
+    OutsideScreen(CaptureRegion, u32, u32),
+    #[error("Capture buffer has {actual} bytes, expected {expected} for {width}x{height}")]
+    BufferLength {
+        width: u32,
+        height: u32,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+/// Rectangle of the screen to capture, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl CaptureRegion {
+    /// The whole screen, from the sizes reported by `GetSystemMetrics`
+    pub fn screen(width: i32, height: i32) -> Result<Self, CaptureError> {
+        if width <= 0 || height <= 0 {
+            return Err(CaptureError::InvalidSize { width: width.into(), height: height.into() });
+        }
+        Ok(Self { x: 0, y: 0, width, height })
+    }
+
+    /// The part of this region inside a `screen_width` x `screen_height`
+    /// screen. Fails if nothing is left.
+    pub fn clamp_to(self, screen_width: u32, screen_height: u32) -> Result<Self, CaptureError> {
+        let outside = CaptureError::OutsideScreen(self, screen_width, screen_height);
+        let left = i64::from(self.x).max(0);
+        let top = i64::from(self.y).max(0);
+        let right = (i64::from(self.x) + i64::from(self.width)).min(screen_width.into());
+        let bottom = (i64::from(self.y) + i64::from(self.height)).min(screen_height.into());
+        if right <= left || bottom <= top {
+            return Err(outside);
+        }
+        // Within the screen, so every value fits in an i32
+        Ok(Self {
+            x: left as i32,
+            y: top as i32,
+            width: (right - left) as i32,
+            height: (bottom - top) as i32,
+        })
+    }
+
+    /// Bytes of a buffer holding this region
+    pub fn buffer_len(&self) -> Result<usize, CaptureError> {
+        let invalid = CaptureError::InvalidSize { width: self.width.into(), height: self.height.into() };
+        let width = usize::try_from(self.width).map_err(|_| invalid.clone())?;
+        let height = usize::try_from(self.height).map_err(|_| invalid.clone())?;
+        width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(BYTES_PER_PIXEL))
+            .filter(|len| *len > 0)
+            .ok_or(invalid)
+    }
+}
+
+/// Region to capture on a `screen_width` x `screen_height` screen: the
+/// selected one clamped to the screen, or the whole screen
+pub fn capture_region(
+    selected: Option<CaptureRegion>,
+    screen_width: i32,
+    screen_height: i32,
+) -> Result<CaptureRegion, CaptureError> {
+    let screen = CaptureRegion::screen(screen_width, screen_height)?;
+    match selected {
+        Some(region) => region.clamp_to(screen.width as u32, screen.height as u32),
+        None => Ok(screen),
+    }
+}
+
+/// Image of a captured `width` x `height` buffer, after checking its length
+pub fn rgba_image(width: u32, height: u32, buffer: Vec<u8>) -> Result<RgbaImage, CaptureError> {
+    let expected = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(BYTES_PER_PIXEL))
+        .filter(|len| *len > 0)
+        .ok_or(CaptureError::InvalidSize { width: width.into(), height: height.into() })?;
+    let actual = buffer.len();
+    if actual != expected {
+        return Err(CaptureError::BufferLength { width, height, expected, actual });
+    }
+    ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, buffer)
+        .ok_or(CaptureError::BufferLength { width, height, expected, actual })
+}
+
+/// Kind of Windows session AirWin runs in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionKind {
+    /// Signed in at the physical console
+    Console,
+    /// Remote Desktop, where GDI often can't read the screen
+    Remote,
+}
+
+impl SessionKind {
+    /// The current session, from `GetSystemMetrics(SM_REMOTESESSION)`
+    pub fn current() -> Self {
+        use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_REMOTESESSION};
+        // SAFETY: GetSystemMetrics has no preconditions
+        if unsafe { GetSystemMetrics(SM_REMOTESESSION) } != 0 {
+            SessionKind::Remote
+        } else {
+            SessionKind::Console
+        }
+    }
+
+    /// What the user can do about a failed capture in this session
+    pub fn hint(self) -> &'static str {
+        match self {
+            SessionKind::Remote => {
+                "screen capture isn't available over Remote Desktop; run AirWin in the session at the physical console"
+            }
+            SessionKind::Console => {
+                "screen capture isn't available in this session; it needs an unlocked, interactive desktop"
+            }
+        }
+    }
+}
+
+/// The error ending a stream whose screen can't be read, with the hint for `session`
+pub fn unavailable(problem: &str, session: SessionKind) -> AirWinError {
+    AirWinError::CaptureUnavailable(format!("{} ({})", problem, session.hint()))
+}
+
+/// `dc` if GDI handed out a usable screen DC. Headless sessions and some
+/// Remote Desktop configurations get a null one.
+pub fn screen_dc(dc: HDC, session: SessionKind) -> Result<HDC, AirWinError> {
+    if dc.is_invalid() {
+        return Err(unavailable("no screen device context", session));
+    }
+    Ok(dc)
+}
+
+/// Header asking `GetDIBits` for 32-bit pixels of a `width` x `height`
+/// bitmap, rows from the top like the image buffer
+pub fn bitmap_info(width: i32, height: i32) -> BITMAPINFO {
+    BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: width,
+            // Negative for rows from the top
+            biHeight: -height,
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Turn the BGRA pixels returned by `GetDIBits` into opaque RGBA in place
+pub fn bgra_to_rgba(buffer: &mut [u8]) {
+    for pixel in buffer.chunks_exact_mut(BYTES_PER_PIXEL) {
+        pixel.swap(0, 2);
+        pixel[3] = u8::MAX;
+    }
+}
+
+/// Counts consecutive captures without a single lit pixel
+#[derive(Clone, Debug, Default)]
+pub struct BlackFrames {
+    consecutive: u32,
+}
+
+impl BlackFrames {
+    /// Record a captured buffer. Fails once [`BLACK_FRAMES_BEFORE_UNAVAILABLE`]
+    /// black captures came in a row.
+    pub fn record(&mut self, buffer: &[u8], session: SessionKind) -> Result<(), AirWinError> {
+        let black = buffer
+            .chunks_exact(BYTES_PER_PIXEL)
+            .all(|pixel| pixel[..3].iter().all(|channel| *channel == 0));
+        self.consecutive = if black { self.consecutive + 1 } else { 0 };
+        if self.consecutive >= BLACK_FRAMES_BEFORE_UNAVAILABLE {
+            return Err(unavailable("every captured frame is black", session));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatched_buffer_is_a_clean_error() {
+        assert_eq!(
+            rgba_image(3, 3, vec![0; 3 * 3 * 4 - 1]).unwrap_err(),
+            CaptureError::BufferLength { width: 3, height: 3, expected: 36, actual: 35 }
+        );
+        assert!(matches!(rgba_image(0, 1080, Vec::new()), Err(CaptureError::InvalidSize { .. })));
+        assert!(matches!(rgba_image(u32::MAX, u32::MAX, Vec::new()), Err(CaptureError::InvalidSize { .. })));
+
+        let image = rgba_image(3, 3, vec![0; 36]).unwrap();
+        assert_eq!(image.dimensions(), (3, 3));
+    }
+
+    #[test]
+    fn test_invalid_dc_is_capture_unavailable() {
+        for session in [SessionKind::Console, SessionKind::Remote] {
+            let error = screen_dc(HDC(0), session).unwrap_err();
+            assert!(matches!(error, AirWinError::CaptureUnavailable(_)), "{}", error);
+            assert!(error.to_string().contains(session.hint()));
+        }
+        assert!(screen_dc(HDC(1), SessionKind::Console).is_ok());
+        assert!(unavailable("x", SessionKind::Remote).to_string().contains("Remote Desktop"));
+    }
+
+    #[test]
+    fn test_black_frames_end_the_stream_only_when_consecutive() {
+        let black = vec![0, 0, 0, 255].repeat(4);
+        let mut lit = black.clone();
+        lit[5] = 1;
+
+        let mut frames = BlackFrames::default();
+        for _ in 1..BLACK_FRAMES_BEFORE_UNAVAILABLE {
+            frames.record(&black, SessionKind::Console).unwrap();
+        }
+        frames.record(&lit, SessionKind::Console).unwrap();
+        for _ in 1..BLACK_FRAMES_BEFORE_UNAVAILABLE {
+            frames.record(&black, SessionKind::Remote).unwrap();
+        }
+        let error = frames.record(&black, SessionKind::Remote).unwrap_err();
+        assert!(matches!(error, AirWinError::CaptureUnavailable(_)));
+
+        let mut pixels = vec![1, 2, 3, 0];
+        bgra_to_rgba(&mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 255]);
+    }
+
+    #[test]
+    fn test_regions_are_clamped_to_the_screen() {
+        assert!(CaptureRegion::screen(0, 1080).is_err());
+        assert!(CaptureRegion::screen(1920, -1).is_err());
+
+        let screen = CaptureRegion::screen(1921, 1081).unwrap();
+        assert_eq!(screen.buffer_len(), Ok(1921 * 1081 * 4));
+
+        let overlapping = CaptureRegion { x: -10, y: 1000, width: 100, height: 200 };
+        assert_eq!(
+            overlapping.clamp_to(1920, 1080),
+            Ok(CaptureRegion { x: 0, y: 1000, width: 90, height: 80 })
+        );
+
+        let outside = CaptureRegion { x: 2000, y: 0, width: 100, height: 100 };
+        assert!(matches!(outside.clamp_to(1920, 1080), Err(CaptureError::OutsideScreen(..))));
+        let huge = CaptureRegion { x: i32::MAX, y: i32::MAX, width: i32::MAX, height: i32::MAX };
+        assert!(huge.clamp_to(1920, 1080).is_err());
+    }
+
+    #[test]
+    fn test_selected_region_is_clamped() {
+        assert_eq!(capture_region(None, 1920, 1080), Ok(CaptureRegion { x: 0, y: 0, width: 1920, height: 1080 }));
+
+        // A window area partly off the right and bottom edges
+        let window = CaptureRegion { x: 1600, y: 900, width: 800, height: 600 };
+        assert_eq!(
+            capture_region(Some(window), 1920, 1080),
+            Ok(CaptureRegion { x: 1600, y: 900, width: 320, height: 180 })
+        );
+
+        // Saved for a larger monitor, entirely outside this one
+        let other_monitor = CaptureRegion { x: 2560, y: 0, width: 640, height: 480 };
+        assert!(matches!(capture_region(Some(other_monitor), 1920, 1080), Err(CaptureError::OutsideScreen(..))));
+        assert!(capture_region(Some(window), 0, 0).is_err());
+    }
+}
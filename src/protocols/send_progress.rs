@@ -0,0 +1,120 @@
+//! Progress of a send of one or more files.
+//!
+//! A send reports the file being transferred, how far along that file is
+//! and how far along the whole send is. Overall progress is computed from
+//! the bytes sent across all files, so a large file moves the overall bar
+//! more than a small one. It only reaches 100% once the last byte of the
+//! last file is sent.
+
+use serde::{Deserialize, Serialize};
+
+/// Snapshot of a send, percentages from 0 to 100
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SendProgress {
+    /// Position of the current file in the send, from 0
+    pub current_file_index: usize,
+    pub file_count: usize,
+    pub current_file_name: String,
+    pub current_file_progress: f32,
+    pub overall_progress: f32,
+}
+
+impl SendProgress {
+    /// Whether the send has more than one file and needs the per-file details
+    pub fn is_multi_file(&self) -> bool {
+        self.file_count > 1
+    }
+}
+
+/// Turns the bytes sent of each file into [`SendProgress`]
+#[derive(Clone, Debug)]
+pub struct ProgressTracker {
+    /// Name and size of every file, in sending order
+    files: Vec<(String, u64)>,
+    total: u64,
+}
+
+impl ProgressTracker {
+    pub fn new(files: Vec<(String, u64)>) -> Self {
+        let total = files.iter().map(|(_, size)| size).sum();
+        Self { files, total }
+    }
+
+    /// Progress once `sent` bytes of the file at `index` are sent
+    pub fn update(&self, index: usize, sent: u64) -> SendProgress {
+        let (name, size) = self.files.get(index).cloned().unwrap_or_default();
+        let sent = sent.min(size);
+        let before: u64 = self.files[..index.min(self.files.len())].iter().map(|(_, size)| size).sum();
+
+        let overall = if self.total == 0 {
+            // Only empty files: each one counts the same
+            let done = index as f64 + if sent == size { 1.0 } else { 0.0 };
+            done / self.files.len().max(1) as f64
+        } else {
+            (before + sent) as f64 / self.total as f64
+        };
+        let last_byte_sent = index + 1 >= self.files.len() && sent == size;
+
+        SendProgress {
+            current_file_index: index,
+            file_count: self.files.len(),
+            current_file_name: name,
+            current_file_progress: percent(sent, size),
+            // Rounding must not show 100% while bytes are still missing
+            overall_progress: if last_byte_sent { 100.0 } else { (overall * 100.0).min(99.9) as f32 },
+        }
+    }
+}
+
+fn percent(sent: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 100.0;
+    }
+    (sent as f64 / total as f64 * 100.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overall_reaches_100_only_after_the_last_file() {
+        let tracker = ProgressTracker::new(vec![("big.bin".to_string(), 3_000), ("small.txt".to_string(), 1_000)]);
+
+        let halfway = tracker.update(0, 1_500);
+        assert_eq!(halfway.current_file_progress, 50.0);
+        assert_eq!(halfway.overall_progress, 37.5);
+
+        // The first file is done, the send isn't
+        let first_done = tracker.update(0, 3_000);
+        assert_eq!(first_done.current_file_progress, 100.0);
+        assert_eq!(first_done.overall_progress, 75.0);
+
+        let second_started = tracker.update(1, 0);
+        assert_eq!(second_started.current_file_index, 1);
+        assert_eq!(second_started.current_file_name, "small.txt");
+        assert_eq!(second_started.overall_progress, 75.0);
+
+        assert!(tracker.update(1, 999).overall_progress < 100.0);
+        let done = tracker.update(1, 1_000);
+        assert_eq!(done.overall_progress, 100.0);
+        assert_eq!(done.current_file_progress, 100.0);
+        assert!(done.is_multi_file());
+    }
+
+    #[test]
+    fn test_large_sends_never_round_to_100_early() {
+        let size = 4 * 1024 * 1024 * 1024;
+        let tracker = ProgressTracker::new(vec![("a".to_string(), size), ("b".to_string(), size)]);
+        assert!(tracker.update(1, size - 1).overall_progress < 100.0);
+        assert_eq!(tracker.update(1, size).overall_progress, 100.0);
+    }
+
+    #[test]
+    fn test_empty_files_count_equally() {
+        let tracker = ProgressTracker::new(vec![("a".to_string(), 0), ("b".to_string(), 0)]);
+        assert_eq!(tracker.update(0, 0).overall_progress, 50.0);
+        assert_eq!(tracker.update(1, 0).overall_progress, 100.0);
+        assert!(!ProgressTracker::new(vec![("a".to_string(), 0)]).update(0, 0).is_multi_file());
+    }
+}
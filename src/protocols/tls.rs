@@ -0,0 +1,289 @@
+//! Certificates and TLS connections for AirDrop transfers.
+//!
+//! Every TLS stack in AirWin gets its certificates from here. The fallback
+//! receiver and the HTTPS server present the same certificate, stored in the
+//! config directory. Outgoing transfers present one generated once per run.
+//!
+//! Outgoing connections use the stack chosen with the `tls_backend` setting,
+//! native-tls or rustls; "TLS Backend" in `docs/DOCUMENTATION.md` explains
+//! how they differ.
+
+use anyhow::Result;
+use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_native_tls::native_tls;
+use tokio_rustls::rustls::{self, client::{ServerCertVerified, ServerCertVerifier}, ServerName};
+use tracing::{info, warn};
+
+use super::airdrop::tls_error;
+use crate::utils::AirWinResult;
+
+/// Certificate and key of the receivers, in the config directory
+const RECEIVER_CERT_FILE: &str = "airdrop_cert.pem";
+const RECEIVER_KEY_FILE: &str = "airdrop_key.pem";
+
+/// TLS stack used for outgoing transfers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsBackend {
+    /// The system stack, SChannel on Windows
+    #[default]
+    NativeTls,
+    Rustls,
+}
+
+/// Self-signed certificate and PKCS#8 key, both PEM encoded
+#[derive(Clone, Debug)]
+pub struct CertificatePem {
+    pub cert: String,
+    pub key: String,
+}
+
+impl CertificatePem {
+    fn from_params(params: CertificateParams) -> AirWinResult<Self> {
+        let cert = Certificate::from_params(params).map_err(tls_error)?;
+        Ok(Self {
+            cert: cert.serialize_pem().map_err(tls_error)?,
+            key: cert.serialize_private_key_pem(),
+        })
+    }
+
+    /// New certificate for the name `AirWin`
+    pub fn generate() -> AirWinResult<Self> {
+        info!("Generating new TLS certificate...");
+        let mut params = CertificateParams::new(vec!["AirWin".to_string()]);
+        params.distinguished_name = DistinguishedName::new();
+        params.distinguished_name.push(DnType::CommonName, "AirWin");
+        params.distinguished_name.push(DnType::OrganizationName, "AirWin");
+        params.distinguished_name.push(DnType::CountryName, "US");
+        Self::from_params(params)
+    }
+
+    /// The certificate as a native-tls identity
+    pub fn native_identity(&self) -> AirWinResult<native_tls::Identity> {
+        native_tls::Identity::from_pkcs8(self.cert.as_bytes(), self.key.as_bytes()).map_err(tls_error)
+    }
+
+    /// The certificate chain and key as rustls expects them
+    pub fn rustls_chain(&self) -> AirWinResult<(Vec<rustls::Certificate>, rustls::PrivateKey)> {
+        let cert = pem::parse(&self.cert).map_err(tls_error)?;
+        let key = pem::parse(&self.key).map_err(tls_error)?;
+        Ok((
+            vec![rustls::Certificate(cert.contents().to_vec())],
+            rustls::PrivateKey(key.contents().to_vec()),
+        ))
+    }
+
+    /// SHA-256 fingerprint of the certificate, in the form peers pin it
+    pub fn fingerprint(&self) -> AirWinResult<String> {
+        let cert = pem::parse(&self.cert).map_err(tls_error)?;
+        Ok(super::cert_pins::fingerprint(cert.contents()))
+    }
+}
+
+/// Certificate presented to receivers, generated once per run
+pub fn sender_certificate() -> AirWinResult<&'static CertificatePem> {
+    static SENDER_CERTIFICATE: OnceLock<CertificatePem> = OnceLock::new();
+    if let Some(certificate) = SENDER_CERTIFICATE.get() {
+        return Ok(certificate);
+    }
+    let certificate = CertificatePem::generate()?;
+    Ok(SENDER_CERTIFICATE.get_or_init(|| certificate))
+}
+
+/// Certificate of the receivers, kept on disk so that peers pinning it see
+/// the same one across connections, servers and restarts. Loaded once per run.
+pub async fn receiver_certificate() -> AirWinResult<&'static CertificatePem> {
+    static RECEIVER_CERTIFICATE: OnceLock<CertificatePem> = OnceLock::new();
+    if let Some(certificate) = RECEIVER_CERTIFICATE.get() {
+        return Ok(certificate);
+    }
+    let certificate = load_receiver_certificate().await?;
+    Ok(RECEIVER_CERTIFICATE.get_or_init(|| certificate))
+}
+
+async fn load_receiver_certificate() -> AirWinResult<CertificatePem> {
+    let dir = crate::utils::config::config_dir();
+    let cert_path = dir.join(RECEIVER_CERT_FILE);
+    let key_path = dir.join(RECEIVER_KEY_FILE);
+
+    if let (Ok(cert), Ok(key)) = (
+        tokio::fs::read_to_string(&cert_path).await,
+        tokio::fs::read_to_string(&key_path).await,
+    ) {
+        let stored = CertificatePem { cert, key };
+        // Both stacks must accept it, since either may serve it
+        match stored.native_identity().and_then(|_| stored.rustls_chain()) {
+            Ok(_) => return Ok(stored),
+            Err(e) => warn!("Invalid stored AirDrop certificate, generating a new one: {}", e),
+        }
+    }
+
+    let certificate = CertificatePem::generate()?;
+    let saved = async {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&cert_path, &certificate.cert).await?;
+        tokio::fs::write(&key_path, &certificate.key).await
+    };
+    if let Err(e) = saved.await {
+        warn!("Failed to store AirDrop certificate in {:?}: {}", dir, e);
+    }
+    Ok(certificate)
+}
+
+/// Acceptor of the fallback receiver
+pub async fn native_acceptor() -> AirWinResult<tokio_native_tls::TlsAcceptor> {
+    let identity = receiver_certificate().await?.native_identity()?;
+    let acceptor = native_tls::TlsAcceptor::new(identity).map_err(tls_error)?;
+    Ok(acceptor.into())
+}
+
+/// Configuration of the HTTPS server
+pub async fn server_config() -> AirWinResult<Arc<rustls::ServerConfig>> {
+    let (chain, key) = receiver_certificate().await?.rustls_chain()?;
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(tls_error)?;
+    Ok(Arc::new(config))
+}
+
+/// Stream of an established connection, whichever stack set it up
+pub trait TlsStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> TlsStream for S {}
+
+/// Client side of an outgoing transfer
+pub enum Connector {
+    Native(tokio_native_tls::TlsConnector),
+    Rustls(tokio_rustls::TlsConnector),
+}
+
+impl Connector {
+    /// Connector presenting `certificate`. With `accept_self_signed` any peer
+    /// certificate is accepted, otherwise it must chain to a trusted root.
+    pub fn new(backend: TlsBackend, certificate: &CertificatePem, accept_self_signed: bool) -> AirWinResult<Self> {
+        match backend {
+            TlsBackend::NativeTls => {
+                let connector = native_tls::TlsConnector::builder()
+                    .identity(certificate.native_identity()?)
+                    .danger_accept_invalid_certs(accept_self_signed)
+                    .danger_accept_invalid_hostnames(accept_self_signed)
+                    .build()
+                    .map_err(tls_error)?;
+                Ok(Connector::Native(connector.into()))
+            }
+            TlsBackend::Rustls => {
+                let (chain, key) = certificate.rustls_chain()?;
+                let builder = rustls::ClientConfig::builder().with_safe_defaults();
+                let config = if accept_self_signed {
+                    builder
+                        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+                        .with_single_cert(chain, key)
+                } else {
+                    builder.with_root_certificates(system_roots()).with_single_cert(chain, key)
+                }
+                .map_err(tls_error)?;
+                Ok(Connector::Rustls(Arc::new(config).into()))
+            }
+        }
+    }
+
+    /// Run the handshake, expecting a certificate for `domain`. Returns the
+    /// stream and the DER certificate the peer presented.
+    pub async fn connect(&self, domain: &str, stream: TcpStream) -> Result<(Box<dyn TlsStream>, Option<Vec<u8>>)> {
+        match self {
+            Connector::Native(connector) => {
+                let stream = connector.connect(domain, stream).await?;
+                let peer_certificate = match stream.get_ref().peer_certificate()? {
+                    Some(cert) => Some(cert.to_der()?),
+                    None => None,
+                };
+                Ok((Box::new(stream), peer_certificate))
+            }
+            Connector::Rustls(connector) => {
+                let name = ServerName::try_from(domain).map_err(tls_error)?;
+                let stream = connector.connect(name, stream).await?;
+                let peer_certificate = stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|chain| chain.first())
+                    .map(|cert| cert.0.clone());
+                Ok((Box::new(stream), peer_certificate))
+            }
+        }
+    }
+}
+
+/// Roots trusted by the system, for rustls
+fn system_roots() -> rustls::RootCertStore {
+    let mut roots = rustls::RootCertStore::empty();
+    match rustls_native_certs::load_native_certs() {
+        Ok(certs) => {
+            let certs: Vec<Vec<u8>> = certs.into_iter().map(|cert| cert.0).collect();
+            roots.add_parsable_certificates(&certs);
+        }
+        Err(e) => warn!("Failed to load the system root certificates: {}", e),
+    }
+    roots
+}
+
+/// Peers present self-signed certificates; they are checked against the
+/// pins after the handshake instead
+struct AcceptAnyCertificate;
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::airdrop::is_tls_error;
+    use crate::utils::AirWinError;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_malformed_certificate_is_a_tls_error() {
+        // rcgen can't generate RSA key pairs, so these parameters can't produce a certificate
+        let mut params = CertificateParams::new(vec!["AirWin".to_string()]);
+        params.alg = &rcgen::PKCS_RSA_SHA256;
+        let error = CertificatePem::from_params(params).unwrap_err();
+        assert!(matches!(error, AirWinError::TlsError(_)), "{}", error);
+        let error: anyhow::Error = error.into();
+        assert!(is_tls_error(&error));
+
+        let malformed = CertificatePem {
+            cert: "not a certificate".to_string(),
+            key: "not a key".to_string(),
+        };
+        let error = malformed.native_identity().unwrap_err();
+        assert!(matches!(error, AirWinError::TlsError(_)), "{}", error);
+        assert!(matches!(malformed.rustls_chain(), Err(AirWinError::TlsError(_))));
+        assert!(!is_tls_error(&anyhow!("connection refused")));
+    }
+
+    #[test]
+    fn test_generated_certificate_works_with_both_backends() {
+        let certificate = CertificatePem::generate().unwrap();
+        for backend in [TlsBackend::NativeTls, TlsBackend::Rustls] {
+            assert!(Connector::new(backend, &certificate, true).is_ok(), "{:?}", backend);
+        }
+    }
+}
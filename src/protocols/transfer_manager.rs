@@ -0,0 +1,324 @@
+//! Queue of outgoing files.
+//!
+//! Files are queued before they are sent and removed once their transfer
+//! finishes, successfully or not. The queue is saved to `send_queue.json` in
+//! the config directory, so sends interrupted by closing AirWin can be
+//! resumed on the next launch. Restored sends wait until the user resumes or
+//! discards them; files queued meanwhile start as usual.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::utils::config::config_dir;
+
+const QUEUE_FILE: &str = "send_queue.json";
+
+/// How many transfers may run at the same time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferLimits {
+    /// Transfers running at once, across all peers
+    pub max_concurrent: usize,
+    /// Transfers running at once to the same peer
+    pub max_per_peer: usize,
+}
+
+impl Default for TransferLimits {
+    fn default() -> Self {
+        Self { max_concurrent: 2, max_per_peer: 1 }
+    }
+}
+
+impl TransferLimits {
+    pub fn new(max_concurrent: usize, max_per_peer: usize) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            max_per_peer: max_per_peer.max(1),
+        }
+    }
+}
+
+/// A file waiting to be sent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedSend {
+    pub id: u64,
+    pub path: PathBuf,
+    pub mime_type: String,
+    /// Name of the target device
+    pub target: String,
+    /// Address of the target when the file was queued
+    #[serde(default)]
+    pub addr: Option<SocketAddr>,
+}
+
+impl QueuedSend {
+    /// Both sends go to the same device. Devices can share a name, so the
+    /// address is compared too when both are known.
+    pub fn same_peer(&self, other: &QueuedSend) -> bool {
+        self.target == other.target
+            && match (self.addr, other.addr) {
+                (Some(a), Some(b)) => a.ip() == b.ip(),
+                _ => true,
+            }
+    }
+}
+
+/// Outgoing files, queued and running, persisted until they finish
+#[derive(Debug, Clone)]
+pub struct TransferManager {
+    path: PathBuf,
+    limits: TransferLimits,
+    queued: Vec<QueuedSend>,
+    running: Vec<QueuedSend>,
+    /// Ids of the queued sends left by the previous session, held until
+    /// they are resumed
+    restored: HashSet<u64>,
+    next_id: u64,
+}
+
+impl TransferManager {
+    /// Load the queue left by the previous session from the config directory
+    pub fn load(limits: TransferLimits) -> Self {
+        Self::load_from(config_dir().join(QUEUE_FILE), limits)
+    }
+
+    /// Load the queue from `path`, empty if missing or invalid. Sends that
+    /// were running when AirWin closed are queued again, and all of them are
+    /// held until [`TransferManager::resume_restored`].
+    pub fn load_from(path: PathBuf, limits: TransferLimits) -> Self {
+        let queued: Vec<QueuedSend> = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Invalid send queue {:?}, starting empty: {}", path, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        let next_id = queued.iter().map(|send| send.id + 1).max().unwrap_or(0);
+        let restored = queued.iter().map(|send| send.id).collect();
+
+        Self {
+            path,
+            limits,
+            queued,
+            running: Vec::new(),
+            restored,
+            next_id,
+        }
+    }
+
+    /// Change the limits. Running transfers are never stopped, the new
+    /// limits apply to the next ones started.
+    pub fn set_limits(&mut self, limits: TransferLimits) {
+        self.limits = limits;
+    }
+
+    /// Queue a file for `target`, returning its id
+    pub fn enqueue(&mut self, path: PathBuf, mime_type: String, target: String, addr: Option<SocketAddr>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queued.push(QueuedSend { id, path, mime_type, target, addr });
+        id
+    }
+
+    /// Drop the queued files that no longer exist and return them
+    pub fn remove_missing(&mut self) -> Vec<QueuedSend> {
+        let (present, missing) = std::mem::take(&mut self.queued)
+            .into_iter()
+            .partition(|send| send.path.is_file());
+        self.queued = present;
+        missing
+    }
+
+    /// Move queued files to running, within the global and per-peer limits,
+    /// and return the ones that should start now. A file waiting for its peer
+    /// doesn't hold back the files queued after it for other peers. Held
+    /// restored files never start.
+    pub fn start_next(&mut self) -> Vec<QueuedSend> {
+        let mut started = Vec::new();
+        let mut index = 0;
+        while index < self.queued.len() && self.running.len() < self.limits.max_concurrent {
+            let to_peer = self.running.iter().filter(|send| send.same_peer(&self.queued[index])).count();
+            if to_peer < self.limits.max_per_peer && !self.restored.contains(&self.queued[index].id) {
+                let send = self.queued.remove(index);
+                self.running.push(send.clone());
+                started.push(send);
+            } else {
+                index += 1;
+            }
+        }
+        started
+    }
+
+    /// Remove a finished send, whatever its outcome
+    pub fn complete(&mut self, id: u64) -> Option<QueuedSend> {
+        if let Some(index) = self.running.iter().position(|send| send.id == id) {
+            return Some(self.running.remove(index));
+        }
+        let index = self.queued.iter().position(|send| send.id == id)?;
+        Some(self.queued.remove(index))
+    }
+
+    /// Let the restored sends start with the next [`TransferManager::start_next`]
+    pub fn resume_restored(&mut self) {
+        self.restored.clear();
+    }
+
+    /// Forget the restored sends, keeping the ones queued since launch
+    pub fn discard_restored(&mut self) {
+        let restored = std::mem::take(&mut self.restored);
+        self.queued.retain(|send| !restored.contains(&send.id));
+    }
+
+    /// Files waiting for a free slot
+    pub fn queued(&self) -> &[QueuedSend] {
+        &self.queued
+    }
+
+    /// Restored files still waiting for the user to resume or discard them
+    pub fn restored(&self) -> Vec<&QueuedSend> {
+        self.queued.iter().filter(|send| self.restored.contains(&send.id)).collect()
+    }
+
+    /// Files being sent
+    pub fn running(&self) -> &[QueuedSend] {
+        &self.running
+    }
+
+    /// Save the unfinished sends, running ones first
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create send queue directory {:?}", dir))?;
+        }
+
+        let pending: Vec<_> = self.running.iter().chain(&self.queued).collect();
+        let json = serde_json::to_string_pretty(&pending)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write send queue to {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_queue_survives_a_restart() {
+        let dir = std::env::temp_dir().join(format!("airwin_queue_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files: Vec<_> = (0..4)
+            .map(|i| {
+                let file = dir.join(format!("file_{}.bin", i));
+                std::fs::write(&file, [i as u8]).unwrap();
+                file
+            })
+            .collect();
+        let path = dir.join(QUEUE_FILE);
+
+        let mut manager = TransferManager::load_from(path.clone(), TransferLimits::new(2, 2));
+        let addr = Some("192.168.1.20:8771".parse().unwrap());
+        for file in &files {
+            manager.enqueue(file.clone(), "application/octet-stream".to_string(), "iPhone".to_string(), addr);
+        }
+        let started = manager.start_next();
+        assert_eq!(started.iter().map(|send| send.id).collect::<Vec<_>>(), vec![0, 1]);
+
+        // The first send finishes, then AirWin closes with one running and two queued
+        manager.complete(0).unwrap();
+        manager.save().unwrap();
+        std::fs::remove_file(&files[3]).unwrap();
+
+        let mut restored = TransferManager::load_from(path.clone(), TransferLimits::new(2, 2));
+        assert!(restored.running().is_empty());
+        assert_eq!(restored.queued().len(), 3);
+        assert_eq!(restored.queued()[0].path, files[1]);
+        assert_eq!(restored.queued()[0].addr, addr);
+
+        let missing = restored.remove_missing();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].path, files[3]);
+
+        // Nothing restored starts before the user resumes
+        assert!(restored.start_next().is_empty());
+        restored.resume_restored();
+
+        // Resuming respects the cap, and new ids don't clash with restored ones
+        assert_eq!(restored.start_next().len(), 2);
+        assert!(restored.start_next().is_empty());
+        let id = restored.enqueue(files[0].clone(), "text/plain".to_string(), "Mac".to_string(), None);
+        assert_eq!(id, 4);
+
+        // Completed sends are cleared from the file
+        for send in restored.running().to_vec() {
+            restored.complete(send.id);
+        }
+        restored.complete(id);
+        restored.save().unwrap();
+        assert!(TransferManager::load_from(path, TransferLimits::default()).queued().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sends_to_one_peer_are_serialized() {
+        let path = std::env::temp_dir().join(format!("airwin_queue_{}.json", Uuid::new_v4().simple()));
+        let mut manager = TransferManager::load_from(path, TransferLimits::new(3, 1));
+        let iphone = Some("192.168.1.20:8771".parse().unwrap());
+        let mac = Some("192.168.1.30:8771".parse().unwrap());
+        let first = manager.enqueue("a.jpg".into(), "image/jpeg".to_string(), "iPhone".to_string(), iphone);
+        let second = manager.enqueue("b.jpg".into(), "image/jpeg".to_string(), "iPhone".to_string(), iphone);
+        let other = manager.enqueue("c.jpg".into(), "image/jpeg".to_string(), "Mac".to_string(), mac);
+
+        // The Mac doesn't wait behind the second file for the iPhone
+        let started: Vec<_> = manager.start_next().iter().map(|send| send.id).collect();
+        assert_eq!(started, vec![first, other]);
+        assert!(manager.start_next().is_empty());
+
+        // The second file to the iPhone starts only once the first is done
+        manager.complete(other);
+        assert!(manager.start_next().is_empty());
+        manager.complete(first);
+        assert_eq!(manager.start_next().iter().map(|send| send.id).collect::<Vec<_>>(), vec![second]);
+
+        // The global limit still applies across peers
+        manager.set_limits(TransferLimits::new(1, 1));
+        manager.enqueue("d.jpg".into(), "image/jpeg".to_string(), "Mac".to_string(), mac);
+        assert!(manager.start_next().is_empty());
+    }
+
+    #[test]
+    fn test_new_sends_do_not_wait_for_restored_ones() {
+        let path = std::env::temp_dir().join(format!("airwin_queue_{}.json", Uuid::new_v4().simple()));
+        let iphone = Some("192.168.1.20:8771".parse().unwrap());
+        let mut manager = TransferManager::load_from(path.clone(), TransferLimits::new(2, 1));
+        manager.enqueue("a.jpg".into(), "image/jpeg".to_string(), "iPhone".to_string(), iphone);
+        manager.save().unwrap();
+
+        let mut restored = TransferManager::load_from(path.clone(), TransferLimits::new(2, 1));
+        assert_eq!(restored.restored().len(), 1);
+        let new = restored.enqueue("b.jpg".into(), "image/jpeg".to_string(), "Mac".to_string(), None);
+        assert_eq!(restored.start_next().iter().map(|send| send.id).collect::<Vec<_>>(), vec![new]);
+
+        // Discarding drops only what the previous session left
+        restored.enqueue("c.jpg".into(), "image/jpeg".to_string(), "Mac".to_string(), None);
+        restored.discard_restored();
+        assert!(restored.restored().is_empty());
+        assert_eq!(restored.queued().len(), 1);
+        assert_eq!(restored.queued()[0].path, PathBuf::from("c.jpg"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_queue_file_starts_empty() {
+        let path = std::env::temp_dir().join(format!("airwin_queue_{}.json", Uuid::new_v4().simple()));
+        std::fs::write(&path, "non json").unwrap();
+        assert!(TransferManager::load_from(path.clone(), TransferLimits::default()).queued().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Fallback transfer through a shared folder.
+//!
+//! When direct transfers to a peer keep failing, files can be copied into a
+//! folder both machines can reach, such as an SMB share. The peer isn't
+//! contacted: a small JSON note is written next to each file and the user is
+//! told where to find it.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::airdrop::{part_path, TransferOutcome};
+use super::file_conflict::{ConflictResolver, FileConflictPolicy};
+use super::file_name::{safe_file_name, wire_name};
+
+/// Direct failures to a peer after which the shared folder is used
+pub const DEFAULT_FAILURES_BEFORE_FALLBACK: u32 = 3;
+
+/// How a file reaches the peer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferMethod {
+    /// AirDrop connection to the peer
+    Direct,
+    /// Copy into the configured shared folder
+    SharedFolder,
+}
+
+/// Picks the method for each peer, falling back to the shared folder after
+/// repeated direct failures
+#[derive(Clone, Debug)]
+pub struct MethodSelector {
+    /// Method chosen by the user, `None` to choose automatically
+    manual: Option<TransferMethod>,
+    shared_folder: Option<PathBuf>,
+    failures_before_fallback: u32,
+    failures: HashMap<String, u32>,
+}
+
+impl MethodSelector {
+    pub fn new(manual: Option<TransferMethod>, shared_folder: Option<PathBuf>, failures_before_fallback: u32) -> Self {
+        Self {
+            manual,
+            shared_folder,
+            failures_before_fallback: failures_before_fallback.max(1),
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Change the configuration, keeping the failure counts
+    pub fn configure(&mut self, manual: Option<TransferMethod>, shared_folder: Option<PathBuf>, failures_before_fallback: u32) {
+        self.manual = manual;
+        self.shared_folder = shared_folder;
+        self.failures_before_fallback = failures_before_fallback.max(1);
+    }
+
+    pub fn shared_folder(&self) -> Option<&Path> {
+        self.shared_folder.as_deref()
+    }
+
+    /// Method for the next file to `peer`. The shared folder is never chosen
+    /// while none is configured.
+    pub fn method_for(&self, peer: &str) -> TransferMethod {
+        if self.shared_folder.is_none() {
+            return TransferMethod::Direct;
+        }
+        match self.manual {
+            Some(method) => method,
+            None if self.failures.get(peer).copied().unwrap_or(0) >= self.failures_before_fallback => {
+                TransferMethod::SharedFolder
+            }
+            None => TransferMethod::Direct,
+        }
+    }
+
+    /// Record the result of a direct transfer to `peer`. Returns `true` when
+    /// this failure makes the next transfers use the shared folder.
+    pub fn record_direct(&mut self, peer: &str, succeeded: bool) -> bool {
+        if succeeded {
+            self.failures.remove(peer);
+            return false;
+        }
+        let before = self.method_for(peer);
+        *self.failures.entry(peer.to_string()).or_default() += 1;
+        before == TransferMethod::Direct && self.method_for(peer) == TransferMethod::SharedFolder
+    }
+}
+
+/// Note written next to a file left in the shared folder
+#[derive(Debug, Serialize, Deserialize)]
+struct SharedFileNote<'a> {
+    sender: &'a str,
+    recipient: &'a str,
+    file: &'a str,
+    size: u64,
+    sent_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Copy `source` into `<folder>/<recipient>/` with a `.airwin.json` note.
+/// The copy goes through a `.part` file, so a peer watching the folder never
+/// sees half a file. A file already there keeps its name and the copy gets a
+/// "name (n).ext" one.
+pub async fn copy_to_shared_folder(
+    source: &Path,
+    folder: &Path,
+    sender: &str,
+    recipient: &str,
+) -> Result<TransferOutcome> {
+    let started = Instant::now();
+    if source.file_name().is_none() {
+        return Err(anyhow!("{:?} is not a file", source));
+    }
+    let file_name = safe_file_name(&wire_name(source).name);
+    let dir = folder.join(safe_file_name(&recipient.replace(['/', '\\'], "_")));
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Shared folder {:?} is not reachable", folder))?;
+
+    let destination = ConflictResolver::new(FileConflictPolicy::KeepBoth)
+        .resolve(&dir, &file_name)
+        .await
+        .with_context(|| format!("Shared folder {:?} is not writable", folder))?;
+    let target = destination.path.clone();
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(file_name);
+    let part = part_path(&target);
+    let copied = tokio::fs::copy(source, &part).await;
+    let size = match copied {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            destination.release().await;
+            return Err(e).with_context(|| format!("Failed to copy {:?} to {:?}", source, dir));
+        }
+    };
+    if let Err(e) = tokio::fs::rename(&part, &target).await {
+        let _ = tokio::fs::remove_file(&part).await;
+        destination.release().await;
+        return Err(e.into());
+    }
+
+    let note = SharedFileNote {
+        sender,
+        recipient,
+        file: &file_name,
+        size,
+        sent_at: chrono::Utc::now(),
+    };
+    let mut note_path = target.clone().into_os_string();
+    note_path.push(".airwin.json");
+    tokio::fs::write(&note_path, serde_json::to_string_pretty(&note)?).await?;
+
+    Ok(TransferOutcome {
+        file_name,
+        total_bytes: size,
+        elapsed: started.elapsed(),
+        peer: target.display().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_falls_back_after_repeated_failures() {
+        let mut selector = MethodSelector::new(None, Some(PathBuf::from(r"\\nas\airwin")), 2);
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::Direct);
+
+        assert!(!selector.record_direct("iPhone", false));
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::Direct);
+        assert!(selector.record_direct("iPhone", false));
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::SharedFolder);
+        // Other peers are unaffected
+        assert_eq!(selector.method_for("Mac"), TransferMethod::Direct);
+
+        // A direct success, e.g. after a manual retry, resets the count
+        selector.record_direct("iPhone", true);
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::Direct);
+    }
+
+    #[test]
+    fn test_manual_choice_and_missing_folder() {
+        let mut selector = MethodSelector::new(Some(TransferMethod::SharedFolder), None, 1);
+        // Without a folder the fallback is unusable
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::Direct);
+        assert!(!selector.record_direct("iPhone", false));
+
+        selector.configure(Some(TransferMethod::SharedFolder), Some(PathBuf::from("share")), 1);
+        assert_eq!(selector.method_for("Mac"), TransferMethod::SharedFolder);
+        selector.configure(Some(TransferMethod::Direct), Some(PathBuf::from("share")), 1);
+        assert_eq!(selector.method_for("iPhone"), TransferMethod::Direct);
+    }
+
+    #[tokio::test]
+    async fn test_copy_leaves_file_and_note() {
+        let root = std::env::temp_dir().join(format!("airwin_share_{}", Uuid::new_v4().simple()));
+        let source = root.join("photo.jpg");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(&source, b"jpeg").unwrap();
+        let share = root.join("share");
+
+        let outcome = copy_to_shared_folder(&source, &share, "PC di Marco", "iPhone: Anna").await.unwrap();
+        assert_eq!(outcome.total_bytes, 4);
+
+        let dir = share.join("iPhone_ Anna");
+        assert_eq!(std::fs::read(dir.join("photo.jpg")).unwrap(), b"jpeg");
+        assert!(!dir.join("photo.jpg.part").exists());
+        let note: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("photo.jpg.airwin.json")).unwrap()).unwrap();
+        assert_eq!(note["sender"], "PC di Marco");
+        assert_eq!(note["size"], 4);
+
+        // A second copy of the same file leaves the first one in place
+        std::fs::write(&source, b"png").unwrap();
+        let outcome = copy_to_shared_folder(&source, &share, "PC di Marco", "iPhone: Anna").await.unwrap();
+        assert_eq!(outcome.file_name, "photo (1).jpg");
+        assert_eq!(std::fs::read(dir.join("photo.jpg")).unwrap(), b"jpeg");
+        assert_eq!(std::fs::read(dir.join("photo (1).jpg")).unwrap(), b"png");
+        assert!(dir.join("photo (1).jpg.airwin.json").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
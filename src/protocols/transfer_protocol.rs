@@ -0,0 +1,243 @@
+//! Extension point for the protocols files and links are sent with.
+//!
+//! Each protocol implements [`TransferProtocol`] and is added to a
+//! [`ProtocolRegistry`]. The UI only talks to the registry: it asks which
+//! actions apply to a device and hands the send to the protocol that
+//! handles it, so a new protocol (Nearby Share, for example) needs no
+//! change to the views. AirDrop is the only protocol registered so far, so
+//! without it no device offers any action.
+//!
+//! Screen mirroring is not a transfer and stays with AirPlay.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::airdrop::TransferOutcome;
+use crate::network::backend::CancelToken;
+use crate::network::discovery::DeviceDiscovery;
+use crate::network::{DeviceAction, DiscoveredDevice};
+
+/// What a send carries
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Payload {
+    Files(Vec<PathBuf>),
+    /// A single file, announced with `mime_type` instead of the detected type
+    File { path: PathBuf, mime_type: String },
+    /// A folder, sent as a single archive
+    Folder(PathBuf),
+    Link(String),
+}
+
+impl Payload {
+    /// Action the payload is sent with
+    pub fn action(&self) -> DeviceAction {
+        match self {
+            Payload::Files(_) | Payload::File { .. } | Payload::Folder(_) => DeviceAction::SendFile,
+            Payload::Link(_) => DeviceAction::SendLink,
+        }
+    }
+}
+
+/// A protocol that advertises this PC, finds peers and sends to them
+#[async_trait]
+pub trait TransferProtocol: Send + Sync {
+    /// Short name used in logs and errors
+    fn name(&self) -> &'static str;
+
+    /// Whether the protocol can reach `device` at all
+    fn can_handle(&self, device: &DiscoveredDevice) -> bool;
+
+    /// Actions offered for a device the protocol can handle
+    fn actions(&self, device: &DiscoveredDevice) -> Vec<DeviceAction>;
+
+    /// Make this PC visible to peers and ready to receive
+    async fn advertise(&self) -> Result<()>;
+
+    /// Peers the protocol can send to, found with the shared `discovery`
+    /// within `timeout` or until `cancel` fires
+    async fn discover(&self, discovery: &DeviceDiscovery, timeout: Duration, cancel: &CancelToken) -> Vec<DiscoveredDevice>;
+
+    /// Send `payload` to `device`, one outcome per file sent
+    async fn send(&self, device: &DiscoveredDevice, payload: Payload) -> Result<Vec<TransferOutcome>>;
+}
+
+/// The registered protocols, in order of preference
+#[derive(Clone, Default)]
+pub struct ProtocolRegistry {
+    protocols: Vec<Arc<dyn TransferProtocol>>,
+}
+
+impl std::fmt::Debug for ProtocolRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.protocols.iter().map(|protocol| protocol.name()))
+            .finish()
+    }
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `protocol` after the ones already registered
+    pub fn register(&mut self, protocol: Arc<dyn TransferProtocol>) {
+        self.protocols.push(protocol);
+    }
+
+    pub fn protocols(&self) -> &[Arc<dyn TransferProtocol>] {
+        &self.protocols
+    }
+
+    /// Actions any protocol offers for `device`, without duplicates
+    pub fn actions_for(&self, device: &DiscoveredDevice) -> Vec<DeviceAction> {
+        let mut actions = Vec::new();
+        for protocol in self.protocols.iter().filter(|protocol| protocol.can_handle(device)) {
+            for action in protocol.actions(device) {
+                if !actions.contains(&action) {
+                    actions.push(action);
+                }
+            }
+        }
+        actions
+    }
+
+    pub fn allows(&self, device: &DiscoveredDevice, action: DeviceAction) -> bool {
+        self.protocol_for(device, action).is_some()
+    }
+
+    /// First registered protocol offering `action` for `device`
+    pub fn protocol_for(&self, device: &DiscoveredDevice, action: DeviceAction) -> Option<&Arc<dyn TransferProtocol>> {
+        self.protocols
+            .iter()
+            .find(|protocol| protocol.can_handle(device) && protocol.actions(device).contains(&action))
+    }
+
+    /// Advertise with every protocol. One failing doesn't stop the others;
+    /// the failures are returned with the name of their protocol.
+    pub async fn advertise(&self) -> Vec<(&'static str, anyhow::Error)> {
+        let mut failures = Vec::new();
+        for protocol in &self.protocols {
+            if let Err(e) = protocol.advertise().await {
+                failures.push((protocol.name(), e));
+            }
+        }
+        failures
+    }
+
+    /// Peers found by every protocol. A device found by more than one is
+    /// listed once.
+    pub async fn discover(&self, discovery: &DeviceDiscovery, timeout: Duration, cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+        let mut devices: Vec<DiscoveredDevice> = Vec::new();
+        for protocol in &self.protocols {
+            for device in protocol.discover(discovery, timeout, cancel).await {
+                if !devices.iter().any(|known| known.same_device(&device)) {
+                    devices.push(device);
+                }
+            }
+        }
+        devices
+    }
+
+    /// Send `payload` with the first protocol offering its action
+    pub async fn send(&self, device: &DiscoveredDevice, payload: Payload) -> Result<Vec<TransferOutcome>> {
+        let protocol = self
+            .protocol_for(device, payload.action())
+            .ok_or_else(|| anyhow!("No protocol can send {:?} to {}", payload.action(), device.name))?;
+        protocol.send(device, payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::ServiceType;
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tokio::sync::Mutex;
+
+    /// Accepts links for devices of an unknown service and records them
+    #[derive(Default)]
+    struct Echo {
+        sent: Mutex<Vec<(String, Payload)>>,
+    }
+
+    #[async_trait]
+    impl TransferProtocol for Echo {
+        fn name(&self) -> &'static str {
+            "Echo"
+        }
+
+        fn can_handle(&self, device: &DiscoveredDevice) -> bool {
+            device.service_type == ServiceType::Other("_echo._tcp.local.".to_string())
+        }
+
+        fn actions(&self, _device: &DiscoveredDevice) -> Vec<DeviceAction> {
+            vec![DeviceAction::SendLink]
+        }
+
+        async fn advertise(&self) -> Result<()> {
+            Err(anyhow!("echo can't advertise"))
+        }
+
+        async fn discover(&self, _discovery: &DeviceDiscovery, _timeout: Duration, _cancel: &CancelToken) -> Vec<DiscoveredDevice> {
+            Vec::new()
+        }
+
+        async fn send(&self, device: &DiscoveredDevice, payload: Payload) -> Result<Vec<TransferOutcome>> {
+            self.sent.lock().await.push((device.name.clone(), payload));
+            Ok(Vec::new())
+        }
+    }
+
+    fn device(name: &str, service_type: ServiceType) -> DiscoveredDevice {
+        DiscoveredDevice {
+            name: name.to_string(),
+            address: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)),
+            port: 8771,
+            service_type,
+            txt_records: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_routes_actions_to_the_protocol_handling_the_device() {
+        let echo = Arc::new(Echo::default());
+        let mut registry = ProtocolRegistry::new();
+        assert!(registry.actions_for(&device("Mac", ServiceType::AirDrop)).is_empty());
+
+        registry.register(Arc::new(crate::protocols::airdrop::AirDrop::new()));
+        registry.register(echo.clone());
+
+        let mac = device("Mac", ServiceType::AirDrop);
+        assert_eq!(registry.actions_for(&mac), [DeviceAction::SendFile, DeviceAction::SendLink]);
+        assert_eq!(registry.protocol_for(&mac, DeviceAction::SendLink).unwrap().name(), "AirDrop");
+
+        let peer = device("Echo peer", ServiceType::Other("_echo._tcp.local.".to_string()));
+        assert_eq!(registry.actions_for(&peer), [DeviceAction::SendLink]);
+        assert!(!registry.allows(&peer, DeviceAction::SendFile));
+        registry.send(&peer, Payload::Link("https://example.com".to_string())).await.unwrap();
+        assert_eq!(
+            *echo.sent.lock().await,
+            [("Echo peer".to_string(), Payload::Link("https://example.com".to_string()))]
+        );
+
+        // Nothing handles a file for this peer, or anything for a TV
+        let error = registry.send(&peer, Payload::Files(vec![PathBuf::from("a.txt")])).await.unwrap_err();
+        assert!(error.to_string().contains("Echo peer"), "{}", error);
+        assert!(registry.actions_for(&device("TV", ServiceType::AirPlay)).is_empty());
+
+        // Seen only over BLE: nothing to send to until mDNS resolves it
+        let ble = DiscoveredDevice {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 0,
+            ..device("iPhone", ServiceType::AirDrop)
+        };
+        assert!(registry.actions_for(&ble).is_empty());
+
+        assert_eq!(format!("{:?}", registry), r#"["AirDrop", "Echo"]"#);
+    }
+}
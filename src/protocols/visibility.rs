@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+use tracing::{info, warn};
+
+/// How long a transfer request waits for the user before it is declined
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Who may send files to this PC.
+///
+/// AirWin has no contacts database: a "contact" is a peer on the allow list
+/// of the [`PeerFilter`](super::peer_filter::PeerFilter), matched by IP or
+/// device name.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    /// Anyone the peer filter permits
+    #[default]
+    Everyone,
+    /// Only peers on the allow list
+    ContactsOnly,
+    /// Not advertised, every request is rejected
+    ReceivingOff,
+}
+
+/// Visibility and auto-accept settings of the AirDrop receiver
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisibilitySettings {
+    pub visibility: Visibility,
+    /// With `ContactsOnly`, accept contacts that are also trusted devices
+    /// without asking. Other contacts are still asked.
+    pub auto_accept_from_contacts: bool,
+}
+
+/// Device the user chose to trust after accepting a transfer from it.
+///
+/// The id is the `SenderID` of an `/Ask` request, or the certificate
+/// fingerprint an AirWin peer sends in the fallback handshake. Neither is
+/// verified: the fingerprint is of a certificate any peer can fetch, so
+/// anyone on the network can claim it. Trust therefore only skips the
+/// prompt for a peer that is already a contact, it never lets a stranger in.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedDevice {
+    pub id: String,
+    /// Name the device had when it was trusted, shown in the settings
+    pub name: String,
+}
+
+/// What to do with a transfer request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReceiveDecision {
+    Accept,
+    /// Ask the user, decline if nobody answers
+    Prompt,
+    Reject(RejectReason),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum RejectReason {
+    #[error("Receiving is off")]
+    ReceivingOff,
+    #[error("Sender is not a contact")]
+    NotAContact,
+    #[error("Transfer declined")]
+    Declined,
+}
+
+impl VisibilitySettings {
+    /// Whether the AirDrop services are advertised over mDNS
+    pub fn advertised(&self) -> bool {
+        self.visibility != Visibility::ReceivingOff
+    }
+
+    /// Decision for a request from a peer that is or isn't on the allow list
+    /// and claims to be a trusted device. With `Everyone` transfers are
+    /// accepted and name clashes follow the conflict policy, as before
+    /// visibility was enforced.
+    pub fn decide(&self, is_contact: bool, is_trusted: bool) -> ReceiveDecision {
+        match self.visibility {
+            Visibility::ReceivingOff => ReceiveDecision::Reject(RejectReason::ReceivingOff),
+            // The trusted id is unverified, so it can't stand in for a contact
+            Visibility::ContactsOnly if !is_contact => ReceiveDecision::Reject(RejectReason::NotAContact),
+            Visibility::ContactsOnly if is_trusted && self.auto_accept_from_contacts => ReceiveDecision::Accept,
+            Visibility::ContactsOnly => ReceiveDecision::Prompt,
+            Visibility::Everyone => ReceiveDecision::Accept,
+        }
+    }
+}
+
+/// Transfer request waiting for the user's answer
+#[derive(Clone, Debug)]
+pub struct TransferPrompt {
+    pub sender: String,
+    /// Id the sender can be trusted by, if it sent one
+    pub device_id: Option<String>,
+    pub files: Vec<String>,
+    responder: Arc<Mutex<Option<oneshot::Sender<bool>>>>,
+}
+
+impl TransferPrompt {
+    /// Send the user's answer back to the receiving connection
+    pub fn respond(&self, accept: bool) {
+        if let Some(responder) = self.responder.lock().unwrap().take() {
+            let _ = responder.send(accept);
+        }
+    }
+}
+
+/// Applies the visibility settings to incoming requests, shared by the
+/// HTTPS and fallback servers
+#[derive(Clone, Debug)]
+pub struct ReceiveGate {
+    settings: Arc<Mutex<VisibilitySettings>>,
+    trusted: Arc<Mutex<Vec<TrustedDevice>>>,
+    prompts: broadcast::Sender<TransferPrompt>,
+}
+
+impl ReceiveGate {
+    pub fn new(settings: VisibilitySettings) -> Self {
+        let (prompts, _) = broadcast::channel(8);
+        Self {
+            settings: Arc::new(Mutex::new(settings)),
+            trusted: Arc::new(Mutex::new(Vec::new())),
+            prompts,
+        }
+    }
+
+    pub fn settings(&self) -> VisibilitySettings {
+        *self.settings.lock().unwrap()
+    }
+
+    pub fn set_settings(&self, settings: VisibilitySettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    pub fn set_trusted_devices(&self, devices: Vec<TrustedDevice>) {
+        *self.trusted.lock().unwrap() = devices;
+    }
+
+    fn is_trusted(&self, device_id: Option<&str>) -> bool {
+        device_id.is_some_and(|id| self.trusted.lock().unwrap().iter().any(|device| device.id == id))
+    }
+
+    /// Sender side of the prompts, used by the UI to subscribe
+    pub fn prompts(&self) -> broadcast::Sender<TransferPrompt> {
+        self.prompts.clone()
+    }
+
+    /// Decide on a request, asking the user when the settings say so
+    pub async fn admit(
+        &self,
+        sender: &str,
+        device_id: Option<&str>,
+        files: Vec<String>,
+        is_contact: bool,
+    ) -> Result<(), RejectReason> {
+        match self.settings().decide(is_contact, self.is_trusted(device_id)) {
+            ReceiveDecision::Accept => Ok(()),
+            ReceiveDecision::Reject(reason) => Err(reason),
+            ReceiveDecision::Prompt => {
+                if self.ask(sender, device_id, files).await {
+                    Ok(())
+                } else {
+                    Err(RejectReason::Declined)
+                }
+            }
+        }
+    }
+
+    async fn ask(&self, sender: &str, device_id: Option<&str>, files: Vec<String>) -> bool {
+        let (responder, answer) = oneshot::channel();
+        let prompt = TransferPrompt {
+            sender: sender.to_string(),
+            device_id: device_id.map(str::to_string),
+            files,
+            responder: Arc::new(Mutex::new(Some(responder))),
+        };
+
+        if self.prompts.send(prompt).is_err() {
+            warn!("No UI to confirm the transfer from {}, declining", sender);
+            return false;
+        }
+
+        match tokio::time::timeout(PROMPT_TIMEOUT, answer).await {
+            Ok(Ok(accept)) => {
+                info!("Transfer from {} {}", sender, if accept { "accepted" } else { "declined" });
+                accept
+            }
+            _ => {
+                warn!("No answer about the transfer from {}, declining", sender);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(visibility: Visibility, auto_accept_from_contacts: bool) -> VisibilitySettings {
+        VisibilitySettings { visibility, auto_accept_from_contacts }
+    }
+
+    #[test]
+    fn test_everyone_accepts_all_peers() {
+        for auto_accept in [false, true] {
+            let everyone = settings(Visibility::Everyone, auto_accept);
+            assert!(everyone.advertised());
+            assert_eq!(everyone.decide(false, false), ReceiveDecision::Accept);
+            assert_eq!(everyone.decide(true, false), ReceiveDecision::Accept);
+            assert_eq!(everyone.decide(false, true), ReceiveDecision::Accept);
+        }
+    }
+
+    #[test]
+    fn test_contacts_only_rejects_strangers() {
+        let asking = settings(Visibility::ContactsOnly, false);
+        assert!(asking.advertised());
+        assert_eq!(asking.decide(false, false), ReceiveDecision::Reject(RejectReason::NotAContact));
+        assert_eq!(asking.decide(true, false), ReceiveDecision::Prompt);
+        assert_eq!(asking.decide(true, true), ReceiveDecision::Prompt);
+
+        // Only trusted contacts are accepted without asking
+        let auto_accept = settings(Visibility::ContactsOnly, true);
+        assert_eq!(auto_accept.decide(false, false), ReceiveDecision::Reject(RejectReason::NotAContact));
+        assert_eq!(auto_accept.decide(true, false), ReceiveDecision::Prompt);
+        assert_eq!(auto_accept.decide(true, true), ReceiveDecision::Accept);
+
+        // A claimed trusted id doesn't make a stranger a contact
+        for contacts in [asking, auto_accept] {
+            assert_eq!(contacts.decide(false, true), ReceiveDecision::Reject(RejectReason::NotAContact));
+        }
+    }
+
+    #[test]
+    fn test_receiving_off_rejects_everyone() {
+        let off = settings(Visibility::ReceivingOff, true);
+        assert!(!off.advertised());
+        assert_eq!(off.decide(true, true), ReceiveDecision::Reject(RejectReason::ReceivingOff));
+        assert_eq!(off.decide(false, false), ReceiveDecision::Reject(RejectReason::ReceivingOff));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_uses_the_user_answer() {
+        let gate = ReceiveGate::new(settings(Visibility::ContactsOnly, false));
+        let mut prompts = gate.prompts().subscribe();
+
+        let ui = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert_eq!(prompt.sender, "iPhone");
+            assert_eq!(prompt.device_id.as_deref(), Some("1a2b3c"));
+            assert_eq!(prompt.files, vec!["photo.jpg".to_string()]);
+            prompt.respond(true);
+        });
+
+        assert_eq!(gate.admit("iPhone", Some("1a2b3c"), vec!["photo.jpg".to_string()], true).await, Ok(()));
+        ui.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prompt_without_ui_declines() {
+        let gate = ReceiveGate::new(settings(Visibility::ContactsOnly, false));
+        assert_eq!(gate.admit("iPhone", None, Vec::new(), true).await, Err(RejectReason::Declined));
+    }
+
+    #[tokio::test]
+    async fn test_trusted_device_is_accepted_and_others_are_asked() {
+        let gate = ReceiveGate::new(settings(Visibility::ContactsOnly, true));
+        gate.set_trusted_devices(vec![TrustedDevice {
+            id: "1a2b3c".to_string(),
+            name: "iPhone".to_string(),
+        }]);
+        let mut prompts = gate.prompts().subscribe();
+
+        assert_eq!(gate.admit("iPhone", Some("1a2b3c"), Vec::new(), true).await, Ok(()));
+        assert!(prompts.try_recv().is_err());
+
+        // The id alone, from a peer that isn't a contact, is not enough
+        assert_eq!(
+            gate.admit("iPhone", Some("1a2b3c"), Vec::new(), false).await,
+            Err(RejectReason::NotAContact)
+        );
+        assert!(prompts.try_recv().is_err());
+
+        // Same name, different id: the name alone doesn't make it trusted
+        let ui = tokio::spawn(async move {
+            let prompt = prompts.recv().await.unwrap();
+            assert_eq!(prompt.device_id.as_deref(), Some("4d5e6f"));
+            prompt.respond(false);
+        });
+        assert_eq!(
+            gate.admit("iPhone", Some("4d5e6f"), Vec::new(), true).await,
+            Err(RejectReason::Declined)
+        );
+        ui.await.unwrap();
+        assert_eq!(gate.admit("iPhone", None, Vec::new(), false).await, Err(RejectReason::NotAContact));
+    }
+}
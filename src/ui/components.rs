@@ -16,65 +16,91 @@ use iced::{
 
 use super::{styles, Message};
 
+/// Formatta una dimensione in byte in forma leggibile (es. "4.2 MB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Formatta il tempo trascorso in forma breve (es. "12s", "3 min")
+pub fn format_elapsed(elapsed: std::time::Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{} min", secs / 60),
+        _ => format!("{} h", secs / 3600),
+    }
+}
+
 /// Componente per il titolo principale
 pub fn title<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::XLARGE)
-        .style(styles::colors::TEXT_PRIMARY)
+        .size(styles::font_size::scaled(styles::font_size::XLARGE))
+        .style(styles::Palette::active().text_primary)
 }
 
 /// Componente per il sottotitolo
 pub fn subtitle<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::LARGE)
-        .style(styles::colors::TEXT_SECONDARY)
+        .size(styles::font_size::scaled(styles::font_size::LARGE))
+        .style(styles::Palette::active().text_secondary)
 } 
 
 /// Componente per il testo normale
 pub fn body_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::MEDIUM)
-        .style(styles::colors::TEXT_PRIMARY)
+        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+        .style(styles::Palette::active().text_primary)
 }
 
 /// Componente per il testo secondario
 pub fn secondary_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::SMALL)
-        .style(styles::colors::TEXT_SECONDARY)
+        .size(styles::font_size::scaled(styles::font_size::SMALL))
+        .style(styles::Palette::active().text_secondary)
 }
 
 /// Componente per il testo muto
 pub fn muted_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::SMALL)
-        .style(styles::colors::TEXT_MUTED)
+        .size(styles::font_size::scaled(styles::font_size::SMALL))
+        .style(styles::Palette::active().text_muted)
 }
 
 /// Componente per il testo di successo
 pub fn success_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::MEDIUM)
-        .style(styles::colors::SUCCESS)
+        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+        .style(styles::Palette::active().success)
 }
 
 /// Componente per il testo di errore
 pub fn error_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::MEDIUM)
-        .style(styles::colors::ERROR)
+        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+        .style(styles::Palette::active().error)
 }
 
 /// Componente per il testo di avviso
 pub fn warning_text<'a>(content: &str) -> Text<'a> {
     text(content)
-        .size(styles::font_size::MEDIUM)
-        .style(styles::colors::WARNING)
+        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+        .style(styles::Palette::active().warning)
 }
 
 /// Pulsante primario
 pub fn primary_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
-    button(text(content).size(styles::font_size::MEDIUM))
+    button(text(content).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
         .style(iced::theme::Button::Primary)
         .padding([styles::spacing::SMALL.0, styles::spacing::MEDIUM.0])
         .on_press(message)
@@ -82,7 +108,7 @@ pub fn primary_button<'a>(content: &str, message: Message) -> Button<'a, Message
 
 /// Pulsante secondario
 pub fn secondary_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
-    button(text(content).size(styles::font_size::MEDIUM))
+    button(text(content).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
         .style(iced::theme::Button::Secondary)
         .padding([styles::spacing::SMALL.0, styles::spacing::MEDIUM.0])
         .on_press(message)
@@ -90,7 +116,7 @@ pub fn secondary_button<'a>(content: &str, message: Message) -> Button<'a, Messa
 
 /// Pulsante card (per selezioni)
 pub fn card_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
-    button(text(content).size(styles::font_size::MEDIUM))
+    button(text(content).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
         .style(iced::theme::Button::Secondary)
         .padding(styles::spacing::MEDIUM.0)
         .width(Length::Fill)
@@ -99,7 +125,7 @@ pub fn card_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
 
 /// Pulsante selezionato
 pub fn selected_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
-    button(text(content).size(styles::font_size::MEDIUM))
+    button(text(content).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
         .style(iced::theme::Button::Secondary)
         .padding(styles::spacing::MEDIUM.0)
         .width(Length::Fill)
@@ -108,7 +134,7 @@ pub fn selected_button<'a>(content: &str, message: Message) -> Button<'a, Messag
 
 /// Pulsante ghost (trasparente)
 pub fn ghost_button<'a>(content: &str, message: Message) -> Button<'a, Message> {
-    button(text(content).size(styles::font_size::SMALL))
+    button(text(content).size(styles::font_size::scaled(styles::font_size::SMALL)))
         .style(iced::theme::Button::Text)
         .padding([styles::spacing::TINY.0, styles::spacing::SMALL.0])
         .on_press(message)
@@ -469,17 +495,17 @@ pub fn loading_state(message: &str) -> Element<Message> {
                 column![
                     container(
                         text("⚡")
-                            .size(64)
-                            .style(styles::colors::PRIMARY)
+                            .size(styles::font_size::scaled(64))
+                            .style(styles::Palette::active().primary)
                     )
                     .padding(20),
                     text("AirWin")
-                        .size(32)
-                        .style(styles::colors::TEXT_PRIMARY),
+                        .size(styles::font_size::scaled(32))
+                        .style(styles::Palette::active().text_primary),
                     Space::with_height(20),
                     text(message)
-                        .size(16)
-                        .style(styles::colors::TEXT_SECONDARY),
+                        .size(styles::font_size::scaled(16))
+                        .style(styles::Palette::active().text_secondary),
                     Space::with_height(30),
                     // Progress indicator
                     container(
@@ -489,9 +515,9 @@ pub fn loading_state(message: &str) -> Element<Message> {
                     .style(|_theme: &Theme| {
                         container::Appearance {
                             background: Some(Background::Color(iced::Color::from_rgba(
-                                styles::colors::PRIMARY.r,
-                                styles::colors::PRIMARY.g,
-                                styles::colors::PRIMARY.b,
+                                styles::Palette::active().primary.r,
+                                styles::Palette::active().primary.g,
+                                styles::Palette::active().primary.b,
                                 0.3
                             ))),
                             border: Border {
@@ -511,7 +537,7 @@ pub fn loading_state(message: &str) -> Element<Message> {
             .height(Length::Fill)
             .style(|_theme: &Theme| {
                 container::Appearance {
-                    background: Some(Background::Color(styles::colors::BACKGROUND)),
+                    background: Some(Background::Color(styles::Palette::active().background)),
                     ..Default::default()
                 }
             })
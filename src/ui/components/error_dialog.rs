@@ -44,11 +44,11 @@ impl ErrorDialog {
         let content = column![
             // Icona e titolo
             row![
-                text("⚠️").size(24),
+                text("⚠️").size(styles::font_size::scaled(styles::font_size::XLARGE)),
                 Space::with_width(10),
                 text(&self.title)
-                    .size(18)
-                    .style(styles::colors::ERROR),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().error),
             ]
             .align_items(Alignment::Center),
             
@@ -56,8 +56,8 @@ impl ErrorDialog {
             
             // Messaggio principale
             text(&self.message)
-                .size(14)
-                .style(styles::colors::TEXT_PRIMARY),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_primary),
         ];
 
         let content = if let Some(ref details) = self.details {
@@ -65,16 +65,17 @@ impl ErrorDialog {
                 .push(
                     container(
                         text(details)
-                            .size(12)
-                            .style(styles::colors::TEXT_MUTED)
+                            .size(styles::font_size::scaled(styles::font_size::SMALL))
+                            .style(styles::Palette::active().text_muted)
                     )
                     .padding(10)
-                    .style(|_theme: &iced::Theme| {
+                    .style(|theme: &iced::Theme| {
+                        let palette = styles::Palette::for_theme(theme);
                         iced::widget::container::Appearance {
-                            text_color: Some(styles::colors::TEXT_MUTED),
+                            text_color: Some(palette.text_muted),
                             background: Some(iced::Background::Color(iced::Color::from_rgb(0.1, 0.1, 0.1))),
                             border: iced::Border {
-                                color: styles::colors::BORDER,
+                                color: palette.border,
                                 width: 1.0,
                                 radius: 4.0.into(),
                             },
@@ -91,7 +92,7 @@ impl ErrorDialog {
             .push(
                 row![
                     Space::with_width(Length::Fill),
-                    button(text("OK").size(14))
+                    button(text("OK").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
                         .on_press(Message::HideError)
                         .style(iced::theme::Button::Primary)
                 ]
@@ -100,12 +101,13 @@ impl ErrorDialog {
         container(
             container(content.spacing(5).width(400))
                 .padding(20)
-                .style(|_theme: &iced::Theme| {
+                .style(|theme: &iced::Theme| {
+                    let palette = styles::Palette::for_theme(theme);
                     iced::widget::container::Appearance {
-                        text_color: Some(styles::colors::TEXT_PRIMARY),
-                        background: Some(iced::Background::Color(styles::colors::BACKGROUND)),
+                        text_color: Some(palette.text_primary),
+                        background: Some(iced::Background::Color(palette.background)),
                         border: iced::Border {
-                            color: styles::colors::ERROR,
+                            color: palette.error,
                             width: 2.0,
                             radius: 8.0.into(),
                         },
@@ -5,7 +5,10 @@
 
 use crate::network::DiscoveredDevice;
 use crate::protocols::airplay::AirPlayStatus;
-use crate::protocols::airdrop::AirDropStatus;
+use crate::protocols::airdrop::{AirDropStatus, IncomingFile, TransferOutcome};
+use crate::protocols::file_conflict::{ConflictChoice, ConflictPrompt};
+use crate::protocols::send_progress::SendProgress;
+use crate::protocols::visibility::TransferPrompt;
 use std::path::PathBuf;
 
 /// Messaggi principali dell'applicazione
@@ -15,28 +18,102 @@ pub enum Message {
     Tick,
     WindowResized(u32, u32),
     ThemeChanged(crate::ui::Theme),
+    /// Scala del testo scelta nelle impostazioni
+    FontScaleChanged(crate::ui::views::settings_view::FontScale),
+    /// L'utente ha chiuso la finestra
+    WindowCloseRequested,
+    /// Scelta alla prima chiusura: `true` riduce a icona, `false` esce
+    CloseChoiceMade(bool),
+    CancelClose,
+    MinimizeToTrayToggled(bool),
+    /// Servizi arrestati, l'applicazione può terminare
+    ServicesStopped,
     InitializationComplete,
+    /// Configurazione iniziale confermata con "Inizia"
+    FinishOnboarding,
     
     // Messaggi di discovery
     StartScanning,
     StopScanning,
+    /// Limita le scansioni a un tipo di servizio e ne avvia una nuova
+    ScanScopeChanged(crate::network::ScanScope),
     DevicesUpdated(Vec<DiscoveredDevice>),
+    /// Toglie l'evidenziazione ai dispositivi comparsi o spariti da qualche secondo
+    ExpireScanHighlights,
     DeviceSelected(DiscoveredDevice),
     DeviceDeselected,
+    RecentDeviceProbed(DiscoveredDevice, Option<std::time::Duration>),
     
     // Messaggi di AirDrop
     AirDropStatusChanged(AirDropStatus),
     SendFile(DiscoveredDevice),
     SendLink(DiscoveredDevice, String),
     FileSelected(Option<PathBuf>),
-    FileSendProgress(f32),
-    FileSendCompleted(Result<(), String>),
+    MimeDetected(PathBuf, String),
+    MimeOverrideChanged(String),
+    FilePreviewLoaded(PathBuf, crate::utils::file_preview::FilePreview),
+    ConfirmSend,
+    CancelSend,
+    FileDropped(PathBuf),
+    SendFolder(DiscoveredDevice),
+    FolderSelected(Option<PathBuf>),
+    FolderArchived(Result<(PathBuf, u64), String>),
+    /// Avanzamento dell'invio: file corrente e invio complessivo
+    FileSendProgress(SendProgress),
+    FileSendCompleted(Result<TransferOutcome, String>),
+    /// Invio di un link terminato, con la scelta di aprirlo in caso di successo
+    LinkSendCompleted(String, bool, Result<TransferOutcome, String>),
+    /// Un invio della coda è terminato
+    QueuedSendCompleted(u64, crate::protocols::transfer_method::TransferMethod, Result<TransferOutcome, String>),
+    /// Riprende gli invii rimasti in coda alla chiusura precedente
+    ResumeQueuedSends,
+    DiscardQueuedSends,
+    FileReceived(IncomingFile),
+    /// Apre un file ricevuto con l'applicazione predefinita
+    OpenReceivedFile(PathBuf),
+    /// Mostra un file ricevuto in Esplora file
+    RevealReceivedFile(PathBuf),
+    MarkAllReceivedRead,
+    /// Un file ricevuto ha lo stesso nome di uno esistente
+    FileConflict(ConflictPrompt),
+    ResolveConflict(ConflictChoice),
+    /// Toglie le richieste di conflitto a cui la connessione non aspetta più
+    /// risposta
+    ExpireConflicts,
+    /// Un dispositivo chiede di inviare file, da accettare o rifiutare
+    TransferRequested(TransferPrompt),
+    RespondTransfer(bool),
+    /// Accetta l'invio e aggiunge il mittente ai dispositivi attendibili
+    AcceptAndTrust,
     
     // Messaggi di AirPlay
     AirPlayStatusChanged(AirPlayStatus),
     StartScreenMirroring(DiscoveredDevice),
     StopScreenMirroring,
     ScreenMirroringFrame(Vec<u8>),
+    AirPlayQualityChanged(crate::ui::views::settings_view::AirPlayQuality),
+    AirPlayIdleTimeoutChanged(crate::ui::views::settings_view::IdleTimeout),
+    AirPlayShowCursorToggled(bool),
+    /// Trasmissione fermata dopo il tempo di inattività indicato
+    AirPlayIdleStopped(std::time::Duration),
+    PollAirPlayQuality,
+    AirPlayEffectiveQuality(u8, u32),
+    AirPlayStats(crate::protocols::airplay_stats::AirPlayStats),
+    AwdlAvailabilityChanged(crate::protocols::awdl::AwdlAvailability),
+    /// Servizi mDNS non registrati e motivo, `None` quando sono annunciati
+    AirDropSendOnlyChanged(Option<String>),
+    /// Nome annunciato dopo il controllo dei nomi già in uso
+    AdvertisedNameChanged(String),
+    /// Nome, indirizzi e servizi con cui questo PC è visibile dopo l'avvio
+    StartupSummary(crate::utils::startup_summary::StartupSummary),
+    /// Certificato o connessione sicura non configurabili
+    TlsSetupFailed(String),
+    DismissTlsFailure,
+    /// Il certificato del dispositivo a questo indirizzo non è più quello memorizzato
+    CertificateChanged(String),
+    /// Dimentica il certificato memorizzato per l'indirizzo
+    ForgetCertificate(String),
+    CertificateForgotten(String, Result<(), String>),
     
     // Messaggi di interfaccia
     ShowActionDialog(DiscoveredDevice),
@@ -44,10 +121,21 @@ pub enum Message {
     ShowLinkDialog,
     HideLinkDialog,
     LinkInputChanged(String),
+    OpenLinkOnCompleteToggled(bool),
+    /// Apre o chiude i dettagli del dispositivo selezionato
+    ToggleDeviceDetails,
+    /// Copia negli appunti i dettagli e i record TXT del dispositivo selezionato
+    CopyDeviceDetails,
+    /// Copia negli appunti un testo mostrato nell'interfaccia; `label` dice
+    /// cosa è stato copiato nella notifica di conferma
+    CopyText { label: String, text: String },
     
     // Messaggi di notifica
     ShowNotification(NotificationMessage),
-    HideNotification,
+    /// Chiude una notifica con il pulsante ✕
+    HideNotification(crate::ui::notifications::NotificationId),
+    /// Chiude le notifiche rimaste visibili oltre la loro durata
+    ExpireNotifications,
     
     // Messaggi di errore
     Error(String),
@@ -57,16 +145,93 @@ pub enum Message {
     HideError,
     
     // Messaggi per le impostazioni
-    CustomPortChanged,
+    ShowNotificationsToggled(bool),
+    /// Notifiche di sistema invece che nella finestra
+    SystemNotificationsToggled(bool),
+    PlaySoundToggled(bool),
+    TestNotification,
+    AirDropEnabledToggled(bool),
+    AirPlayEnabledToggled(bool),
+    ConflictPolicyChanged(crate::ui::views::settings_view::ConflictPolicy),
+    CompletionChoiceChanged(crate::ui::views::settings_view::CompletionChoice),
+    IpModeChanged(crate::ui::views::settings_view::IpModeChoice),
+    SendMethodChanged(crate::ui::views::settings_view::SendMethod),
+    StayConnectedToggled(bool),
+    CompressionChanged(crate::ui::views::settings_view::CompressionChoice),
+    SelectSharedFolder,
+    SharedFolderSelected(Option<PathBuf>),
+    ClearSharedFolder,
+    AirDropVisibilityChanged(crate::ui::views::settings_view::AirDropVisibility),
+    AutoAcceptFromContactsToggled(bool),
+    /// Rimuove un dispositivo attendibile, per id
+    RemoveTrustedDevice(String),
+    /// Esito dell'avvio o dell'arresto di un servizio: nome, abilitato, risultato
+    ServiceToggled(&'static str, bool, Result<(), String>),
+    CustomPortChanged(String),
+    CustomPortSubmitted,
+    /// Campo dell'area trasmessa: 0 x, 1 y, 2 larghezza, 3 altezza
+    CaptureRegionChanged(usize, String),
+    CaptureRegionSubmitted,
+    CaptureRegionCleared,
+    FriendlyNameChanged(String),
+    FriendlyNameSubmitted,
+    NameSuffixChanged(String),
+    NameSuffixSubmitted,
+    AvoidNameCollisionsToggled(bool),
+    ResetSettings,
+    ResetWindowToggled(bool),
+    ConfirmResetSettings,
+    CancelResetSettings,
+    /// Salva le impostazioni in un file scelto dall'utente
+    ExportSettings,
+    SettingsExportPathSelected(Option<PathBuf>),
+    /// Carica le impostazioni da un file esportato
+    ImportSettings,
+    SettingsImportFileSelected(Option<PathBuf>),
+    AirPlayServerRestarted(Result<u16, String>),
     ToggleDebugMode,
     LogLevelChanged,
-    MaxConcurrentTransfersChanged,
+    MaxConcurrentTransfersChanged(u32),
+    MaxTransfersPerPeerChanged(u32),
     OpenLogFolder,
     ClearCache,
+    /// Byte liberati dalla pulizia della cache
+    CacheCleared(u64),
+    CacheSizeComputed(u64),
+    SelectCacheDir,
+    CacheDirSelected(Option<PathBuf>),
+    /// Apre la vista della diagnostica
     RunDiagnostics,
+    /// Raccoglie di nuovo i dati mostrati nella diagnostica
+    RefreshDiagnostics,
+    DiagnosticsRefreshed(crate::utils::bug_report::BugReport),
+    VerifyNetwork,
+    NetworkVerified(crate::utils::self_test::SelfTestReport),
+    CopyNetworkReport,
+    CopyDiagnostics,
+    DiagnosticsCollected(crate::utils::bug_report::BugReport),
+    /// Aggiunge le regole di Windows Firewall, chiedendo l'elevazione se serve
+    AddFirewallRules,
+    FirewallRulesAdded(Result<(), String>),
+    CopyFirewallCommands,
+    /// Apre le impostazioni di Windows Firewall
+    OpenFirewallSettings,
+    /// Apre le impostazioni sul campo della porta AirPlay
+    ChangeAirPlayPort,
+    FullMacsToggled(bool),
+    DismissPrivilegeBanner,
     
     // Messaggi per la navigazione
     ShowMainView,
+    ShowSettings,
+    ShowAbout,
+    ToggleMiniMode,
+    ShowEventLog,
+    
+    // Messaggi del registro eventi
+    EventLogged(crate::utils::event_log::LogEvent),
+    EventLogFilterChanged(Option<crate::utils::event_log::Severity>),
+    CopyEventLog,
     
     // Messaggi per i link esterni
     OpenLicenses,
@@ -83,8 +248,13 @@ pub struct NotificationMessage {
     pub content: String,
     pub notification_type: NotificationType,
     pub duration_ms: Option<u64>,
+    /// Pulsanti mostrati sotto il testo, con il messaggio inviato alla pressione
+    pub actions: Vec<(String, Message)>,
 }
 
+/// Durata minima di una notifica con azioni, per dare il tempo di sceglierne una
+pub const ACTION_DURATION_MS: u64 = 15_000;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum NotificationType {
     Success,
@@ -100,6 +270,7 @@ impl NotificationMessage {
             content: content.into(),
             notification_type: NotificationType::Success,
             duration_ms: Some(3000),
+            actions: Vec::new(),
         }
     }
     
@@ -109,6 +280,7 @@ impl NotificationMessage {
             content: content.into(),
             notification_type: NotificationType::Error,
             duration_ms: Some(5000),
+            actions: Vec::new(),
         }
     }
     
@@ -118,6 +290,7 @@ impl NotificationMessage {
             content: content.into(),
             notification_type: NotificationType::Warning,
             duration_ms: Some(4000),
+            actions: Vec::new(),
         }
     }
     
@@ -127,8 +300,26 @@ impl NotificationMessage {
             content: content.into(),
             notification_type: NotificationType::Info,
             duration_ms: Some(3000),
+            actions: Vec::new(),
         }
     }
+
+    /// Aggiunge un pulsante che invia `message`. La notifica resta visibile
+    /// almeno [`ACTION_DURATION_MS`].
+    pub fn with_action(mut self, label: impl Into<String>, message: Message) -> Self {
+        self.actions.push((label.into(), message));
+        self.duration_ms = Some(self.duration_ms.unwrap_or(0).max(ACTION_DURATION_MS));
+        self
+    }
+
+    /// Errore di avvio del server AirPlay, di solito una porta occupata o
+    /// bloccata dal firewall, con le azioni per rimediare
+    pub fn airplay_bind_failed(title: impl Into<String>, content: impl Into<String>) -> Self {
+        Self::error(title, content)
+            .with_action("Riprova", Message::AirPlayEnabledToggled(true))
+            .with_action("Apri impostazioni firewall", Message::OpenFirewallSettings)
+            .with_action("Cambia porta", Message::ChangeAirPlayPort)
+    }
 }
 
 /// Subscription messages per eventi asincroni
@@ -138,7 +329,7 @@ pub enum SubscriptionMessage {
     AirPlayStatusUpdate(AirPlayStatus),
     AirDropStatusUpdate(AirDropStatus),
     ScreenFrame(Vec<u8>),
-    FileTransferProgress(f32),
+    FileTransferProgress(SendProgress),
 }
 
 impl From<SubscriptionMessage> for Message {
@@ -4,15 +4,44 @@
 //! utilizzando la libreria Iced, con design moderno e reattivo.
 
 use iced::{
-    executor,
+    event, executor, keyboard, window,
     Application, Command, Element, Settings, Subscription, Theme as IcedTheme,
 };
 
+use iced::futures::SinkExt;
 use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::protocols::airdrop::{IncomingFile, TransferOutcome};
+use crate::protocols::awdl::AwdlAvailability;
+use crate::protocols::file_conflict::ConflictPrompt;
+use crate::protocols::transfer_method::TransferMethod;
+use crate::protocols::transfer_protocol::{Payload, ProtocolRegistry};
+use crate::utils::open_file::CompletionAction;
+use crate::protocols::visibility::{TransferPrompt, TrustedDevice};
+use crate::utils::event_log::{self, LogEvent, Severity};
+use crate::utils::poll_rate;
+
+/// Numero massimo di eventi mantenuti nella timeline
+const MAX_LOG_EVENTS: usize = 500;
+
+/// Durata di una scansione dei dispositivi
+const SCAN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Intervallo di lettura dell'avanzamento durante un invio
+const SEND_PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Intervallo di aggiornamento della vista della diagnostica
+const DIAGNOSTICS_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Repository del progetto, usato dai collegamenti della vista informazioni
+const REPOSITORY_URL: &str = "https://github.com/seregonwar/AirWin";
 
 // Moduli pub mod app;
 pub mod components;
 pub mod messages;
+pub mod notifications;
 pub mod styles;
 pub mod views;
 pub mod widgets;
@@ -21,13 +50,62 @@ pub mod widgets;
 pub use messages::Message;
  
 /// Tema dell'applicazione (utilizzato da `styles` per gli stili personalizzati)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Theme {
     #[default]
     Dark,
     Light,
+    /// Testo bianco su nero e accenti saturi, per chi vede poco
+    HighContrast,
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Dark => write!(f, "Scuro"),
+            Theme::Light => write!(f, "Chiaro"),
+            Theme::HighContrast => write!(f, "Alto contrasto"),
+        }
+    }
 }
  
+/// Canali dei servizi in background passati all'interfaccia all'avvio
+#[derive(Debug, Clone, Default)]
+pub struct UiFlags {
+    /// Eventi dei file ricevuti tramite AirDrop
+    pub incoming_files: Option<broadcast::Sender<IncomingFile>>,
+    /// Servizio di discovery usato dalle scansioni
+    pub discovery: Option<std::sync::Arc<tokio::sync::Mutex<crate::network::discovery::DeviceDiscovery>>>,
+    /// Servizio AirDrop, per avviarlo o fermarlo dalle impostazioni
+    pub airdrop: Option<crate::protocols::airdrop::AirDrop>,
+    /// Servizio AirPlay, per riavviare il server quando cambia la porta
+    pub airplay: Option<crate::protocols::airplay::AirPlay>,
+    /// Disponibilità di AWDL, mostrata come badge
+    pub awdl: Option<tokio::sync::watch::Receiver<AwdlAvailability>>,
+    /// Indirizzo MAC dell'interfaccia AWDL, noto dopo l'inizializzazione
+    pub awdl_mac: Option<tokio::sync::watch::Receiver<Option<[u8; 6]>>>,
+    /// Esito dell'avvio dei servizi, pubblicato al termine dell'inizializzazione
+    pub startup_summary: Option<tokio::sync::watch::Receiver<Option<crate::utils::startup_summary::StartupSummary>>>,
+    /// Privilegi e porte rilevati all'avvio
+    pub diagnostics: crate::utils::diagnostics::Diagnostics,
+}
+
+/// File scelto dall'utente in attesa di conferma prima dell'invio
+#[derive(Debug, Clone)]
+pub struct PendingSend {
+    /// Percorso del file selezionato
+    pub path: std::path::PathBuf,
+    /// Tipo MIME rilevato, modificabile dall'utente
+    pub mime_type: String,
+    /// Dimensione in byte, se già letta
+    pub size: Option<u64>,
+    /// Risoluzione delle immagini
+    pub dimensions: Option<(u32, u32)>,
+    /// Miniatura delle immagini, caricata dopo la selezione
+    pub thumbnail: Option<iced::widget::image::Handle>,
+}
+
 /// Struttura principale dell'applicazione AirWin
 #[derive(Debug)]
 pub struct AirWinApp {
@@ -36,12 +114,36 @@ pub struct AirWinApp {
     
     /// Dispositivi scoperti nella rete
     discovered_devices: Vec<crate::network::DiscoveredDevice>,
+
+    /// Dispositivi visti di recente ma assenti dall'ultima scansione
+    recent_devices: Vec<crate::network::DiscoveredDevice>,
+
+    /// Ultimo avvistamento di ogni dispositivo
+    device_cache: crate::network::device_cache::DeviceCache,
+
+    /// Dispositivi comparsi o spariti nelle ultime scansioni, evidenziati per qualche secondo
+    scan_highlights: crate::network::device_cache::ScanHighlights,
+
+    /// Qualità della connessione ai dispositivi, dagli ultimi contatti e invii
+    connection_quality: crate::network::connection_quality::ConnectionQualityTracker,
     
     /// Dispositivo attualmente selezionato
     selected_device: Option<crate::network::DiscoveredDevice>,
     
     /// Stato della scansione
     is_scanning: bool,
+
+    /// Interrompe la scansione in corso
+    scan_cancel: Option<crate::network::backend::CancelToken>,
+
+    /// Servizi cercati dalle scansioni
+    scan_scope: crate::network::ScanScope,
+
+    /// Istante di avvio della scansione in corso
+    scan_started: Option<std::time::Instant>,
+
+    /// Fine dell'ultima scansione completata
+    last_scan: Option<std::time::Instant>,
     
     /// Stato AirPlay
     airplay_status: crate::protocols::airplay::AirPlayStatus,
@@ -49,11 +151,29 @@ pub struct AirWinApp {
     /// Stato AirDrop
     airdrop_status: crate::protocols::airdrop::AirDropStatus,
     
+    /// Inizio della connessione AirPlay in corso, per animare l'indicatore
+    airplay_connecting_since: Option<std::time::Instant>,
+
     /// Progresso del trasferimento file (0.0-100.0)
     file_transfer_progress: Option<f32>,
+
+    /// File corrente e numero di file di un invio, per il progresso su due livelli
+    send_progress: Option<crate::protocols::send_progress::SendProgress>,
+
+    /// Dimensione totale del trasferimento in corso, se nota
+    file_transfer_size: Option<u64>,
+
+    /// Archivio temporaneo creato per l'invio di una cartella
+    pending_archive: Option<std::path::PathBuf>,
+
+    /// File in attesa di conferma dell'invio
+    pending_send: Option<PendingSend>,
     
     /// Notificazioni attive
-    notifications: Vec<messages::NotificationMessage>,
+    notifications: notifications::NotificationQueue,
+
+    /// Notifiche di sistema, usate al posto di quelle nella finestra se scelto
+    system_notifications: Box<dyn notifications::NotificationBackend>,
     
     /// Tema corrente
     theme: Theme,
@@ -69,12 +189,102 @@ pub struct AirWinApp {
     
     /// URL da inviare tramite link
     link_url: String,
+
+    /// Apre il link dopo l'invio riuscito
+    open_link_on_complete: bool,
+
+    /// Dettagli del dispositivo selezionato espansi, chiusi all'avvio
+    device_details_expanded: bool,
     
     /// Stato di caricamento generale
     is_loading: bool,
     
     /// Messaggio di stato
     status_message: String,
+
+    /// Impostazioni persistite su disco
+    settings: crate::utils::config::Settings,
+
+    /// Cronologia dei trasferimenti
+    history: crate::utils::history::History,
+
+    /// Ora usata per la cronologia e il tempo rimanente dei trasferimenti
+    clock: crate::utils::clock::SharedClock,
+
+    /// Sorgente degli eventi dei file ricevuti
+    incoming_files: Option<broadcast::Sender<IncomingFile>>,
+
+    /// Servizio di discovery dei dispositivi
+    discovery: Option<std::sync::Arc<tokio::sync::Mutex<crate::network::discovery::DeviceDiscovery>>>,
+
+    /// Servizio AirDrop in background
+    airdrop: Option<crate::protocols::airdrop::AirDrop>,
+
+    /// Protocolli con cui si inviano file e link, AirDrop per primo
+    protocols: ProtocolRegistry,
+
+    /// Servizio AirPlay in background
+    airplay: Option<crate::protocols::airplay::AirPlay>,
+
+    /// Qualità JPEG e fps scelti dal controllo adattivo durante lo streaming
+    airplay_effective_quality: Option<(u8, u32)>,
+
+    /// Frame inviati e scartati nella sessione di streaming corrente
+    airplay_stats: crate::protocols::airplay_stats::AirPlayStats,
+
+    /// Anteprima dell'ultimo frame inviato
+    airplay_preview: Option<iced::widget::image::Handle>,
+
+    /// Stato di AWDL e canale da cui riceverne le variazioni
+    awdl_availability: AwdlAvailability,
+    awdl: Option<tokio::sync::watch::Receiver<AwdlAvailability>>,
+    awdl_mac: Option<tokio::sync::watch::Receiver<Option<[u8; 6]>>>,
+    startup_summary: Option<tokio::sync::watch::Receiver<Option<crate::utils::startup_summary::StartupSummary>>>,
+
+    /// Timeline degli eventi dei protocolli
+    event_log: Vec<LogEvent>,
+
+    /// Filtro per gravità della timeline
+    event_log_filter: Option<Severity>,
+
+    /// Privilegi e porte rilevati all'avvio
+    diagnostics: crate::utils::diagnostics::Diagnostics,
+
+    /// Ultimi dati raccolti per la vista della diagnostica
+    diagnostics_report: Option<crate::utils::bug_report::BugReport>,
+    /// Aggiunta delle regole di Windows Firewall in corso
+    adding_firewall_rules: bool,
+
+    /// Banner delle funzionalità limitate chiuso dall'utente
+    privilege_banner_dismissed: bool,
+
+    /// Limitazione aggiunta quando AirDrop funziona solo in invio
+    send_only_limitation: Option<String>,
+
+    /// Nome che gli altri dispositivi vedono, con suffisso ed eventuale numero
+    advertised_name: Option<String>,
+
+    /// Ultimo errore nella configurazione della connessione sicura, finché
+    /// l'utente non chiude l'avviso
+    tls_failure: Option<String>,
+
+    /// Richiesta alla prima chiusura se ridurre a icona o uscire
+    close_prompt: bool,
+
+    /// File da inviare, salvati su disco finché l'invio non termina
+    transfers: crate::protocols::transfer_manager::TransferManager,
+
+    /// Invii rimasti in coda all'avvio, in attesa di una scelta
+    resume_prompt: bool,
+
+    /// Sceglie tra invio diretto e cartella condivisa per ogni dispositivo
+    method_selector: crate::protocols::transfer_method::MethodSelector,
+
+    /// File ricevuti con un nome già esistente, in attesa di una scelta
+    pending_conflicts: Vec<ConflictPrompt>,
+
+    /// Richieste di invio in attesa di conferma
+    pending_transfers: Vec<TransferPrompt>,
 } 
 
 /// Viste disponibili nell'applicazione
@@ -88,6 +298,14 @@ pub enum AppView {
     About,
     /// Vista di caricamento iniziale
     Loading,
+    /// Vista compatta con lista dispositivi e area di rilascio
+    Mini,
+    /// Timeline degli eventi
+    EventLog,
+    /// Stato dei servizi, della rete e dei task
+    Diagnostics,
+    /// Configurazione iniziale, mostrata solo al primo avvio
+    Onboarding,
 }
 
 impl Default for AppView {
@@ -100,48 +318,99 @@ impl Application for AirWinApp {
     type Message = Message;
     type Theme = IcedTheme;
     type Executor = executor::Default;
-    type Flags = ();
+    type Flags = UiFlags;
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        let app = Self {
+    fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
+        let settings = crate::utils::config::Settings::load();
+        let device_cache = crate::network::device_cache::DeviceCache::new(
+            Duration::from_secs(settings.recent_devices_expiry_secs),
+        );
+        let mut transfers = crate::protocols::transfer_manager::TransferManager::load(settings.transfer_limits());
+        let missing = transfers.remove_missing();
+        let mut app = Self {
             current_view: AppView::Loading,
             status_message: "Inizializzazione in corso...".to_string(),
             is_loading: true,
-            theme: Theme::default(),
-            settings_view: views::settings_view::SettingsView::new(
-                true,                // enable_auto_discovery
-                15,                  // discovery_interval
-                true,                // show_notifications
-                false,               // minimize_to_tray
-                true,                // airdrop_enabled
-                views::settings_view::AirDropVisibility::Everyone,
-                false,               // auto_accept_from_contacts
-                true,                // airplay_enabled
-                views::settings_view::AirPlayQuality::Auto,
-                false,               // airplay_audio_only
-                None,                // network_interface
-                Vec::new(),          // available_interfaces
-                None,                // custom_port
-                false,               // debug_mode
-                views::settings_view::LogLevel::Info,
-                2,                   // max_concurrent_transfers
-            ),
+            theme: settings.theme,
+            settings_view: Self::settings_view_for(&settings),
             about_view: views::about_view::AboutView::new(
                 "0.1.0".to_string(),
                 "unknown".to_string(),
                 None,
             ),
             discovered_devices: Vec::new(),
+            recent_devices: Vec::new(),
+            device_cache,
+            scan_highlights: Default::default(),
+            connection_quality: Default::default(),
             selected_device: None,
             is_scanning: false,
+            scan_cancel: None,
+            scan_scope: crate::network::ScanScope::All,
+            scan_started: None,
+            last_scan: None,
             airplay_status: crate::protocols::airplay::AirPlayStatus::Idle,
-            airdrop_status: crate::protocols::airdrop::AirDropStatus::Idle,
+            airdrop_status: crate::protocols::airdrop::AirDropStatus::default(),
+            airplay_connecting_since: None,
             file_transfer_progress: None,
-            notifications: Vec::new(),
+            send_progress: None,
+            file_transfer_size: None,
+            pending_archive: None,
+            pending_send: None,
+            notifications: notifications::NotificationQueue::new(settings.max_notifications as usize),
+            system_notifications: notifications::system_backend(),
             show_link_dialog: false,
+            device_details_expanded: false,
             link_url: String::new(),
+            open_link_on_complete: false,
+            settings,
+            history: crate::utils::history::History::load(),
+            clock: crate::utils::clock::system(),
+            incoming_files: flags.incoming_files,
+            discovery: flags.discovery,
+            protocols: protocol_registry(flags.airdrop.as_ref()),
+            airdrop: flags.airdrop,
+            airplay: flags.airplay,
+            airplay_effective_quality: None,
+            airplay_stats: Default::default(),
+            airplay_preview: None,
+            awdl_availability: flags
+                .awdl
+                .as_ref()
+                .map(|awdl| *awdl.borrow())
+                .unwrap_or(AwdlAvailability::Off),
+            awdl: flags.awdl,
+            awdl_mac: flags.awdl_mac,
+            startup_summary: flags.startup_summary,
+            event_log: Vec::new(),
+            event_log_filter: None,
+            diagnostics: flags.diagnostics,
+            diagnostics_report: None,
+            adding_firewall_rules: false,
+            privilege_banner_dismissed: false,
+            send_only_limitation: None,
+            advertised_name: None,
+            tls_failure: None,
+            close_prompt: false,
+            resume_prompt: !transfers.restored().is_empty(),
+            method_selector: settings.method_selector(),
+            transfers,
+            pending_conflicts: Vec::new(),
+            pending_transfers: Vec::new(),
         };
 
+        // I file spostati o eliminati dopo la chiusura non possono essere ripresi
+        if !missing.is_empty() {
+            for send in &missing {
+                app.add_notification(
+                    "File non più disponibile".to_string(),
+                    format!("{} non verrà inviato a {}", send.path.display(), send.target),
+                    messages::NotificationType::Warning,
+                );
+            }
+            app.save_transfers();
+        }
+
         let command = Command::perform(
             async {
                 // Simula inizializzazione
@@ -159,44 +428,234 @@ impl Application for AirWinApp {
             AppView::Settings => "AirWin - Impostazioni".to_string(),
             AppView::About => "AirWin - Informazioni".to_string(),
             AppView::Loading => "AirWin - Caricamento".to_string(),
+            AppView::Mini => "AirWin".to_string(),
+            AppView::EventLog => "AirWin - Registro eventi".to_string(),
+            AppView::Diagnostics => "AirWin - Diagnostica".to_string(),
+            AppView::Onboarding => "AirWin - Benvenuto".to_string(),
         }
     }
 
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Message::InitializationComplete => {
-                self.current_view = AppView::Main;
+                self.current_view = if self.settings.mini_mode {
+                    AppView::Mini
+                } else {
+                    AppView::Main
+                };
                 self.is_loading = false;
                 self.status_message = "Pronto".to_string();
                 
                 // Avvia la scansione automatica
-                Command::perform(
+                let scan = Command::perform(
                     async { () },
                     |_| Message::StartScanning,
-                )
+                );
+
+                let cache_size = self.refresh_cache_size();
+
+                // Al primo avvio la configurazione iniziale precede tutto il resto,
+                // la modalità compatta viene applicata quando si conclude
+                if !self.settings.onboarding_complete {
+                    self.current_view = AppView::Onboarding;
+                    let verify = self.update(Message::VerifyNetwork);
+                    return Command::batch([scan, cache_size, verify]);
+                }
+
+                if self.settings.mini_mode {
+                    Command::batch([self.apply_window_mode(), scan, cache_size])
+                } else {
+                    Command::batch([scan, cache_size])
+                }
+            }
+
+            Message::FinishOnboarding => {
+                let name = self.settings_view.friendly_name();
+                if let Some(Err(e)) = name.as_deref().map(crate::utils::config::validate_friendly_name) {
+                    self.add_notification(
+                        "Nome non valido".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                    return Command::none();
+                }
+
+                let rename = self.update(Message::FriendlyNameSubmitted);
+                self.settings.onboarding_complete = true;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist onboarding state: {}", e);
+                }
+                event_log::info("Configurazione", "Configurazione iniziale completata");
+
+                if self.settings.mini_mode {
+                    self.current_view = AppView::Mini;
+                    Command::batch([rename, self.apply_window_mode()])
+                } else {
+                    self.current_view = AppView::Main;
+                    rename
+                }
+            }
+
+            Message::ToggleMiniMode => {
+                let entering = self.current_view != AppView::Mini;
+                self.current_view = if entering { AppView::Mini } else { AppView::Main };
+                self.settings.mini_mode = entering;
+                if let Err(e) = self.settings.save() {
+                    tracing::warn!("Failed to persist mini mode: {}", e);
+                }
+                self.apply_window_mode()
+            }
+
+            Message::WindowCloseRequested => match self.settings.minimize_to_tray {
+                // Non c'è un'icona nella system tray da cui riaprirla: la
+                // finestra viene ridotta a icona, mai nascosta, e resta
+                // raggiungibile dalla barra delle applicazioni
+                Some(true) => window::minimize(window::Id::MAIN, true),
+                Some(false) => self.quit(),
+                None => {
+                    self.close_prompt = true;
+                    Command::none()
+                }
+            },
+
+            Message::CloseChoiceMade(minimize) => {
+                self.close_prompt = false;
+                self.update(Message::MinimizeToTrayToggled(minimize));
+                self.update(Message::WindowCloseRequested)
+            }
+
+            Message::CancelClose => {
+                self.close_prompt = false;
+                Command::none()
+            }
+
+            Message::MinimizeToTrayToggled(enabled) => {
+                self.settings_view.set_minimize_to_tray(enabled);
+                self.settings.minimize_to_tray = Some(enabled);
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist close behaviour: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::ServicesStopped => window::close(window::Id::MAIN),
+
+            Message::FileDropped(path) => {
+                match self.selected_device.clone() {
+                    Some(device) if !self.protocols.allows(&device, crate::network::DeviceAction::SendFile) => {
+                        let reason = if self.protocols.protocols().is_empty() {
+                            "AirDrop non è attivo su questo PC".to_string()
+                        } else {
+                            format!("{} non accetta file tramite AirDrop", device.name)
+                        };
+                        self.add_notification(
+                            "Invio non disponibile".to_string(),
+                            reason,
+                            messages::NotificationType::Warning,
+                        );
+                        Command::none()
+                    }
+                    Some(device) => {
+                        let file_name = path
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_else(|| path.display().to_string());
+                        self.add_notification(
+                            "File rilasciato".to_string(),
+                            format!("Invio di {} a {}", file_name, device.name),
+                            messages::NotificationType::Info,
+                        );
+                        self.update(Message::FileSelected(Some(path)))
+                    }
+                    None => {
+                        self.add_notification(
+                            "Nessun dispositivo".to_string(),
+                            "Seleziona un dispositivo prima di rilasciare un file".to_string(),
+                            messages::NotificationType::Warning,
+                        );
+                        Command::none()
+                    }
+                }
             }
 
             Message::StartScanning => {
                 self.is_scanning = true;
+                self.scan_started = Some(std::time::Instant::now());
                 self.status_message = "Scansione dispositivi in corso...".to_string();
-                
+
+                let cancel = crate::network::backend::CancelToken::new();
+                if let Some(previous) = self.scan_cancel.replace(cancel.clone()) {
+                    previous.cancel();
+                }
                 Command::perform(
-                    Self::scan_devices(),
+                    Self::scan_devices(self.discovery.clone(), self.scan_scope, cancel),
                     Message::DevicesUpdated,
                 )
             }
 
+            Message::ScanScopeChanged(scope) => {
+                self.scan_scope = scope;
+                self.update(Message::StartScanning)
+            }
+
+            // mDNS e BLE smettono subito di interrogare la rete, i dispositivi
+            // già trovati arrivano comunque con `DevicesUpdated`
             Message::StopScanning => {
+                if let Some(cancel) = self.scan_cancel.take() {
+                    cancel.cancel();
+                }
                 self.is_scanning = false;
+                self.scan_started = None;
                 self.status_message = "Scansione interrotta".to_string();
                 Command::none()
             }
 
             Message::DevicesUpdated(devices) => {
+                let diff = self.device_cache.record_scan(&devices);
+                // Alla prima scansione ogni dispositivo sarebbe "nuovo"
+                if self.last_scan.is_some() {
+                    self.scan_highlights.record(&diff, std::time::Instant::now());
+                }
+                self.recent_devices = self.device_cache.recently_seen();
+
+                // Mantieni la selezione se il dispositivo è ancora presente dopo la scansione
+                if let Some(selected) = self.selected_device.take() {
+                    let still_known = devices
+                        .iter()
+                        .chain(self.recent_devices.iter())
+                        .find(|device| device.same_device(&selected));
+                    match still_known {
+                        Some(device) => self.selected_device = Some(device.clone()),
+                        None => {
+                            self.pending_send = None;
+                            if self.file_transfer_progress.is_some() {
+                                self.add_notification(
+                                    "Dispositivo non disponibile".to_string(),
+                                    format!(
+                                        "{} non è più raggiungibile: il trasferimento in corso potrebbe non completarsi",
+                                        selected.name
+                                    ),
+                                    messages::NotificationType::Error,
+                                );
+                            } else {
+                                self.add_notification(
+                                    "Dispositivo non disponibile".to_string(),
+                                    "Il dispositivo selezionato non è più disponibile".to_string(),
+                                    messages::NotificationType::Warning,
+                                );
+                            }
+                        }
+                    }
+                }
+
                 self.discovered_devices = devices;
                 self.is_scanning = false;
+                self.scan_started = None;
+                self.last_scan = Some(std::time::Instant::now());
+                let stopped = self.scan_cancel.take().is_none();
                 self.status_message = format!(
-                    "Trovati {} dispositivi",
+                    "{}Trovati {} dispositivi",
+                    if stopped { "Scansione interrotta: " } else { "" },
                     self.discovered_devices.len()
                 );
                 
@@ -214,6 +673,25 @@ impl Application for AirWinApp {
                 Command::none()
             }
 
+            Message::RecentDeviceProbed(device, rtt) => {
+                if let Some(rtt) = rtt {
+                    self.connection_quality.record_rtt(device.address, rtt);
+                    self.device_cache.mark_seen(&device);
+                    self.recent_devices.retain(|recent| !recent.same_device(&device));
+                    self.discovered_devices.push(device);
+                    self.update(Message::ConfirmSend)
+                } else {
+                    // La richiesta di invio resta in attesa per poter riprovare
+                    self.status_message = format!("{} non raggiungibile", device.name);
+                    self.add_notification(
+                        "Dispositivo non raggiungibile".to_string(),
+                        format!("{} non risponde: riprova quando torna disponibile", device.name),
+                        messages::NotificationType::Warning,
+                    );
+                    Command::none()
+                }
+            }
+
             Message::DeviceSelected(device) => {
                 self.selected_device = Some(device.clone());
                 self.status_message = format!("Selezionato: {}", device.name);
@@ -228,65 +706,388 @@ impl Application for AirWinApp {
             }
 
             Message::SendFile(_device) => {
-                if self.selected_device.is_some() {
-                    self.airdrop_status = crate::protocols::airdrop::AirDropStatus::Transferring(0.0);
-                    self.file_transfer_progress = Some(0.0);
-                    
-                    Command::perform(
-                        Self::simulate_file_transfer(),
-                        Message::FileSendProgress,
-                    )
-                } else {
-                    Command::none()
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Seleziona il file da inviare")
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::FileSelected,
+                )
+            }
+
+            Message::FileSelected(Some(path)) => {
+                // Rileva il tipo MIME prima dell'invio così l'utente può correggerlo
+                Command::perform(
+                    async move {
+                        let mime_type = crate::utils::mime_type::detect_mime_type(&path).await;
+                        (path, mime_type)
+                    },
+                    |(path, mime_type)| Message::MimeDetected(path, mime_type),
+                )
+            }
+
+            Message::FileSelected(None) => Command::none(),
+
+            Message::MimeDetected(path, mime_type) => {
+                self.pending_send = Some(PendingSend {
+                    path: path.clone(),
+                    mime_type,
+                    size: None,
+                    dimensions: None,
+                    thumbnail: None,
+                });
+                // La miniatura viene generata fuori dal thread dell'interfaccia
+                Command::perform(
+                    async move {
+                        let preview = crate::utils::file_preview::load_preview(path.clone()).await;
+                        (path, preview)
+                    },
+                    |(path, preview)| Message::FilePreviewLoaded(path, preview),
+                )
+            }
+
+            Message::FilePreviewLoaded(path, preview) => {
+                // Ignora l'anteprima di un file nel frattempo sostituito o annullato
+                if let Some(pending) = self.pending_send.as_mut().filter(|pending| pending.path == path) {
+                    pending.size = preview.size;
+                    pending.dimensions = preview.dimensions;
+                    pending.thumbnail = preview.thumbnail.map(|thumbnail| {
+                        iced::widget::image::Handle::from_pixels(thumbnail.width, thumbnail.height, thumbnail.rgba)
+                    });
+                }
+                Command::none()
+            }
+
+            Message::MimeOverrideChanged(mime_type) => {
+                if let Some(pending) = self.pending_send.as_mut() {
+                    pending.mime_type = mime_type;
+                }
+                Command::none()
+            }
+
+            Message::CancelSend => {
+                self.pending_send = None;
+                Command::none()
+            }
+
+            Message::ConfirmSend => {
+                let Some(device) = self.selected_device.clone() else {
+                    return Command::none();
+                };
+                // Un dispositivo visto di recente va ricontattato prima dell'invio
+                if self.recent_devices.iter().any(|recent| recent.same_device(&device)) {
+                    self.status_message = format!("Verifica di {} in corso...", device.name);
+                    return Command::perform(
+                        async move {
+                            let rtt = crate::network::device_cache::probe(&device).await;
+                            (device, rtt)
+                        },
+                        |(device, rtt)| Message::RecentDeviceProbed(device, rtt),
+                    );
                 }
+                match self.pending_send.take() {
+                    Some(pending) => {
+                        let addr = device.airdrop_addr();
+                        self.transfers.enqueue(pending.path, pending.mime_type, device.name, addr);
+                        self.save_transfers();
+                        self.start_queued_sends()
+                    }
+                    None => Command::none(),
+                }
+            }
+
+            Message::SendFolder(_device) => {
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Seleziona la cartella da inviare")
+                            .pick_folder()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::FolderSelected,
+                )
+            }
+
+            Message::FolderSelected(Some(folder)) => {
+                self.airdrop_status.connecting();
+                self.status_message = "Compressione cartella in corso...".to_string();
+
+                let archives = crate::utils::cache::Cache::from_settings(&self.settings).archives_dir();
+                Command::perform(
+                    async move {
+                        let archive = tokio::task::spawn_blocking(move || {
+                            crate::utils::archive::zip_directory(&folder, &archives)
+                        })
+                        .await
+                        .map_err(|e| e.to_string())?
+                        .map_err(|e| e.to_string())?;
+                        let size = tokio::fs::metadata(&archive)
+                            .await
+                            .map_err(|e| e.to_string())?
+                            .len();
+                        Ok::<_, String>((archive, size))
+                    },
+                    Message::FolderArchived,
+                )
+            }
+
+            Message::FolderSelected(None) => Command::none(),
+
+            Message::FolderArchived(Ok((archive, size))) => {
+                let archive_name = archive
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.add_notification(
+                    "Cartella compressa".to_string(),
+                    format!("{} ({})", archive_name, components::format_bytes(size)),
+                    messages::NotificationType::Info,
+                );
+
+                self.pending_archive = Some(archive.clone());
+                let Some(device) = self.selected_device.clone() else {
+                    return self.update(Message::FileSendCompleted(Err("Nessun dispositivo selezionato".to_string())));
+                };
+                self.file_transfer_size = Some(size);
+                self.file_transfer_progress = Some(0.0);
+                self.airdrop_status.start_transfer(self.clock.now());
+
+                Command::perform(
+                    Self::send_payload(self.discovery.clone(), self.protocols.clone(), device, Payload::Files(vec![archive])),
+                    Message::FileSendCompleted,
+                )
+            }
+
+            Message::FolderArchived(Err(e)) => {
+                self.airdrop_status.disconnected();
+                self.add_notification(
+                    "Compressione fallita".to_string(),
+                    e,
+                    messages::NotificationType::Error,
+                );
+                Command::none()
             }
 
             Message::SendLink(device, url) => {
                 self.link_url = url.clone();
+                let open_on_complete = self.open_link_on_complete
+                    && self.settings.completion_action != CompletionAction::Nothing;
+                let sent_url = url.clone();
                 self.add_notification(
                     "Invio link".to_string(),
                     format!("Invio link a {}", device.name),
                     messages::NotificationType::Info,
                 );
-                self.airdrop_status = crate::protocols::airdrop::AirDropStatus::Connecting;
+                self.airdrop_status.connecting();
                 Command::perform(
-                    async {
-                        tokio::time::sleep(Duration::from_secs(2)).await;
-                        Ok::<(), String>(())
-                    },
-                    |res| match res {
-                        Ok(()) => Message::FileSendCompleted(Ok(())),
-                        Err(e) => Message::FileSendCompleted(Err(e)),
-                    },
+                    Self::send_payload(self.discovery.clone(), self.protocols.clone(), device, Payload::Link(url)),
+                    move |result| Message::LinkSendCompleted(sent_url, open_on_complete, result),
                 )
             }
 
+            // Un link non è un file: non finisce nella cronologia dei trasferimenti
+            Message::LinkSendCompleted(url, open_on_complete, result) => {
+                self.airdrop_status.disconnected();
+                match result {
+                    Ok(outcome) => {
+                        self.add_notification(
+                            "Link inviato".to_string(),
+                            format!("{} inviato a {}", url, outcome.peer),
+                            messages::NotificationType::Success,
+                        );
+                        self.play_completion_sound();
+                        // Il link si apre solo se il dispositivo l'ha ricevuto
+                        if open_on_complete {
+                            self.open_url(url);
+                        }
+                    }
+                    Err(e) => self.add_notification(
+                        "Invio del link fallito".to_string(),
+                        e,
+                        messages::NotificationType::Error,
+                    ),
+                }
+                Command::none()
+            }
+
+            // Una lettura arrivata dopo la fine dell'invio non riapre la barra
+            Message::FileSendProgress(_) if self.file_transfer_progress.is_none() => Command::none(),
+
             Message::FileSendProgress(progress) => {
-                self.file_transfer_progress = Some(progress);
-                self.airdrop_status = crate::protocols::airdrop::AirDropStatus::Transferring(progress);
+                // La barra principale mostra sempre l'avanzamento complessivo
+                self.file_transfer_progress = Some(progress.overall_progress);
+                self.airdrop_status.set_progress(progress.overall_progress);
+                self.send_progress = Some(progress);
+                Command::none()
+            }
+
+            Message::FileReceived(file) => {
+                self.add_notification(
+                    "File ricevuto".to_string(),
+                    format!(
+                        "{} ({}) da {}",
+                        file.name,
+                        components::format_bytes(file.size),
+                        file.from.ip()
+                    ),
+                    messages::NotificationType::Success,
+                );
+
+                self.play_completion_sound();
+                self.history.push(crate::utils::history::HistoryEntry::incoming(&file, self.clock.now()));
+                self.save_history();
+
+                // Il file viene pubblicato solo dopo averne ricevuto tutti i byte
+                match self.settings.completion_action.run(&file.path) {
+                    Ok(()) if self.settings.completion_action != CompletionAction::Nothing => {
+                        if self.history.mark_read(&file.path) {
+                            self.save_history();
+                        }
+                    }
+                    Ok(()) => {}
+                    Err(e) => self.add_notification(
+                        "Impossibile aprire il file ricevuto".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Warning,
+                    ),
+                }
+                Command::none()
+            }
+
+            Message::OpenReceivedFile(path) => {
+                if let Err(e) = crate::utils::open_file::open(&path) {
+                    self.add_notification(
+                        "Impossibile aprire il file".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                }
+                if self.history.mark_read(&path) {
+                    self.save_history();
+                }
+                Command::none()
+            }
+
+            Message::RevealReceivedFile(path) => {
+                if let Err(e) = crate::utils::open_file::reveal(&path) {
+                    self.add_notification(
+                        "Impossibile mostrare il file".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                }
+                if self.history.mark_read(&path) {
+                    self.save_history();
+                }
+                Command::none()
+            }
+
+            Message::MarkAllReceivedRead => {
+                if self.history.mark_all_read() {
+                    self.save_history();
+                }
                 Command::none()
             }
 
             Message::FileSendCompleted(result) => {
                 self.file_transfer_progress = None;
-                self.airdrop_status = crate::protocols::airdrop::AirDropStatus::Idle;
-                match result {
-                    Ok(()) => self.add_notification(
-                        "Trasferimento completato".to_string(),
-                        "Operazione completata con successo".to_string(),
-                        messages::NotificationType::Success,
+                self.send_progress = None;
+                self.file_transfer_size = None;
+                self.airdrop_status.disconnected();
+
+                // L'archivio temporaneo va rimosso sia in caso di successo che di errore
+                let cleanup = match self.pending_archive.take() {
+                    Some(archive) => Command::perform(
+                        async move { crate::utils::archive::remove_archive(&archive).await },
+                        |_| Message::Tick,
                     ),
+                    None => Command::none(),
+                };
+
+                match result {
+                    Ok(outcome) => {
+                        self.add_notification(
+                            "Trasferimento completato".to_string(),
+                            format!(
+                                "Inviato {} ({}) a {} in {:.1}s",
+                                outcome.file_name,
+                                components::format_bytes(outcome.total_bytes),
+                                outcome.peer,
+                                outcome.elapsed.as_secs_f64()
+                            ),
+                            messages::NotificationType::Success,
+                        );
+
+                        self.play_completion_sound();
+                        self.history.push(crate::utils::history::HistoryEntry::outgoing(&outcome, self.clock.now()));
+                        self.save_history();
+                    }
                     Err(e) => self.add_notification(
                         "Trasferimento fallito".to_string(),
                         e,
                         messages::NotificationType::Error,
                     ),
                 }
+                cleanup
+            }
+
+            Message::QueuedSendCompleted(id, method, result) => {
+                let completed = self.transfers.complete(id);
+                self.save_transfers();
+                match (method, &result, completed) {
+                    (TransferMethod::Direct, result, Some(send)) => {
+                        // Solo i file inviati con AirDrop misurano la velocità del collegamento,
+                        // all'indirizzo a cui sono andati davvero
+                        if let Ok(outcome) = result {
+                            if let Ok(addr) = outcome.peer.parse::<std::net::SocketAddr>() {
+                                self.connection_quality.record_transfer(addr.ip(), outcome.total_bytes, outcome.elapsed);
+                            }
+                        }
+                        // Conta l'esito dell'invio AirDrop vero e proprio
+                        if self.method_selector.record_direct(&send.target, result.is_ok()) {
+                            self.add_notification(
+                                "Invio tramite cartella condivisa".to_string(),
+                                format!(
+                                    "L'invio diretto a {} continua a fallire: i prossimi file verranno copiati nella cartella condivisa",
+                                    send.target
+                                ),
+                                messages::NotificationType::Warning,
+                            );
+                        }
+                    }
+                    (TransferMethod::SharedFolder, Ok(outcome), Some(send)) => {
+                        self.add_notification(
+                            "File nella cartella condivisa".to_string(),
+                            format!("Avvisa {} che {} è in {}", send.target, outcome.file_name, outcome.peer),
+                            messages::NotificationType::Info,
+                        );
+                    }
+                    _ => {}
+                }
+                let completed = self.update(Message::FileSendCompleted(result));
+                Command::batch([completed, self.start_queued_sends()])
+            }
+
+            Message::ResumeQueuedSends => {
+                self.resume_prompt = false;
+                self.transfers.resume_restored();
+                self.start_queued_sends()
+            }
+
+            Message::DiscardQueuedSends => {
+                self.resume_prompt = false;
+                self.transfers.discard_restored();
+                self.save_transfers();
                 Command::none()
             }
 
             Message::ShowLinkDialog => {
                 self.show_link_dialog = true;
+                self.open_link_on_complete = self.settings.completion_action != CompletionAction::Nothing;
                 Command::none()
             }
 
@@ -301,82 +1102,1413 @@ impl Application for AirWinApp {
                 Command::none()
             }
 
-            Message::ShowNotification(notification) => {
-                self.notifications.push(notification);
-                
-                // Auto-rimuovi notifica dopo 5 secondi
-                Command::perform(
-                    async {
-                        tokio::time::sleep(Duration::from_secs(5)).await;
-                    },
-                    |_| Message::HideNotification,
-                )
+            Message::OpenLinkOnCompleteToggled(open) => {
+                self.open_link_on_complete = open;
+                Command::none()
             }
 
-            Message::HideNotification => {
-                if !self.notifications.is_empty() {
-                    self.notifications.remove(0);
-                }
+            Message::ToggleDeviceDetails => {
+                self.device_details_expanded = !self.device_details_expanded;
                 Command::none()
             }
-            Message::StartScreenMirroring(_device) => {
-                self.airplay_status = crate::protocols::airplay::AirPlayStatus::Connecting;
-                Command::perform(
-                    async {
-                        tokio::time::sleep(Duration::from_secs(3)).await;
-                        crate::protocols::airplay::AirPlayStatus::Connected
-                    },
-                    Message::AirPlayStatusChanged,
-                )
-            }
+
+            Message::CopyDeviceDetails => match &self.selected_device {
+                Some(device) => {
+                    let report = device.details_report();
+                    self.add_notification(
+                        "Dettagli copiati".to_string(),
+                        format!("I dettagli di {} sono negli appunti", device.name),
+                        messages::NotificationType::Info,
+                    );
+                    iced::clipboard::write(report)
+                }
+                None => Command::none(),
+            },
+
+            Message::CopyText { label, text } => {
+                self.add_notification(
+                    format!("{} copiato", label),
+                    text.clone(),
+                    messages::NotificationType::Info,
+                );
+                iced::clipboard::write(text)
+            }
+
+            Message::ShowNotification(notification) => {
+                self.push_notification(notification);
+                Command::none()
+            }
+
+            Message::HideNotification(id) => {
+                self.notifications.dismiss(id);
+                Command::none()
+            }
+
+            Message::ExpireScanHighlights => {
+                self.scan_highlights.expire(std::time::Instant::now());
+                Command::none()
+            }
+
+            Message::ExpireNotifications => {
+                self.notifications.expire(std::time::Instant::now());
+                Command::none()
+            }
+            Message::StartScreenMirroring(device) => {
+                self.airplay_status = crate::protocols::airplay::AirPlayStatus::Connecting;
+                self.airplay_connecting_since = Some(std::time::Instant::now());
+                let Some(airplay) = self.airplay.clone() else {
+                    return self.update(Message::AirPlayStatusChanged(
+                        crate::protocols::airplay::AirPlayStatus::Failed("Servizio AirPlay non avviato".to_string()),
+                    ));
+                };
+                if device.service_type != crate::network::discovery::ServiceType::AirPlay {
+                    return Command::perform(
+                        async move {
+                            crate::protocols::airplay::AirPlayStatus::Failed(format!(
+                                "{} non è un ricevitore AirPlay",
+                                device.name
+                            ))
+                        },
+                        Message::AirPlayStatusChanged,
+                    );
+                }
+                // Invia lo schermo al ricevitore; FairPlay non è supportato e viene segnalato come errore
+                let addr = std::net::SocketAddr::new(device.address, device.port);
+                Command::perform(
+                    async move {
+                        match airplay.start_sending(addr, &device.txt_records).await {
+                            Ok(()) => crate::protocols::airplay::AirPlayStatus::Connected,
+                            // Un doppio clic non deve mostrare un errore sulla sessione già in avvio
+                            Err(e) if e.is::<crate::protocols::airplay::AlreadyStarting>() => airplay.get_status().await,
+                            // Anche una sessione già attiva resta com'è, senza notificare un errore
+                            Err(e) if crate::protocols::airplay::is_already_active(&e) => airplay.get_status().await,
+                            Err(e) => crate::protocols::airplay::AirPlayStatus::Failed(e.to_string()),
+                        }
+                    },
+                    Message::AirPlayStatusChanged,
+                )
+            }
 
             Message::StopScreenMirroring => {
                 self.airplay_status = crate::protocols::airplay::AirPlayStatus::Idle;
+                self.airplay_connecting_since = None;
+                self.airplay_preview = None;
+                match self.airplay.clone() {
+                    Some(airplay) => Command::perform(
+                        async move {
+                            if let Err(e) = airplay.stop_receiving().await {
+                                warn!("Failed to stop AirPlay stream: {}", e);
+                            }
+                        },
+                        |_| Message::Tick,
+                    ),
+                    None => Command::none(),
+                }
+            }
+
+            Message::AirPlayStatusChanged(status) => {
+                // Lo stesso stato può arrivare sia dal comando sia dagli eventi del protocollo
+                if status == self.airplay_status {
+                    return Command::none();
+                }
+                self.airplay_connecting_since = (status == crate::protocols::airplay::AirPlayStatus::Connecting)
+                    .then(std::time::Instant::now);
+                self.airplay_status = status.clone();
+                match status {
+                    crate::protocols::airplay::AirPlayStatus::Connected => {
+                        self.add_notification(
+                            "AirPlay connesso".to_string(),
+                            "Connessione AirPlay stabilita".to_string(),
+                            messages::NotificationType::Success,
+                        );
+                        self.play_completion_sound();
+                    }
+                    crate::protocols::airplay::AirPlayStatus::Failed(err) => {
+                        self.add_notification(
+                            "Errore AirPlay".to_string(),
+                            err,
+                            messages::NotificationType::Error,
+                        );
+                        self.play_failure_sound();
+                    }
+                    _ => {}
+                }
+                Command::none()
+            }
+            
+            Message::ShowMainView => {
+                self.current_view = AppView::Main;
+                Command::none()
+            }
+
+            Message::ShowSettings => {
+                self.current_view = AppView::Settings;
+                self.refresh_cache_size()
+            }
+
+            Message::ShowAbout => {
+                self.current_view = AppView::About;
+                Command::none()
+            }
+
+            Message::ThemeChanged(theme) => {
+                self.theme = theme;
+                styles::set_active_theme(&iced_theme(theme));
+                self.settings.theme = theme;
+                self.settings_view.set_theme(theme);
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist theme: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::FontScaleChanged(scale) => {
+                styles::font_size::set_scale_percent(scale.0);
+                self.settings.font_scale_percent = scale.0;
+                self.settings_view.set_font_scale(scale);
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist font scale: {}", e);
+                }
+                self.add_notification(
+                    "Dimensione del testo".to_string(),
+                    format!("Testo al {}, i testi senza una dimensione propria cambiano al prossimo avvio", scale),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::DeviceDeselected => {
+                self.selected_device = None;
+                self.pending_send = None;
+                Command::none()
+            }
+
+            Message::AirDropStatusChanged(status) => {
+                self.airdrop_status = status;
+                Command::none()
+            }
+
+            Message::Error(message) => {
+                self.add_notification("Errore".to_string(), message, messages::NotificationType::Error);
+                Command::none()
+            }
+
+            Message::Warning(message) => {
+                self.add_notification("Attenzione".to_string(), message, messages::NotificationType::Warning);
+                Command::none()
+            }
+
+            Message::Info(message) => {
+                self.add_notification("Informazione".to_string(), message, messages::NotificationType::Info);
+                Command::none()
+            }
+
+            // I log vengono scritti solo sulla console: il registro eventi è la cosa più vicina
+            Message::OpenLogFolder => {
+                self.current_view = AppView::EventLog;
+                Command::none()
+            }
+
+            Message::OpenWebsite | Message::OpenLicenses => {
+                self.open_url(REPOSITORY_URL.to_string());
+                Command::none()
+            }
+
+            Message::OpenDocumentation => {
+                self.open_url(format!("{}/blob/main/docs/DOCUMENTATION.md", REPOSITORY_URL));
+                Command::none()
+            }
+
+            Message::OpenIssues => {
+                self.open_url(format!("{}/issues", REPOSITORY_URL));
+                Command::none()
+            }
+
+            Message::OpenFeatureRequest => {
+                self.open_url(format!("{}/issues/new", REPOSITORY_URL));
+                Command::none()
+            }
+
+            Message::FileConflict(prompt) => {
+                self.pending_conflicts.push(prompt);
+                Command::none()
+            }
+
+            Message::ResolveConflict(choice) => {
+                if !self.pending_conflicts.is_empty() {
+                    self.pending_conflicts.remove(0).respond(choice);
+                }
+                Command::none()
+            }
+
+            Message::ExpireConflicts => {
+                self.pending_conflicts.retain(ConflictPrompt::is_pending);
+                Command::none()
+            }
+
+            Message::TransferRequested(prompt) => {
+                self.pending_transfers.push(prompt);
+                Command::none()
+            }
+
+            Message::RespondTransfer(accept) => {
+                if !self.pending_transfers.is_empty() {
+                    self.pending_transfers.remove(0).respond(accept);
+                }
+                Command::none()
+            }
+
+            Message::AcceptAndTrust => {
+                if self.pending_transfers.is_empty() {
+                    return Command::none();
+                }
+                let prompt = self.pending_transfers.remove(0);
+                prompt.respond(true);
+                let Some(id) = prompt.device_id.clone() else {
+                    return Command::none();
+                };
+                if !self.settings.trusted_devices.iter().any(|device| device.id == id) {
+                    self.settings.trusted_devices.push(TrustedDevice { id, name: prompt.sender.clone() });
+                    self.apply_trusted_devices();
+                }
+                let content = if self.settings.visibility.auto_accept_from_contacts {
+                    format!("I prossimi invii da {} saranno accettati senza conferma", prompt.sender)
+                } else {
+                    format!(
+                        "Attiva l'accettazione automatica per ricevere da {} senza conferma",
+                        prompt.sender
+                    )
+                };
+                self.add_notification(
+                    "Dispositivo attendibile".to_string(),
+                    content,
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::RemoveTrustedDevice(id) => {
+                self.settings.trusted_devices.retain(|device| device.id != id);
+                self.apply_trusted_devices();
+                Command::none()
+            }
+
+            Message::AirDropVisibilityChanged(visibility) => {
+                self.settings_view.set_airdrop_visibility(visibility.clone());
+                self.settings.visibility.visibility = visibility.into();
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirDrop visibility: {}", e);
+                }
+                self.apply_visibility()
+            }
+
+            Message::AutoAcceptFromContactsToggled(enabled) => {
+                self.settings_view.set_auto_accept_from_contacts(enabled);
+                self.settings.visibility.auto_accept_from_contacts = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirDrop auto-accept: {}", e);
+                }
+                self.apply_visibility()
+            }
+
+            Message::SendMethodChanged(method) => {
+                self.settings_view.set_send_method(method.clone());
+                self.settings.transfer_method = method.into();
+                self.apply_send_method()
+            }
+
+            Message::StayConnectedToggled(enabled) => {
+                self.settings_view.set_stay_connected(enabled);
+                self.settings.after_transfer = if enabled {
+                    crate::protocols::connection_pool::AfterTransfer::StayConnected
+                } else {
+                    crate::protocols::connection_pool::AfterTransfer::Disconnect
+                };
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist connection setting: {}", e);
+                }
+                self.add_notification(
+                    "Connessione dopo l'invio".to_string(),
+                    "La modifica sarà applicata al prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::CompressionChanged(choice) => {
+                self.settings_view.set_compression(choice);
+                self.settings.transfer_compression = choice.0;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist compression setting: {}", e);
+                }
+                self.add_notification(
+                    "Compressione".to_string(),
+                    "La modifica sarà applicata al prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::SelectSharedFolder => {
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Seleziona la cartella condivisa")
+                            .pick_folder()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::SharedFolderSelected,
+                )
+            }
+
+            Message::SharedFolderSelected(Some(folder)) => {
+                self.settings_view.set_shared_folder(Some(folder.clone()));
+                self.settings.shared_folder = Some(folder);
+                self.apply_send_method()
+            }
+
+            Message::SharedFolderSelected(None) => Command::none(),
+
+            Message::ClearSharedFolder => {
+                self.settings_view.set_shared_folder(None);
+                self.settings.shared_folder = None;
+                self.apply_send_method()
+            }
+
+            Message::CompletionChoiceChanged(choice) => {
+                self.settings_view.set_completion_choice(choice.clone());
+                self.settings.completion_action = choice.into();
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist completion action: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::IpModeChanged(choice) => {
+                let mode = choice.clone().into();
+                if mode == self.settings.ip_mode {
+                    return Command::none();
+                }
+                self.settings_view.set_ip_mode(choice);
+                self.settings.ip_mode = mode;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist IP mode: {}", e);
+                }
+                self.add_notification(
+                    "Protocollo IP".to_string(),
+                    "La modifica sarà applicata al prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::ConflictPolicyChanged(policy) => {
+                self.settings_view.set_conflict_policy(policy.clone());
+                self.settings.file_conflict_policy = policy.into();
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist conflict policy: {}", e);
+                }
+                if let Some(airdrop) = &self.airdrop {
+                    airdrop.set_conflict_policy(self.settings.file_conflict_policy);
+                }
+                Command::none()
+            }
+
+            Message::DismissPrivilegeBanner => {
+                self.privilege_banner_dismissed = true;
+                Command::none()
+            }
+
+            Message::RunDiagnostics => {
+                self.current_view = AppView::Diagnostics;
+                self.update(Message::RefreshDiagnostics)
+            }
+
+            Message::RefreshDiagnostics => {
+                Command::perform(
+                    crate::utils::bug_report::collect(self.bug_report_input()),
+                    Message::DiagnosticsRefreshed,
+                )
+            }
+
+            Message::DiagnosticsRefreshed(report) => {
+                self.diagnostics_report = Some(report);
+                Command::none()
+            }
+
+            Message::VerifyNetwork => {
+                self.settings_view.start_network_test();
+                let config = crate::utils::self_test::SelfTestConfig {
+                    ports: self.diagnostics.ports,
+                    own_ports: self.own_ports(),
+                    multicast: self.settings.multicast.clone(),
+                    awdl: self.awdl_availability,
+                    elevated: self.diagnostics.elevated,
+                };
+                Command::perform(crate::utils::self_test::run(config), Message::NetworkVerified)
+            }
+
+            Message::NetworkVerified(report) => {
+                if report.passed() {
+                    event_log::info("Verifica rete", "Tutti i controlli superati");
+                } else {
+                    event_log::warning("Verifica rete", report.to_text());
+                }
+                self.settings_view.set_network_report(report);
+                Command::none()
+            }
+
+            Message::CopyNetworkReport => match self.settings_view.network_report() {
+                Some(report) => {
+                    let text = format!("{}\n\n{}", self.diagnostics.summary(), report.to_text());
+                    self.add_notification(
+                        "Report copiato".to_string(),
+                        "Il report della rete è negli appunti".to_string(),
+                        messages::NotificationType::Info,
+                    );
+                    iced::clipboard::write(text)
+                }
+                None => Command::none(),
+            },
+
+            Message::CopyDiagnostics => {
+                Command::perform(
+                    crate::utils::bug_report::collect(self.bug_report_input()),
+                    Message::DiagnosticsCollected,
+                )
+            }
+
+            Message::DiagnosticsCollected(report) => {
+                self.add_notification(
+                    "Diagnostica copiata".to_string(),
+                    "Incolla il testo negli appunti nella segnalazione".to_string(),
+                    messages::NotificationType::Info,
+                );
+                iced::clipboard::write(report.to_markdown(self.settings_view.full_macs()))
+            }
+
+            Message::AddFirewallRules => {
+                self.adding_firewall_rules = true;
+                Command::perform(
+                    crate::utils::firewall::add_rules(self.diagnostics.ports, self.diagnostics.elevated),
+                    Message::FirewallRulesAdded,
+                )
+            }
+
+            Message::FirewallRulesAdded(result) => {
+                self.adding_firewall_rules = false;
+                match result {
+                    Ok(()) => {
+                        event_log::info("Firewall", "Regole in entrata aggiunte");
+                        self.add_notification(
+                            "Regole aggiunte".to_string(),
+                            "Windows Firewall consente ora le connessioni ad AirWin".to_string(),
+                            messages::NotificationType::Success,
+                        );
+                    }
+                    Err(e) => {
+                        event_log::warning("Firewall", format!("Regole non aggiunte: {}", e));
+                        self.add_notification(
+                            "Regole non aggiunte".to_string(),
+                            format!("{}. Copia i comandi ed eseguili come amministratore", e),
+                            messages::NotificationType::Error,
+                        );
+                    }
+                }
+                self.update(Message::RefreshDiagnostics)
+            }
+
+            Message::CopyFirewallCommands => {
+                self.add_notification(
+                    "Comandi copiati".to_string(),
+                    "Incollali in un prompt dei comandi aperto come amministratore".to_string(),
+                    messages::NotificationType::Info,
+                );
+                iced::clipboard::write(crate::utils::firewall::rule_commands_text(&self.diagnostics.ports))
+            }
+
+            Message::OpenFirewallSettings => {
+                if let Err(e) = crate::utils::firewall::open_settings() {
+                    self.add_notification(
+                        "Impossibile aprire il firewall".to_string(),
+                        e,
+                        messages::NotificationType::Error,
+                    );
+                }
+                Command::none()
+            }
+
+            Message::ChangeAirPlayPort => {
+                let show_settings = self.update(Message::ShowSettings);
+                Command::batch([
+                    show_settings,
+                    iced::widget::scrollable::snap_to(
+                        views::settings_view::scrollable_id(),
+                        views::settings_view::NETWORK_SECTION_OFFSET,
+                    ),
+                    iced::widget::text_input::focus(views::settings_view::custom_port_input_id()),
+                ])
+            }
+
+            Message::FullMacsToggled(enabled) => {
+                self.settings_view.set_full_macs(enabled);
+                Command::none()
+            }
+
+            Message::ShowEventLog => {
+                self.current_view = AppView::EventLog;
+                Command::none()
+            }
+
+            Message::EventLogged(event) => {
+                self.event_log.push(event);
+                if self.event_log.len() > MAX_LOG_EVENTS {
+                    self.event_log.remove(0);
+                }
+                Command::none()
+            }
+
+            Message::EventLogFilterChanged(filter) => {
+                self.event_log_filter = filter;
+                Command::none()
+            }
+
+            Message::CopyEventLog => {
+                self.add_notification(
+                    "Registro copiato".to_string(),
+                    format!("{} eventi copiati negli appunti", self.event_log.len()),
+                    messages::NotificationType::Info,
+                );
+                iced::clipboard::write(event_log::format_events(&self.event_log))
+            }
+
+            Message::AirPlayIdleTimeoutChanged(timeout) => {
+                self.settings_view.set_airplay_idle_timeout(timeout);
+                self.settings.airplay_idle_timeout_mins = timeout.0;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirPlay idle timeout: {}", e);
+                }
+                self.apply_idle_timeout()
+            }
+
+            Message::AirPlayShowCursorToggled(show) => {
+                self.settings_view.set_airplay_show_cursor(show);
+                self.settings.airplay_show_cursor = show;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirPlay cursor setting: {}", e);
+                }
+                if let Some(airplay) = &self.airplay {
+                    airplay.set_draw_cursor(show);
+                }
+                Command::none()
+            }
+
+            Message::AirPlayIdleStopped(idle) => {
+                self.add_notification(
+                    "AirPlay fermato".to_string(),
+                    format!(
+                        "Nessuna attività da {} minuti: la trasmissione è stata interrotta",
+                        idle.as_secs() / 60
+                    ),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::AirPlayQualityChanged(quality) => {
+                self.settings_view.set_airplay_quality(quality.clone());
+                self.settings.airplay_quality = quality.into();
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirPlay quality: {}", e);
+                }
+
+                match self.airplay.clone() {
+                    Some(airplay) => {
+                        let tier = self.settings.airplay_quality;
+                        Command::perform(
+                            async move { airplay.set_quality_tier(tier).await },
+                            |_| Message::PollAirPlayQuality,
+                        )
+                    }
+                    None => Command::none(),
+                }
+            }
+
+            Message::PollAirPlayQuality => match self.airplay.clone() {
+                Some(airplay) => {
+                    poll_rate::record("AirPlay quality");
+                    let stats_source = airplay.clone();
+                    let preview_source = airplay.clone();
+                    Command::batch([
+                        Command::perform(
+                            async move { airplay.get_effective_quality().await },
+                            |(quality, fps)| Message::AirPlayEffectiveQuality(quality, fps),
+                        ),
+                        Command::perform(
+                            async move { stats_source.get_stats().await },
+                            Message::AirPlayStats,
+                        ),
+                        Command::perform(
+                            async move { preview_source.get_preview_frame().await },
+                            |frame| match frame {
+                                Some(frame) => Message::ScreenMirroringFrame(frame.jpeg.clone()),
+                                None => Message::Tick,
+                            },
+                        ),
+                    ])
+                }
+                None => Command::none(),
+            },
+
+            Message::AirPlayEffectiveQuality(quality, fps) => {
+                self.airplay_effective_quality = Some((quality, fps));
+                Command::none()
+            }
+
+            Message::AirPlayStats(stats) => {
+                self.airplay_stats = stats;
+                Command::none()
+            }
+
+            Message::AwdlAvailabilityChanged(availability) => {
+                self.awdl_availability = availability;
+                Command::none()
+            }
+
+            Message::AdvertisedNameChanged(name) => {
+                self.settings_view.set_advertised_name(Some(name.clone()));
+                self.advertised_name = Some(name);
+                Command::none()
+            }
+
+            Message::AirDropSendOnlyChanged(reason) => {
+                if let Some(previous) = self.send_only_limitation.take() {
+                    self.diagnostics.limitations.retain(|limitation| *limitation != previous);
+                }
+                if let Some(reason) = reason {
+                    let limitation = format!(
+                        "Servizi mDNS non registrati, gli altri dispositivi non vedono questo PC ma puoi ancora inviare file ({})",
+                        reason
+                    );
+                    self.diagnostics.limitations.push(limitation.clone());
+                    self.send_only_limitation = Some(limitation);
+                    self.privilege_banner_dismissed = false;
+                    self.add_notification(
+                        "AirDrop solo in invio".to_string(),
+                        "Registrazione mDNS non riuscita, la ricezione non è disponibile".to_string(),
+                        messages::NotificationType::Warning,
+                    );
+                }
+                Command::none()
+            }
+
+            Message::StartupSummary(summary) => {
+                let notification_type = if summary.is_discoverable() && summary.failed.is_empty() {
+                    messages::NotificationType::Success
+                } else {
+                    messages::NotificationType::Warning
+                };
+                self.add_notification(summary.title().to_string(), summary.message(), notification_type);
+                Command::none()
+            }
+
+            Message::TlsSetupFailed(error) => {
+                self.tls_failure = Some(error);
+                Command::none()
+            }
+
+            Message::DismissTlsFailure => {
+                self.tls_failure = None;
+                Command::none()
+            }
+
+            Message::CertificateChanged(peer) => {
+                self.push_notification(
+                    messages::NotificationMessage::error(
+                        "Certificato cambiato",
+                        format!(
+                            "Il dispositivo {} presenta un certificato diverso da quello memorizzato. Se è lo stesso dispositivo, reinstallato o sostituito, dimentica il certificato e invia di nuovo",
+                            peer
+                        ),
+                    )
+                    .with_action("Dimentica certificato", Message::ForgetCertificate(peer)),
+                );
+                Command::none()
+            }
+
+            Message::ForgetCertificate(peer) => match self.airdrop.clone() {
+                Some(airdrop) => Command::perform(
+                    async move {
+                        let result = airdrop.forget_certificate(&peer).await.map_err(|e| e.to_string());
+                        (peer, result)
+                    },
+                    |(peer, result)| Message::CertificateForgotten(peer, result),
+                ),
+                None => Command::none(),
+            },
+
+            Message::CertificateForgotten(peer, result) => {
+                match result {
+                    Ok(()) => self.add_notification(
+                        "Certificato dimenticato".to_string(),
+                        format!("Il prossimo invio a {} memorizzerà il suo nuovo certificato", peer),
+                        messages::NotificationType::Info,
+                    ),
+                    Err(e) => self.add_notification(
+                        "Impossibile dimenticare il certificato".to_string(),
+                        e,
+                        messages::NotificationType::Error,
+                    ),
+                }
+                Command::none()
+            }
+
+            Message::ScreenMirroringFrame(jpeg) => {
+                self.airplay_preview = Some(iced::widget::image::Handle::from_memory(jpeg));
+                Command::none()
+            }
+
+            Message::ShowNotificationsToggled(enabled) => {
+                self.settings_view.set_show_notifications(enabled);
+                self.settings.show_notifications = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist notification setting: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::SystemNotificationsToggled(enabled) => {
+                self.settings_view.set_system_notifications(enabled);
+                self.settings.notification_backend = if enabled {
+                    crate::utils::config::NotificationBackendKind::System
+                } else {
+                    crate::utils::config::NotificationBackendKind::InApp
+                };
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist notification setting: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::PlaySoundToggled(enabled) => {
+                self.settings_view.set_play_sound_on_complete(enabled);
+                self.settings.play_sound_on_complete = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist sound setting: {}", e);
+                }
                 Command::none()
             }
 
-            Message::AirPlayStatusChanged(status) => {
-                self.airplay_status = status.clone();
-                match status {
-                    crate::protocols::airplay::AirPlayStatus::Connected => self.add_notification(
-                        "AirPlay connesso".to_string(),
-                        "Connessione AirPlay stabilita".to_string(),
-                        messages::NotificationType::Success,
-                    ),
-                    crate::protocols::airplay::AirPlayStatus::Failed(err) => self.add_notification(
-                        "Errore AirPlay".to_string(),
-                        err,
-                        messages::NotificationType::Error,
+            Message::MaxConcurrentTransfersChanged(max) => {
+                self.settings_view.set_max_concurrent_transfers(max);
+                self.settings.max_concurrent_transfers = max;
+                self.apply_transfer_limits()
+            }
+
+            Message::MaxTransfersPerPeerChanged(max) => {
+                self.settings_view.set_max_transfers_per_peer(max);
+                self.settings.max_transfers_per_peer = max;
+                self.apply_transfer_limits()
+            }
+
+            Message::TestNotification => {
+                if self.settings.show_notifications {
+                    self.add_notification(
+                        "Notifica di prova".to_string(),
+                        "Le notifiche funzionano correttamente".to_string(),
+                        messages::NotificationType::Success,
+                    );
+                } else {
+                    self.status_message = "Le notifiche sono disattivate".to_string();
+                }
+                self.play_completion_sound();
+                Command::none()
+            }
+
+            Message::AirDropEnabledToggled(enabled) => {
+                self.settings_view.set_airdrop_enabled(enabled);
+                self.settings.airdrop_enabled = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirDrop setting: {}", e);
+                }
+
+                match self.airdrop.clone() {
+                    Some(airdrop) => Command::perform(
+                        async move { airdrop.set_enabled(enabled).await.map_err(|e| e.to_string()) },
+                        move |result| Message::ServiceToggled("AirDrop", enabled, result),
+                    ),
+                    None => Command::none(),
+                }
+            }
+
+            Message::AirPlayEnabledToggled(enabled) => {
+                self.settings_view.set_airplay_enabled(enabled);
+                self.settings.airplay_enabled = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirPlay setting: {}", e);
+                }
+
+                let port = self.settings.airplay_port.unwrap_or(self.diagnostics.ports.airplay);
+                match self.airplay.clone() {
+                    Some(airplay) => Command::perform(
+                        async move {
+                            let result = if enabled {
+                                airplay.restart_server(port).await
+                            } else {
+                                airplay.stop_server().await
+                            };
+                            result.map_err(|e| e.to_string())
+                        },
+                        move |result| Message::ServiceToggled("AirPlay", enabled, result),
+                    ),
+                    None => Command::none(),
+                }
+            }
+
+            Message::ServiceToggled(service, enabled, result) => {
+                match result {
+                    Ok(()) => self.add_notification(
+                        format!("{} {}", service, if enabled { "attivato" } else { "disattivato" }),
+                        if enabled {
+                            format!("Il servizio {} è in esecuzione", service)
+                        } else {
+                            format!("Il servizio {} è stato fermato", service)
+                        },
+                        messages::NotificationType::Info,
+                    ),
+                    // Avviare AirPlay fallisce quando la porta è occupata o bloccata
+                    Err(e) if service == "AirPlay" && enabled => self.push_notification(
+                        messages::NotificationMessage::airplay_bind_failed(format!("Errore {}", service), e),
+                    ),
+                    Err(e) => self.add_notification(
+                        format!("Errore {}", service),
+                        e,
+                        messages::NotificationType::Error,
+                    ),
+                }
+                Command::none()
+            }
+
+            Message::CaptureRegionChanged(field, text) => {
+                if text.chars().all(|c| c.is_ascii_digit() || c == '-') {
+                    self.settings_view.set_capture_region_text(field, text);
+                }
+                Command::none()
+            }
+
+            Message::CaptureRegionSubmitted => {
+                let region = match self.settings_view.parse_capture_region() {
+                    Ok(region) => region,
+                    Err(e) => {
+                        self.add_notification(
+                            "Area non valida".to_string(),
+                            e,
+                            messages::NotificationType::Error,
+                        );
+                        return Command::none();
+                    }
+                };
+
+                // Un'area che esce dallo schermo viene ridotta alla parte visibile
+                let region = match (region, crate::protocols::airplay::primary_screen_size()) {
+                    (Some(selected), Some((width, height))) => match selected.clamp_to(width, height) {
+                        Ok(clamped) => {
+                            if clamped != selected {
+                                self.add_notification(
+                                    "Area ridotta".to_string(),
+                                    format!(
+                                        "L'area esce dallo schermo {}x{}: verrà trasmesso {}x{} da ({}, {})",
+                                        width, height, clamped.width, clamped.height, clamped.x, clamped.y
+                                    ),
+                                    messages::NotificationType::Warning,
+                                );
+                            }
+                            Some(clamped)
+                        }
+                        Err(e) => {
+                            self.add_notification(
+                                "Area non valida".to_string(),
+                                e.to_string(),
+                                messages::NotificationType::Error,
+                            );
+                            return Command::none();
+                        }
+                    },
+                    (region, _) => region,
+                };
+                self.set_capture_region(region)
+            }
+
+            Message::CaptureRegionCleared => self.set_capture_region(None),
+
+            Message::CustomPortChanged(text) => {
+                if text.chars().all(|c| c.is_ascii_digit()) {
+                    self.settings_view.set_custom_port_text(text);
+                }
+                Command::none()
+            }
+
+            Message::FriendlyNameChanged(text) => {
+                self.settings_view.set_friendly_name_text(text);
+                Command::none()
+            }
+
+            Message::FriendlyNameSubmitted => {
+                let name = self.settings_view.friendly_name();
+                if let Some(Err(e)) = name.as_deref().map(crate::utils::config::validate_friendly_name) {
+                    self.add_notification(
+                        "Nome non valido".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                    return Command::none();
+                }
+                if name == self.settings.friendly_name {
+                    return Command::none();
+                }
+
+                self.settings.friendly_name = name;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist friendly name: {}", e);
+                }
+                self.add_notification(
+                    "Nome del dispositivo".to_string(),
+                    "Il nuovo nome sarà annunciato al prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::NameSuffixChanged(text) => {
+                self.settings_view.set_name_suffix_text(text);
+                Command::none()
+            }
+
+            Message::NameSuffixSubmitted => {
+                let suffix = self.settings_view.name_suffix();
+                if let Some(Err(e)) = suffix.as_deref().map(crate::utils::config::validate_name_suffix) {
+                    self.add_notification(
+                        "Suffisso non valido".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                    return Command::none();
+                }
+                if suffix == self.settings.name_suffix {
+                    return Command::none();
+                }
+
+                self.settings.name_suffix = suffix;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist name suffix: {}", e);
+                }
+                self.add_notification(
+                    "Nome del dispositivo".to_string(),
+                    "Il nuovo nome sarà annunciato al prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                Command::none()
+            }
+
+            Message::AvoidNameCollisionsToggled(enabled) => {
+                self.settings_view.set_avoid_name_collisions(enabled);
+                self.settings.avoid_name_collisions = enabled;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist name collision setting: {}", e);
+                }
+                Command::none()
+            }
+
+            Message::CustomPortSubmitted => {
+                let port = match self.settings_view.parse_custom_port() {
+                    Ok(port) => port,
+                    Err(e) => {
+                        self.add_notification(
+                            "Porta non valida".to_string(),
+                            e,
+                            messages::NotificationType::Error,
+                        );
+                        return Command::none();
+                    }
+                };
+
+                if let Some(service) = port.and_then(|port| self.diagnostics.ports.used_by_other_than_airplay(port)) {
+                    self.add_notification(
+                        "Porta non valida".to_string(),
+                        format!("La porta è già usata da {}", service),
+                        messages::NotificationType::Error,
+                    );
+                    return Command::none();
+                }
+
+                self.settings.airplay_port = port;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist AirPlay port: {}", e);
+                }
+
+                // Con AirPlay disattivato la porta verrà usata alla riattivazione
+                let port = port.unwrap_or(self.diagnostics.ports.airplay);
+                match self.airplay.clone().filter(|_| self.settings.airplay_enabled) {
+                    Some(airplay) => Command::perform(
+                        async move {
+                            airplay
+                                .restart_server(port)
+                                .await
+                                .map(|_| port)
+                                .map_err(|e| e.to_string())
+                        },
+                        Message::AirPlayServerRestarted,
+                    ),
+                    None => Command::none(),
+                }
+            }
+
+            Message::ClearCache => {
+                let cache = crate::utils::cache::Cache::from_settings(&self.settings);
+                Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || {
+                            cache.clear(&crate::utils::cache::protected_dirs())
+                        })
+                        .await
+                        .unwrap_or(0)
+                    },
+                    Message::CacheCleared,
+                )
+            }
+
+            Message::CacheCleared(freed) => {
+                self.add_notification(
+                    "Cache pulita".to_string(),
+                    format!("Liberati {}", components::format_bytes(freed)),
+                    messages::NotificationType::Success,
+                );
+                self.refresh_cache_size()
+            }
+
+            Message::CacheSizeComputed(bytes) => {
+                self.settings_view.set_cache_size(bytes);
+                Command::none()
+            }
+
+            Message::SelectCacheDir => {
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Seleziona la cartella della cache")
+                            .pick_folder()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::CacheDirSelected,
+                )
+            }
+
+            Message::CacheDirSelected(Some(dir)) => {
+                let cache = crate::utils::cache::Cache::new(dir.clone());
+                if let Err(e) = cache.ensure() {
+                    self.add_notification(
+                        "Cartella cache non valida".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    );
+                    return Command::none();
+                }
+
+                self.settings.cache_dir = Some(dir.clone());
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist cache directory: {}", e);
+                }
+                self.settings_view.set_cache_dir(dir);
+                self.add_notification(
+                    "Cartella cache cambiata".to_string(),
+                    "I file ricevuti verranno salvati nella nuova cartella dal prossimo avvio".to_string(),
+                    messages::NotificationType::Info,
+                );
+                self.refresh_cache_size()
+            }
+
+            Message::CacheDirSelected(None) => Command::none(),
+
+            Message::ResetSettings => {
+                self.settings_view.show_reset_prompt();
+                Command::none()
+            }
+
+            Message::ResetWindowToggled(reset_window) => {
+                self.settings_view.set_reset_window(reset_window);
+                Command::none()
+            }
+
+            Message::CancelResetSettings => {
+                self.settings_view.hide_reset_prompt();
+                Command::none()
+            }
+
+            Message::ConfirmResetSettings => {
+                let reset_window = self.settings_view.reset_window();
+                let previous = std::mem::take(&mut self.settings);
+                if !reset_window {
+                    self.settings.mini_mode = previous.mini_mode;
+                    self.settings.mini_always_on_top = previous.mini_always_on_top;
+                    self.settings.minimize_to_tray = previous.minimize_to_tray;
+                }
+                // La configurazione iniziale non viene riproposta
+                self.settings.onboarding_complete = previous.onboarding_complete;
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist reset settings: {}", e);
+                }
+                self.add_notification(
+                    "Impostazioni ripristinate".to_string(),
+                    if self.settings.needs_restart_from(&previous) {
+                        "Sono stati ripristinati i valori predefiniti, alcune modifiche saranno applicate al prossimo avvio".to_string()
+                    } else {
+                        "Sono stati ripristinati i valori predefiniti".to_string()
+                    },
+                    messages::NotificationType::Info,
+                );
+                self.apply_settings(&previous)
+            }
+
+            Message::ExportSettings => {
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Esporta impostazioni")
+                            .set_file_name("airwin-settings.json")
+                            .add_filter("JSON", &["json"])
+                            .save_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::SettingsExportPathSelected,
+                )
+            }
+
+            Message::SettingsExportPathSelected(Some(path)) => {
+                let result = self
+                    .settings
+                    .export_json()
+                    .and_then(|json| std::fs::write(&path, json).map_err(Into::into));
+                match result {
+                    Ok(()) => self.add_notification(
+                        "Impostazioni esportate".to_string(),
+                        path.display().to_string(),
+                        messages::NotificationType::Success,
+                    ),
+                    Err(e) => self.add_notification(
+                        "Esportazione non riuscita".to_string(),
+                        e.to_string(),
+                        messages::NotificationType::Error,
+                    ),
+                }
+                Command::none()
+            }
+
+            Message::SettingsExportPathSelected(None) => Command::none(),
+
+            Message::ImportSettings => {
+                Command::perform(
+                    async {
+                        rfd::AsyncFileDialog::new()
+                            .set_title("Importa impostazioni")
+                            .add_filter("JSON", &["json"])
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::SettingsImportFileSelected,
+                )
+            }
+
+            Message::SettingsImportFileSelected(Some(path)) => {
+                let imported = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| crate::utils::config::Settings::import_json(&json, &self.settings));
+                let imported = match imported {
+                    Ok(settings) => settings,
+                    Err(e) => {
+                        self.add_notification(
+                            "Importazione non riuscita".to_string(),
+                            format!("{}: {}", path.display(), e),
+                            messages::NotificationType::Error,
+                        );
+                        return Command::none();
+                    }
+                };
+
+                let previous = std::mem::replace(&mut self.settings, imported);
+                if let Err(e) = self.settings.save() {
+                    warn!("Failed to persist imported settings: {}", e);
+                }
+                self.add_notification(
+                    "Impostazioni importate".to_string(),
+                    if self.settings.needs_restart_from(&previous) {
+                        "Alcune modifiche, come le porte, saranno applicate al prossimo avvio".to_string()
+                    } else {
+                        path.display().to_string()
+                    },
+                    messages::NotificationType::Success,
+                );
+                self.apply_settings(&previous)
+            }
+
+            Message::SettingsImportFileSelected(None) => Command::none(),
+
+            Message::AirPlayServerRestarted(result) => {
+                match result {
+                    Ok(port) => {
+                        self.settings_view.set_custom_port(self.settings.airplay_port);
+                        self.add_notification(
+                            "Server AirPlay riavviato".to_string(),
+                            format!("In ascolto sulla porta {}", port),
+                            messages::NotificationType::Success,
+                        );
+                    }
+                    Err(e) => self.push_notification(
+                        messages::NotificationMessage::airplay_bind_failed("Riavvio AirPlay fallito", e),
                     ),
-                    _ => {}
                 }
                 Command::none()
             }
-            
-            // Handle all other message variants with a wildcard pattern
-            _ => Command::none(),
+
+            // Serve solo a ridisegnare la vista: indicatore di scansione e tempo dall'ultima
+            Message::Tick => Command::none(),
+
+            // Nessuna vista li emette ancora, oppure non hanno effetto sui servizi:
+            // elencati per esteso così una nuova variante non viene ignorata in silenzio
+            Message::WindowResized(..)
+            | Message::ShowActionDialog(_)
+            | Message::HideActionDialog
+            | Message::ClearError
+            | Message::HideError
+            | Message::ToggleDebugMode
+            | Message::LogLevelChanged => Command::none(),
         }
     }
 
     fn view(&self) -> Element<Self::Message> {
-        match self.current_view {
+        let content = match self.current_view {
             AppView::Loading => self.loading_view(),
             AppView::Main => self.main_view(),
             AppView::Settings => self.settings_view(),
             AppView::About => self.about_view(),
+            AppView::Mini => self.mini_view(),
+            AppView::EventLog => self.event_log_view(),
+            AppView::Diagnostics => self.diagnostics_view(),
+            AppView::Onboarding => self.onboarding_view(),
+        };
+
+        let content = if self.resume_prompt {
+            iced::widget::column![self.resume_prompt_view(), content].into()
+        } else {
+            content
+        };
+
+        let content = match &self.tls_failure {
+            Some(error) => iced::widget::column![self.tls_failure_view(error), content].into(),
+            None => content,
+        };
+
+        if self.close_prompt {
+            iced::widget::column![self.close_prompt_view(), content].into()
+        } else {
+            content
         }
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
-        // Subscription per aggiornamenti periodici se necessario
-        Subscription::none()
+        // Scorciatoia Ctrl+M per la modalità compatta
+        let shortcuts = keyboard::on_key_press(|key, modifiers| match key.as_ref() {
+            keyboard::Key::Character("m") if modifiers.command() => Some(Message::ToggleMiniMode),
+            _ => None,
+        });
+
+        // File trascinati sulla finestra e richieste di chiusura
+        let file_drops = event::listen_with(|event, _status| match event {
+            iced::Event::Window(_, window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+            iced::Event::Window(_, window::Event::CloseRequested) => Some(Message::WindowCloseRequested),
+            _ => None,
+        });
+
+        let mut subscriptions = vec![shortcuts, file_drops, event_log_events()];
+
+        // Anima l'indicatore e il timer della scansione, poi aggiorna il tempo dall'ultima
+        if self.is_scanning {
+            subscriptions.push(iced::time::every(Duration::from_millis(250)).map(|_| Message::Tick));
+        } else if self.last_scan.is_some() {
+            subscriptions.push(iced::time::every(Duration::from_secs(1)).map(|_| Message::Tick));
+        }
+
+        // Anima l'indicatore della connessione AirPlay
+        if self.airplay_connecting_since.is_some() {
+            subscriptions.push(iced::time::every(Duration::from_millis(250)).map(|_| Message::Tick));
+        }
+
+        // Aggiorna qualità e fps effettivi durante lo streaming
+        if self.airplay.is_some()
+            && self.airplay_status == crate::protocols::airplay::AirPlayStatus::Connected
+        {
+            subscriptions.push(
+                iced::time::every(Duration::from_secs(1)).map(|_| Message::PollAirPlayQuality),
+            );
+        }
+        // Aggiorna la diagnostica finché è aperta
+        if self.current_view == AppView::Diagnostics {
+            subscriptions.push(iced::time::every(DIAGNOSTICS_REFRESH_INTERVAL).map(|_| Message::RefreshDiagnostics));
+        }
+        // Anima i dispositivi comparsi o spariti finché sono evidenziati
+        if self.scan_highlights.is_active() {
+            subscriptions.push(iced::time::every(Duration::from_millis(100)).map(|_| Message::ExpireScanHighlights));
+        }
+        // Chiude le notifiche scadute
+        if !self.notifications.is_empty() {
+            subscriptions.push(iced::time::every(Duration::from_millis(500)).map(|_| Message::ExpireNotifications));
+        }
+        // Toglie le richieste di conflitto scadute o di trasferimenti interrotti
+        if !self.pending_conflicts.is_empty() {
+            subscriptions.push(iced::time::every(Duration::from_secs(1)).map(|_| Message::ExpireConflicts));
+        }
+        if let Some(events) = &self.incoming_files {
+            subscriptions.push(incoming_files(events.clone()));
+        }
+        if let Some(airdrop) = &self.airdrop {
+            subscriptions.push(conflict_prompts(airdrop.conflict_prompts()));
+            subscriptions.push(transfer_prompts(airdrop.transfer_prompts()));
+            subscriptions.push(send_only_mode(airdrop.send_only()));
+            subscriptions.push(advertised_name(airdrop.advertised_name()));
+            subscriptions.push(tls_failures(airdrop.tls_failures()));
+            subscriptions.push(certificate_changes(airdrop.certificate_changes()));
+            if self.file_transfer_progress.is_some() {
+                subscriptions.push(send_progress(airdrop.clone()));
+            }
+        }
+        if let Some(awdl) = &self.awdl {
+            subscriptions.push(awdl_availability(awdl.clone()));
+        }
+        if let Some(summary) = &self.startup_summary {
+            subscriptions.push(startup_summary(summary.clone()));
+        }
+        if let Some(airplay) = &self.airplay {
+            subscriptions.push(airplay_status(airplay.clone()));
+            subscriptions.push(airplay_idle_stops(airplay.idle_stops()));
+        }
+
+        Subscription::batch(subscriptions)
     }
 
     fn theme(&self) -> Self::Theme {
-        match self.theme {
-            Theme::Light => IcedTheme::Light,
-            Theme::Dark => IcedTheme::Dark,
-        }
+        iced_theme(self.theme)
+    }
+}
+
+/// Tema di iced per il tema scelto nelle impostazioni
+fn iced_theme(theme: Theme) -> IcedTheme {
+    match theme {
+        Theme::Light => IcedTheme::Light,
+        Theme::Dark => IcedTheme::Dark,
+        Theme::HighContrast => styles::high_contrast_theme(),
     }
 }
 
@@ -386,22 +2518,544 @@ impl AirWinApp {
         components::loading_state(&self.status_message)
     }
 
+    /// Richiesta alla prima chiusura della finestra
+    fn close_prompt_view(&self) -> Element<Message> {
+        components::warning_container(
+            iced::widget::row![
+                components::body_text("Chiudendo la finestra AirWin deve restare attivo per ricevere file?"),
+                iced::widget::Space::with_width(iced::Length::Fill),
+                components::secondary_button("Annulla", Message::CancelClose),
+                components::secondary_button("Esci", Message::CloseChoiceMade(false)),
+                components::primary_button("Riduci a icona", Message::CloseChoiceMade(true)),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(iced::Alignment::Center)
+            .into(),
+        )
+        .into()
+    }
+
+    /// Avviso di connessione sicura non configurabile, finché non viene chiuso
+    fn tls_failure_view(&self, error: &str) -> Element<Message> {
+        components::error_container(
+            iced::widget::row![
+                iced::widget::column![
+                    components::body_text("Impossibile preparare la connessione sicura"),
+                    components::secondary_text(
+                        "AirWin non è riuscito a creare il certificato usato per cifrare i trasferimenti, \
+                         quindi non può inviare né ricevere file. Riavvia AirWin; se l'errore si ripete, \
+                         allega la diagnostica a una segnalazione.",
+                    ),
+                    components::muted_text(error),
+                ]
+                .spacing(styles::spacing::TINY)
+                .width(iced::Length::Fill),
+                components::secondary_button("Copia diagnostica", Message::CopyDiagnostics),
+                components::primary_button("Chiudi", Message::DismissTlsFailure),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(iced::Alignment::Center)
+            .into(),
+        )
+        .into()
+    }
+
+    /// Richiesta all'avvio di riprendere gli invii rimasti in coda
+    fn resume_prompt_view(&self) -> Element<Message> {
+        let queued = self.transfers.restored();
+        let targets: std::collections::BTreeSet<_> = queued.iter().map(|send| send.target.as_str()).collect();
+        components::warning_container(
+            iced::widget::row![
+                components::body_text(&format!(
+                    "{} file non inviati alla chiusura precedente ({})",
+                    queued.len(),
+                    targets.into_iter().collect::<Vec<_>>().join(", ")
+                )),
+                iced::widget::Space::with_width(iced::Length::Fill),
+                components::secondary_button("Scarta", Message::DiscardQueuedSends),
+                components::primary_button("Riprendi", Message::ResumeQueuedSends),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(iced::Alignment::Center)
+            .into(),
+        )
+        .into()
+    }
+
     /// Vista principale dell'applicazione
     fn main_view(&self) -> Element<Message> {
         views::main_view::render(
             &self.discovered_devices,
+            &self.recent_devices,
+            &self.scan_highlights,
+            &self.connection_quality,
             self.selected_device.as_ref(),
+            &self.protocols,
             self.is_scanning,
+            self.scan_started.map(|started| started.elapsed()),
+            self.last_scan.map(|finished| finished.elapsed()),
             &self.airplay_status,
             &self.airdrop_status,
+            self.airplay_connecting_since.map(|since| since.elapsed()),
             self.file_transfer_progress,
+            self.send_progress.as_ref(),
+            self.file_transfer_size,
+            self.airdrop_status.remaining(self.clock.now()),
+            self.pending_send.as_ref(),
+            self.airplay_effective_quality,
+            self.airplay_stats,
+            self.airplay_preview.as_ref(),
+            if self.privilege_banner_dismissed { &[] } else { self.diagnostics.limitations.as_slice() },
+            self.pending_conflicts.first(),
+            self.pending_transfers.first(),
+            &self.history,
+            self.awdl_availability,
             &self.notifications,
             self.show_link_dialog,
             &self.link_url,
+            (self.settings.completion_action != CompletionAction::Nothing).then_some(self.open_link_on_complete),
+            self.device_details_expanded,
+            self.scan_scope,
+            &self.status_message,
             &self.theme,
         )
     }
  
+    /// Vista compatta
+    fn mini_view(&self) -> Element<Message> {
+        views::mini_view::MiniView::new(
+            &self.discovered_devices,
+            self.selected_device.as_ref(),
+            self.is_scanning,
+            self.file_transfer_progress,
+            self.pending_send.as_ref(),
+        )
+        .view(&self.theme)
+    }
+
+    /// Vista della configurazione iniziale
+    fn onboarding_view(&self) -> Element<Message> {
+        views::onboarding_view::OnboardingView::new(
+            self.settings_view.network_report(),
+            self.settings_view.network_test_running(),
+            self.diagnostics.elevated,
+            &self.diagnostics.limitations,
+            self.settings_view.friendly_name_text(),
+            crate::utils::cache::Cache::from_settings(&self.settings).received_dir(),
+        )
+        .view(&self.theme)
+    }
+
+    /// Vista della diagnostica
+    fn diagnostics_view(&self) -> Element<Message> {
+        views::diagnostics_view::DiagnosticsView::new(
+            self.diagnostics_report.as_ref(),
+            self.settings.airdrop_enabled,
+            &self.airdrop_status,
+            self.airdrop.as_ref().map_or(0, |airdrop| airdrop.active_https_connections()),
+            self.send_only_limitation.as_deref(),
+            self.settings.airplay_enabled,
+            &self.airplay_status,
+            self.airplay_stats,
+            self.awdl_availability,
+            crate::utils::supervisor::global().health(),
+            self.discovered_devices
+                .iter()
+                .filter_map(|device| match &device.service_type {
+                    crate::network::ServiceType::Other(service) => Some((device.name.clone(), service.clone())),
+                    _ => None,
+                })
+                .collect(),
+            &self.discovered_devices,
+            self.settings_view.full_macs(),
+            self.adding_firewall_rules,
+        )
+        .view(&self.theme)
+    }
+
+    /// Vista della timeline degli eventi
+    fn event_log_view(&self) -> Element<Message> {
+        views::event_log_view::EventLogView::new(&self.event_log, self.event_log_filter)
+            .view(&self.theme)
+    }
+
+    /// Vista impostazioni inizializzata dai valori salvati
+    fn settings_view_for(settings: &crate::utils::config::Settings) -> views::settings_view::SettingsView {
+        let mut view = views::settings_view::SettingsView::new(
+            true,                // enable_auto_discovery
+            15,                  // discovery_interval
+            settings.show_notifications,
+            settings.play_sound_on_complete,
+            settings.minimize_to_tray.unwrap_or(false),
+            settings.airdrop_enabled,
+            settings.visibility.visibility.into(),
+            settings.visibility.auto_accept_from_contacts,
+            settings.file_conflict_policy.into(),
+            settings.airplay_enabled,
+            settings.airplay_quality.into(),
+            false,               // airplay_audio_only
+            None,                // network_interface
+            Vec::new(),          // available_interfaces
+            settings.airplay_port, // custom_port
+            false,               // debug_mode
+            views::settings_view::LogLevel::Info,
+            settings.max_concurrent_transfers,
+        );
+        view.set_max_transfers_per_peer(settings.max_transfers_per_peer);
+        view.set_system_notifications(
+            settings.notification_backend == crate::utils::config::NotificationBackendKind::System,
+        );
+        view.set_cache_dir(crate::utils::cache::Cache::from_settings(settings).root().to_path_buf());
+        view.set_friendly_name_text(settings.friendly_name.clone().unwrap_or_default());
+        view.set_name_suffix_text(settings.name_suffix.clone().unwrap_or_default());
+        view.set_avoid_name_collisions(settings.avoid_name_collisions);
+        view.set_capture_region(settings.capture_region);
+        view.set_airplay_idle_timeout(views::settings_view::IdleTimeout(settings.airplay_idle_timeout_mins));
+        view.set_airplay_show_cursor(settings.airplay_show_cursor);
+        view.set_send_method(settings.transfer_method.into());
+        view.set_theme(settings.theme);
+        view.set_font_scale(views::settings_view::FontScale(settings.font_scale_percent));
+        view.set_stay_connected(settings.after_transfer == crate::protocols::connection_pool::AfterTransfer::StayConnected);
+        view.set_compression(views::settings_view::CompressionChoice(settings.transfer_compression));
+        view.set_completion_choice(settings.completion_action.into());
+        view.set_ip_mode(settings.ip_mode.into());
+        view.set_shared_folder(settings.shared_folder.clone());
+        view.set_trusted_devices(settings.trusted_devices.clone());
+        view.set_screen_size(crate::protocols::airplay::primary_screen_size());
+        view
+    }
+
+    /// Arresta i servizi in background, poi chiude la finestra e termina
+    fn quit(&self) -> Command<Message> {
+        let airdrop = self.airdrop.clone();
+        let airplay = self.airplay.clone();
+        let discovery = self.discovery.clone();
+        Command::perform(
+            async move {
+                // Prima la scansione, che altrimenti legge dai servizi in chiusura
+                if let Some(discovery) = discovery {
+                    discovery.lock().await.stop_discovery().await;
+                }
+                if let Some(airdrop) = airdrop {
+                    if let Err(e) = airdrop.stop_server().await {
+                        warn!("Failed to stop AirDrop on exit: {}", e);
+                    }
+                }
+                if let Some(airplay) = airplay {
+                    if let Err(e) = airplay.stop_server().await {
+                        warn!("Failed to stop AirPlay on exit: {}", e);
+                    }
+                }
+            },
+            |_| Message::ServicesStopped,
+        )
+    }
+
+    /// Riapplica ai servizi e alla finestra solo i valori cambiati rispetto a `previous`
+    fn apply_settings(&mut self, previous: &crate::utils::config::Settings) -> Command<Message> {
+        self.settings_view = Self::settings_view_for(&self.settings);
+        self.settings_view.set_advertised_name(self.advertised_name.clone());
+        self.theme = self.settings.theme;
+        styles::set_active_theme(&iced_theme(self.theme));
+        styles::font_size::set_scale_percent(self.settings.font_scale_percent);
+        self.device_cache.set_expiry(Duration::from_secs(self.settings.recent_devices_expiry_secs));
+        self.notifications.set_max(self.settings.max_notifications as usize);
+        if let Some(airdrop) = &self.airdrop {
+            airdrop.set_conflict_policy(self.settings.file_conflict_policy);
+            airdrop.set_trusted_devices(self.settings.trusted_devices.clone());
+        }
+        let mut commands = vec![self.refresh_cache_size()];
+        if previous.visibility != self.settings.visibility {
+            commands.push(self.apply_visibility());
+        }
+        self.method_selector.configure(
+            self.settings.transfer_method,
+            self.settings.shared_folder.clone(),
+            self.settings.shared_folder_after_failures,
+        );
+        if previous.transfer_limits() != self.settings.transfer_limits() {
+            self.transfers.set_limits(self.settings.transfer_limits());
+            commands.push(self.start_queued_sends());
+        }
+        if previous.airdrop_enabled != self.settings.airdrop_enabled {
+            commands.push(self.update(Message::AirDropEnabledToggled(self.settings.airdrop_enabled)));
+        }
+        if previous.airplay_enabled != self.settings.airplay_enabled {
+            commands.push(self.update(Message::AirPlayEnabledToggled(self.settings.airplay_enabled)));
+        } else if previous.airplay_port != self.settings.airplay_port {
+            commands.push(self.update(Message::CustomPortSubmitted));
+        }
+        if previous.capture_region != self.settings.capture_region {
+            commands.push(self.set_capture_region(self.settings.capture_region));
+        }
+        if previous.airplay_quality != self.settings.airplay_quality {
+            commands.push(self.update(Message::AirPlayQualityChanged(self.settings.airplay_quality.into())));
+        }
+        if previous.airplay_idle_timeout_mins != self.settings.airplay_idle_timeout_mins {
+            commands.push(self.apply_idle_timeout());
+        }
+        if let Some(airplay) = &self.airplay {
+            airplay.set_draw_cursor(self.settings.airplay_show_cursor);
+        }
+        if (previous.mini_mode, previous.mini_always_on_top)
+            != (self.settings.mini_mode, self.settings.mini_always_on_top)
+        {
+            if !self.settings.mini_mode && self.current_view == AppView::Mini {
+                self.current_view = AppView::Main;
+            }
+            commands.push(self.apply_window_mode());
+        }
+        Command::batch(commands)
+    }
+
+    /// Applica il tempo di inattività anche alla trasmissione in corso
+    fn apply_idle_timeout(&self) -> Command<Message> {
+        match self.airplay.clone() {
+            Some(airplay) => {
+                let timeout = self.settings.airplay_idle_timeout();
+                Command::perform(async move { airplay.set_idle_timeout(timeout).await }, |_| Message::Tick)
+            }
+            None => Command::none(),
+        }
+    }
+
+    /// Salva l'area dello schermo trasmessa e la applica allo streaming in corso
+    fn set_capture_region(
+        &mut self,
+        region: Option<crate::protocols::screen_capture::CaptureRegion>,
+    ) -> Command<Message> {
+        self.settings_view.set_capture_region(region);
+        self.settings.capture_region = region;
+        if let Err(e) = self.settings.save() {
+            warn!("Failed to persist capture region: {}", e);
+        }
+        match self.airplay.clone() {
+            Some(airplay) => Command::perform(
+                async move { airplay.set_capture_region(region).await },
+                |_| Message::Tick,
+            ),
+            None => Command::none(),
+        }
+    }
+
+    /// Salva i dispositivi attendibili e li applica al server AirDrop
+    fn apply_trusted_devices(&mut self) {
+        self.settings_view.set_trusted_devices(self.settings.trusted_devices.clone());
+        if let Err(e) = self.settings.save() {
+            warn!("Failed to persist trusted devices: {}", e);
+        }
+        if let Some(airdrop) = &self.airdrop {
+            airdrop.set_trusted_devices(self.settings.trusted_devices.clone());
+        }
+    }
+
+    /// Applica la visibilità salvata al server AirDrop in esecuzione
+    fn apply_visibility(&self) -> Command<Message> {
+        let settings = self.settings.visibility;
+        match self.airdrop.clone() {
+            Some(airdrop) => Command::perform(
+                async move {
+                    if let Err(e) = airdrop.set_visibility(settings).await {
+                        event_log::error("AirDrop", format!("Visibilità non applicata: {}", e));
+                    }
+                },
+                |_| Message::Tick,
+            ),
+            None => Command::none(),
+        }
+    }
+
+    /// Stato corrente da raccogliere per la diagnostica
+    fn bug_report_input(&self) -> crate::utils::bug_report::BugReportInput {
+        crate::utils::bug_report::BugReportInput {
+            diagnostics: self.diagnostics.clone(),
+            own_ports: self.own_ports(),
+            awdl: self.awdl_availability,
+            awdl_mac: self.awdl_mac.as_ref().and_then(|mac| *mac.borrow()),
+            airplay: self.airplay_stats,
+            recent_events: self.event_log.clone(),
+        }
+    }
+
+    /// Porte già aperte dai servizi attivi, che non possono essere verificate
+    fn own_ports(&self) -> Vec<u16> {
+        let ports = self.diagnostics.ports;
+        let mut own_ports = Vec::new();
+        if self.settings.airdrop_enabled {
+            own_ports.extend([ports.airdrop_https, ports.airdrop_fallback]);
+        }
+        if self.settings.airplay_enabled {
+            own_ports.push(self.settings.airplay_port.unwrap_or(ports.airplay));
+        }
+        own_ports
+    }
+
+    /// Apre un collegamento nel browser predefinito
+    fn open_url(&mut self, url: String) {
+        if let Err(e) = opener::open(&url) {
+            self.add_notification(
+                "Impossibile aprire il collegamento".to_string(),
+                format!("{}: {}", url, e),
+                messages::NotificationType::Error,
+            );
+        }
+    }
+
+    /// Salva la cronologia, un errore viene solo registrato
+    fn save_history(&self) {
+        if let Err(e) = self.history.save() {
+            warn!("Failed to save transfer history: {}", e);
+        }
+    }
+
+    /// Salva la coda di invio, un errore viene solo registrato
+    fn save_transfers(&self) {
+        if let Err(e) = self.transfers.save() {
+            warn!("Failed to save send queue: {}", e);
+        }
+    }
+
+    /// Salva metodo di invio e cartella condivisa e li applica ai prossimi invii
+    fn apply_send_method(&mut self) -> Command<Message> {
+        if let Err(e) = self.settings.save() {
+            warn!("Failed to persist send method: {}", e);
+        }
+        self.method_selector.configure(
+            self.settings.transfer_method,
+            self.settings.shared_folder.clone(),
+            self.settings.shared_folder_after_failures,
+        );
+        Command::none()
+    }
+
+    /// Salva i limiti dei trasferimenti simultanei e avvia gli invii che
+    /// ora rientrano nei limiti
+    fn apply_transfer_limits(&mut self) -> Command<Message> {
+        if let Err(e) = self.settings.save() {
+            warn!("Failed to persist transfer limits: {}", e);
+        }
+        self.transfers.set_limits(self.settings.transfer_limits());
+        self.start_queued_sends()
+    }
+
+    /// Avvia gli invii in coda entro i limiti di trasferimenti simultanei
+    fn start_queued_sends(&mut self) -> Command<Message> {
+        let started = self.transfers.start_next();
+        if started.is_empty() {
+            return Command::none();
+        }
+        self.airdrop_status.start_transfer(self.clock.now());
+        self.file_transfer_progress = Some(0.0);
+
+        let sender = self.advertised_name.clone().unwrap_or_else(|| {
+            crate::protocols::apple_records::friendly_name(self.settings.friendly_name.as_deref())
+                .unwrap_or_else(|_| "AirWin".to_string())
+        });
+        Command::batch(started.into_iter().map(|send| {
+            let id = send.id;
+            let method = self.method_selector.method_for(&send.target);
+            match (method, self.method_selector.shared_folder().map(std::path::Path::to_path_buf)) {
+                (TransferMethod::SharedFolder, Some(folder)) => {
+                    let sender = sender.clone();
+                    Command::perform(
+                        async move {
+                            crate::protocols::transfer_method::copy_to_shared_folder(&send.path, &folder, &sender, &send.target)
+                                .await
+                                .map_err(|e| e.to_string())
+                        },
+                        move |result| Message::QueuedSendCompleted(id, method, result),
+                    )
+                }
+                _ => {
+                    let device = self.queued_device(&send);
+                    let (discovery, protocols) = (self.discovery.clone(), self.protocols.clone());
+                    Command::perform(
+                        async move {
+                            let device = device
+                                .ok_or_else(|| format!("{} non è più disponibile, ripeti la scansione", send.target))?;
+                            let payload = Payload::File { path: send.path, mime_type: send.mime_type };
+                            Self::send_payload(discovery, protocols, device, payload).await
+                        },
+                        move |result| Message::QueuedSendCompleted(id, TransferMethod::Direct, result),
+                    )
+                }
+            }
+        }))
+    }
+
+    /// Cerca di nuovo `device` per inviare all'indirizzo che ha ora, poi gli
+    /// invia `payload` con il primo protocollo che lo gestisce
+    async fn send_payload(
+        discovery: Option<std::sync::Arc<tokio::sync::Mutex<crate::network::discovery::DeviceDiscovery>>>,
+        protocols: ProtocolRegistry,
+        device: crate::network::DiscoveredDevice,
+        payload: Payload,
+    ) -> Result<TransferOutcome, String> {
+        // La ricerca dura fino a 2 secondi: il lock della discovery si rilascia prima
+        let resolver = match &discovery {
+            Some(discovery) => Some(discovery.lock().await.resolver()),
+            None => None,
+        };
+        let device = match resolver {
+            Some(resolver) => resolver.resolve(&device).await.map_err(|e| e.to_string())?,
+            None => device,
+        };
+        first_outcome(protocols.send(&device, payload).await)
+    }
+
+    /// Dispositivo a cui va un invio in coda, tra quelli trovati dalle
+    /// scansioni. Per un invio ripristinato prima di una scansione si usa
+    /// l'indirizzo salvato con la coda.
+    fn queued_device(
+        &self,
+        send: &crate::protocols::transfer_manager::QueuedSend,
+    ) -> Option<crate::network::DiscoveredDevice> {
+        let scanned = self
+            .discovered_devices
+            .iter()
+            .chain(&self.recent_devices)
+            .find(|device| device.name == send.target && send.addr.map_or(true, |addr| device.airdrop_addr() == Some(addr)))
+            .cloned();
+        scanned.or_else(|| {
+            send.addr.map(|addr| crate::network::DiscoveredDevice {
+                name: send.target.clone(),
+                address: addr.ip(),
+                port: addr.port(),
+                service_type: crate::network::ServiceType::AirDrop,
+                txt_records: Default::default(),
+            })
+        })
+    }
+
+    /// Ricalcola in background lo spazio occupato dalla cache
+    fn refresh_cache_size(&self) -> Command<Message> {
+        let cache = crate::utils::cache::Cache::from_settings(&self.settings);
+        Command::perform(
+            async move { tokio::task::spawn_blocking(move || cache.size()).await.unwrap_or(0) },
+            Message::CacheSizeComputed,
+        )
+    }
+
+    /// Applica dimensioni e livello della finestra in base alla vista corrente
+    fn apply_window_mode(&self) -> Command<Message> {
+        if self.current_view == AppView::Mini {
+            let level = if self.settings.mini_always_on_top {
+                window::Level::AlwaysOnTop
+            } else {
+                window::Level::Normal
+            };
+            Command::batch([
+                window::resize(window::Id::MAIN, views::mini_view::MINI_WINDOW_SIZE),
+                window::change_level(window::Id::MAIN, level),
+            ])
+        } else {
+            Command::batch([
+                window::resize(window::Id::MAIN, iced::Size::new(1200.0, 800.0)),
+                window::change_level(window::Id::MAIN, window::Level::Normal),
+            ])
+        }
+    }
+ 
     /// Vista impostazioni
     fn settings_view(&self) -> Element<Message> {
         self.settings_view.view(&self.theme)
@@ -412,79 +3066,350 @@ impl AirWinApp {
         self.about_view.view(&self.theme)
     }
   
-    /// Simula la scansione dei dispositivi nella rete
-    async fn scan_devices() -> Vec<crate::network::DiscoveredDevice> {
-        // Simula una pausa per la scansione
-        tokio::time::sleep(Duration::from_secs(3)).await;
-        
-        // Dispositivi di esempio per il testing
-        vec![
-            crate::network::DiscoveredDevice {
-                name: "iPhone di Marco".to_string(),
-                address: std::net::IpAddr::V4(std::net::Ipv4Addr::new(192,168,1,100)),
-                port: 8771,
-                service_type: crate::network::ServiceType::AirDrop,
-                txt_records: std::collections::HashMap::new(),
-            },
-            crate::network::DiscoveredDevice {
-                name: "iPad Pro".to_string(),
-                address: std::net::IpAddr::V4(std::net::Ipv4Addr::new(192,168,1,101)),
-                port: 7100,
-                service_type: crate::network::ServiceType::AirPlay,
-                txt_records: std::collections::HashMap::new(),
-            },
-            crate::network::DiscoveredDevice {
-                name: "MacBook Pro".to_string(),
-                address: std::net::IpAddr::V4(std::net::Ipv4Addr::new(192,168,1,102)),
-                port: 8771,
-                service_type: crate::network::ServiceType::AirDrop,
-                txt_records: std::collections::HashMap::new(),
-            },
-        ]
+    /// Scansiona i servizi di `scope` con tutte le sorgenti del servizio di
+    /// discovery, fino al timeout o all'interruzione
+    async fn scan_devices(
+        discovery: Option<std::sync::Arc<tokio::sync::Mutex<crate::network::discovery::DeviceDiscovery>>>,
+        scope: crate::network::ScanScope,
+        cancel: crate::network::backend::CancelToken,
+    ) -> Vec<crate::network::DiscoveredDevice> {
+        match discovery {
+            Some(discovery) => discovery.lock().await.browse(SCAN_TIMEOUT, scope.service_types(), &cancel).await,
+            None => Vec::new(),
+        }
     }
 
-    /// Simula il trasferimento di un file
-    async fn simulate_file_transfer() -> f32 {
-        for progress in (0..=100).step_by(10) {
-            tokio::time::sleep(Duration::from_millis(200)).await;
-            if progress == 100 {
-                return 100.0;
-            }
+    /// Suono di fine trasferimento, se abilitato nelle impostazioni
+    fn play_completion_sound(&self) {
+        if self.settings.play_sound_on_complete {
+            crate::utils::sound::play_completion_chime();
+        }
+    }
+
+    /// Suono di errore, se i suoni sono abilitati nelle impostazioni
+    fn play_failure_sound(&self) {
+        if self.settings.play_sound_on_complete {
+            crate::utils::sound::play_failure_chime();
         }
-        100.0
     }
 
-    /// Aggiunge una notifica alla lista
+    /// Aggiunge una notifica alla lista; con le notifiche disattivate
+    /// vengono mostrati solo gli errori
     fn add_notification(
         &mut self,
         title: String,
         message: String,
         notification_type: messages::NotificationType,
     ) {
-        let notification = messages::NotificationMessage {
-            title,
-            content: message,
-            notification_type,
-            duration_ms: Some(3000),
+        let notification = match notification_type {
+            messages::NotificationType::Success => messages::NotificationMessage::success(title, message),
+            messages::NotificationType::Warning => messages::NotificationMessage::warning(title, message),
+            messages::NotificationType::Error => messages::NotificationMessage::error(title, message),
+            messages::NotificationType::Info => messages::NotificationMessage::info(title, message),
         };
-        
-        self.notifications.push(notification);
-        
-        // Mantieni solo le ultime 5 notifiche
-        if self.notifications.len() > 5 {
-            self.notifications.remove(0);
+        self.push_notification(notification);
+    }
+
+    /// Unico punto da cui passano le notifiche: le consegna alla finestra,
+    /// con il limite delle impostazioni e la chiusura automatica, oppure al
+    /// sistema
+    fn push_notification(&mut self, notification: messages::NotificationMessage) {
+        if !self.settings.show_notifications
+            && notification.notification_type != messages::NotificationType::Error
+        {
+            return;
         }
+        notifications::dispatch(
+            self.settings.notification_backend,
+            notification,
+            std::time::Instant::now(),
+            &mut self.notifications,
+            self.system_notifications.as_mut(),
+        );
     }
 }
 
+/// Inoltra all'interfaccia i file ricevuti dal server AirDrop
+fn incoming_files(events: broadcast::Sender<IncomingFile>) -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<IncomingFile>(),
+        crate::protocols::airdrop::INCOMING_EVENTS_CAPACITY,
+        move |mut output| async move {
+            let mut receiver = events.subscribe();
+            loop {
+                match receiver.recv().await {
+                    Ok(file) => {
+                        let _ = output.send(Message::FileReceived(file)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("UI fell behind, dropped {} incoming file events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        // Il server AirDrop è stato chiuso: nessun altro evento in arrivo
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Inoltra all'interfaccia le richieste di scelta per i file già esistenti
+fn conflict_prompts(prompts: broadcast::Sender<ConflictPrompt>) -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<ConflictPrompt>(),
+        8,
+        move |mut output| async move {
+            let mut receiver = prompts.subscribe();
+            loop {
+                match receiver.recv().await {
+                    Ok(prompt) => {
+                        let _ = output.send(Message::FileConflict(prompt)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // Le richieste perse scadono e mantengono entrambi i file
+                        warn!("UI fell behind, dropped {} conflict prompts", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Inoltra all'interfaccia le richieste di invio da confermare
+fn transfer_prompts(prompts: broadcast::Sender<TransferPrompt>) -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<TransferPrompt>(),
+        8,
+        move |mut output| async move {
+            let mut receiver = prompts.subscribe();
+            loop {
+                match receiver.recv().await {
+                    Ok(prompt) => {
+                        let _ = output.send(Message::TransferRequested(prompt)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // Le richieste perse scadono e vengono rifiutate
+                        warn!("UI fell behind, dropped {} transfer prompts", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Inoltra all'interfaccia i cambi di stato pubblicati da AirPlay, come un
+/// errore durante lo streaming
+fn airplay_status(airplay: crate::protocols::airplay::AirPlay) -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<crate::protocols::airplay::AirPlayStatus>(),
+        4,
+        move |mut output| async move {
+            let mut receiver = airplay.status_changes().subscribe();
+            loop {
+                let status = match receiver.recv().await {
+                    Ok(status) => status,
+                    // Rimasti indietro: conta solo lo stato attuale
+                    Err(broadcast::error::RecvError::Lagged(_)) => airplay.get_status().await,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        std::future::pending::<()>().await;
+                        continue;
+                    }
+                };
+                let _ = output.send(Message::AirPlayStatusChanged(status)).await;
+            }
+        },
+    )
+}
+
+/// Inoltra all'interfaccia gli arresti di AirPlay per inattività
+fn airplay_idle_stops(stops: broadcast::Sender<Duration>) -> Subscription<Message> {
+    struct IdleStops;
+    iced::subscription::channel(
+        std::any::TypeId::of::<IdleStops>(),
+        4,
+        move |mut output| async move {
+            let mut receiver = stops.subscribe();
+            loop {
+                match receiver.recv().await {
+                    Ok(idle) => {
+                        let _ = output.send(Message::AirPlayIdleStopped(idle)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Inoltra all'interfaccia i cambi di disponibilità di AWDL
+fn awdl_availability(mut awdl: tokio::sync::watch::Receiver<AwdlAvailability>) -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<AwdlAvailability>(),
+        4,
+        move |mut output| async move {
+            while awdl.changed().await.is_ok() {
+                let availability = *awdl.borrow_and_update();
+                let _ = output.send(Message::AwdlAvailabilityChanged(availability)).await;
+            }
+            // Il gestore AWDL è stato chiuso: lo stato non cambierà più
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia l'ingresso e l'uscita dalla modalità solo invio
+fn send_only_mode(mut send_only: tokio::sync::watch::Receiver<Option<String>>) -> Subscription<Message> {
+    struct SendOnlyMode;
+    iced::subscription::channel(
+        std::any::TypeId::of::<SendOnlyMode>(),
+        4,
+        move |mut output| async move {
+            // La registrazione può essere fallita prima dell'apertura della finestra
+            let reason = send_only.borrow_and_update().clone();
+            if reason.is_some() {
+                let _ = output.send(Message::AirDropSendOnlyChanged(reason)).await;
+            }
+            while send_only.changed().await.is_ok() {
+                let reason = send_only.borrow_and_update().clone();
+                let _ = output.send(Message::AirDropSendOnlyChanged(reason)).await;
+            }
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia il nome annunciato, anche quando cambia dopo
+/// una nuova registrazione
+fn advertised_name(mut name: tokio::sync::watch::Receiver<String>) -> Subscription<Message> {
+    struct AdvertisedName;
+    iced::subscription::channel(
+        std::any::TypeId::of::<AdvertisedName>(),
+        4,
+        move |mut output| async move {
+            let current = name.borrow_and_update().clone();
+            let _ = output.send(Message::AdvertisedNameChanged(current)).await;
+            while name.changed().await.is_ok() {
+                let current = name.borrow_and_update().clone();
+                let _ = output.send(Message::AdvertisedNameChanged(current)).await;
+            }
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia l'esito dell'avvio dei servizi, una sola volta
+fn startup_summary(
+    mut summary: tokio::sync::watch::Receiver<Option<crate::utils::startup_summary::StartupSummary>>,
+) -> Subscription<Message> {
+    struct StartupSummary;
+    iced::subscription::channel(
+        std::any::TypeId::of::<StartupSummary>(),
+        1,
+        move |mut output| async move {
+            // L'inizializzazione può essere terminata prima dell'apertura della finestra
+            loop {
+                let current = summary.borrow_and_update().clone();
+                if let Some(current) = current {
+                    let _ = output.send(Message::StartupSummary(current)).await;
+                    break;
+                }
+                if summary.changed().await.is_err() {
+                    break;
+                }
+            }
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia gli errori nella configurazione della connessione sicura
+fn tls_failures(mut failures: tokio::sync::watch::Receiver<Option<String>>) -> Subscription<Message> {
+    struct TlsFailures;
+    iced::subscription::channel(
+        std::any::TypeId::of::<TlsFailures>(),
+        4,
+        move |mut output| async move {
+            // Il server può essere fallito prima dell'apertura della finestra
+            if let Some(error) = failures.borrow_and_update().clone() {
+                let _ = output.send(Message::TlsSetupFailed(error)).await;
+            }
+            while failures.changed().await.is_ok() {
+                if let Some(error) = failures.borrow_and_update().clone() {
+                    let _ = output.send(Message::TlsSetupFailed(error)).await;
+                }
+            }
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia i dispositivi il cui certificato è cambiato
+fn certificate_changes(mut changes: tokio::sync::watch::Receiver<Option<String>>) -> Subscription<Message> {
+    struct CertificateChanges;
+    iced::subscription::channel(
+        std::any::TypeId::of::<CertificateChanges>(),
+        4,
+        move |mut output| async move {
+            while changes.changed().await.is_ok() {
+                if let Some(peer) = changes.borrow_and_update().clone() {
+                    let _ = output.send(Message::CertificateChanged(peer)).await;
+                }
+            }
+            std::future::pending().await
+        },
+    )
+}
+
+/// Inoltra all'interfaccia gli eventi pubblicati dai protocolli
+fn event_log_events() -> Subscription<Message> {
+    iced::subscription::channel(
+        std::any::TypeId::of::<LogEvent>(),
+        100,
+        |mut output| async move {
+            let (recent, mut receiver) = event_log::subscribe();
+            for event in recent {
+                let _ = output.send(Message::EventLogged(event)).await;
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let _ = output.send(Message::EventLogged(event)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Event log fell behind, dropped {} events", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        std::future::pending::<()>().await;
+                    }
+                }
+            }
+        },
+    )
+}
+
 /// Funzione principale per avviare l'applicazione
-pub fn run() -> iced::Result {
+pub fn run(flags: UiFlags) -> iced::Result {
     // Prefer DirectX 12 backend on Windows to avoid Vulkan validation spam
     // and disable extra WGPU validation layers in release usage.
     // These can be overridden by user environment variables if needed.
     std::env::set_var("WGPU_BACKEND", "dx12");
     std::env::set_var("WGPU_VALIDATION", "0");
 
+    // La dimensione predefinita del testo è fissata all'avvio della finestra
+    let saved = crate::utils::config::Settings::load();
+    styles::font_size::set_scale_percent(saved.font_scale_percent);
+    styles::set_active_theme(&iced_theme(saved.theme));
+
     let settings = Settings {
         window: iced::window::Settings {
             size: iced::Size::new(1200.0, 800.0),
@@ -494,25 +3419,64 @@ pub fn run() -> iced::Result {
             decorations: true,
             transparent: false,
             icon: None,
+            // La chiusura è gestita da `Message::WindowCloseRequested`
+            exit_on_close_request: false,
             ..Default::default()
         },
         default_font: iced::Font::DEFAULT,
-        default_text_size: iced::Pixels(14.0),
+        default_text_size: styles::font_size::default_text_size(),
         antialiasing: true,
+        flags,
         ..Default::default()
     };
 
     AirWinApp::run(settings)
 }
 
+/// Registro dei protocolli di trasferimento disponibili
+fn protocol_registry(
+    airdrop: Option<&crate::protocols::airdrop::AirDrop>,
+) -> ProtocolRegistry {
+    let mut registry = ProtocolRegistry::new();
+    if let Some(airdrop) = airdrop {
+        registry.register(std::sync::Arc::new(airdrop.clone()));
+    }
+    registry
+}
+
+/// Esito di un invio di un solo elemento tramite il registro dei protocolli
+fn first_outcome(result: anyhow::Result<Vec<TransferOutcome>>) -> Result<TransferOutcome, String> {
+    result
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| "Nessun file inviato".to_string())
+}
+
 /// Avvia l'applicazione AirWin con i servizi forniti
 pub async fn run_app(
-    _services: std::sync::Arc<crate::AirWinServices>,
+    services: std::sync::Arc<crate::AirWinServices>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let flags = UiFlags {
+        incoming_files: Some(services.airdrop.lock().await.incoming_events()),
+        discovery: Some(services.device_discovery.clone()),
+        airdrop: Some(services.airdrop.lock().await.clone()),
+        airplay: Some(services.airplay.lock().await.clone()),
+        awdl: Some(services.awdl.lock().await.availability()),
+        awdl_mac: Some(services.awdl.lock().await.local_mac_updates()),
+        startup_summary: Some(services.startup_summary()),
+        diagnostics: services.diagnostics.clone(),
+    };
+
     // Prefer DX12 and disable WGPU validation in async run path as well
     std::env::set_var("WGPU_BACKEND", "dx12");
     std::env::set_var("WGPU_VALIDATION", "0");
 
+    // La dimensione predefinita del testo è fissata all'avvio della finestra
+    let saved = crate::utils::config::Settings::load();
+    styles::font_size::set_scale_percent(saved.font_scale_percent);
+    styles::set_active_theme(&iced_theme(saved.theme));
+
     let settings = Settings {
         window: iced::window::Settings {
             size: iced::Size::new(1200.0, 800.0),
@@ -522,11 +3486,14 @@ pub async fn run_app(
             decorations: true,
             transparent: false,
             icon: None,
+            // La chiusura è gestita da `Message::WindowCloseRequested`
+            exit_on_close_request: false,
             ..Default::default()
         },
         antialiasing: true,
         default_font: iced::Font::DEFAULT,
-        default_text_size: iced::Pixels(14.0),
+        default_text_size: styles::font_size::default_text_size(),
+        flags,
         ..Default::default()
     };
     
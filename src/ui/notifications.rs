@@ -0,0 +1,298 @@
+//! Notifiche mostrate all'utente
+//!
+//! Tutte le notifiche passano da [`dispatch`], che le consegna al
+//! [`NotificationBackend`] scelto nelle impostazioni: la finestra di AirWin
+//! oppure le notifiche di sistema, visibili anche con la finestra ridotta a
+//! icona.
+//!
+//! Nella finestra, [`NotificationQueue::push`] tiene solo le notifiche più
+//! recenti e ne registra la scadenza; [`NotificationQueue::expire`] viene
+//! chiamata dal timer dell'interfaccia finché ce n'è almeno una. Ogni
+//! notifica ha un [`NotificationId`] crescente: chiusura e scadenza agiscono
+//! sull'id e non sulla posizione, che cambia appena un'altra notifica viene
+//! rimossa.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use super::messages::NotificationMessage;
+use crate::utils::config::{NotificationBackendKind, DEFAULT_MAX_NOTIFICATIONS};
+
+/// Durata di una notifica che non ne indica una propria
+const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+
+/// Identificativo di una notifica, crescente nell'ordine di arrivo
+pub type NotificationId = u64;
+
+#[derive(Debug, Clone)]
+struct Entry {
+    message: NotificationMessage,
+    expires_at: Instant,
+}
+
+/// Notifiche visibili, dalla più vecchia alla più recente
+#[derive(Debug, Clone)]
+pub struct NotificationQueue {
+    /// Ordinate per id, quindi per arrivo
+    entries: BTreeMap<NotificationId, Entry>,
+    next_id: NotificationId,
+    max: usize,
+}
+
+impl NotificationQueue {
+    pub fn new(max: usize) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            next_id: 0,
+            max: max.max(1),
+        }
+    }
+
+    /// Aggiunge una notifica, scartando le più vecchie oltre il limite
+    pub fn push(&mut self, notification: NotificationMessage, now: Instant) -> NotificationId {
+        let duration = notification
+            .duration_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DURATION);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(
+            id,
+            Entry {
+                message: notification,
+                expires_at: now + duration,
+            },
+        );
+        self.trim();
+        id
+    }
+
+    /// Rimuove le notifiche scadute
+    pub fn expire(&mut self, now: Instant) {
+        self.entries.retain(|_, entry| entry.expires_at > now);
+    }
+
+    /// Chiude la notifica `id`. Una notifica già scaduta o chiusa viene
+    /// ignorata, senza toccare le altre.
+    pub fn dismiss(&mut self, id: NotificationId) -> bool {
+        self.entries.remove(&id).is_some()
+    }
+
+    pub fn set_max(&mut self, max: usize) {
+        self.max = max.max(1);
+        self.trim();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, id: NotificationId) -> Option<&NotificationMessage> {
+        self.entries.get(&id).map(|entry| &entry.message)
+    }
+
+    /// Notifiche dalla più vecchia alla più recente
+    pub fn iter(&self) -> impl Iterator<Item = (NotificationId, &NotificationMessage)> {
+        self.entries.iter().map(|(id, entry)| (*id, &entry.message))
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.max {
+            self.entries.pop_first();
+        }
+    }
+}
+
+impl Default for NotificationQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_NOTIFICATIONS as usize)
+    }
+}
+
+/// Destinazione delle notifiche
+pub trait NotificationBackend: std::fmt::Debug + Send {
+    fn show(&mut self, notification: NotificationMessage, now: Instant);
+}
+
+/// Notifiche disegnate dentro la finestra
+impl NotificationBackend for NotificationQueue {
+    fn show(&mut self, notification: NotificationMessage, now: Instant) {
+        self.push(notification, now);
+    }
+}
+
+/// Notifiche di sistema (toast su Windows). Le azioni non vengono mostrate:
+/// restano disponibili nelle impostazioni e nella diagnostica.
+#[derive(Debug, Default)]
+pub struct SystemBackend;
+
+impl NotificationBackend for SystemBackend {
+    fn show(&mut self, notification: NotificationMessage, _now: Instant) {
+        let shown = notify_rust::Notification::new()
+            .appname("AirWin")
+            .summary(&notification.title)
+            .body(&notification.content)
+            .show();
+        if let Err(e) = shown {
+            warn!("Failed to show system notification {:?}: {}", notification.title, e);
+        }
+    }
+}
+
+/// Scarta le notifiche, per i test e le esecuzioni senza desktop
+#[derive(Debug, Default)]
+pub struct NoopBackend;
+
+impl NotificationBackend for NoopBackend {
+    fn show(&mut self, _notification: NotificationMessage, _now: Instant) {}
+}
+
+/// Backend delle notifiche di sistema. Nei test non viene mostrato nulla.
+pub fn system_backend() -> Box<dyn NotificationBackend> {
+    if cfg!(test) {
+        Box::new(NoopBackend)
+    } else {
+        Box::new(SystemBackend)
+    }
+}
+
+/// Consegna `notification` al backend scelto nelle impostazioni
+pub fn dispatch(
+    kind: NotificationBackendKind,
+    notification: NotificationMessage,
+    now: Instant,
+    in_app: &mut dyn NotificationBackend,
+    system: &mut dyn NotificationBackend,
+) {
+    match kind {
+        NotificationBackendKind::InApp => in_app.show(notification, now),
+        NotificationBackendKind::System => system.show(notification, now),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::messages::{Message, ACTION_DURATION_MS};
+
+    fn titles(queue: &NotificationQueue) -> Vec<&str> {
+        queue.iter().map(|(_, n)| n.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_cap_holds_after_many_pushes() {
+        let now = Instant::now();
+        let mut queue = NotificationQueue::new(3);
+        for i in 0..10 {
+            queue.push(NotificationMessage::info(format!("n{}", i), ""), now);
+            assert!(queue.len() <= 3);
+        }
+
+        assert_eq!(titles(&queue), ["n7", "n8", "n9"]);
+
+        queue.set_max(1);
+        assert_eq!(titles(&queue), ["n9"]);
+    }
+
+    #[test]
+    fn test_dismiss_by_id_while_others_come_and_go() {
+        let now = Instant::now();
+        let mut queue = NotificationQueue::new(3);
+        let first = queue.push(NotificationMessage::info("primo", ""), now);
+        let second = queue.push(NotificationMessage::error("secondo", ""), now);
+        let third = queue.push(NotificationMessage::info("terzo", ""), now + Duration::from_secs(1));
+
+        // Il primo scade mentre il pulsante di chiusura del terzo viene premuto:
+        // con gli indici la chiusura sarebbe finita sulla notifica sbagliata
+        queue.expire(now + Duration::from_secs(3));
+        assert!(queue.dismiss(third));
+        assert_eq!(titles(&queue), ["secondo"]);
+
+        // Chiudere una notifica già scaduta o già chiusa non tocca le altre
+        assert!(!queue.dismiss(first));
+        assert!(!queue.dismiss(third));
+        assert!(queue.get(second).is_some());
+
+        // Gli id non vengono riusati e l'ordine resta quello di arrivo
+        let fourth = queue.push(NotificationMessage::info("quarto", ""), now);
+        let fifth = queue.push(NotificationMessage::info("quinto", ""), now);
+        assert!(fourth > third && fifth > fourth);
+        assert_eq!(titles(&queue), ["secondo", "quarto", "quinto"]);
+
+        // Oltre il limite esce la più vecchia
+        queue.push(NotificationMessage::info("sesto", ""), now);
+        assert!(queue.dismiss(fourth));
+        assert_eq!(titles(&queue), ["quinto", "sesto"]);
+        assert!(queue.get(second).is_none());
+    }
+
+    #[test]
+    fn test_notifications_expire_after_their_duration() {
+        let now = Instant::now();
+        let mut queue = NotificationQueue::default();
+        queue.push(NotificationMessage::info("info", ""), now);
+        queue.push(NotificationMessage::error("errore", ""), now);
+
+        // Le informazioni durano 3 secondi, gli errori 5
+        queue.expire(now + Duration::from_secs(4));
+        assert_eq!(titles(&queue), ["errore"]);
+
+        queue.expire(now + Duration::from_secs(5));
+        assert!(queue.is_empty());
+    }
+
+    /// Registra i titoli ricevuti
+    #[derive(Debug, Default)]
+    struct Recorder(Vec<String>);
+
+    impl NotificationBackend for Recorder {
+        fn show(&mut self, notification: NotificationMessage, _now: Instant) {
+            self.0.push(notification.title);
+        }
+    }
+
+    #[test]
+    fn test_action_notification_dispatches_its_message() {
+        let now = Instant::now();
+        let mut queue = NotificationQueue::default();
+        let id = queue.push(NotificationMessage::airplay_bind_failed("Errore AirPlay", "porta occupata"), now);
+
+        let notification = queue.get(id).unwrap();
+        let labels: Vec<_> = notification.actions.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, ["Riprova", "Apri impostazioni firewall", "Cambia porta"]);
+
+        // Il pulsante invia il messaggio associato, che l'interfaccia gestisce come gli altri
+        let (_, retry) = notification.actions[0].clone();
+        assert!(matches!(retry, Message::AirPlayEnabledToggled(true)));
+        assert!(matches!(notification.actions[2].1, Message::ChangeAirPlayPort));
+
+        // Resta visibile più a lungo di un errore senza azioni
+        queue.expire(now + Duration::from_secs(10));
+        assert_eq!(queue.len(), 1);
+        queue.expire(now + Duration::from_millis(ACTION_DURATION_MS));
+        assert!(queue.is_empty());
+
+        let custom = NotificationMessage::info("Copiato", "").with_action("Apri", Message::ShowEventLog);
+        assert_eq!(custom.duration_ms, Some(ACTION_DURATION_MS));
+        assert!(matches!(custom.actions[..], [(_, Message::ShowEventLog)]));
+    }
+
+    #[test]
+    fn test_dispatch_uses_the_configured_backend() {
+        let now = Instant::now();
+        let mut in_app = NotificationQueue::default();
+        let mut system = Recorder::default();
+
+        dispatch(NotificationBackendKind::InApp, NotificationMessage::info("finestra", ""), now, &mut in_app, &mut system);
+        dispatch(NotificationBackendKind::System, NotificationMessage::info("sistema", ""), now, &mut in_app, &mut system);
+
+        assert_eq!(titles(&in_app), ["finestra"]);
+        assert_eq!(system.0, ["sistema"]);
+    }
+}
@@ -14,6 +14,7 @@ use iced::{
 };
 
 use iced::Theme;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Stato visuale per i pulsanti (locale a questo modulo)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,12 +26,57 @@ pub enum ButtonStatus {
 }
 
 /// Dimensioni dei font
+///
+/// Le costanti sono le dimensioni di base: le viste usano sempre
+/// [`font_size::scaled`], che le moltiplica per la scala scelta nelle
+/// impostazioni.
 pub mod font_size {
+    use iced::Pixels;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
     pub const TINY: u16 = 10;
     pub const SMALL: u16 = 12;
     pub const MEDIUM: u16 = 14;
     pub const LARGE: u16 = 18;
     pub const XLARGE: u16 = 24;
+
+    /// Scala del testo predefinita, in percentuale
+    pub const DEFAULT_SCALE_PERCENT: u32 = 100;
+    /// Scala minima accettata
+    pub const MIN_SCALE_PERCENT: u32 = 80;
+    /// Scala massima accettata
+    pub const MAX_SCALE_PERCENT: u32 = 200;
+
+    static SCALE_PERCENT: AtomicU32 = AtomicU32::new(DEFAULT_SCALE_PERCENT);
+
+    /// Imposta la scala usata da tutte le viste, limitata all'intervallo ammesso
+    pub fn set_scale_percent(percent: u32) {
+        SCALE_PERCENT.store(clamp_percent(percent), Ordering::Relaxed);
+    }
+
+    /// `percent` riportata nell'intervallo ammesso
+    pub fn clamp_percent(percent: u32) -> u32 {
+        percent.clamp(MIN_SCALE_PERCENT, MAX_SCALE_PERCENT)
+    }
+
+    pub fn scale_percent() -> u32 {
+        SCALE_PERCENT.load(Ordering::Relaxed)
+    }
+
+    /// Dimensione effettiva di un testo di dimensione di base `size`
+    pub fn scaled(size: u16) -> f32 {
+        at_scale(size, scale_percent())
+    }
+
+    /// `size` alla scala `percent`, arrotondata al pixel
+    pub fn at_scale(size: u16, percent: u32) -> f32 {
+        (f32::from(size) * percent as f32 / 100.0).round()
+    }
+
+    /// Dimensione dei testi senza una dimensione esplicita
+    pub fn default_text_size() -> Pixels {
+        Pixels(scaled(MEDIUM))
+    }
 }
 
 /// Spaziature standard
@@ -89,115 +135,262 @@ pub mod colors {
     pub const SHADOW_STRONG: Color = Color::from_rgba(0.0, 0.0, 0.0, 0.4);
 }
 
+/// Palette colori per il tema ad alto contrasto: testo bianco su nero e
+/// accenti saturi, per chi vede poco
+pub mod high_contrast {
+    use iced::Color;
+
+    pub const BACKGROUND: Color = Color::BLACK;
+    pub const SURFACE: Color = Color::BLACK;
+    pub const SURFACE_VARIANT: Color = Color::from_rgb(0.10, 0.10, 0.10); // #1A1A1A
+
+    pub const TEXT_PRIMARY: Color = Color::WHITE;
+    pub const TEXT_SECONDARY: Color = Color::WHITE;
+    pub const TEXT_MUTED: Color = Color::from_rgb(0.85, 0.85, 0.85); // #D9D9D9
+
+    pub const PRIMARY: Color = Color::from_rgb(1.0, 0.84, 0.04); // #FFD60A
+    pub const PRIMARY_HOVER: Color = Color::from_rgb(1.0, 0.90, 0.36); // #FFE55C
+    pub const PRIMARY_ACTIVE: Color = Color::from_rgb(0.90, 0.75, 0.0); // #E6BF00
+    /// Testo sopra i pulsanti principali, gialli
+    pub const ON_PRIMARY: Color = Color::BLACK;
+
+    pub const SUCCESS: Color = Color::from_rgb(0.30, 1.0, 0.53); // #4DFF87
+    pub const WARNING: Color = Color::from_rgb(1.0, 0.70, 0.0); // #FFB300
+    pub const ERROR: Color = Color::from_rgb(1.0, 0.40, 0.40); // #FF6666
+    pub const INFO: Color = Color::from_rgb(0.35, 0.85, 1.0); // #59D9FF
+
+    pub const BORDER: Color = Color::WHITE;
+    pub const SHADOW: Color = Color::TRANSPARENT;
+}
+
+/// Palette di iced del tema ad alto contrasto, con cui lo si riconosce
+const HIGH_CONTRAST_THEME_PALETTE: iced::theme::Palette = iced::theme::Palette {
+    background: high_contrast::BACKGROUND,
+    text: high_contrast::TEXT_PRIMARY,
+    primary: high_contrast::PRIMARY,
+    success: high_contrast::SUCCESS,
+    danger: high_contrast::ERROR,
+};
+
+/// Tema di iced ad alto contrasto
+pub fn high_contrast_theme() -> Theme {
+    Theme::custom("Alto contrasto".to_string(), HIGH_CONTRAST_THEME_PALETTE)
+}
+
+pub fn is_high_contrast(theme: &Theme) -> bool {
+    theme.palette() == HIGH_CONTRAST_THEME_PALETTE
+}
+
+/// Il tema attivo è ad alto contrasto. I testi colorati con `.style(colore)`
+/// non ricevono il tema, quindi lo leggono da qui tramite [`Palette::active`].
+static HIGH_CONTRAST_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Imposta il tema di cui [`Palette::active`] restituisce i colori
+pub fn set_active_theme(theme: &Theme) {
+    HIGH_CONTRAST_ACTIVE.store(is_high_contrast(theme), Ordering::Relaxed);
+}
+
+/// Colori usati dagli stili personalizzati per un tema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: Color,
+    pub surface: Color,
+    pub surface_variant: Color,
+    pub text_primary: Color,
+    pub text_secondary: Color,
+    pub text_muted: Color,
+    pub primary: Color,
+    pub primary_hover: Color,
+    pub primary_active: Color,
+    pub on_primary: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub info: Color,
+    pub border: Color,
+    pub shadow: Color,
+}
+
+impl Palette {
+    pub const DARK: Self = Self {
+        background: colors::BACKGROUND,
+        surface: colors::SURFACE,
+        surface_variant: colors::SURFACE_VARIANT,
+        text_primary: colors::TEXT_PRIMARY,
+        text_secondary: colors::TEXT_SECONDARY,
+        text_muted: colors::TEXT_MUTED,
+        primary: colors::PRIMARY,
+        primary_hover: colors::PRIMARY_HOVER,
+        primary_active: colors::PRIMARY_ACTIVE,
+        on_primary: colors::TEXT_PRIMARY,
+        success: colors::SUCCESS,
+        warning: colors::WARNING,
+        error: colors::ERROR,
+        info: colors::INFO,
+        border: colors::BORDER,
+        shadow: colors::SHADOW,
+    };
+
+    pub const HIGH_CONTRAST: Self = Self {
+        background: high_contrast::BACKGROUND,
+        surface: high_contrast::SURFACE,
+        surface_variant: high_contrast::SURFACE_VARIANT,
+        text_primary: high_contrast::TEXT_PRIMARY,
+        text_secondary: high_contrast::TEXT_SECONDARY,
+        text_muted: high_contrast::TEXT_MUTED,
+        primary: high_contrast::PRIMARY,
+        primary_hover: high_contrast::PRIMARY_HOVER,
+        primary_active: high_contrast::PRIMARY_ACTIVE,
+        on_primary: high_contrast::ON_PRIMARY,
+        success: high_contrast::SUCCESS,
+        warning: high_contrast::WARNING,
+        error: high_contrast::ERROR,
+        info: high_contrast::INFO,
+        border: high_contrast::BORDER,
+        shadow: high_contrast::SHADOW,
+    };
+
+    /// Palette degli stili per `theme`. I temi chiaro e scuro condividono
+    /// quella scura.
+    pub fn for_theme(theme: &Theme) -> Self {
+        Self::for_high_contrast(is_high_contrast(theme))
+    }
+
+    /// Palette del tema impostato con [`set_active_theme`], per i colori
+    /// usati fuori dagli stili
+    pub fn active() -> Self {
+        Self::for_high_contrast(HIGH_CONTRAST_ACTIVE.load(Ordering::Relaxed))
+    }
+
+    fn for_high_contrast(high_contrast: bool) -> Self {
+        if high_contrast {
+            Self::HIGH_CONTRAST
+        } else {
+            Self::DARK
+        }
+    }
+}
+
 /// Stili per il testo
-pub fn text_primary(_theme: &Theme) -> TextAppearance {
+pub fn text_primary(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::TEXT_PRIMARY),
+        color: Some(palette.text_primary),
     }
 }
 
-pub fn text_secondary(_theme: &Theme) -> TextAppearance {
+pub fn text_secondary(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::TEXT_SECONDARY),
+        color: Some(palette.text_secondary),
     }
 }
 
-pub fn text_muted(_theme: &Theme) -> TextAppearance {
+pub fn text_muted(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::TEXT_MUTED),
+        color: Some(palette.text_muted),
     }
 }
 
-pub fn text_accent(_theme: &Theme) -> TextAppearance {
+pub fn text_accent(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::PRIMARY),
+        color: Some(palette.primary),
     }
 }
 
-pub fn text_success(_theme: &Theme) -> TextAppearance {
+pub fn text_success(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::SUCCESS),
+        color: Some(palette.success),
     }
 }
 
-pub fn text_warning(_theme: &Theme) -> TextAppearance {
+pub fn text_warning(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::WARNING),
+        color: Some(palette.warning),
     }
 }
 
-pub fn text_error(_theme: &Theme) -> TextAppearance {
+pub fn text_error(theme: &Theme) -> TextAppearance {
+    let palette = Palette::for_theme(theme);
     TextAppearance {
-        color: Some(colors::ERROR),
+        color: Some(palette.error),
     }
 }
 
 /// Stili per i container
-pub fn container_primary(_theme: &Theme) -> ContainerAppearance {
+pub fn container_primary(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
-        background: Some(Background::Color(colors::BACKGROUND)),
+        text_color: Some(palette.text_primary),
+        background: Some(Background::Color(palette.background)),
         border: Border {
-            color: colors::BORDER,
+            color: palette.border,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 4.0,
         },
     }
 }
 
-pub fn container_secondary(_theme: &Theme) -> ContainerAppearance {
+pub fn container_secondary(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_SECONDARY),
-        background: Some(Background::Color(colors::SURFACE)),
+        text_color: Some(palette.text_secondary),
+        background: Some(Background::Color(palette.surface)),
         border: Border {
-            color: colors::BORDER,
+            color: palette.border,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 1.0),
             blur_radius: 2.0,
         },
     }
 }
 
-pub fn container_header(_theme: &Theme) -> ContainerAppearance {
+pub fn container_header(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
-        background: Some(Background::Color(colors::SURFACE_VARIANT)),
+        text_color: Some(palette.text_primary),
+        background: Some(Background::Color(palette.surface_variant)),
         border: Border {
-            color: colors::BORDER,
+            color: palette.border,
             width: 0.0,
             radius: Radius::from(radius::SMALL),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 8.0,
         },
     }
 }
 
-pub fn container_disabled(_theme: &Theme) -> ContainerAppearance {
+pub fn container_disabled(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_MUTED),
+        text_color: Some(palette.text_muted),
         background: Some(Background::Color(Color::from_rgba(
-            colors::SURFACE.r,
-            colors::SURFACE.g,
-            colors::SURFACE.b,
+            palette.surface.r,
+            palette.surface.g,
+            palette.surface.b,
             0.5,
         ))),
         border: Border {
             color: Color::from_rgba(
-                colors::BORDER.r,
-                colors::BORDER.g,
-                colors::BORDER.b,
+                palette.border.r,
+                palette.border.g,
+                palette.border.b,
                 0.5,
             ),
             width: 1.0,
@@ -208,88 +401,92 @@ pub fn container_disabled(_theme: &Theme) -> ContainerAppearance {
 }
 
 /// Container per notifiche
-pub fn container_success(_theme: &Theme) -> ContainerAppearance {
+pub fn container_success(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
+        text_color: Some(palette.text_primary),
         background: Some(Background::Color(Color::from_rgba(
-            colors::SUCCESS.r,
-            colors::SUCCESS.g,
-            colors::SUCCESS.b,
+            palette.success.r,
+            palette.success.g,
+            palette.success.b,
             0.1,
         ))),
         border: Border {
-            color: colors::SUCCESS,
+            color: palette.success,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 8.0,
         },
     }
 }
 
-pub fn container_error(_theme: &Theme) -> ContainerAppearance {
+pub fn container_error(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
+        text_color: Some(palette.text_primary),
         background: Some(Background::Color(Color::from_rgba(
-            colors::ERROR.r,
-            colors::ERROR.g,
-            colors::ERROR.b,
+            palette.error.r,
+            palette.error.g,
+            palette.error.b,
             0.1,
         ))),
         border: Border {
-            color: colors::ERROR,
+            color: palette.error,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 8.0,
         },
     }
 }
 
-pub fn container_warning(_theme: &Theme) -> ContainerAppearance {
+pub fn container_warning(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
+        text_color: Some(palette.text_primary),
         background: Some(Background::Color(Color::from_rgba(
-            colors::WARNING.r,
-            colors::WARNING.g,
-            colors::WARNING.b,
+            palette.warning.r,
+            palette.warning.g,
+            palette.warning.b,
             0.1,
         ))),
         border: Border {
-            color: colors::WARNING,
+            color: palette.warning,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 8.0,
         },
     }
 }
 
-pub fn container_info(_theme: &Theme) -> ContainerAppearance {
+pub fn container_info(theme: &Theme) -> ContainerAppearance {
+    let palette = Palette::for_theme(theme);
     ContainerAppearance {
-        text_color: Some(colors::TEXT_PRIMARY),
+        text_color: Some(palette.text_primary),
         background: Some(Background::Color(Color::from_rgba(
-            colors::INFO.r,
-            colors::INFO.g,
-            colors::INFO.b,
+            palette.info.r,
+            palette.info.g,
+            palette.info.b,
             0.1,
         ))),
         border: Border {
-            color: colors::INFO,
+            color: palette.info,
             width: 1.0,
             radius: Radius::from(radius::MEDIUM),
         },
         shadow: Shadow {
-            color: colors::SHADOW,
+            color: palette.shadow,
             offset: Vector::new(0.0, 2.0),
             blur_radius: 8.0,
         },
@@ -297,66 +494,67 @@ pub fn container_info(_theme: &Theme) -> ContainerAppearance {
 }
 
 /// Stili per i pulsanti
-pub fn button_primary(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+pub fn button_primary(theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+    let palette = Palette::for_theme(theme);
     match status {
         ButtonStatus::Active => ButtonAppearance {
-            background: Some(Background::Color(colors::PRIMARY)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.primary)),
+            text_color: palette.on_primary,
             border: Border {
-                color: colors::PRIMARY,
+                color: palette.primary,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 2.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 2.0),
                 blur_radius: 4.0,
             },
         },
         ButtonStatus::Hovered => ButtonAppearance {
-            background: Some(Background::Color(colors::PRIMARY_HOVER)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.primary_hover)),
+            text_color: palette.on_primary,
             border: Border {
-                color: colors::PRIMARY_HOVER,
+                color: palette.primary_hover,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 4.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 4.0),
                 blur_radius: 8.0,
             },
         },
         ButtonStatus::Pressed => ButtonAppearance {
-            background: Some(Background::Color(colors::PRIMARY_ACTIVE)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.primary_active)),
+            text_color: palette.on_primary,
             border: Border {
-                color: colors::PRIMARY_ACTIVE,
+                color: palette.primary_active,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 1.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 1.0),
                 blur_radius: 2.0,
             },
         },
         ButtonStatus::Disabled => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.5,
             ))),
-            text_color: colors::TEXT_MUTED,
+            text_color: palette.text_muted,
             border: Border {
                 color: Color::from_rgba(
-                    colors::BORDER.r,
-                    colors::BORDER.g,
-                    colors::BORDER.b,
+                    palette.border.r,
+                    palette.border.g,
+                    palette.border.b,
                     0.5,
                 ),
                 width: 1.0,
@@ -368,71 +566,72 @@ pub fn button_primary(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance
     }
 }
 
-pub fn button_secondary(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+pub fn button_secondary(theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+    let palette = Palette::for_theme(theme);
     match status {
         ButtonStatus::Active => ButtonAppearance {
-            background: Some(Background::Color(colors::SURFACE)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.surface)),
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::BORDER,
+                color: palette.border,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 2.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 2.0),
                 blur_radius: 4.0,
             },
         },
         ButtonStatus::Hovered => ButtonAppearance {
-            background: Some(Background::Color(colors::SURFACE_VARIANT)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.surface_variant)),
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY,
+                color: palette.primary,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 4.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 4.0),
                 blur_radius: 8.0,
             },
         },
         ButtonStatus::Pressed => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE_VARIANT.r,
-                colors::SURFACE_VARIANT.g,
-                colors::SURFACE_VARIANT.b,
+                palette.surface_variant.r,
+                palette.surface_variant.g,
+                palette.surface_variant.b,
                 0.8,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY_ACTIVE,
+                color: palette.primary_active,
                 width: 1.0,
                 radius: Radius::from(radius::MEDIUM),
             },
             shadow_offset: Vector::new(0.0, 1.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 1.0),
                 blur_radius: 2.0,
             },
         },
         ButtonStatus::Disabled => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.5,
             ))),
-            text_color: colors::TEXT_MUTED,
+            text_color: palette.text_muted,
             border: Border {
                 color: Color::from_rgba(
-                    colors::BORDER.r,
-                    colors::BORDER.g,
-                    colors::BORDER.b,
+                    palette.border.r,
+                    palette.border.g,
+                    palette.border.b,
                     0.5,
                 ),
                 width: 1.0,
@@ -444,71 +643,72 @@ pub fn button_secondary(_theme: &Theme, status: ButtonStatus) -> ButtonAppearanc
     }
 }
 
-pub fn button_card(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+pub fn button_card(theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+    let palette = Palette::for_theme(theme);
     match status {
         ButtonStatus::Active => ButtonAppearance {
-            background: Some(Background::Color(colors::SURFACE)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.surface)),
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::BORDER,
+                color: palette.border,
                 width: 1.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 2.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 2.0),
                 blur_radius: 4.0,
             },
         },
         ButtonStatus::Hovered => ButtonAppearance {
-            background: Some(Background::Color(colors::SURFACE_VARIANT)),
-            text_color: colors::TEXT_PRIMARY,
+            background: Some(Background::Color(palette.surface_variant)),
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY,
+                color: palette.primary,
                 width: 2.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 4.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 4.0),
                 blur_radius: 12.0,
             },
         },
         ButtonStatus::Pressed => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE_VARIANT.r,
-                colors::SURFACE_VARIANT.g,
-                colors::SURFACE_VARIANT.b,
+                palette.surface_variant.r,
+                palette.surface_variant.g,
+                palette.surface_variant.b,
                 0.8,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY_ACTIVE,
+                color: palette.primary_active,
                 width: 2.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 1.0),
             shadow: Shadow {
-                color: colors::SHADOW,
+                color: palette.shadow,
                 offset: Vector::new(0.0, 1.0),
                 blur_radius: 2.0,
             },
         },
         ButtonStatus::Disabled => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.3,
             ))),
-            text_color: colors::TEXT_MUTED,
+            text_color: palette.text_muted,
             border: Border {
                 color: Color::from_rgba(
-                    colors::BORDER.r,
-                    colors::BORDER.g,
-                    colors::BORDER.b,
+                    palette.border.r,
+                    palette.border.g,
+                    palette.border.b,
                     0.3,
                 ),
                 width: 1.0,
@@ -520,27 +720,28 @@ pub fn button_card(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
     }
 }
 
-pub fn button_selected(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+pub fn button_selected(theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+    let palette = Palette::for_theme(theme);
     match status {
         ButtonStatus::Active => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::PRIMARY.r,
-                colors::PRIMARY.g,
-                colors::PRIMARY.b,
+                palette.primary.r,
+                palette.primary.g,
+                palette.primary.b,
                 0.2,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY,
+                color: palette.primary,
                 width: 2.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 0.0),
             shadow: Shadow {
                 color: Color::from_rgba(
-                    colors::PRIMARY.r,
-                    colors::PRIMARY.g,
-                    colors::PRIMARY.b,
+                    palette.primary.r,
+                    palette.primary.g,
+                    palette.primary.b,
                     0.3,
                 ),
                 offset: Vector::new(0.0, 0.0),
@@ -549,23 +750,23 @@ pub fn button_selected(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance
         },
         ButtonStatus::Hovered => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::PRIMARY.r,
-                colors::PRIMARY.g,
-                colors::PRIMARY.b,
+                palette.primary.r,
+                palette.primary.g,
+                palette.primary.b,
                 0.3,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY_HOVER,
+                color: palette.primary_hover,
                 width: 2.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 0.0),
             shadow: Shadow {
                 color: Color::from_rgba(
-                    colors::PRIMARY.r,
-                    colors::PRIMARY.g,
-                    colors::PRIMARY.b,
+                    palette.primary.r,
+                    palette.primary.g,
+                    palette.primary.b,
                     0.4,
                 ),
                 offset: Vector::new(0.0, 0.0),
@@ -574,23 +775,23 @@ pub fn button_selected(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance
         },
         ButtonStatus::Pressed => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::PRIMARY.r,
-                colors::PRIMARY.g,
-                colors::PRIMARY.b,
+                palette.primary.r,
+                palette.primary.g,
+                palette.primary.b,
                 0.4,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
-                color: colors::PRIMARY_ACTIVE,
+                color: palette.primary_active,
                 width: 2.0,
                 radius: Radius::from(radius::LARGE),
             },
             shadow_offset: Vector::new(0.0, 0.0),
             shadow: Shadow {
                 color: Color::from_rgba(
-                    colors::PRIMARY.r,
-                    colors::PRIMARY.g,
-                    colors::PRIMARY.b,
+                    palette.primary.r,
+                    palette.primary.g,
+                    palette.primary.b,
                     0.2,
                 ),
                 offset: Vector::new(0.0, 0.0),
@@ -599,17 +800,17 @@ pub fn button_selected(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance
         },
         ButtonStatus::Disabled => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.3,
             ))),
-            text_color: colors::TEXT_MUTED,
+            text_color: palette.text_muted,
             border: Border {
                 color: Color::from_rgba(
-                    colors::BORDER.r,
-                    colors::BORDER.g,
-                    colors::BORDER.b,
+                    palette.border.r,
+                    palette.border.g,
+                    palette.border.b,
                     0.3,
                 ),
                 width: 1.0,
@@ -621,11 +822,12 @@ pub fn button_selected(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance
     }
 }
 
-pub fn button_ghost(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+pub fn button_ghost(theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
+    let palette = Palette::for_theme(theme);
     match status {
         ButtonStatus::Active => ButtonAppearance {
             background: Some(Background::Color(Color::TRANSPARENT)),
-            text_color: colors::TEXT_SECONDARY,
+            text_color: palette.text_secondary,
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
@@ -636,12 +838,12 @@ pub fn button_ghost(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
         },
         ButtonStatus::Hovered => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.5,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
@@ -652,12 +854,12 @@ pub fn button_ghost(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
         },
         ButtonStatus::Pressed => ButtonAppearance {
             background: Some(Background::Color(Color::from_rgba(
-                colors::SURFACE.r,
-                colors::SURFACE.g,
-                colors::SURFACE.b,
+                palette.surface.r,
+                palette.surface.g,
+                palette.surface.b,
                 0.7,
             ))),
-            text_color: colors::TEXT_PRIMARY,
+            text_color: palette.text_primary,
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
@@ -668,7 +870,7 @@ pub fn button_ghost(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
         },
         ButtonStatus::Disabled => ButtonAppearance {
             background: Some(Background::Color(Color::TRANSPARENT)),
-            text_color: colors::TEXT_MUTED,
+            text_color: palette.text_muted,
             border: Border {
                 color: Color::TRANSPARENT,
                 width: 0.0,
@@ -681,35 +883,86 @@ pub fn button_ghost(_theme: &Theme, status: ButtonStatus) -> ButtonAppearance {
 }
 
 /// Stili per le progress bar
-pub fn progress_bar_primary(_theme: &Theme) -> ProgressBarAppearance {
+pub fn progress_bar_primary(theme: &Theme) -> ProgressBarAppearance {
+    let palette = Palette::for_theme(theme);
     ProgressBarAppearance {
-        background: Background::Color(colors::SURFACE),
-        bar: Background::Color(colors::PRIMARY),
+        background: Background::Color(palette.surface),
+        bar: Background::Color(palette.primary),
         border_radius: Radius::from(radius::SMALL),
     }
 }
 
-pub fn progress_bar_success(_theme: &Theme) -> ProgressBarAppearance {
+pub fn progress_bar_success(theme: &Theme) -> ProgressBarAppearance {
+    let palette = Palette::for_theme(theme);
     ProgressBarAppearance {
-        background: Background::Color(colors::SURFACE),
-        bar: Background::Color(colors::SUCCESS),
+        background: Background::Color(palette.surface),
+        bar: Background::Color(palette.success),
         border_radius: Radius::from(radius::SMALL),
     }
 }
 
-pub fn progress_bar_warning(_theme: &Theme) -> ProgressBarAppearance {
+pub fn progress_bar_warning(theme: &Theme) -> ProgressBarAppearance {
+    let palette = Palette::for_theme(theme);
     ProgressBarAppearance {
-        background: Background::Color(colors::SURFACE),
-        bar: Background::Color(colors::WARNING),
+        background: Background::Color(palette.surface),
+        bar: Background::Color(palette.warning),
         border_radius: Radius::from(radius::SMALL),
     }
 }
 
-pub fn progress_bar_error(_theme: &Theme) -> ProgressBarAppearance {
+pub fn progress_bar_error(theme: &Theme) -> ProgressBarAppearance {
+    let palette = Palette::for_theme(theme);
     ProgressBarAppearance {
-        background: Background::Color(colors::SURFACE),
-        bar: Background::Color(colors::ERROR),
+        background: Background::Color(palette.surface),
+        bar: Background::Color(palette.error),
         border_radius: Radius::from(radius::SMALL),
     }
 }
-    
\ No newline at end of file
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_scale_changes_every_size_consistently() {
+        let bases = [font_size::TINY, font_size::SMALL, font_size::MEDIUM, font_size::LARGE, font_size::XLARGE];
+
+        // Alla scala predefinita le dimensioni sono quelle di base
+        for base in bases {
+            assert_eq!(font_size::at_scale(base, font_size::DEFAULT_SCALE_PERCENT), f32::from(base));
+        }
+
+        for percent in [font_size::MIN_SCALE_PERCENT, 125, 150, font_size::MAX_SCALE_PERCENT] {
+            let sizes: Vec<f32> = bases.iter().map(|&base| font_size::at_scale(base, percent)).collect();
+            // L'ordine tra le dimensioni resta lo stesso
+            assert!(sizes.windows(2).all(|pair| pair[0] < pair[1]), "{}%: {:?}", percent, sizes);
+            for (&base, &size) in bases.iter().zip(&sizes) {
+                let expected = f32::from(base) * percent as f32 / 100.0;
+                assert!((size - expected).abs() <= 0.5, "{}% of {}: {}", percent, base, size);
+            }
+        }
+        assert_eq!(font_size::at_scale(font_size::SMALL, 150), f32::from(font_size::LARGE));
+        assert_eq!(font_size::at_scale(font_size::SMALL, 200), f32::from(font_size::XLARGE));
+
+        assert_eq!(font_size::at_scale(font_size::MEDIUM, 150), 21.0);
+        assert_eq!(font_size::clamp_percent(1_000), font_size::MAX_SCALE_PERCENT);
+        assert_eq!(font_size::clamp_percent(10), font_size::MIN_SCALE_PERCENT);
+
+        // Testi con e senza dimensione esplicita seguono la stessa scala. La
+        // scala è globale e non viene cambiata qui, perché i test girano in parallelo.
+        assert_eq!(font_size::default_text_size().0, font_size::scaled(font_size::MEDIUM));
+    }
+
+    #[test]
+    fn test_high_contrast_theme_uses_its_palette() {
+        assert_eq!(Palette::for_theme(&high_contrast_theme()), Palette::HIGH_CONTRAST);
+        assert_eq!(Palette::for_theme(&Theme::Dark), Palette::DARK);
+        assert_eq!(Palette::for_theme(&Theme::Light), Palette::DARK);
+        assert_eq!(button_primary(&high_contrast_theme(), ButtonStatus::Active).text_color, Color::BLACK);
+        assert_eq!(text_primary(&high_contrast_theme()).color, Some(Color::WHITE));
+
+        // I colori usati fuori dagli stili seguono il tema attivo
+        assert_eq!(Palette::for_high_contrast(true).text_muted, high_contrast::TEXT_MUTED);
+        assert_eq!(Palette::for_high_contrast(false).text_muted, colors::TEXT_MUTED);
+    }
+}
@@ -45,7 +45,7 @@ impl AboutView {
         let header = row![
             button(
                 text("← Indietro")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
             )
             .on_press(Message::ShowMainView)
             .style(iced::theme::Button::Secondary),
@@ -53,8 +53,8 @@ impl AboutView {
             Space::with_width(styles::spacing::MEDIUM),
             
             text("Informazioni")
-                .size(24)
-                .style(styles::colors::TEXT_PRIMARY),
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
+                .style(styles::Palette::active().text_primary),
         ]
         .align_items(Alignment::Center)
         .padding(styles::spacing::MEDIUM.0);
@@ -119,20 +119,20 @@ impl AboutView {
             column![
                 // Logo (emoji come placeholder)
                 text("📱")
-                    .size(64)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(64))
+                    .style(styles::Palette::active().text_primary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 // Nome applicazione
                 text("AirWin")
-                    .size(32)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(32))
+                    .style(styles::Palette::active().text_primary),
                 
                 // Sottotitolo
                 text("Condivisione wireless per Windows")
-                    .size(16)
-                    .style(styles::colors::TEXT_MUTED),
+                    .size(styles::font_size::scaled(16))
+                    .style(styles::Palette::active().text_muted),
             ]
             .align_items(Alignment::Center)
             .spacing(styles::spacing::SMALL)
@@ -147,26 +147,26 @@ impl AboutView {
         let version_items = column![
             row![
                 text("Versione:")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary)
                     .width(Length::FillPortion(1)),
                 
                 text(self.app_version.clone())
-                    .size(14)
-                    .style(styles::colors::TEXT_MUTED)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_muted)
                     .width(Length::FillPortion(2)),
             ]
             .align_items(Alignment::Center),
             
             row![
                 text("Build:")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary)
                     .width(Length::FillPortion(1)),
                 
                 text(self.build_date.clone())
-                    .size(14)
-                    .style(styles::colors::TEXT_MUTED)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_muted)
                     .width(Length::FillPortion(2)),
             ]
             .align_items(Alignment::Center),
@@ -179,13 +179,13 @@ impl AboutView {
                 
                 row![
                     text("Commit:")
-                        .size(14)
-                        .style(styles::colors::TEXT_PRIMARY)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().text_primary)
                         .width(Length::FillPortion(1)),
                     
                     text(commit.clone())
-                        .size(14)
-                        .style(styles::colors::TEXT_MUTED)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().text_muted)
                         .width(Length::FillPortion(2)),
                 ]
                 .align_items(Alignment::Center),
@@ -198,8 +198,8 @@ impl AboutView {
         container(
             column![
                 text("Versione")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
@@ -217,20 +217,20 @@ impl AboutView {
         container(
             column![
                 text("Descrizione")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("AirWin è un'applicazione che porta le funzionalità di AirDrop e AirPlay di Apple su Windows. Permette di condividere file, link e contenuti multimediali tra dispositivi Apple e Windows in modo semplice e intuitivo.")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("L'applicazione utilizza i protocolli di rete standard per garantire compatibilità e sicurezza nelle comunicazioni wireless.")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary),
             ]
         )
         .style(styles::container_secondary)
@@ -254,8 +254,8 @@ impl AboutView {
         container(
             column![
                 text("Funzionalità")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
@@ -278,17 +278,17 @@ impl AboutView {
     ) -> Element<Message> {
         row![
             text(icon)
-                .size(20)
+                .size(styles::font_size::scaled(20))
                 .width(Length::Fixed(40.0)),
             
             column![
                 text(title)
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary),
                 
                 text(description)
-                    .size(12)
-                    .style(styles::colors::TEXT_MUTED),
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
             ]
             .spacing(iced::Pixels(styles::spacing::SMALL.0 / 2.0)),
         ]
@@ -302,41 +302,41 @@ impl AboutView {
         container(
             column![
                 text("Crediti")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("Sviluppato con ❤️ utilizzando:")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary),
                 
                 Space::with_height(styles::spacing::SMALL),
                 
                 column![
                     text("• Rust - Linguaggio di programmazione")
-                        .size(12)
-                        .style(styles::colors::TEXT_MUTED),
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
                     
                     text("• Iced - Framework per interfacce grafiche")
-                        .size(12)
-                        .style(styles::colors::TEXT_MUTED),
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
                     
                     text("• Tokio - Runtime asincrono")
-                        .size(12)
-                        .style(styles::colors::TEXT_MUTED),
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
                     
                     text("• mDNS-SD - Scoperta servizi di rete")
-                        .size(12)
-                        .style(styles::colors::TEXT_MUTED),
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
                 ]
                 .spacing(iced::Pixels(styles::spacing::SMALL.0 / 2.0)),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("Ringraziamenti speciali alla comunità open source per i contributi e il supporto.")
-                    .size(12)
-                    .style(styles::colors::TEXT_MUTED),
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
             ]
         )
         .style(styles::container_secondary)
@@ -350,26 +350,26 @@ impl AboutView {
         container(
             column![
                 text("Licenze")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("AirWin è distribuito sotto licenza MIT.")
-                    .size(14)
-                    .style(styles::colors::TEXT_PRIMARY),
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_primary),
                 
                 Space::with_height(styles::spacing::SMALL),
                 
                 text("Questo software utilizza librerie di terze parti, ciascuna con la propria licenza. Per informazioni dettagliate, consulta il file LICENSE nel repository del progetto.")
-                    .size(12)
-                    .style(styles::colors::TEXT_MUTED),
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 button(
                     text("📄 Visualizza Licenze")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press(Message::OpenLicenses)
                 .style(iced::theme::Button::Secondary),
@@ -386,22 +386,22 @@ impl AboutView {
         container(
             column![
                 text("Collegamenti")
-                    .size(18)
-                    .style(styles::colors::TEXT_SECONDARY),
+                    .size(styles::font_size::scaled(styles::font_size::LARGE))
+                    .style(styles::Palette::active().text_secondary),
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 row![
                     button(
                         text("🌐 Sito Web")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     )
                     .on_press(Message::OpenWebsite)
                     .style(iced::theme::Button::Secondary),
                     
                     button(
                         text("📚 Documentazione")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     )
                     .on_press(Message::OpenDocumentation)
                     .style(iced::theme::Button::Secondary),
@@ -411,14 +411,14 @@ impl AboutView {
                 row![
                     button(
                         text("🐛 Segnala Bug")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     )
                     .on_press(Message::OpenIssues)
                     .style(iced::theme::Button::Secondary),
                     
                     button(
                         text("💡 Richiedi Funzionalità")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     )
                     .on_press(Message::OpenFeatureRequest)
                     .style(iced::theme::Button::Secondary),
@@ -428,8 +428,8 @@ impl AboutView {
                 Space::with_height(styles::spacing::MEDIUM),
                 
                 text("Per supporto e assistenza, visita il nostro repository GitHub o contatta il team di sviluppo.")
-                    .size(12)
-                    .style(styles::colors::TEXT_MUTED),
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
             ]
         )
         .style(styles::container_secondary)
@@ -0,0 +1,440 @@
+//! Vista della diagnostica
+//!
+//! Riunisce in una schermata lo stato dei servizi e dei task, le porte, le
+//! interfacce di rete, Bluetooth, AWDL, le regole del firewall, le
+//! statistiche AirPlay e gli ultimi avvisi. I dati vengono raccolti di nuovo a intervalli regolari finché la
+//! vista è aperta.
+
+use iced::{
+    widget::{button, checkbox, column, container, row, scrollable, text, Space},
+    Alignment, Color, Element, Length,
+};
+
+use crate::network::capability_matrix::{self, LEGEND};
+use crate::network::DiscoveredDevice;
+use crate::protocols::airdrop::{AirDropStatus, ConnectionState};
+use crate::protocols::airplay::AirPlayStatus;
+use crate::protocols::airplay_stats::AirPlayStats;
+use crate::protocols::awdl::{AwdlAvailability, AwdlUtils};
+use crate::ui::{
+    messages::Message,
+    styles,
+    Theme,
+};
+use crate::utils::bug_report::{airplay_lines, scrub, BugReport};
+use crate::utils::firewall::{self, FirewallStatus};
+use crate::utils::supervisor::{TaskHealth, TaskState};
+
+/// Struttura per la vista della diagnostica
+pub struct DiagnosticsView<'a> {
+    /// Ultima raccolta, `None` finché la prima non è terminata
+    report: Option<&'a BugReport>,
+    airdrop_enabled: bool,
+    airdrop_status: &'a AirDropStatus,
+    /// Connessioni aperte sul server HTTPS AirDrop
+    https_connections: usize,
+    /// Motivo della modalità solo invio
+    send_only: Option<&'a str>,
+    airplay_enabled: bool,
+    airplay_status: &'a AirPlayStatus,
+    airplay_stats: AirPlayStats,
+    awdl: AwdlAvailability,
+    tasks: Vec<TaskHealth>,
+    /// Nome dei dispositivi con un servizio mDNS non riconosciuto e il suo tipo
+    unknown_services: Vec<(String, String)>,
+    /// Dispositivi trovati, per la matrice delle capacità
+    devices: &'a [DiscoveredDevice],
+    full_macs: bool,
+    /// Aggiunta delle regole del firewall in corso
+    adding_firewall_rules: bool,
+}
+
+impl<'a> DiagnosticsView<'a> {
+    /// Crea una nuova istanza della vista della diagnostica
+    pub fn new(
+        report: Option<&'a BugReport>,
+        airdrop_enabled: bool,
+        airdrop_status: &'a AirDropStatus,
+        https_connections: usize,
+        send_only: Option<&'a str>,
+        airplay_enabled: bool,
+        airplay_status: &'a AirPlayStatus,
+        airplay_stats: AirPlayStats,
+        awdl: AwdlAvailability,
+        tasks: Vec<TaskHealth>,
+        unknown_services: Vec<(String, String)>,
+        devices: &'a [DiscoveredDevice],
+        full_macs: bool,
+        adding_firewall_rules: bool,
+    ) -> Self {
+        Self {
+            report,
+            airdrop_enabled,
+            airdrop_status,
+            https_connections,
+            send_only,
+            airplay_enabled,
+            airplay_status,
+            airplay_stats,
+            awdl,
+            tasks,
+            unknown_services,
+            devices,
+            full_macs,
+            adding_firewall_rules,
+        }
+    }
+
+    /// Renderizza la vista della diagnostica
+    pub fn view(&self, _theme: &Theme) -> Element<'a, Message> {
+        let header = row![
+            button(text("← Indietro").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(Message::ShowSettings)
+                .style(iced::theme::Button::Secondary),
+
+            Space::with_width(styles::spacing::MEDIUM),
+
+            text("Diagnostica")
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
+                .style(styles::Palette::active().text_primary),
+
+            Space::with_width(Length::Fill),
+
+            button(text("🔄 Aggiorna").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(Message::RefreshDiagnostics)
+                .style(iced::theme::Button::Secondary),
+
+            button(text("📋 Copia diagnostica").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(Message::CopyDiagnostics),
+
+            checkbox("Indirizzi MAC completi", self.full_macs)
+                .on_toggle(Message::FullMacsToggled),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .align_items(Alignment::Center)
+        .padding(styles::spacing::MEDIUM.0);
+
+        let mut sections = column![self.services()].spacing(styles::spacing::LARGE);
+        match self.report {
+            Some(report) => {
+                sections = sections
+                    .push(self.ports(report))
+                    .push(self.firewall(report))
+                    .push(self.interfaces(report))
+                    .push(self.unknown_services())
+                    .push(self.capability_matrix())
+                    .push(self.adapters(report))
+                    .push(self.airplay())
+                    .push(self.recent_events(report));
+            }
+            None => {
+                sections = sections.push(
+                    text("Raccolta dei dati in corso...")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().text_muted),
+                );
+            }
+        }
+
+        column![
+            header,
+            scrollable(sections.padding(styles::spacing::MEDIUM.0)).height(Length::Fill),
+        ]
+        .spacing(styles::spacing::MEDIUM)
+        .padding(styles::spacing::MEDIUM.0)
+        .into()
+    }
+
+    /// Servizi, privilegi e task in background
+    fn services(&self) -> Element<'a, Message> {
+        let airdrop = if !self.airdrop_enabled {
+            ("disattivato".to_string(), styles::Palette::active().text_muted)
+        } else if let Some(error) = &self.airdrop_status.error {
+            (format!("errore: {}", error), styles::Palette::active().error)
+        } else if let Some(reason) = self.send_only {
+            (format!("solo invio ({})", reason), styles::Palette::active().warning)
+        } else {
+            let state = match self.airdrop_status.connection {
+                ConnectionState::Idle => "in ascolto",
+                ConnectionState::Connecting => "connessione in corso",
+                ConnectionState::Connected => "connesso",
+            };
+            (state.to_string(), styles::Palette::active().success)
+        };
+
+        let airplay = if !self.airplay_enabled {
+            ("disattivato".to_string(), styles::Palette::active().text_muted)
+        } else {
+            match self.airplay_status {
+                AirPlayStatus::Idle => ("in attesa".to_string(), styles::Palette::active().success),
+                AirPlayStatus::Connecting => ("connessione in corso".to_string(), styles::Palette::active().success),
+                AirPlayStatus::Connected => ("trasmissione in corso".to_string(), styles::Palette::active().success),
+                AirPlayStatus::Failed(error) => (format!("errore: {}", error), styles::Palette::active().error),
+            }
+        };
+
+        let awdl = match self.awdl {
+            AwdlAvailability::On => ("attivo", styles::Palette::active().success),
+            AwdlAvailability::Off => ("disattivato", styles::Palette::active().text_muted),
+            AwdlAvailability::Unavailable => ("non disponibile", styles::Palette::active().warning),
+        };
+        let awdl_state = match self.report.and_then(|report| report.awdl_mac) {
+            Some(mac) => scrub(
+                &format!("{}, MAC {}", awdl.0, AwdlUtils::format_mac_address(&mac)),
+                self.full_macs,
+            ),
+            None => awdl.0.to_string(),
+        };
+
+        let mut lines = column![
+            status_line("AirDrop", airdrop.0, airdrop.1),
+            status_line(
+                "Connessioni HTTPS",
+                self.https_connections.to_string(),
+                styles::Palette::active().text_secondary,
+            ),
+            status_line("AirPlay", airplay.0, airplay.1),
+            status_line("AWDL", awdl_state, awdl.1),
+        ]
+        .spacing(styles::spacing::TINY);
+
+        if let Some(report) = self.report {
+            let privileges = if report.diagnostics.elevated {
+                ("amministratore", styles::Palette::active().success)
+            } else {
+                ("utente standard", styles::Palette::active().warning)
+            };
+            lines = lines.push(status_line("Privilegi", privileges.0.to_string(), privileges.1));
+            for limitation in &report.diagnostics.limitations {
+                lines = lines.push(text(format!("⚠ {}", limitation)).size(styles::font_size::scaled(styles::font_size::SMALL)).style(styles::Palette::active().warning));
+            }
+        }
+
+        for task in &self.tasks {
+            let color = match task.state {
+                TaskState::Running => styles::Palette::active().text_secondary,
+                TaskState::Restarting => styles::Palette::active().warning,
+                TaskState::Failed => styles::Palette::active().error,
+            };
+            lines = lines.push(text(task.to_line()).size(styles::font_size::scaled(styles::font_size::SMALL)).style(color));
+        }
+
+        section("Servizi", lines.into())
+    }
+
+    /// Porte dei servizi ed esito della verifica
+    fn ports(&self, report: &BugReport) -> Element<'a, Message> {
+        let lines = report.ports.iter().fold(
+            column![].spacing(styles::spacing::TINY),
+            |col, (name, port, result)| {
+                let color = if result.starts_with("non disponibile") {
+                    styles::Palette::active().error
+                } else {
+                    styles::Palette::active().text_secondary
+                };
+                col.push(status_line(&format!("{} {}", name, port), result.clone(), color))
+            },
+        );
+        section("Porte", lines.into())
+    }
+
+    /// Regole di Windows Firewall e comandi per aggiungerle
+    fn firewall(&self, report: &BugReport) -> Element<'a, Message> {
+        let color = match report.firewall {
+            FirewallStatus::Allowed => styles::Palette::active().success,
+            FirewallStatus::Missing(_) => styles::Palette::active().error,
+            FirewallStatus::NotApplicable => styles::Palette::active().text_muted,
+            FirewallStatus::Unknown(_) => styles::Palette::active().warning,
+        };
+        let mut lines = column![status_line("Windows Firewall", report.firewall.describe(), color)]
+            .spacing(styles::spacing::TINY);
+
+        if report.firewall.needs_rules() {
+            let hint = if report.diagnostics.elevated {
+                "Gli altri dispositivi non possono connettersi a queste porte."
+            } else {
+                "Gli altri dispositivi non possono connettersi a queste porte. Windows chiederà di confermare l'aggiunta come amministratore; in alternativa esegui questi comandi in un prompt dei comandi come amministratore:"
+            };
+            lines = lines.push(muted(hint));
+            if !report.diagnostics.elevated {
+                lines = lines.push(
+                    text(firewall::rule_commands_text(&report.diagnostics.ports))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .font(iced::Font::MONOSPACE)
+                        .style(styles::Palette::active().text_secondary),
+                );
+            }
+            lines = lines.push(
+                row![
+                    button(text(if self.adding_firewall_rules { "⏳ Aggiunta..." } else { "🛡 Aggiungi regole" }).size(styles::font_size::scaled(styles::font_size::SMALL)))
+                        .on_press_maybe((!self.adding_firewall_rules).then_some(Message::AddFirewallRules)),
+                    button(text("📋 Copia comandi").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                        .on_press(Message::CopyFirewallCommands)
+                        .style(iced::theme::Button::Secondary),
+                ]
+                .spacing(styles::spacing::SMALL),
+            );
+        }
+        section("Firewall", lines.into())
+    }
+
+    fn interfaces(&self, report: &BugReport) -> Element<'a, Message> {
+        let lines: Element<'a, Message> = if report.interfaces.is_empty() {
+            muted("Nessuna interfaccia trovata")
+        } else {
+            report.interfaces
+                .iter()
+                .fold(column![].spacing(styles::spacing::TINY), |col, (name, ip)| {
+                    col.push(status_line(name, ip.clone(), styles::Palette::active().text_secondary))
+                })
+                .into()
+        };
+        section("Interfacce di rete", lines)
+    }
+
+    /// Dispositivi che hanno risposto con un servizio sconosciuto
+    fn unknown_services(&self) -> Element<'a, Message> {
+        let lines: Element<'a, Message> = if self.unknown_services.is_empty() {
+            muted("Nessuno")
+        } else {
+            self.unknown_services
+                .iter()
+                .fold(column![].spacing(styles::spacing::TINY), |col, (name, service)| {
+                    col.push(status_line(name, service.clone(), styles::Palette::active().text_secondary))
+                })
+                .into()
+        };
+        section("Servizi non riconosciuti", lines)
+    }
+
+    /// Capacità lette dai record TXT di ogni dispositivo, per capire perché
+    /// un'azione è disponibile o no
+    fn capability_matrix(&self) -> Element<'a, Message> {
+        if self.devices.is_empty() {
+            return section("Capacità dei dispositivi", muted("Nessun dispositivo trovato"));
+        }
+        let cell = |content: String, width: f32, color: Color| {
+            text(content).size(styles::font_size::scaled(styles::font_size::SMALL)).style(color).width(Length::Fixed(width))
+        };
+        let header = LEGEND.iter().fold(
+            row![cell("Dispositivo".to_string(), 160.0, styles::Palette::active().text_muted)].spacing(styles::spacing::SMALL),
+            |header, (column, _)| header.push(cell(column.to_string(), 110.0, styles::Palette::active().text_muted)),
+        );
+        let table = capability_matrix::rows(self.devices).into_iter().fold(
+            column![header].spacing(styles::spacing::TINY),
+            |table, device| {
+                let cells = device.cells().into_iter().fold(
+                    row![cell(device.name.clone(), 160.0, styles::Palette::active().text_primary)].spacing(styles::spacing::SMALL),
+                    |cells, value| {
+                        let color = match value.as_str() {
+                            "yes" => styles::Palette::active().success,
+                            "no" => styles::Palette::active().text_muted,
+                            _ => styles::Palette::active().text_secondary,
+                        };
+                        cells.push(cell(value, 110.0, color))
+                    },
+                );
+                table.push(cells)
+            },
+        );
+        let legend = LEGEND.iter().fold(
+            column![muted("? = non annunciato dal dispositivo")].spacing(styles::spacing::TINY),
+            |legend, (column, meaning)| legend.push(muted(&format!("{}: {}", column, meaning))),
+        );
+
+        let content = column![
+            scrollable(table).direction(scrollable::Direction::Horizontal(scrollable::Properties::default())),
+            legend,
+            button(text("📋 Copia come Markdown").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                .on_press(Message::CopyText {
+                    label: "Matrice delle capacità".to_string(),
+                    text: capability_matrix::to_markdown(self.devices),
+                })
+                .style(iced::theme::Button::Secondary),
+        ]
+        .spacing(styles::spacing::SMALL);
+        section("Capacità dei dispositivi", content.into())
+    }
+
+    /// Adattatori Bluetooth e frequenza dei poller
+    fn adapters(&self, report: &BugReport) -> Element<'a, Message> {
+        let bluetooth = match &report.ble_adapters {
+            Ok(0) => ("nessun adattatore".to_string(), styles::Palette::active().warning),
+            Ok(count) => (format!("{} adattatori", count), styles::Palette::active().success),
+            Err(e) => (format!("non disponibile ({})", e), styles::Palette::active().error),
+        };
+        let lines = report.poll_rates.iter().fold(
+            column![status_line("Bluetooth", bluetooth.0, bluetooth.1)].spacing(styles::spacing::TINY),
+            |col, (name, rate)| {
+                col.push(status_line(name, format!("{:.1}/s", rate), styles::Palette::active().text_secondary))
+            },
+        );
+        section("Adattatori e polling", lines.into())
+    }
+
+    /// Statistiche della sessione AirPlay in corso o dell'ultima
+    fn airplay(&self) -> Element<'a, Message> {
+        let lines: Element<'a, Message> = if self.airplay_stats.frames_sent == 0 {
+            muted("Nessuna sessione dall'avvio")
+        } else {
+            airplay_lines(&self.airplay_stats)
+                .into_iter()
+                .fold(column![].spacing(styles::spacing::TINY), |col, line| {
+                    col.push(text(line).size(styles::font_size::scaled(styles::font_size::SMALL)).style(styles::Palette::active().text_secondary))
+                })
+                .into()
+        };
+        section("AirPlay", lines)
+    }
+
+    fn recent_events(&self, report: &BugReport) -> Element<'a, Message> {
+        let lines: Element<'a, Message> = if report.recent_log.is_empty() {
+            muted("Nessun avviso o errore")
+        } else {
+            report.recent_log
+                .iter()
+                .fold(column![].spacing(styles::spacing::TINY), |col, line| {
+                    col.push(text(line.clone()).size(styles::font_size::scaled(styles::font_size::SMALL)).style(styles::Palette::active().text_secondary))
+                })
+                .push(
+                    button(text("Apri il registro eventi").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                        .on_press(Message::ShowEventLog)
+                        .style(iced::theme::Button::Secondary),
+                )
+                .into()
+        };
+        section("Ultimi avvisi ed errori", lines)
+    }
+}
+
+/// Titolo e contenuto di una sezione
+fn section<'a>(title: &str, content: Element<'a, Message>) -> Element<'a, Message> {
+    column![
+        text(title.to_string())
+            .size(styles::font_size::scaled(16))
+            .style(styles::Palette::active().text_primary),
+        container(content).padding([0, 0, 0, 12]),
+    ]
+    .spacing(styles::spacing::SMALL)
+    .into()
+}
+
+/// Riga "nome: stato" con lo stato colorato
+fn status_line<'a>(name: &str, state: String, color: Color) -> Element<'a, Message> {
+    row![
+        text(format!("{}:", name))
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
+            .style(styles::Palette::active().text_muted)
+            .width(Length::Fixed(180.0)),
+        text(state).size(styles::font_size::scaled(styles::font_size::SMALL)).style(color),
+    ]
+    .spacing(styles::spacing::SMALL)
+    .into()
+}
+
+fn muted<'a>(content: &str) -> Element<'a, Message> {
+    text(content.to_string())
+        .size(styles::font_size::scaled(styles::font_size::SMALL))
+        .style(styles::Palette::active().text_muted)
+        .into()
+}
@@ -0,0 +1,114 @@
+//! Vista della timeline degli eventi
+//!
+//! Mostra gli eventi pubblicati dai protocolli con data, gravità e origine,
+//! con un filtro per gravità e la copia negli appunti per le segnalazioni.
+
+use iced::{
+    widget::{button, column, container, row, scrollable, text, Space},
+    Alignment, Element, Length,
+};
+
+use crate::ui::{
+    messages::Message,
+    styles,
+    Theme,
+};
+use crate::utils::event_log::{LogEvent, Severity};
+
+/// Struttura per la vista della timeline
+pub struct EventLogView<'a> {
+    events: &'a [LogEvent],
+    filter: Option<Severity>,
+}
+
+impl<'a> EventLogView<'a> {
+    /// Crea una nuova istanza della vista della timeline
+    pub fn new(events: &'a [LogEvent], filter: Option<Severity>) -> Self {
+        Self { events, filter }
+    }
+
+    /// Renderizza la vista della timeline
+    pub fn view(&self, _theme: &Theme) -> Element<'a, Message> {
+        let header = row![
+            button(text("← Indietro").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(Message::ShowMainView)
+                .style(iced::theme::Button::Secondary),
+
+            Space::with_width(styles::spacing::MEDIUM),
+
+            text("Registro eventi")
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
+                .style(styles::Palette::active().text_primary),
+
+            Space::with_width(Length::Fill),
+
+            button(text("📋 Copia tutto").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press_maybe((!self.events.is_empty()).then_some(Message::CopyEventLog)),
+        ]
+        .align_items(Alignment::Center)
+        .padding(styles::spacing::MEDIUM.0);
+
+        let filters = [
+            (None, "Tutti"),
+            (Some(Severity::Info), "Info"),
+            (Some(Severity::Warning), "Avvisi"),
+            (Some(Severity::Error), "Errori"),
+        ]
+        .into_iter()
+        .fold(row![].spacing(styles::spacing::SMALL), |row, (severity, label)| {
+            let style = if self.filter == severity {
+                iced::theme::Button::Primary
+            } else {
+                iced::theme::Button::Secondary
+            };
+            row.push(
+                button(text(label).size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::EventLogFilterChanged(severity))
+                    .style(style),
+            )
+        });
+
+        let visible: Vec<&LogEvent> = self.events
+            .iter()
+            .filter(|event| self.filter.is_none() || self.filter == Some(event.severity))
+            .collect();
+
+        let list: Element<'a, Message> = if visible.is_empty() {
+            container(
+                text("Nessun evento registrato")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_muted),
+            )
+            .center_x()
+            .center_y()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+        } else {
+            let lines = visible.into_iter().fold(
+                column![].spacing(styles::spacing::TINY),
+                |col, event| {
+                    let color = match event.severity {
+                        Severity::Info => styles::Palette::active().text_secondary,
+                        Severity::Warning => styles::Palette::active().warning,
+                        Severity::Error => styles::Palette::active().error,
+                    };
+                    col.push(text(event.to_line()).size(styles::font_size::scaled(styles::font_size::SMALL)).style(color))
+                },
+            );
+
+            scrollable(lines)
+                .height(Length::Fill)
+                .into()
+        };
+
+        column![
+            header,
+            filters,
+            list,
+        ]
+        .spacing(styles::spacing::MEDIUM)
+        .padding(styles::spacing::MEDIUM.0)
+        .into()
+    }
+}
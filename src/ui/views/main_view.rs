@@ -5,54 +5,148 @@
 
 use iced::{
     widget::{
-        button, column, container, row, scrollable, text, text_input, Space,
+        button, checkbox, column, container, image, row, scrollable, text, text_input, Space,
         horizontal_rule, vertical_rule,
     },
     Alignment, Element, Length,
 };
 
+use std::time::Duration;
+
 use crate::ui::{
     components,
-    messages::{Message, NotificationMessage},
+    messages::Message,
+    notifications::NotificationQueue,
     styles,
+    widgets,
+    PendingSend,
     Theme,
 };
+use crate::network::connection_quality::{ConnectionQuality, ConnectionQualityTracker};
+use crate::network::device_cache::ScanHighlights;
+use crate::network::{DeviceAction, ScanScope};
+use crate::protocols::airplay_stats::AirPlayStats;
+use crate::protocols::awdl::AwdlAvailability;
+use crate::protocols::file_conflict::{ConflictChoice, ConflictPrompt};
+use crate::protocols::send_progress::SendProgress;
+use crate::protocols::transfer_protocol::ProtocolRegistry;
+use crate::protocols::visibility::TransferPrompt;
+use crate::utils::history::History;
+
+/// File ricevuti mostrati nel pannello dei dispositivi
+const RECEIVED_FILES_SHOWN: usize = 5;
 
 /// Struttura per la vista principale
 pub struct MainView<'a> {
     discovered_devices: &'a [crate::network::DiscoveredDevice],
+    recent_devices: &'a [crate::network::DiscoveredDevice],
+    scan_highlights: &'a ScanHighlights,
+    /// Qualità della connessione ai dispositivi
+    connection_quality: &'a ConnectionQualityTracker,
     selected_device: Option<&'a crate::network::DiscoveredDevice>,
+    /// Protocolli di trasferimento, per le azioni offerte al dispositivo
+    protocols: &'a ProtocolRegistry,
     is_scanning: bool,
+    scan_elapsed: Option<Duration>,
+    last_scan_elapsed: Option<Duration>,
     airplay_status: &'a crate::protocols::airplay::AirPlayStatus,
     airdrop_status: &'a crate::protocols::airdrop::AirDropStatus,
+    /// Tempo dall'inizio della connessione AirPlay, per l'indicatore
+    airplay_connecting: Option<Duration>,
     file_transfer_progress: Option<f32>,
-    notifications: &'a [NotificationMessage],
+    send_progress: Option<&'a SendProgress>,
+    file_transfer_size: Option<u64>,
+    transfer_remaining: Option<Duration>,
+    pending_send: Option<&'a PendingSend>,
+    airplay_effective_quality: Option<(u8, u32)>,
+    airplay_stats: AirPlayStats,
+    airplay_preview: Option<&'a image::Handle>,
+    limitations: &'a [String],
+    conflict: Option<&'a ConflictPrompt>,
+    transfer: Option<&'a TransferPrompt>,
+    history: &'a History,
+    awdl: AwdlAvailability,
+    notifications: &'a NotificationQueue,
     show_link_dialog: bool,
     link_url: &'a str,
+    /// Scelta "apri al termine" del link, `None` se l'azione è disattivata
+    open_link_on_complete: Option<bool>,
+    /// Sezione dei dettagli del dispositivo selezionato espansa
+    device_details_expanded: bool,
+    /// Servizi cercati dalle scansioni
+    scan_scope: ScanScope,
+    /// Ultimo messaggio di stato dell'applicazione
+    status_message: &'a str,
 }  
 /// Helper function to render the main view without constructing a temporary in the caller
 pub fn render<'a>(
     discovered_devices: &'a [crate::network::DiscoveredDevice],
+    recent_devices: &'a [crate::network::DiscoveredDevice],
+    scan_highlights: &'a ScanHighlights,
+    connection_quality: &'a ConnectionQualityTracker,
     selected_device: Option<&'a crate::network::DiscoveredDevice>,
+    protocols: &'a ProtocolRegistry,
     is_scanning: bool,
+    scan_elapsed: Option<Duration>,
+    last_scan_elapsed: Option<Duration>,
     airplay_status: &'a crate::protocols::airplay::AirPlayStatus,
     airdrop_status: &'a crate::protocols::airdrop::AirDropStatus,
+    airplay_connecting: Option<Duration>,
     file_transfer_progress: Option<f32>,
-    notifications: &'a [NotificationMessage],
+    send_progress: Option<&'a SendProgress>,
+    file_transfer_size: Option<u64>,
+    transfer_remaining: Option<Duration>,
+    pending_send: Option<&'a PendingSend>,
+    airplay_effective_quality: Option<(u8, u32)>,
+    airplay_stats: AirPlayStats,
+    airplay_preview: Option<&'a image::Handle>,
+    limitations: &'a [String],
+    conflict: Option<&'a ConflictPrompt>,
+    transfer: Option<&'a TransferPrompt>,
+    history: &'a History,
+    awdl: AwdlAvailability,
+    notifications: &'a NotificationQueue,
     show_link_dialog: bool,
     link_url: &'a str,
+    open_link_on_complete: Option<bool>,
+    device_details_expanded: bool,
+    scan_scope: ScanScope,
+    status_message: &'a str,
     theme: &Theme,
 ) -> Element<'a, Message> {
     MainView::new(
         discovered_devices,
+        recent_devices,
+        scan_highlights,
+        connection_quality,
         selected_device,
+        protocols,
         is_scanning,
+        scan_elapsed,
+        last_scan_elapsed,
         airplay_status,
         airdrop_status,
+        airplay_connecting,
         file_transfer_progress,
+        send_progress,
+        file_transfer_size,
+        transfer_remaining,
+        pending_send,
+        airplay_effective_quality,
+        airplay_stats,
+        airplay_preview,
+        limitations,
+        conflict,
+        transfer,
+        history,
+        awdl,
         notifications,
         show_link_dialog,
         link_url,
+        open_link_on_complete,
+        device_details_expanded,
+        scan_scope,
+        status_message,
     )
     .view(theme)
 }
@@ -61,25 +155,71 @@ impl<'a> MainView<'a> {
     /// Crea una nuova istanza della vista principale
     pub fn new(
         discovered_devices: &'a [crate::network::DiscoveredDevice],
+        recent_devices: &'a [crate::network::DiscoveredDevice],
+        scan_highlights: &'a ScanHighlights,
+        connection_quality: &'a ConnectionQualityTracker,
         selected_device: Option<&'a crate::network::DiscoveredDevice>,
+        protocols: &'a ProtocolRegistry,
         is_scanning: bool,
+        scan_elapsed: Option<Duration>,
+        last_scan_elapsed: Option<Duration>,
         airplay_status: &'a crate::protocols::airplay::AirPlayStatus,
         airdrop_status: &'a crate::protocols::airdrop::AirDropStatus,
+        airplay_connecting: Option<Duration>,
         file_transfer_progress: Option<f32>,
-        notifications: &'a [NotificationMessage],
+        send_progress: Option<&'a SendProgress>,
+        file_transfer_size: Option<u64>,
+        transfer_remaining: Option<Duration>,
+        pending_send: Option<&'a PendingSend>,
+        airplay_effective_quality: Option<(u8, u32)>,
+        airplay_stats: AirPlayStats,
+        airplay_preview: Option<&'a image::Handle>,
+        limitations: &'a [String],
+        conflict: Option<&'a ConflictPrompt>,
+        transfer: Option<&'a TransferPrompt>,
+        history: &'a History,
+        awdl: AwdlAvailability,
+        notifications: &'a NotificationQueue,
         show_link_dialog: bool,
         link_url: &'a str,
+        open_link_on_complete: Option<bool>,
+        device_details_expanded: bool,
+        scan_scope: ScanScope,
+        status_message: &'a str,
     ) -> Self {
         Self {
             discovered_devices,
+            recent_devices,
+            scan_highlights,
+            connection_quality,
             selected_device,
+            protocols,
             is_scanning,
+            scan_elapsed,
+            last_scan_elapsed,
             airplay_status,
             airdrop_status,
+            airplay_connecting,
             file_transfer_progress,
+            send_progress,
+            file_transfer_size,
+            transfer_remaining,
+            pending_send,
+            airplay_effective_quality,
+            airplay_stats,
+            airplay_preview,
+            limitations,
+            conflict,
+            transfer,
+            history,
+            awdl,
             notifications,
             show_link_dialog,
             link_url,
+            open_link_on_complete,
+            device_details_expanded,
+            scan_scope,
+            status_message,
         }
     }
 
@@ -97,22 +237,34 @@ impl<'a> MainView<'a> {
         .spacing(styles::spacing::MEDIUM)
         .height(Length::Fill);
 
-        let content = column![
+        let mut content = column![
             // Header con titolo e controlli
             self.header(theme),
             
             horizontal_rule(1),
-            
-            // Contenuto principale
-            main_content,
-            
-            horizontal_rule(1),
-            
-            // Barra di stato
-            self.status_bar(theme),
         ]
         .spacing(styles::spacing::SMALL);
 
+        // Avviso delle funzionalità limitate senza privilegi
+        if !self.limitations.is_empty() {
+            content = content.push(self.privilege_banner(theme));
+        }
+
+        // Scelta per un file ricevuto con un nome già esistente
+        if let Some(conflict) = self.conflict {
+            content = content.push(self.conflict_banner(conflict, theme));
+        }
+
+        // Richiesta di invio da confermare
+        if let Some(transfer) = self.transfer {
+            content = content.push(self.transfer_banner(transfer, theme));
+        }
+
+        let content = content
+            .push(main_content)
+            .push(horizontal_rule(1))
+            .push(self.status_bar(theme));
+
         // Overlay per notificazioni
         let with_notifications = if !self.notifications.is_empty() {
             container(
@@ -149,17 +301,24 @@ impl<'a> MainView<'a> {
         row![
             // Titolo
             text("AirWin")
-                .size(24)
-                .style(styles::colors::TEXT_PRIMARY),
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
+                .style(styles::Palette::active().text_primary),
             
             Space::with_width(Length::Fill),
             
             // Controlli header
             row![
+                self.awdl_badge(),
+
+                // Indicatore discreto durante la scansione
+                text(self.scan_elapsed.filter(|_| self.is_scanning).map(scan_indicator).unwrap_or(""))
+                    .size(styles::font_size::scaled(16))
+                    .style(styles::Palette::active().primary),
+
                 // Pulsante refresh/scansione
                 button(
                     text(if self.is_scanning { "⏹" } else { "🔄" })
-                        .size(16)
+                        .size(styles::font_size::scaled(16))
                 )
                 .on_press(if self.is_scanning {
                     Message::StopScanning
@@ -170,12 +329,43 @@ impl<'a> MainView<'a> {
                 // Toggle tema
                 button(
                     text(match theme {
-                        Theme::Light => "🌙",
+                        Theme::Light | Theme::HighContrast => "🌙",
                         Theme::Dark => "☀",
                     })
-                    .size(16)
+                    .size(styles::font_size::scaled(16))
                 )
-                .on_press(Message::ThemeChanged(match theme { Theme::Light => Theme::Dark, Theme::Dark => Theme::Light })),
+                .on_press(Message::ThemeChanged(match theme {
+                    Theme::Light | Theme::HighContrast => Theme::Dark,
+                    Theme::Dark => Theme::Light,
+                })),
+
+                // Modalità compatta
+                button(
+                    text("🗗")
+                        .size(styles::font_size::scaled(16))
+                )
+                .on_press(Message::ToggleMiniMode),
+
+                // Registro eventi
+                button(
+                    text("📜")
+                        .size(styles::font_size::scaled(16))
+                )
+                .on_press(Message::ShowEventLog),
+
+                // Impostazioni
+                button(
+                    text("⚙")
+                        .size(styles::font_size::scaled(16))
+                )
+                .on_press(Message::ShowSettings),
+
+                // Informazioni
+                button(
+                    text("ℹ")
+                        .size(styles::font_size::scaled(16))
+                )
+                .on_press(Message::ShowAbout),
             ]
             .spacing(styles::spacing::SMALL)
         ]
@@ -184,31 +374,71 @@ impl<'a> MainView<'a> {
         .into()
     }
 
+    /// Stato di AWDL, lo stesso annunciato nei record mDNS
+    fn awdl_badge(&self) -> Element<'a, Message> {
+        let (label, color) = match self.awdl {
+            AwdlAvailability::On => ("AWDL attivo", styles::Palette::active().success),
+            AwdlAvailability::Off => ("AWDL spento", styles::Palette::active().text_muted),
+            AwdlAvailability::Unavailable => ("AWDL non disponibile", styles::Palette::active().warning),
+        };
+        text(label).size(styles::font_size::scaled(styles::font_size::SMALL)).style(color).into()
+    }
+
     /// Pannello dei dispositivi
     fn device_panel(&self, _theme: &Theme) -> Element<'a, Message> {
         let header = row![
             text("Dispositivi Scoperti")
-                .size(18)
-                .style(styles::colors::TEXT_SECONDARY),
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_secondary),
             
             Space::with_width(Length::Fill),
             
             text(format!("({})", self.discovered_devices.len()))
-                .size(14)
-                .style(styles::colors::TEXT_MUTED),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_muted),
         ]
         .align_items(Alignment::Center);
 
-        let device_list: Element<'a, Message> = if self.discovered_devices.is_empty() {
+        // Servizi cercati: sceglierne uno avvia subito una nuova scansione
+        let scopes = ScanScope::ALL.iter().fold(
+            row![].spacing(styles::spacing::SMALL),
+            |scopes, &scope| {
+                let label = match scope {
+                    ScanScope::All => "Tutti",
+                    ScanScope::AirDrop => "AirDrop",
+                    ScanScope::AirPlay => "AirPlay",
+                };
+                scopes.push(
+                    button(text(label).size(styles::font_size::scaled(styles::font_size::SMALL)))
+                        .on_press(Message::ScanScopeChanged(scope))
+                        .style(if scope == self.scan_scope {
+                            iced::theme::Button::Primary
+                        } else {
+                            iced::theme::Button::Secondary
+                        }),
+                )
+            },
+        );
+        let header = column![header, scopes].spacing(styles::spacing::SMALL);
+
+        let device_list: Element<'a, Message> = if self.discovered_devices.is_empty() && self.recent_devices.is_empty() {
             if self.is_scanning {
+                let elapsed = self.scan_elapsed.unwrap_or_default();
                 container(
                     column![
-                        text("🔍")
-                            .size(48)
-                            .style(styles::colors::TEXT_MUTED),
+                        text(scan_indicator(elapsed))
+                            .size(styles::font_size::scaled(48))
+                            .style(styles::Palette::active().primary),
                         text("Scansione in corso...")
-                            .size(16)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(16))
+                            .style(styles::Palette::active().text_muted),
+                        text(format!("{}s", elapsed.as_secs()))
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .style(styles::Palette::active().text_muted),
+                        button(text("⏹ Interrompi").size(styles::font_size::scaled(16)))
+                            .on_press(Message::StopScanning)
+                            .padding([styles::spacing::SMALL.0, styles::spacing::LARGE.0])
+                            .style(iced::theme::Button::Secondary),
                     ]
                     .align_items(Alignment::Center)
                     .spacing(styles::spacing::MEDIUM)
@@ -221,14 +451,15 @@ impl<'a> MainView<'a> {
                 container(
                     column![
                         text("📱")
-                            .size(48)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(48))
+                            .style(styles::Palette::active().text_muted),
                         text("Nessun dispositivo trovato")
-                            .size(16)
-                            .style(styles::colors::TEXT_MUTED),
-                        text("Premi il pulsante refresh per cercare")
-                            .size(14)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(16))
+                            .style(styles::Palette::active().text_muted),
+                        button(text("🔄 Cerca di nuovo").size(styles::font_size::scaled(16)))
+                            .on_press(Message::StartScanning)
+                            .padding([styles::spacing::SMALL.0, styles::spacing::LARGE.0])
+                            .style(iced::theme::Button::Primary),
                     ]
                     .align_items(Alignment::Center)
                     .spacing(styles::spacing::SMALL)
@@ -239,26 +470,41 @@ impl<'a> MainView<'a> {
                 .into()
             }
         } else {
-            let devices: Element<'a, Message> = self.discovered_devices
+            let now = std::time::Instant::now();
+            let devices = self.discovered_devices
                 .iter()
                 .cloned()
                 .fold(
                     column![].spacing(styles::spacing::SMALL),
                     |col, device| {
-                        let is_selected = self.selected_device
-                            .as_ref()
-                            .map(|selected| selected.name == device.name)
-                            .unwrap_or(false);
-                        
-                        let desc = format!("{} • {}:{}", 
-                            match device.service_type { 
-                                crate::network::ServiceType::AirDrop => "AirDrop",
-                                crate::network::ServiceType::AirPlay => "AirPlay",
-                                _ => "Altro",
-                            },
-                            device.address,
-                            device.port
-                        );
+                        let is_selected = self.is_selected(&device);
+                        let desc = self.card_description(&device);
+                        // Comparso nell'ultima scansione: evidenziato per qualche secondo
+                        if self.scan_highlights.is_new(&device, now) {
+                            let label = column![
+                                row![
+                                    text(&device.name).size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                                    text("(nuovo)")
+                                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                        .style(styles::Palette::active().success),
+                                ]
+                                .spacing(styles::spacing::TINY)
+                                .align_items(Alignment::Center),
+                                text(desc)
+                                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                    .style(styles::Palette::active().text_muted),
+                            ];
+                            return col.push(
+                                button(label)
+                                    .on_press(Message::DeviceSelected(device.clone()))
+                                    .style(if is_selected {
+                                        iced::theme::Button::Primary
+                                    } else {
+                                        iced::theme::Button::Positive
+                                    })
+                                    .width(Length::Fill)
+                            );
+                        }
                         col.push(
                             components::selection_card(
                                 &device.name,
@@ -268,8 +514,49 @@ impl<'a> MainView<'a> {
                             )
                         )
                     }
-                )
-                .into();
+                );
+
+            // Dispositivi mancati dall'ultima scansione: in grigio ma ancora selezionabili
+            let devices: Element<'a, Message> = if self.recent_devices.is_empty() {
+                devices.into()
+            } else {
+                self.recent_devices
+                    .iter()
+                    .cloned()
+                    .fold(
+                        devices
+                            .push(Space::with_height(styles::spacing::SMALL))
+                            .push(
+                                text("Visti di recente")
+                                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                                    .style(styles::Palette::active().text_muted),
+                            ),
+                        |col, device| {
+                            // Appena sparito dalla scansione: sfuma verso il grigio
+                            let fade = self.scan_highlights.fade(&device, now);
+                            let label = column![
+                                text(&device.name)
+                                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                                    .style(fade_color(styles::Palette::active().text_primary, styles::Palette::active().text_muted, fade)),
+                                text(format!("{} • non trovato nell'ultima scansione", self.card_description(&device)))
+                                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                    .style(styles::Palette::active().text_muted),
+                            ];
+                            let style = if self.is_selected(&device) {
+                                iced::theme::Button::Primary
+                            } else {
+                                iced::theme::Button::Secondary
+                            };
+                            col.push(
+                                button(label)
+                                    .on_press(Message::DeviceSelected(device.clone()))
+                                    .style(style)
+                                    .width(Length::Fill)
+                            )
+                        }
+                    )
+                    .into()
+            };
 
             scrollable(devices)
                 .height(Length::Fill)
@@ -281,6 +568,7 @@ impl<'a> MainView<'a> {
                 header,
                 Space::with_height(styles::spacing::MEDIUM),
                 device_list,
+                self.received_files(),
             ]
         )
         .padding(styles::spacing::MEDIUM.0)
@@ -289,11 +577,102 @@ impl<'a> MainView<'a> {
         .into()
     }
 
+    /// File ricevuti più recenti, con i nuovi evidenziati
+    fn received_files(&self) -> Element<'a, Message> {
+        if self.history.received().next().is_none() {
+            return Space::with_height(0).into();
+        }
+
+        let unread = self.history.unread_count();
+        let mut header = row![
+            text("File ricevuti")
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_secondary),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .align_items(Alignment::Center);
+        if unread > 0 {
+            header = header
+                .push(
+                    text(format!("({} nuovi)", unread))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().primary),
+                )
+                .push(Space::with_width(Length::Fill))
+                .push(
+                    button(text("Segna come visti").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                        .on_press(Message::MarkAllReceivedRead)
+                        .style(iced::theme::Button::Secondary),
+                );
+        }
+
+        self.history
+            .received()
+            .take(RECEIVED_FILES_SHOWN)
+            .fold(
+                column![horizontal_rule(1), header].spacing(styles::spacing::SMALL),
+                |col, entry| {
+                    // Il filtro di `received` garantisce il percorso
+                    let Some(path) = entry.path.clone() else {
+                        return col;
+                    };
+                    let name = if entry.unread { format!("● {}", entry.name) } else { entry.name.clone() };
+                    col.push(
+                        row![
+                            column![
+                                text(name)
+                                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                                    .style(if entry.unread {
+                                        styles::Palette::active().text_primary
+                                    } else {
+                                        styles::Palette::active().text_secondary
+                                    }),
+                                text(format!(
+                                    "{} • {} • {}",
+                                    components::format_bytes(entry.size),
+                                    entry.peer,
+                                    entry.timestamp.with_timezone(&chrono::Local).format("%d/%m %H:%M"),
+                                ))
+                                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                    .style(styles::Palette::active().text_muted),
+                            ]
+                            .width(Length::Fill),
+
+                            button(text("Apri").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                                .on_press(Message::OpenReceivedFile(path.clone())),
+
+                            button(text("Mostra nella cartella").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                                .on_press(Message::RevealReceivedFile(path))
+                                .style(iced::theme::Button::Secondary),
+                        ]
+                        .spacing(styles::spacing::SMALL)
+                        .align_items(Alignment::Center),
+                    )
+                },
+            )
+            .into()
+    }
+
+    /// Servizio e indirizzo del dispositivo, con la qualità della connessione se nota
+    fn card_description(&self, device: &crate::network::DiscoveredDevice) -> String {
+        let description = device_description(device);
+        match self.connection_quality.quality(device.address) {
+            Some(quality) => format!("{} • {}", description, quality_label(quality)),
+            None => description,
+        }
+    }
+
+    fn is_selected(&self, device: &crate::network::DiscoveredDevice) -> bool {
+        self.selected_device
+            .map(|selected| selected.same_device(device))
+            .unwrap_or(false)
+    }
+
     /// Pannello delle azioni
     fn action_panel(&self, theme: &Theme) -> Element<'a, Message> {
         let header = text("Azioni")
-            .size(18)
-            .style(styles::colors::TEXT_SECONDARY);
+            .size(styles::font_size::scaled(styles::font_size::LARGE))
+            .style(styles::Palette::active().text_secondary);
 
         let content = if let Some(device) = self.selected_device {
             column![
@@ -302,8 +681,12 @@ impl<'a> MainView<'a> {
                 
                 Space::with_height(styles::spacing::LARGE),
                 
-                // Azioni AirDrop
-                self.airdrop_actions(theme),
+                // Azioni AirDrop, anche per i dispositivi Companion
+                match self.pending_send {
+                    Some(pending) => self.pending_send_panel(pending, theme),
+                    None if device.service_type.accepts_airdrop() => self.airdrop_actions(theme),
+                    None => Space::with_height(0).into(),
+                },
                 
                 Space::with_height(styles::spacing::MEDIUM),
                 
@@ -328,14 +711,14 @@ impl<'a> MainView<'a> {
                 container(
                     column![
                         text("👆")
-                            .size(48)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(48))
+                            .style(styles::Palette::active().text_muted),
                         text("Seleziona un dispositivo")
-                            .size(16)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(16))
+                            .style(styles::Palette::active().text_muted),
                         text("per iniziare")
-                            .size(14)
-                            .style(styles::colors::TEXT_MUTED),
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .style(styles::Palette::active().text_muted),
                     ]
                     .align_items(Alignment::Center)
                     .spacing(styles::spacing::SMALL)
@@ -363,75 +746,239 @@ impl<'a> MainView<'a> {
     fn selected_device_info(
         &self,
         device: &crate::network::DiscoveredDevice,
-        _theme: &Theme,
+        theme: &Theme,
     ) -> Element<'a, Message> {
-        column![
+        let mut info = column![
             text(&device.name)
-                .size(16)
-                .style(styles::colors::TEXT_PRIMARY),
+                .size(styles::font_size::scaled(16))
+                .style(styles::Palette::active().text_primary),
             
-            text(format!("{} • {}:{}", 
-                match device.service_type { 
-                    crate::network::ServiceType::AirDrop => "AirDrop",
-                    crate::network::ServiceType::AirPlay => "AirPlay",
-                    _ => "Altro",
-                },
-                device.address,
-                device.port
+            text(device_description(device))
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted),
+        ]
+        .spacing(styles::spacing::SMALL);
+
+        // Un dispositivo Companion è associato all'account, non un peer AirDrop diretto
+        if device.service_type == crate::network::ServiceType::Companion {
+            info = info.push(
+                text("🔗 Companion • dispositivo associato, invio tramite AirDrop")
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().primary),
+            );
+        }
+
+        info.push(self.device_details(device, theme)).into()
+    }
+
+    /// Record TXT e capacità del dispositivo, chiusi finché non li si apre
+    fn device_details(
+        &self,
+        device: &crate::network::DiscoveredDevice,
+        theme: &Theme,
+    ) -> Element<'a, Message> {
+        let capabilities = device.capabilities();
+        let known = |value: Option<bool>| match value {
+            Some(true) => "sì",
+            Some(false) => "no",
+            None => "non indicato",
+        };
+
+        let mut details = column![
+            text(format!("Servizio: {}", device.service_type)).size(styles::font_size::scaled(styles::font_size::SMALL)),
+            widgets::with_copy_button(
+                text(format!("Indirizzo: {}:{}", device.address, device.port)).size(styles::font_size::scaled(styles::font_size::SMALL)),
+                "Indirizzo",
+                std::net::SocketAddr::new(device.address, device.port).to_string(),
+            ),
+            text(format!(
+                "File: {} • Link: {} • Duplicazione schermo: {} • Audio AirPlay: {}",
+                known(capabilities.files),
+                known(capabilities.links),
+                known(capabilities.mirroring),
+                known(capabilities.audio),
             ))
-                .size(12)
-                .style(styles::colors::TEXT_MUTED),
+            .size(styles::font_size::scaled(styles::font_size::SMALL)),
+            Space::with_height(styles::spacing::SMALL),
+            text("Record TXT")
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_secondary),
         ]
-        .spacing(styles::spacing::SMALL)
-        .into()
+        .spacing(styles::spacing::SMALL);
+
+        let mut records: Vec<_> = device.txt_records.iter().collect();
+        records.sort();
+        if records.is_empty() {
+            details = details.push(
+                text("Nessun record")
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
+            );
+        }
+        for (key, value) in records {
+            details = details.push(
+                text(format!("{} = {}", key, value))
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_muted),
+            );
+        }
+
+        let details = column![
+            scrollable(details).height(Length::Shrink),
+            button(text("📋 Copia dettagli").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                .on_press(Message::CopyDeviceDetails)
+                .style(iced::theme::Button::Secondary),
+        ]
+        .spacing(styles::spacing::SMALL);
+
+        let iced_theme = match theme {
+            Theme::Dark => iced::Theme::Dark,
+            Theme::Light => iced::Theme::Light,
+        };
+        widgets::collapsible_panel(
+            "Dettagli",
+            self.device_details_expanded,
+            details.into(),
+            Message::ToggleDeviceDetails,
+            &iced_theme,
+        )
     }
 
     /// Azioni AirDrop
     fn airdrop_actions(&self, _theme: &Theme) -> Element<'a, Message> {
-        let status_text = match self.airdrop_status {
-            crate::protocols::airdrop::AirDropStatus::Idle => "Pronto",
-            crate::protocols::airdrop::AirDropStatus::Connecting => "Connessione...",
-            crate::protocols::airdrop::AirDropStatus::Connected => "Connesso",
-            crate::protocols::airdrop::AirDropStatus::Transferring(_) => "Trasferimento...",
-            crate::protocols::airdrop::AirDropStatus::Failed(_) => "Errore",
+        use crate::protocols::airdrop::ConnectionState;
+
+        let (status_text, error) = match (&self.airdrop_status.connection, &self.airdrop_status.transfer) {
+            (_, Some(_)) => ("Trasferimento...".to_string(), None),
+            (ConnectionState::Connecting, None) => ("Connessione...".to_string(), None),
+            (ConnectionState::Connected, None) => ("Connesso".to_string(), None),
+            (ConnectionState::Idle, None) => match &self.airdrop_status.error {
+                Some(error) => (format!("Errore: {}", error), Some(error.clone())),
+                None => ("Pronto".to_string(), None),
+            },
+        };
+        let status = text(status_text)
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
+            .style(styles::Palette::active().text_muted);
+        // I dettagli dell'errore si copiano per allegarli a una segnalazione
+        let status = match error {
+            Some(error) => widgets::with_copy_button(status, "Errore", error),
+            None => status.into(),
         };
 
         column![
             text("AirDrop")
-                .size(14)
-                .style(styles::colors::TEXT_SECONDARY),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_secondary),
             
-            text(status_text)
-                .size(12)
-                .style(styles::colors::TEXT_MUTED),
+            status,
             
             Space::with_height(styles::spacing::SMALL),
             
-            button(
-                text("📁 Invia File")
-                    .size(14)
-            )
-            .on_press_maybe(
-                if matches!(self.airdrop_status, crate::protocols::airdrop::AirDropStatus::Idle | crate::protocols::airdrop::AirDropStatus::Connected) {
-                    self.selected_device.map(|d| Message::SendFile(d.clone()))
-                } else {
-                    None
-                }
-            )
-            .width(Length::Fill),
+            self.action_button("📁 Invia File", DeviceAction::SendFile, |d| Message::SendFile(d.clone())),
             
-            button(
-                text("🔗 Invia Link")
-                    .size(14)
-            )
-            .on_press_maybe(
-                if matches!(self.airdrop_status, crate::protocols::airdrop::AirDropStatus::Idle | crate::protocols::airdrop::AirDropStatus::Connected) {
-                    Some(Message::ShowLinkDialog)
-                } else {
-                    None
-                }
-            )
-            .width(Length::Fill),
+            self.action_button("📂 Invia Cartella", DeviceAction::SendFile, |d| Message::SendFolder(d.clone())),
+            
+            self.action_button("🔗 Invia Link", DeviceAction::SendLink, |_| Message::ShowLinkDialog),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .into()
+    }
+
+    /// Pulsante di un'azione AirDrop, disattivato con un tooltip se il
+    /// dispositivo non la supporta
+    fn action_button(
+        &self,
+        label: &str,
+        action: DeviceAction,
+        message: impl Fn(&crate::network::DiscoveredDevice) -> Message,
+    ) -> Element<'a, Message> {
+        let Some(device) = self.selected_device else {
+            return Space::with_height(0).into();
+        };
+        let supported = self.protocols.allows(device, action);
+        let action_button = button(text(label).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+            .on_press_maybe((supported && self.airdrop_status.is_ready()).then(|| message(device)))
+            .width(Length::Fill);
+
+        if supported {
+            action_button.into()
+        } else if self.protocols.protocols().is_empty() {
+            widgets::info_tooltip(action_button, "AirDrop non è attivo su questo PC")
+        } else if device.address.is_unspecified() {
+            widgets::info_tooltip(action_button, "Visto solo via Bluetooth: ripeti la scansione per trovarne l'indirizzo")
+        } else {
+            widgets::info_tooltip(action_button, unsupported_reason(action))
+        }
+    }
+
+    /// Conferma dell'invio con il tipo MIME rilevato, modificabile
+    fn pending_send_panel(&self, pending: &'a PendingSend, _theme: &Theme) -> Element<'a, Message> {
+        let file_name = pending.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| pending.path.display().to_string());
+
+        // Miniatura per le immagini, icona del tipo per gli altri file
+        let preview: Element<'a, Message> = match &pending.thumbnail {
+            Some(thumbnail) => image(thumbnail.clone())
+                .width(crate::utils::file_preview::THUMBNAIL_SIZE as f32)
+                .into(),
+            None => text(crate::utils::file_preview::type_icon(&pending.mime_type))
+                .size(styles::font_size::scaled(48))
+                .into(),
+        };
+
+        let mut details = Vec::new();
+        if let Some((width, height)) = pending.dimensions {
+            details.push(format!("{}×{}", width, height));
+        }
+        if let Some(size) = pending.size {
+            details.push(components::format_bytes(size));
+        }
+        details.push(pending.mime_type.clone());
+
+        column![
+            text("Conferma invio")
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_secondary),
+
+            row![
+                preview,
+                column![
+                    text(file_name)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().text_primary),
+                    text(details.join(" • "))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
+                ]
+                .spacing(styles::spacing::SMALL),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(Alignment::Center),
+
+            text("Tipo di file:")
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted),
+
+            text_input("application/octet-stream", &pending.mime_type)
+                .on_input(Message::MimeOverrideChanged)
+                .width(Length::Fill),
+
+            row![
+                button(text("Annulla").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                    .on_press(Message::CancelSend)
+                    .style(iced::theme::Button::Secondary),
+
+                Space::with_width(Length::Fill),
+
+                button(text("📤 Invia").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                    .on_press_maybe(
+                        (!pending.mime_type.trim().is_empty()).then_some(Message::ConfirmSend)
+                    ),
+            ]
+            .align_items(Alignment::Center),
         ]
         .spacing(styles::spacing::SMALL)
         .into()
@@ -454,23 +1001,92 @@ impl<'a> MainView<'a> {
             },
         };
 
+        // Un ricevitore che non accetta la duplicazione si può solo disconnettere
+        let supported = self.selected_device
+            .map_or(true, |device| device.capabilities().allows(DeviceAction::ScreenMirroring));
+        let mirroring_button = button(
+            text(button_text)
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+        )
+        .on_press_maybe(button_action.filter(|action| supported || matches!(action, Message::StopScreenMirroring)))
+        .width(Length::Fill);
+        let audio_only = self.selected_device.is_some_and(|device| device.capabilities().is_audio_only());
+        let mirroring_button = if supported {
+            Element::from(mirroring_button)
+        } else if audio_only {
+            // Un altoparlante AirPlay accetterebbe solo lo streaming audio
+            widgets::info_tooltip(mirroring_button, "Il ricevitore riproduce solo audio, AirWin trasmette solo lo schermo")
+        } else {
+            widgets::info_tooltip(mirroring_button, unsupported_reason(DeviceAction::ScreenMirroring))
+        };
+
+        let status = text(match (self.airplay_status, self.airplay_effective_quality) {
+            (crate::protocols::airplay::AirPlayStatus::Connecting, _) => {
+                format!("{} {}", scan_indicator(self.airplay_connecting.unwrap_or_default()), status_text)
+            }
+            (crate::protocols::airplay::AirPlayStatus::Connected, Some((quality, fps))) => {
+                if self.airplay_stats.frames_dropped > 0 {
+                    format!(
+                        "{} • qualità {}% • {} fps • {} frame scartati",
+                        status_text, quality, fps, self.airplay_stats.frames_dropped
+                    )
+                } else {
+                    format!("{} • qualità {}% • {} fps", status_text, quality, fps)
+                }
+            }
+            (crate::protocols::airplay::AirPlayStatus::Failed(error), _) => {
+                format!("{}: {}", status_text, error)
+            }
+            _ => status_text.to_string(),
+        })
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
+            // Verde se connesso, rosso in caso di errore
+            .style(match self.airplay_status {
+                crate::protocols::airplay::AirPlayStatus::Connected => styles::Palette::active().success,
+                crate::protocols::airplay::AirPlayStatus::Failed(_) => styles::Palette::active().error,
+                _ => styles::Palette::active().text_muted,
+            });
+        let status = match self.airplay_status {
+            crate::protocols::airplay::AirPlayStatus::Failed(error) => {
+                widgets::with_copy_button(status, "Errore", error.clone())
+            }
+            _ => status.into(),
+        };
+
         column![
             text("AirPlay")
-                .size(14)
-                .style(styles::colors::TEXT_SECONDARY),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_secondary),
             
-            text(status_text)
-                .size(12)
-                .style(styles::colors::TEXT_MUTED),
+            status,
             
+            self.airplay_preview
+                .filter(|_| *self.airplay_status == crate::protocols::airplay::AirPlayStatus::Connected)
+                .map(|preview| {
+                    // Statistiche della sessione sotto l'anteprima
+                    let stats = self.airplay_stats;
+                    Element::from(
+                        column![
+                            image(preview.clone()).width(Length::Fill),
+                            text(format!(
+                                "{:.1} fps • {} inviati in {} frame • codifica {:.1} ms • {} scartati",
+                                stats.current_fps,
+                                components::format_bytes(stats.bytes_sent),
+                                stats.frames_sent,
+                                stats.avg_encode_ms,
+                                stats.frames_dropped
+                            ))
+                                .size(styles::font_size::scaled(11))
+                                .style(styles::Palette::active().text_muted),
+                        ]
+                        .spacing(styles::spacing::SMALL)
+                    )
+                })
+                .unwrap_or_else(|| Space::with_height(0).into()),
+
             Space::with_height(styles::spacing::SMALL),
             
-            button(
-                text(button_text)
-                    .size(14)
-            )
-            .on_press_maybe(button_action)
-            .width(Length::Fill),
+            mirroring_button,
         ]
         .spacing(styles::spacing::SMALL)
         .into()
@@ -478,16 +1094,45 @@ impl<'a> MainView<'a> {
 
     /// Progresso del trasferimento
     fn transfer_progress(&self, progress: f32, _theme: &Theme) -> Element<'a, Message> {
+        // Con più file, sotto la barra complessiva il file corrente e la sua barra
+        let current_file: Element<'a, Message> = match self.send_progress.filter(|send| send.is_multi_file()) {
+            Some(send) => column![
+                text(format!(
+                    "File {} di {}: {}",
+                    send.current_file_index + 1,
+                    send.file_count,
+                    send.current_file_name
+                ))
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_secondary),
+                components::primary_progress_bar(send.current_file_progress),
+            ]
+            .spacing(styles::spacing::TINY)
+            .into(),
+            None => Space::with_height(0).into(),
+        };
+
         column![
             text("Trasferimento in corso")
-                .size(14)
-                .style(styles::colors::TEXT_SECONDARY),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_secondary),
             
             iced::Element::<Message>::from(components::primary_progress_bar(progress)),
             
-            text(format!("{:.1}%", progress))
-                .size(12)
-                .style(styles::colors::TEXT_MUTED),
+            text({
+                let done = match self.file_transfer_size {
+                    Some(size) => format!("{:.1}% di {}", progress, components::format_bytes(size)),
+                    None => format!("{:.1}%", progress),
+                };
+                match self.transfer_remaining {
+                    Some(remaining) => format!("{} • {} rimanenti", done, components::format_elapsed(remaining)),
+                    None => done,
+                }
+            })
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted),
+
+            current_file,
         ]
         .spacing(styles::spacing::SMALL)
         .into()
@@ -495,13 +1140,30 @@ impl<'a> MainView<'a> {
 
     /// Barra di stato
     fn status_bar(&self, _theme: &Theme) -> Element<'a, Message> {
-        let left = if self.is_scanning { "Scansione in corso...".to_string() } else { format!("Dispositivi: {}", self.discovered_devices.len()) };
+        let left = if self.is_scanning {
+            "Scansione in corso...".to_string()
+        } else {
+            match self.last_scan_elapsed {
+                Some(elapsed) => format!(
+                    "Dispositivi: {} • Ultima scansione: {} fa",
+                    self.discovered_devices.len(),
+                    components::format_elapsed(elapsed)
+                ),
+                None => format!("Dispositivi: {}", self.discovered_devices.len()),
+            }
+        };
         let right = self.selected_device.map(|d| d.name.clone()).unwrap_or_else(|| "Nessun dispositivo".to_string());
         container(
             row![
-                text(left).style(styles::colors::TEXT_SECONDARY),
+                text(left).style(styles::Palette::active().text_secondary),
+                Space::with_width(styles::spacing::MEDIUM),
+                widgets::with_copy_button(
+                    text(self.status_message).style(styles::Palette::active().text_muted),
+                    "Stato",
+                    self.status_message.to_string(),
+                ),
                 Space::with_width(Length::Fill),
-                text(right).style(styles::colors::TEXT_MUTED),
+                text(right).style(styles::Palette::active().text_muted),
             ]
             .align_items(Alignment::Center)
         )
@@ -515,13 +1177,32 @@ impl<'a> MainView<'a> {
             .iter()
             .fold(
                 column![].spacing(styles::spacing::SMALL),
-                |col, notification| {
+                |col, (id, notification)| {
+                    let actions = notification.actions.iter().fold(
+                        row![].spacing(styles::spacing::SMALL),
+                        |actions, (label, message)| {
+                            actions.push(
+                                button(text(label).size(styles::font_size::scaled(styles::font_size::SMALL)))
+                                    .on_press(message.clone())
+                                    .style(iced::theme::Button::Secondary),
+                            )
+                        },
+                    );
                     col.push(
                         container(
                             column![
-                                text(&notification.title).style(styles::colors::TEXT_PRIMARY),
-                                text(&notification.content).style(styles::colors::TEXT_SECONDARY),
+                                row![
+                                    text(&notification.title).style(styles::Palette::active().text_primary),
+                                    Space::with_width(Length::Fill),
+                                    button(text("✕").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                                        .on_press(Message::HideNotification(id))
+                                        .style(iced::theme::Button::Text),
+                                ]
+                                .align_items(Alignment::Center),
+                                text(&notification.content).style(styles::Palette::active().text_secondary),
+                                actions,
                             ]
+                            .spacing(styles::spacing::TINY)
                         )
                         .padding(styles::spacing::SMALL.0)
                     )
@@ -534,25 +1215,134 @@ impl<'a> MainView<'a> {
             .into()
     }
 
+    /// Banner con le funzionalità non disponibili senza privilegi di amministratore
+    fn privilege_banner(&self, _theme: &Theme) -> Element<'a, Message> {
+        let limitations = self.limitations.iter().fold(
+            column![].spacing(styles::spacing::TINY),
+            |col, limitation| {
+                col.push(
+                    text(format!("• {}", limitation))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_secondary),
+                )
+            },
+        );
+
+        container(
+            row![
+                column![
+                    text("⚠ AirWin non è in esecuzione come amministratore")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().warning),
+                    limitations,
+                ]
+                .spacing(styles::spacing::TINY),
+
+                Space::with_width(Length::Fill),
+
+                button(text("Chiudi").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::DismissPrivilegeBanner)
+                    .style(iced::theme::Button::Secondary),
+            ]
+            .align_items(Alignment::Center),
+        )
+        .padding(styles::spacing::SMALL.0)
+        .width(Length::Fill)
+        .into()
+    }
+
+    /// Richiesta di scelta per un file ricevuto con un nome già esistente
+    fn conflict_banner(&self, conflict: &'a ConflictPrompt, _theme: &Theme) -> Element<'a, Message> {
+        container(
+            row![
+                column![
+                    text(format!("Il file {} esiste già", conflict.name))
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().warning),
+                    text(conflict.existing.display().to_string())
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
+                ]
+                .spacing(styles::spacing::TINY),
+
+                Space::with_width(Length::Fill),
+
+                button(text("Mantieni entrambi").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::ResolveConflict(ConflictChoice::KeepBoth)),
+
+                button(text("Sovrascrivi").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::ResolveConflict(ConflictChoice::Overwrite))
+                    .style(iced::theme::Button::Destructive),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(Alignment::Center),
+        )
+        .padding(styles::spacing::SMALL.0)
+        .width(Length::Fill)
+        .into()
+    }
+
+    /// Richiesta di conferma per un invio da un contatto
+    fn transfer_banner(&self, transfer: &'a TransferPrompt, _theme: &Theme) -> Element<'a, Message> {
+        let files = match transfer.files.as_slice() {
+            [name] => name.clone(),
+            files => format!("{} file", files.len()),
+        };
+        container(
+            row![
+                text(format!("{} vuole inviarti {}", transfer.sender, files))
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().warning),
+
+                Space::with_width(Length::Fill),
+
+                button(text("Accetta").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::RespondTransfer(true)),
+
+                // Solo un mittente che invia un id può essere riconosciuto la volta dopo
+                button(text("Accetta sempre").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press_maybe(transfer.device_id.is_some().then_some(Message::AcceptAndTrust)),
+
+                button(text("Rifiuta").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::RespondTransfer(false))
+                    .style(iced::theme::Button::Destructive),
+            ]
+            .spacing(styles::spacing::SMALL)
+            .align_items(Alignment::Center),
+        )
+        .padding(styles::spacing::SMALL.0)
+        .width(Length::Fill)
+        .into()
+    }
+
     /// Dialog per l'invio di link
     fn link_dialog(&self, _theme: &Theme) -> Element<'a, Message> {
+        let open_on_complete: Element<'a, Message> = match self.open_link_on_complete {
+            Some(open) => checkbox("Apri il link al termine", open)
+                .on_toggle(Message::OpenLinkOnCompleteToggled)
+                .into(),
+            None => Space::with_height(0).into(),
+        };
+
         let dialog_content = column![
             text("Invia Link")
-                .size(18)
-                .style(styles::colors::TEXT_SECONDARY),
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_secondary),
             
             Space::with_height(styles::spacing::MEDIUM),
             
             text_input("Inserisci URL...", self.link_url)
                 .on_input(Message::LinkInputChanged)
                 .width(Length::Fill),
+
+            open_on_complete,
             
             Space::with_height(styles::spacing::MEDIUM),
             
             row![
                 button(
                     text("Annulla")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press(Message::HideLinkDialog),
                 
@@ -560,7 +1350,7 @@ impl<'a> MainView<'a> {
                 
                 button(
                     text("Invia")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press_maybe(
                     if !self.link_url.trim().is_empty() {
@@ -586,4 +1376,50 @@ impl<'a> MainView<'a> {
         
         .into()
     }
-}
\ No newline at end of file
+}
+
+/// Colore tra `from` e `to`, a `progress` (0-1) del percorso
+fn fade_color(from: iced::Color, to: iced::Color, progress: f32) -> iced::Color {
+    let mix = |a: f32, b: f32| a + (b - a) * progress;
+    iced::Color::from_rgb(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+/// Servizio e indirizzo del dispositivo
+fn device_description(device: &crate::network::DiscoveredDevice) -> String {
+    format!("{} • {}:{}",
+        match &device.service_type {
+            crate::network::ServiceType::AirDrop => "AirDrop",
+            crate::network::ServiceType::AirPlay => "AirPlay",
+            crate::network::ServiceType::Companion => "Companion",
+            // Il tipo originale aiuta a capire quale servizio ha risposto
+            crate::network::ServiceType::Other(service) => service.as_str(),
+            _ => "Altro",
+        },
+        device.address,
+        device.port
+    )
+}
+
+/// Qualità della connessione mostrata sulla scheda del dispositivo
+fn quality_label(quality: ConnectionQuality) -> &'static str {
+    match quality {
+        ConnectionQuality::Good => "🟢 connessione buona",
+        ConnectionQuality::Fair => "🟡 connessione discreta",
+        ConnectionQuality::Poor => "🔴 connessione scarsa",
+    }
+}
+
+/// Spiegazione mostrata su un'azione che il dispositivo non supporta
+fn unsupported_reason(action: DeviceAction) -> &'static str {
+    match action {
+        DeviceAction::SendFile => "Il dispositivo non accetta file tramite AirDrop",
+        DeviceAction::SendLink => "Il dispositivo non accetta link",
+        DeviceAction::ScreenMirroring => "Il ricevitore non supporta la duplicazione dello schermo",
+    }
+}
+
+/// Fotogramma dell'indicatore di attività (scansione, connessione AirPlay) per il tempo trascorso
+fn scan_indicator(elapsed: Duration) -> &'static str {
+    const FRAMES: [&str; 4] = ["◐", "◓", "◑", "◒"];
+    FRAMES[(elapsed.as_millis() / 250) as usize % FRAMES.len()]
+}
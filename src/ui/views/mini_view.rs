@@ -0,0 +1,168 @@
+//! Vista compatta dell'applicazione AirWin
+//!
+//! Mostra solo la lista dei dispositivi e un'area di rilascio file,
+//! pensata per invii rapidi con la finestra sempre in primo piano.
+
+use iced::{
+    widget::{button, column, container, row, scrollable, text, Space},
+    Alignment, Element, Length,
+};
+
+use crate::ui::{
+    components,
+    messages::Message,
+    styles,
+    PendingSend,
+    Theme,
+};
+
+/// Dimensioni della finestra in modalità compatta
+pub const MINI_WINDOW_SIZE: iced::Size = iced::Size::new(360.0, 520.0);
+
+/// Struttura per la vista compatta
+pub struct MiniView<'a> {
+    discovered_devices: &'a [crate::network::DiscoveredDevice],
+    selected_device: Option<&'a crate::network::DiscoveredDevice>,
+    is_scanning: bool,
+    file_transfer_progress: Option<f32>,
+    pending_send: Option<&'a PendingSend>,
+}
+
+impl<'a> MiniView<'a> {
+    /// Crea una nuova istanza della vista compatta
+    pub fn new(
+        discovered_devices: &'a [crate::network::DiscoveredDevice],
+        selected_device: Option<&'a crate::network::DiscoveredDevice>,
+        is_scanning: bool,
+        file_transfer_progress: Option<f32>,
+        pending_send: Option<&'a PendingSend>,
+    ) -> Self {
+        Self {
+            discovered_devices,
+            selected_device,
+            is_scanning,
+            file_transfer_progress,
+            pending_send,
+        }
+    }
+
+    /// Renderizza la vista compatta
+    pub fn view(&self, _theme: &Theme) -> Element<'a, Message> {
+        let header = row![
+            text("AirWin")
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_primary),
+
+            Space::with_width(Length::Fill),
+
+            button(text(if self.is_scanning { "⏹" } else { "🔄" }).size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(if self.is_scanning {
+                    Message::StopScanning
+                } else {
+                    Message::StartScanning
+                }),
+
+            // Ritorna alla vista completa
+            button(text("⤢").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                .on_press(Message::ToggleMiniMode),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .align_items(Alignment::Center);
+
+        let devices: Element<'a, Message> = if self.discovered_devices.is_empty() {
+            container(
+                text(if self.is_scanning { "Scansione in corso..." } else { "Nessun dispositivo trovato" })
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .style(styles::Palette::active().text_muted),
+            )
+            .center_x()
+            .center_y()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+        } else {
+            let list = self.discovered_devices
+                .iter()
+                .fold(
+                    column![].spacing(styles::spacing::SMALL),
+                    |col, device| {
+                        let is_selected = self.selected_device
+                            .map(|selected| selected.same_device(device))
+                            .unwrap_or(false);
+
+                        col.push(components::selection_card(
+                            &device.name,
+                            &device.address.to_string(),
+                            is_selected,
+                            Message::DeviceSelected(device.clone()),
+                        ))
+                    },
+                );
+
+            scrollable(list)
+                .height(Length::Fill)
+                .into()
+        };
+
+        column![
+            header,
+            devices,
+            self.drop_target(),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .padding(styles::spacing::SMALL.0)
+        .into()
+    }
+
+    /// Area di rilascio dei file
+    fn drop_target(&self) -> Element<'a, Message> {
+        let content: Element<'a, Message> = if let Some(progress) = self.file_transfer_progress {
+            column![
+                text("Trasferimento in corso")
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_secondary),
+                iced::Element::<Message>::from(components::primary_progress_bar(progress)),
+            ]
+            .spacing(styles::spacing::TINY)
+            .into()
+        } else if let Some(pending) = self.pending_send {
+            let file_name = pending.path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            column![
+                text(format!("{} ({})", file_name, pending.mime_type))
+                    .size(styles::font_size::scaled(styles::font_size::SMALL))
+                    .style(styles::Palette::active().text_secondary),
+                row![
+                    button(text("Annulla").size(styles::font_size::scaled(styles::font_size::SMALL))).on_press(Message::CancelSend),
+                    button(text("Invia").size(styles::font_size::scaled(styles::font_size::SMALL))).on_press(Message::ConfirmSend),
+                ]
+                .spacing(styles::spacing::SMALL),
+            ]
+            .spacing(styles::spacing::TINY)
+            .align_items(Alignment::Center)
+            .into()
+        } else {
+            let hint = match self.selected_device {
+                Some(device) => format!("Trascina qui un file per inviarlo a {}", device.name),
+                None => "Seleziona un dispositivo, poi trascina qui un file".to_string(),
+            };
+
+            text(hint)
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted)
+                .into()
+        };
+
+        components::secondary_container(
+            container(content)
+                .center_x()
+                .width(Length::Fill)
+                .padding(styles::spacing::MEDIUM.0)
+                .into(),
+        )
+        .into()
+    }
+}
@@ -3,6 +3,10 @@
 //! Questo modulo contiene le diverse viste dell'applicazione AirWin,
 //! organizzate in moduli separati per una migliore manutenibilità.
 
+pub mod diagnostics_view;
+pub mod event_log_view;
 pub mod main_view;
+pub mod mini_view;
+pub mod onboarding_view;
 pub mod settings_view;
 pub mod about_view;
\ No newline at end of file
@@ -0,0 +1,203 @@
+//! Configurazione iniziale
+//!
+//! Mostrata solo al primo avvio: verifica i requisiti di rete con gli stessi
+//! controlli di "Verifica rete", spiega cosa fa AirWin sulla rete e permette
+//! di scegliere il nome del dispositivo e la cartella dei file ricevuti.
+
+use iced::{
+    widget::{button, column, row, scrollable, text, text_input, Space},
+    Alignment, Element, Length,
+};
+
+use crate::ui::{
+    messages::Message,
+    styles,
+    Theme,
+};
+use crate::utils::self_test::{CheckStatus, SelfTestReport};
+
+/// Struttura per la vista della configurazione iniziale
+pub struct OnboardingView<'a> {
+    /// Esito dei controlli, `None` finché non sono terminati
+    report: Option<&'a SelfTestReport>,
+    test_running: bool,
+    elevated: bool,
+    /// Funzionalità ridotte senza privilegi di amministratore
+    limitations: &'a [String],
+    friendly_name: &'a str,
+    received_dir: std::path::PathBuf,
+}
+
+impl<'a> OnboardingView<'a> {
+    /// Crea una nuova istanza della vista della configurazione iniziale
+    pub fn new(
+        report: Option<&'a SelfTestReport>,
+        test_running: bool,
+        elevated: bool,
+        limitations: &'a [String],
+        friendly_name: &'a str,
+        received_dir: std::path::PathBuf,
+    ) -> Self {
+        Self {
+            report,
+            test_running,
+            elevated,
+            limitations,
+            friendly_name,
+            received_dir,
+        }
+    }
+
+    /// Renderizza la vista della configurazione iniziale
+    pub fn view(&self, _theme: &Theme) -> Element<'a, Message> {
+        let header = column![
+            text("Benvenuto in AirWin")
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
+                .style(styles::Palette::active().text_primary),
+            text("Qualche controllo e due scelte prima di iniziare. Tutto si può cambiare in seguito dalle impostazioni.")
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_muted),
+        ]
+        .spacing(styles::spacing::SMALL);
+
+        let sections = column![
+            self.prerequisites(),
+            self.permissions(),
+            self.preferences(),
+        ]
+        .spacing(styles::spacing::LARGE);
+
+        let footer = row![
+            Space::with_width(Length::Fill),
+            button(text("Inizia").size(styles::font_size::scaled(16)))
+                .on_press(Message::FinishOnboarding)
+                .padding([styles::spacing::SMALL.0, styles::spacing::LARGE.0])
+                .style(iced::theme::Button::Primary),
+        ];
+
+        column![
+            header,
+            scrollable(sections).height(Length::Fill),
+            footer,
+        ]
+        .spacing(styles::spacing::MEDIUM)
+        .padding(styles::spacing::LARGE.0)
+        .into()
+    }
+
+    /// Porte, multicast, mDNS, Bluetooth e AWDL
+    fn prerequisites(&self) -> Element<'a, Message> {
+        let title = row![
+            text("Requisiti")
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_secondary),
+            Space::with_width(Length::Fill),
+            button(text(if self.test_running { "⏳ Verifica..." } else { "🔄 Ripeti verifica" }).size(styles::font_size::scaled(styles::font_size::SMALL)))
+                .on_press_maybe((!self.test_running).then_some(Message::VerifyNetwork))
+                .style(iced::theme::Button::Secondary),
+        ]
+        .align_items(Alignment::Center);
+
+        let mut section = column![title].spacing(styles::spacing::SMALL);
+        match self.report {
+            Some(report) => {
+                for check in &report.checks {
+                    let color = match check.status {
+                        CheckStatus::Pass => styles::Palette::active().success,
+                        CheckStatus::Fail => styles::Palette::active().error,
+                        CheckStatus::Skip => styles::Palette::active().text_muted,
+                    };
+                    section = section.push(
+                        text(format!("{} {}: {}", check.status.icon(), check.name, check.detail))
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .style(color),
+                    );
+                    if let Some(hint) = &check.hint {
+                        section = section.push(
+                            text(format!("    {}", hint))
+                                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                .style(styles::Palette::active().text_muted),
+                        );
+                    }
+                }
+            }
+            None => {
+                section = section.push(
+                    text("Verifica della rete in corso...")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        .style(styles::Palette::active().text_muted),
+                );
+            }
+        }
+        section.into()
+    }
+
+    /// Cosa fa AirWin sulla rete e cosa richiede
+    fn permissions(&self) -> Element<'a, Message> {
+        let mut section = column![
+            text("Rete e permessi")
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_secondary),
+            text("• La scoperta dei dispositivi è automatica: AirWin cerca i dispositivi Apple vicini a ogni avvio e si annuncia sulla rete locale.")
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+            text("• Windows Firewall può chiedere di consentire le connessioni in entrata: senza, gli altri dispositivi non vedono questo PC e non possono inviare file.")
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+        ]
+        .spacing(styles::spacing::SMALL);
+
+        if self.elevated {
+            section = section.push(
+                text("• AirWin è in esecuzione come amministratore: tutte le funzionalità sono disponibili.")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+            );
+        } else {
+            section = section.push(
+                text("• AirWin non è in esecuzione come amministratore. Funziona comunque, con queste limitazioni:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+            );
+            for limitation in self.limitations {
+                section = section.push(
+                    text(format!("    {}", limitation))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().warning),
+                );
+            }
+        }
+        section.into()
+    }
+
+    /// Nome del dispositivo e cartella dei file ricevuti
+    fn preferences(&self) -> Element<'a, Message> {
+        let hostname = crate::protocols::apple_records::hostname().unwrap_or_default();
+        column![
+            text("Preferenze")
+                .size(styles::font_size::scaled(styles::font_size::LARGE))
+                .style(styles::Palette::active().text_secondary),
+            row![
+                text("Nome del dispositivo:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+                text_input(&hostname, self.friendly_name)
+                    .on_input(Message::FriendlyNameChanged)
+                    .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+            row![
+                text("File ricevuti in:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+                text(self.received_dir.display().to_string())
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(2)),
+                button(text("📁 Cambia").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::SelectCacheDir)
+                    .style(iced::theme::Button::Secondary),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+        ]
+        .spacing(styles::spacing::SMALL)
+        .into()
+    }
+}
@@ -11,12 +11,34 @@ use iced::{
     Alignment, Element, Length,
 };
 
+use crate::network::ip_mode::IpMode;
+use crate::protocols::adaptive_quality::QualityTier;
+use crate::protocols::compression::CompressionMode;
+use crate::protocols::file_conflict::FileConflictPolicy;
+use crate::protocols::screen_capture::CaptureRegion;
+use crate::protocols::transfer_method::TransferMethod;
+use crate::protocols::visibility::{TrustedDevice, Visibility};
+use crate::utils::open_file::CompletionAction;
+use crate::utils::self_test::{CheckStatus, SelfTestReport};
 use crate::ui::{
     messages::Message,
     styles,
     Theme,
 };
 
+/// Area scorrevole delle impostazioni
+pub fn scrollable_id() -> scrollable::Id {
+    scrollable::Id::new("settings")
+}
+
+/// Campo della porta AirPlay, raggiunto dalle notifiche di porta occupata
+pub fn custom_port_input_id() -> text_input::Id {
+    text_input::Id::new("settings-airplay-port")
+}
+
+/// Posizione approssimativa delle impostazioni di rete nell'area scorrevole
+pub const NETWORK_SECTION_OFFSET: scrollable::RelativeOffset = scrollable::RelativeOffset { x: 0.0, y: 0.7 };
+
 // Scelte statiche per i controlli `pick_list` per evitare riferimenti a temporanei
 const AIRDROP_VISIBILITIES: [AirDropVisibility; 3] = [
     AirDropVisibility::Everyone,
@@ -24,6 +46,36 @@ const AIRDROP_VISIBILITIES: [AirDropVisibility; 3] = [
     AirDropVisibility::ReceivingOff,
 ];
  
+const COMPRESSION_CHOICES: [CompressionChoice; 3] = [
+    CompressionChoice(CompressionMode::Off),
+    CompressionChoice(CompressionMode::Auto),
+    CompressionChoice(CompressionMode::Zstd),
+];
+
+const SEND_METHODS: [SendMethod; 3] = [
+    SendMethod::Automatic,
+    SendMethod::Direct,
+    SendMethod::SharedFolder,
+];
+
+const COMPLETION_CHOICES: [CompletionChoice; 3] = [
+    CompletionChoice::Nothing,
+    CompletionChoice::Open,
+    CompletionChoice::Reveal,
+];
+
+const IP_MODES: [IpModeChoice; 3] = [
+    IpModeChoice::Auto,
+    IpModeChoice::Ipv4Only,
+    IpModeChoice::Ipv6Only,
+];
+
+const CONFLICT_POLICIES: [ConflictPolicy; 3] = [
+    ConflictPolicy::KeepBoth,
+    ConflictPolicy::Overwrite,
+    ConflictPolicy::Ask,
+];
+ 
 const AIRPLAY_QUALITIES: [AirPlayQuality; 4] = [
     AirPlayQuality::Auto,
     AirPlayQuality::Low,
@@ -31,6 +83,28 @@ const AIRPLAY_QUALITIES: [AirPlayQuality; 4] = [
     AirPlayQuality::High,
 ]; 
  
+const IDLE_TIMEOUTS: [IdleTimeout; 5] = [
+    IdleTimeout(Some(5)),
+    IdleTimeout(Some(15)),
+    IdleTimeout(Some(30)),
+    IdleTimeout(Some(60)),
+    IdleTimeout(None),
+];
+
+const THEMES: [Theme; 3] = [
+    Theme::Dark,
+    Theme::Light,
+    Theme::HighContrast,
+];
+
+const FONT_SCALES: [FontScale; 5] = [
+    FontScale(100),
+    FontScale(125),
+    FontScale(150),
+    FontScale(175),
+    FontScale(200),
+];
+
 const LOG_LEVELS: [LogLevel; 5] = [
     LogLevel::Error,
     LogLevel::Warn,
@@ -46,20 +120,48 @@ const EMPTY_INTERFACES: [&str; 0] = [];
 #[derive(Debug, Clone)]
 pub struct SettingsView {
     // Impostazioni generali
+    // Nome mostrato agli altri dispositivi, vuoto per usare il nome del computer
+    friendly_name_text: String,
+    /// Suffisso aggiunto al nome, vuoto per nessuno
+    name_suffix_text: String,
+    avoid_name_collisions: bool,
+    /// Nome che gli altri dispositivi vedono, noto dopo la registrazione
+    advertised_name: Option<String>,
     auto_discovery: bool,
     discovery_interval: u32,
     show_notifications: bool,
+    /// Notifiche di sistema invece che nella finestra
+    system_notifications: bool,
+    play_sound_on_complete: bool,
     minimize_to_tray: bool,
+
+    // Aspetto
+    theme: Theme,
+    font_scale: FontScale,
     
     // Impostazioni AirDrop
     airdrop_enabled: bool,
     airdrop_visibility: AirDropVisibility,
     auto_accept_from_contacts: bool,
+    trusted_devices: Vec<TrustedDevice>,
+    conflict_policy: ConflictPolicy,
+    completion_choice: CompletionChoice,
+    send_method: SendMethod,
+    /// Tiene aperta la connessione dopo un invio per quello successivo
+    stay_connected: bool,
+    compression: CompressionChoice,
+    shared_folder: Option<std::path::PathBuf>,
     
     // Impostazioni AirPlay
     airplay_enabled: bool,
     airplay_quality: AirPlayQuality,
     airplay_audio_only: bool,
+    airplay_idle_timeout: IdleTimeout,
+    airplay_show_cursor: bool,
+    // Area trasmessa e testo dei campi x, y, larghezza e altezza
+    capture_region: Option<CaptureRegion>,
+    capture_region_text: [String; 4],
+    screen_size: Option<(u32, u32)>,
     
     // Impostazioni di rete
     network_interface: Option<String>,
@@ -67,11 +169,27 @@ pub struct SettingsView {
     custom_port: Option<u16>,
     // Versione testuale persistente della porta personalizzata per `text_input`
     custom_port_text: String,
+    ip_mode: IpModeChoice,
     
     // Impostazioni avanzate
     debug_mode: bool,
     log_level: LogLevel,
     max_concurrent_transfers: u32,
+    max_transfers_per_peer: u32,
+
+    // Cache
+    cache_dir: std::path::PathBuf,
+    cache_size: Option<u64>,
+
+    // Verifica della rete: `None` finché non è stata eseguita
+    network_report: Option<SelfTestReport>,
+    network_test_running: bool,
+
+    // Diagnostica copiata con gli indirizzi MAC completi
+    full_macs: bool,
+
+    // Conferma del ripristino: `Some(true)` se va ripristinata anche la finestra
+    reset_prompt: Option<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -81,6 +199,26 @@ pub enum AirDropVisibility {
     ReceivingOff,
 }
 
+impl From<Visibility> for AirDropVisibility {
+    fn from(visibility: Visibility) -> Self {
+        match visibility {
+            Visibility::Everyone => AirDropVisibility::Everyone,
+            Visibility::ContactsOnly => AirDropVisibility::ContactsOnly,
+            Visibility::ReceivingOff => AirDropVisibility::ReceivingOff,
+        }
+    }
+}
+
+impl From<AirDropVisibility> for Visibility {
+    fn from(visibility: AirDropVisibility) -> Self {
+        match visibility {
+            AirDropVisibility::Everyone => Visibility::Everyone,
+            AirDropVisibility::ContactsOnly => Visibility::ContactsOnly,
+            AirDropVisibility::ReceivingOff => Visibility::ReceivingOff,
+        }
+    }
+}
+
 impl std::fmt::Display for AirDropVisibility {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -91,6 +229,220 @@ impl std::fmt::Display for AirDropVisibility {
     }
 }
 
+/// Azione al termine di un trasferimento
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompletionChoice {
+    Nothing,
+    Open,
+    Reveal,
+}
+
+impl From<CompletionAction> for CompletionChoice {
+    fn from(action: CompletionAction) -> Self {
+        match action {
+            CompletionAction::Nothing => CompletionChoice::Nothing,
+            CompletionAction::Open => CompletionChoice::Open,
+            CompletionAction::Reveal => CompletionChoice::Reveal,
+        }
+    }
+}
+
+impl From<CompletionChoice> for CompletionAction {
+    fn from(choice: CompletionChoice) -> Self {
+        match choice {
+            CompletionChoice::Nothing => CompletionAction::Nothing,
+            CompletionChoice::Open => CompletionAction::Open,
+            CompletionChoice::Reveal => CompletionAction::Reveal,
+        }
+    }
+}
+
+impl std::fmt::Display for CompletionChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompletionChoice::Nothing => write!(f, "Nessuna azione"),
+            CompletionChoice::Open => write!(f, "Apri"),
+            CompletionChoice::Reveal => write!(f, "Mostra nella cartella"),
+        }
+    }
+}
+
+/// Versioni di IP usate dai servizi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpModeChoice {
+    Auto,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+impl From<IpMode> for IpModeChoice {
+    fn from(mode: IpMode) -> Self {
+        match mode {
+            IpMode::Auto => IpModeChoice::Auto,
+            IpMode::Ipv4Only => IpModeChoice::Ipv4Only,
+            IpMode::Ipv6Only => IpModeChoice::Ipv6Only,
+        }
+    }
+}
+
+impl From<IpModeChoice> for IpMode {
+    fn from(choice: IpModeChoice) -> Self {
+        match choice {
+            IpModeChoice::Auto => IpMode::Auto,
+            IpModeChoice::Ipv4Only => IpMode::Ipv4Only,
+            IpModeChoice::Ipv6Only => IpMode::Ipv6Only,
+        }
+    }
+}
+
+impl std::fmt::Display for IpModeChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpModeChoice::Auto => write!(f, "Automatico"),
+            IpModeChoice::Ipv4Only => write!(f, "Solo IPv4"),
+            IpModeChoice::Ipv6Only => write!(f, "Solo IPv6"),
+        }
+    }
+}
+
+/// Come vengono inviati i file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendMethod {
+    /// Diretto, con la cartella condivisa dopo ripetuti errori
+    Automatic,
+    Direct,
+    SharedFolder,
+}
+
+impl From<Option<TransferMethod>> for SendMethod {
+    fn from(method: Option<TransferMethod>) -> Self {
+        match method {
+            None => SendMethod::Automatic,
+            Some(TransferMethod::Direct) => SendMethod::Direct,
+            Some(TransferMethod::SharedFolder) => SendMethod::SharedFolder,
+        }
+    }
+}
+
+impl From<SendMethod> for Option<TransferMethod> {
+    fn from(method: SendMethod) -> Self {
+        match method {
+            SendMethod::Automatic => None,
+            SendMethod::Direct => Some(TransferMethod::Direct),
+            SendMethod::SharedFolder => Some(TransferMethod::SharedFolder),
+        }
+    }
+}
+
+impl std::fmt::Display for SendMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendMethod::Automatic => write!(f, "Automatico"),
+            SendMethod::Direct => write!(f, "Solo diretto"),
+            SendMethod::SharedFolder => write!(f, "Cartella condivisa"),
+        }
+    }
+}
+
+/// Compressione dei file scambiati con altre istanze di AirWin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionChoice(pub CompressionMode);
+
+impl std::fmt::Display for CompressionChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            CompressionMode::Off => write!(f, "Disattivata"),
+            CompressionMode::Auto => write!(f, "Automatica"),
+            CompressionMode::Zstd => write!(f, "Solo zstd"),
+        }
+    }
+}
+
+/// Comportamento quando un file ricevuto ha lo stesso nome di uno esistente
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    KeepBoth,
+    Overwrite,
+    Ask,
+}
+
+impl From<FileConflictPolicy> for ConflictPolicy {
+    fn from(policy: FileConflictPolicy) -> Self {
+        match policy {
+            FileConflictPolicy::KeepBoth => ConflictPolicy::KeepBoth,
+            FileConflictPolicy::Overwrite => ConflictPolicy::Overwrite,
+            FileConflictPolicy::Ask => ConflictPolicy::Ask,
+        }
+    }
+}
+
+impl From<ConflictPolicy> for FileConflictPolicy {
+    fn from(policy: ConflictPolicy) -> Self {
+        match policy {
+            ConflictPolicy::KeepBoth => FileConflictPolicy::KeepBoth,
+            ConflictPolicy::Overwrite => FileConflictPolicy::Overwrite,
+            ConflictPolicy::Ask => FileConflictPolicy::Ask,
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictPolicy::KeepBoth => write!(f, "Mantieni entrambi"),
+            ConflictPolicy::Overwrite => write!(f, "Sovrascrivi"),
+            ConflictPolicy::Ask => write!(f, "Chiedi"),
+        }
+    }
+}
+
+/// Minuti senza attività prima di fermare AirPlay, `None` per mai
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleTimeout(pub Option<u32>);
+
+impl IdleTimeout {
+    /// Scelte predefinite, più il valore corrente se impostato a mano
+    fn choices(self) -> Vec<IdleTimeout> {
+        let mut choices = IDLE_TIMEOUTS.to_vec();
+        if !choices.contains(&self) {
+            choices.insert(choices.len() - 1, self);
+        }
+        choices
+    }
+}
+
+/// Scala del testo, in percentuale della dimensione di base
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FontScale(pub u32);
+
+impl FontScale {
+    /// Scelte predefinite, più il valore corrente se impostato a mano
+    fn choices(self) -> Vec<FontScale> {
+        let mut choices = FONT_SCALES.to_vec();
+        if !choices.contains(&self) {
+            choices.push(self);
+            choices.sort_by_key(|scale| scale.0);
+        }
+        choices
+    }
+}
+
+impl std::fmt::Display for FontScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl std::fmt::Display for IdleTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(minutes) if minutes % 60 == 0 => write!(f, "Dopo {} h", minutes / 60),
+            Some(minutes) => write!(f, "Dopo {} min", minutes),
+            None => write!(f, "Mai"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AirPlayQuality {
     Low,
@@ -99,6 +451,28 @@ pub enum AirPlayQuality {
     Auto,
 }
 
+impl From<QualityTier> for AirPlayQuality {
+    fn from(tier: QualityTier) -> Self {
+        match tier {
+            QualityTier::Low => AirPlayQuality::Low,
+            QualityTier::Medium => AirPlayQuality::Medium,
+            QualityTier::High => AirPlayQuality::High,
+            QualityTier::Auto => AirPlayQuality::Auto,
+        }
+    }
+}
+
+impl From<AirPlayQuality> for QualityTier {
+    fn from(quality: AirPlayQuality) -> Self {
+        match quality {
+            AirPlayQuality::Low => QualityTier::Low,
+            AirPlayQuality::Medium => QualityTier::Medium,
+            AirPlayQuality::High => QualityTier::High,
+            AirPlayQuality::Auto => QualityTier::Auto,
+        }
+    }
+}
+
 impl std::fmt::Display for AirPlayQuality {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -137,10 +511,12 @@ impl SettingsView {
         auto_discovery: bool,
         discovery_interval: u32,
         show_notifications: bool,
+        play_sound_on_complete: bool,
         minimize_to_tray: bool,
         airdrop_enabled: bool,
         airdrop_visibility: AirDropVisibility,
         auto_accept_from_contacts: bool,
+        conflict_policy: ConflictPolicy,
         airplay_enabled: bool,
         airplay_quality: AirPlayQuality,
         airplay_audio_only: bool,
@@ -152,30 +528,356 @@ impl SettingsView {
         max_concurrent_transfers: u32,
     ) -> Self {
         Self {
+            friendly_name_text: String::new(),
+            name_suffix_text: String::new(),
+            avoid_name_collisions: true,
+            advertised_name: None,
+            completion_choice: CompletionChoice::Nothing,
+            send_method: SendMethod::Automatic,
+            stay_connected: false,
+            compression: CompressionChoice(CompressionMode::Off),
+            shared_folder: None,
+            theme: Theme::default(),
+            font_scale: FontScale(styles::font_size::DEFAULT_SCALE_PERCENT),
+            capture_region: None,
+            capture_region_text: Default::default(),
+            screen_size: None,
             auto_discovery,
             discovery_interval,
             show_notifications,
+            system_notifications: false,
+            play_sound_on_complete,
             minimize_to_tray,
             airdrop_enabled,
             airdrop_visibility,
             auto_accept_from_contacts,
+            trusted_devices: Vec::new(),
+            conflict_policy,
             airplay_enabled,
             airplay_quality,
             airplay_audio_only,
+            airplay_idle_timeout: IdleTimeout(Some(30)),
+            airplay_show_cursor: true,
             network_interface,
             available_interfaces,
             custom_port,
             custom_port_text: custom_port.map(|p| p.to_string()).unwrap_or_default(),
+            ip_mode: IpModeChoice::Auto,
             debug_mode,
             log_level,
             max_concurrent_transfers,
+            max_transfers_per_peer: 1,
+            cache_dir: crate::utils::cache::default_dir(),
+            cache_size: None,
+            network_report: None,
+            network_test_running: false,
+            full_macs: false,
+            reset_prompt: None,
+        }
+    }
+
+    pub fn set_cache_dir(&mut self, dir: std::path::PathBuf) {
+        self.cache_dir = dir;
+        self.cache_size = None;
+    }
+
+    pub fn set_cache_size(&mut self, bytes: u64) {
+        self.cache_size = Some(bytes);
+    }
+
+    pub fn start_network_test(&mut self) {
+        self.network_test_running = true;
+    }
+
+    pub fn set_network_report(&mut self, report: SelfTestReport) {
+        self.network_test_running = false;
+        self.network_report = Some(report);
+    }
+
+    pub fn network_report(&self) -> Option<&SelfTestReport> {
+        self.network_report.as_ref()
+    }
+
+    pub fn network_test_running(&self) -> bool {
+        self.network_test_running
+    }
+
+    pub fn full_macs(&self) -> bool {
+        self.full_macs
+    }
+
+    pub fn set_full_macs(&mut self, enabled: bool) {
+        self.full_macs = enabled;
+    }
+
+    /// Mostra la richiesta di conferma del ripristino
+    pub fn show_reset_prompt(&mut self) {
+        self.reset_prompt = Some(false);
+    }
+
+    pub fn hide_reset_prompt(&mut self) {
+        self.reset_prompt = None;
+    }
+
+    /// Sceglie se ripristinare anche la modalità della finestra
+    pub fn set_reset_window(&mut self, reset_window: bool) {
+        if self.reset_prompt.is_some() {
+            self.reset_prompt = Some(reset_window);
+        }
+    }
+
+    /// Il ripristino deve includere la modalità della finestra
+    pub fn reset_window(&self) -> bool {
+        self.reset_prompt.unwrap_or(false)
+    }
+
+    pub fn set_max_concurrent_transfers(&mut self, max: u32) {
+        self.max_concurrent_transfers = max;
+    }
+
+    pub fn set_max_transfers_per_peer(&mut self, max: u32) {
+        self.max_transfers_per_peer = max;
+    }
+
+    /// Aggiorna il nome mostrato agli altri dispositivi
+    pub fn set_friendly_name_text(&mut self, text: String) {
+        self.friendly_name_text = text;
+    }
+
+    /// Testo del campo del nome, così come è stato scritto
+    pub fn friendly_name_text(&self) -> &str {
+        &self.friendly_name_text
+    }
+
+    /// Nome inserito: `None` se il campo è vuoto
+    pub fn friendly_name(&self) -> Option<String> {
+        let name = self.friendly_name_text.trim();
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    pub fn set_name_suffix_text(&mut self, text: String) {
+        self.name_suffix_text = text;
+    }
+
+    /// Suffisso inserito: `None` se il campo è vuoto
+    pub fn name_suffix(&self) -> Option<String> {
+        let suffix = self.name_suffix_text.trim();
+        (!suffix.is_empty()).then(|| suffix.to_string())
+    }
+
+    pub fn set_avoid_name_collisions(&mut self, enabled: bool) {
+        self.avoid_name_collisions = enabled;
+    }
+
+    pub fn set_advertised_name(&mut self, name: Option<String>) {
+        self.advertised_name = name;
+    }
+
+    /// Aggiorna il testo della porta personalizzata
+    pub fn set_custom_port_text(&mut self, text: String) {
+        self.custom_port_text = text;
+    }
+
+    /// Porta personalizzata inserita: `None` se il campo è vuoto
+    pub fn parse_custom_port(&self) -> Result<Option<u16>, String> {
+        let text = self.custom_port_text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        match text.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(Some(port)),
+            _ => Err(format!("Porta non valida: {}", text)),
+        }
+    }
+
+    /// Aggiorna la qualità AirPlay selezionata
+    pub fn set_airplay_quality(&mut self, quality: AirPlayQuality) {
+        self.airplay_quality = quality;
+    }
+
+    /// Aggiorna la visualizzazione delle notifiche
+    pub fn set_show_notifications(&mut self, enabled: bool) {
+        self.show_notifications = enabled;
+    }
+
+    /// Aggiorna la scelta tra notifiche nella finestra e di sistema
+    pub fn set_system_notifications(&mut self, enabled: bool) {
+        self.system_notifications = enabled;
+    }
+
+    /// Aggiorna il suono di fine trasferimento
+    pub fn set_play_sound_on_complete(&mut self, enabled: bool) {
+        self.play_sound_on_complete = enabled;
+    }
+
+    /// Aggiorna il comportamento alla chiusura della finestra
+    pub fn set_minimize_to_tray(&mut self, enabled: bool) {
+        self.minimize_to_tray = enabled;
+    }
+
+    /// Aggiorna lo stato del servizio AirDrop
+    pub fn set_airdrop_enabled(&mut self, enabled: bool) {
+        self.airdrop_enabled = enabled;
+    }
+
+    /// Aggiorna chi può inviare file
+    pub fn set_airdrop_visibility(&mut self, visibility: AirDropVisibility) {
+        self.airdrop_visibility = visibility;
+    }
+
+    /// Aggiorna l'accettazione automatica dai contatti
+    pub fn set_auto_accept_from_contacts(&mut self, enabled: bool) {
+        self.auto_accept_from_contacts = enabled;
+    }
+
+    /// Aggiorna i dispositivi attendibili
+    pub fn set_trusted_devices(&mut self, devices: Vec<TrustedDevice>) {
+        self.trusted_devices = devices;
+    }
+
+    /// Aggiorna il comportamento per i file con lo stesso nome
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    /// Aggiorna lo stato del servizio AirPlay
+    pub fn set_airplay_enabled(&mut self, enabled: bool) {
+        self.airplay_enabled = enabled;
+    }
+
+    /// Conferma la porta personalizzata applicata
+    pub fn set_custom_port(&mut self, port: Option<u16>) {
+        self.custom_port = port;
+        self.custom_port_text = port.map(|p| p.to_string()).unwrap_or_default();
+    }
+
+    pub fn set_completion_choice(&mut self, choice: CompletionChoice) {
+        self.completion_choice = choice;
+    }
+
+    pub fn set_ip_mode(&mut self, choice: IpModeChoice) {
+        self.ip_mode = choice;
+    }
+
+    pub fn set_airplay_idle_timeout(&mut self, timeout: IdleTimeout) {
+        self.airplay_idle_timeout = timeout;
+    }
+
+    pub fn set_airplay_show_cursor(&mut self, show: bool) {
+        self.airplay_show_cursor = show;
+    }
+
+    pub fn set_send_method(&mut self, method: SendMethod) {
+        self.send_method = method;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn set_font_scale(&mut self, scale: FontScale) {
+        self.font_scale = scale;
+    }
+
+    pub fn set_compression(&mut self, compression: CompressionChoice) {
+        self.compression = compression;
+    }
+
+    pub fn set_stay_connected(&mut self, enabled: bool) {
+        self.stay_connected = enabled;
+    }
+
+    pub fn set_shared_folder(&mut self, folder: Option<std::path::PathBuf>) {
+        self.shared_folder = folder;
+    }
+
+    /// Conferma l'area dello schermo applicata
+    pub fn set_capture_region(&mut self, region: Option<CaptureRegion>) {
+        self.capture_region = region;
+        self.capture_region_text = match region {
+            Some(region) => [region.x, region.y, region.width, region.height].map(|value| value.to_string()),
+            None => Default::default(),
+        };
+    }
+
+    /// Dimensioni dello schermo usate per l'anteprima dell'area
+    pub fn set_screen_size(&mut self, size: Option<(u32, u32)>) {
+        self.screen_size = size;
+    }
+
+    /// Aggiorna uno dei campi dell'area: 0 x, 1 y, 2 larghezza, 3 altezza
+    pub fn set_capture_region_text(&mut self, field: usize, text: String) {
+        if let Some(value) = self.capture_region_text.get_mut(field) {
+            *value = text;
+        }
+    }
+
+    /// Area inserita: `None` se tutti i campi sono vuoti
+    pub fn parse_capture_region(&self) -> Result<Option<CaptureRegion>, String> {
+        if self.capture_region_text.iter().all(|value| value.trim().is_empty()) {
+            return Ok(None);
+        }
+
+        let mut values = [0i32; 4];
+        for (value, text) in values.iter_mut().zip(&self.capture_region_text) {
+            *value = text
+                .trim()
+                .parse()
+                .map_err(|_| format!("Valore non valido: \"{}\"", text.trim()))?;
+        }
+        let [x, y, width, height] = values;
+        if width <= 0 || height <= 0 {
+            return Err("Larghezza e altezza devono essere maggiori di zero".to_string());
         }
+        Ok(Some(CaptureRegion { x, y, width, height }))
+    }
+
+    /// Miniatura dello schermo con l'area trasmessa evidenziata
+    fn region_preview(&self) -> Element<Message> {
+        const PREVIEW_WIDTH: f32 = 192.0;
+
+        let (Some((screen_width, screen_height)), Some(region)) = (self.screen_size, self.capture_region) else {
+            return Space::with_height(0).into();
+        };
+        let Ok(region) = region.clamp_to(screen_width, screen_height) else {
+            return Space::with_height(0).into();
+        };
+
+        // Porzioni in millesimi dello schermo
+        let portion = |value: i32, total: u32| ((value as u64 * 1000) / total as u64).max(1) as u16;
+        let left = portion(region.x, screen_width);
+        let width = portion(region.width, screen_width);
+        let right = 1000u16.saturating_sub(left + width).max(1);
+        let top = portion(region.y, screen_height);
+        let height = portion(region.height, screen_height);
+        let bottom = 1000u16.saturating_sub(top + height).max(1);
+
+        let area = row![
+            Space::with_width(Length::FillPortion(left)),
+            container(Space::new(Length::Fill, Length::Fill))
+                .width(Length::FillPortion(width))
+                .height(Length::Fill)
+                .style(styles::container_info),
+            Space::with_width(Length::FillPortion(right)),
+        ]
+        .height(Length::FillPortion(height));
+
+        container(column![
+            Space::with_height(Length::FillPortion(top)),
+            area,
+            Space::with_height(Length::FillPortion(bottom)),
+        ])
+        .width(PREVIEW_WIDTH)
+        .height(PREVIEW_WIDTH * screen_height as f32 / screen_width as f32)
+        .style(styles::container_secondary)
+        .into()
     }
 
     /// Sezione impostazioni AirPlay
     fn airplay_settings(&self, _theme: &Theme) -> Element<Message> {
         let section_header = text("AirPlay")
-            .size(18);
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
 
         let settings = column![
             // AirPlay abilitato
@@ -183,32 +885,88 @@ impl SettingsView {
                 "Abilita AirPlay",
                 self.airplay_enabled
             )
-            .on_toggle(|_| Message::Tick),
+            .on_toggle(Message::AirPlayEnabledToggled),
             
             if self.airplay_enabled {
                 column![
                     // Qualità
                     row![
                         text("Qualità video:")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                             .width(Length::FillPortion(1)),
                         
                         pick_list(
                             &AIRPLAY_QUALITIES[..],
                             Some(self.airplay_quality.clone()),
-                            |_| Message::Tick
+                            Message::AirPlayQualityChanged
                         )
                         .width(Length::FillPortion(2)),
                     ]
                     .align_items(Alignment::Center)
                     .spacing(styles::spacing::MEDIUM),
                     
+                    // Arresto per inattività
+                    row![
+                        text("Ferma se inattivo:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        pick_list(
+                            self.airplay_idle_timeout.choices(),
+                            Some(self.airplay_idle_timeout),
+                            Message::AirPlayIdleTimeoutChanged
+                        )
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    // Puntatore del mouse nei fotogrammi
+                    checkbox(
+                        "Mostra il puntatore del mouse",
+                        self.airplay_show_cursor
+                    )
+                    .on_toggle(Message::AirPlayShowCursorToggled),
+
                     // Solo audio
                     checkbox(
                         "Solo audio (migliori prestazioni)",
                         self.airplay_audio_only
                     )
                     .on_toggle(|_| Message::Tick),
+
+                    // Area dello schermo trasmessa
+                    row![
+                        text("Area trasmessa:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        row(["X", "Y", "Larghezza", "Altezza"]
+                            .into_iter()
+                            .enumerate()
+                            .map(|(field, placeholder)| {
+                                text_input(placeholder, &self.capture_region_text[field])
+                                    .on_input(move |text| Message::CaptureRegionChanged(field, text))
+                                    .on_submit(Message::CaptureRegionSubmitted)
+                                    .into()
+                            })
+                            .collect::<Vec<Element<Message>>>())
+                        .spacing(styles::spacing::SMALL)
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    row![
+                        self.region_preview(),
+                        Space::with_width(Length::Fill),
+                        button(text("Applica").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                            .on_press(Message::CaptureRegionSubmitted),
+                        button(text("Schermo intero").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                            .on_press(Message::CaptureRegionCleared),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::SMALL),
                 ]
                 .spacing(styles::spacing::MEDIUM)
             } else {
@@ -234,7 +992,7 @@ impl SettingsView {
         let header = row![
             button(
                 text("← Indietro")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
             )
             .on_press(Message::ShowMainView)
             .style(iced::theme::Button::Secondary),
@@ -242,25 +1000,38 @@ impl SettingsView {
             Space::with_width(styles::spacing::MEDIUM),
             
             text("Impostazioni")
-                .size(24)
+                .size(styles::font_size::scaled(styles::font_size::XLARGE))
                 ,
             
             Space::with_width(Length::Fill),
             
             button(
                 text("💾 Salva")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
             )
             // Placeholder azione salvataggio
             .on_press(Message::Tick)
             .style(iced::theme::Button::Primary),
             
+            button(
+                text("📤 Esporta")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+            )
+            .on_press(Message::ExportSettings)
+            .style(iced::theme::Button::Secondary),
+
+            button(
+                text("📥 Importa")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+            )
+            .on_press(Message::ImportSettings)
+            .style(iced::theme::Button::Secondary),
+
             button(
                 text("🔄 Reset")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
             )
-            // Placeholder azione reset
-            .on_press(Message::Tick)
+            .on_press(Message::ResetSettings)
             .style(iced::theme::Button::Secondary),
         ]
         .align_items(Alignment::Center)
@@ -271,6 +1042,11 @@ impl SettingsView {
                 // Impostazioni generali
                 self.general_settings(theme),
                 
+                Space::with_height(styles::spacing::LARGE),
+
+                // Tema e dimensione del testo
+                self.appearance_settings(),
+
                 Space::with_height(styles::spacing::LARGE),
                 
                 // Impostazioni AirDrop
@@ -295,11 +1071,13 @@ impl SettingsView {
             ]
             .spacing(0)
         )
+        .id(scrollable_id())
         .height(Length::Fill);
 
         container(
             column![
                 header,
+                self.reset_confirmation(),
                 horizontal_rule(1),
                 content,
             ]
@@ -308,12 +1086,84 @@ impl SettingsView {
         .into()
     }
 
+    /// Conferma prima di ripristinare le impostazioni predefinite
+    fn reset_confirmation(&self) -> Element<Message> {
+        let Some(reset_window) = self.reset_prompt else {
+            return Space::with_height(0).into();
+        };
+
+        container(
+            column![
+                text("Ripristinare tutte le impostazioni ai valori predefiniti?")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                checkbox("Ripristina anche la modalità della finestra", reset_window)
+                    .on_toggle(Message::ResetWindowToggled),
+                row![
+                    Space::with_width(Length::Fill),
+                    button(text("Annulla").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                        .on_press(Message::CancelResetSettings)
+                        .style(iced::theme::Button::Secondary),
+                    button(text("Ripristina").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                        .on_press(Message::ConfirmResetSettings)
+                        .style(iced::theme::Button::Destructive),
+                ]
+                .spacing(styles::spacing::SMALL),
+            ]
+            .spacing(styles::spacing::SMALL)
+        )
+        .padding(styles::spacing::MEDIUM.0)
+        .width(Length::Fill)
+        .into()
+    }
+
     /// Sezione impostazioni generali
     fn general_settings(&self, _theme: &Theme) -> Element<Message> {
         let section_header = text("Generale")
-            .size(18);
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
 
+        let hostname = crate::protocols::apple_records::hostname().unwrap_or_default();
         let settings = column![
+            // Nome mostrato agli altri dispositivi
+            row![
+                text("Nome del dispositivo:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+
+                text_input(&hostname, &self.friendly_name_text)
+                    .on_input(Message::FriendlyNameChanged)
+                    .on_submit(Message::FriendlyNameSubmitted)
+                    .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+
+            row![
+                text("Suffisso del nome:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+
+                text_input("es. ufficio", &self.name_suffix_text)
+                    .on_input(Message::NameSuffixChanged)
+                    .on_submit(Message::NameSuffixSubmitted)
+                    .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+
+            checkbox(
+                "Aggiungi un numero se il nome è già in uso sulla rete",
+                self.avoid_name_collisions
+            )
+            .on_toggle(Message::AvoidNameCollisionsToggled),
+
+            // Nome effettivo, con suffisso e numero
+            text(match &self.advertised_name {
+                Some(name) => format!("Nome annunciato: {}", name),
+                None => "Nome annunciato: non ancora registrato".to_string(),
+            })
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
+            .style(styles::Palette::active().text_muted),
+
             // Auto discovery
             row![
                 checkbox(
@@ -327,7 +1177,7 @@ impl SettingsView {
             if self.auto_discovery {
                 column![
                     text(format!("Intervallo scansione: {} secondi", self.discovery_interval))
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                         ,
                     
                     slider(
@@ -343,18 +1193,93 @@ impl SettingsView {
             },
             
             // Notifiche
+            row![
+                checkbox(
+                    "Mostra notifiche",
+                    self.show_notifications
+                )
+                .on_toggle(Message::ShowNotificationsToggled),
+
+                Space::with_width(Length::Fill),
+
+                button(
+                    text("🔔 Notifica di prova")
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                )
+                .on_press(Message::TestNotification)
+                .style(iced::theme::Button::Secondary),
+            ]
+            .align_items(Alignment::Center),
+
             checkbox(
-                "Mostra notifiche",
-                self.show_notifications
+                "Usa le notifiche di sistema (visibili anche con la finestra ridotta a icona)",
+                self.system_notifications
             )
-            .on_toggle(|_| Message::Tick),
+            .on_toggle(Message::SystemNotificationsToggled),
+
+            // Suono a fine trasferimento
+            checkbox(
+                "Riproduci un suono al termine dei trasferimenti",
+                self.play_sound_on_complete
+            )
+            .on_toggle(Message::PlaySoundToggled),
             
             // Minimize to tray
             checkbox(
-                "Minimizza nella system tray",
+                "Alla chiusura riduci a icona invece di uscire",
                 self.minimize_to_tray
             )
-            .on_toggle(|_| Message::Tick),
+            .on_toggle(Message::MinimizeToTrayToggled),
+        ]
+        .spacing(styles::spacing::MEDIUM);
+
+        container(
+            column![
+                section_header,
+                Space::with_height(styles::spacing::MEDIUM),
+                settings,
+            ]
+        )
+        .padding(styles::spacing::MEDIUM.0)
+        .width(Length::Fill)
+        .into()
+    }
+
+    /// Sezione tema e dimensione del testo
+    fn appearance_settings(&self) -> Element<Message> {
+        let section_header = text("Aspetto")
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
+
+        let settings = column![
+            row![
+                text("Tema:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+
+                pick_list(
+                    &THEMES[..],
+                    Some(self.theme),
+                    Message::ThemeChanged
+                )
+                .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+
+            row![
+                text("Dimensione del testo:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+
+                pick_list(
+                    self.font_scale.choices(),
+                    Some(self.font_scale),
+                    Message::FontScaleChanged
+                )
+                .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
         ]
         .spacing(styles::spacing::MEDIUM);
 
@@ -373,7 +1298,7 @@ impl SettingsView {
     /// Sezione impostazioni AirDrop
     fn airdrop_settings(&self, _theme: &Theme) -> Element<Message> {
         let section_header = text("AirDrop")
-            .size(18);
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
 
         let settings = column![
             // AirDrop abilitato
@@ -381,21 +1306,21 @@ impl SettingsView {
                 "Abilita AirDrop",
                 self.airdrop_enabled
             )
-            .on_toggle(|_| Message::Tick),
+            .on_toggle(Message::AirDropEnabledToggled),
             
             if self.airdrop_enabled {
                 column![
                     // Visibilità
                     row![
                         text("Visibilità:")
-                            .size(14)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                             
                             .width(Length::FillPortion(1)),
                         
                         pick_list(
                             &AIRDROP_VISIBILITIES[..],
                             Some(self.airdrop_visibility.clone()),
-                            |_| Message::Tick
+                            Message::AirDropVisibilityChanged
                         )
                         
                         .width(Length::FillPortion(2)),
@@ -403,12 +1328,117 @@ impl SettingsView {
                     .align_items(Alignment::Center)
                     .spacing(styles::spacing::MEDIUM),
                     
-                    // Auto accept da contatti
+                    // Auto accept dai dispositivi attendibili
                     checkbox(
-                        "Accetta automaticamente da contatti",
+                        "Accetta automaticamente dai dispositivi attendibili",
                         self.auto_accept_from_contacts
                     )
-                    .on_toggle(|_| Message::Tick),
+                    .on_toggle(Message::AutoAcceptFromContactsToggled),
+
+                    self.trusted_devices_list(),
+
+                    // File con lo stesso nome
+                    row![
+                        text("File già esistenti:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        pick_list(
+                            &CONFLICT_POLICIES[..],
+                            Some(self.conflict_policy.clone()),
+                            Message::ConflictPolicyChanged
+                        )
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    // Azione sui file ricevuti
+                    row![
+                        text("A ricezione completata:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        pick_list(
+                            &COMPLETION_CHOICES[..],
+                            Some(self.completion_choice.clone()),
+                            Message::CompletionChoiceChanged
+                        )
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    // Metodo di invio
+                    row![
+                        text("Metodo di invio:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        pick_list(
+                            &SEND_METHODS[..],
+                            Some(self.send_method.clone()),
+                            Message::SendMethodChanged
+                        )
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    // Connessione dopo l'invio
+                    checkbox(
+                        "Resta connesso dopo l'invio per i file successivi",
+                        self.stay_connected
+                    )
+                    .on_toggle(Message::StayConnectedToggled),
+
+                    // Compressione verso altre istanze di AirWin
+                    row![
+                        text("Compressione tra PC AirWin:")
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::FillPortion(1)),
+
+                        pick_list(
+                            &COMPRESSION_CHOICES[..],
+                            Some(self.compression),
+                            Message::CompressionChanged
+                        )
+                        .width(Length::FillPortion(2)),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::MEDIUM),
+
+                    // Cartella condivisa per l'invio di riserva
+                    row![
+                        column![
+                            text("Cartella condivisa:")
+                                .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                            text(
+                                self.shared_folder
+                                    .as_ref()
+                                    .map(|folder| folder.display().to_string())
+                                    .unwrap_or_else(|| "Nessuna".to_string())
+                            )
+                                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                                .style(styles::Palette::active().text_muted),
+                        ]
+                        .width(Length::Fill),
+
+                        button(
+                            text("📁 Scegli")
+                                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                        )
+                        .on_press(Message::SelectSharedFolder),
+
+                        if self.shared_folder.is_some() {
+                            button(text("Rimuovi").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                                .on_press(Message::ClearSharedFolder)
+                        } else {
+                            button(text("Rimuovi").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                        },
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::SMALL),
                 ]
                 .spacing(styles::spacing::MEDIUM)
             } else {
@@ -429,16 +1459,42 @@ impl SettingsView {
         .into()
     }
 
+    /// Dispositivi attendibili, aggiunti con "Accetta sempre" da una richiesta di invio
+    fn trusted_devices_list(&self) -> Element<Message> {
+        if self.trusted_devices.is_empty() {
+            return text("Nessun dispositivo attendibile: scegli \"Accetta sempre\" quando un contatto ti invia un file")
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted)
+                .into();
+        }
+        self.trusted_devices
+            .iter()
+            .fold(column![].spacing(styles::spacing::SMALL), |list, device| {
+                list.push(
+                    row![
+                        text(&device.name)
+                            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                            .width(Length::Fill),
+                        button(text("Rimuovi").size(styles::font_size::scaled(styles::font_size::MEDIUM)))
+                            .on_press(Message::RemoveTrustedDevice(device.id.clone())),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(styles::spacing::SMALL),
+                )
+            })
+            .into()
+    }
+
     /// Sezione impostazioni di rete
     fn network_settings(&self, _theme: &Theme) -> Element<Message> {
         let section_header = text("Rete")
-            .size(18);
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
 
         let settings = column![
             // Interfaccia di rete
             row![
                 text("Interfaccia di rete:")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     
                     .width(Length::FillPortion(1)),
                 
@@ -454,18 +1510,36 @@ impl SettingsView {
             .align_items(Alignment::Center)
             .spacing(styles::spacing::MEDIUM),
             
-            // Porta personalizzata
+            // Porta personalizzata del server AirPlay
             row![
-                text("Porta personalizzata:")
-                    .size(14)
+                text("Porta AirPlay:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     
                     .width(Length::FillPortion(1)),
                 
                 text_input(
-                    "Automatica",
-                    ""
+                    "7100",
+                    &self.custom_port_text
+                )
+                .id(custom_port_input_id())
+                .on_input(Message::CustomPortChanged)
+                .on_submit(Message::CustomPortSubmitted)
+                .width(Length::FillPortion(2)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+
+            // Versioni di IP, applicate al prossimo avvio
+            row![
+                text("Protocollo IP:")
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    .width(Length::FillPortion(1)),
+
+                pick_list(
+                    &IP_MODES[..],
+                    Some(self.ip_mode.clone()),
+                    Message::IpModeChanged
                 )
-                .on_input(|_| Message::Tick)
                 .width(Length::FillPortion(2)),
             ]
             .align_items(Alignment::Center)
@@ -488,7 +1562,7 @@ impl SettingsView {
     /// Sezione impostazioni avanzate
     fn advanced_settings(&self, _theme: &Theme) -> Element<Message> {
         let section_header = text("Avanzate")
-            .size(18);
+            .size(styles::font_size::scaled(styles::font_size::LARGE));
 
         let settings = column![
             // Debug mode
@@ -502,7 +1576,7 @@ impl SettingsView {
             // Log level
             row![
                 text("Livello di log:")
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     
                     .width(Length::FillPortion(1)),
                 
@@ -520,42 +1594,111 @@ impl SettingsView {
             // Max concurrent transfers
             column![
                 text(format!("Trasferimenti simultanei: {}", self.max_concurrent_transfers))
-                    .size(14)
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                     ,
                 
                 slider(
                     1..=10,
                     self.max_concurrent_transfers,
-                    |_| Message::Tick
+                    Message::MaxConcurrentTransfersChanged
+                )
+                ,
+            ]
+            .spacing(styles::spacing::SMALL),
+
+            // Max transfers to the same device
+            column![
+                text(format!("Trasferimenti simultanei per dispositivo: {}", self.max_transfers_per_peer))
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                    ,
+                
+                slider(
+                    1..=10,
+                    self.max_transfers_per_peer,
+                    Message::MaxTransfersPerPeerChanged
                 )
                 ,
             ]
             .spacing(styles::spacing::SMALL),
             
+            // Cache
+            row![
+                column![
+                    text("Cartella cache:")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                    text(format!(
+                        "{} • {}",
+                        self.cache_dir.display(),
+                        self.cache_size
+                            .map(crate::ui::components::format_bytes)
+                            .unwrap_or_else(|| "calcolo...".to_string())
+                    ))
+                        .size(styles::font_size::scaled(styles::font_size::SMALL))
+                        .style(styles::Palette::active().text_muted),
+                ]
+                .width(Length::Fill),
+
+                button(
+                    text("📁 Cambia")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                )
+                .on_press(Message::SelectCacheDir)
+                .style(iced::theme::Button::Secondary),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(styles::spacing::MEDIUM),
+
             // Azioni avanzate
             row![
                 button(
                     text("🗂 Apri Log")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press(Message::OpenLogFolder)
                 .style(iced::theme::Button::Secondary),
                 
                 button(
                     text("🧹 Pulisci Cache")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press(Message::ClearCache)
                 .style(iced::theme::Button::Secondary),
                 
                 button(
                     text("📊 Diagnostica")
-                        .size(14)
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
                 )
                 .on_press(Message::RunDiagnostics)
                 .style(iced::theme::Button::Secondary),
+
+                button(
+                    text(if self.network_test_running { "⏳ Verifica..." } else { "🩺 Verifica rete" })
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                )
+                .on_press_maybe((!self.network_test_running).then_some(Message::VerifyNetwork))
+                .style(iced::theme::Button::Secondary),
             ]
             .spacing(styles::spacing::MEDIUM),
+
+            // Diagnostica per le segnalazioni
+            row![
+                button(
+                    text("📋 Copia diagnostica")
+                        .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                )
+                .on_press(Message::CopyDiagnostics)
+                .style(iced::theme::Button::Secondary),
+
+                checkbox(
+                    "Includi gli indirizzi MAC completi",
+                    self.full_macs
+                )
+                .on_toggle(Message::FullMacsToggled),
+            ]
+            .spacing(styles::spacing::MEDIUM)
+            .align_items(Alignment::Center),
+
+            self.network_checklist(),
         ]
         .spacing(styles::spacing::MEDIUM);
 
@@ -570,4 +1713,47 @@ impl SettingsView {
         .width(Length::Fill)
         .into()
     }
-}
\ No newline at end of file
+
+    /// Esiti della verifica della rete, uno per riga con il suggerimento
+    fn network_checklist(&self) -> Element<Message> {
+        let Some(report) = &self.network_report else {
+            return Space::with_height(0).into();
+        };
+
+        let checks = report.checks.iter().fold(column![].spacing(styles::spacing::SMALL), |col, check| {
+            let color = match check.status {
+                CheckStatus::Pass => styles::Palette::active().success,
+                CheckStatus::Fail => styles::Palette::active().error,
+                CheckStatus::Skip => styles::Palette::active().text_muted,
+            };
+            let mut entry = column![
+                row![
+                    text(check.status.icon()).size(styles::font_size::scaled(styles::font_size::MEDIUM)).style(color),
+                    text(&check.name).size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                    text(&check.detail).size(styles::font_size::scaled(styles::font_size::SMALL)).style(styles::Palette::active().text_muted),
+                ]
+                .spacing(styles::spacing::SMALL)
+                .align_items(Alignment::Center),
+            ];
+            if let Some(hint) = &check.hint {
+                entry = entry.push(text(format!("→ {}", hint)).size(styles::font_size::scaled(styles::font_size::SMALL)).style(styles::Palette::active().warning));
+            }
+            col.push(entry)
+        });
+
+        column![
+            row![
+                text(if report.passed() { "Rete verificata" } else { "Problemi di rete rilevati" })
+                    .size(styles::font_size::scaled(styles::font_size::MEDIUM)),
+                Space::with_width(Length::Fill),
+                button(text("📋 Copia report").size(styles::font_size::scaled(styles::font_size::SMALL)))
+                    .on_press(Message::CopyNetworkReport)
+                    .style(iced::theme::Button::Secondary),
+            ]
+            .align_items(Alignment::Center),
+            checks,
+        ]
+        .spacing(styles::spacing::SMALL)
+        .into()
+    }
+}
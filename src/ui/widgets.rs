@@ -5,7 +5,7 @@
 
 use iced::{
     widget::{
-        button, column, container, row, text, Space, progress_bar,
+        button, column, container, row, text, tooltip, Space, progress_bar,
         horizontal_rule, vertical_rule,
     },
     Alignment, Element, Length, Background, Color, Border, Shadow, Pixels,
@@ -22,14 +22,14 @@ pub fn connection_status<'a>(
     _theme: &IcedTheme,
 ) -> Element<'a, Message> {
     let (status_text, status_color) = if is_connected {
-        ("Connesso", styles::colors::SUCCESS)
+        ("Connesso", styles::Palette::active().success)
     } else {
-        ("Disconnesso", styles::colors::ERROR)
+        ("Disconnesso", styles::Palette::active().error)
     };
 
     let status_indicator = container(
         text("●")
-            .size(12)
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
             .style(status_color)
     )
     .width(Length::Fixed(20.0))
@@ -38,17 +38,17 @@ pub fn connection_status<'a>(
     let status_content = if let Some(name) = device_name {
         column![
             text(status_text)
-                .size(12)
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
                 .style(Color::BLACK),
             text(name)
-                .size(10)
+                .size(styles::font_size::scaled(styles::font_size::TINY))
                 .style(Color::from_rgb(0.5, 0.5, 0.5)),
         ]
         .spacing(2)
     } else {
         column![
             text(status_text)
-                .size(12)
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
                 .style(Color::BLACK),
         ]
     };
@@ -82,12 +82,12 @@ pub fn transfer_progress<'a>(
         .height(Length::Fixed(8.0));
 
     let progress_text = text(format!("{:.1}%", progress))
-        .size(12)
+        .size(styles::font_size::scaled(styles::font_size::SMALL))
         .style(Color::from_rgb(0.5, 0.5, 0.5));
 
     let file_info = row![
         text(file_name)
-            .size(14)
+            .size(styles::font_size::scaled(styles::font_size::MEDIUM))
             .style(Color::BLACK),
         Space::with_width(Length::Fill),
         progress_text,
@@ -97,8 +97,8 @@ pub fn transfer_progress<'a>(
     let speed_info = if let Some(speed) = transfer_speed {
         Some(
             text(speed)
-                .size(10)
-                .style(styles::colors::TEXT_MUTED)
+                .size(styles::font_size::scaled(styles::font_size::TINY))
+                .style(styles::Palette::active().text_muted)
         )
     } else {
         None
@@ -142,10 +142,10 @@ pub fn network_stats<'a>(
     let stat_item = |label: &str, value: &str| -> Element<'a, Message> {
         column![
             text(value)
-                .size(16)
+                .size(styles::font_size::scaled(16))
                 .style(Color::BLACK),
             text(label)
-                .size(10)
+                .size(styles::font_size::scaled(styles::font_size::TINY))
                 .style(Color::from_rgb(0.5, 0.5, 0.5)),
         ]
         .align_items(Alignment::Center)
@@ -186,16 +186,16 @@ pub fn status_badge<'a>(
     _theme: &IcedTheme,
 ) -> Element<'a, Message> {
     let (bg_color, text_color) = match badge_type {
-        BadgeType::Success => (styles::colors::SUCCESS, Color::WHITE),
-        BadgeType::Warning => (styles::colors::WARNING, Color::BLACK),
-        BadgeType::Error => (styles::colors::ERROR, Color::WHITE),
-        BadgeType::Info => (styles::colors::INFO, Color::WHITE),
-        BadgeType::Neutral => (styles::colors::SURFACE, styles::colors::TEXT_PRIMARY),
+        BadgeType::Success => (styles::Palette::active().success, Color::WHITE),
+        BadgeType::Warning => (styles::Palette::active().warning, Color::BLACK),
+        BadgeType::Error => (styles::Palette::active().error, Color::WHITE),
+        BadgeType::Info => (styles::Palette::active().info, Color::WHITE),
+        BadgeType::Neutral => (styles::Palette::active().surface, styles::Palette::active().text_primary),
     };
 
     container(
         text(text_content)
-            .size(10)
+            .size(styles::font_size::scaled(styles::font_size::TINY))
             .style(text_color)
     )
     .padding([2, 6])
@@ -228,8 +228,8 @@ pub fn text_separator<'a>(
         
         container(
             text(text_content)
-                .size(12)
-                .style(styles::colors::TEXT_MUTED)
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted)
         )
         .padding([0, styles::spacing::MEDIUM.0 as u16]),
         
@@ -241,12 +241,31 @@ pub fn text_separator<'a>(
 
 /// Widget per visualizzare un tooltip informativo
 pub fn info_tooltip<'a>(
-    content: Element<'a, Message>,
-    _tooltip_text: &str,
-    _theme: &IcedTheme,
+    content: impl Into<Element<'a, Message>>,
+    tooltip_text: &str,
+) -> Element<'a, Message> {
+    tooltip(content, text(tooltip_text).size(styles::font_size::scaled(styles::font_size::SMALL)), tooltip::Position::Bottom)
+        .gap(4.0)
+        .padding(styles::spacing::SMALL.0)
+        .style(iced::theme::Container::Box)
+        .into()
+}
+
+/// Contenuto seguito da un pulsante che copia `value` negli appunti.
+/// `label` descrive il valore nella notifica di conferma (es. "Indirizzo").
+pub fn with_copy_button<'a>(
+    content: impl Into<Element<'a, Message>>,
+    label: &str,
+    value: String,
 ) -> Element<'a, Message> {
-    // Per ora restituiamo solo il contenuto, in futuro si può implementare un vero tooltip
-    content
+    let copy = button(text("📋").size(styles::font_size::scaled(styles::font_size::SMALL)))
+        .on_press(Message::CopyText { label: label.to_string(), text: value })
+        .padding([0, 4])
+        .style(iced::theme::Button::Text);
+    row![content.into(), info_tooltip(copy, "Copia negli appunti")]
+        .spacing(styles::spacing::TINY)
+        .align_items(Alignment::Center)
+        .into()
 }
 
 /// Widget per creare un layout a griglia responsive
@@ -283,15 +302,15 @@ pub fn section_header<'a>(
     _theme: &IcedTheme,
 ) -> Element<'a, Message> {
     let title_text = text(title)
-        .size(18)
-        .style(styles::colors::TEXT_PRIMARY);
+        .size(styles::font_size::scaled(styles::font_size::LARGE))
+        .style(styles::Palette::active().text_primary);
 
     let mut header_content = column![title_text];
 
     if let Some(sub) = subtitle {
         let subtitle_text = text(sub)
-            .size(12)
-            .style(styles::colors::TEXT_MUTED);
+            .size(styles::font_size::scaled(styles::font_size::SMALL))
+            .style(styles::Palette::active().text_muted);
         header_content = header_content.push(subtitle_text);
     }
 
@@ -321,14 +340,14 @@ pub fn collapsible_panel<'a>(
     let header = button(
         row![
             text(toggle_icon)
-                .size(12)
-                .style(styles::colors::TEXT_MUTED),
+                .size(styles::font_size::scaled(styles::font_size::SMALL))
+                .style(styles::Palette::active().text_muted),
             
             Space::with_width(styles::spacing::SMALL),
             
             text(title)
-                .size(14)
-                .style(styles::colors::TEXT_PRIMARY),
+                .size(styles::font_size::scaled(styles::font_size::MEDIUM))
+                .style(styles::Palette::active().text_primary),
         ]
         .align_items(Alignment::Center)
     )
@@ -1,16 +1,49 @@
+//! Main egui window
+//!
+//! The UI never locks a protocol mutex while drawing. Every value it shows
+//! (AirPlay and AirDrop status, discovered devices, scan and receive state,
+//! frame info) lives in a `watch` channel. Background tasks own the writes:
+//! they can afford to `.await` the protocol locks and publish the result,
+//! and the UI only reads the last published value with `borrow()`. A slow
+//! lock therefore delays an update instead of showing a default status, and
+//! sends wait for the AirDrop lock on their own task instead of failing.
+
 use eframe::egui::{self, RichText, Color32};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 use crate::network::{DeviceDiscovery, DiscoveredDevice, ServiceType};
 use crate::protocols::airplay::{AirPlay, AirPlayStatus};
-use crate::protocols::airdrop::{AirDrop, AirDropStatus};
+use crate::protocols::airdrop::{AirDrop, AirDropStatus, ConnectionState};
 use crate::protocols::awdl::AwdlManager;
 use super::components::{self, DeviceCard, DeviceStatus, StyleConfig};
 use tokio::time::Duration;
 use std::net::SocketAddr;
 use rfd::FileDialog;
 use std::path::PathBuf;
-use tracing::{error, warn};
+use tracing::error;
+
+/// How often the background tasks refresh a status that is changing: a
+/// session connecting or streaming, a transfer in progress
+const ACTIVE_STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often they refresh it while nothing happens
+const IDLE_STATUS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait before polling the AirPlay status again after seeing `status`
+fn airplay_poll_interval(status: &AirPlayStatus) -> Duration {
+    match status {
+        AirPlayStatus::Connecting | AirPlayStatus::Connected => ACTIVE_STATUS_POLL_INTERVAL,
+        AirPlayStatus::Idle | AirPlayStatus::Failed(_) => IDLE_STATUS_POLL_INTERVAL,
+    }
+}
+
+/// Wait before polling the AirDrop status again after seeing `status`
+fn airdrop_poll_interval(status: &AirDropStatus) -> Duration {
+    if status.connection == ConnectionState::Idle && status.transfer.is_none() {
+        IDLE_STATUS_POLL_INTERVAL
+    } else {
+        ACTIVE_STATUS_POLL_INTERVAL
+    }
+}
 
 #[derive(Clone, PartialEq)]
 #[allow(dead_code)]
@@ -22,14 +55,17 @@ enum SendOption {
 
 pub struct MainWindow {
     discovery: Arc<DeviceDiscovery>,
-    discovered_devices: Arc<Mutex<Vec<DiscoveredDevice>>>,
+    discovered_devices: Arc<watch::Sender<Vec<DiscoveredDevice>>>,
     airdrop: Arc<Mutex<AirDrop>>,
     airplay: Arc<AirPlay>,
     awdl_manager: Arc<Mutex<AwdlManager>>,
-    is_receiving_screen: Arc<Mutex<bool>>,
-    current_frame_info: Arc<Mutex<Option<(u32, u32, u64)>>>,
-    cached_status: Arc<Mutex<AirPlayStatus>>,
-    is_scanning: Arc<Mutex<bool>>,
+    is_receiving_screen: Arc<watch::Sender<bool>>,
+    current_frame_info: Arc<watch::Sender<Option<(u32, u32, u64)>>>,
+    /// Last AirPlay status seen by the poll task
+    cached_status: watch::Receiver<AirPlayStatus>,
+    /// Last AirDrop status seen by the poll task
+    cached_airdrop_status: watch::Receiver<AirDropStatus>,
+    is_scanning: Arc<watch::Sender<bool>>,
     style: StyleConfig,
     url_to_send: String,
     show_link_dialog: bool,
@@ -43,24 +79,39 @@ impl MainWindow {
         airplay: Arc<AirPlay>,
         awdl_manager: Arc<Mutex<AwdlManager>>,
     ) -> Self {
-        let discovered_devices = Arc::new(Mutex::new(Vec::new()));
-        let is_receiving_screen = Arc::new(Mutex::new(false));
-        let current_frame_info = Arc::new(Mutex::new(None));
-        let cached_status = Arc::new(Mutex::new(AirPlayStatus::Idle));
-        let is_scanning = Arc::new(Mutex::new(false));
-        
-        // Start status update task
-        let status_update = cached_status.clone();
+        let discovered_devices = Arc::new(watch::channel(Vec::new()).0);
+        let is_receiving_screen = Arc::new(watch::channel(false).0);
+        let current_frame_info = Arc::new(watch::channel(None).0);
+        let is_scanning = Arc::new(watch::channel(false).0);
+
+        // Status poll tasks: they wait for the locks, the UI never does
+        let (status_update, cached_status) = watch::channel(AirPlayStatus::Idle);
         let airplay_clone = airplay.clone();
         tokio::spawn(async move {
             loop {
                 let status = airplay_clone.get_status().await;
-                if let Ok(mut current) = status_update.try_lock() {
-                    if *current != status {
-                        *current = status;
-                    }
-                }
-                tokio::time::sleep(Duration::from_millis(100)).await;
+                let interval = airplay_poll_interval(&status);
+                status_update.send_if_modified(|current| {
+                    let changed = *current != status;
+                    *current = status;
+                    changed
+                });
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        let (airdrop_update, cached_airdrop_status) = watch::channel(AirDropStatus::Idle);
+        let airdrop_clone = airdrop.clone();
+        tokio::spawn(async move {
+            loop {
+                let status = {
+                    let airdrop = airdrop_clone.lock().await;
+                    let status = airdrop.status.lock().await.clone();
+                    status
+                };
+                let interval = airdrop_poll_interval(&status);
+                airdrop_update.send_replace(status);
+                tokio::time::sleep(interval).await;
             }
         });
 
@@ -73,6 +124,7 @@ impl MainWindow {
             is_receiving_screen,
             current_frame_info,
             cached_status,
+            cached_airdrop_status,
             is_scanning,
             style: StyleConfig::default(),
             url_to_send: String::new(),
@@ -92,14 +144,12 @@ impl MainWindow {
         }
 
         // Adjust repaint rate based on scanning status
-        if let Ok(scanning) = self.is_scanning.try_lock() {
-            let repaint_after = if *scanning {
-                Duration::from_millis(100)
-            } else {
-                Duration::from_millis(500)
-            };
-            ctx.request_repaint_after(repaint_after);
-        }
+        let repaint_after = if *self.is_scanning.borrow() {
+            Duration::from_millis(100)
+        } else {
+            Duration::from_millis(500)
+        };
+        ctx.request_repaint_after(repaint_after);
 
         // Draw main UI
         self.draw_main_panel(ctx);
@@ -112,8 +162,7 @@ impl MainWindow {
         
         tokio::spawn(async move {
             if let Ok(new_devices) = discovery.get_devices().await {
-                let mut devices = devices.lock().await;
-                *devices = new_devices;
+                devices.send_replace(new_devices);
                 ctx_clone.request_repaint();
             }
         });
@@ -126,12 +175,11 @@ impl MainWindow {
         let ctx_clone = ctx.clone();
         
         tokio::spawn(async move {
-            if let Ok(receiving) = is_receiving.try_lock() {
-                if *receiving {
-                    if let Some(info) = airplay.get_frame_info().await {
-                        *frame_info.lock().await = Some(info);
-                        ctx_clone.request_repaint();
-                    }
+            let receiving = *is_receiving.borrow();
+            if receiving {
+                if let Some(info) = airplay.get_frame_info().await {
+                    frame_info.send_replace(Some(info));
+                    ctx_clone.request_repaint();
                 }
             }
         });
@@ -179,24 +227,12 @@ impl MainWindow {
                 .color(self.style.text_color));
             ui.add_space(10.0);
 
-            // Clone devices in a separate scope to avoid holding an immutable borrow of `self`
-            let devices_clone_opt = {
-                let discovered = self.discovered_devices.clone();
-                // Ensure the temporary from try_lock() is dropped before the block ends
-                let tmp = if let Ok(devices) = discovered.try_lock() {
-                    Some(devices.clone())
-                } else {
-                    None
-                };
-                tmp
-            };
-
-            if let Some(devices_clone) = devices_clone_opt {
-                if devices_clone.is_empty() {
-                    self.draw_empty_state(ui);
-                } else {
-                    self.draw_device_list(ui, &devices_clone);
-                }
+            // Cloned so the watch borrow isn't held while drawing
+            let devices = self.discovered_devices.borrow().clone();
+            if devices.is_empty() {
+                self.draw_empty_state(ui);
+            } else {
+                self.draw_device_list(ui, &devices);
             }
 
             // Persist the action dialog when a device is selected
@@ -209,19 +245,29 @@ impl MainWindow {
     fn draw_empty_state(&mut self, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
             ui.add_space(40.0);
-            if let Ok(scanning) = self.is_scanning.try_lock() {
-                if *scanning {
-                    ui.label(RichText::new("Scanning for devices...")
-                        .size(16.0)
-                        .color(self.style.text_color));
-                    ui.spinner();
-                } else {
-                    ui.label(RichText::new("No devices found")
+            let scanning = *self.is_scanning.borrow();
+            if scanning {
+                ui.label(RichText::new("Scanning for devices...")
+                    .size(16.0)
+                    .color(self.style.text_color));
+                ui.spinner();
+            } else {
+                ui.label(RichText::new("No devices found")
+                    .size(16.0)
+                    .color(self.style.text_color));
+                ui.add_space(10.0);
+                let retry_button = egui::Button::new(
+                    RichText::new("🔄 Scan again")
                         .size(16.0)
-                        .color(self.style.text_color));
-                    ui.label(RichText::new("Click 'Scan for Devices' to search")
-                        .size(14.0)
-                        .color(self.style.text_color.linear_multiply(0.7)));
+                        .color(Color32::WHITE)
+                )
+                .min_size(egui::vec2(140.0, 36.0))
+                .fill(self.style.primary_color)
+                .rounding(6.0);
+
+                if ui.add(retry_button).clicked() {
+                    let ctx = ui.ctx().clone();
+                    self.handle_scan_click(&ctx);
                 }
             }
             ui.add_space(40.0);
@@ -282,24 +328,11 @@ impl MainWindow {
     fn get_device_status(&self, device: &DiscoveredDevice) -> DeviceStatus {
         match device.service_type {
             crate::network::discovery::ServiceType::AirDrop => {
-                // Avoid blocking within a runtime; read cached status via try_lock
-                if let Ok(airdrop) = self.airdrop.try_lock() {
-                    if let Ok(status) = airdrop.status.try_lock() {
-                        DeviceStatus::AirDrop(status.clone())
-                    } else {
-                        DeviceStatus::AirDrop(AirDropStatus::Idle)
-                    }
-                } else {
-                    DeviceStatus::AirDrop(AirDropStatus::Idle)
-                }
+                DeviceStatus::AirDrop(self.cached_airdrop_status.borrow().clone())
             },
             crate::network::discovery::ServiceType::AirPlay |
             crate::network::discovery::ServiceType::Companion => {
-                if let Ok(status) = self.cached_status.try_lock() {
-                    DeviceStatus::AirPlay(status.clone())
-                } else {
-                    DeviceStatus::AirPlay(AirPlayStatus::Idle)
-                }
+                DeviceStatus::AirPlay(self.cached_status.borrow().clone())
             },
             _ => DeviceStatus::None,
         }
@@ -311,20 +344,14 @@ impl MainWindow {
         let is_scanning = self.is_scanning.clone();
         let ctx_clone = ctx.clone();
         
-        if let Ok(mut scanning) = is_scanning.try_lock() {
-            *scanning = true;
-        }
+        is_scanning.send_replace(true);
         ctx_clone.request_repaint();
         
         tokio::spawn(async move {
             if let Ok(new_devices) = discovery.get_devices().await {
-                if let Ok(mut devices) = devices.try_lock() {
-                    *devices = new_devices;
-                }
-            }
-            if let Ok(mut scanning) = is_scanning.try_lock() {
-                *scanning = false;
+                devices.send_replace(new_devices);
             }
+            is_scanning.send_replace(false);
             ctx_clone.request_repaint();
         });
     }
@@ -452,58 +479,38 @@ impl MainWindow {
             let airdrop = self.airdrop.clone();
             // Use AirDrop standard port for AirDrop/Companion services
             let port = match device.service_type {
-                ServiceType::AirDrop | ServiceType::Companion => 8771,
+                ServiceType::AirDrop | ServiceType::Companion => crate::protocols::airdrop::AIRDROP_HTTPS_PORT,
                 _ => device.port,
             };
             let addr = SocketAddr::new(device.address, port);
 
-            // Clone AirDrop instance without holding the lock across .await
-            let ad_opt = match airdrop.try_lock() {
-                Ok(guard) => Some(guard.clone()),
-                Err(_) => None,
-            };
-
             tokio::spawn(async move {
-                if let Some(ad) = ad_opt {
-                    if let Err(e) = ad.send_file_to(addr, PathBuf::from(path)).await {
-                        error!("Failed to send file to {}: {}", addr, e);
-                    }
-                } else {
-                    warn!("AirDrop busy; could not acquire lock to send file");
+                // Waits for the lock here rather than failing on the UI thread,
+                // and releases it before the transfer starts
+                let ad = airdrop.lock().await.clone();
+                if let Err(e) = ad.send_file_to(addr, PathBuf::from(path)).await {
+                    error!("Failed to send file to {}: {}", addr, e);
                 }
             });
         }
     }
     
     fn send_link_to_device(&self, device: DiscoveredDevice, url: String) {
-        // Create a temporary text file with the URL
-        let temp_path = std::env::temp_dir().join("airwin_link.url");
-        let url_content = format!("[InternetShortcut]\nURL={}", url);
-        
-        if std::fs::write(&temp_path, url_content.as_bytes()).is_ok() {
-            let airdrop = self.airdrop.clone();
-            // Use AirDrop standard port for AirDrop/Companion services
-            let port = match device.service_type {
-                ServiceType::AirDrop | ServiceType::Companion => 8771,
-                _ => device.port,
-            };
-            let addr = SocketAddr::new(device.address, port);
-
-            // Clone AirDrop instance without holding the lock across .await
-            let ad_opt = match airdrop.try_lock() {
-                Ok(guard) => Some(guard.clone()),
-                Err(_) => None,
-            };
+        let airdrop = self.airdrop.clone();
+        // Use AirDrop standard port for AirDrop/Companion services
+        let port = match device.service_type {
+            ServiceType::AirDrop | ServiceType::Companion => crate::protocols::airdrop::AIRDROP_HTTPS_PORT,
+            _ => device.port,
+        };
+        let addr = SocketAddr::new(device.address, port);
+        let encoding = crate::protocols::airdrop::LinkEncoding::for_recipient(&device.txt_records);
 
-            tokio::spawn(async move {
-                if let Some(ad) = ad_opt {
-                    if let Err(e) = ad.send_file_to(addr, temp_path).await {
-                        error!("Failed to send link file to {}: {}", addr, e);
-                    }
-                } else {
-                    warn!("AirDrop busy; could not acquire lock to send link");
-                }
-            });
-        }
+        tokio::spawn(async move {
+            let ad = airdrop.lock().await.clone();
+            // The temporary link file is removed by send_link_to
+            if let Err(e) = ad.send_link_to(addr, &url, encoding).await {
+                error!("Failed to send link to {}: {}", addr, e);
+            }
+        });
     }
 }
@@ -0,0 +1,134 @@
+//! Compressione di cartelle per l'invio tramite AirDrop
+//!
+//! AirDrop trasferisce un singolo file per volta: le cartelle vengono
+//! compresse al volo in un archivio zip temporaneo con il nome della cartella,
+//! salvato nella cache di AirWin.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Comprime `dir` in `<out_dir>/airwin_<uuid>/<nome cartella>.zip`
+///
+/// I file vengono copiati nell'archivio a blocchi, senza caricarli in memoria.
+pub fn zip_directory(dir: &Path, out_dir: &Path) -> Result<PathBuf> {
+    if !dir.is_dir() {
+        return Err(anyhow!("{:?} is not a directory", dir));
+    }
+
+    let folder_name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "cartella".to_string());
+
+    let staging = out_dir.join(format!("airwin_{}", Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&staging)
+        .with_context(|| format!("Failed to create staging directory {:?}", staging))?;
+
+    let archive_path = staging.join(format!("{}.zip", folder_name));
+    let file = File::create(&archive_path)
+        .with_context(|| format!("Failed to create archive {:?}", archive_path))?;
+
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    if let Err(e) = add_directory(&mut zip, dir, dir, options) {
+        drop(zip);
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let mut writer = zip.finish()?;
+    writer.flush()?;
+
+    debug!("Archived {:?} into {:?}", dir, archive_path);
+    Ok(archive_path)
+}
+
+/// Rimuove un archivio creato da [`zip_directory`] insieme alla sua cartella temporanea
+pub async fn remove_archive(archive: &Path) {
+    let target = archive.parent().unwrap_or(archive);
+    if let Err(e) = tokio::fs::remove_dir_all(target).await {
+        warn!("Failed to remove temporary archive {:?}: {}", archive, e);
+    }
+}
+
+fn add_directory<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    root: &Path,
+    dir: &Path,
+    options: FileOptions,
+) -> Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = archive_name(root, &path)?;
+
+        if path.is_dir() {
+            zip.add_directory(format!("{}/", name), options)?;
+            add_directory(zip, root, &path, options)?;
+        } else {
+            zip.start_file(name, options)?;
+            let mut source = File::open(&path)
+                .with_context(|| format!("Failed to open {:?}", path))?;
+            std::io::copy(&mut source, zip)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Percorso relativo alla radice con separatori `/`, come richiesto dal formato zip
+fn archive_name(root: &Path, path: &Path) -> Result<String> {
+    let relative = path.strip_prefix(root)?;
+    Ok(relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_zip_directory_tree() {
+        let root = std::env::temp_dir().join(format!("airwin_test_{}", Uuid::new_v4().simple()));
+        let folder = root.join("Foto");
+        std::fs::create_dir_all(folder.join("vacanze")).unwrap();
+        std::fs::write(folder.join("note.txt"), b"ciao").unwrap();
+        std::fs::write(folder.join("vacanze").join("mare.txt"), b"spiaggia").unwrap();
+
+        let archive = zip_directory(&folder, &root).unwrap();
+        assert_eq!(archive.file_name().unwrap(), "Foto.zip");
+
+        let mut zip = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["note.txt", "vacanze/", "vacanze/mare.txt"]);
+
+        let mut contents = String::new();
+        zip.by_name("vacanze/mare.txt").unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "spiaggia");
+
+        assert_eq!(archive.parent().unwrap().parent().unwrap(), root);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_zip_directory_rejects_files() {
+        let file = std::env::temp_dir().join(format!("airwin_test_{}.txt", Uuid::new_v4().simple()));
+        std::fs::write(&file, b"x").unwrap();
+        assert!(zip_directory(&file, &std::env::temp_dir()).is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
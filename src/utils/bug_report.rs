@@ -0,0 +1,299 @@
+//! Diagnostica da allegare alle segnalazioni
+//!
+//! Raccoglie in un unico testo markdown versione, sistema, privilegi, porte,
+//! interfacce di rete, Bluetooth, AWDL e gli ultimi errori del registro eventi,
+//! così ogni segnalazione contiene le stesse informazioni. Il percorso della
+//! cartella utente viene sempre sostituito e gli indirizzi MAC sono mascherati,
+//! salvo scelta esplicita dell'utente.
+
+use super::config::Ports;
+use super::diagnostics::Diagnostics;
+use super::event_log::{LogEvent, Severity};
+use super::firewall::{self, FirewallStatus};
+use super::poll_rate;
+use super::self_test::probe_bind;
+use crate::protocols::airplay_stats::AirPlayStats;
+use crate::protocols::awdl::{AwdlAvailability, AwdlUtils};
+
+/// Righe del registro eventi incluse nel report
+pub const MAX_LOG_LINES: usize = 20;
+
+/// Stato dell'applicazione da includere nel report
+#[derive(Debug, Clone)]
+pub struct BugReportInput {
+    pub diagnostics: Diagnostics,
+    /// Porte già aperte da AirWin, che non possono essere verificate
+    pub own_ports: Vec<u16>,
+    pub awdl: AwdlAvailability,
+    /// Indirizzo MAC dell'interfaccia AWDL, se noto
+    pub awdl_mac: Option<[u8; 6]>,
+    /// Statistiche dell'ultima sessione AirPlay
+    pub airplay: AirPlayStats,
+    /// Ultimi avvisi ed errori, dal più vecchio
+    pub recent_events: Vec<LogEvent>,
+}
+
+/// Dati raccolti per il report
+#[derive(Debug, Clone, PartialEq)]
+pub struct BugReport {
+    pub version: String,
+    pub os: String,
+    pub diagnostics: Diagnostics,
+    /// Nome, porta ed esito del bind
+    pub ports: Vec<(String, u16, String)>,
+    /// Nome e indirizzo delle interfacce di rete
+    pub interfaces: Vec<(String, String)>,
+    /// Adattatori Bluetooth trovati, o l'errore
+    pub ble_adapters: Result<usize, String>,
+    /// Regole di Windows Firewall per le porte in uso
+    pub firewall: FirewallStatus,
+    pub awdl: AwdlAvailability,
+    pub awdl_mac: Option<[u8; 6]>,
+    pub airplay: AirPlayStats,
+    /// Cicli al secondo dei poller in background
+    pub poll_rates: Vec<(String, f64)>,
+    pub recent_log: Vec<String>,
+}
+
+/// Raccoglie i dati del report
+pub async fn collect(input: BugReportInput) -> BugReport {
+    let ports = input.diagnostics.ports;
+    BugReport {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        ports: port_results(ports, &input.own_ports),
+        diagnostics: input.diagnostics,
+        interfaces: local_ip_address::list_afinet_netifas()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, ip)| (name, ip.to_string()))
+            .collect(),
+        ble_adapters: crate::network::ble::adapter_count().await.map_err(|e| e.to_string()),
+        firewall: firewall::check(ports).await,
+        awdl: input.awdl,
+        awdl_mac: input.awdl_mac,
+        airplay: input.airplay,
+        poll_rates: poll_rate::snapshot()
+            .into_iter()
+            .map(|(name, rate)| (name.to_string(), rate))
+            .collect(),
+        recent_log: recent_problems(&input.recent_events),
+    }
+}
+
+fn port_results(ports: Ports, own_ports: &[u16]) -> Vec<(String, u16, String)> {
+    ports
+        .named()
+        .iter()
+        .map(|(name, port)| {
+            let result = if own_ports.contains(port) {
+                "in uso da AirWin".to_string()
+            } else {
+                match probe_bind(*port) {
+                    Ok(()) => "libera".to_string(),
+                    Err(e) => format!("non disponibile: {}", e),
+                }
+            };
+            (name.to_string(), *port, result)
+        })
+        .collect()
+}
+
+/// Ultimi avvisi ed errori, al massimo [`MAX_LOG_LINES`]
+fn recent_problems(events: &[LogEvent]) -> Vec<String> {
+    let problems: Vec<_> = events
+        .iter()
+        .filter(|event| event.severity >= Severity::Warning)
+        .map(LogEvent::to_line)
+        .collect();
+    problems[problems.len().saturating_sub(MAX_LOG_LINES)..].to_vec()
+}
+
+impl BugReport {
+    /// Report markdown da incollare in una segnalazione
+    pub fn to_markdown(&self, full_macs: bool) -> String {
+        let mut lines = vec![
+            "## Diagnostica AirWin".to_string(),
+            String::new(),
+            format!("- **Versione:** {}", self.version),
+            format!("- **Sistema:** {}", self.os),
+            format!(
+                "- **Privilegi:** {}",
+                if self.diagnostics.elevated { "amministratore" } else { "utente standard" }
+            ),
+            format!(
+                "- **Bluetooth:** {}",
+                match &self.ble_adapters {
+                    Ok(count) => format!("{} adattatori", count),
+                    Err(e) => format!("non disponibile ({})", e),
+                }
+            ),
+            format!("- **Firewall:** {}", self.firewall.describe()),
+            match self.awdl_mac {
+                Some(mac) => format!("- **AWDL:** {:?} (MAC {})", self.awdl, AwdlUtils::format_mac_address(&mac)),
+                None => format!("- **AWDL:** {:?}", self.awdl),
+            },
+            String::new(),
+            "### Porte".to_string(),
+            String::new(),
+        ];
+        lines.extend(self.ports.iter().map(|(name, port, result)| format!("- {} {}: {}", name, port, result)));
+
+        if !self.diagnostics.limitations.is_empty() {
+            lines.extend([String::new(), "### Limitazioni".to_string(), String::new()]);
+            lines.extend(self.diagnostics.limitations.iter().map(|limitation| format!("- {}", limitation)));
+        }
+
+        lines.extend([String::new(), "### Interfacce di rete".to_string(), String::new()]);
+        lines.extend(self.interfaces.iter().map(|(name, ip)| format!("- {}: {}", name, ip)));
+
+        if self.airplay.frames_sent > 0 {
+            lines.extend([String::new(), "### AirPlay".to_string(), String::new()]);
+            lines.extend(airplay_lines(&self.airplay));
+        }
+
+        if !self.poll_rates.is_empty() {
+            lines.extend([String::new(), "### Polling".to_string(), String::new()]);
+            lines.extend(self.poll_rates.iter().map(|(name, rate)| format!("- {}: {:.1}/s", name, rate)));
+        }
+
+        lines.extend([String::new(), "### Ultimi avvisi ed errori".to_string(), String::new()]);
+        if self.recent_log.is_empty() {
+            lines.push("Nessuno".to_string());
+        } else {
+            lines.push("```".to_string());
+            lines.extend(self.recent_log.iter().cloned());
+            lines.push("```".to_string());
+        }
+
+        scrub(&lines.join("\n"), full_macs)
+    }
+}
+
+/// Contatori della sessione AirPlay, una voce per riga
+pub fn airplay_lines(stats: &AirPlayStats) -> Vec<String> {
+    vec![
+        format!("- Frame inviati: {}", stats.frames_sent),
+        format!("- Byte inviati: {}", stats.bytes_sent),
+        format!("- Frame scartati: {}", stats.frames_dropped),
+        format!("- Frequenza: {:.1} fps", stats.current_fps),
+        format!("- Codifica media: {:.1} ms", stats.avg_encode_ms),
+    ]
+}
+
+/// Rimuove i dati personali: cartella utente e, se richiesto, la parte
+/// finale degli indirizzi MAC
+pub fn scrub(text: &str, full_macs: bool) -> String {
+    let mut text = text.to_string();
+    if let Some(home) = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME")) {
+        let home = home.to_string_lossy();
+        if home.len() > 1 {
+            text = text.replace(home.as_ref(), "~");
+        }
+    }
+    if full_macs {
+        text
+    } else {
+        mask_macs(&text)
+    }
+}
+
+/// Lunghezza di un indirizzo MAC in forma testuale, `AA:BB:CC:DD:EE:FF`
+const MAC_LEN: usize = 17;
+
+/// Maschera gli ultimi tre byte degli indirizzi MAC, lasciando il produttore
+fn mask_macs(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut masked = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if i + MAC_LEN <= bytes.len() && is_mac(&bytes[i..i + MAC_LEN]) {
+            let separator = bytes[i + 2] as char;
+            masked.push_str(&text[i..i + 9]);
+            masked.push_str(&["**"; 3].join(&separator.to_string()));
+            i += MAC_LEN;
+        } else {
+            let ch = text[i..].chars().next().unwrap_or_default();
+            masked.push(ch);
+            i += ch.len_utf8().max(1);
+        }
+    }
+    masked
+}
+
+fn is_mac(candidate: &[u8]) -> bool {
+    let separator = candidate[2];
+    (separator == b':' || separator == b'-')
+        && candidate.iter().enumerate().all(|(i, byte)| {
+            if i % 3 == 2 {
+                *byte == separator
+            } else {
+                byte.is_ascii_hexdigit()
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(severity: Severity, message: &str) -> LogEvent {
+        LogEvent {
+            timestamp: chrono::Local::now(),
+            severity,
+            source: "Test",
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_macs_are_masked_unless_requested() {
+        let text = "BLE a4:83:e7:12:34:56 e adattatore A4-83-E7-12-34-56";
+        assert_eq!(scrub(text, false), "BLE a4:83:e7:**:**:** e adattatore A4-83-E7-**-**-**");
+        assert_eq!(scrub(text, true), text);
+        // Non sono indirizzi MAC
+        assert_eq!(mask_macs("12:30:45 ok"), "12:30:45 ok");
+        assert_eq!(mask_macs("città 10:20"), "città 10:20");
+    }
+
+    #[test]
+    fn test_recent_problems_keep_only_latest_warnings() {
+        let mut events = vec![event(Severity::Info, "avvio")];
+        events.extend((0..MAX_LOG_LINES + 5).map(|i| event(Severity::Error, &format!("errore {}", i))));
+
+        let problems = recent_problems(&events);
+        assert_eq!(problems.len(), MAX_LOG_LINES);
+        assert!(problems[0].ends_with("errore 5"));
+        assert!(problems.iter().all(|line| !line.contains("avvio")));
+    }
+
+    #[test]
+    fn test_markdown_has_all_sections() {
+        let report = BugReport {
+            version: "0.1.0".to_string(),
+            os: "windows x86_64".to_string(),
+            diagnostics: Diagnostics::default(),
+            ports: vec![("AirDrop HTTPS".to_string(), 8771, "libera".to_string())],
+            interfaces: vec![("Wi-Fi".to_string(), "192.168.1.10".to_string())],
+            ble_adapters: Ok(1),
+            firewall: FirewallStatus::Missing(vec![5353]),
+            awdl: AwdlAvailability::On,
+            awdl_mac: Some([0xa4, 0x5e, 0x60, 0xd1, 0x0b, 0xee]),
+            airplay: AirPlayStats { frames_sent: 300, bytes_sent: 1_500_000, ..Default::default() },
+            poll_rates: vec![("AirPlay status".to_string(), 1.0)],
+            recent_log: vec!["[ERROR] BLE: adattatore 00:11:22:33:44:55 perso".to_string()],
+        };
+
+        let markdown = report.to_markdown(false);
+        for section in ["## Diagnostica AirWin", "### Porte", "### AirPlay", "### Interfacce di rete", "### Ultimi avvisi ed errori"] {
+            assert!(markdown.contains(section), "{} mancante", section);
+        }
+        assert!(markdown.contains("- AirDrop HTTPS 8771: libera"));
+        assert!(markdown.contains("- AirPlay status: 1.0/s"));
+        assert!(markdown.contains("- Frame inviati: 300"));
+        assert!(markdown.contains("- **Firewall:** porte bloccate: 5353"));
+        assert!(markdown.contains("00:11:22:**:**:**"));
+        assert!(markdown.contains("- **AWDL:** On (MAC a4:5e:60:**:**:**)"));
+        assert!(report.to_markdown(true).contains("(MAC a4:5e:60:d1:0b:ee)"));
+    }
+}
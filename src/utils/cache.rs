@@ -0,0 +1,185 @@
+//! Cartella della cache di AirWin
+//!
+//! I file ricevuti e gli archivi delle cartelle da inviare vengono salvati in
+//! sottocartelle di un'unica cartella, configurabile nelle impostazioni.
+//! La pulizia rimuove solo queste sottocartelle: se la cache punta a una
+//! cartella dell'utente (es. Download) il resto del contenuto non viene toccato.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::config::Settings;
+
+/// Sottocartella dei file ricevuti
+const RECEIVED_DIR: &str = "received";
+/// Sottocartella degli archivi zip delle cartelle da inviare
+const ARCHIVES_DIR: &str = "archives";
+
+/// Sottocartelle create e gestite da AirWin
+const MANAGED_DIRS: [&str; 2] = [RECEIVED_DIR, ARCHIVES_DIR];
+
+/// Cartella della cache e delle sue sottocartelle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+impl Cache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Cache configurata nelle impostazioni, o quella predefinita
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.cache_dir.clone().unwrap_or_else(default_dir))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Cartella in cui salvare i file ricevuti
+    pub fn received_dir(&self) -> PathBuf {
+        self.root.join(RECEIVED_DIR)
+    }
+
+    /// Cartella degli archivi temporanei delle cartelle da inviare
+    pub fn archives_dir(&self) -> PathBuf {
+        self.root.join(ARCHIVES_DIR)
+    }
+
+    /// Crea le sottocartelle mancanti
+    pub fn ensure(&self) -> io::Result<()> {
+        for dir in MANAGED_DIRS {
+            std::fs::create_dir_all(self.root.join(dir))?;
+        }
+        Ok(())
+    }
+
+    /// Spazio occupato in byte
+    pub fn size(&self) -> u64 {
+        MANAGED_DIRS.iter().map(|dir| dir_size(&self.root.join(dir))).sum()
+    }
+
+    /// Svuota la cache e restituisce i byte liberati.
+    ///
+    /// Le voci che sono o contengono uno dei percorsi `protected` non vengono
+    /// eliminate, così una cartella scelta dall'utente resta intatta anche se
+    /// si trova dentro la cache.
+    pub fn clear(&self, protected: &[PathBuf]) -> u64 {
+        let mut freed = 0;
+        for dir in MANAGED_DIRS {
+            let Ok(entries) = std::fs::read_dir(self.root.join(dir)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if protected.iter().any(|kept| kept.starts_with(&path)) {
+                    continue;
+                }
+                let size = dir_size(&path);
+                let removed = if path.is_dir() {
+                    std::fs::remove_dir_all(&path)
+                } else {
+                    std::fs::remove_file(&path)
+                };
+                match removed {
+                    Ok(()) => freed += size,
+                    Err(e) => warn!("Failed to remove cached {:?}: {}", path, e),
+                }
+            }
+        }
+        freed
+    }
+}
+
+/// Cartella predefinita, in `%LOCALAPPDATA%\AirWin\Cache` su Windows
+pub fn default_dir() -> PathBuf {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("AirWin")
+        .join("Cache")
+}
+
+/// Cartelle dell'utente da non eliminare mai durante la pulizia
+pub fn protected_dirs() -> Vec<PathBuf> {
+    let Some(home) = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME")) else {
+        return Vec::new();
+    };
+    let home = PathBuf::from(home);
+    let mut dirs: Vec<_> = ["Downloads", "Desktop", "Documents", "Pictures"]
+        .iter()
+        .map(|name| home.join(name))
+        .collect();
+    dirs.push(home);
+    dirs
+}
+
+/// Dimensione di un file o, ricorsivamente, di una cartella
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_cache() -> Cache {
+        let cache = Cache::new(std::env::temp_dir().join(format!("airwin_cache_{}", Uuid::new_v4().simple())));
+        cache.ensure().unwrap();
+        cache
+    }
+
+    #[test]
+    fn test_size_counts_nested_files() {
+        let cache = temp_cache();
+        std::fs::write(cache.received_dir().join("foto.jpg"), [0u8; 100]).unwrap();
+        let staging = cache.archives_dir().join("airwin_1");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("Foto.zip"), [0u8; 50]).unwrap();
+        // Fuori dalle sottocartelle gestite: non fa parte della cache
+        std::fs::write(cache.root().join("altro.txt"), [0u8; 25]).unwrap();
+
+        assert_eq!(cache.size(), 150);
+        std::fs::remove_dir_all(cache.root()).unwrap();
+    }
+
+    #[test]
+    fn test_clear_reports_freed_bytes_and_keeps_other_files() {
+        let cache = temp_cache();
+        std::fs::write(cache.received_dir().join("foto.jpg"), [0u8; 100]).unwrap();
+        std::fs::write(cache.archives_dir().join("Foto.zip"), [0u8; 50]).unwrap();
+        let unrelated = cache.root().join("altro.txt");
+        std::fs::write(&unrelated, [0u8; 25]).unwrap();
+
+        assert_eq!(cache.clear(&[]), 150);
+        assert_eq!(cache.size(), 0);
+        assert!(unrelated.exists());
+        assert!(cache.received_dir().is_dir());
+        std::fs::remove_dir_all(cache.root()).unwrap();
+    }
+
+    #[test]
+    fn test_clear_skips_protected_dirs() {
+        let cache = temp_cache();
+        let downloads = cache.received_dir().join("Download");
+        std::fs::create_dir_all(&downloads).unwrap();
+        std::fs::write(downloads.join("tesi.pdf"), [0u8; 10]).unwrap();
+        std::fs::write(cache.received_dir().join("foto.jpg"), [0u8; 100]).unwrap();
+
+        assert_eq!(cache.clear(&[downloads.clone()]), 100);
+        assert!(downloads.join("tesi.pdf").exists());
+        std::fs::remove_dir_all(cache.root()).unwrap();
+    }
+}
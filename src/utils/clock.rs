@@ -0,0 +1,75 @@
+//! Orologio iniettabile
+//!
+//! Scadenza dei peer, tempo rimanente dei trasferimenti e orari della
+//! cronologia leggono l'ora da un [`Clock`] invece che da `Utc::now()`, così
+//! i test possono spostare il tempo in avanti con [`FakeClock`].
+
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Sorgente dell'ora corrente
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Orologio condiviso tra servizi e interfaccia
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Ora di sistema
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Orologio di sistema condiviso
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+/// Orologio fermo che avanza solo quando richiesto
+#[cfg(test)]
+#[derive(Debug)]
+pub struct FakeClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(start: DateTime<Utc>) -> Arc<Self> {
+        Arc::new(Self { now: std::sync::Mutex::new(start) })
+    }
+
+    /// Sposta l'ora in avanti di `by`
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.now.lock().unwrap() += chrono::Duration::from_std(by).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fake_clock_moves_only_when_advanced() {
+        let start = "2024-01-01T00:00:00Z".parse().unwrap();
+        let clock = FakeClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(90));
+    }
+}
@@ -0,0 +1,808 @@
+//! Impostazioni persistenti dell'applicazione
+//!
+//! Le impostazioni vengono salvate in formato JSON nella cartella di
+//! configurazione di AirWin (`%APPDATA%\AirWin` su Windows).
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::network::ip_mode::IpMode;
+use crate::protocols::adaptive_quality::QualityTier;
+use crate::protocols::airplay::SocketTuning;
+use crate::protocols::apple_records::{sanitize_name, MAX_NAME_LEN, MAX_SUFFIX_LEN};
+use crate::protocols::compression::CompressionMode;
+use crate::protocols::connection_pool::AfterTransfer;
+use crate::protocols::file_conflict::FileConflictPolicy;
+use crate::protocols::http_server::ConnectionLimits;
+use crate::protocols::peer_filter::PeerFilter;
+use crate::protocols::screen_capture::CaptureRegion;
+use crate::protocols::tls::TlsBackend;
+use crate::protocols::transfer_manager::TransferLimits;
+use crate::protocols::transfer_method::{MethodSelector, TransferMethod, DEFAULT_FAILURES_BEFORE_FALLBACK};
+use crate::protocols::visibility::{TrustedDevice, VisibilitySettings};
+use crate::ui::styles::font_size::{DEFAULT_SCALE_PERCENT, MAX_SCALE_PERCENT, MIN_SCALE_PERCENT};
+use crate::ui::Theme;
+use crate::utils::open_file::CompletionAction;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Versione del formato dei file esportati. I file senza versione sono
+/// copie di `settings.json` e vengono importati come versione 0.
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
+/// Notifiche visibili contemporaneamente
+pub const DEFAULT_MAX_NOTIFICATIONS: u32 = 5;
+
+/// Dove vengono mostrate le notifiche
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationBackendKind {
+    /// Dentro la finestra di AirWin
+    #[default]
+    InApp,
+    /// Notifiche di sistema, visibili anche con la finestra ridotta a icona
+    System,
+}
+
+/// Intervallo minimo tra due letture dei dispositivi Bluetooth
+const MIN_BLE_POLL_INTERVAL_MS: u64 = 250;
+
+/// Impostazioni salvate tra un avvio e l'altro
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Avvia l'interfaccia in modalità compatta
+    pub mini_mode: bool,
+    /// Mantieni la finestra compatta sopra le altre
+    pub mini_always_on_top: bool,
+    /// Tema dell'interfaccia
+    pub theme: Theme,
+    /// Scala del testo in percentuale, per chi vede poco
+    pub font_scale_percent: u32,
+    /// Opzioni del traffico multicast mDNS
+    pub multicast: MulticastSettings,
+    /// Versioni di IP usate per ascoltare, per il multicast e per connettersi
+    pub ip_mode: IpMode,
+    /// Porta personalizzata del server AirPlay, `None` per quella predefinita
+    pub airplay_port: Option<u16>,
+    /// Livello di qualità dello streaming AirPlay
+    pub airplay_quality: QualityTier,
+    /// Area dello schermo trasmessa con AirPlay, `None` per lo schermo intero
+    pub capture_region: Option<CaptureRegion>,
+    /// Minuti senza attività dopo cui la trasmissione AirPlay si ferma, `None` per mai
+    pub airplay_idle_timeout_mins: Option<u32>,
+    /// Disegna il puntatore del mouse nei fotogrammi AirPlay
+    pub airplay_show_cursor: bool,
+    /// Opzioni TCP del ricevitore AirPlay (Nagle, buffer di invio, backlog)
+    pub airplay_socket: SocketTuning,
+    /// Avvia il server AirDrop e registra i servizi mDNS
+    pub airdrop_enabled: bool,
+    /// Avvia il server AirPlay
+    pub airplay_enabled: bool,
+    /// Mostra le notifiche nell'interfaccia
+    pub show_notifications: bool,
+    /// Notifiche visibili contemporaneamente, le più vecchie vengono chiuse
+    pub max_notifications: u32,
+    /// Notifiche nella finestra o di sistema
+    pub notification_backend: NotificationBackendKind,
+    /// Riproduci un suono al termine di un trasferimento
+    pub play_sound_on_complete: bool,
+    /// Cosa fare quando un file ricevuto ha lo stesso nome di uno esistente
+    pub file_conflict_policy: FileConflictPolicy,
+    /// Azione al termine di un trasferimento riuscito, per i file ricevuti e
+    /// come scelta iniziale per i link inviati. Disattivata per non aprire
+    /// file all'insaputa dell'utente.
+    pub completion_action: CompletionAction,
+    /// Dimensione massima in byte di un file ricevuto
+    pub max_incoming_file_size: u64,
+    /// KiB letti e scritti alla volta durante i trasferimenti
+    pub transfer_chunk_kb: u32,
+    /// Compressione dei file scambiati con altre istanze di AirWin
+    pub transfer_compression: CompressionMode,
+    /// Dispositivi (IP o nome) ammessi o bloccati in ricezione
+    pub peer_filter: PeerFilter,
+    /// Chi può inviare file e se i contatti sono accettati senza conferma
+    pub visibility: VisibilitySettings,
+    /// Dispositivi accettati senza conferma quando l'accettazione automatica è attiva
+    pub trusted_devices: Vec<TrustedDevice>,
+    /// Avvisa se il certificato di un dispositivo già noto cambia
+    pub pin_peer_certificates: bool,
+    /// Libreria TLS usata per inviare i file
+    pub tls_backend: TlsBackend,
+    /// API HTTP locale per l'automazione, disattivata per sicurezza
+    pub control_api: ControlApiSettings,
+    /// Secondi per cui un dispositivo non più trovato resta tra i "visti di recente"
+    pub recent_devices_expiry_secs: u64,
+    /// Cartella della cache (file ricevuti, archivi), `None` per quella predefinita
+    pub cache_dir: Option<PathBuf>,
+    /// Porte dei servizi locali
+    pub ports: Ports,
+    /// Secondi di silenzio dopo cui il server HTTPS AirDrop chiude una connessione
+    pub https_idle_timeout_secs: u64,
+    /// Secondi dopo cui il server HTTPS AirDrop chiude una connessione, anche se attiva
+    pub https_max_connection_secs: u64,
+    /// Millisecondi tra due letture dei dispositivi Bluetooth durante la scansione
+    pub ble_poll_interval_ms: u64,
+    /// Chiudendo la finestra la riduce a icona invece di uscire, `None` finché
+    /// l'utente non ha scelto
+    pub minimize_to_tray: Option<bool>,
+    /// Nome mostrato agli altri dispositivi, `None` per usare il nome del computer
+    pub friendly_name: Option<String>,
+    /// Testo aggiunto dopo il nome, per distinguere PC con lo stesso nome
+    pub name_suffix: Option<String>,
+    /// Aggiunge un numero al nome se un altro dispositivo lo annuncia già
+    pub avoid_name_collisions: bool,
+    /// Dopo un invio chiude la connessione o la tiene aperta per il successivo
+    pub after_transfer: AfterTransfer,
+    /// Invii eseguiti contemporaneamente verso tutti i dispositivi
+    pub max_concurrent_transfers: u32,
+    /// Invii eseguiti contemporaneamente verso lo stesso dispositivo
+    pub max_transfers_per_peer: u32,
+    /// Metodo di invio scelto dall'utente, `None` per sceglierlo automaticamente
+    pub transfer_method: Option<TransferMethod>,
+    /// Cartella condivisa (ad esempio SMB) usata quando l'invio diretto fallisce
+    pub shared_folder: Option<PathBuf>,
+    /// Invii diretti falliti verso un dispositivo prima di usare la cartella condivisa
+    pub shared_folder_after_failures: u32,
+    /// Configurazione iniziale completata. Un `settings.json` scritto prima
+    /// che esistesse viene da un'installazione già in uso e la salta.
+    #[serde(default = "existing_install")]
+    pub onboarding_complete: bool,
+}
+
+fn existing_install() -> bool {
+    true
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mini_mode: false,
+            mini_always_on_top: true,
+            theme: Theme::Dark,
+            font_scale_percent: DEFAULT_SCALE_PERCENT,
+            multicast: MulticastSettings::default(),
+            ip_mode: IpMode::Auto,
+            airplay_port: None,
+            airplay_quality: QualityTier::Auto,
+            capture_region: None,
+            airplay_show_cursor: true,
+            airplay_idle_timeout_mins: Some((crate::protocols::airplay::DEFAULT_IDLE_TIMEOUT.as_secs() / 60) as u32),
+            airplay_socket: SocketTuning::default(),
+            airdrop_enabled: true,
+            airplay_enabled: true,
+            show_notifications: true,
+            max_notifications: DEFAULT_MAX_NOTIFICATIONS,
+            notification_backend: NotificationBackendKind::InApp,
+            play_sound_on_complete: false,
+            file_conflict_policy: FileConflictPolicy::KeepBoth,
+            completion_action: CompletionAction::Nothing,
+            max_incoming_file_size: crate::protocols::airdrop::DEFAULT_MAX_FILE_SIZE,
+            transfer_chunk_kb: (crate::protocols::airdrop::DEFAULT_CHUNK_SIZE / 1024) as u32,
+            transfer_compression: CompressionMode::Off,
+            peer_filter: PeerFilter::default(),
+            visibility: VisibilitySettings::default(),
+            trusted_devices: Vec::new(),
+            pin_peer_certificates: false,
+            tls_backend: TlsBackend::NativeTls,
+            control_api: ControlApiSettings::default(),
+            recent_devices_expiry_secs: crate::network::device_cache::DEFAULT_RECENT_EXPIRY.as_secs(),
+            cache_dir: None,
+            ports: Ports::default(),
+            https_idle_timeout_secs: ConnectionLimits::default().idle_timeout.as_secs(),
+            https_max_connection_secs: ConnectionLimits::default().max_lifetime.as_secs(),
+            ble_poll_interval_ms: crate::network::ble::DEFAULT_POLL_INTERVAL.as_millis() as u64,
+            minimize_to_tray: None,
+            friendly_name: None,
+            name_suffix: None,
+            avoid_name_collisions: true,
+            after_transfer: AfterTransfer::Disconnect,
+            max_concurrent_transfers: TransferLimits::default().max_concurrent as u32,
+            max_transfers_per_peer: TransferLimits::default().max_per_peer as u32,
+            transfer_method: None,
+            shared_folder: None,
+            shared_folder_after_failures: DEFAULT_FAILURES_BEFORE_FALLBACK,
+            onboarding_complete: false,
+        }
+    }
+}
+
+/// Porte su cui AirWin ascolta e che annuncia via mDNS, modificabili per
+/// evitare conflitti con altri programmi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ports {
+    /// Server HTTPS AirDrop (`_airdrop._tcp`)
+    pub airdrop_https: u16,
+    /// Server TCP AirDrop di riserva
+    pub airdrop_fallback: u16,
+    /// Servizio Companion Link annunciato via mDNS
+    pub companion_link: u16,
+    /// Servizio Device Info annunciato via mDNS
+    pub device_info: u16,
+    /// Ricevitore AirPlay
+    pub airplay: u16,
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        Self {
+            airdrop_https: crate::protocols::airdrop::AIRDROP_HTTPS_PORT,
+            airdrop_fallback: crate::utils::privileges::AIRDROP_FALLBACK_PORT,
+            companion_link: crate::protocols::airdrop::COMPANION_LINK_PORT,
+            device_info: crate::protocols::airdrop::DEVICE_INFO_PORT,
+            airplay: crate::protocols::airplay::DEFAULT_AIRPLAY_PORT,
+        }
+    }
+}
+
+impl Ports {
+    /// Nome e porta di ogni servizio
+    pub fn named(&self) -> [(&'static str, u16); 5] {
+        [
+            ("AirDrop HTTPS", self.airdrop_https),
+            ("AirDrop di riserva", self.airdrop_fallback),
+            ("Companion Link", self.companion_link),
+            ("Device Info", self.device_info),
+            ("AirPlay", self.airplay),
+        ]
+    }
+
+    /// Verifica che nessuna porta sia 0 e che due servizi non condividano la stessa
+    pub fn validate(&self) -> Result<()> {
+        let named = self.named();
+        for (i, (name, port)) in named.iter().enumerate() {
+            if *port == 0 {
+                return Err(anyhow!("Port of {} must not be 0", name));
+            }
+            if let Some((other, _)) = named[..i].iter().find(|(_, other_port)| other_port == port) {
+                return Err(anyhow!("{} and {} both use port {}", other, name, port));
+            }
+        }
+        Ok(())
+    }
+
+    /// Servizio diverso da AirPlay che usa già `port`, se esiste
+    pub fn used_by_other_than_airplay(&self, port: u16) -> Option<&'static str> {
+        self.named()
+            .into_iter()
+            .find(|(name, used)| *used == port && *name != "AirPlay")
+            .map(|(name, _)| name)
+    }
+}
+
+/// Opzioni dell'API di controllo locale (vedi `network::control_api`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlApiSettings {
+    /// Avvia l'API su 127.0.0.1
+    pub enabled: bool,
+    /// Porta locale dell'API
+    pub port: u16,
+    /// Token richiesto in `Authorization: Bearer`, generato al primo avvio
+    pub token: Option<String>,
+}
+
+impl Default for ControlApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: crate::network::control_api::DEFAULT_PORT,
+            token: None,
+        }
+    }
+}
+
+impl ControlApiSettings {
+    /// Genera un token casuale se manca; restituisce `true` se è stato creato
+    pub fn ensure_token(&mut self) -> bool {
+        if self.token.as_deref().is_some_and(|token| !token.is_empty()) {
+            return false;
+        }
+        self.token = Some(uuid::Uuid::new_v4().simple().to_string());
+        true
+    }
+}
+
+/// Opzioni del traffico multicast mDNS, utili su reti con più interfacce
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MulticastSettings {
+    /// TTL dei pacchetti multicast (1..=255)
+    pub ttl: u32,
+    /// Nomi delle interfacce su cui unirsi al gruppo; vuoto = tutte
+    pub interfaces: Vec<String>,
+}
+
+impl Default for MulticastSettings {
+    fn default() -> Self {
+        Self {
+            ttl: 255,
+            interfaces: Vec::new(),
+        }
+    }
+}
+
+impl MulticastSettings {
+    /// Verifica che il TTL sia valido
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=255).contains(&self.ttl) {
+            return Err(anyhow!("Multicast TTL must be between 1 and 255, got {}", self.ttl));
+        }
+        Ok(())
+    }
+
+    /// Indica se l'interfaccia è ammessa dalla lista configurata
+    pub fn allows_interface(&self, name: &str) -> bool {
+        self.interfaces.is_empty()
+            || self.interfaces.iter().any(|allowed| allowed.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Settings {
+    /// Carica le impostazioni dal disco, usando i valori predefiniti in caso di errore
+    pub fn load() -> Self {
+        let path = settings_path();
+        let mut settings: Self = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Invalid settings file {:?}, using defaults: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+
+        if let Err(e) = settings.multicast.validate() {
+            warn!("{}, using default multicast settings", e);
+            settings.multicast = MulticastSettings::default();
+        }
+
+        // La porta AirPlay personalizzata sostituisce quella della mappa
+        let mut ports = settings.ports;
+        ports.airplay = settings.airplay_port.unwrap_or(ports.airplay);
+        if let Err(e) = ports.validate() {
+            warn!("{}, using default ports", e);
+            settings.ports = Ports::default();
+            settings.airplay_port = None;
+        }
+
+        if let Err(e) = validate_https_timeouts(settings.https_idle_timeout_secs, settings.https_max_connection_secs) {
+            warn!("{}, using the default HTTPS connection timeouts", e);
+            settings.https_idle_timeout_secs = Self::default().https_idle_timeout_secs;
+            settings.https_max_connection_secs = Self::default().https_max_connection_secs;
+        }
+
+        if let Err(e) = validate_font_scale(settings.font_scale_percent) {
+            warn!("{}, using the default", e);
+            settings.font_scale_percent = DEFAULT_SCALE_PERCENT;
+        }
+
+        if settings.ble_poll_interval_ms < MIN_BLE_POLL_INTERVAL_MS {
+            warn!(
+                "BLE poll interval of {} ms is below {} ms, using the default",
+                settings.ble_poll_interval_ms, MIN_BLE_POLL_INTERVAL_MS
+            );
+            settings.ble_poll_interval_ms = Self::default().ble_poll_interval_ms;
+        }
+
+        // Caratteri di controllo e spazi esterni non vengono mai annunciati
+        settings.friendly_name = settings.friendly_name.as_deref().and_then(sanitize_name);
+        if let Some(Err(e)) = settings.name_suffix.as_deref().map(validate_name_suffix) {
+            warn!("{}, advertising the name without a suffix", e);
+            settings.name_suffix = None;
+        }
+
+        if let Some(Err(e)) = settings.capture_region.map(|region| region.buffer_len()) {
+            warn!("{}, capturing the whole screen", e);
+            settings.capture_region = None;
+        }
+
+        if settings.airplay_idle_timeout_mins == Some(0) {
+            warn!("AirPlay idle timeout must be at least 1 minute, using the default");
+            settings.airplay_idle_timeout_mins = Self::default().airplay_idle_timeout_mins;
+        }
+
+        if let Err(e) = settings.airplay_socket.validate() {
+            warn!("{}, using the default AirPlay socket options", e);
+            settings.airplay_socket = SocketTuning::default();
+        }
+
+        if settings.max_notifications == 0 {
+            warn!("At least 1 notification must be shown, using the default");
+            settings.max_notifications = DEFAULT_MAX_NOTIFICATIONS;
+        }
+
+        if let Err(e) = validate_chunk_kb(settings.transfer_chunk_kb) {
+            warn!("{}, using the default", e);
+            settings.transfer_chunk_kb = Self::default().transfer_chunk_kb;
+        }
+
+        if settings.max_concurrent_transfers == 0 || settings.max_transfers_per_peer == 0 {
+            warn!("Transfer limits must be at least 1, using the defaults");
+            settings.max_concurrent_transfers = Self::default().max_concurrent_transfers;
+            settings.max_transfers_per_peer = Self::default().max_transfers_per_peer;
+        }
+
+        if settings.shared_folder_after_failures == 0 {
+            warn!("Shared folder fallback needs at least 1 failure, using the default");
+            settings.shared_folder_after_failures = DEFAULT_FAILURES_BEFORE_FALLBACK;
+        }
+
+        settings
+    }
+
+    /// Verifica i valori che `load` corregge in silenzio, per rifiutare un file importato
+    pub fn validate(&self) -> Result<()> {
+        self.multicast.validate()?;
+        let mut ports = self.ports;
+        ports.airplay = self.airplay_port.unwrap_or(ports.airplay);
+        ports.validate()?;
+        validate_https_timeouts(self.https_idle_timeout_secs, self.https_max_connection_secs)?;
+        validate_font_scale(self.font_scale_percent)?;
+        if self.ble_poll_interval_ms < MIN_BLE_POLL_INTERVAL_MS {
+            return Err(anyhow!(
+                "BLE poll interval must be at least {} ms, got {}",
+                MIN_BLE_POLL_INTERVAL_MS, self.ble_poll_interval_ms
+            ));
+        }
+        if let Some(name) = &self.friendly_name {
+            validate_friendly_name(name)?;
+        }
+        if let Some(suffix) = &self.name_suffix {
+            validate_name_suffix(suffix)?;
+        }
+        if let Some(region) = self.capture_region {
+            region.buffer_len()?;
+        }
+        if self.airplay_idle_timeout_mins == Some(0) {
+            return Err(anyhow!("AirPlay idle timeout must be at least 1 minute"));
+        }
+        self.airplay_socket.validate()?;
+        if self.max_notifications == 0 {
+            return Err(anyhow!("At least 1 notification must be shown"));
+        }
+        validate_chunk_kb(self.transfer_chunk_kb)?;
+        if self.max_concurrent_transfers == 0 || self.max_transfers_per_peer == 0 {
+            return Err(anyhow!("Transfer limits must be at least 1"));
+        }
+        if self.shared_folder_after_failures == 0 {
+            return Err(anyhow!("Shared folder fallback needs at least 1 failure"));
+        }
+        Ok(())
+    }
+
+    /// Inattività dopo cui la trasmissione AirPlay si ferma, `None` per mai
+    pub fn airplay_idle_timeout(&self) -> Option<std::time::Duration> {
+        self.airplay_idle_timeout_mins
+            .map(|minutes| std::time::Duration::from_secs(u64::from(minutes) * 60))
+    }
+
+    /// Limiti degli invii simultanei
+    pub fn transfer_limits(&self) -> TransferLimits {
+        TransferLimits::new(self.max_concurrent_transfers as usize, self.max_transfers_per_peer as usize)
+    }
+
+    /// Tempi dopo cui il server HTTPS AirDrop chiude una connessione
+    pub fn https_connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            idle_timeout: std::time::Duration::from_secs(self.https_idle_timeout_secs),
+            max_lifetime: std::time::Duration::from_secs(self.https_max_connection_secs),
+        }
+    }
+
+    /// Scelta del metodo di invio per ogni dispositivo
+    pub fn method_selector(&self) -> MethodSelector {
+        MethodSelector::new(self.transfer_method, self.shared_folder.clone(), self.shared_folder_after_failures)
+    }
+
+    /// Impostazioni che vengono lette solo all'avvio dei servizi e che
+    /// differiscono da `previous`
+    pub fn needs_restart_from(&self, previous: &Settings) -> bool {
+        previous.multicast != self.multicast
+            || previous.ip_mode != self.ip_mode
+            || previous.airplay_socket != self.airplay_socket
+            || previous.max_incoming_file_size != self.max_incoming_file_size
+            || previous.transfer_chunk_kb != self.transfer_chunk_kb
+            || previous.peer_filter != self.peer_filter
+            || previous.pin_peer_certificates != self.pin_peer_certificates
+            || previous.tls_backend != self.tls_backend
+            || previous.control_api != self.control_api
+            || previous.cache_dir != self.cache_dir
+            || previous.ports != self.ports
+            || previous.https_idle_timeout_secs != self.https_idle_timeout_secs
+            || previous.https_max_connection_secs != self.https_max_connection_secs
+            || previous.ble_poll_interval_ms != self.ble_poll_interval_ms
+            || previous.friendly_name != self.friendly_name
+            || previous.name_suffix != self.name_suffix
+            || previous.avoid_name_collisions != self.avoid_name_collisions
+            || previous.after_transfer != self.after_transfer
+            || previous.transfer_compression != self.transfer_compression
+    }
+
+    /// JSON da esportare, senza il token dell'API di controllo che è proprio
+    /// di ogni PC
+    pub fn export_json(&self) -> Result<String> {
+        let mut settings = self.clone();
+        settings.control_api.token = None;
+        let export = serde_json::json!({
+            "schema_version": SETTINGS_SCHEMA_VERSION,
+            "settings": settings,
+        });
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    /// Legge un file esportato, o una copia di `settings.json`, e lo valida.
+    /// Il token dell'API di controllo locale viene mantenuto da `current`.
+    pub fn import_json(json: &str, current: &Settings) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json).context("Not a JSON file")?;
+        let version = match value.get("schema_version") {
+            None => 0,
+            Some(version) => version
+                .as_u64()
+                .ok_or_else(|| anyhow!("Invalid schema_version {}", version))?,
+        };
+
+        let settings = match version {
+            // Copia di settings.json: i campi mancanti prendono il valore predefinito
+            0 => value,
+            1 => value
+                .get("settings")
+                .cloned()
+                .ok_or_else(|| anyhow!("The file has no settings"))?,
+            newer => {
+                return Err(anyhow!(
+                    "The file was exported by a newer AirWin (schema {}, this version reads up to {})",
+                    newer, SETTINGS_SCHEMA_VERSION
+                ))
+            }
+        };
+
+        let mut settings: Settings = serde_json::from_value(settings).context("Invalid settings")?;
+        settings.validate()?;
+        settings.control_api.token = current.control_api.token.clone();
+        Ok(settings)
+    }
+
+    /// Salva le impostazioni sul disco
+    pub fn save(&self) -> Result<()> {
+        let dir = config_dir();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create config directory {:?}", dir))?;
+
+        let path = dir.join(SETTINGS_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("Failed to write settings to {:?}", path))?;
+
+        info!("Saved settings to {:?}", path);
+        Ok(())
+    }
+}
+
+/// Cartella di configurazione di AirWin
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("AirWin")
+}
+
+fn settings_path() -> PathBuf {
+    config_dir().join(SETTINGS_FILE)
+}
+
+/// Una connessione deve poter restare aperta almeno quanto può restare inattiva
+fn validate_https_timeouts(idle_secs: u64, max_secs: u64) -> Result<()> {
+    if idle_secs == 0 {
+        return Err(anyhow!("HTTPS idle timeout must be at least 1 second"));
+    }
+    if max_secs < idle_secs {
+        return Err(anyhow!(
+            "HTTPS connection lifetime of {} s is shorter than the idle timeout of {} s",
+            max_secs, idle_secs
+        ));
+    }
+    Ok(())
+}
+
+/// Verifica che la scala del testo sia tra quelle che l'interfaccia applica
+fn validate_font_scale(percent: u32) -> Result<()> {
+    if !(MIN_SCALE_PERCENT..=MAX_SCALE_PERCENT).contains(&percent) {
+        return Err(anyhow!(
+            "Font scale must be between {}% and {}%, got {}%",
+            MIN_SCALE_PERCENT, MAX_SCALE_PERCENT, percent
+        ));
+    }
+    Ok(())
+}
+
+/// Verifica che la dimensione dei blocchi di trasferimento sia nei limiti
+fn validate_chunk_kb(kb: u32) -> Result<()> {
+    use crate::protocols::airdrop::{MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
+    let bytes = kb as usize * 1024;
+    if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&bytes) {
+        return Err(anyhow!(
+            "Transfer chunk size must be between {} and {} KiB, got {}",
+            MIN_CHUNK_SIZE / 1024, MAX_CHUNK_SIZE / 1024, kb
+        ));
+    }
+    Ok(())
+}
+
+/// Verifica che il nome mostrato agli altri dispositivi sia annunciabile così com'è
+pub fn validate_friendly_name(name: &str) -> Result<()> {
+    if name.len() > MAX_NAME_LEN {
+        return Err(anyhow!("Friendly name must be at most {} bytes, got {}", MAX_NAME_LEN, name.len()));
+    }
+    if sanitize_name(name).as_deref() != Some(name) {
+        return Err(anyhow!("Friendly name must not be empty or contain control characters"));
+    }
+    Ok(())
+}
+
+/// Verifica che il suffisso del nome sia annunciabile così com'è
+pub fn validate_name_suffix(suffix: &str) -> Result<()> {
+    if suffix.len() > MAX_SUFFIX_LEN {
+        return Err(anyhow!("Name suffix must be at most {} bytes, got {}", MAX_SUFFIX_LEN, suffix.len()));
+    }
+    if sanitize_name(suffix).as_deref() != Some(suffix) {
+        return Err(anyhow!("Name suffix must not be empty or contain control characters"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multicast_ttl_range() {
+        let mut multicast = MulticastSettings::default();
+        assert!(multicast.validate().is_ok());
+
+        multicast.ttl = 0;
+        assert!(multicast.validate().is_err());
+        multicast.ttl = 256;
+        assert!(multicast.validate().is_err());
+        multicast.ttl = 1;
+        assert!(multicast.validate().is_ok());
+    }
+
+    #[test]
+    fn test_control_api_off_by_default() {
+        let mut control_api = Settings::default().control_api;
+        assert!(!control_api.enabled);
+        assert!(control_api.token.is_none());
+
+        assert!(control_api.ensure_token());
+        let token = control_api.token.clone().unwrap();
+        assert!(!control_api.ensure_token());
+        assert_eq!(control_api.token, Some(token));
+    }
+
+    #[test]
+    fn test_ports_must_be_distinct() {
+        let mut ports = Ports::default();
+        assert!(ports.validate().is_ok());
+
+        ports.companion_link = ports.airdrop_https;
+        let err = ports.validate().unwrap_err().to_string();
+        assert!(err.contains("AirDrop HTTPS") && err.contains("Companion Link"), "{}", err);
+
+        ports.companion_link = 0;
+        assert!(ports.validate().is_err());
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut settings = Settings::default();
+        settings.airplay_port = Some(7100);
+        settings.mini_mode = true;
+        settings.peer_filter.allow = vec!["iPhone di Anna".to_string()];
+        settings.control_api.token = Some("segreto".to_string());
+
+        let json = settings.export_json().unwrap();
+        assert!(json.contains("\"schema_version\": 1"));
+        assert!(!json.contains("segreto"));
+
+        let imported = Settings::import_json(&json, &settings).unwrap();
+        assert_eq!(imported, settings);
+    }
+
+    #[test]
+    fn test_import_migrates_and_rejects() {
+        // Una copia di settings.json senza versione, con campi mancanti
+        let legacy = Settings::import_json(r#"{"mini_mode":true}"#, &Settings::default()).unwrap();
+        assert!(legacy.mini_mode);
+        assert_eq!(legacy.ports, Ports::default());
+
+        let newer = r#"{"schema_version":2,"settings":{}}"#;
+        let err = Settings::import_json(newer, &Settings::default()).unwrap_err().to_string();
+        assert!(err.contains("newer AirWin"), "{}", err);
+
+        let bad_ports = r#"{"schema_version":1,"settings":{"ports":{"airdrop_https":0}}}"#;
+        assert!(Settings::import_json(bad_ports, &Settings::default()).is_err());
+        let empty_region = r#"{"schema_version":1,"settings":{"capture_region":{"x":0,"y":0,"width":0,"height":600}}}"#;
+        assert!(Settings::import_json(empty_region, &Settings::default()).is_err());
+        let short_lifetime = r#"{"schema_version":1,"settings":{"https_idle_timeout_secs":60,"https_max_connection_secs":30}}"#;
+        assert!(Settings::import_json(short_lifetime, &Settings::default()).is_err());
+        let no_idle = r#"{"schema_version":1,"settings":{"airplay_idle_timeout_mins":0}}"#;
+        assert!(Settings::import_json(no_idle, &Settings::default()).is_err());
+        let never_idle = r#"{"schema_version":1,"settings":{"airplay_idle_timeout_mins":null}}"#;
+        assert_eq!(Settings::import_json(never_idle, &Settings::default()).unwrap().airplay_idle_timeout(), None);
+        let no_backlog = r#"{"schema_version":1,"settings":{"airplay_socket":{"backlog":0}}}"#;
+        assert!(Settings::import_json(no_backlog, &Settings::default()).is_err());
+        let ipv6_only = r#"{"schema_version":1,"settings":{"ip_mode":"ipv6_only"}}"#;
+        assert_eq!(Settings::import_json(ipv6_only, &Settings::default()).unwrap().ip_mode, IpMode::Ipv6Only);
+        let dual_stack = r#"{"schema_version":1,"settings":{"ip_mode":"dual_stack"}}"#;
+        assert!(Settings::import_json(dual_stack, &Settings::default()).is_err());
+        let system_notifications = r#"{"schema_version":1,"settings":{"notification_backend":"system"}}"#;
+        assert_eq!(
+            Settings::import_json(system_notifications, &Settings::default()).unwrap().notification_backend,
+            NotificationBackendKind::System
+        );
+        let no_notifications = r#"{"schema_version":1,"settings":{"max_notifications":0}}"#;
+        assert!(Settings::import_json(no_notifications, &Settings::default()).is_err());
+        let rustls = r#"{"schema_version":1,"settings":{"tls_backend":"rustls"}}"#;
+        assert_eq!(
+            Settings::import_json(rustls, &Settings::default()).unwrap().tls_backend,
+            TlsBackend::Rustls
+        );
+        let stay_connected = r#"{"schema_version":1,"settings":{"after_transfer":"stay_connected"}}"#;
+        assert_eq!(
+            Settings::import_json(stay_connected, &Settings::default()).unwrap().after_transfer,
+            AfterTransfer::StayConnected
+        );
+        let high_contrast = r#"{"schema_version":1,"settings":{"theme":"high_contrast","font_scale_percent":150}}"#;
+        let imported = Settings::import_json(high_contrast, &Settings::default()).unwrap();
+        assert_eq!((imported.theme, imported.font_scale_percent), (Theme::HighContrast, 150));
+        let huge_text = r#"{"schema_version":1,"settings":{"font_scale_percent":500}}"#;
+        assert!(Settings::import_json(huge_text, &Settings::default()).is_err());
+        let trusted = r#"{"schema_version":1,"settings":{"trusted_devices":[{"id":"1a2b3c","name":"iPhone"}]}}"#;
+        assert_eq!(
+            Settings::import_json(trusted, &Settings::default()).unwrap().trusted_devices[0].id,
+            "1a2b3c"
+        );
+        let tiny_chunks = r#"{"schema_version":1,"settings":{"transfer_chunk_kb":1}}"#;
+        assert!(Settings::import_json(tiny_chunks, &Settings::default()).is_err());
+        let no_transfers = r#"{"schema_version":1,"settings":{"max_transfers_per_peer":0}}"#;
+        assert!(Settings::import_json(no_transfers, &Settings::default()).is_err());
+        let long_suffix = r#"{"schema_version":1,"settings":{"name_suffix":"ufficio al secondo piano"}}"#;
+        assert!(Settings::import_json(long_suffix, &Settings::default()).is_err());
+        assert!(Settings::import_json("non json", &Settings::default()).is_err());
+    }
+
+    #[test]
+    fn test_onboarding_only_for_new_installs() {
+        assert!(!Settings::default().onboarding_complete);
+        let existing: Settings = serde_json::from_str(r#"{"mini_mode":true}"#).unwrap();
+        assert!(existing.onboarding_complete);
+        let fresh: Settings = serde_json::from_str(r#"{"onboarding_complete":false}"#).unwrap();
+        assert!(!fresh.onboarding_complete);
+    }
+
+    #[test]
+    fn test_friendly_name_validation() {
+        assert!(validate_friendly_name("Marco's PC").is_ok());
+        assert!(validate_friendly_name("").is_err());
+        assert!(validate_friendly_name(" PC").is_err());
+        assert!(validate_friendly_name("PC\u{7}").is_err());
+        assert!(validate_friendly_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+
+        let mut settings = Settings::default();
+        settings.friendly_name = Some("PC\nnuovo".to_string());
+        assert!(settings.validate().is_err());
+        settings.friendly_name = Some("PC nuovo".to_string());
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_multicast_interface_allow_list() {
+        let mut multicast = MulticastSettings::default();
+        assert!(multicast.allows_interface("Ethernet"));
+
+        multicast.interfaces = vec!["Wi-Fi".to_string()];
+        assert!(multicast.allows_interface("wi-fi"));
+        assert!(!multicast.allows_interface("Ethernet"));
+    }
+}
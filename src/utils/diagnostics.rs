@@ -0,0 +1,52 @@
+//! Dati di diagnostica dell'applicazione
+//!
+//! Raccoglie lo stato rilevato all'avvio, da mostrare all'utente e da
+//! allegare alle segnalazioni.
+
+use super::config::Ports;
+use super::privileges;
+
+/// Stato dell'applicazione rilevato all'avvio
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    /// Il processo è in esecuzione come amministratore
+    pub elevated: bool,
+    /// Porte effettivamente usate dai servizi
+    pub ports: Ports,
+    /// Funzionalità ridotte e motivo
+    pub limitations: Vec<String>,
+}
+
+impl Diagnostics {
+    /// Rileva lo stato corrente a partire dalle porte configurate
+    pub fn collect(configured: Ports) -> Self {
+        Self::for_privileges(configured, privileges::is_elevated())
+    }
+
+    pub fn for_privileges(configured: Ports, elevated: bool) -> Self {
+        Self {
+            elevated,
+            ports: privileges::service_ports(configured, elevated),
+            limitations: privileges::limitations(configured, elevated),
+        }
+    }
+
+    /// Riepilogo testuale, una voce per riga
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "Privilegi: {}",
+                if self.elevated { "amministratore" } else { "utente standard" }
+            ),
+        ];
+        lines.extend(self.ports.named().iter().map(|(name, port)| format!("Porta {}: {}", name, port)));
+        lines.extend(self.limitations.iter().map(|limitation| format!("Limitazione: {}", limitation)));
+        lines.join("\n")
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::for_privileges(Ports::default(), true)
+    }
+}
@@ -18,6 +18,21 @@ pub enum AirWinError {
     #[error("Invalid network interface: {0}")]
     InvalidInterface(String),
 
+    #[error("Secure connection setup failed: {0}")]
+    TlsError(String),
+
+    #[error("A stream is already in progress")]
+    AlreadyActive,
+
+    #[error("{0} refused the connection: AirDrop may be disabled on it, or it is on a different network")]
+    PeerUnreachable(std::net::SocketAddr),
+
+    #[error("The certificate of {0} changed since the last transfer, so the transfer was stopped. If it is the same device, forget its certificate and send again")]
+    CertificateChanged(String),
+
+    #[error("Screen capture unavailable: {0}")]
+    CaptureUnavailable(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -26,7 +41,8 @@ impl AirWinError {
     pub fn is_temporary(&self) -> bool {
         matches!(self, 
             AirWinError::ConnectionTimeout |
-            AirWinError::NetworkError(_)
+            AirWinError::NetworkError(_) |
+            AirWinError::PeerUnreachable(_)
         )
     }
 
@@ -0,0 +1,161 @@
+//! Registro degli eventi principali dell'applicazione
+//!
+//! I livelli di protocollo pubblicano eventi di alto livello (servizi registrati,
+//! dispositivi scoperti, trasferimenti, errori) su un canale broadcast globale.
+//! L'interfaccia li raccoglie in una timeline indipendente dal log su file,
+//! da copiare nelle segnalazioni di bug. Gli ultimi eventi restano in memoria,
+//! così anche quelli pubblicati all'avvio, prima che l'interfaccia si
+//! sottoscriva, arrivano nella timeline.
+
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+/// Eventi trattenuti dal canale per i sottoscrittori più lenti, e ultimi
+/// eventi consegnati a chi si sottoscrive
+const EVENT_LOG_CAPACITY: usize = 256;
+
+/// Gravità di un evento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "INFO"),
+            Severity::Warning => write!(f, "WARN"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// Evento della timeline
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub timestamp: DateTime<Local>,
+    pub severity: Severity,
+    /// Componente che ha generato l'evento (es. "AirDrop")
+    pub source: &'static str,
+    pub message: String,
+}
+
+impl LogEvent {
+    /// Riga di testo usata per la visualizzazione e la copia
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} [{}] {}: {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.severity,
+            self.source,
+            self.message
+        )
+    }
+}
+
+struct EventLog {
+    sender: broadcast::Sender<LogEvent>,
+    recent: Mutex<VecDeque<LogEvent>>,
+}
+
+fn log() -> &'static EventLog {
+    static LOG: OnceLock<EventLog> = OnceLock::new();
+    LOG.get_or_init(|| EventLog {
+        sender: broadcast::channel(EVENT_LOG_CAPACITY).0,
+        recent: Mutex::new(VecDeque::with_capacity(EVENT_LOG_CAPACITY)),
+    })
+}
+
+/// Pubblica un evento e lo tiene fra gli ultimi
+pub fn publish(severity: Severity, source: &'static str, message: impl Into<String>) {
+    let event = LogEvent {
+        timestamp: Local::now(),
+        severity,
+        source,
+        message: message.into(),
+    };
+    let log = log();
+    // Il lock copre anche l'invio: chi si sottoscrive riceve ogni evento una volta sola
+    let mut recent = log.recent.lock().unwrap();
+    if recent.len() == EVENT_LOG_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(event.clone());
+    let _ = log.sender.send(event);
+}
+
+pub fn info(source: &'static str, message: impl Into<String>) {
+    publish(Severity::Info, source, message);
+}
+
+pub fn warning(source: &'static str, message: impl Into<String>) {
+    publish(Severity::Warning, source, message);
+}
+
+pub fn error(source: &'static str, message: impl Into<String>) {
+    publish(Severity::Error, source, message);
+}
+
+/// Nuovo sottoscrittore della timeline, con gli eventi già pubblicati dal
+/// più vecchio
+pub fn subscribe() -> (Vec<LogEvent>, broadcast::Receiver<LogEvent>) {
+    let log = log();
+    let recent = log.recent.lock().unwrap();
+    (recent.iter().cloned().collect(), log.sender.subscribe())
+}
+
+/// Testo di tutti gli eventi, una riga per evento
+pub fn format_events<'a>(events: impl IntoIterator<Item = &'a LogEvent>) -> String {
+    events
+        .into_iter()
+        .map(LogEvent::to_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_events_reach_subscribers() {
+        let (_, mut receiver) = subscribe();
+        error("Test", "bind failed on port 7000");
+
+        // Il canale è globale: altri test possono pubblicare in parallelo
+        let event = std::iter::from_fn(|| receiver.try_recv().ok())
+            .find(|event| event.source == "Test")
+            .unwrap();
+        assert_eq!(event.severity, Severity::Error);
+        assert_eq!(event.message, "bind failed on port 7000");
+    }
+
+    #[test]
+    fn test_events_before_subscribing_are_kept() {
+        warning("Test", "published before the UI started");
+
+        // Il canale è globale: altri test possono pubblicare in parallelo
+        let (recent, _) = subscribe();
+        assert!(recent
+            .iter()
+            .any(|event| event.source == "Test" && event.message == "published before the UI started"));
+    }
+
+    #[test]
+    fn test_format_events() {
+        let event = LogEvent {
+            timestamp: Local::now(),
+            severity: Severity::Warning,
+            source: "AirPlay",
+            message: "frame dropped".to_string(),
+        };
+
+        let text = format_events([&event, &event]);
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.lines().all(|line| line.ends_with("[WARN] AirPlay: frame dropped")));
+    }
+}
@@ -0,0 +1,138 @@
+//! Anteprima dei file da inviare
+//!
+//! Prima della conferma dell'invio vengono mostrati dimensione, risoluzione
+//! e una miniatura delle immagini, così è più difficile inviare il file
+//! sbagliato. La decodifica avviene su un thread bloccante, mai su quello
+//! dell'interfaccia.
+
+use std::path::{Path, PathBuf};
+
+/// Lato massimo della miniatura in pixel
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+/// Le immagini più grandi non vengono decodificate per la miniatura
+const MAX_DECODE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Miniatura RGBA pronta per l'interfaccia
+#[derive(Clone)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl std::fmt::Debug for Thumbnail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Thumbnail({}x{})", self.width, self.height)
+    }
+}
+
+/// Informazioni mostrate prima di inviare un file
+#[derive(Debug, Clone, Default)]
+pub struct FilePreview {
+    /// Dimensione in byte
+    pub size: Option<u64>,
+    /// Risoluzione originale, solo per le immagini
+    pub dimensions: Option<(u32, u32)>,
+    /// Miniatura, solo per le immagini decodificabili
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// Legge dimensione e, per le immagini, risoluzione e miniatura
+pub async fn load_preview(path: PathBuf) -> FilePreview {
+    let size = tokio::fs::metadata(&path).await.ok().map(|metadata| metadata.len());
+    let mut preview = FilePreview { size, ..Default::default() };
+
+    if size.is_some_and(|size| size <= MAX_DECODE_SIZE) {
+        if let Ok(Some((dimensions, thumbnail))) =
+            tokio::task::spawn_blocking(move || thumbnail(&path)).await
+        {
+            preview.dimensions = Some(dimensions);
+            preview.thumbnail = Some(thumbnail);
+        }
+    }
+
+    preview
+}
+
+/// Decodifica un'immagine e la riduce a `THUMBNAIL_SIZE`, `None` se il file
+/// non è un'immagine supportata
+fn thumbnail(path: &Path) -> Option<((u32, u32), Thumbnail)> {
+    let image = image::io::Reader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()?;
+    let dimensions = (image.width(), image.height());
+    let scaled = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgba8();
+
+    Some((
+        dimensions,
+        Thumbnail {
+            width: scaled.width(),
+            height: scaled.height(),
+            rgba: scaled.into_raw(),
+        },
+    ))
+}
+
+/// Icona mostrata al posto della miniatura, in base al tipo MIME
+pub fn type_icon(mime_type: &str) -> &'static str {
+    match mime_type.split('/').next().unwrap_or_default() {
+        "image" => "🖼",
+        "video" => "🎬",
+        "audio" => "🎵",
+        "text" => "📝",
+        _ => match mime_type {
+            "application/pdf" => "📕",
+            "application/zip" | "application/gzip" | "application/x-7z-compressed" => "🗜",
+            _ => "📄",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("airwin_preview_{}.{}", Uuid::new_v4().simple(), extension))
+    }
+
+    #[tokio::test]
+    async fn test_image_preview_is_scaled() {
+        let path = temp_path("png");
+        image::RgbaImage::from_pixel(512, 256, image::Rgba([10, 20, 30, 255]))
+            .save(&path)
+            .unwrap();
+
+        let preview = load_preview(path.clone()).await;
+        assert_eq!(preview.dimensions, Some((512, 256)));
+        let thumbnail = preview.thumbnail.unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+        assert_eq!(thumbnail.rgba.len(), (thumbnail.width * thumbnail.height * 4) as usize);
+        assert!(preview.size.unwrap() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_other_files_have_no_thumbnail() {
+        let path = temp_path("txt");
+        std::fs::write(&path, b"non sono un'immagine").unwrap();
+
+        let preview = load_preview(path.clone()).await;
+        assert_eq!(preview.size, Some(20));
+        assert!(preview.dimensions.is_none());
+        assert!(preview.thumbnail.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_type_icon() {
+        assert_eq!(type_icon("image/heic"), "🖼");
+        assert_eq!(type_icon("application/pdf"), "📕");
+        assert_eq!(type_icon("application/octet-stream"), "📄");
+    }
+}
@@ -0,0 +1,357 @@
+//! Regole di Windows Firewall
+//!
+//! Se il firewall blocca le connessioni in entrata, gli altri dispositivi non
+//! vedono questo PC e non possono inviare file. AirWin verifica con `netsh` se
+//! le proprie regole esistono e coprono le porte in uso, e può aggiungerle:
+//! direttamente se il processo è elevato, altrimenti chiedendo l'elevazione.
+//! I comandi sono sempre disponibili da copiare ed eseguire a mano.
+//!
+//! Vengono controllate solo le regole create da AirWin: un'eventuale regola
+//! per il programma aggiunta da Windows al primo avvio non viene riconosciuta.
+
+use super::config::Ports;
+
+/// Nome della regola per le porte TCP dei servizi
+pub const TCP_RULE_NAME: &str = "AirWin-TCP";
+/// Nome della regola per mDNS
+pub const MDNS_RULE_NAME: &str = "AirWin-mDNS";
+/// Porta UDP di mDNS, necessaria per essere trovati
+pub const MDNS_PORT: u16 = 5353;
+
+/// Esito della verifica delle regole
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FirewallStatus {
+    /// Le regole coprono tutte le porte in uso
+    Allowed,
+    /// Porte senza una regola in entrata
+    Missing(Vec<u16>),
+    /// Fuori da Windows non c'è nulla da verificare
+    NotApplicable,
+    /// Verifica non riuscita
+    Unknown(String),
+}
+
+impl FirewallStatus {
+    /// Descrizione per la diagnostica
+    pub fn describe(&self) -> String {
+        match self {
+            FirewallStatus::Allowed => "connessioni in entrata consentite".to_string(),
+            FirewallStatus::Missing(ports) => format!(
+                "porte bloccate: {}",
+                ports.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
+            ),
+            FirewallStatus::NotApplicable => "non applicabile su questo sistema".to_string(),
+            FirewallStatus::Unknown(error) => format!("non verificabile ({})", error),
+        }
+    }
+
+    pub fn needs_rules(&self) -> bool {
+        matches!(self, FirewallStatus::Missing(_))
+    }
+}
+
+/// Comando `netsh` con i suoi argomenti
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetshCommand {
+    pub args: Vec<String>,
+}
+
+impl std::fmt::Display for NetshCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "netsh {}", self.args.join(" "))
+    }
+}
+
+fn netsh(args: &[&str]) -> NetshCommand {
+    NetshCommand {
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+/// Comandi che sostituiscono le regole di AirWin con quelle per le porte in uso.
+///
+/// Le regole valgono solo per le reti private e di dominio: su una rete
+/// pubblica Windows continua a bloccare le connessioni in entrata.
+pub fn rule_commands(ports: &Ports) -> Vec<NetshCommand> {
+    let tcp_ports = tcp_ports(ports)
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    let mdns_port = MDNS_PORT.to_string();
+    let tcp_name = format!("name={}", TCP_RULE_NAME);
+    let mdns_name = format!("name={}", MDNS_RULE_NAME);
+    let tcp_localport = format!("localport={}", tcp_ports);
+    let mdns_localport = format!("localport={}", mdns_port);
+
+    vec![
+        netsh(&["advfirewall", "firewall", "delete", "rule", &tcp_name]),
+        netsh(&[
+            "advfirewall", "firewall", "add", "rule", &tcp_name,
+            "dir=in", "action=allow", "protocol=TCP", &tcp_localport, "profile=private,domain",
+        ]),
+        netsh(&["advfirewall", "firewall", "delete", "rule", &mdns_name]),
+        netsh(&[
+            "advfirewall", "firewall", "add", "rule", &mdns_name,
+            "dir=in", "action=allow", "protocol=UDP", &mdns_localport, "profile=private,domain",
+        ]),
+    ]
+}
+
+/// Comandi da incollare in un prompt dei comandi come amministratore
+pub fn rule_commands_text(ports: &Ports) -> String {
+    rule_commands(ports)
+        .iter()
+        .map(NetshCommand::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Porte TCP dei servizi, senza duplicati
+fn tcp_ports(ports: &Ports) -> Vec<u16> {
+    let mut tcp: Vec<u16> = ports.named().iter().map(|(_, port)| *port).collect();
+    tcp.sort_unstable();
+    tcp.dedup();
+    tcp
+}
+
+/// Valore "Sì" del campo Abilitata nelle lingue di Windows più diffuse
+const ENABLED_VALUES: &[&str] = &["Yes", "Sì", "Ja", "Oui", "Sí", "Sim"];
+/// Valore "Consenti" del campo Azione nelle lingue di Windows più diffuse
+const ALLOW_VALUES: &[&str] = &["Allow", "Consenti", "Zulassen", "Autoriser", "Permitir"];
+
+/// Porte locali delle regole attive che consentono il traffico, nell'output
+/// di `netsh ... show rule`.
+///
+/// Le etichette sono tradotte nella lingua di Windows, quindi si guardano
+/// posizioni e valori. Ogni regola è un blocco separato da una riga vuota;
+/// dopo la riga di trattini il primo campo è Abilitata e l'ultimo è Azione.
+/// Una regola disattivata, che blocca o in una lingua non riconosciuta non
+/// conta. L'unico campo composto solo da numeri di porta è la porta locale:
+/// la porta remota, quando non è "Qualsiasi", non compare nelle regole di
+/// AirWin.
+pub fn allowed_ports(output: &str) -> Vec<u16> {
+    let mut rules: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            rules.push(Vec::new());
+        } else if let Some(rule) = rules.last_mut() {
+            rule.push(line);
+        }
+    }
+
+    let mut allowed = Vec::new();
+    for rule in rules {
+        let values: Vec<&str> = rule
+            .into_iter()
+            .skip_while(|line| !line.trim_start().starts_with("---"))
+            .filter_map(|line| line.split_once(':'))
+            .map(|(_, value)| value.trim())
+            .collect();
+        let enabled = values.first().is_some_and(|value| ENABLED_VALUES.contains(value));
+        let allows = values.last().is_some_and(|value| ALLOW_VALUES.contains(value));
+        if enabled && allows {
+            allowed.extend(values.iter().filter_map(|value| port_list(value)).flatten());
+        }
+    }
+    allowed
+}
+
+/// Porte di un valore come "7000,8770-8771", se è fatto solo di porte
+fn port_list(value: &str) -> Option<Vec<u16>> {
+    if value.is_empty() {
+        return None;
+    }
+    let mut ports = Vec::new();
+    let all_ports = value.split(',').all(|part| match part.trim().split_once('-') {
+        Some((start, end)) => match (start.parse::<u16>(), end.parse::<u16>()) {
+            (Ok(start), Ok(end)) if start <= end => {
+                ports.extend(start..=end);
+                true
+            }
+            _ => false,
+        },
+        None => part.trim().parse::<u16>().map(|port| ports.push(port)).is_ok(),
+    });
+    all_ports.then_some(ports)
+}
+
+/// Porte in uso non coperte dalle regole trovate
+fn missing_ports(ports: &Ports, tcp_output: Option<&str>, mdns_output: Option<&str>) -> Vec<u16> {
+    let tcp_allowed = tcp_output.map(allowed_ports).unwrap_or_default();
+    let mut missing: Vec<u16> = tcp_ports(ports)
+        .into_iter()
+        .filter(|port| !tcp_allowed.contains(port))
+        .collect();
+    if !mdns_output.map(allowed_ports).unwrap_or_default().contains(&MDNS_PORT) {
+        missing.push(MDNS_PORT);
+    }
+    missing
+}
+
+/// Verifica se le regole di AirWin consentono le porte in uso
+pub async fn check(ports: Ports) -> FirewallStatus {
+    if !cfg!(windows) {
+        return FirewallStatus::NotApplicable;
+    }
+    tokio::task::spawn_blocking(move || check_blocking(&ports))
+        .await
+        .unwrap_or_else(|e| FirewallStatus::Unknown(e.to_string()))
+}
+
+fn check_blocking(ports: &Ports) -> FirewallStatus {
+    let show = |name: &str| -> std::io::Result<Option<String>> {
+        let name = format!("name={}", name);
+        let output = run(&netsh(&["advfirewall", "firewall", "show", "rule", &name, "verbose"]))?;
+        // netsh termina con errore quando la regola non esiste
+        Ok(output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+    };
+
+    match (show(TCP_RULE_NAME), show(MDNS_RULE_NAME)) {
+        (Ok(tcp), Ok(mdns)) => {
+            let missing = missing_ports(ports, tcp.as_deref(), mdns.as_deref());
+            if missing.is_empty() {
+                FirewallStatus::Allowed
+            } else {
+                FirewallStatus::Missing(missing)
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => FirewallStatus::Unknown(e.to_string()),
+    }
+}
+
+/// Aggiunge le regole per le porte in uso.
+///
+/// Senza privilegi i comandi vengono eseguiti in un prompt elevato, che
+/// Windows fa confermare all'utente; se l'elevazione viene rifiutata
+/// restituisce un errore e i comandi vanno eseguiti a mano.
+pub async fn add_rules(ports: Ports, elevated: bool) -> Result<(), String> {
+    if !cfg!(windows) {
+        return Err("disponibile solo su Windows".to_string());
+    }
+    tokio::task::spawn_blocking(move || add_rules_blocking(&ports, elevated))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn add_rules_blocking(ports: &Ports, elevated: bool) -> Result<(), String> {
+    let commands = rule_commands(ports);
+    if elevated {
+        for command in &commands {
+            let output = run(command).map_err(|e| e.to_string())?;
+            // L'eliminazione fallisce se la regola non c'era ancora
+            let is_delete = command.args.get(2).map(String::as_str) == Some("delete");
+            if !output.status.success() && !is_delete {
+                return Err(String::from_utf8_lossy(&output.stdout).trim().to_string());
+            }
+        }
+        return Ok(());
+    }
+
+    let script = commands
+        .iter()
+        .map(NetshCommand::to_string)
+        .collect::<Vec<_>>()
+        .join(" & ");
+    let elevate = format!(
+        "$p = Start-Process -FilePath cmd -ArgumentList '/c {}' -Verb RunAs -WindowStyle Hidden -Wait -PassThru; exit $p.ExitCode",
+        script
+    );
+    let output = hidden(std::process::Command::new("powershell"))
+        .args(["-NoProfile", "-NonInteractive", "-Command", &elevate])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err("elevazione annullata o non riuscita".to_string())
+    }
+}
+
+/// Apre le impostazioni di Windows Firewall nel Pannello di controllo
+pub fn open_settings() -> Result<(), String> {
+    if !cfg!(windows) {
+        return Err("disponibile solo su Windows".to_string());
+    }
+    std::process::Command::new("control")
+        .arg("firewall.cpl")
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn run(command: &NetshCommand) -> std::io::Result<std::process::Output> {
+    hidden(std::process::Command::new("netsh"))
+        .args(&command.args)
+        .output()
+}
+
+/// Evita che si apra una finestra della console per ogni comando
+#[cfg(windows)]
+fn hidden(mut command: std::process::Command) -> std::process::Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    command.creation_flags(CREATE_NO_WINDOW);
+    command
+}
+
+#[cfg(not(windows))]
+fn hidden(command: std::process::Command) -> std::process::Command {
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_commands_cover_all_service_ports() {
+        let ports = Ports::default();
+        let text = rule_commands_text(&ports);
+        for (_, port) in ports.named() {
+            assert!(text.contains(&port.to_string()), "porta {} mancante", port);
+        }
+        assert!(text.contains("protocol=UDP localport=5353"));
+        assert_eq!(text.lines().count(), 4);
+        assert!(text.lines().all(|line| line.starts_with("netsh advfirewall firewall ")));
+    }
+
+    #[test]
+    fn test_missing_ports_from_localized_netsh_output() {
+        let ports = Ports::default();
+        let mut tcp: Vec<_> = ports.named().iter().map(|(_, port)| port.to_string()).collect();
+        // Regola creata quando una porta era diversa
+        let stale = tcp.pop().unwrap();
+        let tcp_output = format!(
+            "\r\nNome regola:          AirWin-TCP\r\n----------\r\nAbilitata:   Sì\r\nProtocollo:   TCP\r\nPorta locale:   {},9999\r\nPorta remota:   Qualsiasi\r\nAzione:   Consenti\r\n\r\nOk.\r\n",
+            tcp.join(",")
+        );
+        let mdns_output = "Rule Name: AirWin-mDNS\n----------\nEnabled: Yes\nLocalPort: 5350-5360\nRemotePort: Any\nAction: Allow\n";
+
+        assert_eq!(allowed_ports(mdns_output), (5350..=5360).collect::<Vec<_>>());
+        assert_eq!(
+            missing_ports(&ports, Some(&tcp_output), Some(mdns_output)),
+            vec![stale.parse::<u16>().unwrap()]
+        );
+        assert!(missing_ports(&ports, Some(&tcp_output), None).contains(&MDNS_PORT));
+        assert_eq!(missing_ports(&ports, None, None).len(), tcp.len() + 2);
+    }
+
+    #[test]
+    fn test_disabled_and_blocking_rules_allow_nothing() {
+        let rule = |enabled: &str, action: &str, port: u16| {
+            format!(
+                "Rule Name: AirWin-TCP\n----------\nEnabled: {}\nDirection: In\nLocalPort: {}\nRemotePort: Any\nEdge traversal: No\nAction: {}\n",
+                enabled, port, action
+            )
+        };
+        let output = [
+            rule("No", "Allow", 7000),
+            rule("Yes", "Block", 7001),
+            rule("Yes", "Allow", 7002),
+            "Ok.\n".to_string(),
+        ]
+        .join("\n");
+        assert_eq!(allowed_ports(&output), vec![7002]);
+    }
+}
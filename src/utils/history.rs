@@ -0,0 +1,271 @@
+//! Cronologia dei trasferimenti
+//!
+//! Le voci vengono salvate in `history.json` nella cartella di configurazione,
+//! dalla più recente alla meno recente, fino a un massimo di [`MAX_ENTRIES`].
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use super::config::config_dir;
+use crate::protocols::airdrop::{IncomingFile, TransferOutcome};
+
+const HISTORY_FILE: &str = "history.json";
+
+/// Numero massimo di voci conservate
+pub const MAX_ENTRIES: usize = 200;
+
+/// Direzione di un trasferimento
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferDirection {
+    /// File ricevuto da un altro dispositivo
+    Incoming,
+    /// File inviato a un altro dispositivo
+    Outgoing,
+}
+
+/// Voce della cronologia
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub direction: TransferDirection,
+    pub name: String,
+    pub size: u64,
+    /// Indirizzo o nome del dispositivo remoto
+    pub peer: String,
+    pub timestamp: DateTime<Utc>,
+    /// Durata del trasferimento in millisecondi, se nota
+    #[serde(default)]
+    pub elapsed_ms: Option<u64>,
+    /// Percorso del file salvato, solo per i file ricevuti
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// File ricevuto non ancora aperto né mostrato nella cartella
+    #[serde(default)]
+    pub unread: bool,
+}
+
+impl HistoryEntry {
+    /// Voce per un invio completato all'ora `now`
+    pub fn outgoing(outcome: &TransferOutcome, now: DateTime<Utc>) -> Self {
+        Self {
+            direction: TransferDirection::Outgoing,
+            name: outcome.file_name.clone(),
+            size: outcome.total_bytes,
+            peer: outcome.peer.clone(),
+            timestamp: now,
+            elapsed_ms: Some(outcome.elapsed.as_millis() as u64),
+            path: None,
+            unread: false,
+        }
+    }
+
+    /// Voce per un file ricevuto all'ora `now`, da leggere
+    pub fn incoming(file: &IncomingFile, now: DateTime<Utc>) -> Self {
+        Self {
+            direction: TransferDirection::Incoming,
+            name: file.name.clone(),
+            size: file.size,
+            peer: file.from.to_string(),
+            timestamp: now,
+            elapsed_ms: None,
+            path: Some(file.path.clone()),
+            unread: true,
+        }
+    }
+}
+
+/// Cronologia dei trasferimenti persistita su disco
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Carica la cronologia dalla cartella di configurazione
+    pub fn load() -> Self {
+        Self::load_from(config_dir().join(HISTORY_FILE))
+    }
+
+    /// Carica la cronologia da un file specifico, vuota se assente o non valida
+    pub fn load_from(path: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Invalid history file {:?}, starting empty: {}", path, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        Self { path, entries }
+    }
+
+    /// Aggiunge una voce in testa, scartando le più vecchie oltre il limite
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_ENTRIES);
+    }
+
+    /// Voci dalla più recente
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// File ricevuti di cui è noto il percorso, dal più recente
+    pub fn received(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.direction == TransferDirection::Incoming && entry.path.is_some())
+    }
+
+    /// Numero di file ricevuti non ancora visti
+    pub fn unread_count(&self) -> usize {
+        self.received().filter(|entry| entry.unread).count()
+    }
+
+    /// Segna come visto il file ricevuto in `path`, restituisce se è cambiato qualcosa
+    pub fn mark_read(&mut self, path: &Path) -> bool {
+        let mut changed = false;
+        for entry in &mut self.entries {
+            if entry.unread && entry.path.as_deref() == Some(path) {
+                entry.unread = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Segna come visti tutti i file ricevuti, restituisce se è cambiato qualcosa
+    pub fn mark_all_read(&mut self) -> bool {
+        let mut changed = false;
+        for entry in self.entries.iter_mut().filter(|entry| entry.unread) {
+            entry.unread = false;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Salva la cronologia sul disco
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create history directory {:?}", dir))?;
+        }
+
+        let json = serde_json::to_string_pretty(&self.entries)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write history to {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::{Clock, FakeClock};
+    use uuid::Uuid;
+
+    fn entry(name: &str) -> HistoryEntry {
+        HistoryEntry {
+            direction: TransferDirection::Incoming,
+            name: name.to_string(),
+            size: 42,
+            peer: "192.168.1.100:7000".to_string(),
+            timestamp: Utc::now(),
+            elapsed_ms: None,
+            path: None,
+            unread: false,
+        }
+    }
+
+    fn received(name: &str) -> HistoryEntry {
+        HistoryEntry::incoming(
+            &IncomingFile {
+                name: name.to_string(),
+                size: 42,
+                from: "192.168.1.100:7000".parse().unwrap(),
+                path: PathBuf::from(name),
+            },
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("airwin_test_{}", Uuid::new_v4().simple()))
+            .join(HISTORY_FILE);
+
+        let mut history = History::load_from(path.clone());
+        assert!(history.entries().is_empty());
+
+        history.push(entry("prima.txt"));
+        history.push(entry("seconda.txt"));
+        history.save().unwrap();
+
+        let loaded = History::load_from(path.clone());
+        assert_eq!(loaded.entries(), history.entries());
+        assert_eq!(loaded.entries()[0].name, "seconda.txt");
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_entries_without_duration_still_load() {
+        let json = r#"[{"direction":"Incoming","name":"a.txt","size":1,"peer":"x","timestamp":"2024-01-01T00:00:00Z"}]"#;
+        let entries: Vec<HistoryEntry> = serde_json::from_str(json).unwrap();
+        assert_eq!(entries[0].elapsed_ms, None);
+        assert_eq!(entries[0].path, None);
+        assert!(!entries[0].unread);
+    }
+
+    #[test]
+    fn test_received_files_stay_unread_until_seen() {
+        let mut history = History::load_from(std::env::temp_dir().join("airwin_missing_history.json"));
+        history.push(received("foto.jpg"));
+        history.push(entry("senza_percorso.txt"));
+        history.push(received("tesi.pdf"));
+
+        assert_eq!(history.received().count(), 2);
+        assert_eq!(history.unread_count(), 2);
+
+        assert!(history.mark_read(Path::new("foto.jpg")));
+        assert!(!history.mark_read(Path::new("foto.jpg")));
+        assert_eq!(history.unread_count(), 1);
+
+        assert!(history.mark_all_read());
+        assert_eq!(history.unread_count(), 0);
+        assert!(!history.mark_all_read());
+    }
+
+    #[test]
+    fn test_entries_are_stamped_with_the_clock() {
+        let clock = FakeClock::new("2024-01-01T00:00:00Z".parse().unwrap());
+        let outcome = TransferOutcome {
+            file_name: "foto.jpg".to_string(),
+            total_bytes: 42,
+            elapsed: std::time::Duration::from_secs(1),
+            peer: "iPhone".to_string(),
+        };
+
+        let first = HistoryEntry::outgoing(&outcome, clock.now());
+        clock.advance(std::time::Duration::from_secs(5));
+        let second = HistoryEntry::outgoing(&outcome, clock.now());
+
+        assert_eq!(first.timestamp.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(second.timestamp - first.timestamp, chrono::Duration::seconds(5));
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut history = History::load_from(std::env::temp_dir().join("airwin_missing_history.json"));
+        for i in 0..MAX_ENTRIES + 10 {
+            history.push(entry(&format!("file_{}", i)));
+        }
+
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0].name, format!("file_{}", MAX_ENTRIES + 9));
+    }
+}
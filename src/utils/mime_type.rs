@@ -0,0 +1,150 @@
+//! Rilevamento del tipo MIME dei file da inviare
+//!
+//! L'estensione viene usata per prima; per i file senza estensione (o con
+//! estensione sconosciuta) si leggono i primi byte alla ricerca di un
+//! "magic number" noto prima di ripiegare su `application/octet-stream`.
+
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Tipo MIME usato quando non è possibile rilevare nulla di meglio
+pub const FALLBACK_MIME_TYPE: &str = "application/octet-stream";
+
+/// Numero di byte letti per il riconoscimento del contenuto
+const SNIFF_LEN: usize = 16;
+
+/// Rileva il tipo MIME di un file dall'estensione o dal contenuto
+pub async fn detect_mime_type(path: &Path) -> String {
+    if let Some(mime) = mime_guess::from_path(path).first() {
+        return mime.to_string();
+    }
+
+    let mut head = [0u8; SNIFF_LEN];
+    let read = match tokio::fs::File::open(path).await {
+        Ok(mut file) => file.read(&mut head).await.unwrap_or(0),
+        Err(_) => 0,
+    };
+
+    sniff_mime_type(&head[..read])
+        .unwrap_or(FALLBACK_MIME_TYPE)
+        .to_string()
+}
+
+/// Il formato è già compresso e comprimerlo di nuovo non lo riduce: foto,
+/// audio e video codificati, archivi e documenti Office, che sono archivi ZIP
+pub fn is_compressed(mime: &str) -> bool {
+    const UNCOMPRESSED: &[&str] = &["image/bmp", "image/svg+xml", "image/tiff", "image/x-icon", "audio/wav", "audio/x-wav"];
+    const ARCHIVES: &[&str] = &[
+        "application/zip",
+        "application/gzip",
+        "application/x-gzip",
+        "application/x-7z-compressed",
+        "application/x-rar-compressed",
+        "application/vnd.rar",
+        "application/x-bzip2",
+        "application/x-xz",
+        "application/zstd",
+        "application/java-archive",
+        "application/vnd.android.package-archive",
+        "application/epub+zip",
+        "application/pdf",
+    ];
+    let mime = mime.split(';').next().unwrap_or_default().trim();
+    if UNCOMPRESSED.contains(&mime) {
+        return false;
+    }
+    mime.starts_with("image/")
+        || mime.starts_with("audio/")
+        || mime.starts_with("video/")
+        || mime.starts_with("application/vnd.openxmlformats-officedocument.")
+        || ARCHIVES.contains(&mime)
+}
+
+/// Riconosce i formati più comuni dai primi byte del file
+pub fn sniff_mime_type(head: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"ID3", "audio/mpeg"),
+        (b"fLaC", "audio/flac"),
+        (b"OggS", "audio/ogg"),
+    ];
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(*mime);
+    }
+
+    // Contenitori RIFF: il formato reale è indicato agli offset 8..12
+    if head.len() >= 12 && &head[..4] == b"RIFF" {
+        return match &head[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        };
+    }
+
+    // Contenitori ISO BMFF (HEIC dalle foto iPhone, MP4, MOV)
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return match &head[8..12] {
+            b"heic" | b"heix" | b"mif1" | b"msf1" => Some("image/heic"),
+            b"qt  " => Some("video/quicktime"),
+            _ => Some("video/mp4"),
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_file(contents: &[u8]) -> std::path::PathBuf {
+        // Nessuna estensione: il rilevamento deve basarsi sul contenuto
+        let path = std::env::temp_dir().join(format!("airwin_mime_{}", Uuid::new_v4().simple()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_png_without_extension() {
+        let path = temp_file(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR");
+        assert_eq!(detect_mime_type(&path).await, "image/png");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unknown_binary_falls_back() {
+        let path = temp_file(&[0x00, 0x13, 0x37, 0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(detect_mime_type(&path).await, FALLBACK_MIME_TYPE);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_already_compressed_types() {
+        for mime in ["image/jpeg", "video/mp4", "application/zip", "audio/mpeg", "application/pdf"] {
+            assert!(is_compressed(mime), "{}", mime);
+        }
+        for mime in ["text/plain; charset=utf-8", "image/bmp", "audio/wav", "application/json", FALLBACK_MIME_TYPE] {
+            assert!(!is_compressed(mime), "{}", mime);
+        }
+    }
+
+    #[test]
+    fn test_extension_takes_precedence() {
+        assert_eq!(
+            mime_guess::from_path("foto.jpg").first().unwrap().to_string(),
+            "image/jpeg"
+        );
+        assert_eq!(sniff_mime_type(b"\x00\x00\x00\x18ftypheic"), Some("image/heic"));
+    }
+}
@@ -1,4 +1,22 @@
+pub mod archive;
+pub mod bug_report;
+pub mod cache;
+pub mod clock;
+pub mod config;
+pub mod diagnostics;
 pub mod error;
+pub mod event_log;
+pub mod file_preview;
+pub mod firewall;
+pub mod history;
+pub mod mime_type;
+pub mod open_file;
+pub mod poll_rate;
+pub mod privileges;
+pub mod self_test;
+pub mod sound;
+pub mod startup_summary;
+pub mod supervisor;
 mod logging;
 
 pub use error::{AirWinError, AirWinResult};
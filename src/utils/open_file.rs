@@ -0,0 +1,164 @@
+//! Apertura dei file ricevuti
+//!
+//! Il nome dei file ricevuti è scelto dal mittente, quindi prima di passarli
+//! al sistema il percorso viene risolto e controllato: deve esistere ed essere
+//! un file normale. I file eseguibili non vengono mai aperti direttamente, si
+//! possono solo mostrare nella cartella.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Estensioni che Windows eseguirebbe invece di aprire
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "com", "bat", "cmd", "msi", "msp", "scr", "pif", "cpl", "lnk", "url", "ps1", "psm1",
+    "vbs", "vbe", "js", "jse", "wsf", "wsh", "hta", "jar", "reg", "appref-ms",
+];
+
+/// Errore nell'aprire un file ricevuto
+#[derive(Debug, thiserror::Error)]
+pub enum OpenError {
+    #[error("Il file {0} non esiste più")]
+    Missing(PathBuf),
+    #[error("{0} non è un file")]
+    NotAFile(PathBuf),
+    #[error("{0} è un eseguibile e non viene aperto per sicurezza, usa \"Mostra nella cartella\"")]
+    Executable(PathBuf),
+    #[error("Impossibile aprire {path}: {message}")]
+    Launch { path: PathBuf, message: String },
+}
+
+/// Azione eseguita al termine di un trasferimento riuscito
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompletionAction {
+    /// Nessuna azione, il file resta nell'elenco dei ricevuti
+    #[default]
+    Nothing,
+    /// Apre il file con l'applicazione predefinita
+    Open,
+    /// Mostra il file in Esplora file
+    Reveal,
+}
+
+impl CompletionAction {
+    /// Esegue l'azione sul file. Valgono gli stessi controlli di [`open`] e
+    /// [`reveal`], quindi un eseguibile non viene mai aperto.
+    pub fn run(self, path: &Path) -> Result<(), OpenError> {
+        match self {
+            CompletionAction::Nothing => Ok(()),
+            CompletionAction::Open => open(path),
+            CompletionAction::Reveal => reveal(path),
+        }
+    }
+}
+
+/// Apre il file con l'applicazione predefinita
+pub fn open(path: &Path) -> Result<(), OpenError> {
+    let path = sanitize(path)?;
+    if is_executable(&path) {
+        return Err(OpenError::Executable(path));
+    }
+    opener::open(&path).map_err(|e| OpenError::Launch { message: e.to_string(), path })
+}
+
+/// Mostra il file selezionato in Esplora file
+pub fn reveal(path: &Path) -> Result<(), OpenError> {
+    let path = sanitize(path)?;
+    opener::reveal(&path).map_err(|e| OpenError::Launch { message: e.to_string(), path })
+}
+
+/// Percorso assoluto e senza `..` o collegamenti di un file esistente
+fn sanitize(path: &Path) -> Result<PathBuf, OpenError> {
+    let resolved = match path.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(OpenError::Missing(path.to_path_buf())),
+        Err(e) => {
+            return Err(OpenError::Launch {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })
+        }
+    };
+    if !resolved.is_file() {
+        return Err(OpenError::NotAFile(resolved));
+    }
+    Ok(strip_verbatim_prefix(resolved))
+}
+
+/// Su Windows `canonicalize` restituisce percorsi `\\?\C:\...`, che Esplora
+/// file non accetta
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|s| s.strip_prefix(r"\\?\")) {
+        Some(stripped) if !stripped.starts_with("UNC\\") => PathBuf::from(stripped),
+        _ => path,
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            EXECUTABLE_EXTENSIONS.iter().any(|blocked| blocked.eq_ignore_ascii_case(extension))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("airwin_open_{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_deleted_file_is_reported() {
+        let dir = temp_dir();
+        let missing = dir.join("cancellato.jpg");
+
+        assert!(matches!(open(&missing), Err(OpenError::Missing(_))));
+        assert!(matches!(reveal(&missing), Err(OpenError::Missing(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sanitize_resolves_parent_components() {
+        let dir = temp_dir();
+        std::fs::create_dir_all(dir.join("sotto")).unwrap();
+        std::fs::write(dir.join("foto.jpg"), b"jpg").unwrap();
+
+        let resolved = sanitize(&dir.join("sotto").join("..").join("foto.jpg")).unwrap();
+        assert_eq!(resolved.file_name().unwrap(), "foto.jpg");
+        assert!(!resolved.components().any(|c| c == std::path::Component::ParentDir));
+        assert!(matches!(sanitize(&dir.join("sotto")), Err(OpenError::NotAFile(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_completion_action_checks_the_file() {
+        let dir = temp_dir();
+        let missing = dir.join("interrotto.pdf");
+
+        assert!(CompletionAction::Nothing.run(&missing).is_ok());
+        assert!(matches!(CompletionAction::Open.run(&missing), Err(OpenError::Missing(_))));
+        assert!(matches!(CompletionAction::Reveal.run(&missing), Err(OpenError::Missing(_))));
+
+        let script = dir.join("script.ps1");
+        std::fs::write(&script, b"Get-Date").unwrap();
+        assert!(matches!(CompletionAction::Open.run(&script), Err(OpenError::Executable(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_executables_are_not_opened() {
+        let dir = temp_dir();
+        let installer = dir.join("Setup.EXE");
+        std::fs::write(&installer, b"MZ").unwrap();
+
+        assert!(matches!(open(&installer), Err(OpenError::Executable(_))));
+        assert!(!is_executable(Path::new("foto.jpg")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
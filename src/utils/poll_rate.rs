@@ -0,0 +1,85 @@
+//! Frequenza dei polling in background
+//!
+//! I poller registrano ogni ciclo con [`record`]. La frequenza degli ultimi
+//! secondi compare nella diagnostica, così si vede se un poller gira più
+//! spesso del previsto quando l'applicazione è inattiva.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Finestra su cui è calcolata la frequenza
+pub const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Cicli recenti di ogni poller
+#[derive(Debug, Default)]
+pub struct PollRates {
+    polls: BTreeMap<&'static str, VecDeque<Instant>>,
+}
+
+impl PollRates {
+    pub fn record_at(&mut self, name: &'static str, now: Instant) {
+        let polls = self.polls.entry(name).or_default();
+        polls.push_back(now);
+        prune(polls, now);
+    }
+
+    /// Cicli al secondo di ogni poller nella finestra che termina a `now`
+    pub fn rates_at(&mut self, now: Instant) -> Vec<(&'static str, f64)> {
+        self.polls
+            .iter_mut()
+            .map(|(name, polls)| {
+                prune(polls, now);
+                (*name, polls.len() as f64 / RATE_WINDOW.as_secs_f64())
+            })
+            .collect()
+    }
+}
+
+fn prune(polls: &mut VecDeque<Instant>, now: Instant) {
+    while polls.front().is_some_and(|poll| now.duration_since(*poll) > RATE_WINDOW) {
+        polls.pop_front();
+    }
+}
+
+fn global() -> &'static Mutex<PollRates> {
+    static RATES: OnceLock<Mutex<PollRates>> = OnceLock::new();
+    RATES.get_or_init(Default::default)
+}
+
+/// Registra un ciclo del poller `name`
+pub fn record(name: &'static str) {
+    if let Ok(mut rates) = global().lock() {
+        rates.record_at(name, Instant::now());
+    }
+}
+
+/// Cicli al secondo di tutti i poller, in ordine di nome
+pub fn snapshot() -> Vec<(&'static str, f64)> {
+    global()
+        .lock()
+        .map(|mut rates| rates.rates_at(Instant::now()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_counts_only_the_window() {
+        let mut rates = PollRates::default();
+        let start = Instant::now();
+        // 10 cicli al secondo per 10 secondi
+        for i in 0..100 {
+            rates.record_at("AirPlay", start + Duration::from_millis(i * 100));
+        }
+        rates.record_at("Network", start);
+
+        let now = start + Duration::from_secs(10);
+        let snapshot = rates.rates_at(now);
+        assert_eq!(snapshot[0].0, "AirPlay");
+        assert!((snapshot[0].1 - 10.0).abs() < 0.5, "{}", snapshot[0].1);
+        assert_eq!(snapshot[1], ("Network", 0.0));
+    }
+}
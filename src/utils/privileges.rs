@@ -0,0 +1,106 @@
+//! Rilevamento dei privilegi di amministratore
+//!
+//! Senza elevazione alcuni servizi non possono partire o devono usare porte
+//! diverse: l'avvio non viene bloccato, ma le funzionalità vengono ridotte
+//! e l'utente viene informato di cosa non è disponibile.
+
+use super::config::Ports;
+
+/// Porta di riserva del server AirDrop TCP
+pub const AIRDROP_FALLBACK_PORT: u16 = 7000;
+
+/// Scostamento applicato alle porte quando il processo non è elevato
+const UNPRIVILEGED_PORT_OFFSET: u16 = 10000;
+
+/// Indica se il processo è in esecuzione come amministratore
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return false;
+        }
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut returned = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut std::ffi::c_void),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut returned,
+        )
+        .as_bool();
+        CloseHandle(token);
+
+        ok && elevation.TokenIsElevated != 0
+    }
+}
+
+/// Fuori da Windows le porte usate non richiedono privilegi
+#[cfg(not(windows))]
+pub fn is_elevated() -> bool {
+    true
+}
+
+/// Porte effettive dei servizi a partire da quelle configurate.
+///
+/// Senza elevazione le porte del server di riserva e di AirPlay vengono
+/// spostate su valori più alti.
+pub fn service_ports(configured: Ports, elevated: bool) -> Ports {
+    if elevated {
+        return configured;
+    }
+    Ports {
+        airdrop_fallback: configured.airdrop_fallback.saturating_add(UNPRIVILEGED_PORT_OFFSET),
+        airplay: configured.airplay.saturating_add(UNPRIVILEGED_PORT_OFFSET),
+        ..configured
+    }
+}
+
+/// Funzionalità ridotte o non disponibili senza privilegi di amministratore
+pub fn limitations(configured: Ports, elevated: bool) -> Vec<String> {
+    if elevated {
+        return Vec::new();
+    }
+
+    let ports = service_ports(configured, false);
+    vec![
+        "AWDL disattivato: richiede l'accesso diretto alla scheda di rete".to_string(),
+        format!(
+            "AirPlay in ascolto sulla porta {} invece di {}",
+            ports.airplay, configured.airplay
+        ),
+        format!(
+            "Server AirDrop di riserva sulla porta {} invece di {}",
+            ports.airdrop_fallback, configured.airdrop_fallback
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unprivileged_ports_are_moved() {
+        let elevated = service_ports(Ports::default(), true);
+        let limited = service_ports(Ports::default(), false);
+
+        assert_eq!(elevated.airdrop_fallback, AIRDROP_FALLBACK_PORT);
+        assert!(limited.airdrop_fallback > elevated.airdrop_fallback);
+        assert!(limited.airplay > elevated.airplay);
+        // Le porte annunciate via mDNS non cambiano
+        assert_eq!(limited.airdrop_https, elevated.airdrop_https);
+    }
+
+    #[test]
+    fn test_limitations_only_without_elevation() {
+        assert!(limitations(Ports::default(), true).is_empty());
+        assert_eq!(limitations(Ports::default(), false).len(), 3);
+    }
+}
@@ -0,0 +1,376 @@
+//! Verifica della rete
+//!
+//! Controlla i requisiti di rete di AirWin (porte libere, mDNS, multicast,
+//! Bluetooth, AWDL) e produce una lista di esiti con un suggerimento per
+//! ogni problema, da allegare alle segnalazioni.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use super::config::{MulticastSettings, Ports};
+use crate::network::interface::NetworkInterface;
+use crate::protocols::awdl::AwdlAvailability;
+
+/// Gruppo e porta mDNS
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+/// Attesa massima di una risposta mDNS
+const MDNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Servizio interrogato. AirWin stesso lo annuncia quando AirDrop è attivo,
+/// quindi le sue risposte non contano: servono quelle di un altro dispositivo.
+const MDNS_QUERY_NAME: &str = "_airdrop._tcp.local";
+
+/// Esito di un controllo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Controllo non eseguito, ad esempio perché non applicabile
+    Skip,
+}
+
+impl CheckStatus {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "✔",
+            CheckStatus::Fail => "✖",
+            CheckStatus::Skip => "–",
+        }
+    }
+}
+
+/// Risultato di un singolo controllo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Cosa può fare l'utente per risolvere un errore
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn skip(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: CheckStatus::Skip, detail: detail.into(), hint: None }
+    }
+}
+
+/// Esiti di tutti i controlli
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// Nessun controllo è fallito
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.status != CheckStatus::Fail)
+    }
+
+    /// Report testuale, da copiare in una segnalazione
+    pub fn to_text(&self) -> String {
+        self.checks
+            .iter()
+            .map(|check| match &check.hint {
+                Some(hint) => format!("{} {}: {} ({})", check.status.icon(), check.name, check.detail, hint),
+                None => format!("{} {}: {}", check.status.icon(), check.name, check.detail),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Stato dell'applicazione necessario ai controlli
+#[derive(Debug, Clone)]
+pub struct SelfTestConfig {
+    pub ports: Ports,
+    /// Porte già aperte da AirWin, che non possono essere verificate
+    pub own_ports: Vec<u16>,
+    pub multicast: MulticastSettings,
+    pub awdl: AwdlAvailability,
+    pub elevated: bool,
+}
+
+/// Esegue tutti i controlli
+pub async fn run(config: SelfTestConfig) -> SelfTestReport {
+    let mut checks = Vec::new();
+
+    for (name, port) in config.ports.named() {
+        checks.push(check_port(name, port, &config.own_ports));
+    }
+
+    let interfaces = selected_interfaces(&config.multicast);
+    checks.push(check_multicast(&interfaces));
+    checks.push(check_mdns(&interfaces).await);
+    checks.push(check_ble().await);
+    checks.push(check_awdl(config.awdl, config.elevated));
+
+    SelfTestReport { checks }
+}
+
+/// Verifica che la porta TCP possa essere aperta
+pub fn probe_bind(port: u16) -> std::io::Result<()> {
+    TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))).map(drop)
+}
+
+fn check_port(name: &str, port: u16, own_ports: &[u16]) -> CheckResult {
+    let title = format!("Porta {} ({})", port, name);
+    if own_ports.contains(&port) {
+        return CheckResult::skip(title, "in uso da AirWin");
+    }
+    match probe_bind(port) {
+        Ok(()) => CheckResult::pass(title, "libera"),
+        Err(e) => CheckResult::fail(
+            title,
+            format!("non disponibile: {}", e),
+            "Chiudi il programma che la usa o cambia la porta in `ports` di settings.json",
+        ),
+    }
+}
+
+/// Interfacce IPv4 valide ammesse dalle impostazioni multicast
+fn selected_interfaces(multicast: &MulticastSettings) -> Vec<NetworkInterface> {
+    local_ip_address::list_afinet_netifas()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, ip)| NetworkInterface::new(name, ip))
+        .filter(|interface| interface.is_valid() && multicast.allows_interface(interface.name()))
+        .collect()
+}
+
+fn interface_v4(interface: &NetworkInterface) -> Option<Ipv4Addr> {
+    match interface.ip() {
+        IpAddr::V4(ip) => Some(*ip),
+        IpAddr::V6(_) => None,
+    }
+}
+
+fn check_multicast(interfaces: &[NetworkInterface]) -> CheckResult {
+    const NAME: &str = "Multicast";
+    if interfaces.is_empty() {
+        return CheckResult::fail(
+            NAME,
+            "nessuna interfaccia di rete selezionata",
+            "Collegati a una rete o controlla `multicast.interfaces` in settings.json",
+        );
+    }
+
+    let socket = match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)) {
+        Ok(socket) => socket,
+        Err(e) => return CheckResult::fail(NAME, format!("socket non disponibile: {}", e), "Riavvia il PC"),
+    };
+    let (joined, failed): (Vec<_>, Vec<_>) = interfaces
+        .iter()
+        .filter_map(|interface| interface_v4(interface).map(|ip| (interface.name(), ip)))
+        .partition(|(_, ip)| socket.join_multicast_v4(&MDNS_GROUP, ip).is_ok());
+
+    let names = |list: &[(&str, Ipv4Addr)]| list.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+    if joined.is_empty() {
+        CheckResult::fail(
+            NAME,
+            format!("gruppo mDNS non raggiungibile su {}", names(&failed)),
+            "Alcuni adattatori virtuali o VPN bloccano il multicast: scegli un'altra interfaccia",
+        )
+    } else {
+        CheckResult::pass(NAME, format!("gruppo mDNS raggiunto su {}", names(&joined)))
+    }
+}
+
+async fn check_mdns(interfaces: &[NetworkInterface]) -> CheckResult {
+    const NAME: &str = "Query mDNS";
+    let Some(interface) = interfaces.iter().find_map(interface_v4) else {
+        return CheckResult::skip(NAME, "nessuna interfaccia disponibile");
+    };
+
+    let socket = match mdns_socket(interface) {
+        Ok(socket) => socket,
+        Err(e) => {
+            return CheckResult::fail(
+                NAME,
+                format!("porta {} non disponibile: {}", MDNS_PORT, e),
+                "Un altro programma usa la porta mDNS in modo esclusivo: chiudilo e riprova",
+            )
+        }
+    };
+
+    if let Err(e) = socket.send_to(&mdns_query(MDNS_QUERY_NAME), (MDNS_GROUP, MDNS_PORT)).await {
+        return CheckResult::fail(
+            NAME,
+            format!("invio non riuscito: {}", e),
+            "Controlla che il firewall consenta il traffico UDP sulla porta 5353",
+        );
+    }
+
+    let own_addresses = own_addresses();
+    let mut answered_locally = false;
+    let wait_response = async {
+        let mut buffer = [0u8; 1500];
+        loop {
+            let (len, from) = socket.recv_from(&mut buffer).await?;
+            // Risposta (bit QR), non la nostra stessa domanda
+            if len < 12 || buffer[2] & 0x80 == 0 {
+                continue;
+            }
+            if is_other_host(from.ip(), &own_addresses) {
+                return Ok::<_, std::io::Error>(from);
+            }
+            answered_locally = true;
+        }
+    };
+    let outcome = tokio::time::timeout(MDNS_TIMEOUT, wait_response).await;
+    match outcome {
+        Ok(Ok(from)) => CheckResult::pass(NAME, format!("risposta ricevuta da {}", from.ip())),
+        Ok(Err(e)) => CheckResult::fail(
+            NAME,
+            format!("ricezione non riuscita: {}", e),
+            "Controlla che il firewall consenta il traffico UDP sulla porta 5353",
+        ),
+        Err(_) if answered_locally => CheckResult::skip(
+            NAME,
+            format!(
+                "solo locale: ha risposto solo questo PC, nessun altro dispositivo entro {}s",
+                MDNS_TIMEOUT.as_secs()
+            ),
+        ),
+        Err(_) => CheckResult::fail(
+            NAME,
+            format!("nessuna risposta entro {}s", MDNS_TIMEOUT.as_secs()),
+            "Consenti AirWin nel firewall di Windows per le reti private e verifica che la rete non blocchi il multicast",
+        ),
+    }
+}
+
+/// Indirizzi di questo PC, su tutte le interfacce
+fn own_addresses() -> Vec<IpAddr> {
+    local_ip_address::list_afinet_netifas()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_, ip)| ip)
+        .collect()
+}
+
+/// La risposta arriva da un altro dispositivo e non da AirWin stesso
+fn is_other_host(from: IpAddr, own_addresses: &[IpAddr]) -> bool {
+    !from.is_loopback() && !own_addresses.contains(&from)
+}
+
+fn mdns_socket(interface: Ipv4Addr) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    socket.join_multicast_v4(&MDNS_GROUP, &interface)?;
+    socket.set_multicast_if_v4(&interface)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Domanda mDNS di tipo PTR per `name`
+fn mdns_query(name: &str) -> Vec<u8> {
+    // Intestazione: id 0, flag 0, una domanda
+    let mut packet = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    // Tipo PTR (12), classe IN (1)
+    packet.extend_from_slice(&[0, 12, 0, 1]);
+    packet
+}
+
+async fn check_ble() -> CheckResult {
+    const NAME: &str = "Bluetooth";
+    match crate::network::ble::adapter_count().await {
+        Ok(0) => CheckResult::fail(
+            NAME,
+            "nessun adattatore trovato",
+            "Attiva il Bluetooth nelle impostazioni di Windows o collega un adattatore Bluetooth LE",
+        ),
+        Ok(count) => CheckResult::pass(NAME, format!("{} adattatori disponibili", count)),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Bluetooth non disponibile: {}", e),
+            "Verifica che il servizio di supporto Bluetooth di Windows sia in esecuzione",
+        ),
+    }
+}
+
+fn check_awdl(awdl: AwdlAvailability, elevated: bool) -> CheckResult {
+    const NAME: &str = "AWDL";
+    match awdl {
+        AwdlAvailability::On => CheckResult::pass(NAME, "attivo"),
+        AwdlAvailability::Off if !elevated => {
+            CheckResult::skip(NAME, "spento: richiede i privilegi di amministratore")
+        }
+        AwdlAvailability::Off => CheckResult::skip(NAME, "spento"),
+        AwdlAvailability::Unavailable => CheckResult::fail(
+            NAME,
+            "non disponibile",
+            "La scheda Wi-Fi non supporta AWDL o la sua porta è occupata: controlla il registro eventi",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_busy_port_fails_and_own_port_is_skipped() {
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let busy = check_port("Test", port, &[]);
+        assert_eq!(busy.status, CheckStatus::Fail);
+        assert!(busy.hint.is_some());
+        assert_eq!(check_port("Test", port, &[port]).status, CheckStatus::Skip);
+
+        drop(listener);
+        assert_eq!(check_port("Test", port, &[]).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn test_mdns_query_encoding() {
+        let query = mdns_query("_airdrop._tcp.local");
+        assert_eq!(&query[..12], &[0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..21], b"\x08_airdrop");
+        assert_eq!(&query[query.len() - 5..], &[0, 0, 12, 0, 1]);
+    }
+
+    #[test]
+    fn test_own_answers_are_not_other_hosts() {
+        let own = [IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))];
+        assert!(!is_other_host(own[0], &own));
+        assert!(!is_other_host(IpAddr::V4(Ipv4Addr::LOCALHOST), &own));
+        assert!(is_other_host(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), &own));
+    }
+
+    #[test]
+    fn test_report_text_and_result() {
+        let report = SelfTestReport {
+            checks: vec![
+                CheckResult::pass("AWDL", "attivo"),
+                CheckResult::skip("Bluetooth", "non verificato"),
+            ],
+        };
+        assert!(report.passed());
+        assert_eq!(report.to_text(), "✔ AWDL: attivo\n– Bluetooth: non verificato");
+
+        let failed = SelfTestReport { checks: vec![CheckResult::fail("Multicast", "bloccato", "cambia rete")] };
+        assert!(!failed.passed());
+        assert_eq!(failed.to_text(), "✖ Multicast: bloccato (cambia rete)");
+    }
+}
@@ -0,0 +1,54 @@
+//! Suono di completamento dei trasferimenti
+//!
+//! Il suono viene riprodotto su un thread separato per non bloccare
+//! l'interfaccia; senza un dispositivo audio la riproduzione viene saltata.
+
+use rodio::source::{SineWave, Source};
+use std::time::Duration;
+use tracing::debug;
+
+/// Note del suono di completamento (Hz) e durata di ciascuna
+const CHIME_NOTES: [f32; 2] = [880.0, 1320.0];
+/// Note del suono di errore, discendenti
+const FAILURE_NOTES: [f32; 2] = [660.0, 440.0];
+const NOTE_DURATION: Duration = Duration::from_millis(120);
+
+/// Riproduce il suono di completamento in background
+pub fn play_completion_chime() {
+    play_in_background(CHIME_NOTES);
+}
+
+/// Riproduce il suono di errore in background
+pub fn play_failure_chime() {
+    play_in_background(FAILURE_NOTES);
+}
+
+fn play_in_background(notes: [f32; 2]) {
+    let spawned = std::thread::Builder::new()
+        .name("airwin-chime".to_string())
+        .spawn(move || {
+            if let Err(e) = play_chime_blocking(notes) {
+                debug!("Chime skipped: {}", e);
+            }
+        });
+
+    if let Err(e) = spawned {
+        debug!("Failed to spawn chime thread: {}", e);
+    }
+}
+
+fn play_chime_blocking(notes: [f32; 2]) -> anyhow::Result<()> {
+    // Fallisce senza dispositivo audio: il chiamante lo tratta come no-op
+    let (_stream, handle) = rodio::OutputStream::try_default()?;
+    let sink = rodio::Sink::try_new(&handle)?;
+
+    for frequency in notes {
+        sink.append(
+            SineWave::new(frequency)
+                .take_duration(NOTE_DURATION)
+                .amplify(0.2),
+        );
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
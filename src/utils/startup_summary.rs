@@ -0,0 +1,122 @@
+//! Riepilogo dell'avvio dei servizi
+//!
+//! Al termine dell'inizializzazione dice all'utente con quale nome e su quali
+//! indirizzi gli altri dispositivi trovano questo PC, e quali servizi non
+//! sono partiti. Viene costruito da ciò che i servizi hanno effettivamente
+//! fatto: gli indirizzi delle interfacce su cui sono annunciati e i servizi
+//! registrati via mDNS.
+
+use std::net::IpAddr;
+
+/// Esito dell'avvio dei servizi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupSummary {
+    /// Nome annunciato agli altri dispositivi
+    pub name: String,
+    /// Indirizzi su cui i servizi sono raggiungibili
+    pub addresses: Vec<IpAddr>,
+    /// Servizi avviati e annunciati via mDNS
+    pub advertised: Vec<&'static str>,
+    /// Servizi non avviati o non annunciati, con il motivo
+    pub failed: Vec<(&'static str, String)>,
+}
+
+impl StartupSummary {
+    pub fn new(name: String, addresses: Vec<IpAddr>) -> Self {
+        Self {
+            name,
+            addresses,
+            advertised: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Segna `service` come avviato e annunciato
+    pub fn mark_advertised(&mut self, service: &'static str) {
+        self.advertised.push(service);
+    }
+
+    /// Segna `service` come non disponibile per `reason`
+    pub fn mark_failed(&mut self, service: &'static str, reason: impl Into<String>) {
+        self.failed.push((service, reason.into()));
+    }
+
+    /// Gli altri dispositivi possono trovare questo PC
+    pub fn is_discoverable(&self) -> bool {
+        !self.advertised.is_empty() && !self.addresses.is_empty()
+    }
+
+    /// Titolo della notifica
+    pub fn title(&self) -> &'static str {
+        match (self.is_discoverable(), self.failed.is_empty()) {
+            (true, true) => "AirWin è pronto",
+            (true, false) => "AirWin avviato in parte",
+            (false, _) => "AirWin non è visibile",
+        }
+    }
+
+    /// Riepilogo in una o più frasi, ad esempio
+    /// "Visibile come 'PC di Marco' su 192.168.1.5 (AirDrop, AirPlay)."
+    pub fn message(&self) -> String {
+        let mut sentences = Vec::new();
+        if self.is_discoverable() {
+            let addresses: Vec<String> = self.addresses.iter().map(IpAddr::to_string).collect();
+            sentences.push(format!(
+                "Visibile come '{}' su {} ({}).",
+                self.name,
+                addresses.join(", "),
+                self.advertised.join(", ")
+            ));
+        } else if !self.advertised.is_empty() {
+            sentences.push(format!(
+                "Nessuna rete disponibile: '{}' non è visibile agli altri dispositivi.",
+                self.name
+            ));
+        } else if self.failed.is_empty() {
+            sentences.push("Nessun servizio attivo: gli altri dispositivi non vedono questo PC.".to_string());
+        }
+        for (service, reason) in &self.failed {
+            sentences.push(format!("{} non disponibile: {}.", service, reason.trim_end_matches('.')));
+        }
+        sentences.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_message_reflects_services_and_addresses() {
+        let lan = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 5));
+
+        let mut ready = StartupSummary::new("PC di Marco".to_string(), vec![lan]);
+        ready.mark_advertised("AirDrop");
+        ready.mark_advertised("AirPlay");
+        assert_eq!(ready.title(), "AirWin è pronto");
+        assert_eq!(ready.message(), "Visibile come 'PC di Marco' su 192.168.1.5 (AirDrop, AirPlay).");
+
+        let mut partial = StartupSummary::new("PC".to_string(), vec![lan, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+        partial.mark_advertised("AirDrop");
+        partial.mark_failed("AirPlay", "porta 7000 già in uso.");
+        assert_eq!(partial.title(), "AirWin avviato in parte");
+        assert_eq!(
+            partial.message(),
+            "Visibile come 'PC' su 192.168.1.5, 10.0.0.2 (AirDrop). AirPlay non disponibile: porta 7000 già in uso."
+        );
+
+        // Servizi annunciati ma nessuna interfaccia su cui trovarli
+        let mut offline = StartupSummary::new("PC".to_string(), Vec::new());
+        offline.mark_advertised("AirDrop");
+        assert!(!offline.is_discoverable());
+        assert_eq!(offline.title(), "AirWin non è visibile");
+        assert!(offline.message().starts_with("Nessuna rete disponibile"));
+
+        let mut failed = StartupSummary::new("PC".to_string(), vec![lan]);
+        failed.mark_failed("AirDrop", "registrazione mDNS non riuscita");
+        assert_eq!(failed.message(), "AirDrop non disponibile: registrazione mDNS non riuscita.");
+
+        assert!(StartupSummary::new("PC".to_string(), vec![lan]).message().starts_with("Nessun servizio attivo"));
+    }
+}
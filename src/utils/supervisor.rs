@@ -0,0 +1,308 @@
+//! Supervisione dei task in background
+//!
+//! I task critici (loop di accept, watcher di rete) vengono eseguiti sotto un
+//! supervisore che intercetta i panic, li registra e riavvia il task con un
+//! ritardo crescente. Lo stato di ogni task è consultabile dalla diagnostica
+//! finché il task è in vita; un task abbandonato resta nell'elenco per
+//! mostrarne l'errore.
+//!
+//! Un task riavviato riceve di nuovo le stesse risorse dalla propria factory:
+//! i listener vanno creati prima e condivisi (es. `Arc<TcpListener>`), così un
+//! riavvio non tenta un secondo bind sulla stessa porta.
+
+use futures::FutureExt;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+use super::event_log;
+
+/// Ritardi tra un riavvio e l'altro
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Attesa prima del primo riavvio
+    pub initial: Duration,
+    /// Attesa massima, raggiunta raddoppiando quella precedente
+    pub max: Duration,
+    /// Un task rimasto attivo almeno così a lungo riparte dal ritardo iniziale
+    pub reset_after: Duration,
+    /// Riavvii consecutivi dopo cui il task viene abbandonato, `None` per nessun limite
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            reset_after: Duration::from_secs(60),
+            max_restarts: Some(10),
+        }
+    }
+}
+
+impl Backoff {
+    /// Attesa prima del riavvio numero `attempt` (da 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// Stato di un task supervisionato
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    /// In attesa di ripartire dopo un panic
+    Restarting,
+    /// Abbandonato dopo troppi panic
+    Failed,
+}
+
+/// Salute di un task, mostrata nella diagnostica
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+    /// Riavvii dall'avvio dell'applicazione
+    pub restarts: u32,
+    /// Messaggio dell'ultimo panic
+    pub last_panic: Option<String>,
+}
+
+impl TaskHealth {
+    /// Riga di testo per il riepilogo della diagnostica
+    pub fn to_line(&self) -> String {
+        let state = match self.state {
+            TaskState::Running => "attivo",
+            TaskState::Restarting => "in riavvio",
+            TaskState::Failed => "abbandonato",
+        };
+        match &self.last_panic {
+            Some(panic) => format!("{}: {} ({} riavvii, ultimo panic: {})", self.name, state, self.restarts, panic),
+            None => format!("{}: {}", self.name, state),
+        }
+    }
+}
+
+type HealthMap = Arc<Mutex<BTreeMap<String, TaskHealth>>>;
+
+/// Esegue task riavviandoli quando vanno in panic
+#[derive(Debug, Clone, Default)]
+pub struct Supervisor {
+    backoff: Backoff,
+    tasks: HealthMap,
+}
+
+/// Supervisore condiviso dai servizi dell'applicazione
+pub fn global() -> &'static Supervisor {
+    static SUPERVISOR: OnceLock<Supervisor> = OnceLock::new();
+    SUPERVISOR.get_or_init(Supervisor::default)
+}
+
+impl Supervisor {
+    pub fn new(backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            tasks: HealthMap::default(),
+        }
+    }
+
+    /// Avvia `task` e lo ricrea dopo ogni panic.
+    ///
+    /// Interrompere il `JoinHandle` restituito ferma anche il task in corso.
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, mut task: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        let backoff = self.backoff;
+        let tasks = self.tasks.clone();
+
+        tokio::spawn(async move {
+            let guard = HealthGuard::register(tasks, name.clone());
+            let mut attempt = 0;
+
+            loop {
+                guard.update(|health| health.state = TaskState::Running);
+                let started = Instant::now();
+
+                let Err(panic) = AssertUnwindSafe(task()).catch_unwind().await else {
+                    return;
+                };
+
+                let message = panic_message(panic.as_ref());
+                error!("Task {} panicked: {}", name, message);
+                attempt = if started.elapsed() >= backoff.reset_after { 1 } else { attempt + 1 };
+                guard.update(|health| {
+                    health.restarts += 1;
+                    health.last_panic = Some(message.clone());
+                });
+
+                if backoff.max_restarts.is_some_and(|max| attempt > max) {
+                    guard.update(|health| health.state = TaskState::Failed);
+                    event_log::error("Supervisore", format!("{} abbandonato dopo {} panic: {}", name, attempt, message));
+                    return;
+                }
+
+                let delay = backoff.delay(attempt);
+                warn!("Restarting task {} in {:?}", name, delay);
+                event_log::warning("Supervisore", format!("{} riavviato tra {:?} dopo un panic: {}", name, delay, message));
+                guard.update(|health| health.state = TaskState::Restarting);
+                tokio::time::sleep(delay).await;
+            }
+        })
+    }
+
+    /// Stato di tutti i task, in ordine di nome
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.tasks.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Riepilogo testuale, una riga per task
+    pub fn summary(&self) -> String {
+        self.health().iter().map(TaskHealth::to_line).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Indica se qualche task è stato abbandonato
+    pub fn has_failures(&self) -> bool {
+        self.health().iter().any(|health| health.state == TaskState::Failed)
+    }
+}
+
+/// Tiene aggiornata la voce del task e la rimuove quando il task termina o
+/// viene interrotto dall'esterno, così i servizi fermati non restano
+/// nell'elenco. La voce di un task abbandonato resta.
+struct HealthGuard {
+    tasks: HealthMap,
+    name: String,
+}
+
+impl HealthGuard {
+    fn register(tasks: HealthMap, name: String) -> Self {
+        tasks.lock().unwrap().entry(name.clone()).or_insert_with(|| TaskHealth {
+            name: name.clone(),
+            state: TaskState::Running,
+            restarts: 0,
+            last_panic: None,
+        });
+        Self { tasks, name }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut TaskHealth)) {
+        if let Some(health) = self.tasks.lock().unwrap().get_mut(&self.name) {
+            change(health);
+        }
+    }
+}
+
+impl Drop for HealthGuard {
+    fn drop(&mut self) {
+        let mut tasks = self.tasks.lock().unwrap();
+        if tasks.get(&self.name).is_some_and(|health| health.state != TaskState::Failed) {
+            tasks.remove(&self.name);
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic senza messaggio".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast_backoff(max_restarts: Option<u32>) -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(40),
+            reset_after: Duration::from_secs(60),
+            max_restarts,
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let backoff = fast_backoff(None);
+        let delays: Vec<_> = (1..=5).map(|attempt| backoff.delay(attempt).as_millis()).collect();
+        assert_eq!(delays, [10, 20, 40, 40, 40]);
+    }
+
+    #[tokio::test]
+    async fn test_restarts_after_panics_with_backoff() {
+        let supervisor = Supervisor::new(fast_backoff(None));
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let started = Instant::now();
+        let counter = runs.clone();
+        let handle = supervisor.spawn("flaky", move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 3 {
+                    panic!("boom");
+                }
+                std::future::pending::<()>().await
+            }
+        });
+
+        while runs.load(Ordering::SeqCst) < 4 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // 10 + 20 + 40 ms of backoff before the fourth run
+        assert!(started.elapsed() >= Duration::from_millis(70));
+
+        let health = supervisor.health();
+        assert_eq!(health.len(), 1);
+        assert_eq!(health[0].state, TaskState::Running);
+        assert_eq!(health[0].restarts, 3);
+        assert_eq!(health[0].last_panic.as_deref(), Some("boom"));
+
+        handle.abort();
+        let _ = handle.await;
+        assert!(supervisor.health().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_restarts() {
+        let supervisor = Supervisor::new(fast_backoff(Some(2)));
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        supervisor
+            .spawn("broken", move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { panic!("always") }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(supervisor.health()[0].state, TaskState::Failed);
+        assert!(supervisor.has_failures());
+    }
+
+    #[tokio::test]
+    async fn test_ended_tasks_are_removed() {
+        let supervisor = Supervisor::new(fast_backoff(None));
+        let handle = supervisor.spawn("listener :7000", || std::future::pending::<()>());
+        tokio::task::yield_now().await;
+        assert_eq!(supervisor.health()[0].state, TaskState::Running);
+
+        // A service restarted on another port doesn't leave the old entry behind
+        handle.abort();
+        let _ = handle.await;
+        supervisor.spawn("listener :7001", || async {}).await.unwrap();
+        assert!(supervisor.health().is_empty());
+    }
+}